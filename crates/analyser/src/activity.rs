@@ -0,0 +1,269 @@
+//! Sessions of a live database and the locks they wait on.
+//!
+//! A migration that hangs is usually waiting for a lock that another session holds, often one
+//! that is idle in a transaction. The sessions are loaded with [`ACTIVITY_QUERY`], which includes
+//! the sessions that block each of them, and rendered as chains from the blocking sessions down
+//! to the ones waiting on them. A stuck session can then be cancelled with
+//! [`CANCEL_BACKEND_QUERY`] or terminated with [`TERMINATE_BACKEND_QUERY`].
+
+use std::collections::HashSet;
+use std::fmt::Write;
+
+/// The number of characters of a query that are shown
+const QUERY_LEN: usize = 80;
+
+/// Returns the client sessions other than the current one as [`Activity`]s, the oldest
+/// transactions first
+pub const ACTIVITY_QUERY: &str = "select
+    a.pid,
+    a.usename::text as user_name,
+    a.application_name,
+    a.state,
+    a.wait_event_type,
+    a.wait_event,
+    extract(epoch from now() - a.xact_start)::float8 as transaction_secs,
+    extract(epoch from now() - a.query_start)::float8 as query_secs,
+    a.query,
+    pg_catalog.pg_blocking_pids(a.pid) as blocked_by
+from pg_catalog.pg_stat_activity a
+where a.backend_type = 'client backend'
+    and a.pid <> pg_catalog.pg_backend_pid()
+order by a.xact_start nulls last, a.pid";
+
+/// Cancels the query of the client session `$1`. Returns whether the session has been signalled, or
+/// no row if there is no such client session, so that background processes are left alone.
+pub const CANCEL_BACKEND_QUERY: &str = "select pg_catalog.pg_cancel_backend(a.pid)
+from pg_catalog.pg_stat_activity a
+where a.pid = $1
+    and a.backend_type = 'client backend'";
+
+/// Like [`CANCEL_BACKEND_QUERY`], but terminates the session, which rolls back its transaction
+pub const TERMINATE_BACKEND_QUERY: &str = "select pg_catalog.pg_terminate_backend(a.pid)
+from pg_catalog.pg_stat_activity a
+where a.pid = $1
+    and a.backend_type = 'client backend'";
+
+/// A session of a live database, as loaded with [`ACTIVITY_QUERY`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Activity {
+    pub pid: i32,
+    pub user_name: Option<String>,
+    pub application_name: Option<String>,
+    /// e.g. `active` or `idle in transaction`
+    pub state: Option<String>,
+    /// e.g. `Lock`, together with the `wait_event` such as `relation`
+    pub wait_event_type: Option<String>,
+    pub wait_event: Option<String>,
+    /// How long the current transaction has been open, if there is one
+    pub transaction_secs: Option<f64>,
+    /// How long the current query, or the last one of an idle session, has been running
+    pub query_secs: Option<f64>,
+    pub query: Option<String>,
+    /// The sessions that hold or wait for a lock that this session waits for
+    pub blocked_by: Vec<i32>,
+}
+
+/// Renders `sessions` as Markdown, with the chains of blocked sessions first and the open
+/// transactions after them
+pub fn activity_markdown(sessions: &[Activity]) -> String {
+    let mut md = String::from("## Blocking locks\n\n");
+    // sessions that block others without being blocked themselves start a chain, as do blocked
+    // sessions whose blockers are not part of `sessions`, e.g. because they are not clients
+    let roots = sessions
+        .iter()
+        .filter(|s| {
+            let blocks = sessions
+                .iter()
+                .any(|other| other.blocked_by.contains(&s.pid));
+            let blocked = s
+                .blocked_by
+                .iter()
+                .any(|pid| sessions.iter().any(|other| other.pid == *pid));
+            !blocked && (blocks || !s.blocked_by.is_empty())
+        })
+        .collect::<Vec<_>>();
+    if sessions.iter().all(|s| s.blocked_by.is_empty()) {
+        md.push_str("No session waits for a lock.\n");
+    }
+    let mut visited = HashSet::new();
+    for root in roots {
+        write_chain(&mut md, sessions, root, 0, &mut visited);
+    }
+    // the sessions of a deadlock block each other, so none of them starts a chain
+    for session in sessions {
+        if !session.blocked_by.is_empty() && !visited.contains(&session.pid) {
+            write_chain(&mut md, sessions, session, 0, &mut visited);
+        }
+    }
+
+    md.push_str("\n## Open transactions\n\n");
+    let transactions = sessions
+        .iter()
+        .filter(|s| s.transaction_secs.is_some())
+        .collect::<Vec<_>>();
+    if transactions.is_empty() {
+        md.push_str("No open transactions.\n");
+        return md;
+    }
+    md.push_str("| Pid | User | State | Transaction | Query |\n");
+    md.push_str("| --- | --- | --- | --- | --- |\n");
+    for session in transactions {
+        writeln!(
+            md,
+            "| {} | {} | {} | {} | {} |",
+            session.pid,
+            session.user_name.as_deref().unwrap_or(""),
+            state(session),
+            session.transaction_secs.map(duration).unwrap_or_default(),
+            query(session).replace('|', "\\|")
+        )
+        .unwrap();
+    }
+    md
+}
+
+/// Writes `session` and the sessions it blocks as a nested list
+fn write_chain(
+    md: &mut String,
+    sessions: &[Activity],
+    session: &Activity,
+    depth: usize,
+    visited: &mut HashSet<i32>,
+) {
+    if !visited.insert(session.pid) {
+        writeln!(
+            md,
+            "{}- pid {} (see above)",
+            "  ".repeat(depth),
+            session.pid
+        )
+        .unwrap();
+        return;
+    }
+    write!(md, "{}- pid {}", "  ".repeat(depth), session.pid).unwrap();
+    if let Some(user_name) = &session.user_name {
+        write!(md, " ({})", user_name).unwrap();
+    }
+    write!(md, ", {}", state(session)).unwrap();
+    if let Some(secs) = session.query_secs {
+        write!(md, " for {}", duration(secs)).unwrap();
+    }
+    let query = query(session);
+    if !query.is_empty() {
+        write!(md, ": {}", query).unwrap();
+    }
+    md.push('\n');
+    for blocked in sessions
+        .iter()
+        .filter(|s| s.blocked_by.contains(&session.pid))
+    {
+        write_chain(md, sessions, blocked, depth + 1, visited);
+    }
+}
+
+/// Returns the state of `session` together with the event it waits for, if any
+fn state(session: &Activity) -> String {
+    let state = session.state.as_deref().unwrap_or("unknown");
+    match (&session.wait_event_type, &session.wait_event) {
+        (Some(event_type), Some(event)) => {
+            format!("{}, waiting on {}/{}", state, event_type, event)
+        }
+        _ => state.to_string(),
+    }
+}
+
+/// Returns the query of `session` on a single line as inline code, truncated to `QUERY_LEN`
+fn query(session: &Activity) -> String {
+    let query = session
+        .query
+        .as_deref()
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('`', "'");
+    if query.is_empty() {
+        return String::new();
+    }
+    match query.char_indices().nth(QUERY_LEN) {
+        Some((idx, _)) => format!("`{}…`", &query[..idx]),
+        None => format!("`{}`", query),
+    }
+}
+
+/// Formats a duration in seconds such as `1h 5m` or `42s`
+fn duration(secs: f64) -> String {
+    let secs = secs.max(0.0) as u64;
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {}s", m, s),
+        (h, m, _) => format!("{}h {}m", h, m),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(pid: i32, query: &str, blocked_by: Vec<i32>) -> Activity {
+        Activity {
+            pid,
+            user_name: Some("app".to_string()),
+            application_name: None,
+            state: Some("active".to_string()),
+            wait_event_type: None,
+            wait_event: None,
+            transaction_secs: Some(65.0),
+            query_secs: Some(3.0),
+            query: Some(query.to_string()),
+            blocked_by,
+        }
+    }
+
+    #[test]
+    fn test_activity_markdown() {
+        let idle = Activity {
+            state: Some("idle in transaction".to_string()),
+            transaction_secs: Some(4000.0),
+            ..session(1, "update contact\n    set email = ''", vec![])
+        };
+        let migration = Activity {
+            wait_event_type: Some("Lock".to_string()),
+            wait_event: Some("relation".to_string()),
+            ..session(2, "alter table contact add column age int", vec![1])
+        };
+        let query = session(3, "select * from contact", vec![2]);
+        let other = Activity {
+            transaction_secs: None,
+            ..session(4, "select 1", vec![])
+        };
+        let md = activity_markdown(&[idle, migration, query, other]);
+
+        assert!(md.contains(
+            "- pid 1 (app), idle in transaction for 3s: `update contact set email = ''`
+  - pid 2 (app), active, waiting on Lock/relation for 3s: `alter table contact add column age int`
+    - pid 3 (app), active for 3s: `select * from contact`
+"
+        ));
+        assert!(md.contains("| 1 | app | idle in transaction | 1h 6m |"));
+        assert!(!md.contains("| 4 |"));
+    }
+
+    #[test]
+    fn test_deadlock() {
+        let md = activity_markdown(&[session(1, "", vec![2]), session(2, "", vec![1])]);
+        assert!(md.contains(
+            "- pid 1 (app), active for 3s
+  - pid 2 (app), active for 3s
+    - pid 1 (see above)
+"
+        ));
+    }
+
+    #[test]
+    fn test_no_activity() {
+        let md = activity_markdown(&[]);
+        assert!(md.contains("No session waits for a lock."));
+        assert!(md.contains("No open transactions."));
+    }
+}
@@ -0,0 +1,350 @@
+//! Unqualified column references that more than one relation of a query has.
+//!
+//! Postgres rejects such a reference with `column reference "id" is ambiguous`. The relations of
+//! the `FROM` clause are looked up in a schema model, and every relation that has the column is a
+//! candidate whose alias or name can qualify the reference. Columns that are joined with `USING`
+//! are merged into one and are not ambiguous, and queries with a `NATURAL` join are left out.
+//! References within a subquery are resolved against the relations of the subquery only, so a
+//! correlated reference to an outer column is not reported. A common table expression shadows a
+//! table of the same name, and has the output columns of its query. A name in `ORDER BY` that is
+//! an output column of the query refers to that column, but output columns are not visible in the
+//! other clauses. Every statement is checked against the schema as changed by the statements
+//! before it.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use cstree::syntax::ResolvedNode;
+use cstree::text::{TextRange, TextSize};
+use parser::make::quote_ident;
+use parser::{RawStmt, SyntaxKind};
+use pg_query::protobuf::{Node, RangeVar, SelectStmt};
+use pg_query::NodeEnum;
+
+use crate::lint::{LintDiagnostic, Severity};
+use crate::migrations::MigrationState;
+use crate::schema::Schema;
+use crate::utils::{descendants, string_value};
+
+const AMBIGUOUS_COLUMN: &str = "ambiguous-column";
+
+/// A column reference that several relations of its query have
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AmbiguousColumn {
+    pub name: String,
+    /// The range of the column name
+    pub range: TextRange,
+    /// The aliases or names of the relations that have the column, quoted where necessary
+    pub candidates: Vec<String>,
+}
+
+impl AmbiguousColumn {
+    pub fn diagnostic(&self) -> LintDiagnostic {
+        LintDiagnostic {
+            rule: AMBIGUOUS_COLUMN,
+            message: format!(
+                "column reference \"{}\" is ambiguous, it could refer to {}",
+                self.name,
+                self.candidates
+                    .iter()
+                    .map(|c| format!("{}.{}", c, quote_ident(&self.name)))
+                    .collect::<Vec<_>>()
+                    .join(" or ")
+            ),
+            severity: Severity::Warning,
+            range: self.range,
+        }
+    }
+}
+
+/// Returns the ambiguous column references of the queries of `stmts`, given the tables of
+/// `schemas` before the first statement
+pub fn ambiguous_columns(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmts: &[RawStmt],
+    schemas: &BTreeMap<String, Schema>,
+) -> Vec<AmbiguousColumn> {
+    let mut state = MigrationState::from_schemas(schemas.clone());
+    let mut columns = Vec::new();
+    for stmt in stmts {
+        let nodes = descendants(&stmt.stmt);
+        let ctes = common_table_expressions(&nodes);
+        let selects = nodes.iter().filter_map(|node| match node {
+            NodeEnum::SelectStmt(select) => Some(select),
+            _ => None,
+        });
+        for select in selects {
+            let mut scope = Scope::default();
+            scope.add(&select.from_clause);
+            if scope.is_natural {
+                continue;
+            }
+            let outputs = output_columns(select).unwrap_or_default();
+            let sorted_by_output = select
+                .sort_clause
+                .iter()
+                .filter_map(|sort| match sort.node.as_ref()? {
+                    NodeEnum::SortBy(s) => match s.node.as_ref()?.node.as_ref()? {
+                        NodeEnum::ColumnRef(c) => Some(c.location),
+                        _ => None,
+                    },
+                    _ => None,
+                })
+                .collect::<BTreeSet<_>>();
+            for (name, location) in own_column_refs(select) {
+                if scope.using.contains(&name)
+                    || (sorted_by_output.contains(&location) && outputs.contains(&name))
+                {
+                    continue;
+                }
+                let candidates = scope
+                    .relations
+                    .iter()
+                    .filter(|(_, relation)| has_column(relation, &name, &ctes, &state))
+                    .map(|(qualifier, _)| quote_ident(qualifier))
+                    .collect::<Vec<_>>();
+                if candidates.len() < 2 {
+                    continue;
+                }
+                let start = stmt.range.start() + TextSize::from(location.max(0) as u32);
+                let Some(token) = cst
+                    .descendants_with_tokens()
+                    .filter_map(|element| element.into_token())
+                    .find(|token| token.text_range().start() == start)
+                else {
+                    continue;
+                };
+                columns.push(AmbiguousColumn {
+                    name,
+                    range: token.text_range(),
+                    candidates,
+                });
+            }
+        }
+        state.replay(std::slice::from_ref(stmt));
+    }
+    columns.sort_by_key(|column| column.range.start());
+    columns.dedup();
+    columns
+}
+
+/// The relations of a `FROM` clause by their alias or name
+#[derive(Default)]
+struct Scope {
+    relations: Vec<(String, RangeVar)>,
+    /// The columns of `USING` clauses, which are merged into one
+    using: BTreeSet<String>,
+    is_natural: bool,
+}
+
+impl Scope {
+    fn add<'a>(&mut self, items: impl IntoIterator<Item = &'a Node>) {
+        for item in items {
+            match item.node.as_ref() {
+                Some(NodeEnum::RangeVar(r)) => {
+                    let name = r.alias.as_ref().map_or(&r.relname, |a| &a.aliasname);
+                    self.relations.push((name.clone(), r.clone()));
+                }
+                Some(NodeEnum::JoinExpr(j)) => {
+                    self.is_natural |= j.is_natural;
+                    self.using.extend(
+                        j.using_clause
+                            .iter()
+                            .filter_map(string_value)
+                            .map(str::to_string),
+                    );
+                    self.add(j.larg.as_deref().into_iter().chain(j.rarg.as_deref()));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Returns the names and locations of the unqualified column references of `select`, without
+/// those of its subqueries
+fn own_column_refs(select: &SelectStmt) -> Vec<(String, i32)> {
+    let node = NodeEnum::SelectStmt(Box::new(select.clone()));
+    let nested = descendants(&node)
+        .into_iter()
+        .skip(1)
+        .filter(|node| matches!(node, NodeEnum::SelectStmt(_)))
+        .flat_map(|node| descendants(&node))
+        .filter_map(|node| match node {
+            NodeEnum::ColumnRef(c) => Some(c.location),
+            _ => None,
+        })
+        .collect::<BTreeSet<_>>();
+    descendants(&node)
+        .into_iter()
+        .filter_map(|node| match node {
+            NodeEnum::ColumnRef(c) if !nested.contains(&c.location) => match c.fields.as_slice() {
+                [field] => Some((string_value(field)?.to_string(), c.location)),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the output columns of `select`, or `None` if they are not known, e.g. for `*`
+fn output_columns(select: &SelectStmt) -> Option<BTreeSet<String>> {
+    if let Some(larg) = &select.larg {
+        return output_columns(larg);
+    }
+    let mut columns = BTreeSet::new();
+    for target in &select.target_list {
+        let Some(NodeEnum::ResTarget(t)) = target.node.as_ref() else {
+            continue;
+        };
+        if !t.name.is_empty() {
+            columns.insert(t.name.clone());
+            continue;
+        }
+        if let Some(NodeEnum::ColumnRef(c)) = t.val.as_ref().and_then(|v| v.node.as_ref()) {
+            match c.fields.last()?.node.as_ref()? {
+                NodeEnum::String(s) => columns.insert(s.sval.clone()),
+                _ => return None,
+            };
+        }
+    }
+    Some(columns)
+}
+
+/// Returns the common table expressions among `nodes` with their output columns, or `None` if
+/// those are not known
+fn common_table_expressions(nodes: &[NodeEnum]) -> BTreeMap<String, Option<BTreeSet<String>>> {
+    nodes
+        .iter()
+        .filter_map(|node| match node {
+            NodeEnum::CommonTableExpr(cte) => {
+                let columns = if cte.aliascolnames.is_empty() {
+                    match cte.ctequery.as_ref().and_then(|q| q.node.as_ref()) {
+                        Some(NodeEnum::SelectStmt(select)) => output_columns(select),
+                        _ => None,
+                    }
+                } else {
+                    Some(
+                        cte.aliascolnames
+                            .iter()
+                            .filter_map(string_value)
+                            .map(str::to_string)
+                            .collect(),
+                    )
+                };
+                Some((cte.ctename.clone(), columns))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn has_column(
+    relation: &RangeVar,
+    name: &str,
+    ctes: &BTreeMap<String, Option<BTreeSet<String>>>,
+    state: &MigrationState,
+) -> bool {
+    if relation.schemaname.is_empty() {
+        if let Some(columns) = ctes.get(&relation.relname) {
+            return columns.as_ref().is_some_and(|c| c.contains(name));
+        }
+    }
+    let (schema, table) = state.relation_name(relation);
+    state
+        .schemas
+        .get(&schema)
+        .and_then(|s| s.table(&table))
+        .is_some_and(|table| table.column(name).is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    fn ambiguous(query: &str) -> Vec<(String, Vec<String>)> {
+        let input = format!(
+            "create table contact (id int, name text);
+            create table orders (id int, contact_id int, name text);
+            {}",
+            query
+        );
+        let parse = parse_source(&input);
+        ambiguous_columns(&parse.cst, &parse.stmts, &BTreeMap::new())
+            .into_iter()
+            .map(|c| {
+                assert_eq!(&input[c.range], c.name);
+                (c.name, c.candidates)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_ambiguous_columns() {
+        assert_eq!(
+            ambiguous("select id, contact_id from contact c join orders on c.id = contact_id;"),
+            vec![(
+                "id".to_string(),
+                vec!["c".to_string(), "orders".to_string()]
+            )]
+        );
+        assert_eq!(
+            ambiguous("select name from contact join orders using (name) where id = 1;"),
+            vec![(
+                "id".to_string(),
+                vec!["contact".to_string(), "orders".to_string()]
+            )]
+        );
+        assert!(ambiguous("select id from contact natural join orders;").is_empty());
+        assert!(ambiguous(
+            "select c.id from contact c where exists (select id from orders o where o.id = 1);"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_ambiguous_columns_with_output_columns() {
+        // an output column is only visible in ORDER BY
+        assert!(
+            ambiguous("select c.name as id from contact c join orders o on true order by id;")
+                .is_empty()
+        );
+        assert_eq!(
+            ambiguous("select c.name as id from contact c join orders o on true where id = 1;"),
+            vec![("id".to_string(), vec!["c".to_string(), "o".to_string()])]
+        );
+        assert_eq!(
+            ambiguous("select c.name as id from contact c join orders o on true order by id + 1;"),
+            vec![("id".to_string(), vec!["c".to_string(), "o".to_string()])]
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_columns_with_ctes() {
+        assert!(ambiguous(
+            "with orders as (select contact_id from orders)
+            select id from contact join orders on true;"
+        )
+        .is_empty());
+        assert_eq!(
+            ambiguous(
+                "with recent (id) as (select id from orders)
+                select id from contact join recent on true;"
+            ),
+            vec![(
+                "id".to_string(),
+                vec!["contact".to_string(), "recent".to_string()]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_columns_at_statement() {
+        assert!(ambiguous(
+            "select contact_id from contact join orders on true;
+            alter table contact add column contact_id int;"
+        )
+        .is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+//! Advice on bloated tables that the statements of a workspace use.
+//!
+//! Updates and deletes leave dead rows behind until vacuum removes them, and queries keep reading
+//! them until then. The bloat of a table is estimated from the row counts of the statistics
+//! collector, which are cheap to load with [`TABLE_BLOAT_QUERY`] but only approximate. Queries on
+//! tables whose share of dead rows is above the [`BloatThresholds`] get a hint with the
+//! maintenance to run.
+
+use cstree::text::{TextRange, TextSize};
+use parser::{RawStmt, StmtKind, SyntaxKind};
+
+use crate::lint::{LintDiagnostic, Severity};
+use crate::moniker::{qualified_name, relations};
+
+const BLOATED_TABLE: &str = "bloated-table";
+
+/// Returns the estimated bloat of every user table as [`TableBloat`]s
+pub const TABLE_BLOAT_QUERY: &str = "select
+    s.schemaname as schema_name,
+    s.relname as table_name,
+    s.n_live_tup as live_rows,
+    s.n_dead_tup as dead_rows,
+    pg_catalog.pg_table_size(s.relid) as table_bytes,
+    greatest(s.last_vacuum, s.last_autovacuum)::text as last_vacuum
+from pg_catalog.pg_stat_user_tables s
+order by 1, 2";
+
+/// The estimated bloat of a table, as loaded with [`TABLE_BLOAT_QUERY`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableBloat {
+    pub schema_name: String,
+    pub table_name: String,
+    pub live_rows: i64,
+    pub dead_rows: i64,
+    /// The size of the table including TOAST, but without indexes
+    pub table_bytes: i64,
+    /// When the table was last vacuumed, manually or by autovacuum, if ever
+    pub last_vacuum: Option<String>,
+}
+
+impl TableBloat {
+    /// Returns the name of the table as `schema.table`
+    pub fn qualified_name(&self) -> String {
+        format!("{}.{}", self.schema_name, self.table_name)
+    }
+
+    /// Returns the share of dead rows among all rows of the table
+    pub fn dead_ratio(&self) -> f64 {
+        let total = self.live_rows + self.dead_rows;
+        if total == 0 {
+            return 0.0;
+        }
+        self.dead_rows as f64 / total as f64
+    }
+
+    /// Returns the maintenance that removes the bloat of the table
+    pub fn suggestion(&self) -> String {
+        let name = self.qualified_name();
+        let mut suggestion = format!("run VACUUM (ANALYZE) {}", name);
+        if self.dead_ratio() >= 0.5 {
+            // vacuum makes the space reusable, but only a rewrite returns it to the system
+            suggestion
+                .push_str(", and pg_repack or VACUUM FULL, which locks the table, to shrink it");
+        }
+        match self.last_vacuum {
+            Some(_) => suggestion.push_str(&format!(
+                "; lower autovacuum_vacuum_scale_factor of {} so that autovacuum keeps up",
+                name
+            )),
+            None => suggestion.push_str("; the table has never been vacuumed, check autovacuum"),
+        }
+        suggestion
+    }
+}
+
+/// The thresholds from which a table counts as bloated
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BloatThresholds {
+    /// The minimum share of dead rows
+    pub min_dead_ratio: f64,
+    /// The minimum number of dead rows, so that small tables are not reported
+    pub min_dead_rows: i64,
+}
+
+impl Default for BloatThresholds {
+    fn default() -> Self {
+        Self {
+            min_dead_ratio: 0.2,
+            min_dead_rows: 10_000,
+        }
+    }
+}
+
+impl BloatThresholds {
+    /// Returns true if `table` is above both thresholds
+    pub fn is_bloated(&self, table: &TableBloat) -> bool {
+        table.dead_rows >= self.min_dead_rows && table.dead_ratio() >= self.min_dead_ratio
+    }
+}
+
+/// Returns a hint for every use of a `bloated` table in the queries of `stmts`
+pub fn bloat_hints(stmts: &[RawStmt], bloated: &[&TableBloat]) -> Vec<LintDiagnostic> {
+    stmts
+        .iter()
+        .filter(|stmt| SyntaxKind::from(&stmt.stmt).stmt_kind() == Some(StmtKind::Dml))
+        .flat_map(|stmt| {
+            relations(&stmt.stmt)
+                .into_iter()
+                .filter_map(move |relation| {
+                    let name = qualified_name(&relation);
+                    let table = bloated.iter().find(|t| t.qualified_name() == name)?;
+                    let offset =
+                        stmt.range.start() + TextSize::from(relation.location.max(0) as u32);
+                    Some(LintDiagnostic {
+                        rule: BLOATED_TABLE,
+                        message: format!(
+                            "{} has {:.0}% dead rows ({} of {}) that this query reads; {}",
+                            name,
+                            table.dead_ratio() * 100.0,
+                            table.dead_rows,
+                            table.live_rows + table.dead_rows,
+                            table.suggestion()
+                        ),
+                        severity: Severity::Hint,
+                        range: TextRange::empty(offset),
+                    })
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    fn table(name: &str, live_rows: i64, dead_rows: i64) -> TableBloat {
+        TableBloat {
+            schema_name: "public".to_string(),
+            table_name: name.to_string(),
+            live_rows,
+            dead_rows,
+            table_bytes: 0,
+            last_vacuum: None,
+        }
+    }
+
+    #[test]
+    fn test_is_bloated() {
+        let thresholds = BloatThresholds::default();
+        assert!(thresholds.is_bloated(&table("contact", 60_000, 40_000)));
+        assert!(!thresholds.is_bloated(&table("contact", 1_000_000, 40_000)));
+        assert!(!thresholds.is_bloated(&table("contact", 100, 900)));
+        assert_eq!(table("contact", 0, 0).dead_ratio(), 0.0);
+    }
+
+    #[test]
+    fn test_bloat_hints() {
+        let contact = table("contact", 60_000, 40_000);
+        let input = "create index on contact (email);
+select * from contact join org on org.id = contact.org;
+update public.contact set email = '';";
+        let diagnostics = bloat_hints(&parse_source(input).stmts, &[&contact]);
+
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| usize::from(d.range.start()))
+                .collect::<Vec<_>>(),
+            vec![
+                input.find("contact join").unwrap(),
+                input.find("public.contact").unwrap()
+            ]
+        );
+        assert_eq!(diagnostics[0].rule, BLOATED_TABLE);
+        assert!(diagnostics[0]
+            .message
+            .starts_with("public.contact has 40% dead rows (40000 of 100000)"));
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+
+use cstree::text::TextRange;
+use parser::RawStmt;
+use petgraph::graph::{DiGraph, NodeIndex};
+use pg_query::{protobuf::CreateCastStmt, NodeEnum};
+
+use crate::utils::{normalize_type_name, string_value, type_name};
+
+/// Query to load all casts from the `pg_cast` catalog of a live database.
+///
+/// Every row can be converted into a [`CatalogCast`].
+pub const PG_CAST_QUERY: &str = "select
+    sn.nspname as source_schema,
+    st.typname as source_name,
+    tn.nspname as target_schema,
+    tt.typname as target_name,
+    c.castcontext::text as context,
+    c.castmethod::text as method,
+    case when c.castfunc = 0 then null else c.castfunc::regproc::text end as function
+from pg_catalog.pg_cast c
+    join pg_catalog.pg_type st on st.oid = c.castsource
+    join pg_catalog.pg_namespace sn on sn.oid = st.typnamespace
+    join pg_catalog.pg_type tt on tt.oid = c.casttarget
+    join pg_catalog.pg_namespace tn on tn.oid = tt.typnamespace";
+
+/// The context in which a cast may be invoked implicitly.
+///
+/// The variants are ordered from the most to the least permissive: a cast marked as `Implicit` can
+/// be used in any context, while an `Explicit` cast requires `CAST(x AS type)` or `x::type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CastContext {
+    Implicit,
+    Assignment,
+    Explicit,
+}
+
+impl CastContext {
+    /// Converts the pg_query `CoercionContext` of a `CreateCastStmt`
+    fn from_coercion_context(context: i32) -> CastContext {
+        match context {
+            // CoercionImplicit
+            1 => CastContext::Implicit,
+            // CoercionAssignment
+            2 => CastContext::Assignment,
+            _ => CastContext::Explicit,
+        }
+    }
+
+    /// Converts the `castcontext` column of `pg_cast`
+    fn from_catalog(context: &str) -> Option<CastContext> {
+        match context {
+            "i" => Some(CastContext::Implicit),
+            "a" => Some(CastContext::Assignment),
+            "e" => Some(CastContext::Explicit),
+            _ => None,
+        }
+    }
+}
+
+/// How a cast is performed
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CastMethod {
+    /// `WITH FUNCTION name(args)`
+    Function(String),
+    /// `WITH INOUT`: the output function of the source type is fed into the input function of
+    /// the target type
+    InOut,
+    /// `WITHOUT FUNCTION`: the types are binary coercible
+    Binary,
+}
+
+/// Where a cast has been defined
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CastOrigin {
+    /// A `CREATE CAST` statement at the given range in the source text
+    Statement(TextRange),
+    /// The `pg_cast` catalog of a live database
+    Catalog,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Cast {
+    pub context: CastContext,
+    pub method: CastMethod,
+    pub origin: CastOrigin,
+}
+
+/// A row returned by [`PG_CAST_QUERY`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatalogCast {
+    pub source_schema: String,
+    pub source_name: String,
+    pub target_schema: String,
+    pub target_name: String,
+    pub context: String,
+    pub method: String,
+    pub function: Option<String>,
+}
+
+/// A directed graph with types as nodes and casts as edges.
+///
+/// Casts are collected from `CREATE CAST` statements and from the `pg_cast` catalog of a live
+/// database. If a cast between the same two types is added twice, the latest definition wins, so
+/// casts from the workspace should be added after the catalog has been loaded.
+///
+/// Postgres never chains casts, so the graph only answers questions about direct edges.
+#[derive(Debug, Clone, Default)]
+pub struct CastGraph {
+    graph: DiGraph<String, Cast>,
+    types: HashMap<String, NodeIndex>,
+}
+
+impl CastGraph {
+    pub fn new() -> CastGraph {
+        CastGraph::default()
+    }
+
+    /// Builds a cast graph from all `CREATE CAST` statements in `stmts`
+    pub fn from_stmts(stmts: &[RawStmt]) -> CastGraph {
+        let mut graph = CastGraph::new();
+        stmts.iter().for_each(|stmt| graph.add_stmt(stmt));
+        graph
+    }
+
+    /// Adds the cast defined by `stmt` if it is a `CREATE CAST` statement
+    pub fn add_stmt(&mut self, stmt: &RawStmt) {
+        if let NodeEnum::CreateCastStmt(n) = &stmt.stmt {
+            if let Some((source, target)) = cast_types(n) {
+                let method = if n.inout {
+                    CastMethod::InOut
+                } else if let Some(func) = &n.func {
+                    CastMethod::Function(
+                        func.objname
+                            .iter()
+                            .filter_map(string_value)
+                            .collect::<Vec<&str>>()
+                            .join("."),
+                    )
+                } else {
+                    CastMethod::Binary
+                };
+                self.add_cast(
+                    &source,
+                    &target,
+                    Cast {
+                        context: CastContext::from_coercion_context(n.context),
+                        method,
+                        origin: CastOrigin::Statement(stmt.range),
+                    },
+                );
+            }
+        }
+    }
+
+    /// Adds a cast loaded from the `pg_cast` catalog
+    pub fn add_catalog_cast(&mut self, row: &CatalogCast) {
+        let source = normalize_type_name(&[&row.source_schema, &row.source_name]);
+        let target = normalize_type_name(&[&row.target_schema, &row.target_name]);
+        let context = CastContext::from_catalog(&row.context);
+        let method = match row.method.as_str() {
+            "f" => row.function.clone().map(CastMethod::Function),
+            "i" => Some(CastMethod::InOut),
+            "b" => Some(CastMethod::Binary),
+            _ => None,
+        };
+        if let (Some(source), Some(target), Some(context), Some(method)) =
+            (source, target, context, method)
+        {
+            self.add_cast(
+                &source,
+                &target,
+                Cast {
+                    context,
+                    method,
+                    origin: CastOrigin::Catalog,
+                },
+            );
+        }
+    }
+
+    /// Adds a cast from `source` to `target`, replacing any existing cast between the two types
+    pub fn add_cast(&mut self, source: &str, target: &str, cast: Cast) {
+        let source = self.type_node(source);
+        let target = self.type_node(target);
+        match self.graph.find_edge(source, target) {
+            Some(edge) => self.graph[edge] = cast,
+            None => {
+                self.graph.add_edge(source, target, cast);
+            }
+        }
+    }
+
+    /// Returns the cast from `source` to `target`, if any
+    pub fn find_cast(&self, source: &str, target: &str) -> Option<&Cast> {
+        let source = self.types.get(source)?;
+        let target = self.types.get(target)?;
+        self.graph
+            .find_edge(*source, *target)
+            .map(|edge| &self.graph[edge])
+    }
+
+    /// Returns true if a value of type `source` can be coerced into `target` within `context`
+    pub fn can_coerce(&self, source: &str, target: &str, context: CastContext) -> bool {
+        source == target
+            || self
+                .find_cast(source, target)
+                .is_some_and(|cast| cast.context <= context)
+    }
+
+    /// Returns true if `source` and `target` are binary coercible, i.e. a value of `source` can be
+    /// read as `target` without calling a function
+    pub fn is_binary_coercible(&self, source: &str, target: &str) -> bool {
+        source == target
+            || self
+                .find_cast(source, target)
+                .is_some_and(|cast| cast.method == CastMethod::Binary)
+    }
+
+    /// Returns all casts starting at `source`, together with their target type
+    pub fn casts_from<'a>(&'a self, source: &str) -> impl Iterator<Item = (&'a str, &'a Cast)> {
+        let source = self.types.get(source).copied();
+        source
+            .into_iter()
+            .flat_map(move |idx| self.graph.edges(idx))
+            .map(|edge| {
+                use petgraph::visit::EdgeRef;
+                (self.graph[edge.target()].as_str(), edge.weight())
+            })
+    }
+
+    fn type_node(&mut self, name: &str) -> NodeIndex {
+        if let Some(idx) = self.types.get(name) {
+            return *idx;
+        }
+        let idx = self.graph.add_node(name.to_string());
+        self.types.insert(name.to_string(), idx);
+        idx
+    }
+}
+
+/// Returns the normalized source and target type names of a `CREATE CAST` statement
+pub fn cast_types(n: &CreateCastStmt) -> Option<(String, String)> {
+    Some((
+        type_name(n.sourcetype.as_ref()?)?,
+        type_name(n.targettype.as_ref()?)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    #[test]
+    fn test_cast_from_stmt() {
+        let input = "CREATE CAST (bigint AS mytype) WITH FUNCTION to_mytype(bigint) AS ASSIGNMENT;
+CREATE CAST (mytype AS text) WITH INOUT AS IMPLICIT;";
+
+        let graph = CastGraph::from_stmts(&parse_source(input).stmts);
+
+        let cast = graph.find_cast("int8", "mytype").unwrap();
+        assert_eq!(cast.context, CastContext::Assignment);
+        assert_eq!(cast.method, CastMethod::Function("to_mytype".to_string()));
+
+        let cast = graph.find_cast("mytype", "text").unwrap();
+        assert_eq!(cast.context, CastContext::Implicit);
+        assert_eq!(cast.method, CastMethod::InOut);
+
+        assert!(graph.can_coerce("int8", "mytype", CastContext::Assignment));
+        assert!(!graph.can_coerce("int8", "mytype", CastContext::Implicit));
+        assert!(graph.can_coerce("mytype", "text", CastContext::Implicit));
+        assert!(graph.find_cast("text", "mytype").is_none());
+    }
+
+    #[test]
+    fn test_workspace_cast_overrides_catalog() {
+        let mut graph = CastGraph::new();
+        graph.add_catalog_cast(&CatalogCast {
+            source_schema: "public".to_string(),
+            source_name: "mytype".to_string(),
+            target_schema: "pg_catalog".to_string(),
+            target_name: "text".to_string(),
+            context: "e".to_string(),
+            method: "i".to_string(),
+            function: None,
+        });
+        assert_eq!(
+            graph.find_cast("public.mytype", "text").unwrap().origin,
+            CastOrigin::Catalog
+        );
+
+        parse_source("CREATE CAST (public.mytype AS text) WITHOUT FUNCTION AS IMPLICIT;")
+            .stmts
+            .iter()
+            .for_each(|stmt| graph.add_stmt(stmt));
+
+        let cast = graph.find_cast("public.mytype", "text").unwrap();
+        assert_eq!(cast.method, CastMethod::Binary);
+        assert!(graph.is_binary_coercible("public.mytype", "text"));
+        assert!(!graph.is_binary_coercible("text", "public.mytype"));
+        assert_eq!(cast.context, CastContext::Implicit);
+        assert_eq!(graph.casts_from("public.mytype").count(), 1);
+    }
+}
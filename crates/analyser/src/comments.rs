@@ -0,0 +1,439 @@
+//! The objects that `COMMENT ON` and `SECURITY LABEL` describe.
+//!
+//! Objects are identified like [`definitions`](crate::definitions), e.g. `public.contact`, and
+//! columns, constraints and triggers by the identifier of their table followed by their name, e.g.
+//! `public.contact.email`. Schemas, roles and extensions are identified by their name.
+//!
+//! An object that is neither defined in the workspace nor in the database is reported, but only if
+//! both can tell: objects whose kind is not indexed in the workspace, e.g. constraints, triggers
+//! and roles, are never reported, and sequences, types, domains, schemas, foreign tables and
+//! procedures only if there is no database, since it is not loaded with them.
+
+use std::collections::BTreeMap;
+
+use cstree::syntax::ResolvedNode;
+use cstree::text::TextRange;
+use parser::{RawStmt, SyntaxKind};
+use pg_query::protobuf::Node;
+use pg_query::NodeEnum;
+
+use crate::definitions::{Definition, ObjectKind, Reference};
+use crate::moniker::DEFAULT_SCHEMA;
+use crate::references::Target;
+use crate::rename::normalize_identifier;
+use crate::utils::string_value;
+use crate::{Function, LintDiagnostic, Schema, Severity, View};
+
+const UNKNOWN_COMMENTED_OBJECT: &str = "unknown-commented-object";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentedKind {
+    Aggregate,
+    Column,
+    Constraint,
+    Domain,
+    ForeignTable,
+    Function,
+    Index,
+    MaterializedView,
+    Procedure,
+    Schema,
+    Sequence,
+    Table,
+    Trigger,
+    Type,
+    View,
+    /// Any other object, e.g. a role or an extension
+    Other,
+}
+
+impl CommentedKind {
+    /// Returns the kind from the `objtype` of a statement
+    fn from_objtype(objtype: i32) -> CommentedKind {
+        match objtype {
+            2 => CommentedKind::Aggregate,
+            7 => CommentedKind::Column,
+            13 => CommentedKind::Domain,
+            19 => CommentedKind::ForeignTable,
+            20 => CommentedKind::Function,
+            21 => CommentedKind::Index,
+            24 => CommentedKind::MaterializedView,
+            30 => CommentedKind::Procedure,
+            37 => CommentedKind::Schema,
+            38 => CommentedKind::Sequence,
+            41 => CommentedKind::Constraint,
+            42 => CommentedKind::Table,
+            45 => CommentedKind::Trigger,
+            50 => CommentedKind::Type,
+            52 => CommentedKind::View,
+            _ => CommentedKind::Other,
+        }
+    }
+
+    /// Returns the kind as written after `COMMENT ON`
+    pub fn name(&self) -> &'static str {
+        match self {
+            CommentedKind::Aggregate => "aggregate",
+            CommentedKind::Column => "column",
+            CommentedKind::Constraint => "constraint",
+            CommentedKind::Domain => "domain",
+            CommentedKind::ForeignTable => "foreign table",
+            CommentedKind::Function => "function",
+            CommentedKind::Index => "index",
+            CommentedKind::MaterializedView => "materialized view",
+            CommentedKind::Procedure => "procedure",
+            CommentedKind::Schema => "schema",
+            CommentedKind::Sequence => "sequence",
+            CommentedKind::Table => "table",
+            CommentedKind::Trigger => "trigger",
+            CommentedKind::Type => "type",
+            CommentedKind::View => "view",
+            CommentedKind::Other => "object",
+        }
+    }
+
+    /// Returns the kind of the definitions that define objects of this kind, if they are indexed
+    fn object_kind(&self) -> Option<ObjectKind> {
+        match self {
+            CommentedKind::Table | CommentedKind::ForeignTable => Some(ObjectKind::Table),
+            CommentedKind::View | CommentedKind::MaterializedView => Some(ObjectKind::View),
+            CommentedKind::Function | CommentedKind::Procedure | CommentedKind::Aggregate => {
+                Some(ObjectKind::Function)
+            }
+            CommentedKind::Index => Some(ObjectKind::Index),
+            CommentedKind::Sequence => Some(ObjectKind::Sequence),
+            CommentedKind::Type | CommentedKind::Domain => Some(ObjectKind::Type),
+            CommentedKind::Schema => Some(ObjectKind::Schema),
+            _ => None,
+        }
+    }
+
+    /// Returns true if every object of this kind that the workspace defines is indexed
+    fn is_indexed(&self) -> bool {
+        *self == CommentedKind::Column
+            || (*self != CommentedKind::Aggregate && self.object_kind().is_some())
+    }
+
+    /// Returns true if every object of this kind is loaded from the database
+    fn is_loaded(&self) -> bool {
+        matches!(
+            self,
+            CommentedKind::Table
+                | CommentedKind::View
+                | CommentedKind::MaterializedView
+                | CommentedKind::Column
+                | CommentedKind::Index
+                | CommentedKind::Function
+                | CommentedKind::Aggregate
+        )
+    }
+}
+
+/// An object described by `COMMENT ON` or `SECURITY LABEL`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommentedObject {
+    pub kind: CommentedKind,
+    /// The names as written, e.g. `["app", "contact", "email"]` for a column
+    pub names: Vec<String>,
+    /// The range of the name of the object, or of the statement if it cannot be found
+    pub range: TextRange,
+}
+
+impl CommentedObject {
+    /// Returns the qualified name, e.g. `public.contact.email`
+    pub fn identifier(&self) -> String {
+        let names = self.names.iter().map(String::as_str).collect::<Vec<_>>();
+        let parts = match self.kind {
+            CommentedKind::Schema | CommentedKind::Other => return names.join("."),
+            CommentedKind::Column | CommentedKind::Constraint | CommentedKind::Trigger => 3,
+            _ => 2,
+        };
+        let mut qualified = names[names.len().saturating_sub(parts)..].to_vec();
+        if qualified.len() < parts {
+            qualified.insert(0, DEFAULT_SCHEMA);
+        }
+        qualified.join(".")
+    }
+
+    /// Returns the target of the object, if it is a relation, column or function
+    pub fn target(&self) -> Option<Target> {
+        match self.kind {
+            CommentedKind::Table
+            | CommentedKind::ForeignTable
+            | CommentedKind::View
+            | CommentedKind::MaterializedView => Some(Target::Relation(self.identifier())),
+            CommentedKind::Column => Some(Target::Column(self.identifier())),
+            CommentedKind::Function | CommentedKind::Procedure | CommentedKind::Aggregate => {
+                let (name, qualifiers) = self.names.split_last()?;
+                Some(Target::Function {
+                    schema: qualifiers.last().cloned(),
+                    name: name.clone(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the reference to the object, if it is a relation or function
+    pub fn reference(&self) -> Option<Reference> {
+        match self.target()? {
+            Target::Relation(identifier) => Some(Reference::Relation(identifier)),
+            Target::Function { schema, name } => Some(Reference::Function { schema, name }),
+            _ => None,
+        }
+    }
+
+    /// Returns true if `definition` defines the object
+    pub fn is_defined_by(&self, definition: &Definition) -> bool {
+        if self.kind.object_kind() != Some(definition.kind) {
+            return false;
+        }
+        match self.reference() {
+            Some(reference @ Reference::Function { .. }) => reference.matches(definition),
+            _ => definition.identifier == self.identifier(),
+        }
+    }
+}
+
+/// The objects of a live database
+#[derive(Debug, Clone, Copy)]
+pub struct Catalog<'a> {
+    pub schemas: &'a BTreeMap<String, Schema>,
+    pub views: &'a [View],
+    pub functions: &'a [Function],
+}
+
+impl Catalog<'_> {
+    /// Returns true if the database has the object
+    fn contains(&self, object: &CommentedObject) -> bool {
+        let identifier = object.identifier();
+        let mut parts = identifier.splitn(3, '.');
+        let (Some(schema), Some(name)) = (parts.next(), parts.next()) else {
+            return false;
+        };
+        let tables = self.schemas.get(schema).map(|s| &s.tables);
+        match object.kind {
+            CommentedKind::Table => tables.is_some_and(|t| t.contains_key(name)),
+            CommentedKind::View | CommentedKind::MaterializedView => self
+                .views
+                .iter()
+                .any(|v| v.schema_name == schema && v.name == name),
+            CommentedKind::Column => parts.next().is_some_and(|column| {
+                tables
+                    .and_then(|t| t.get(name))
+                    .is_some_and(|t| t.column(column).is_some())
+            }),
+            CommentedKind::Index => tables.is_some_and(|t| t.values().any(|t| t.has_index(name))),
+            CommentedKind::Function | CommentedKind::Aggregate => {
+                let Some(Target::Function { schema, name }) = object.target() else {
+                    return false;
+                };
+                self.functions
+                    .iter()
+                    .any(|f| f.name == name && schema.iter().all(|s| f.schema_name == *s))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Returns the objects that the `COMMENT ON` and `SECURITY LABEL` statements of `stmts` describe
+pub fn commented_objects(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmts: &[RawStmt],
+) -> Vec<CommentedObject> {
+    let tokens = cst
+        .descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| !token.kind().is_trivia())
+        .collect::<Vec<_>>();
+
+    stmts
+        .iter()
+        .filter_map(|stmt| {
+            let (objtype, object) = match &stmt.stmt {
+                NodeEnum::CommentStmt(n) => (n.objtype, n.object.as_ref()?),
+                NodeEnum::SecLabelStmt(n) => (n.objtype, n.object.as_ref()?),
+                _ => return None,
+            };
+            let kind = CommentedKind::from_objtype(objtype);
+            let names = object_names(object)?;
+
+            // constraints and triggers are written as `name ON table`
+            let written = match kind {
+                CommentedKind::Constraint | CommentedKind::Trigger => &names[names.len() - 1..],
+                _ => &names[..],
+            };
+            let stmt_tokens = tokens
+                .iter()
+                .filter(|token| stmt.range.contains_range(token.text_range()))
+                .skip_while(|token| token.kind() != SyntaxKind::On)
+                .collect::<Vec<_>>();
+            let range = stmt_tokens
+                .windows(written.len() * 2 - 1)
+                .find(|window| {
+                    window.iter().enumerate().all(|(idx, token)| {
+                        if idx % 2 == 1 {
+                            token.kind() == SyntaxKind::Ascii46
+                        } else {
+                            normalize_identifier(token.text()) == written[idx / 2]
+                        }
+                    })
+                })
+                .and_then(|window| window.last())
+                .map_or(stmt.range, |token| token.text_range());
+
+            Some(CommentedObject { kind, names, range })
+        })
+        .collect()
+}
+
+/// Returns the names of the object of a `COMMENT ON` or `SECURITY LABEL` statement
+fn object_names(object: &Node) -> Option<Vec<String>> {
+    let names = match object.node.as_ref()? {
+        NodeEnum::List(l) => strings(&l.items),
+        NodeEnum::ObjectWithArgs(o) => strings(&o.objname),
+        NodeEnum::TypeName(t) => strings(&t.names),
+        NodeEnum::String(s) => vec![s.sval.clone()],
+        _ => return None,
+    };
+    (!names.is_empty()).then_some(names)
+}
+
+fn strings(nodes: &[Node]) -> Vec<String> {
+    nodes
+        .iter()
+        .filter_map(string_value)
+        .map(str::to_string)
+        .collect()
+}
+
+/// Returns a diagnostic for every object described by `stmts` that is neither defined in the
+/// workspace, as told by `is_defined`, nor in the database of the `catalog`, if there is one
+pub fn check_commented_objects(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmts: &[RawStmt],
+    is_defined: impl Fn(&CommentedObject) -> bool,
+    catalog: Option<&Catalog<'_>>,
+) -> Vec<LintDiagnostic> {
+    commented_objects(cst, stmts)
+        .into_iter()
+        .filter(|object| object.kind.is_indexed() && (catalog.is_none() || object.kind.is_loaded()))
+        .filter(|object| {
+            !is_defined(object) && !catalog.is_some_and(|catalog| catalog.contains(object))
+        })
+        .map(|object| LintDiagnostic {
+            rule: UNKNOWN_COMMENTED_OBJECT,
+            message: format!(
+                "{} {} does not exist",
+                object.kind.name(),
+                object.names.join(".")
+            ),
+            severity: Severity::Warning,
+            range: object.range,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+    use crate::definitions::definitions;
+    use crate::Table;
+
+    #[test]
+    fn test_commented_objects() {
+        let input = "comment on table app.contact is 'people';
+comment on column contact.email is null;
+comment on function full_name(contact) is 'the full name';
+comment on constraint contact_pkey on app.contact is 'the key';
+security label for selinux on schema app is 'system_u:object_r:sepgsql_schema_t:s0';";
+        let parse = parse_source(input);
+        let objects = commented_objects(&parse.cst, &parse.stmts);
+        assert_eq!(
+            objects
+                .iter()
+                .map(|o| (o.kind, o.identifier(), &input[o.range]))
+                .collect::<Vec<_>>(),
+            vec![
+                (CommentedKind::Table, "app.contact".to_string(), "contact"),
+                (
+                    CommentedKind::Column,
+                    "public.contact.email".to_string(),
+                    "email"
+                ),
+                (
+                    CommentedKind::Function,
+                    "public.full_name".to_string(),
+                    "full_name"
+                ),
+                (
+                    CommentedKind::Constraint,
+                    "app.contact.contact_pkey".to_string(),
+                    "contact_pkey"
+                ),
+                (CommentedKind::Schema, "app".to_string(), "app"),
+            ]
+        );
+        assert_eq!(
+            objects[2].target(),
+            Some(Target::Function {
+                schema: None,
+                name: "full_name".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_check_commented_objects() {
+        let workspace = parse_source(
+            "create table contact (id int primary key, email text);
+            create sequence contact_seq;",
+        );
+        let definitions = definitions(&workspace.stmts);
+        let is_defined =
+            |object: &CommentedObject| definitions.iter().any(|d| object.is_defined_by(d));
+
+        let input = "comment on table contact is 'people';
+comment on table company is 'companies';
+comment on sequence contact_seq is null;
+comment on type mood is null;
+comment on trigger audit on contact is null;
+comment on index company_name_idx is null;";
+        let parse = parse_source(input);
+        let messages = |catalog: Option<&Catalog<'_>>| {
+            check_commented_objects(&parse.cst, &parse.stmts, is_defined, catalog)
+                .into_iter()
+                .map(|d| (d.message, &input[d.range]))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            messages(None),
+            vec![
+                ("table company does not exist".to_string(), "company"),
+                ("type mood does not exist".to_string(), "mood"),
+                (
+                    "index company_name_idx does not exist".to_string(),
+                    "company_name_idx"
+                ),
+            ]
+        );
+
+        let mut schema = Schema::new("public");
+        let company = Table {
+            name: "company".to_string(),
+            indexes: BTreeMap::from([("company_name_idx".to_string(), String::new())]),
+            ..Table::default()
+        };
+        schema.tables.insert("company".to_string(), company);
+        let schemas = BTreeMap::from([("public".to_string(), schema)]);
+        let catalog = Catalog {
+            schemas: &schemas,
+            views: &[],
+            functions: &[],
+        };
+        assert!(messages(Some(&catalog)).is_empty());
+    }
+}
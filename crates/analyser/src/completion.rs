@@ -0,0 +1,670 @@
+//! Completion of keywords and statement skeletons.
+//!
+//! The statement at the cursor is usually incomplete and does not parse, so the context is taken
+//! from the keywords before the cursor instead of the syntax tree: the leading keywords tell the
+//! kind of statement, and the last clause tells which clauses may still follow. Only the keywords
+//! of these statements count, others such as `data` are more likely to be names. Keywords within
+//! parentheses, e.g. of subqueries, are skipped. Keywords are offered in the case of the latest
+//! statement.
+//!
+//! At the start of a statement, snippets with the skeletons of common statements are offered in
+//! addition to the keywords. Functions are offered in the clauses that take expressions, e.g.
+//! `WHERE`, as calls with a tab stop for every argument. Roles are offered after `OWNER TO`.
+
+use cstree::syntax::ResolvedNode;
+use cstree::text::{TextRange, TextSize};
+use parser::make::quote_ident;
+use parser::SyntaxKind;
+
+use crate::Function;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    Keyword,
+    /// A statement skeleton, whose insert text contains tab stops such as `${1:table}`
+    Snippet,
+    /// A call of a function, whose insert text contains a tab stop for every argument
+    Function,
+    Role,
+    /// The name of a relation or sequence within a string, see
+    /// [`name_arguments`](crate::name_arguments)
+    ObjectName,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub insert_text: String,
+    /// Details such as the return type of a function
+    pub detail: Option<String>,
+    /// The range of the word being typed, which the completion replaces
+    pub range: TextRange,
+}
+
+/// A kind of statement, described by the keywords it starts with and the clauses that follow
+struct Statement {
+    keywords: &'static [&'static str],
+    /// Keywords that may directly follow the leading ones, e.g. `IF NOT EXISTS`
+    modifiers: &'static [&'static str],
+    /// The clauses after the leading keywords and their operand, e.g. a table, in the order in
+    /// which they appear. Alternatives are grouped.
+    clauses: &'static [&'static [&'static str]],
+    /// Whether the first group of clauses must come before any of the others
+    first_required: bool,
+}
+
+const STATEMENTS: &[Statement] = &[
+    Statement {
+        keywords: &["SELECT"],
+        modifiers: &["DISTINCT"],
+        clauses: &[
+            &["FROM"],
+            &["WHERE"],
+            &["GROUP BY"],
+            &["HAVING"],
+            &["WINDOW"],
+            &["ORDER BY"],
+            &["LIMIT"],
+            &["OFFSET"],
+        ],
+        first_required: false,
+    },
+    Statement {
+        keywords: &["INSERT", "INTO"],
+        modifiers: &[],
+        clauses: &[
+            &["VALUES", "SELECT", "DEFAULT VALUES"],
+            &["ON CONFLICT"],
+            &["RETURNING"],
+        ],
+        first_required: true,
+    },
+    Statement {
+        keywords: &["UPDATE"],
+        modifiers: &["ONLY"],
+        clauses: &[&["SET"], &["FROM"], &["WHERE"], &["RETURNING"]],
+        first_required: true,
+    },
+    Statement {
+        keywords: &["DELETE", "FROM"],
+        modifiers: &["ONLY"],
+        clauses: &[&["USING"], &["WHERE"], &["RETURNING"]],
+        first_required: false,
+    },
+    Statement {
+        keywords: &["CREATE", "TABLE"],
+        modifiers: &["IF NOT EXISTS"],
+        clauses: &[],
+        first_required: false,
+    },
+    Statement {
+        keywords: &["CREATE", "INDEX"],
+        modifiers: &["CONCURRENTLY", "IF NOT EXISTS"],
+        clauses: &[&["ON"], &["USING"], &["INCLUDE"], &["WHERE"]],
+        first_required: true,
+    },
+    Statement {
+        keywords: &["CREATE", "UNIQUE", "INDEX"],
+        modifiers: &["CONCURRENTLY", "IF NOT EXISTS"],
+        clauses: &[&["ON"], &["USING"], &["INCLUDE"], &["WHERE"]],
+        first_required: true,
+    },
+    Statement {
+        keywords: &["ALTER", "TABLE"],
+        modifiers: &["IF EXISTS", "ONLY"],
+        clauses: &[&[
+            "ADD COLUMN",
+            "DROP COLUMN",
+            "ALTER COLUMN",
+            "RENAME COLUMN",
+            "RENAME TO",
+            "ADD CONSTRAINT",
+            "DROP CONSTRAINT",
+            "SET SCHEMA",
+            "CLUSTER ON",
+            "SET WITHOUT CLUSTER",
+            "SET ACCESS METHOD",
+            "OWNER TO",
+        ]],
+        first_required: true,
+    },
+    Statement {
+        keywords: &["DROP", "TABLE"],
+        modifiers: &["IF EXISTS"],
+        clauses: &[&["CASCADE", "RESTRICT"]],
+        first_required: false,
+    },
+    Statement {
+        keywords: &["DROP", "INDEX"],
+        modifiers: &["CONCURRENTLY", "IF EXISTS"],
+        clauses: &[&["CASCADE", "RESTRICT"]],
+        first_required: false,
+    },
+    Statement {
+        keywords: &["CLUSTER"],
+        modifiers: &["VERBOSE"],
+        clauses: &[&["USING"]],
+        first_required: false,
+    },
+    Statement {
+        keywords: &["LOCK"],
+        modifiers: &["TABLE", "ONLY"],
+        clauses: &[&["IN"], &["NOWAIT"]],
+        first_required: false,
+    },
+];
+
+/// Keywords that can follow the table of a `FROM` clause
+const JOINS: &[&str] = &["JOIN", "LEFT JOIN", "INNER JOIN", "CROSS JOIN"];
+
+/// The lock modes of `LOCK`, which follow `IN`, from the weakest to the strongest one
+const LOCK_MODES: &[&str] = &[
+    "ACCESS SHARE MODE",
+    "ROW SHARE MODE",
+    "ROW EXCLUSIVE MODE",
+    "SHARE UPDATE EXCLUSIVE MODE",
+    "SHARE MODE",
+    "SHARE ROW EXCLUSIVE MODE",
+    "EXCLUSIVE MODE",
+    "ACCESS EXCLUSIVE MODE",
+];
+
+/// Statement skeletons as label and snippet
+const SNIPPETS: &[(&str, &str)] = &[
+    (
+        "CREATE TABLE …",
+        "CREATE TABLE ${1:name} (\n\t${2:id} bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY$0\n);",
+    ),
+    (
+        "CREATE INDEX CONCURRENTLY …",
+        "CREATE INDEX CONCURRENTLY ${1:name} ON ${2:table} (${3:column});",
+    ),
+    (
+        "INSERT INTO … VALUES",
+        "INSERT INTO ${1:table} (${2:column})\nVALUES (${3:value});",
+    ),
+    (
+        "SELECT … FROM",
+        "SELECT ${1:*}\nFROM ${2:table}\nWHERE ${3:condition};",
+    ),
+    (
+        "UPDATE … SET",
+        "UPDATE ${1:table}\nSET ${2:column} = ${3:value}\nWHERE ${4:condition};",
+    ),
+    (
+        "DELETE FROM … WHERE",
+        "DELETE FROM ${1:table}\nWHERE ${2:condition};",
+    ),
+];
+
+/// The roles that `OWNER TO` accepts besides the names of roles
+const ROLE_KEYWORDS: &[&str] = &["CURRENT_ROLE", "CURRENT_USER", "SESSION_USER"];
+
+/// Keywords after which an expression follows, possibly after other expressions, so that
+/// functions can be called there. `BY` stands for `GROUP BY` and `ORDER BY`.
+const EXPRESSION_KEYWORDS: &[&str] = &[
+    "SELECT",
+    "DISTINCT",
+    "WHERE",
+    "BY",
+    "HAVING",
+    "SET",
+    "VALUES",
+    "RETURNING",
+];
+
+/// The statement before the cursor
+struct Context {
+    /// The keywords of the statement, in uppercase, and an empty string for every operand such as
+    /// a name or a parenthesized expression
+    words: Vec<String>,
+    /// Whether the statement is written in lowercase, if that is known yet
+    lowercase: Option<bool>,
+    /// The range of the word being typed
+    range: TextRange,
+}
+
+impl Context {
+    /// Returns the context at `offset`, or `None` if nothing can be completed there, e.g. within a
+    /// comment
+    fn at(cst: &ResolvedNode<SyntaxKind>, offset: TextSize) -> Option<Context> {
+        let mut words = Vec::new();
+        let mut lowercase = None;
+        let mut depth = 0_usize;
+        let mut range = TextRange::empty(offset);
+        for token in cst
+            .descendants_with_tokens()
+            .filter_map(|element| element.into_token())
+        {
+            let token_range = token.text_range();
+            if token_range.start() >= offset {
+                break;
+            }
+            let kind = token.kind();
+            let is_word = kind == SyntaxKind::Ident || kind.is_keyword();
+            let is_whitespace = matches!(kind, SyntaxKind::Whitespace | SyntaxKind::Newline);
+            if token_range.end() == offset && is_word {
+                // the word that is being typed is replaced by the completion
+                range = token_range;
+                if lowercase.is_none() {
+                    lowercase = Some(token.text().chars().all(|c| !c.is_uppercase()));
+                }
+                break;
+            }
+            if token_range.end() > offset && !is_whitespace {
+                return None;
+            }
+            if token_range.end() == offset && kind.is_trivia() && !is_whitespace {
+                // there is nothing to complete within a comment
+                return None;
+            }
+            match kind {
+                SyntaxKind::Ascii59 => {
+                    words.clear();
+                    depth = 0;
+                }
+                SyntaxKind::Ascii40 => depth += 1,
+                SyntaxKind::Ascii41 => {
+                    depth = depth.saturating_sub(1);
+                    if depth == 0 {
+                        words.push(String::new());
+                    }
+                }
+                _ if kind.is_trivia() || depth > 0 => {}
+                _ if kind.is_keyword() && is_statement_keyword(token.text()) => {
+                    if words.is_empty() {
+                        lowercase = Some(token.text().chars().all(|c| !c.is_uppercase()));
+                    }
+                    words.push(token.text().to_uppercase());
+                }
+                _ => words.push(String::new()),
+            }
+        }
+        Some(Context {
+            words,
+            lowercase,
+            range,
+        })
+    }
+}
+
+/// Returns the completions at `offset`
+pub fn completions(cst: &ResolvedNode<SyntaxKind>, offset: TextSize) -> Vec<Completion> {
+    let Some(Context {
+        words,
+        lowercase,
+        range,
+    }) = Context::at(cst, offset)
+    else {
+        return Vec::new();
+    };
+
+    let case = |keyword: &str| match lowercase {
+        Some(true) => keyword.to_lowercase(),
+        _ => keyword.to_string(),
+    };
+    let mut completions = next_keywords(&words)
+        .into_iter()
+        .map(|keyword| Completion {
+            label: case(&keyword),
+            kind: CompletionKind::Keyword,
+            insert_text: case(&keyword),
+            detail: None,
+            range,
+        })
+        .collect::<Vec<_>>();
+    if words.is_empty() {
+        completions.extend(SNIPPETS.iter().map(|(label, snippet)| Completion {
+            label: label.to_string(),
+            kind: CompletionKind::Snippet,
+            insert_text: snippet.to_string(),
+            detail: None,
+            range,
+        }));
+    }
+    completions
+}
+
+/// Returns a call of every function of `functions` if an expression can be written at `offset`
+pub fn function_completions(
+    cst: &ResolvedNode<SyntaxKind>,
+    offset: TextSize,
+    functions: &[Function],
+) -> Vec<Completion> {
+    let Some(context) = Context::at(cst, offset) else {
+        return Vec::new();
+    };
+    let in_expression = context
+        .words
+        .iter()
+        .rev()
+        .find(|w| !w.is_empty())
+        .is_some_and(|w| EXPRESSION_KEYWORDS.contains(&w.as_str()));
+    if !in_expression {
+        return Vec::new();
+    }
+    functions
+        .iter()
+        .map(|function| Completion {
+            label: format!("{}({})", function.name, function.arguments),
+            kind: CompletionKind::Function,
+            insert_text: function.snippet(),
+            detail: Some(if function.is_aggregate {
+                format!("{} (aggregate)", function.result_type)
+            } else {
+                function.result_type.clone()
+            }),
+            range: context.range,
+        })
+        .collect()
+}
+
+/// Returns the `roles` and the keywords that stand for roles if a new owner can be written at
+/// `offset`, i.e. after `OWNER TO` of any `ALTER` statement
+pub fn role_completions(
+    cst: &ResolvedNode<SyntaxKind>,
+    offset: TextSize,
+    roles: &[String],
+) -> Vec<Completion> {
+    let Some(context) = Context::at(cst, offset) else {
+        return Vec::new();
+    };
+    if !context
+        .words
+        .ends_with(&["OWNER".to_string(), "TO".to_string()])
+    {
+        return Vec::new();
+    }
+    let keywords = ROLE_KEYWORDS.iter().map(|keyword| {
+        let keyword = match context.lowercase {
+            Some(true) => keyword.to_lowercase(),
+            _ => keyword.to_string(),
+        };
+        Completion {
+            label: keyword.clone(),
+            kind: CompletionKind::Keyword,
+            insert_text: keyword,
+            detail: None,
+            range: context.range,
+        }
+    });
+    roles
+        .iter()
+        .map(|role| Completion {
+            label: role.clone(),
+            kind: CompletionKind::Role,
+            insert_text: quote_ident(role),
+            detail: None,
+            range: context.range,
+        })
+        .chain(keywords)
+        .collect()
+}
+
+/// Returns the keywords that can follow `words`, the keywords of a statement
+fn next_keywords(words: &[String]) -> Vec<String> {
+    let mut words = words;
+    if words.first().is_some_and(|w| w == "WITH") {
+        // the statement that the common table expressions are for follows them
+        let Some(start) = words
+            .iter()
+            .rposition(|w| ["SELECT", "INSERT", "UPDATE", "DELETE"].contains(&w.as_str()))
+        else {
+            return Vec::new();
+        };
+        words = &words[start..];
+    }
+
+    // the leading keywords of the statements that `words` is the start of
+    let mut keywords = STATEMENTS
+        .iter()
+        .filter(|s| s.keywords.len() > words.len() && starts_with(s.keywords, words))
+        .map(|s| s.keywords[words.len()..].join(" "))
+        .collect::<Vec<_>>();
+    keywords.dedup();
+    if words.is_empty() {
+        keywords.push("WITH".to_string());
+        return keywords;
+    }
+
+    let Some(statement) = STATEMENTS
+        .iter()
+        .filter(|s| s.keywords.len() <= words.len() && starts_with(s.keywords, words))
+        .max_by_key(|s| s.keywords.len())
+    else {
+        return keywords;
+    };
+    let rest = &words[statement.keywords.len()..];
+    let modifiers = statement
+        .modifiers
+        .iter()
+        .filter(|m| !contains_phrase(rest, m))
+        .map(|m| m.to_string());
+    if rest.iter().all(|w| {
+        statement
+            .modifiers
+            .iter()
+            .any(|m| m.split(' ').any(|k| k == w))
+    }) {
+        keywords.extend(modifiers);
+        return keywords;
+    }
+    if statement.keywords == ["LOCK"] && rest.last().is_some_and(|w| w == "IN") {
+        keywords.extend(LOCK_MODES.iter().map(|m| m.to_string()));
+        return keywords;
+    }
+    // a keyword that is followed by an operand, e.g. a table after `FROM`. A lock mode is
+    // complete with `MODE`.
+    if rest.last().is_some_and(|w| !w.is_empty() && w != "MODE") {
+        return keywords;
+    }
+
+    let last_clause = statement
+        .clauses
+        .iter()
+        .rposition(|group| group.iter().any(|clause| contains_phrase(rest, clause)));
+    let following = match last_clause {
+        Some(idx) => &statement.clauses[idx + 1..],
+        None if statement.first_required => &statement.clauses[..statement.clauses.len().min(1)],
+        None => statement.clauses,
+    };
+    keywords.extend(
+        following
+            .iter()
+            .flat_map(|group| group.iter().map(|c| c.to_string())),
+    );
+    if statement.keywords == ["SELECT"] && last_clause == Some(0) {
+        keywords.extend(JOINS.iter().map(|j| j.to_string()));
+    }
+    keywords
+}
+
+/// Returns true if the keywords of `phrase`, e.g. `GROUP BY`, appear in `words` in a row
+fn contains_phrase(words: &[String], phrase: &str) -> bool {
+    let phrase = phrase.split(' ').collect::<Vec<_>>();
+    words.windows(phrase.len()).any(|w| w == phrase.as_slice())
+}
+
+/// Returns true if `keywords` and `words` agree as far as both go
+fn starts_with(keywords: &[&str], words: &[String]) -> bool {
+    keywords
+        .iter()
+        .zip(words)
+        .all(|(keyword, word)| keyword == word)
+}
+
+/// Returns true if `text` is one of the keywords of [`STATEMENTS`], [`JOINS`] or [`LOCK_MODES`]
+fn is_statement_keyword(text: &str) -> bool {
+    let text = text.to_uppercase();
+    text == "WITH"
+        || STATEMENTS
+            .iter()
+            .flat_map(|s| {
+                s.keywords
+                    .iter()
+                    .chain(s.modifiers)
+                    .chain(s.clauses.iter().flat_map(|group| group.iter()))
+            })
+            .chain(JOINS)
+            .chain(LOCK_MODES)
+            .any(|phrase| phrase.split(' ').any(|keyword| keyword == text))
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    fn labels(input: &str) -> Vec<String> {
+        let offset = TextSize::from(input.find('|').unwrap() as u32);
+        let text = input.replace('|', "");
+        completions(&parse_source(&text).cst, offset)
+            .into_iter()
+            .map(|c| c.label)
+            .collect()
+    }
+
+    #[test]
+    fn test_statement_start() {
+        let labels = labels("SELECT 1;\n|");
+        assert!(labels.contains(&"SELECT".to_string()));
+        assert!(labels.contains(&"INSERT INTO".to_string()));
+        assert!(labels.contains(&"CREATE INDEX CONCURRENTLY …".to_string()));
+
+        assert_eq!(labels("CREATE |"), vec!["TABLE", "INDEX", "UNIQUE INDEX"]);
+    }
+
+    #[test]
+    fn test_clauses() {
+        assert_eq!(
+            labels("select a from contact |"),
+            vec![
+                "where",
+                "group by",
+                "having",
+                "window",
+                "order by",
+                "limit",
+                "offset",
+                "join",
+                "left join",
+                "inner join",
+                "cross join"
+            ]
+        );
+        assert_eq!(
+            labels("SELECT a FROM contact WHERE a = 1 ORDER BY a |"),
+            vec!["LIMIT", "OFFSET"]
+        );
+        assert!(labels("select a from |").is_empty());
+        assert_eq!(labels("select (select 1 from x) |")[0], "from");
+        assert_eq!(labels("UPDATE contact |"), vec!["SET"]);
+        assert_eq!(
+            labels("INSERT INTO contact (id) VALUES (1) |"),
+            vec!["ON CONFLICT", "RETURNING"]
+        );
+        assert_eq!(
+            labels("CREATE INDEX |"),
+            vec!["CONCURRENTLY", "IF NOT EXISTS"]
+        );
+        assert_eq!(
+            labels("WITH c AS (SELECT 1) SELECT a FROM c WHERE a > 1 LIMIT 1 |"),
+            vec!["OFFSET"]
+        );
+    }
+
+    #[test]
+    fn test_lock_modes() {
+        assert_eq!(labels("lock |"), vec!["table", "only"]);
+        assert_eq!(labels("LOCK TABLE contact |"), vec!["IN", "NOWAIT"]);
+        let modes = labels("LOCK TABLE contact IN |");
+        assert_eq!(modes.len(), 8);
+        assert_eq!(modes[0], "ACCESS SHARE MODE");
+        assert_eq!(modes[7], "ACCESS EXCLUSIVE MODE");
+        assert_eq!(
+            labels("lock table contact in share row exclusive mode |"),
+            vec!["nowait"]
+        );
+        assert!(labels("LOCK TABLE contact IN ACCESS |").is_empty());
+    }
+
+    #[test]
+    fn test_cluster() {
+        assert_eq!(labels("cluster |"), vec!["verbose"]);
+        assert_eq!(labels("CLUSTER VERBOSE contact |"), vec!["USING"]);
+        assert!(labels("CLUSTER contact USING |").is_empty());
+    }
+
+    #[test]
+    fn test_role_completions() {
+        let roles = ["app".to_string(), "Reporting".to_string()];
+        let completions = |input: &str| {
+            let offset = TextSize::from(input.find('|').unwrap() as u32);
+            let text = input.replace('|', "");
+            role_completions(&parse_source(&text).cst, offset, &roles)
+                .into_iter()
+                .map(|c| (c.label, c.insert_text))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            completions("alter table contact owner to a|"),
+            vec![
+                ("app".to_string(), "app".to_string()),
+                ("Reporting".to_string(), "\"Reporting\"".to_string()),
+                ("current_role".to_string(), "current_role".to_string()),
+                ("current_user".to_string(), "current_user".to_string()),
+                ("session_user".to_string(), "session_user".to_string()),
+            ]
+        );
+        assert_eq!(
+            completions("ALTER FUNCTION full_name(contact) OWNER TO |").len(),
+            5
+        );
+        assert!(completions("alter table contact owner |").is_empty());
+        assert!(labels("ALTER TABLE contact |").contains(&"OWNER TO".to_string()));
+    }
+
+    #[test]
+    fn test_typed_word() {
+        let text = "select a fr";
+        let completions = completions(&parse_source(text).cst, TextSize::from(11));
+        assert_eq!(completions[0].label, "from");
+        assert_eq!(completions[0].range, TextRange::new(9.into(), 11.into()));
+
+        assert!(labels("select 1; -- sel|").is_empty());
+    }
+
+    #[test]
+    fn test_function_completions() {
+        let lower = Function {
+            schema_name: "pg_catalog".to_string(),
+            name: "lower".to_string(),
+            arguments: "text".to_string(),
+            result_type: "text".to_string(),
+            is_aggregate: false,
+            comment: None,
+        };
+        let functions = |input: &str| {
+            let offset = TextSize::from(input.find('|').unwrap() as u32);
+            let text = input.replace('|', "");
+            function_completions(
+                &parse_source(&text).cst,
+                offset,
+                std::slice::from_ref(&lower),
+            )
+        };
+
+        let completions = functions("select a from contact where low|");
+        assert_eq!(completions[0].label, "lower(text)");
+        assert_eq!(completions[0].insert_text, "lower(${1:text})$0");
+        assert_eq!(completions[0].detail.as_deref(), Some("text"));
+        assert_eq!(completions[0].range, TextRange::new(28.into(), 31.into()));
+
+        assert_eq!(functions("select count(|").len(), 1);
+        assert!(functions("select a from |").is_empty());
+        assert!(functions("insert into contact (|").is_empty());
+    }
+}
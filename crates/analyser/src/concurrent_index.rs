@@ -0,0 +1,123 @@
+//! Support for running `CREATE INDEX CONCURRENTLY` safely.
+//!
+//! A concurrent index build cannot run inside a transaction block, and when it fails, e.g.
+//! because of a deadlock or a unique violation, it leaves behind an invalid index that is still
+//! maintained on every write but never used. The runner executes these statements on their own,
+//! looks for invalid indexes with [`INVALID_INDEXES_QUERY`] afterwards and drops them before a
+//! retry.
+
+use parser::make::quote_ident;
+use parser::RawStmt;
+use pg_query::NodeEnum;
+
+use crate::lint::{LintDiagnostic, Severity};
+
+const CONCURRENT_INDEX_IN_TRANSACTION: &str = "concurrent-index-in-transaction";
+
+/// Returns the qualified names of the invalid indexes of the table given as `$1`, e.g.
+/// `public.contact`. The names are quoted where necessary.
+pub const INVALID_INDEXES_QUERY: &str = "select
+    x.indexrelid::pg_catalog.regclass::text as index_name
+from pg_catalog.pg_index x
+where x.indrelid = pg_catalog.to_regclass($1)
+    and not x.indisvalid
+order by 1";
+
+/// Returns the table of a `CREATE INDEX CONCURRENTLY` statement as a quoted name that can be
+/// passed to [`INVALID_INDEXES_QUERY`], or `None` for any other statement
+pub fn concurrent_index_table(stmt: &NodeEnum) -> Option<String> {
+    let NodeEnum::IndexStmt(n) = stmt else {
+        return None;
+    };
+    if !n.concurrent {
+        return None;
+    }
+    let relation = n.relation.as_ref()?;
+    Some(match relation.schemaname.as_str() {
+        "" => quote_ident(&relation.relname),
+        schema => format!("{}.{}", quote_ident(schema), quote_ident(&relation.relname)),
+    })
+}
+
+/// Reports `CREATE INDEX CONCURRENTLY` statements within an explicit transaction block, where
+/// they fail
+pub fn check_concurrent_indexes(stmts: &[RawStmt]) -> Vec<LintDiagnostic> {
+    stmts
+        .iter()
+        .zip(in_transaction_block(stmts))
+        .filter(|(stmt, in_block)| *in_block && concurrent_index_table(&stmt.stmt).is_some())
+        .map(|(stmt, _)| LintDiagnostic {
+            rule: CONCURRENT_INDEX_IN_TRANSACTION,
+            message: "CREATE INDEX CONCURRENTLY cannot run inside a transaction block, \
+                move it after the COMMIT"
+                .to_string(),
+            severity: Severity::Error,
+            range: stmt.range,
+        })
+        .collect()
+}
+
+/// Returns for each of `stmts` whether it runs inside an explicit transaction block, i.e. after a
+/// `BEGIN` and before the `COMMIT` or `ROLLBACK`
+pub(crate) fn in_transaction_block(stmts: &[RawStmt]) -> Vec<bool> {
+    let mut in_transaction = false;
+    stmts
+        .iter()
+        .map(|stmt| {
+            let in_block = in_transaction;
+            if let NodeEnum::TransactionStmt(n) = &stmt.stmt {
+                match n.kind {
+                    // TransStmtBegin, TransStmtStart
+                    1 | 2 => in_transaction = true,
+                    // TransStmtCommit, TransStmtRollback, TransStmtPrepare
+                    3 | 4 | 8 => in_transaction = false,
+                    _ => {}
+                }
+            }
+            in_block
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    #[test]
+    fn test_concurrent_index_table() {
+        let tables = parse_source(
+            "create index concurrently on contact (email);
+create index concurrently contact_name_idx on \"CRM\".contact (name);
+create index on contact (email);",
+        )
+        .stmts
+        .iter()
+        .map(|s| concurrent_index_table(&s.stmt))
+        .collect::<Vec<_>>();
+
+        assert_eq!(
+            tables,
+            vec![
+                Some("contact".to_string()),
+                Some("\"CRM\".contact".to_string()),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_concurrent_indexes() {
+        let parse = parse_source(
+            "create index concurrently on contact (email);
+begin;
+create index concurrently on contact (name);
+commit;",
+        );
+        let diagnostics = check_concurrent_indexes(&parse.stmts);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].rule, CONCURRENT_INDEX_IN_TRANSACTION);
+        assert_eq!(diagnostics[0].range, parse.stmts[2].range);
+    }
+}
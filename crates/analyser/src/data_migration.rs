@@ -0,0 +1,201 @@
+//! Detection of migrations that change both the schema and the data.
+//!
+//! Migrations usually run in a single transaction, so the locks taken by their schema changes are
+//! held until the whole file is done. A backfill such as `UPDATE contact SET ...` in the same file
+//! can take minutes on a large table, during which the altered tables are blocked for everyone.
+//! Such migrations are reported and can be split into migrations that change only the schema or
+//! only the data.
+
+use cstree::text::TextRange;
+use parser::{RawStmt, SyntaxKind};
+use pg_query::NodeEnum;
+
+use crate::lint::{LintDiagnostic, Severity};
+
+const MIXED_MIGRATION: &str = "mixed-migration";
+
+/// The kind of change a migration statement makes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Schema,
+    Data,
+}
+
+/// A consecutive run of statements of a migration that make the same kind of change
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationPart {
+    pub kind: ChangeKind,
+    /// The range in the source text, including the comments above the first statement
+    pub range: TextRange,
+}
+
+/// Returns the kind of change `stmt` makes, or `None` if it neither changes the schema nor
+/// potentially many rows, e.g. `SET lock_timeout` or `INSERT ... VALUES`
+pub fn change_kind(stmt: &NodeEnum) -> Option<ChangeKind> {
+    match stmt {
+        NodeEnum::InsertStmt(n) => {
+            // literal rows such as seed data are cheap to insert, unlike rows from a query
+            let is_values = match n.select_stmt.as_ref().and_then(|s| s.node.as_ref()) {
+                Some(NodeEnum::SelectStmt(select)) => !select.values_lists.is_empty(),
+                // `DEFAULT VALUES`
+                _ => true,
+            };
+            (!is_values).then_some(ChangeKind::Data)
+        }
+        NodeEnum::UpdateStmt(_) | NodeEnum::DeleteStmt(_) | NodeEnum::MergeStmt(_) => {
+            Some(ChangeKind::Data)
+        }
+        _ if SyntaxKind::from(stmt).is_ddl_stmt() => Some(ChangeKind::Schema),
+        _ => None,
+    }
+}
+
+/// Reports the data changes of a migration that also changes the schema
+pub fn check_mixed_migration(stmts: &[RawStmt]) -> Vec<LintDiagnostic> {
+    if !stmts
+        .iter()
+        .any(|s| change_kind(&s.stmt) == Some(ChangeKind::Schema))
+    {
+        return Vec::new();
+    }
+    stmts
+        .iter()
+        .filter(|s| change_kind(&s.stmt) == Some(ChangeKind::Data))
+        .map(|s| LintDiagnostic {
+            rule: MIXED_MIGRATION,
+            message: "this migration also changes the schema, whose locks are held until this \
+                statement is done; move the data changes into a separate migration"
+                .to_string(),
+            severity: Severity::Warning,
+            range: s.range,
+        })
+        .collect()
+}
+
+/// Splits a migration into parts that only change the schema or only the data, in the order of
+/// the source text. Statements that make neither kind of change stay in the part they are in, and
+/// the whole text is covered by the parts. Returns a single part if there is nothing to split.
+pub fn split_migration(text: &str, stmts: &[RawStmt]) -> Vec<MigrationPart> {
+    let mut parts = Vec::<MigrationPart>::new();
+    let mut prev_end = 0;
+    for stmt in stmts {
+        let Some(kind) = change_kind(&stmt.stmt) else {
+            prev_end = stmt.range.end().into();
+            continue;
+        };
+        match parts.last_mut() {
+            Some(part) if part.kind != kind => {
+                let start = part_start(text, prev_end).min(stmt.range.start().into());
+                part.range = TextRange::new(part.range.start(), (start as u32).into());
+                parts.push(MigrationPart {
+                    kind,
+                    range: TextRange::new((start as u32).into(), (text.len() as u32).into()),
+                });
+            }
+            Some(_) => {}
+            None => parts.push(MigrationPart {
+                kind,
+                range: TextRange::up_to((text.len() as u32).into()),
+            }),
+        }
+        prev_end = stmt.range.end().into();
+    }
+    parts
+}
+
+/// Returns the start of the part after a statement that ends at `prev_end`. The rest of the line
+/// of the statement, e.g. its semicolon, stays with it.
+fn part_start(text: &str, prev_end: usize) -> usize {
+    match text[prev_end..].find('\n') {
+        Some(idx) => prev_end + idx + 1,
+        None => text.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    #[test]
+    fn test_change_kind() {
+        let kinds = parse_source(
+            "set lock_timeout = '1s';
+create index on contact (email);
+insert into contact (id) values (1);
+insert into contact_copy select * from contact;
+update contact set email = lower(email);
+delete from contact where id = 1;",
+        )
+        .stmts
+        .iter()
+        .map(|s| change_kind(&s.stmt))
+        .collect::<Vec<_>>();
+
+        assert_eq!(
+            kinds,
+            vec![
+                None,
+                Some(ChangeKind::Schema),
+                None,
+                Some(ChangeKind::Data),
+                Some(ChangeKind::Data),
+                Some(ChangeKind::Data),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_mixed_migration() {
+        let mixed = parse_source(
+            "alter table contact add column email text;
+update contact set email = '';",
+        );
+        let diagnostics = check_mixed_migration(&mixed.stmts);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].rule, MIXED_MIGRATION);
+        assert_eq!(diagnostics[0].range, mixed.stmts[1].range);
+
+        let seed = parse_source(
+            "create table status (id int);
+insert into status (id) values (1), (2);",
+        );
+        assert!(check_mixed_migration(&seed.stmts).is_empty());
+
+        let data = parse_source("update contact set email = '';");
+        assert!(check_mixed_migration(&data.stmts).is_empty());
+    }
+
+    #[test]
+    fn test_split_migration() {
+        let text = "set lock_timeout = '1s';
+alter table contact add column email text;
+
+-- backfill
+update contact set email = '';
+alter table contact alter column email set not null;
+";
+        let parse = parse_source(text);
+        let parts = split_migration(text, &parse.stmts);
+
+        assert_eq!(
+            parts.iter().map(|p| p.kind).collect::<Vec<_>>(),
+            vec![ChangeKind::Schema, ChangeKind::Data, ChangeKind::Schema]
+        );
+        assert_eq!(
+            parts.iter().map(|p| &text[p.range]).collect::<Vec<_>>(),
+            vec![
+                "set lock_timeout = '1s';\nalter table contact add column email text;\n",
+                "\n-- backfill\nupdate contact set email = '';\n",
+                "alter table contact alter column email set not null;\n",
+            ]
+        );
+
+        let schema = "create table contact (id int);";
+        assert_eq!(
+            split_migration(schema, &parse_source(schema).stmts).len(),
+            1
+        );
+    }
+}
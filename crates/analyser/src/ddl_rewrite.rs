@@ -0,0 +1,379 @@
+//! Rewrites of DDL statements that make migrations safer to run.
+//!
+//! A range of statements is wrapped in `BEGIN` and `COMMIT`, so that they are applied together or
+//! not at all, unless one of them controls transactions itself, cannot run in a transaction block
+//! or already runs in one. `CREATE` and `DROP` statements get `IF NOT EXISTS` and `IF EXISTS`, so
+//! that a script can run again after it failed halfway. `CREATE INDEX` gets `CONCURRENTLY`, which
+//! builds the index without blocking writes, unless the statement is in a transaction block where
+//! a concurrent build fails. Keywords are added in the case of the statement they are added to.
+
+use cstree::syntax::{ResolvedNode, ResolvedToken};
+use cstree::text::{TextRange, TextSize};
+use parser::{RawStmt, SyntaxKind};
+use pg_query::NodeEnum;
+
+use crate::concurrent_index::in_transaction_block;
+
+/// An insertion or replacement of source text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub range: TextRange,
+    pub text: String,
+}
+
+/// Returns the edits that wrap the statements within `range` in a transaction block, or `None` if
+/// they cannot be wrapped. An empty range selects the statement it is in.
+pub fn wrap_in_transaction(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmts: &[RawStmt],
+    range: TextRange,
+) -> Option<Vec<Edit>> {
+    let in_block = in_transaction_block(stmts);
+    let selected = stmts
+        .iter()
+        .zip(in_block)
+        .filter(|(stmt, _)| {
+            if range.is_empty() {
+                stmt.range.contains_inclusive(range.start())
+            } else {
+                stmt.range.intersect(range).is_some_and(|r| !r.is_empty())
+            }
+        })
+        .collect::<Vec<_>>();
+    let (first, _) = selected.first()?;
+    let (last, _) = selected.last()?;
+    let wrappable = selected.iter().all(|(stmt, in_block)| {
+        !in_block
+            && !matches!(stmt.stmt, NodeEnum::TransactionStmt(_))
+            && can_run_in_transaction(&stmt.stmt)
+    });
+    if !wrappable {
+        return None;
+    }
+    let tokens = stmt_tokens(cst, first);
+    Some(vec![
+        Edit {
+            range: TextRange::empty(first.range.start()),
+            text: format!("{};\n", keyword(&tokens, "BEGIN")),
+        },
+        Edit {
+            range: TextRange::empty(stmt_end(cst, last)),
+            text: format!("\n{};", keyword(&tokens, "COMMIT")),
+        },
+    ])
+}
+
+/// Returns the insertion of `IF NOT EXISTS` into the `CREATE` statement at `offset`, or of
+/// `IF EXISTS` into the `DROP` statement there, if the statement supports it and has none
+pub fn add_if_exists(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmts: &[RawStmt],
+    offset: TextSize,
+) -> Option<Edit> {
+    let stmt = stmts
+        .iter()
+        .find(|stmt| stmt.range.contains_inclusive(offset))?;
+    let tokens = stmt_tokens(cst, stmt);
+    let location = |location: i32| stmt.range.start() + TextSize::from(location.max(0) as u32);
+    let after = |word: &str| {
+        let idx = tokens
+            .iter()
+            .position(|t| t.text().eq_ignore_ascii_case(word))?;
+        Some(tokens.get(idx + 1)?.text_range().start())
+    };
+    let (at, text) = match &stmt.stmt {
+        NodeEnum::CreateStmt(n) if !n.if_not_exists => {
+            (location(n.relation.as_ref()?.location), "IF NOT EXISTS")
+        }
+        NodeEnum::CreateSeqStmt(n) if !n.if_not_exists => {
+            (location(n.sequence.as_ref()?.location), "IF NOT EXISTS")
+        }
+        NodeEnum::CreateTableAsStmt(n) if !n.if_not_exists => (
+            location(n.into.as_ref()?.rel.as_ref()?.location),
+            "IF NOT EXISTS",
+        ),
+        // an index without a name cannot have `IF NOT EXISTS`
+        NodeEnum::IndexStmt(n) if !n.if_not_exists && !n.idxname.is_empty() => {
+            let idx = tokens
+                .iter()
+                .position(|t| t.text().eq_ignore_ascii_case("index"))?;
+            let name = tokens[idx + 1..]
+                .iter()
+                .find(|t| !t.text().eq_ignore_ascii_case("concurrently"))?;
+            (name.text_range().start(), "IF NOT EXISTS")
+        }
+        NodeEnum::CreateSchemaStmt(n) if !n.if_not_exists => (after("schema")?, "IF NOT EXISTS"),
+        NodeEnum::CreateExtensionStmt(n) if !n.if_not_exists => {
+            (after("extension")?, "IF NOT EXISTS")
+        }
+        // only the words of the object type are skipped, as a name can be such a word, e.g. `text`
+        NodeEnum::DropStmt(n) if !n.missing_ok => {
+            let mut idx = 1;
+            for word in drop_words(n.remove_type)? {
+                if tokens
+                    .get(idx)
+                    .is_some_and(|t| t.text().eq_ignore_ascii_case(word))
+                {
+                    idx += 1;
+                }
+            }
+            (tokens.get(idx)?.text_range().start(), "IF EXISTS")
+        }
+        _ => return None,
+    };
+    Some(Edit {
+        range: TextRange::empty(at),
+        text: format!("{} ", keyword(&tokens, text)),
+    })
+}
+
+/// Returns the insertion of `CONCURRENTLY` into the `CREATE INDEX` at `offset`, if it builds the
+/// index without it and outside of a transaction block
+pub fn add_concurrently(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmts: &[RawStmt],
+    offset: TextSize,
+) -> Option<Edit> {
+    let (stmt, in_block) = stmts
+        .iter()
+        .zip(in_transaction_block(stmts))
+        .find(|(stmt, _)| stmt.range.contains_inclusive(offset))?;
+    let NodeEnum::IndexStmt(n) = &stmt.stmt else {
+        return None;
+    };
+    if n.concurrent || in_block {
+        return None;
+    }
+    let tokens = stmt_tokens(cst, stmt);
+    let index = tokens
+        .iter()
+        .find(|t| t.text().eq_ignore_ascii_case("index"))?;
+    Some(Edit {
+        range: TextRange::empty(index.text_range().end()),
+        text: format!(" {}", keyword(&tokens, "CONCURRENTLY")),
+    })
+}
+
+/// Returns the end of `stmt` including its semicolon, whether or not the range of the statement
+/// includes it
+pub(crate) fn stmt_end(cst: &ResolvedNode<SyntaxKind>, stmt: &RawStmt) -> TextSize {
+    cst.descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| !token.kind().is_trivia())
+        .find(|token| {
+            let end = token.text_range().end();
+            end > stmt.range.end()
+                || (end == stmt.range.end() && token.kind() == SyntaxKind::Ascii59)
+        })
+        .filter(|token| token.kind() == SyntaxKind::Ascii59)
+        .map_or(stmt.range.end(), |token| token.text_range().end())
+}
+
+/// Returns the words between `DROP` and `IF EXISTS` for the object type `remove_type`, including
+/// the optional ones, e.g. `MATERIALIZED VIEW`, or `None` if the type is not known
+fn drop_words(remove_type: i32) -> Option<&'static [&'static str]> {
+    Some(match remove_type {
+        // ObjectAccessMethod
+        1 => &["access", "method"],
+        // ObjectAggregate
+        2 => &["aggregate"],
+        // ObjectCast
+        6 => &["cast"],
+        // ObjectCollation
+        8 => &["collation"],
+        // ObjectConversion
+        9 => &["conversion"],
+        // ObjectDomain
+        13 => &["domain"],
+        // ObjectEventTrigger
+        15 => &["event", "trigger"],
+        // ObjectExtension
+        16 => &["extension"],
+        // ObjectFdw
+        17 => &["foreign", "data", "wrapper"],
+        // ObjectForeignServer
+        18 => &["server"],
+        // ObjectForeignTable
+        19 => &["foreign", "table"],
+        // ObjectFunction
+        20 => &["function"],
+        // ObjectIndex
+        21 => &["index", "concurrently"],
+        // ObjectLanguage
+        22 => &["procedural", "language"],
+        // ObjectMatview
+        24 => &["materialized", "view"],
+        // ObjectOpclass
+        25 => &["operator", "class"],
+        // ObjectOperator
+        26 => &["operator"],
+        // ObjectOpfamily
+        27 => &["operator", "family"],
+        // ObjectPolicy
+        29 => &["policy"],
+        // ObjectProcedure
+        30 => &["procedure"],
+        // ObjectPublication
+        31 => &["publication"],
+        // ObjectRoutine
+        35 => &["routine"],
+        // ObjectRule
+        36 => &["rule"],
+        // ObjectSchema
+        37 => &["schema"],
+        // ObjectSequence
+        38 => &["sequence"],
+        // ObjectStatisticExt
+        40 => &["statistics"],
+        // ObjectTable
+        42 => &["table"],
+        // ObjectTransform
+        44 => &["transform"],
+        // ObjectTrigger
+        45 => &["trigger"],
+        // ObjectTsconfiguration
+        46 => &["text", "search", "configuration"],
+        // ObjectTsdictionary
+        47 => &["text", "search", "dictionary"],
+        // ObjectTsparser
+        48 => &["text", "search", "parser"],
+        // ObjectTstemplate
+        49 => &["text", "search", "template"],
+        // ObjectType
+        50 => &["type"],
+        // ObjectView
+        52 => &["view"],
+        _ => return None,
+    })
+}
+
+/// Returns false for statements that Postgres refuses to run inside a transaction block
+fn can_run_in_transaction(stmt: &NodeEnum) -> bool {
+    match stmt {
+        NodeEnum::IndexStmt(n) => !n.concurrent,
+        NodeEnum::DropStmt(n) => !n.concurrent,
+        NodeEnum::VacuumStmt(_)
+        | NodeEnum::CreatedbStmt(_)
+        | NodeEnum::DropdbStmt(_)
+        | NodeEnum::CreateTableSpaceStmt(_)
+        | NodeEnum::DropTableSpaceStmt(_)
+        | NodeEnum::AlterSystemStmt(_) => false,
+        _ => true,
+    }
+}
+
+fn stmt_tokens<'a>(
+    cst: &'a ResolvedNode<SyntaxKind>,
+    stmt: &RawStmt,
+) -> Vec<&'a ResolvedToken<SyntaxKind>> {
+    cst.descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| stmt.range.contains_range(token.text_range()) && !token.kind().is_trivia())
+        .collect()
+}
+
+/// Returns `keyword` in lower case if the statement of `tokens` starts with a lower case keyword
+fn keyword(tokens: &[&ResolvedToken<SyntaxKind>], keyword: &str) -> String {
+    if tokens
+        .first()
+        .is_some_and(|t| t.text().chars().any(|c| c.is_lowercase()))
+    {
+        keyword.to_lowercase()
+    } else {
+        keyword.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    fn apply(input: &str, mut edits: Vec<Edit>) -> String {
+        let mut text = input.to_string();
+        edits.sort_by_key(|e| e.range.start());
+        for edit in edits.iter().rev() {
+            text.replace_range(std::ops::Range::<usize>::from(edit.range), &edit.text);
+        }
+        text
+    }
+
+    #[test]
+    fn test_wrap_in_transaction() {
+        let input = "create table note (body text);
+alter table note add column title text;
+create index concurrently on note (title);";
+        let parse = parse_source(input);
+        let range = TextRange::new(5.into(), 40.into());
+        let edits = wrap_in_transaction(&parse.cst, &parse.stmts, range).unwrap();
+        assert_eq!(
+            apply(input, edits),
+            "begin;
+create table note (body text);
+alter table note add column title text;
+commit;
+create index concurrently on note (title);"
+        );
+        let at_index = TextRange::empty(TextSize::try_from(input.rfind("index").unwrap()).unwrap());
+        assert!(wrap_in_transaction(&parse.cst, &parse.stmts, at_index).is_none());
+    }
+
+    #[test]
+    fn test_add_if_exists() {
+        let edit = |input: &str| {
+            let parse = parse_source(input);
+            add_if_exists(&parse.cst, &parse.stmts, 0.into()).map(|e| apply(input, vec![e]))
+        };
+        assert_eq!(
+            edit("CREATE TABLE app.note (body text);").as_deref(),
+            Some("CREATE TABLE IF NOT EXISTS app.note (body text);")
+        );
+        assert_eq!(
+            edit("create index concurrently note_idx on note (body);").as_deref(),
+            Some("create index concurrently if not exists note_idx on note (body);")
+        );
+        assert_eq!(
+            edit("drop materialized view report, summary;").as_deref(),
+            Some("drop materialized view if exists report, summary;")
+        );
+        assert_eq!(
+            edit("create schema app;").as_deref(),
+            Some("create schema if not exists app;")
+        );
+        assert_eq!(
+            edit("DROP CAST (text AS app.code);").as_deref(),
+            Some("DROP CAST IF EXISTS (text AS app.code);")
+        );
+        assert_eq!(
+            edit("drop table text, data;").as_deref(),
+            Some("drop table if exists text, data;")
+        );
+        assert_eq!(
+            edit("drop index concurrently note_idx;").as_deref(),
+            Some("drop index concurrently if exists note_idx;")
+        );
+        assert_eq!(
+            edit("drop text search configuration app.english;").as_deref(),
+            Some("drop text search configuration if exists app.english;")
+        );
+        assert_eq!(edit("create index on note (body);"), None);
+        assert_eq!(edit("drop table if exists note;"), None);
+    }
+
+    #[test]
+    fn test_add_concurrently() {
+        let input = "create index note_idx on note (body);
+begin;
+CREATE INDEX ON note (title);
+commit;";
+        let parse = parse_source(input);
+        let edit = add_concurrently(&parse.cst, &parse.stmts, 0.into()).unwrap();
+        assert_eq!(
+            apply(input, vec![edit]),
+            input.replacen("create index", "create index concurrently", 1)
+        );
+        let in_block = TextSize::try_from(input.find("CREATE").unwrap()).unwrap();
+        assert!(add_concurrently(&parse.cst, &parse.stmts, in_block).is_none());
+    }
+}
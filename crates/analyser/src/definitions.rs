@@ -0,0 +1,237 @@
+//! The tables, views, functions and other objects that statements define, and the references to
+//! them.
+//!
+//! Objects are identified by their qualified names like the symbols of
+//! [`moniker`](crate::moniker), e.g. `public.contact`, and schemas by their name, so that a
+//! reference in one file can be matched with the `CREATE` statement in another one. All overloads
+//! of a function share the identity of its name. A function that is called without a schema may
+//! be defined in any schema, since the search path is not known.
+
+use cstree::syntax::ResolvedNode;
+use cstree::text::{TextRange, TextSize};
+use parser::{RawStmt, SyntaxKind};
+use pg_query::protobuf::Node;
+use pg_query::NodeEnum;
+
+use crate::comments::commented_objects;
+use crate::moniker::{qualified_name, symbol_at, SymbolKind, DEFAULT_SCHEMA};
+use crate::signature_help::function_name_at;
+use crate::utils::string_value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectKind {
+    Table,
+    View,
+    Function,
+    Index,
+    Sequence,
+    /// A composite, enum or range type, or a domain
+    Type,
+    Schema,
+}
+
+/// An object defined by a statement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Definition {
+    /// The qualified name, e.g. `public.contact`
+    pub identifier: String,
+    pub kind: ObjectKind,
+    /// The range of the defining statement
+    pub range: TextRange,
+}
+
+/// A name in the source text that refers to an object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reference {
+    /// A table or view, by its qualified name
+    Relation(String),
+    Function {
+        schema: Option<String>,
+        name: String,
+    },
+}
+
+impl Reference {
+    /// Returns true if `definition` defines the object that this refers to
+    pub fn matches(&self, definition: &Definition) -> bool {
+        match self {
+            Reference::Relation(identifier) => {
+                matches!(definition.kind, ObjectKind::Table | ObjectKind::View)
+                    && definition.identifier == *identifier
+            }
+            Reference::Function { schema, name } => {
+                definition.kind == ObjectKind::Function
+                    && match definition.identifier.split_once('.') {
+                        Some((s, n)) => n == name && schema.iter().all(|schema| s == schema),
+                        None => false,
+                    }
+            }
+        }
+    }
+}
+
+/// Returns the objects that `stmts` define
+pub fn definitions(stmts: &[RawStmt]) -> Vec<Definition> {
+    stmts
+        .iter()
+        .filter_map(|stmt| {
+            let (identifier, kind) = match &stmt.stmt {
+                NodeEnum::CreateStmt(n) => {
+                    (qualified_name(n.relation.as_ref()?), ObjectKind::Table)
+                }
+                NodeEnum::CreateForeignTableStmt(n) => (
+                    qualified_name(n.base_stmt.as_ref()?.relation.as_ref()?),
+                    ObjectKind::Table,
+                ),
+                NodeEnum::ViewStmt(n) => (qualified_name(n.view.as_ref()?), ObjectKind::View),
+                NodeEnum::CreateTableAsStmt(n) => {
+                    let relation = n.into.as_ref()?.rel.as_ref()?;
+                    // ObjectMatview
+                    let kind = if n.objtype == 24 {
+                        ObjectKind::View
+                    } else {
+                        ObjectKind::Table
+                    };
+                    (qualified_name(relation), kind)
+                }
+                NodeEnum::CreateFunctionStmt(n) => {
+                    (qualified_parts(&n.funcname)?, ObjectKind::Function)
+                }
+                NodeEnum::IndexStmt(n) if !n.idxname.is_empty() => {
+                    // an index is always in the schema of its table
+                    let table = qualified_name(n.relation.as_ref()?);
+                    let (schema, _) = table.split_once('.')?;
+                    (format!("{}.{}", schema, n.idxname), ObjectKind::Index)
+                }
+                NodeEnum::CreateSeqStmt(n) => {
+                    (qualified_name(n.sequence.as_ref()?), ObjectKind::Sequence)
+                }
+                NodeEnum::CompositeTypeStmt(n) => {
+                    (qualified_name(n.typevar.as_ref()?), ObjectKind::Type)
+                }
+                NodeEnum::CreateEnumStmt(n) => (qualified_parts(&n.type_name)?, ObjectKind::Type),
+                NodeEnum::CreateRangeStmt(n) => (qualified_parts(&n.type_name)?, ObjectKind::Type),
+                NodeEnum::CreateDomainStmt(n) => {
+                    (qualified_parts(&n.domainname)?, ObjectKind::Type)
+                }
+                NodeEnum::CreateSchemaStmt(n) if !n.schemaname.is_empty() => {
+                    (n.schemaname.clone(), ObjectKind::Schema)
+                }
+                _ => return None,
+            };
+            Some(Definition {
+                identifier,
+                kind,
+                range: stmt.range,
+            })
+        })
+        .collect()
+}
+
+/// Returns the qualified name of an object whose name is given as a list of strings, which is in
+/// the default schema if it is not qualified
+fn qualified_parts(names: &[Node]) -> Option<String> {
+    let names = names.iter().filter_map(string_value).collect::<Vec<_>>();
+    match names.as_slice() {
+        [name] => Some(format!("{}.{}", DEFAULT_SCHEMA, name)),
+        [.., schema, name] => Some(format!("{}.{}", schema, name)),
+        [] => None,
+    }
+}
+
+/// Returns the reference to a table, view or function at `offset`, if any
+pub fn reference_at(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmts: &[RawStmt],
+    offset: TextSize,
+) -> Option<Reference> {
+    if let Some(symbol) = symbol_at(cst, stmts, offset) {
+        return (symbol.kind == SymbolKind::Table)
+            .then_some(Reference::Relation(symbol.identifier));
+    }
+    if let Some(object) = commented_objects(cst, stmts)
+        .into_iter()
+        .find(|object| object.range.contains_inclusive(offset))
+    {
+        return object.reference();
+    }
+    let (_, call) = function_name_at(cst, offset)?;
+    Some(Reference::Function {
+        schema: call.schema,
+        name: call.name,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    fn reference(input: &str) -> Option<Reference> {
+        let offset = TextSize::from(input.find('|').unwrap() as u32);
+        let parse = parse_source(&input.replace('|', ""));
+        reference_at(&parse.cst, &parse.stmts, offset)
+    }
+
+    #[test]
+    fn test_definitions() {
+        let parse = parse_source(
+            "create table app.contact (id int);
+            create view active as select * from app.contact;
+            create materialized view stats as select count(*) from app.contact;
+            create function app.full_name(c app.contact) returns text language sql as 'select 1';
+            create index contact_id_idx on app.contact (id);
+            create sequence app.contact_seq;
+            create type mood as enum ('sad', 'happy');
+            create domain app.email as text;
+            create schema app;
+            select 1;",
+        );
+        assert_eq!(
+            definitions(&parse.stmts)
+                .into_iter()
+                .map(|d| (d.identifier, d.kind))
+                .collect::<Vec<_>>(),
+            vec![
+                ("app.contact".to_string(), ObjectKind::Table),
+                ("public.active".to_string(), ObjectKind::View),
+                ("public.stats".to_string(), ObjectKind::View),
+                ("app.full_name".to_string(), ObjectKind::Function),
+                ("app.contact_id_idx".to_string(), ObjectKind::Index),
+                ("app.contact_seq".to_string(), ObjectKind::Sequence),
+                ("public.mood".to_string(), ObjectKind::Type),
+                ("app.email".to_string(), ObjectKind::Type),
+                ("app".to_string(), ObjectKind::Schema),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reference_at() {
+        assert_eq!(
+            reference("select * from app.con|tact;"),
+            Some(Reference::Relation("app.contact".to_string()))
+        );
+        let call = reference("select full_na|me(c) from app.contact c;").unwrap();
+        assert_eq!(
+            call,
+            Reference::Function {
+                schema: None,
+                name: "full_name".to_string()
+            }
+        );
+        let function = Definition {
+            identifier: "app.full_name".to_string(),
+            kind: ObjectKind::Function,
+            range: TextRange::default(),
+        };
+        assert!(call.matches(&function));
+        assert!(!Reference::Relation("app.full_name".to_string()).matches(&function));
+        assert_eq!(reference("select i|d from app.contact;"), None);
+        assert_eq!(
+            reference("comment on view app.act|ive is null;"),
+            Some(Reference::Relation("app.active".to_string()))
+        );
+    }
+}
@@ -0,0 +1,305 @@
+//! Mapping of errors reported by a live database back to the source text.
+//!
+//! When a statement fails, the server reports a SQLSTATE, a message and optionally a detail, a
+//! hint and the position of the error within the statement. The position is a 1-based character
+//! index into the text that has been sent, which is the text of the statement within the source.
+//! It is mapped to the token it points at, so the diagnostic marks e.g. the misspelled relation
+//! name instead of the entire statement. Queries that a cursor can be declared for can be fetched
+//! in pages.
+
+use std::collections::BTreeMap;
+
+use cstree::text::{TextRange, TextSize};
+use parser::RawStmt;
+use pg_query::NodeEnum;
+
+use crate::lint::{LintDiagnostic, Severity};
+
+const EXECUTION_ERROR: &str = "execution-error";
+
+/// An error reported by the server while executing a statement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerError {
+    /// The five character SQLSTATE, e.g. `42P01`
+    pub code: String,
+    pub message: String,
+    pub detail: Option<String>,
+    pub hint: Option<String>,
+    pub position: Option<ErrorPosition>,
+}
+
+/// The position of a server error
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorPosition {
+    /// A 1-based character index into the statement that has been sent
+    Original(u32),
+    /// A 1-based character index into a query that the server generated internally, e.g. the body
+    /// of a function
+    Internal { position: u32, query: String },
+}
+
+/// Configures the severity of server errors by their SQLSTATE
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SqlStateMapping {
+    /// The severity of SQLSTATEs, keyed by either a full SQLSTATE or its two character class.
+    /// Full SQLSTATEs take precedence over classes.
+    pub severities: BTreeMap<String, Severity>,
+}
+
+impl Default for SqlStateMapping {
+    fn default() -> Self {
+        Self {
+            severities: BTreeMap::from([
+                // successful completion
+                ("00".to_string(), Severity::Information),
+                // warning
+                ("01".to_string(), Severity::Warning),
+                // no data
+                ("02".to_string(), Severity::Information),
+            ]),
+        }
+    }
+}
+
+impl SqlStateMapping {
+    /// Returns the severity of `code`. Anything that is not configured is an error.
+    pub fn severity(&self, code: &str) -> Severity {
+        self.severities
+            .get(code)
+            .or_else(|| code.get(..2).and_then(|class| self.severities.get(class)))
+            .copied()
+            .unwrap_or(Severity::Error)
+    }
+}
+
+/// Returns the name of the class of the SQLSTATE `code`, as listed in Appendix A of the Postgres
+/// documentation
+pub fn sqlstate_class(code: &str) -> Option<&'static str> {
+    let class = match code.get(..2)? {
+        "00" => "successful completion",
+        "01" => "warning",
+        "02" => "no data",
+        "03" => "SQL statement not yet complete",
+        "08" => "connection exception",
+        "09" => "triggered action exception",
+        "0A" => "feature not supported",
+        "0B" => "invalid transaction initiation",
+        "0F" => "locator exception",
+        "0L" => "invalid grantor",
+        "0P" => "invalid role specification",
+        "0Z" => "diagnostics exception",
+        "20" => "case not found",
+        "21" => "cardinality violation",
+        "22" => "data exception",
+        "23" => "integrity constraint violation",
+        "24" => "invalid cursor state",
+        "25" => "invalid transaction state",
+        "26" => "invalid SQL statement name",
+        "27" => "triggered data change violation",
+        "28" => "invalid authorization specification",
+        "2B" => "dependent privilege descriptors still exist",
+        "2D" => "invalid transaction termination",
+        "2F" => "SQL routine exception",
+        "34" => "invalid cursor name",
+        "38" => "external routine exception",
+        "39" => "external routine invocation exception",
+        "3B" => "savepoint exception",
+        "3D" => "invalid catalog name",
+        "3F" => "invalid schema name",
+        "40" => "transaction rollback",
+        "42" => "syntax error or access rule violation",
+        "44" => "WITH CHECK OPTION violation",
+        "53" => "insufficient resources",
+        "54" => "program limit exceeded",
+        "55" => "object not in prerequisite state",
+        "57" => "operator intervention",
+        "58" => "system error",
+        "72" => "snapshot failure",
+        "F0" => "configuration file error",
+        "HV" => "foreign data wrapper error",
+        "P0" => "PL/pgSQL error",
+        "XX" => "internal error",
+        _ => return None,
+    };
+    Some(class)
+}
+
+/// Converts `error`, which the server reported for `stmt`, into a diagnostic
+///
+/// `source` is the text that `stmt` has been parsed from.
+pub fn execution_error_diagnostic(
+    stmt: &RawStmt,
+    source: &str,
+    error: &ServerError,
+    mapping: &SqlStateMapping,
+) -> LintDiagnostic {
+    let stmt_text = &source[stmt.range];
+
+    let mut message = match sqlstate_class(&error.code) {
+        Some(class) => format!("{} (SQLSTATE {}, {})", error.message, error.code, class),
+        None => format!("{} (SQLSTATE {})", error.message, error.code),
+    };
+    if let Some(detail) = &error.detail {
+        message.push_str(&format!("\nDETAIL: {}", detail));
+    }
+    if let Some(hint) = &error.hint {
+        message.push_str(&format!("\nHINT: {}", hint));
+    }
+
+    let range = match &error.position {
+        Some(ErrorPosition::Original(position)) => {
+            token_range_at(stmt_text, *position).map(|r| r + stmt.range.start())
+        }
+        Some(ErrorPosition::Internal { position, query }) => {
+            message.push_str(&format!(
+                "\nQUERY: {}\nat character {} of the query",
+                query, position
+            ));
+            None
+        }
+        None => None,
+    };
+
+    LintDiagnostic {
+        rule: EXECUTION_ERROR,
+        message,
+        severity: mapping.severity(&error.code),
+        range: range.unwrap_or(stmt.range),
+    }
+}
+
+/// Returns true for the statements that a cursor can be declared for, which can be fetched in
+/// pages
+pub fn is_cursor_query(stmt: &NodeEnum) -> bool {
+    match stmt {
+        NodeEnum::SelectStmt(n) => n.into_clause.is_none(),
+        _ => false,
+    }
+}
+
+/// Returns the range of the token that starts at the 1-based character `position` of `text`,
+/// relative to the start of `text`
+fn token_range_at(text: &str, position: u32) -> Option<TextRange> {
+    let start = text
+        .char_indices()
+        .nth(usize::try_from(position).ok()?.checked_sub(1)?)?
+        .0;
+    let rest = &text[start..];
+
+    let len = match rest.chars().next()? {
+        // a quoted identifier or string, including the closing quote
+        quote @ ('"' | '\'') => {
+            let mut chars = rest.char_indices().skip(1).peekable();
+            let mut end = rest.len();
+            while let Some((idx, c)) = chars.next() {
+                if c == quote {
+                    // a doubled quote is an escaped quote
+                    if chars.peek().map(|(_, c)| *c) == Some(quote) {
+                        chars.next();
+                    } else {
+                        end = idx + 1;
+                        break;
+                    }
+                }
+            }
+            end
+        }
+        c if is_word_char(c) => rest.find(|c: char| !is_word_char(c)).unwrap_or(rest.len()),
+        c => c.len_utf8(),
+    };
+
+    Some(TextRange::at(
+        TextSize::try_from(start).ok()?,
+        TextSize::try_from(len).ok()?,
+    ))
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    fn error(code: &str, position: Option<ErrorPosition>) -> ServerError {
+        ServerError {
+            code: code.to_string(),
+            message: "relation \"contacts\" does not exist".to_string(),
+            detail: None,
+            hint: None,
+            position,
+        }
+    }
+
+    #[test]
+    fn test_position_to_range() {
+        let source = "select 1;\nselect * from contacts where id = 1;";
+        let stmts = parse_source(source).stmts;
+        let d = execution_error_diagnostic(
+            &stmts[1],
+            source,
+            &error("42P01", Some(ErrorPosition::Original(15))),
+            &SqlStateMapping::default(),
+        );
+        assert_eq!(&source[d.range], "contacts");
+        assert_eq!(d.severity, Severity::Error);
+        assert_eq!(
+            d.message,
+            "relation \"contacts\" does not exist (SQLSTATE 42P01, syntax error or access rule violation)"
+        );
+    }
+
+    #[test]
+    fn test_quoted_and_multibyte() {
+        let text = "select 'ä' from \"my \"\"table\"\"\" t";
+        let range = token_range_at(text, 17).unwrap();
+        assert_eq!(&text[range], "\"my \"\"table\"\"\"");
+        let range = token_range_at(text, 8).unwrap();
+        assert_eq!(&text[range], "'ä'");
+        assert_eq!(token_range_at(text, 0), None);
+        assert_eq!(token_range_at(text, 100), None);
+    }
+
+    #[test]
+    fn test_detail_hint_and_mapping() {
+        let source = "insert into t values (1);";
+        let stmts = parse_source(source).stmts;
+        let mut e = error("23505", None);
+        e.message = "duplicate key value violates unique constraint \"t_pkey\"".to_string();
+        e.detail = Some("Key (id)=(1) already exists.".to_string());
+        e.hint = Some("Use ON CONFLICT.".to_string());
+
+        let mut mapping = SqlStateMapping::default();
+        mapping
+            .severities
+            .insert("23".to_string(), Severity::Warning);
+        let d = execution_error_diagnostic(&stmts[0], source, &e, &mapping);
+
+        assert_eq!(d.range, stmts[0].range);
+        assert_eq!(d.severity, Severity::Warning);
+        assert!(d
+            .message
+            .ends_with("\nDETAIL: Key (id)=(1) already exists.\nHINT: Use ON CONFLICT."));
+
+        mapping
+            .severities
+            .insert("23505".to_string(), Severity::Hint);
+        assert_eq!(mapping.severity("23505"), Severity::Hint);
+        assert_eq!(mapping.severity("42P01"), Severity::Error);
+        assert_eq!(mapping.severity("01000"), Severity::Warning);
+    }
+
+    #[test]
+    fn test_is_cursor_query() {
+        let stmts =
+            parse_source("select 1; values (1); select 1 into t; insert into t values (1);").stmts;
+        let queries = stmts
+            .iter()
+            .map(|stmt| is_cursor_query(&stmt.stmt))
+            .collect::<Vec<_>>();
+        assert_eq!(queries, [true, true, false, false]);
+    }
+}
@@ -0,0 +1,318 @@
+//! Advice on the fillfactor of tables that the queries of a workspace update heavily.
+//!
+//! An update writes a new version of the row and leaves the old one behind as a dead row. If the
+//! new version fits on the page of the old one and the update changes no indexed column, it is a
+//! HOT (heap-only tuple) update: the indexes are not touched, and the dead row is pruned from the
+//! page without waiting for vacuum. Tables have no room left on their pages with the default
+//! fillfactor of 100, so a lower fillfactor makes HOT updates more likely. Tables that at least
+//! [`MIN_UPDATES`] `UPDATE` statements of the workspace update count as heavily updated. Their
+//! updates get a hint to lower the fillfactor if they can be HOT updates, and a hint that they
+//! cannot if they change a column that an index refers to. The indexes and fillfactors are taken
+//! from the schema model. Since Postgres 16, BRIN indexes summarize pages and no longer prevent
+//! HOT updates, so their columns only count for older versions.
+//!
+//! The advice is given by the lint rules [`FILLFACTOR_RULE`] and [`NON_HOT_UPDATE_RULE`], so that
+//! it can be turned off like any other rule. They need the schemas and the `UPDATE` statements of
+//! the workspace, which the other rules do not get, so they are checked by [`check_fillfactor`]
+//! instead of their `check`.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use cstree::syntax::ResolvedNode;
+use cstree::text::{TextRange, TextSize};
+use parser::{PgVersion, RawStmt, StmtKind, SyntaxKind};
+use pg_query::protobuf::{RangeVar, UpdateStmt};
+use pg_query::NodeEnum;
+
+use crate::foreign_key_index::{constraints, PRIMARY_KEY, UNIQUE};
+use crate::lint::{LintConfig, LintContext, LintDiagnostic, Rule, RuleGroup, Severity};
+use crate::moniker::{qualified_name, DEFAULT_SCHEMA};
+use crate::schema::{Schema, Table};
+use crate::utils::{descendants, string_value};
+
+pub const FILLFACTOR: &str = "fillfactor";
+pub const NON_HOT_UPDATE: &str = "non-hot-update";
+
+pub const FILLFACTOR_RULE: Rule = Rule {
+    name: FILLFACTOR,
+    description: "Heavily updated tables need room on their pages for HOT updates",
+    group: RuleGroup::Recommended,
+    severity: Severity::Hint,
+    stmt_kinds: &[StmtKind::Dml],
+    check,
+    fix: None,
+};
+
+pub const NON_HOT_UPDATE_RULE: Rule = Rule {
+    name: NON_HOT_UPDATE,
+    description: "Updates of indexed columns cannot be HOT updates",
+    group: RuleGroup::Recommended,
+    severity: Severity::Hint,
+    stmt_kinds: &[StmtKind::Dml],
+    check,
+    fix: None,
+};
+
+/// The number of `UPDATE` statements from which a table counts as heavily updated
+pub const MIN_UPDATES: usize = 3;
+
+/// The fillfactor that the advice suggests
+const SUGGESTED_FILLFACTOR: u32 = 90;
+
+/// Returns the qualified name of the table of every `UPDATE` in `stmts`, once per statement
+pub fn updated_tables(stmts: &[RawStmt]) -> Vec<String> {
+    stmts
+        .iter()
+        .flat_map(|stmt| updates(&stmt.stmt))
+        .filter_map(|update| update.relation.as_ref().map(|r| qualified_name(r)))
+        .collect()
+}
+
+/// Returns true if `config` enables one of the rules of the advice, so that the schemas are only
+/// built for the advice if it is given
+pub fn is_enabled(config: &LintConfig) -> bool {
+    config.is_enabled(&FILLFACTOR_RULE) || config.is_enabled(&NON_HOT_UPDATE_RULE)
+}
+
+/// Returns an advice of the rules that `config` enables for every `UPDATE` in `stmts` of a table
+/// that the workspace updates heavily, given the number of `UPDATE` statements of every table in
+/// the workspace and the tables of `schemas` in the targeted `version` of Postgres
+pub fn check_fillfactor(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmts: &[RawStmt],
+    schemas: &BTreeMap<String, Schema>,
+    update_counts: &BTreeMap<String, usize>,
+    config: &LintConfig,
+    version: PgVersion,
+) -> Vec<LintDiagnostic> {
+    let mut diagnostics = Vec::new();
+    // the index definitions of a table are parsed once for all of its updates
+    let mut indexed_by_table = BTreeMap::new();
+    for stmt in stmts {
+        for update in updates(&stmt.stmt) {
+            let Some(relation) = &update.relation else {
+                continue;
+            };
+            let name = qualified_name(relation);
+            let count = update_counts.get(&name).copied().unwrap_or_default();
+            if count < MIN_UPDATES {
+                continue;
+            }
+            let Some(table) = table(relation, schemas) else {
+                continue;
+            };
+            let indexed = indexed_by_table
+                .entry(name.clone())
+                .or_insert_with(|| indexed_columns(table, version));
+            let changed =
+                update
+                    .target_list
+                    .iter()
+                    .find_map(|target| match target.node.as_ref()? {
+                        NodeEnum::ResTarget(t) if indexed.contains(&t.name) => {
+                            Some((t.name.clone(), t.location))
+                        }
+                        _ => None,
+                    });
+            let (rule, message, location) = match changed {
+                Some((column, location)) => (
+                    &NON_HOT_UPDATE_RULE,
+                    format!(
+                        "The indexed column {} of {} is changed, so this update cannot be a HOT \
+                         update and adds an entry to every index of the table.",
+                        column, name
+                    ),
+                    location,
+                ),
+                None if table.fillfactor.unwrap_or(100) >= 100 => (
+                    &FILLFACTOR_RULE,
+                    format!(
+                        "{} is updated by {} statements of the workspace, and this one can be a \
+                         HOT update. Lower its fillfactor from 100 to leave room for the new row \
+                         versions on the same page, e.g. with ALTER TABLE {} SET (fillfactor = {}).",
+                        name, count, name, SUGGESTED_FILLFACTOR
+                    ),
+                    relation.location,
+                ),
+                None => continue,
+            };
+            if !config.is_enabled(rule) {
+                continue;
+            }
+            let start = stmt.range.start() + TextSize::from(location.max(0) as u32);
+            let range = cst
+                .descendants_with_tokens()
+                .filter_map(|element| element.into_token())
+                .find(|token| token.text_range().start() == start)
+                .map_or(TextRange::empty(start), |token| token.text_range());
+            diagnostics.push(LintDiagnostic {
+                rule: rule.name,
+                message,
+                severity: rule.severity,
+                range,
+            });
+        }
+    }
+    diagnostics
+}
+
+/// Reports nothing, since the rules are checked by [`check_fillfactor`]
+fn check(_: &mut LintContext<'_>) {}
+
+/// Returns the `UPDATE`s within `stmt`, including those of common table expressions
+fn updates(stmt: &NodeEnum) -> Vec<UpdateStmt> {
+    descendants(stmt)
+        .into_iter()
+        .filter_map(|node| match node {
+            NodeEnum::UpdateStmt(n) => Some(n),
+            _ => None,
+        })
+        .collect()
+}
+
+fn table<'a>(relation: &RangeVar, schemas: &'a BTreeMap<String, Schema>) -> Option<&'a Table> {
+    let schema = if relation.schemaname.is_empty() {
+        DEFAULT_SCHEMA
+    } else {
+        &relation.schemaname
+    };
+    schemas.get(schema)?.table(&relation.relname)
+}
+
+/// Returns the columns that an index of `table` refers to, in its keys, expressions, `INCLUDE`
+/// columns or predicate, including the indexes of primary key and unique constraints, without
+/// those of BRIN indexes since Postgres 16
+fn indexed_columns(table: &Table, version: PgVersion) -> BTreeSet<String> {
+    let summarizing = version >= PgVersion::new(16);
+    let mut columns = BTreeSet::new();
+    for definition in table.indexes.values() {
+        let stmt = pg_query::parse(definition)
+            .ok()
+            .and_then(|result| result.protobuf.stmts.into_iter().next())
+            .and_then(|stmt| stmt.stmt)
+            .and_then(|stmt| stmt.node);
+        let Some(stmt @ NodeEnum::IndexStmt(_)) = stmt else {
+            continue;
+        };
+        if summarizing && matches!(&stmt, NodeEnum::IndexStmt(i) if i.access_method == "brin") {
+            continue;
+        }
+        for node in descendants(&stmt) {
+            match node {
+                NodeEnum::IndexElem(elem) if !elem.name.is_empty() => {
+                    columns.insert(elem.name);
+                }
+                NodeEnum::ColumnRef(c) => {
+                    columns.extend(c.fields.last().and_then(string_value).map(str::to_string));
+                }
+                _ => {}
+            }
+        }
+    }
+    columns.extend(
+        constraints(table)
+            .into_iter()
+            .filter(|(_, kind, _)| matches!(*kind, PRIMARY_KEY | UNIQUE))
+            .flat_map(|(_, _, columns)| columns),
+    );
+    columns
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+    use crate::migrations::MigrationState;
+
+    fn advice(queries: &str) -> Vec<(String, &'static str)> {
+        advice_with(queries, &LintConfig::default(), PgVersion::PARSER)
+    }
+
+    fn advice_with(
+        queries: &str,
+        config: &LintConfig,
+        version: PgVersion,
+    ) -> Vec<(String, &'static str)> {
+        let input = format!(
+            "create table contact (id int primary key, email text, name text, visits int);
+            create index contact_email on contact (lower(email));
+            create index contact_visits on contact using brin (visits);
+            create table session (id int, seen_at timestamptz) with (fillfactor = 80);
+            {}",
+            queries
+        );
+        let parse = parse_source(&input);
+        let mut state = MigrationState::default();
+        state.replay(&parse.stmts);
+        let mut update_counts = BTreeMap::new();
+        for table in updated_tables(&parse.stmts) {
+            *update_counts.entry(table).or_default() += 1;
+        }
+        check_fillfactor(
+            &parse.cst,
+            &parse.stmts,
+            &state.schemas,
+            &update_counts,
+            config,
+            version,
+        )
+        .into_iter()
+        .map(|d| (input[d.range].to_string(), d.rule))
+        .collect()
+    }
+
+    #[test]
+    fn test_fillfactor() {
+        let queries = "update contact set visits = visits + 1 where id = $1;
+            update contact set name = $2 where id = $1;
+            update contact set email = $2 where id = $1;";
+        assert_eq!(
+            advice(queries),
+            vec![
+                ("visits".to_string(), NON_HOT_UPDATE),
+                ("contact".to_string(), FILLFACTOR),
+                ("email".to_string(), NON_HOT_UPDATE),
+            ]
+        );
+        // the BRIN index on visits does not prevent HOT updates since Postgres 16
+        assert_eq!(
+            advice_with(queries, &LintConfig::default(), PgVersion::new(16)),
+            vec![
+                ("contact".to_string(), FILLFACTOR),
+                ("contact".to_string(), FILLFACTOR),
+                ("email".to_string(), NON_HOT_UPDATE),
+            ]
+        );
+        // the rules are turned off like the other lint rules
+        let config = LintConfig {
+            disabled_rules: vec![FILLFACTOR.to_string()],
+            ..LintConfig::default()
+        };
+        assert_eq!(
+            advice_with(queries, &config, PgVersion::PARSER),
+            vec![
+                ("visits".to_string(), NON_HOT_UPDATE),
+                ("email".to_string(), NON_HOT_UPDATE),
+            ]
+        );
+        assert!(is_enabled(&config));
+        assert!(!is_enabled(&LintConfig {
+            disabled_rules: vec![FILLFACTOR.to_string(), NON_HOT_UPDATE.to_string()],
+            ..LintConfig::default()
+        }));
+    }
+
+    #[test]
+    fn test_fillfactor_without_advice() {
+        // too few updates, and a table with a fillfactor
+        assert!(advice(
+            "update contact set visits = 1;
+            update contact set name = $1;
+            update session set seen_at = now() where id = $1;
+            update session set seen_at = now() where id = $1;
+            update session set seen_at = now() where id = $1;"
+        )
+        .is_empty());
+    }
+}
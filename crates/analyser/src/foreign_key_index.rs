@@ -0,0 +1,352 @@
+//! Foreign keys whose columns have no index.
+//!
+//! Postgres does not index the referencing columns of a foreign key. Without an index, every
+//! delete from the referenced table and every update of its key scans the referencing table, and
+//! joins along the foreign key cannot use an index either. A foreign key counts as indexed if its
+//! columns are the leading columns of an index or of a primary key or unique constraint, in any
+//! order. The fix creates the index concurrently, so that it does not block writes to a table
+//! that is already in use, unless the foreign key is defined within `BEGIN` and `COMMIT`, where
+//! indexes cannot be created concurrently.
+
+use cstree::text::{TextRange, TextSize};
+use parser::make::quote_ident;
+use parser::RawStmt;
+use pg_query::protobuf::{Constraint, RangeVar};
+use pg_query::NodeEnum;
+
+use crate::lint::{LintDiagnostic, Severity};
+use crate::migrations::{alter_table_cmds, MigrationState};
+use crate::moniker::DEFAULT_SCHEMA;
+use crate::schema::Table;
+use crate::utils::string_value;
+
+const FOREIGN_KEY_WITHOUT_INDEX: &str = "foreign-key-without-index";
+
+/// `CONSTR_PRIMARY`, `CONSTR_UNIQUE` and `CONSTR_FOREIGN`
+pub(crate) const PRIMARY_KEY: i32 = 7;
+pub(crate) const UNIQUE: i32 = 8;
+const FOREIGN_KEY: i32 = 10;
+
+/// The maximum length of an identifier in bytes, `NAMEDATALEN - 1`, beyond which Postgres
+/// truncates it
+const MAX_IDENTIFIER_LEN: usize = 63;
+
+/// A foreign key that a statement defines without an index on its columns
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnindexedForeignKey {
+    /// The schema and name of the referencing table
+    pub schema: String,
+    pub table: String,
+    pub columns: Vec<String>,
+    /// The start of the constraint
+    pub range: TextRange,
+    /// The range of the statement that defines the foreign key
+    pub stmt_range: TextRange,
+    /// True if the statement runs within `BEGIN` and `COMMIT`
+    pub in_transaction: bool,
+}
+
+impl UnindexedForeignKey {
+    pub fn diagnostic(&self) -> LintDiagnostic {
+        LintDiagnostic {
+            rule: FOREIGN_KEY_WITHOUT_INDEX,
+            message: format!(
+                "no index on the foreign key columns ({}) of {}, deletes from the referenced table scan it",
+                self.columns.join(", "),
+                self.table
+            ),
+            severity: Severity::Warning,
+            range: self.range,
+        }
+    }
+
+    /// Returns the statement that creates the missing index
+    pub fn create_index(&self) -> String {
+        let columns = self
+            .columns
+            .iter()
+            .map(|c| quote_ident(c))
+            .collect::<Vec<_>>();
+        let table = if self.schema == DEFAULT_SCHEMA {
+            quote_ident(&self.table)
+        } else {
+            format!("{}.{}", quote_ident(&self.schema), quote_ident(&self.table))
+        };
+        let concurrently = if self.in_transaction {
+            ""
+        } else {
+            " CONCURRENTLY"
+        };
+        let name = format!("{}_{}_idx", self.table, self.columns.join("_"));
+        format!(
+            "CREATE INDEX{} {} ON {} ({});",
+            concurrently,
+            quote_ident(truncate_identifier(&name)),
+            table,
+            columns.join(", ")
+        )
+    }
+}
+
+/// Returns the longest prefix of `name` that Postgres keeps of an identifier
+fn truncate_identifier(name: &str) -> &str {
+    let mut len = name.len().min(MAX_IDENTIFIER_LEN);
+    while !name.is_char_boundary(len) {
+        len -= 1;
+    }
+    &name[..len]
+}
+
+/// Returns the foreign keys that `stmts` define and that have no index in `state`, which is the
+/// schema after all statements have been applied, so that indexes created later count as well
+pub fn unindexed_foreign_keys(
+    stmts: &[RawStmt],
+    state: &MigrationState,
+) -> Vec<UnindexedForeignKey> {
+    let mut foreign_keys = Vec::new();
+    let mut in_transaction = false;
+    for stmt in stmts {
+        let (relation, elements) = match &stmt.stmt {
+            // TransStmtBegin, TransStmtStart
+            NodeEnum::TransactionStmt(n) if n.kind == 1 || n.kind == 2 => {
+                in_transaction = true;
+                continue;
+            }
+            // TransStmtCommit, TransStmtRollback, TransStmtPrepare
+            NodeEnum::TransactionStmt(n) if matches!(n.kind, 3 | 4 | 8) => {
+                in_transaction = false;
+                continue;
+            }
+            NodeEnum::CreateStmt(n) => {
+                (n.relation.as_ref(), n.table_elts.iter().collect::<Vec<_>>())
+            }
+            NodeEnum::AlterTableStmt(n) => (
+                n.relation.as_ref(),
+                alter_table_cmds(n)
+                    .filter_map(|cmd| cmd.def.as_deref())
+                    .collect::<Vec<_>>(),
+            ),
+            _ => continue,
+        };
+        // constraints of a column have no column list of their own
+        let mut constraints = Vec::new();
+        for element in elements {
+            match element.node.as_ref() {
+                Some(NodeEnum::ColumnDef(c)) => {
+                    constraints.extend(c.constraints.iter().filter_map(|node| {
+                        match node.node.as_ref()? {
+                            NodeEnum::Constraint(x) => Some((x, Some(c.colname.as_str()))),
+                            _ => None,
+                        }
+                    }))
+                }
+                Some(NodeEnum::Constraint(x)) => constraints.push((x, None)),
+                _ => {}
+            }
+        }
+        let Some(relation) = relation else {
+            continue;
+        };
+        let Some(table) = table(state, relation) else {
+            continue;
+        };
+        for (constraint, column) in constraints {
+            if constraint.contype != FOREIGN_KEY {
+                continue;
+            }
+            let columns = match column {
+                Some(column) => vec![column.to_string()],
+                None => constraint
+                    .fk_attrs
+                    .iter()
+                    .filter_map(string_value)
+                    .map(str::to_string)
+                    .collect(),
+            };
+            if columns.is_empty() || is_indexed(table, &columns) {
+                continue;
+            }
+            let range = if constraint.location >= 0 {
+                TextRange::empty(stmt.range.start() + TextSize::from(constraint.location as u32))
+            } else {
+                stmt.range
+            };
+            foreign_keys.push(UnindexedForeignKey {
+                schema: schema_name(relation).to_string(),
+                table: relation.relname.clone(),
+                columns,
+                range,
+                stmt_range: stmt.range,
+                in_transaction,
+            });
+        }
+    }
+    foreign_keys
+}
+
+fn schema_name(relation: &RangeVar) -> &str {
+    if relation.schemaname.is_empty() {
+        DEFAULT_SCHEMA
+    } else {
+        &relation.schemaname
+    }
+}
+
+fn table<'a>(state: &'a MigrationState, relation: &RangeVar) -> Option<&'a Table> {
+    state
+        .schemas
+        .get(schema_name(relation))?
+        .table(&relation.relname)
+}
+
+/// Returns the constraints of `table` with their name, kind and columns
+pub(crate) fn constraints(table: &Table) -> Vec<(&str, i32, Vec<String>)> {
+    table
+        .constraints
+        .iter()
+        .filter_map(|(name, definition)| {
+            let (kind, columns) = constraint(definition)?;
+            Some((name.as_str(), kind, columns))
+        })
+        .collect()
+}
+
+/// Returns true if the `columns` are the leading columns of an index of `table`, including those
+/// of its primary key and unique constraints
+pub(crate) fn is_indexed(table: &Table, columns: &[String]) -> bool {
+    let indexed = table
+        .indexes
+        .values()
+        .filter_map(|definition| index_columns(definition))
+        .chain(
+            constraints(table)
+                .into_iter()
+                .filter(|(_, kind, _)| matches!(*kind, PRIMARY_KEY | UNIQUE))
+                .map(|(_, _, columns)| columns),
+        )
+        .collect::<Vec<_>>();
+    indexed.iter().any(|index| {
+        index.len() >= columns.len() && columns.iter().all(|c| index[..columns.len()].contains(c))
+    })
+}
+
+/// Returns the names and columns of the foreign keys of `table` that have no index
+pub(crate) fn table_unindexed_foreign_keys(table: &Table) -> Vec<(&str, Vec<String>)> {
+    constraints(table)
+        .into_iter()
+        .filter(|(_, kind, columns)| *kind == FOREIGN_KEY && !is_indexed(table, columns))
+        .map(|(name, _, columns)| (name, columns))
+        .collect()
+}
+
+/// Returns the kind and the columns of the constraint with the `definition`, e.g.
+/// `FOREIGN KEY (contact_id) REFERENCES contact(id)`
+fn constraint(definition: &str) -> Option<(i32, Vec<String>)> {
+    let stmt = pg_query::parse(&format!("ALTER TABLE t ADD {}", definition))
+        .ok()?
+        .protobuf
+        .stmts
+        .into_iter()
+        .next()?
+        .stmt?
+        .node?;
+    let NodeEnum::AlterTableStmt(n) = stmt else {
+        return None;
+    };
+    alter_table_cmds(&n).find_map(|cmd| match cmd.def.as_ref()?.node.as_ref()? {
+        NodeEnum::Constraint(c) => Some((c.contype, constraint_columns(c))),
+        _ => None,
+    })
+}
+
+fn constraint_columns(c: &Constraint) -> Vec<String> {
+    let columns = if c.contype == FOREIGN_KEY {
+        &c.fk_attrs
+    } else {
+        &c.keys
+    };
+    columns
+        .iter()
+        .filter_map(string_value)
+        .map(str::to_string)
+        .collect()
+}
+
+/// Returns the columns of an index in their order, or `None` if it has expressions
+fn index_columns(definition: &str) -> Option<Vec<String>> {
+    let stmt = pg_query::parse(definition)
+        .ok()?
+        .protobuf
+        .stmts
+        .into_iter()
+        .next()?
+        .stmt?
+        .node?;
+    let NodeEnum::IndexStmt(index) = stmt else {
+        return None;
+    };
+    index
+        .index_params
+        .iter()
+        .map(|param| match param.node.as_ref()? {
+            NodeEnum::IndexElem(elem) if !elem.name.is_empty() => Some(elem.name.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    #[test]
+    fn test_unindexed_foreign_keys() {
+        let input = "create table contact (id int primary key);
+            create table orders (id int, contact_id int references contact (id));
+            create table note (contact_id int, foreign key (contact_id) references contact (id));
+            create index on note (contact_id);
+            alter table orders add column owner_id int references contact (id);";
+        let parse = parse_source(input);
+        let mut state = MigrationState::default();
+        state.replay(&parse.stmts);
+        let foreign_keys = unindexed_foreign_keys(&parse.stmts, &state);
+        assert_eq!(
+            foreign_keys
+                .iter()
+                .map(|fk| (fk.table.as_str(), fk.columns.join(", ")))
+                .collect::<Vec<_>>(),
+            vec![
+                ("orders", "contact_id".to_string()),
+                ("orders", "owner_id".to_string())
+            ]
+        );
+        assert!(input[usize::from(foreign_keys[0].range.start())..].starts_with("references"));
+        assert_eq!(
+            foreign_keys[0].create_index(),
+            "CREATE INDEX CONCURRENTLY orders_contact_id_idx ON orders (contact_id);"
+        );
+    }
+
+    #[test]
+    fn test_create_index_in_transaction() {
+        let input = "create table contact (id int primary key);
+            begin;
+            create table customer_relationship_management_entries (
+                customer_relationship_management_contact_id int references contact (id)
+            );
+            commit;";
+        let parse = parse_source(input);
+        let mut state = MigrationState::default();
+        state.replay(&parse.stmts);
+        let foreign_keys = unindexed_foreign_keys(&parse.stmts, &state);
+        assert_eq!(
+            foreign_keys[0].create_index(),
+            "CREATE INDEX customer_relationship_management_entries_customer_relationship_ \
+             ON customer_relationship_management_entries \
+             (customer_relationship_management_contact_id);"
+        );
+        assert_eq!(truncate_identifier(&"ü".repeat(40)).len(), 62);
+    }
+}
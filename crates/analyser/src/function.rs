@@ -0,0 +1,142 @@
+/// Query to load the functions, aggregates and window functions of the schemas given as a
+/// `text[]` in `$1`, together with the built-in ones of `pg_catalog`.
+///
+/// Functions that can only be called by the server, e.g. triggers, are left out. Every row can be
+/// converted into a [`Function`].
+pub const FUNCTIONS_QUERY: &str = "select
+    n.nspname as schema_name,
+    p.proname as function_name,
+    pg_catalog.pg_get_function_identity_arguments(p.oid) as arguments,
+    pg_catalog.pg_get_function_result(p.oid) as result_type,
+    p.prokind = 'a' as is_aggregate,
+    pg_catalog.obj_description(p.oid, 'pg_proc') as comment
+from pg_catalog.pg_proc p
+    join pg_catalog.pg_namespace n on n.oid = p.pronamespace
+where (n.nspname = any($1) or n.nspname = 'pg_catalog')
+    and p.prokind in ('f', 'a', 'w')
+    and p.prorettype not in (
+        'pg_catalog.trigger'::pg_catalog.regtype::oid,
+        'pg_catalog.event_trigger'::pg_catalog.regtype::oid,
+        'pg_catalog.internal'::pg_catalog.regtype::oid
+    )
+    and not 'pg_catalog.internal'::pg_catalog.regtype::oid = any(p.proargtypes)
+order by n.nspname, p.proname, 3";
+
+/// Types whose name consists of several words, which an argument without a name can start with
+const MULTI_WORD_TYPES: &[&str] = &[
+    "bit varying",
+    "character varying",
+    "double precision",
+    "time with",
+    "time without",
+    "timestamp with",
+    "timestamp without",
+];
+
+/// A row returned by [`FUNCTIONS_QUERY`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub schema_name: String,
+    pub name: String,
+    /// The arguments as in a signature, e.g. `a integer, VARIADIC b text[]`
+    pub arguments: String,
+    pub result_type: String,
+    pub is_aggregate: bool,
+    /// The comment set with `COMMENT ON FUNCTION`
+    pub comment: Option<String>,
+}
+
+impl Function {
+    /// Returns the arguments of the signature one by one
+    pub fn argument_list(&self) -> Vec<&str> {
+        self.arguments
+            .split(',')
+            .map(str::trim)
+            .filter(|a| !a.is_empty())
+            .collect()
+    }
+
+    /// Returns true if the last argument takes any number of values
+    pub fn is_variadic(&self) -> bool {
+        self.argument_list()
+            .last()
+            .is_some_and(|a| a.starts_with("VARIADIC "))
+    }
+
+    /// Returns a call of the function as a snippet, with a tab stop for every argument
+    pub fn snippet(&self) -> String {
+        let arguments = self
+            .argument_list()
+            .iter()
+            .enumerate()
+            .map(|(idx, argument)| format!("${{{}:{}}}", idx + 1, escape_placeholder(argument)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("{}({})$0", self.name, arguments)
+    }
+}
+
+/// Returns the name of an argument of a signature, e.g. `a` of `VARIADIC a text[]`, or `None`
+/// if it only consists of its type
+pub(crate) fn argument_name(argument: &str) -> Option<&str> {
+    let argument = ["IN ", "OUT ", "INOUT ", "VARIADIC "]
+        .iter()
+        .find_map(|mode| argument.strip_prefix(mode))
+        .unwrap_or(argument);
+    if MULTI_WORD_TYPES.iter().any(|t| argument.starts_with(t)) {
+        return None;
+    }
+    argument.split_once(' ').map(|(name, _)| name)
+}
+
+/// Escapes the characters that have a meaning within the placeholder of a snippet
+fn escape_placeholder(text: &str) -> String {
+    text.chars().fold(String::new(), |mut escaped, c| {
+        if matches!(c, '\\' | '$' | '}') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+        escaped
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(name: &str, arguments: &str) -> Function {
+        Function {
+            schema_name: "pg_catalog".to_string(),
+            name: name.to_string(),
+            arguments: arguments.to_string(),
+            result_type: "text".to_string(),
+            is_aggregate: false,
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn test_snippet() {
+        assert_eq!(
+            function("left", "text, integer").snippet(),
+            "left(${1:text}, ${2:integer})$0"
+        );
+        assert_eq!(function("now", "").snippet(), "now()$0");
+        assert_eq!(
+            function("format", "VARIADIC \"any\"").snippet(),
+            "format(${1:VARIADIC \"any\"})$0"
+        );
+        assert_eq!(function("f", "a text[]").snippet(), "f(${1:a text[]})$0");
+        assert_eq!(escape_placeholder("${x}"), "\\${x\\}");
+    }
+
+    #[test]
+    fn test_argument_name() {
+        assert_eq!(argument_name("years integer"), Some("years"));
+        assert_eq!(argument_name("VARIADIC a text[]"), Some("a"));
+        assert_eq!(argument_name("VARIADIC \"any\""), None);
+        assert_eq!(argument_name("double precision"), None);
+        assert_eq!(argument_name("timestamp with time zone"), None);
+        assert_eq!(argument_name("text"), None);
+    }
+}
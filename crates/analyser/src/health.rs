@@ -0,0 +1,194 @@
+//! Indicators of the quality of the schema that the migrations of a project produce.
+//!
+//! The schema after the last migration is checked for tables without a primary key, columns
+//! without a comment, tables without row level security and foreign keys whose columns are not
+//! the leading columns of any index, which makes deletes from the referenced table scan the
+//! referencing one. Row level security and comments are not part of the schema model, so they are
+//! taken from the statements of all files in their order.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use parser::RawStmt;
+use pg_query::NodeEnum;
+
+use crate::comments::{CommentedKind, CommentedObject};
+use crate::definitions::{definitions, ObjectKind};
+use crate::foreign_key_index::{constraints, table_unindexed_foreign_keys, PRIMARY_KEY};
+use crate::migrations::{alter_table_cmds, MigrationState};
+use crate::moniker::qualified_name;
+
+/// `AT_EnableRowSecurity` and `AT_DisableRowSecurity`
+const ENABLE_ROW_SECURITY: i32 = 60;
+const DISABLE_ROW_SECURITY: i32 = 61;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaHealth {
+    /// The number of objects by their kind in plural, e.g. `tables`
+    pub object_counts: BTreeMap<&'static str, usize>,
+    /// The qualified names of the tables without a primary key
+    pub tables_without_primary_key: Vec<String>,
+    /// The qualified names of the columns without a comment, e.g. `public.contact.email`
+    pub columns_without_comment: Vec<String>,
+    /// The qualified names of the tables that row level security is enabled for
+    pub tables_with_row_security: Vec<String>,
+    /// The qualified names of the tables without row level security
+    pub tables_without_row_security: Vec<String>,
+    /// The tables with row level security but no policy, which hides all their rows
+    pub row_security_without_policy: Vec<String>,
+    /// Foreign keys without an index, e.g. `public.orders.orders_contact_id_fkey (contact_id)`
+    pub unindexed_foreign_keys: Vec<String>,
+}
+
+/// Returns the health of the schema of `state`, which `files` produced, given the objects that
+/// their comments describe
+pub fn schema_health(
+    state: &MigrationState,
+    files: &[&[RawStmt]],
+    commented: &[CommentedObject],
+) -> SchemaHealth {
+    let mut health = SchemaHealth::default();
+
+    let mut row_security = BTreeSet::new();
+    let mut policies = BTreeSet::new();
+    let mut defined = BTreeMap::<&'static str, BTreeSet<String>>::new();
+    for stmts in files {
+        for stmt in stmts.iter() {
+            match &stmt.stmt {
+                NodeEnum::AlterTableStmt(n) => {
+                    let Some(relation) = &n.relation else {
+                        continue;
+                    };
+                    for cmd in alter_table_cmds(n) {
+                        match cmd.subtype {
+                            ENABLE_ROW_SECURITY => {
+                                row_security.insert(qualified_name(relation));
+                            }
+                            DISABLE_ROW_SECURITY => {
+                                row_security.remove(&qualified_name(relation));
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                NodeEnum::CreatePolicyStmt(n) => {
+                    if let Some(table) = &n.table {
+                        policies.insert(qualified_name(table));
+                    }
+                }
+                _ => {}
+            }
+        }
+        for definition in definitions(stmts) {
+            let kind = match definition.kind {
+                ObjectKind::View => "views",
+                ObjectKind::Function => "functions",
+                ObjectKind::Sequence => "sequences",
+                ObjectKind::Type => "types",
+                ObjectKind::Schema => "schemas",
+                // tables and indexes are counted in the schema, which leaves out dropped ones
+                ObjectKind::Table | ObjectKind::Index => continue,
+            };
+            defined
+                .entry(kind)
+                .or_default()
+                .insert(definition.identifier);
+        }
+    }
+    let commented_columns = commented
+        .iter()
+        .filter(|object| object.kind == CommentedKind::Column)
+        .map(|object| object.identifier())
+        .collect::<BTreeSet<_>>();
+
+    let mut counts = BTreeMap::from([("tables", 0), ("columns", 0), ("indexes", 0)]);
+    for (schema_name, schema) in &state.schemas {
+        for table in schema.tables.values() {
+            let identifier = format!("{}.{}", schema_name, table.name);
+            *counts.entry("tables").or_default() += 1;
+            *counts.entry("columns").or_default() += table.columns.len();
+            *counts.entry("indexes").or_default() += table.indexes.len();
+
+            if !constraints(table)
+                .iter()
+                .any(|(_, kind, _)| *kind == PRIMARY_KEY)
+            {
+                health.tables_without_primary_key.push(identifier.clone());
+            }
+            health.columns_without_comment.extend(
+                table
+                    .columns
+                    .iter()
+                    .map(|column| format!("{}.{}", identifier, column.name))
+                    .filter(|column| !commented_columns.contains(column)),
+            );
+            if row_security.contains(&identifier) {
+                health.tables_with_row_security.push(identifier.clone());
+                if !policies.contains(&identifier) {
+                    health.row_security_without_policy.push(identifier.clone());
+                }
+            } else {
+                health.tables_without_row_security.push(identifier.clone());
+            }
+            health.unindexed_foreign_keys.extend(
+                table_unindexed_foreign_keys(table)
+                    .into_iter()
+                    .map(|(name, columns)| {
+                        format!("{}.{} ({})", identifier, name, columns.join(", "))
+                    }),
+            );
+        }
+    }
+    counts.extend(defined.into_iter().map(|(kind, names)| (kind, names.len())));
+    health.object_counts = counts;
+    health
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+    use crate::comments::commented_objects;
+
+    #[test]
+    fn test_schema_health() {
+        let input = "create table contact (id int primary key, email text);
+            create table orders (id int, contact_id int references contact (id));
+            create table note (contact_id int references contact (id));
+            create index note_contact_id on note (contact_id);
+            create view active as select 1;
+            alter table contact enable row level security;
+            create policy own on contact using (true);
+            alter table orders enable row level security;
+            comment on column contact.email is 'primary address';";
+        let parse = parse_source(input);
+        let mut state = MigrationState::default();
+        state.replay(&parse.stmts);
+        let health = schema_health(
+            &state,
+            &[&parse.stmts],
+            &commented_objects(&parse.cst, &parse.stmts),
+        );
+
+        assert_eq!(health.object_counts["tables"], 3);
+        assert_eq!(health.object_counts["views"], 1);
+        assert_eq!(
+            health.tables_without_primary_key,
+            vec!["public.note", "public.orders"]
+        );
+        assert!(health
+            .columns_without_comment
+            .contains(&"public.contact.id".to_string()));
+        assert!(!health
+            .columns_without_comment
+            .contains(&"public.contact.email".to_string()));
+        assert_eq!(
+            health.tables_with_row_security,
+            vec!["public.contact", "public.orders"]
+        );
+        assert_eq!(health.row_security_without_policy, vec!["public.orders"]);
+        assert_eq!(health.tables_without_row_security, vec!["public.note"]);
+        assert_eq!(health.unindexed_foreign_keys.len(), 1);
+        assert!(health.unindexed_foreign_keys[0].starts_with("public.orders."));
+    }
+}
@@ -0,0 +1,69 @@
+//! Impact of changes to the statements of a set of files.
+//!
+//! A changed statement impacts itself and every statement, in any of the files, that uses an
+//! object the changed statement creates. Dependencies are derived the same way as for restore
+//! scripts, see the `restore` module. Objects whose creating statement was deleted cannot be
+//! traced, because the deleted statement is not part of the files anymore.
+
+use std::collections::{BTreeSet, HashSet};
+
+use parser::RawStmt;
+
+use crate::restore::{stmt_dependencies, Object};
+
+/// A statement, identified by the index of its file and its index within the file
+pub type StmtId = (usize, usize);
+
+/// Returns the statements of `files` that are impacted by the `changed` statements, including the
+/// changed statements themselves
+pub fn impacted_stmts(files: &[&[RawStmt]], changed: &BTreeSet<StmtId>) -> BTreeSet<StmtId> {
+    let dependencies = files
+        .iter()
+        .map(|stmts| stmt_dependencies(stmts))
+        .collect::<Vec<_>>();
+
+    let changed_objects = changed
+        .iter()
+        .filter_map(|&(file, stmt)| dependencies.get(file)?.get(stmt))
+        .flat_map(|deps| deps.creates.iter())
+        .collect::<HashSet<&Object>>();
+
+    let mut impacted = changed.clone();
+    for (file, deps) in dependencies.iter().enumerate() {
+        for (stmt, deps) in deps.iter().enumerate() {
+            if deps
+                .uses
+                .iter()
+                .any(|(object, _)| changed_objects.contains(object))
+            {
+                impacted.insert((file, stmt));
+            }
+        }
+    }
+    impacted
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    #[test]
+    fn test_impacted_stmts() {
+        let schema = parse_source("create table contact (id int);\ncreate table orders (id int);");
+        let queries = parse_source(
+            "select id from contact;\nselect id from orders;\ncreate view v as select * from public.contact;",
+        );
+        let files = [schema.stmts.as_slice(), queries.stmts.as_slice()];
+
+        assert_eq!(
+            impacted_stmts(&files, &BTreeSet::from([(0, 0)])),
+            BTreeSet::from([(0, 0), (1, 0), (1, 2)])
+        );
+        assert_eq!(
+            impacted_stmts(&files, &BTreeSet::from([(1, 1)])),
+            BTreeSet::from([(1, 1)])
+        );
+    }
+}
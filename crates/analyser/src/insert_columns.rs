@@ -0,0 +1,167 @@
+//! The column list of `INSERT` statements that leave it out.
+//!
+//! Without a column list, the values of an `INSERT` fill the columns of the table in their order,
+//! which silently changes meaning when a column is added or the table is recreated in another
+//! order. The explicit list names the columns that the values currently go to: as many leading
+//! columns of the table as the longest row has values, or all of them for a query whose width is
+//! unknown. Rows with more values than the table has columns are reported by
+//! [`MigrationState::check`](crate::migrations::MigrationState::check).
+
+use std::collections::BTreeMap;
+
+use cstree::syntax::ResolvedNode;
+use cstree::text::TextSize;
+use parser::make::quote_ident;
+use parser::{RawStmt, SyntaxKind};
+use pg_query::NodeEnum;
+
+use crate::moniker::DEFAULT_SCHEMA;
+use crate::schema::Schema;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnList {
+    /// The offset after the table name where the list is inserted
+    pub offset: TextSize,
+    /// The list with a leading space, e.g. ` (id, name)`
+    pub text: String,
+}
+
+/// Returns the column list of the `INSERT` at `offset` if it has none and its table is one of
+/// `schemas`
+pub fn insert_column_list(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmts: &[RawStmt],
+    offset: TextSize,
+    schemas: &BTreeMap<String, Schema>,
+) -> Option<ColumnList> {
+    let stmt = stmts
+        .iter()
+        .find(|stmt| stmt.range.contains_inclusive(offset))?;
+    let NodeEnum::InsertStmt(insert) = &stmt.stmt else {
+        return None;
+    };
+    if !insert.cols.is_empty() {
+        return None;
+    }
+    let relation = insert.relation.as_ref()?;
+    let schema = if relation.schemaname.is_empty() {
+        DEFAULT_SCHEMA
+    } else {
+        &relation.schemaname
+    };
+    let columns = &schemas.get(schema)?.table(&relation.relname)?.columns;
+
+    // `DEFAULT VALUES` has no query and needs no column list
+    let NodeEnum::SelectStmt(select) = insert.select_stmt.as_ref()?.node.as_ref()? else {
+        return None;
+    };
+    let width = if select.values_lists.is_empty() {
+        let has_star = select.target_list.iter().any(|target| {
+            matches!(
+                target.node.as_ref(),
+                Some(NodeEnum::ResTarget(t)) if matches!(
+                    t.val.as_ref().and_then(|v| v.node.as_ref()),
+                    Some(NodeEnum::ColumnRef(c)) if c.fields.iter().any(
+                        |f| matches!(f.node, Some(NodeEnum::AStar(_)))
+                    )
+                )
+            )
+        });
+        if has_star || select.target_list.is_empty() {
+            columns.len()
+        } else {
+            select.target_list.len()
+        }
+    } else {
+        select
+            .values_lists
+            .iter()
+            .map(|row| match row.node.as_ref() {
+                Some(NodeEnum::List(list)) => list.items.len(),
+                _ => 0,
+            })
+            .max()
+            .unwrap_or(0)
+    };
+    if width == 0 {
+        return None;
+    }
+
+    // the list goes after the table and its alias, before `OVERRIDING` or the query
+    let start = stmt.range.start() + TextSize::from(relation.location.max(0) as u32);
+    let tokens = cst
+        .descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| token.text_range().start() >= start && !token.kind().is_trivia())
+        .take_while(|token| token.text_range().end() <= stmt.range.end())
+        .collect::<Vec<_>>();
+    let end = tokens.iter().position(|token| {
+        matches!(
+            token.kind(),
+            SyntaxKind::Values | SyntaxKind::Select | SyntaxKind::With | SyntaxKind::Ascii40
+        ) || token.text().eq_ignore_ascii_case("overriding")
+    })?;
+    let offset = tokens[..end].last()?.text_range().end();
+
+    let names = columns
+        .iter()
+        .take(width)
+        .map(|c| quote_ident(&c.name))
+        .collect::<Vec<_>>();
+    Some(ColumnList {
+        offset,
+        text: format!(" ({})", names.join(", ")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+    use crate::migrations::MigrationState;
+
+    fn column_list(input: &str) -> Option<(String, String)> {
+        let parse = parse_source(input);
+        let offset = TextSize::try_from(input.rfind("insert").unwrap()).unwrap();
+        let mut state = MigrationState::default();
+        state.replay(&parse.stmts);
+        let list = insert_column_list(&parse.cst, &parse.stmts, offset, &state.schemas)?;
+        let mut text = input.to_string();
+        text.insert_str(list.offset.into(), &list.text);
+        Some((
+            list.text,
+            text[input.rfind("insert").unwrap()..].to_string(),
+        ))
+    }
+
+    #[test]
+    fn test_insert_column_list() {
+        let ddl = "create table app.contact (id int, \"Name\" text, email text);\n";
+        assert_eq!(
+            column_list(&format!("{}insert into app.contact values (1, 'a');", ddl)),
+            Some((
+                " (id, \"Name\")".to_string(),
+                "insert into app.contact (id, \"Name\") values (1, 'a');".to_string()
+            ))
+        );
+        assert_eq!(
+            column_list(&format!(
+                "{}insert into app.contact as c overriding user value select * from app.contact;",
+                ddl
+            ))
+            .unwrap()
+            .1,
+            "insert into app.contact as c (id, \"Name\", email) overriding user value select * from app.contact;"
+        );
+        assert_eq!(
+            column_list(&format!("{}insert into app.contact (id) values (1);", ddl)),
+            None
+        );
+        assert_eq!(
+            column_list(&format!("{}insert into app.contact default values;", ddl)),
+            None
+        );
+        assert_eq!(column_list("insert into invoice values (1);"), None);
+    }
+}
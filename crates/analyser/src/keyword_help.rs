@@ -0,0 +1,684 @@
+//! Explanations of the keywords of a statement.
+//!
+//! Hovering over a keyword explains the clause or construct that it introduces and links to the
+//! section of the Postgres documentation on it. Some keywords mean different things in different
+//! statements, e.g. `SET` of an `UPDATE` and of a `SET` statement, so an explanation may be
+//! restricted to the kind of the innermost statement around the keyword. Subqueries are statements
+//! of their own, so `LATERAL` is explained as part of the `SELECT` it appears in.
+//!
+//! The keywords of the lock mode of a `LOCK` statement, e.g. `SHARE ROW EXCLUSIVE`, are explained
+//! together, by the statements that the mode blocks.
+
+use cstree::syntax::{ResolvedNode, ResolvedToken};
+use cstree::text::{TextRange, TextSize};
+use parser::SyntaxKind;
+
+/// The documentation of the latest Postgres release, which the pages of the explanations are in
+pub const DOCUMENTATION_URL: &str = "https://www.postgresql.org/docs/current/";
+
+struct Explanation {
+    keyword: SyntaxKind,
+    /// The kind of statement the explanation is restricted to, if any
+    statement: Option<SyntaxKind>,
+    title: &'static str,
+    summary: &'static str,
+    /// The page within the documentation, with the anchor of the section if any
+    page: &'static str,
+}
+
+const fn explain(
+    keyword: SyntaxKind,
+    statement: Option<SyntaxKind>,
+    title: &'static str,
+    summary: &'static str,
+    page: &'static str,
+) -> Explanation {
+    Explanation {
+        keyword,
+        statement,
+        title,
+        summary,
+        page,
+    }
+}
+
+/// The explanations, those of a kind of statement before the general ones of the same keyword
+const EXPLANATIONS: &[Explanation] = &[
+    // queries
+    explain(
+        SyntaxKind::Select,
+        None,
+        "SELECT",
+        "Retrieves rows from tables, views and other row sources, computing an output row from \
+         the expressions of the select list for every row of the `FROM` clause.",
+        "sql-select.html",
+    ),
+    explain(
+        SyntaxKind::Distinct,
+        Some(SyntaxKind::SelectStmt),
+        "SELECT DISTINCT",
+        "Removes duplicate rows from the result. `DISTINCT ON (expressions)` keeps only the first \
+         row of each set of rows with equal expressions.",
+        "sql-select.html#SQL-DISTINCT",
+    ),
+    explain(
+        SyntaxKind::From,
+        Some(SyntaxKind::DeleteStmt),
+        "DELETE FROM",
+        "Names the table to delete rows from.",
+        "sql-delete.html",
+    ),
+    explain(
+        SyntaxKind::From,
+        None,
+        "FROM",
+        "Lists the tables, subqueries and functions to read rows from. Several items are combined \
+         as a cross join.",
+        "queries-table-expressions.html#QUERIES-FROM",
+    ),
+    explain(
+        SyntaxKind::LateralP,
+        None,
+        "LATERAL",
+        "Lets a subquery or function in `FROM` refer to columns of the items before it. It is \
+         evaluated once for every row of those items.",
+        "queries-table-expressions.html#QUERIES-LATERAL",
+    ),
+    explain(
+        SyntaxKind::Where,
+        None,
+        "WHERE",
+        "Keeps only the rows for which the condition is true. Rows for which it is false or null \
+         are left out.",
+        "queries-table-expressions.html#QUERIES-WHERE",
+    ),
+    explain(
+        SyntaxKind::GroupP,
+        None,
+        "GROUP BY",
+        "Combines the rows with equal values of the grouping expressions into one row, over which \
+         aggregates are computed.",
+        "queries-table-expressions.html#QUERIES-GROUP",
+    ),
+    explain(
+        SyntaxKind::Having,
+        None,
+        "HAVING",
+        "Keeps only the groups for which the condition is true. Unlike `WHERE`, it is evaluated \
+         after grouping and may use aggregates.",
+        "queries-table-expressions.html#QUERIES-GROUP",
+    ),
+    explain(
+        SyntaxKind::Window,
+        None,
+        "WINDOW",
+        "Names window definitions, which the `OVER` clauses of window functions can refer to.",
+        "sql-select.html#SQL-WINDOW",
+    ),
+    explain(
+        SyntaxKind::Over,
+        None,
+        "OVER",
+        "Makes a function a window function, computed over the rows that are related to the \
+         current one instead of collapsing them.",
+        "tutorial-window.html",
+    ),
+    explain(
+        SyntaxKind::Partition,
+        Some(SyntaxKind::SelectStmt),
+        "PARTITION BY",
+        "Divides the rows of a window into partitions, which window functions are computed over \
+         separately.",
+        "sql-expressions.html#SYNTAX-WINDOW-FUNCTIONS",
+    ),
+    explain(
+        SyntaxKind::Filter,
+        None,
+        "FILTER",
+        "Passes only the rows for which the condition is true to an aggregate.",
+        "sql-expressions.html#SYNTAX-AGGREGATES",
+    ),
+    explain(
+        SyntaxKind::Order,
+        None,
+        "ORDER BY",
+        "Sorts the rows. Without it, the order of the rows is unspecified.",
+        "queries-order.html",
+    ),
+    explain(
+        SyntaxKind::Limit,
+        None,
+        "LIMIT",
+        "Returns at most the given number of rows. Use it with `ORDER BY` to get a predictable \
+         subset.",
+        "queries-limit.html",
+    ),
+    explain(
+        SyntaxKind::Offset,
+        None,
+        "OFFSET",
+        "Skips the given number of rows before returning any. The skipped rows are still \
+         computed, so large offsets are slow.",
+        "queries-limit.html",
+    ),
+    explain(
+        SyntaxKind::With,
+        None,
+        "WITH",
+        "Defines common table expressions, auxiliary statements whose results the main statement \
+         can refer to by name.",
+        "queries-with.html",
+    ),
+    explain(
+        SyntaxKind::Recursive,
+        None,
+        "WITH RECURSIVE",
+        "Lets a common table expression refer to its own output, e.g. to walk a hierarchy.",
+        "queries-with.html#QUERIES-WITH-RECURSIVE",
+    ),
+    explain(
+        SyntaxKind::Union,
+        None,
+        "UNION",
+        "Appends the rows of the second query to those of the first. Duplicates are removed \
+         unless `UNION ALL` is used.",
+        "queries-union.html",
+    ),
+    explain(
+        SyntaxKind::Intersect,
+        None,
+        "INTERSECT",
+        "Returns the rows that are in the results of both queries.",
+        "queries-union.html",
+    ),
+    explain(
+        SyntaxKind::Except,
+        None,
+        "EXCEPT",
+        "Returns the rows of the first query that are not in the result of the second.",
+        "queries-union.html",
+    ),
+    explain(
+        SyntaxKind::Values,
+        None,
+        "VALUES",
+        "Builds rows from lists of expressions, e.g. to insert them or to use them as a table.",
+        "queries-values.html",
+    ),
+    // joins
+    explain(
+        SyntaxKind::Join,
+        None,
+        "JOIN",
+        "Combines the rows of two tables. An inner join keeps the pairs of rows for which the \
+         join condition is true.",
+        "queries-table-expressions.html#QUERIES-JOIN",
+    ),
+    explain(
+        SyntaxKind::InnerP,
+        None,
+        "INNER JOIN",
+        "Keeps the pairs of rows for which the join condition is true.",
+        "queries-table-expressions.html#QUERIES-JOIN",
+    ),
+    explain(
+        SyntaxKind::Left,
+        None,
+        "LEFT JOIN",
+        "Keeps every row of the left table, with nulls for the columns of the right table if no \
+         row of it matches.",
+        "queries-table-expressions.html#QUERIES-JOIN",
+    ),
+    explain(
+        SyntaxKind::Right,
+        None,
+        "RIGHT JOIN",
+        "Keeps every row of the right table, with nulls for the columns of the left table if no \
+         row of it matches.",
+        "queries-table-expressions.html#QUERIES-JOIN",
+    ),
+    explain(
+        SyntaxKind::Full,
+        None,
+        "FULL JOIN",
+        "Keeps every row of both tables, with nulls for the columns of the other table if no row \
+         of it matches.",
+        "queries-table-expressions.html#QUERIES-JOIN",
+    ),
+    explain(
+        SyntaxKind::Cross,
+        None,
+        "CROSS JOIN",
+        "Combines every row of the left table with every row of the right table.",
+        "queries-table-expressions.html#QUERIES-JOIN",
+    ),
+    explain(
+        SyntaxKind::Natural,
+        None,
+        "NATURAL JOIN",
+        "Joins on equality of all columns that have the same name in both tables. Adding a \
+         column later can silently change the join.",
+        "queries-table-expressions.html#QUERIES-JOIN",
+    ),
+    explain(
+        SyntaxKind::On,
+        Some(SyntaxKind::InsertStmt),
+        "ON CONFLICT",
+        "Does nothing or updates the existing row instead of failing when an inserted row \
+         violates a unique constraint.",
+        "sql-insert.html#SQL-ON-CONFLICT",
+    ),
+    explain(
+        SyntaxKind::Conflict,
+        None,
+        "ON CONFLICT",
+        "Does nothing or updates the existing row instead of failing when an inserted row \
+         violates a unique constraint.",
+        "sql-insert.html#SQL-ON-CONFLICT",
+    ),
+    explain(
+        SyntaxKind::On,
+        Some(SyntaxKind::SelectStmt),
+        "JOIN ... ON",
+        "The join condition, which the pairs of rows of a join must satisfy.",
+        "queries-table-expressions.html#QUERIES-JOIN",
+    ),
+    explain(
+        SyntaxKind::Using,
+        Some(SyntaxKind::SelectStmt),
+        "JOIN ... USING",
+        "Joins on equality of the listed columns, which appear only once in the output.",
+        "queries-table-expressions.html#QUERIES-JOIN",
+    ),
+    // expressions
+    explain(
+        SyntaxKind::Case,
+        None,
+        "CASE",
+        "Returns the result of the first `WHEN` branch whose condition is true, or that of `ELSE` \
+         if there is none.",
+        "functions-conditional.html#FUNCTIONS-CASE",
+    ),
+    explain(
+        SyntaxKind::Exists,
+        None,
+        "EXISTS",
+        "Is true if the subquery returns at least one row.",
+        "functions-subquery.html#FUNCTIONS-SUBQUERY-EXISTS",
+    ),
+    explain(
+        SyntaxKind::Between,
+        None,
+        "BETWEEN",
+        "Is true if the value is within the range, including both bounds.",
+        "functions-comparison.html",
+    ),
+    explain(
+        SyntaxKind::Like,
+        None,
+        "LIKE",
+        "Matches a string against a pattern, in which `%` stands for any sequence of characters \
+         and `_` for any single character.",
+        "functions-matching.html#FUNCTIONS-LIKE",
+    ),
+    explain(
+        SyntaxKind::Ilike,
+        None,
+        "ILIKE",
+        "Matches a string against a pattern like `LIKE`, ignoring the case of letters.",
+        "functions-matching.html#FUNCTIONS-LIKE",
+    ),
+    // data modification
+    explain(
+        SyntaxKind::Insert,
+        None,
+        "INSERT",
+        "Inserts rows into a table.",
+        "sql-insert.html",
+    ),
+    explain(
+        SyntaxKind::Update,
+        None,
+        "UPDATE",
+        "Changes the values of columns of the rows that satisfy the condition.",
+        "sql-update.html",
+    ),
+    explain(
+        SyntaxKind::DeleteP,
+        None,
+        "DELETE",
+        "Deletes the rows that satisfy the condition. Without `WHERE`, all rows are deleted.",
+        "sql-delete.html",
+    ),
+    explain(
+        SyntaxKind::Set,
+        Some(SyntaxKind::UpdateStmt),
+        "UPDATE ... SET",
+        "Assigns new values to columns of the updated rows.",
+        "sql-update.html",
+    ),
+    explain(
+        SyntaxKind::Set,
+        Some(SyntaxKind::VariableSetStmt),
+        "SET",
+        "Changes a run-time parameter for the session, or for the transaction with `SET LOCAL`.",
+        "sql-set.html",
+    ),
+    explain(
+        SyntaxKind::Set,
+        Some(SyntaxKind::AlterTableStmt),
+        "ALTER TABLE ... SET",
+        "Changes a property of a column or the table, e.g. its default or not null constraint.",
+        "sql-altertable.html",
+    ),
+    explain(
+        SyntaxKind::Returning,
+        None,
+        "RETURNING",
+        "Returns values of the inserted, updated or deleted rows, as if the statement was a \
+         query.",
+        "dml-returning.html",
+    ),
+    // definition
+    explain(
+        SyntaxKind::Create,
+        Some(SyntaxKind::CreateStmt),
+        "CREATE TABLE",
+        "Creates a table.",
+        "sql-createtable.html",
+    ),
+    explain(
+        SyntaxKind::Create,
+        Some(SyntaxKind::IndexStmt),
+        "CREATE INDEX",
+        "Creates an index. It blocks writes to the table while it is built, unless it is created \
+         `CONCURRENTLY`.",
+        "sql-createindex.html",
+    ),
+    explain(
+        SyntaxKind::Concurrently,
+        Some(SyntaxKind::IndexStmt),
+        "CONCURRENTLY",
+        "Builds the index without blocking writes to the table. It takes longer and cannot run \
+         inside a transaction block.",
+        "sql-createindex.html#SQL-CREATEINDEX-CONCURRENTLY",
+    ),
+    explain(
+        SyntaxKind::Alter,
+        Some(SyntaxKind::AlterTableStmt),
+        "ALTER TABLE",
+        "Changes the definition of a table. Its commands are applied in order.",
+        "sql-altertable.html",
+    ),
+    explain(
+        SyntaxKind::Cascade,
+        None,
+        "CASCADE",
+        "Also drops or truncates the objects that depend on the object, and in turn all objects \
+         that depend on those.",
+        "ddl-depend.html",
+    ),
+    explain(
+        SyntaxKind::Truncate,
+        None,
+        "TRUNCATE",
+        "Deletes all rows of the tables quickly, without scanning them. It takes an exclusive \
+         lock on the tables.",
+        "sql-truncate.html",
+    ),
+    explain(
+        SyntaxKind::LockP,
+        Some(SyntaxKind::LockStmt),
+        "LOCK",
+        "Locks tables until the end of the transaction, in `ACCESS EXCLUSIVE` mode unless another \
+         mode is given. It waits for conflicting locks to be released unless `NOWAIT` is given.",
+        "sql-lock.html",
+    ),
+    explain(
+        SyntaxKind::Cluster,
+        Some(SyntaxKind::ClusterStmt),
+        "CLUSTER",
+        "Rewrites a table in the order of an index, which later runs of `CLUSTER` on the table \
+         use unless another one is given. The table is locked in `ACCESS EXCLUSIVE` mode while it \
+         is rewritten.",
+        "sql-cluster.html",
+    ),
+    // transactions
+    explain(
+        SyntaxKind::BeginP,
+        Some(SyntaxKind::TransactionStmt),
+        "BEGIN",
+        "Starts a transaction block, whose statements are committed or rolled back together.",
+        "sql-begin.html",
+    ),
+    explain(
+        SyntaxKind::Commit,
+        None,
+        "COMMIT",
+        "Commits the current transaction, making its changes visible to other sessions.",
+        "sql-commit.html",
+    ),
+    explain(
+        SyntaxKind::Rollback,
+        None,
+        "ROLLBACK",
+        "Aborts the current transaction, discarding its changes.",
+        "sql-rollback.html",
+    ),
+];
+
+/// The lock modes as they are written in a `LOCK` statement, with the statements they block
+const LOCK_MODES: &[(&str, &str)] = &[
+    (
+        "ACCESS SHARE",
+        "The lock of `SELECT`. It only blocks `ACCESS EXCLUSIVE` locks, i.e. `DROP TABLE`, \
+         `TRUNCATE`, `VACUUM FULL` and most forms of `ALTER TABLE`.",
+    ),
+    (
+        "ROW SHARE",
+        "The lock of `SELECT ... FOR UPDATE` and `FOR SHARE`. It blocks `EXCLUSIVE` and `ACCESS \
+         EXCLUSIVE` locks.",
+    ),
+    (
+        "ROW EXCLUSIVE",
+        "The lock of `INSERT`, `UPDATE`, `DELETE` and `MERGE`. It blocks `CREATE INDEX` without \
+         `CONCURRENTLY`, and the `SHARE` locks and stronger ones of schema changes.",
+    ),
+    (
+        "SHARE UPDATE EXCLUSIVE",
+        "The lock of `VACUUM`, `ANALYZE` and `CREATE INDEX CONCURRENTLY`. It blocks other \
+         vacuums and schema changes, but neither reads nor writes.",
+    ),
+    (
+        "SHARE",
+        "The lock of `CREATE INDEX`. It blocks `INSERT`, `UPDATE`, `DELETE` and schema changes, \
+         but not reads.",
+    ),
+    (
+        "SHARE ROW EXCLUSIVE",
+        "The lock of `CREATE TRIGGER` and of adding foreign keys. It blocks all writes and schema \
+         changes, and only one session can hold it.",
+    ),
+    (
+        "EXCLUSIVE",
+        "The lock of `REFRESH MATERIALIZED VIEW CONCURRENTLY`. It blocks everything but plain \
+         `SELECT`.",
+    ),
+    (
+        "ACCESS EXCLUSIVE",
+        "The lock of `DROP TABLE`, `TRUNCATE` and most forms of `ALTER TABLE`, and the default of \
+         `LOCK`. It blocks every access to the table, including `SELECT`.",
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeywordHelp {
+    /// The range of the keyword
+    pub range: TextRange,
+    /// The construct the keyword belongs to, e.g. `GROUP BY`
+    pub title: &'static str,
+    pub summary: &'static str,
+    /// The section of the documentation on the construct
+    pub url: String,
+}
+
+impl KeywordHelp {
+    pub fn to_markdown(&self) -> String {
+        format!(
+            "**{}**\n\n{}\n\n[Postgres documentation]({})",
+            self.title, self.summary, self.url
+        )
+    }
+}
+
+/// Returns the explanation of the keyword at `offset`, or `None` if there is no keyword or it is
+/// not explained
+pub fn keyword_help(cst: &ResolvedNode<SyntaxKind>, offset: TextSize) -> Option<KeywordHelp> {
+    let mut tokens = cst
+        .descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .skip_while(|token| !token.text_range().contains(offset));
+    let token = tokens.next()?;
+    let kind = token.kind();
+    if !kind.is_keyword() {
+        return None;
+    }
+    // keywords such as `left` are also names of functions
+    let is_call = tokens
+        .find(|t| !t.kind().is_trivia())
+        .is_some_and(|t| t.kind() == SyntaxKind::Ascii40);
+    if is_call
+        && kind != SyntaxKind::Join
+        && kind
+            .keyword_category()
+            .is_some_and(|c| c.is_type_func_name())
+    {
+        return None;
+    }
+
+    let statement = token
+        .parent()
+        .ancestors()
+        .map(|node| node.kind())
+        .find(|kind| kind.stmt_kind().is_some());
+    if statement == Some(SyntaxKind::LockStmt) {
+        if let Some(help) = lock_mode_help(&token) {
+            return Some(help);
+        }
+    }
+    let explanation = EXPLANATIONS
+        .iter()
+        .filter(|e| e.keyword == kind)
+        .find(|e| e.statement.is_none() || e.statement == statement)?;
+    Some(KeywordHelp {
+        range: token.text_range(),
+        title: explanation.title,
+        summary: explanation.summary,
+        url: format!("{}{}", DOCUMENTATION_URL, explanation.page),
+    })
+}
+
+/// Returns the explanation of the lock mode of a `LOCK` statement if `token` is part of it
+fn lock_mode_help(token: &ResolvedToken<SyntaxKind>) -> Option<KeywordHelp> {
+    let stmt = token
+        .parent()
+        .ancestors()
+        .find(|node| node.kind() == SyntaxKind::LockStmt)?;
+    // the keywords from `IN` to `MODE`
+    let words = stmt
+        .descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|t| !t.kind().is_trivia())
+        .skip_while(|t| t.kind() != SyntaxKind::InP)
+        .collect::<Vec<_>>();
+    let end = words.iter().position(|t| t.kind() == SyntaxKind::Mode)?;
+    let range = TextRange::new(
+        words.first()?.text_range().start(),
+        words[end].text_range().end(),
+    );
+    if !range.contains_range(token.text_range()) {
+        return None;
+    }
+    let mode = words[1..end]
+        .iter()
+        .map(|t| t.text().to_uppercase())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let &(title, summary) = LOCK_MODES.iter().find(|(name, _)| *name == mode)?;
+    Some(KeywordHelp {
+        range,
+        title,
+        summary,
+        url: format!("{}explicit-locking.html#LOCKING-TABLES", DOCUMENTATION_URL),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    fn help(input: &str) -> Option<KeywordHelp> {
+        let offset = TextSize::from(input.find('|').unwrap() as u32);
+        let text = input.replace('|', "");
+        keyword_help(&parse_source(&text).cst, offset)
+    }
+
+    #[test]
+    fn test_keyword_help() {
+        let lateral =
+            help("select * from contact c, |lateral (select * from orders o where o.id = c.id) x;")
+                .unwrap();
+        assert_eq!(lateral.title, "LATERAL");
+        assert_eq!(
+            lateral.url,
+            "https://www.postgresql.org/docs/current/queries-table-expressions.html#QUERIES-LATERAL"
+        );
+        assert_eq!(lateral.range, TextRange::new(25.into(), 32.into()));
+
+        assert_eq!(
+            help("select * from contact gr|oup by name;").map(|h| h.title),
+            Some("GROUP BY")
+        );
+        assert_eq!(
+            help("update contact |set name = 'x';").map(|h| h.title),
+            Some("UPDATE ... SET")
+        );
+        assert_eq!(
+            help("|set search_path to app;").map(|h| h.title),
+            Some("SET")
+        );
+        assert_eq!(
+            help("delete |from contact;").map(|h| h.title),
+            Some("DELETE FROM")
+        );
+        assert_eq!(
+            help("select * |from contact;").map(|h| h.title),
+            Some("FROM")
+        );
+    }
+
+    #[test]
+    fn test_lock_mode_help() {
+        let help_of_mode = help("lock table contact in share row |exclusive mode nowait;").unwrap();
+        assert_eq!(help_of_mode.title, "SHARE ROW EXCLUSIVE");
+        assert_eq!(help_of_mode.range, TextRange::new(19.into(), 46.into()));
+        assert_eq!(
+            help_of_mode.url,
+            "https://www.postgresql.org/docs/current/explicit-locking.html#LOCKING-TABLES"
+        );
+        assert_eq!(
+            help("LOCK contact IN ACCESS SHARE |MODE;").map(|h| h.title),
+            Some("ACCESS SHARE")
+        );
+        assert_eq!(help("|lock contact;").map(|h| h.title), Some("LOCK"));
+    }
+
+    #[test]
+    fn test_no_keyword_help() {
+        assert_eq!(help("select |name from contact;"), None);
+        assert_eq!(help("select |left(name, 1) from contact;"), None);
+        assert_eq!(help("select * from contact |;"), None);
+    }
+}
@@ -0,0 +1,69 @@
+//! Semantic analysis on top of the Postgres parser.
+//!
+//! This crate consumes the abstract syntax tree produced by the `parser` crate (a list of pg_query
+//! statements and their ranges) and derives knowledge from it that goes beyond syntax, such as the
+//! casts that are available between types.
+//!
+//! It also hosts the linter. Lint rules are plain functions that inspect a single statement and
+//! report diagnostics at ranges within the source text. See the `lint` module for details.
+
+pub mod activity;
+pub mod ambiguous_columns;
+pub mod bloat;
+mod cast_graph;
+pub mod comments;
+pub mod completion;
+pub mod concurrent_index;
+pub mod data_migration;
+pub mod ddl_rewrite;
+pub mod definitions;
+pub mod execution_error;
+pub mod fillfactor;
+pub mod foreign_key_index;
+mod function;
+pub mod health;
+pub mod impact;
+pub mod insert_columns;
+pub mod keyword_help;
+pub mod lint;
+pub mod messages;
+pub mod migrations;
+pub mod moniker;
+pub mod name_arguments;
+pub mod object_definition;
+pub mod object_hover;
+pub mod outline;
+pub mod partition_pruning;
+pub mod plan;
+pub mod policy;
+pub mod read_only;
+pub mod references;
+pub mod rename;
+pub mod restore;
+mod schema;
+pub mod schema_change;
+pub mod schema_diff;
+pub mod selection;
+pub mod signature_help;
+pub mod spelling;
+pub mod star_expansion;
+pub mod table_rewrite;
+pub mod tenants;
+mod type_hierarchy;
+mod utils;
+
+pub use crate::cast_graph::{
+    Cast, CastContext, CastGraph, CastMethod, CastOrigin, CatalogCast, PG_CAST_QUERY,
+};
+pub use crate::function::{Function, FUNCTIONS_QUERY};
+pub use crate::lint::{
+    lint, lint_cancellable, lint_fix, lint_with_config, rule_group, LintConfig, LintDiagnostic,
+    LintFix, RuleGroup, Severity, TableFacts,
+};
+pub use crate::schema::{
+    CatalogColumn, CatalogConstraint, CatalogIndex, Column, Deferral, Schema, Table, View,
+    SCHEMA_COLUMNS_QUERY, SCHEMA_CONSTRAINTS_QUERY, SCHEMA_INDEXES_QUERY, SCHEMA_VIEWS_QUERY,
+};
+pub use crate::type_hierarchy::{
+    defined_type, TypeDefinition, TypeHierarchy, TypeKind, TypeRelation,
+};
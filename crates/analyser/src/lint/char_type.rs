@@ -0,0 +1,47 @@
+use super::{LintContext, Rule, RuleGroup, Severity};
+use crate::utils::type_names;
+
+/// Flags usages of `char(n)` and `character(n)`.
+///
+/// `char(n)` pads values with spaces up to the given length, and the padding is ignored in some
+/// comparisons and significant in others. It is not faster than `text` or `varchar(n)` either.
+/// The advice is `text` rather than `varchar(n)`, which `prefer-text` flags in turn. The internal
+/// single-byte `"char"` type is not affected.
+pub const RULE: Rule = Rule {
+    name: "char-type",
+    description: "char(n) pads its values with spaces",
+    group: RuleGroup::ModernPostgres,
+    severity: Severity::Warning,
+    stmt_kinds: &[],
+    check,
+    fix: None,
+};
+
+fn check(ctx: &mut LintContext<'_>) {
+    for (t, name) in type_names(&ctx.stmt.stmt) {
+        if name == "bpchar" {
+            ctx.report_at_location(
+                "char(n) pads values with spaces. Use text instead.",
+                t.location,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use crate::lint::{lint_with_config, LintConfig, RuleGroup};
+
+    #[test]
+    fn test_char_type() {
+        let diagnostics = lint_with_config(
+            &parse_source("CREATE TABLE t (code char(3), name varchar(10), flag \"char\");").stmts,
+            &LintConfig::default().with_group(RuleGroup::ModernPostgres),
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].rule, "char-type");
+        assert_eq!(usize::from(diagnostics[0].range.start()), 21);
+    }
+}
@@ -0,0 +1,401 @@
+use cstree::syntax::{ResolvedNode, ResolvedToken};
+use cstree::text::{TextRange, TextSize};
+use parser::{RawStmt, StmtKind, SyntaxKind};
+use pg_query::protobuf::CreateStmt;
+use pg_query::NodeEnum;
+
+use super::{LintContext, LintDiagnostic, LintFix, Rule, RuleGroup, Severity};
+use crate::moniker::qualified_name;
+use crate::utils::type_name;
+
+/// Flags `CREATE TABLE` whose column order wastes space on alignment padding.
+///
+/// Postgres aligns every fixed-width value to the alignment of its type, so a `boolean` before a
+/// `bigint` leaves seven bytes of padding in every row. Ordering the columns from the largest
+/// alignment to the smallest, followed by the variable-length columns, avoids most of it. Short
+/// variable-length values are not aligned, and each is counted as one byte, as an empty text
+/// takes. Tables with a column of a type whose layout is not known are left out. The fix reorders
+/// the columns and keeps the comments above and behind a column with it. The new order is the one
+/// of `SELECT *` and of `INSERT` without a column list, so queries that rely on the old one break.
+pub const RULE: Rule = Rule {
+    name: "column-padding",
+    description: "Ordering columns by alignment avoids padding in rows",
+    group: RuleGroup::SchemaDesign,
+    severity: Severity::Information,
+    stmt_kinds: &[StmtKind::Ddl],
+    check,
+    fix: Some(fix),
+};
+
+/// The length and alignment of the fixed-width types by their normalized name
+const FIXED_WIDTH_TYPES: &[(&str, u32, u32)] = &[
+    ("bool", 1, 1),
+    ("int2", 2, 2),
+    ("smallserial", 2, 2),
+    ("serial2", 2, 2),
+    ("int4", 4, 4),
+    ("serial", 4, 4),
+    ("serial4", 4, 4),
+    ("float4", 4, 4),
+    ("date", 4, 4),
+    ("oid", 4, 4),
+    ("macaddr", 6, 4),
+    ("macaddr8", 8, 4),
+    ("int8", 8, 8),
+    ("bigserial", 8, 8),
+    ("serial8", 8, 8),
+    ("float8", 8, 8),
+    ("money", 8, 8),
+    ("time", 8, 8),
+    ("timestamp", 8, 8),
+    ("timestamptz", 8, 8),
+    ("timetz", 12, 8),
+    ("interval", 16, 8),
+    ("point", 16, 8),
+    ("uuid", 16, 1),
+];
+
+/// The variable-length types
+const VARIABLE_LENGTH_TYPES: &[&str] = &[
+    "bpchar", "bytea", "cidr", "citext", "hstore", "inet", "json", "jsonb", "numeric", "text",
+    "tsquery", "tsvector", "varchar", "xml",
+];
+
+/// How the values of a column are laid out in a row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    Fixed { len: u32, align: u32 },
+    Variable,
+}
+
+impl Layout {
+    /// Columns are ordered by this key, from the largest alignment to the variable-length ones
+    fn order_key(self) -> u32 {
+        match self {
+            Layout::Fixed { align, .. } => u32::MAX - align,
+            Layout::Variable => u32::MAX,
+        }
+    }
+}
+
+fn check(ctx: &mut LintContext<'_>) {
+    let NodeEnum::CreateStmt(n) = &ctx.stmt.stmt else {
+        return;
+    };
+    let (Some(relation), Some(layouts)) = (n.relation.as_ref(), column_layouts(n)) else {
+        return;
+    };
+    let ordered = optimal_order(&layouts)
+        .into_iter()
+        .map(|idx| layouts[idx])
+        .collect::<Vec<_>>();
+    let saved = row_size(&layouts) - row_size(&ordered);
+    if saved > 0 {
+        ctx.report_at_location(
+            format!(
+                "Reordering the columns of {} by alignment saves {} bytes of padding per row, but \
+                 changes the column order of SELECT * and of INSERT without a column list.",
+                qualified_name(relation),
+                saved
+            ),
+            relation.location,
+        );
+    }
+}
+
+fn fix(cst: &ResolvedNode<SyntaxKind>, stmt: &RawStmt, _: &LintDiagnostic) -> Option<LintFix> {
+    let NodeEnum::CreateStmt(n) = &stmt.stmt else {
+        return None;
+    };
+    let layouts = column_layouts(n)?;
+    let relation = n.relation.as_ref()?;
+    let start = stmt.range.start() + TextSize::from(relation.location.max(0) as u32);
+    let tokens = cst
+        .descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| token.text_range().start() >= start)
+        .take_while(|token| token.text_range().end() <= stmt.range.end())
+        .collect::<Vec<_>>();
+    let open = tokens
+        .iter()
+        .position(|token| token.kind() == SyntaxKind::Ascii40)?;
+
+    // split the element list at the commas outside of parentheses
+    let mut segments = vec![Vec::new()];
+    let mut depth = 0;
+    let mut close = None;
+    for (idx, token) in tokens.iter().enumerate().skip(open + 1) {
+        match token.kind() {
+            SyntaxKind::Ascii40 => depth += 1,
+            SyntaxKind::Ascii41 if depth == 0 => {
+                close = Some(idx);
+                break;
+            }
+            SyntaxKind::Ascii41 => depth -= 1,
+            SyntaxKind::Ascii44 if depth == 0 => {
+                segments.push(Vec::new());
+                continue;
+            }
+            _ => {}
+        }
+        segments.last_mut().unwrap().push(*token);
+    }
+    let close = close?;
+    if segments.len() != n.table_elts.len() {
+        return None;
+    }
+    let elements = split_elements(&segments)?;
+
+    // the columns take the places of the columns in their new order, constraints keep theirs
+    let columns = n
+        .table_elts
+        .iter()
+        .enumerate()
+        .filter(|(_, element)| matches!(element.node, Some(NodeEnum::ColumnDef(_))))
+        .map(|(idx, _)| idx)
+        .collect::<Vec<_>>();
+    let mut order = (0..elements.len()).collect::<Vec<_>>();
+    for (slot, column) in columns.iter().zip(optimal_order(&layouts)) {
+        order[*slot] = columns[column];
+    }
+
+    let mut text = String::new();
+    for (slot, &idx) in order.iter().enumerate() {
+        let element = &elements[idx];
+        text.push_str(&elements[slot].layout);
+        text.push_str(&element.body);
+        if slot + 1 < order.len() {
+            text.push(',');
+        }
+        if let Some(trailing) = &element.trailing {
+            text.push(' ');
+            text.push_str(trailing);
+        }
+    }
+    // the whitespace behind the last element
+    let end = elements.last()?.end;
+    let tail = tokens[open + 1..close]
+        .iter()
+        .filter(|token| token.text_range().start() >= end)
+        .map(|token| token.text())
+        .collect::<String>();
+    text.push_str(&tail);
+
+    Some(LintFix {
+        title: "Reorder the columns by alignment, which changes the order of SELECT *".to_string(),
+        range: TextRange::new(
+            tokens[open].text_range().end(),
+            tokens[close].text_range().start(),
+        ),
+        text,
+    })
+}
+
+/// An element of the column list of a `CREATE TABLE`, split from the text around it
+struct Element {
+    /// The whitespace before the element, which stays in its place
+    layout: String,
+    /// The element with the comments above it
+    body: String,
+    /// A comment behind the element on the same line, which moves with it
+    trailing: Option<String>,
+    /// The end of the element including its trailing comment
+    end: TextSize,
+}
+
+/// Splits the segments of the column list between its commas into elements. Returns `None` if a
+/// line comment would end up in front of a comma.
+fn split_elements(segments: &[Vec<&ResolvedToken<SyntaxKind>>]) -> Option<Vec<Element>> {
+    let is_space = |token: &&ResolvedToken<SyntaxKind>| {
+        token.kind().is_trivia()
+            && !matches!(
+                token.kind(),
+                SyntaxKind::LineComment | SyntaxKind::BlockComment
+            )
+    };
+    let is_comment = |token: &&ResolvedToken<SyntaxKind>| {
+        matches!(
+            token.kind(),
+            SyntaxKind::LineComment | SyntaxKind::BlockComment
+        )
+    };
+    let text = |tokens: &[&ResolvedToken<SyntaxKind>]| {
+        tokens.iter().map(|token| token.text()).collect::<String>()
+    };
+
+    let mut elements: Vec<Element> = Vec::new();
+    for (idx, segment) in segments.iter().enumerate() {
+        let mut segment = segment.as_slice();
+        // a comment behind the comma on the same line belongs to the element before it
+        let line_end = segment
+            .iter()
+            .position(|token| token.kind() == SyntaxKind::Newline || !token.kind().is_trivia())
+            .unwrap_or(segment.len());
+        if let Some(previous) = elements.last_mut() {
+            if let Some(first) = segment[..line_end].iter().position(is_comment) {
+                previous.trailing = Some(text(&segment[first..line_end]).trim_end().to_string());
+                previous.end = segment[line_end - 1].text_range().end();
+                segment = &segment[line_end..];
+            }
+        }
+
+        let body_start = segment.iter().position(|token| !is_space(token))?;
+        let is_last = idx + 1 == segments.len();
+        let body_end = if is_last {
+            segment
+                .iter()
+                .rposition(|token| !token.kind().is_trivia())?
+                + 1
+        } else {
+            segment.iter().rposition(|token| !is_space(token))? + 1
+        };
+        if segment[body_end - 1].kind() == SyntaxKind::LineComment {
+            return None;
+        }
+        let mut element = Element {
+            layout: text(&segment[..body_start]),
+            body: text(&segment[body_start..body_end]),
+            trailing: None,
+            end: segment[body_end - 1].text_range().end(),
+        };
+        if is_last {
+            let rest = &segment[body_end..];
+            let line_end = rest
+                .iter()
+                .position(|token| token.kind() == SyntaxKind::Newline)
+                .unwrap_or(rest.len());
+            if let Some(first) = rest[..line_end].iter().position(is_comment) {
+                element.trailing = Some(text(&rest[first..line_end]).trim_end().to_string());
+                element.end = rest[line_end - 1].text_range().end();
+            }
+        }
+        elements.push(element);
+    }
+
+    // a line comment behind an element must be followed by a line break wherever it ends up
+    let breaks_line = |layout: &str| layout.contains('\n');
+    let all_break = elements.iter().skip(1).all(|e| breaks_line(&e.layout));
+    let has_line_comment = elements
+        .iter()
+        .any(|e| e.trailing.as_deref().is_some_and(|t| t.starts_with("--")));
+    if has_line_comment && !all_break {
+        return None;
+    }
+    Some(elements)
+}
+
+/// Returns the layouts of the columns of `n`, or `None` if it is not a plain table or the layout
+/// of a type is not known
+fn column_layouts(n: &CreateStmt) -> Option<Vec<Layout>> {
+    if n.partbound.is_some() || n.of_typename.is_some() {
+        return None;
+    }
+    let mut layouts = Vec::new();
+    for element in &n.table_elts {
+        match element.node.as_ref()? {
+            NodeEnum::ColumnDef(column) => {
+                let type_name = column.type_name.as_ref()?;
+                if !type_name.array_bounds.is_empty() {
+                    layouts.push(Layout::Variable);
+                    continue;
+                }
+                let name = type_name(type_name)?;
+                let layout = match FIXED_WIDTH_TYPES.iter().find(|(t, _, _)| *t == name) {
+                    Some((_, len, align)) => Layout::Fixed {
+                        len: *len,
+                        align: *align,
+                    },
+                    None if VARIABLE_LENGTH_TYPES.contains(&name.as_str()) => Layout::Variable,
+                    None => return None,
+                };
+                layouts.push(layout);
+            }
+            NodeEnum::Constraint(_) => {}
+            _ => return None,
+        }
+    }
+    Some(layouts)
+}
+
+/// Returns the indexes of `layouts` in the order with the least padding, which keeps the order of
+/// columns with the same alignment
+fn optimal_order(layouts: &[Layout]) -> Vec<usize> {
+    let mut order = (0..layouts.len()).collect::<Vec<_>>();
+    order.sort_by_key(|idx| layouts[*idx].order_key());
+    order
+}
+
+/// Returns the size of a row with columns of `layouts`, without its header
+fn row_size(layouts: &[Layout]) -> u32 {
+    let end = layouts.iter().fold(0, |offset: u32, layout| match layout {
+        Layout::Fixed { len, align } => offset.next_multiple_of(*align) + len,
+        Layout::Variable => offset + 1,
+    });
+    // rows are aligned to 8 bytes as a whole
+    end.next_multiple_of(8)
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use crate::lint::{lint_fix, lint_with_config, LintConfig};
+
+    fn diagnostics(input: &str) -> Vec<(String, Option<String>)> {
+        let parse = parse_source(input);
+        lint_with_config(&parse.stmts, &LintConfig::all())
+            .iter()
+            .filter(|d| d.rule == "column-padding")
+            .map(|d| {
+                let fixed = lint_fix(&parse.cst, &parse.stmts, d).map(|fix| {
+                    let mut text = input.to_string();
+                    text.replace_range(std::ops::Range::<usize>::from(fix.range), &fix.text);
+                    text
+                });
+                (d.message.clone(), fixed)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_column_padding() {
+        assert_eq!(
+            diagnostics(
+                "create table event (
+    -- whether the event is public
+    is_public boolean,
+    id bigint, -- the key
+    name text,
+    created_at timestamptz,
+    primary key (id)
+);"
+            ),
+            [(
+                "Reordering the columns of public.event by alignment saves 8 bytes of padding per \
+                 row, but changes the column order of SELECT * and of INSERT without a column list."
+                    .to_string(),
+                Some(
+                    "create table event (
+    id bigint, -- the key
+    created_at timestamptz,
+    -- whether the event is public
+    is_public boolean,
+    name text,
+    primary key (id)
+);"
+                    .to_string()
+                )
+            )]
+        );
+        assert_eq!(
+            diagnostics("create table t (a bool, b int8, c numeric(10, 2), d int2)")[0].1,
+            Some("create table t (b int8, d int2, a bool, c numeric(10, 2))".to_string())
+        );
+        // aligned already, or with a type of unknown layout
+        assert!(diagnostics(
+            "create table t (id bigint, created_at timestamptz, flag boolean, name text);
+            create table u (a bool, b int8, c mood);"
+        )
+        .is_empty());
+    }
+}
@@ -0,0 +1,69 @@
+use parser::StmtKind;
+use pg_query::NodeEnum;
+
+use super::{LintContext, Rule, RuleGroup, Severity};
+use crate::cast_graph::cast_types;
+
+/// Flags `CREATE CAST ... AS IMPLICIT` if either side of the cast is a text type.
+///
+/// Implicit casts to or from text make function and operator resolution ambiguous, and silently
+/// accept values where a type error would have been raised otherwise. Postgres removed most of
+/// its own implicit casts to text in 8.3 for exactly this reason.
+pub const RULE: Rule = Rule {
+    name: "implicit-text-cast",
+    description: "Implicit casts to or from text make function and operator resolution ambiguous",
+    group: RuleGroup::Recommended,
+    severity: Severity::Warning,
+    stmt_kinds: &[StmtKind::Ddl],
+    check,
+    fix: None,
+};
+
+const TEXT_TYPES: &[&str] = &["text", "varchar", "bpchar"];
+
+fn check(ctx: &mut LintContext<'_>) {
+    if let NodeEnum::CreateCastStmt(n) = &ctx.stmt.stmt {
+        // CoercionImplicit
+        if n.context != 1 {
+            return;
+        }
+        if let Some((source, target)) = cast_types(n) {
+            if TEXT_TYPES.contains(&source.as_str()) || TEXT_TYPES.contains(&target.as_str()) {
+                ctx.report_stmt(format!(
+                    "Implicit cast from {} to {} makes function and operator resolution ambiguous. Use AS ASSIGNMENT or an explicit cast instead.",
+                    source, target
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use crate::lint::lint;
+
+    #[test]
+    fn test_implicit_text_cast() {
+        let diagnostics =
+            lint(&parse_source("CREATE CAST (mytype AS text) WITH INOUT AS IMPLICIT;").stmts);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].rule, "implicit-text-cast");
+        assert_eq!(usize::from(diagnostics[0].range.start()), 0);
+    }
+
+    #[test]
+    fn test_assignment_text_cast() {
+        let diagnostics =
+            lint(&parse_source("CREATE CAST (mytype AS text) WITH INOUT AS ASSIGNMENT;").stmts);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_implicit_non_text_cast() {
+        let diagnostics =
+            lint(&parse_source("CREATE CAST (int8 AS mytype) WITHOUT FUNCTION AS IMPLICIT;").stmts);
+        assert!(diagnostics.is_empty());
+    }
+}
@@ -0,0 +1,294 @@
+//! The linter.
+//!
+//! Every rule is a plain function that inspects a single statement and reports its findings to a
+//! `LintContext`. Rules are registered in the static `RULES` list below, which is also the place
+//! to look for all available rule names. The findings of a rule at a statement only depend on the
+//! statement itself and on the `TableFacts` that the statements of the workspace declare, e.g. a
+//! primary key that a later `ALTER TABLE` adds.
+//!
+//! Rules that only apply to some kinds of statements, e.g. to DDL, declare them in `stmt_kinds`
+//! and are not run on other statements.
+//!
+//! A few rules need more than that, e.g. the schemas of the database. They are registered here so
+//! that they can be configured like the others, but checked by their own module, see
+//! [`crate::fillfactor`].
+//!
+//! Each rule belongs to a `RuleGroup`. Only the recommended rules run by default, opinionated
+//! groups such as `modern-postgres` have to be enabled with a `LintConfig`.
+//!
+//! Rules can offer a `LintFix` for their diagnostics, which editors show as a quick fix.
+
+mod char_type;
+mod column_padding;
+mod implicit_text_cast;
+mod money_type;
+mod prefer_identity;
+mod prefer_jsonb;
+mod prefer_text;
+mod prefer_timestamptz;
+mod prefer_trigger_over_rule;
+mod replica_identity;
+mod table_facts;
+mod table_without_primary_key;
+mod truncate_table;
+mod type_fix;
+mod with_oids;
+
+use cstree::syntax::ResolvedNode;
+use cstree::text::{TextRange, TextSize};
+use parser::{Cancellation, Cancelled, RawStmt, StmtKind, SyntaxKind};
+
+pub use self::table_facts::TableFacts;
+
+/// The severity of a lint diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+/// A diagnostic reported by a lint rule
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LintDiagnostic {
+    /// The name of the rule that reported the diagnostic
+    pub rule: &'static str,
+    pub message: String,
+    pub severity: Severity,
+    /// The range in the source text
+    pub range: TextRange,
+}
+
+/// An edit that fixes a lint diagnostic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintFix {
+    pub title: String,
+    /// The range that `text` replaces, empty for an insertion
+    pub range: TextRange,
+    pub text: String,
+}
+
+/// A group of lint rules that are enabled or disabled together
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RuleGroup {
+    /// Rules that catch likely mistakes. Enabled by default.
+    Recommended,
+    /// Opinionated rules that flag legacy constructs which have better alternatives in modern
+    /// Postgres, such as rules, `money` or `char(n)`
+    ModernPostgres,
+    /// Rules for tables that must identify their rows, e.g. to publish updates and deletes with
+    /// logical replication
+    Replication,
+    /// Type choices of widely used Postgres style guides, such as `timestamptz` over `timestamp`
+    /// or identity columns over `serial`, and column orders without alignment padding
+    SchemaDesign,
+}
+
+impl RuleGroup {
+    /// The name of the group in kebab-case
+    pub fn name(&self) -> &'static str {
+        match self {
+            RuleGroup::Recommended => "recommended",
+            RuleGroup::ModernPostgres => "modern-postgres",
+            RuleGroup::Replication => "replication",
+            RuleGroup::SchemaDesign => "schema-design",
+        }
+    }
+
+    /// Returns the group with the given `name`, if any
+    pub fn from_name(name: &str) -> Option<RuleGroup> {
+        match name {
+            "recommended" => Some(RuleGroup::Recommended),
+            "modern-postgres" => Some(RuleGroup::ModernPostgres),
+            "replication" => Some(RuleGroup::Replication),
+            "schema-design" => Some(RuleGroup::SchemaDesign),
+            _ => None,
+        }
+    }
+}
+
+/// Configures which lint rules are run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintConfig {
+    /// The enabled rule groups
+    pub groups: Vec<RuleGroup>,
+    /// The names of rules that are disabled even though their group is enabled
+    pub disabled_rules: Vec<String>,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            groups: vec![RuleGroup::Recommended],
+            disabled_rules: Vec::new(),
+        }
+    }
+}
+
+impl LintConfig {
+    /// Returns a config with all rule groups enabled
+    pub fn all() -> Self {
+        Self {
+            groups: vec![
+                RuleGroup::Recommended,
+                RuleGroup::ModernPostgres,
+                RuleGroup::Replication,
+                RuleGroup::SchemaDesign,
+            ],
+            disabled_rules: Vec::new(),
+        }
+    }
+
+    /// Enables `group` in addition to the groups that are already enabled
+    pub fn with_group(mut self, group: RuleGroup) -> Self {
+        if !self.groups.contains(&group) {
+            self.groups.push(group);
+        }
+        self
+    }
+
+    /// Returns true if `rule` should be run
+    pub fn is_enabled(&self, rule: &Rule) -> bool {
+        self.groups.contains(&rule.group) && !self.disabled_rules.iter().any(|r| r == rule.name)
+    }
+}
+
+/// A lint rule
+pub struct Rule {
+    /// The unique name of the rule in kebab-case
+    pub name: &'static str,
+    /// What the rule checks in a sentence, which is translated like the diagnostics
+    pub description: &'static str,
+    /// The group the rule belongs to
+    pub group: RuleGroup,
+    /// The default severity of diagnostics reported by this rule
+    pub severity: Severity,
+    /// The kinds of statements the rule checks, all if empty
+    pub stmt_kinds: &'static [StmtKind],
+    /// Checks a single statement and reports diagnostics to the context
+    pub check: fn(&mut LintContext<'_>),
+    /// Returns the fix of a diagnostic that the rule reported for a statement, if it has one
+    pub fix: Option<fn(&ResolvedNode<SyntaxKind>, &RawStmt, &LintDiagnostic) -> Option<LintFix>>,
+}
+
+/// The state passed to a rule while it checks a statement
+pub struct LintContext<'a> {
+    pub stmt: &'a RawStmt,
+    /// What the statements of the workspace declare about its tables
+    pub tables: &'a TableFacts,
+    rule: &'a Rule,
+    diagnostics: Vec<LintDiagnostic>,
+}
+
+impl<'a> LintContext<'a> {
+    /// collects a diagnostic with `message` at `range`
+    pub fn report(&mut self, message: impl Into<String>, range: TextRange) {
+        self.diagnostics.push(LintDiagnostic {
+            rule: self.rule.name,
+            message: message.into(),
+            severity: self.rule.severity,
+            range,
+        });
+    }
+
+    /// collects a diagnostic with `message` at `location`, which is an offset within the statement
+    /// as reported by pg_query
+    pub fn report_at_location(&mut self, message: impl Into<String>, location: i32) {
+        let offset = self.stmt.range.start() + TextSize::from(location.max(0) as u32);
+        self.report(message, TextRange::empty(offset));
+    }
+
+    /// collects a diagnostic with `message` for the entire statement
+    pub fn report_stmt(&mut self, message: impl Into<String>) {
+        let range = self.stmt.range;
+        self.report(message, range);
+    }
+}
+
+/// All available lint rules
+pub static RULES: &[Rule] = &[
+    implicit_text_cast::RULE,
+    prefer_trigger_over_rule::RULE,
+    with_oids::RULE,
+    money_type::RULE,
+    char_type::RULE,
+    truncate_table::RULE,
+    table_without_primary_key::RULE,
+    replica_identity::RULE,
+    prefer_timestamptz::RULE,
+    prefer_text::RULE,
+    prefer_identity::RULE,
+    prefer_jsonb::RULE,
+    column_padding::RULE,
+    crate::fillfactor::FILLFACTOR_RULE,
+    crate::fillfactor::NON_HOT_UPDATE_RULE,
+];
+
+/// Returns the group of the lint rule `name`, or `None` if no lint rule has that name, e.g. because
+/// the diagnostic was reported by a check against the schema
+pub fn rule_group(name: &str) -> Option<RuleGroup> {
+    RULES
+        .iter()
+        .find(|rule| rule.name == name)
+        .map(|rule| rule.group)
+}
+
+/// Runs the recommended lint rules on `stmts`
+pub fn lint(stmts: &[RawStmt]) -> Vec<LintDiagnostic> {
+    lint_with_config(stmts, &LintConfig::default())
+}
+
+/// Runs all lint rules enabled in `config` on `stmts`, with the `TableFacts` of `stmts` only
+pub fn lint_with_config(stmts: &[RawStmt], config: &LintConfig) -> Vec<LintDiagnostic> {
+    let tables = TableFacts::collect([stmts]);
+    lint_cancellable(stmts, config, &tables, &Cancellation::new()).unwrap_or_default()
+}
+
+/// Like `lint_with_config`, but with the `tables` of the whole workspace, and stops before the next
+/// statement once `cancellation` has been cancelled
+pub fn lint_cancellable(
+    stmts: &[RawStmt],
+    config: &LintConfig,
+    tables: &TableFacts,
+    cancellation: &Cancellation,
+) -> Result<Vec<LintDiagnostic>, Cancelled> {
+    let rules = RULES
+        .iter()
+        .filter(|rule| config.is_enabled(rule))
+        .collect::<Vec<&Rule>>();
+    let mut diagnostics = Vec::new();
+    for stmt in stmts {
+        cancellation.check()?;
+        let kind = SyntaxKind::from(&stmt.stmt).stmt_kind();
+        for rule in rules.iter().filter(|rule| {
+            rule.stmt_kinds.is_empty() || kind.is_some_and(|k| rule.stmt_kinds.contains(&k))
+        }) {
+            let mut ctx = LintContext {
+                stmt,
+                tables,
+                rule,
+                diagnostics: Vec::new(),
+            };
+            (rule.check)(&mut ctx);
+            diagnostics.extend(ctx.diagnostics);
+        }
+    }
+    Ok(diagnostics)
+}
+
+/// Returns the fix of `diagnostic`, if the rule that reported it offers one
+pub fn lint_fix(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmts: &[RawStmt],
+    diagnostic: &LintDiagnostic,
+) -> Option<LintFix> {
+    let fix = RULES
+        .iter()
+        .find(|rule| rule.name == diagnostic.rule)?
+        .fix?;
+    let stmt = stmts
+        .iter()
+        .find(|stmt| stmt.range.contains_range(diagnostic.range))?;
+    fix(cst, stmt, diagnostic)
+}
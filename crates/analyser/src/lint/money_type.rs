@@ -0,0 +1,55 @@
+use super::{LintContext, Rule, RuleGroup, Severity};
+use crate::utils::type_names;
+
+/// Flags usages of the `money` type.
+///
+/// `money` stores a fixed number of fractional digits that depends on the `lc_monetary` setting,
+/// so its input and output change with the locale of the session. Use `numeric`, optionally
+/// together with a currency column, instead.
+pub const RULE: Rule = Rule {
+    name: "money-type",
+    description: "The money type depends on the lc_monetary setting",
+    group: RuleGroup::ModernPostgres,
+    severity: Severity::Warning,
+    stmt_kinds: &[],
+    check,
+    fix: None,
+};
+
+fn check(ctx: &mut LintContext<'_>) {
+    for (t, name) in type_names(&ctx.stmt.stmt) {
+        if name == "money" {
+            ctx.report_at_location(
+                "The money type depends on the lc_monetary setting. Use numeric instead.",
+                t.location,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use crate::lint::{lint_with_config, LintConfig, RuleGroup};
+
+    #[test]
+    fn test_money_type() {
+        let diagnostics = lint_with_config(
+            &parse_source("CREATE TABLE t (a int, price money, total numeric);").stmts,
+            &LintConfig::default().with_group(RuleGroup::ModernPostgres),
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].rule, "money-type");
+        assert_eq!(usize::from(diagnostics[0].range.start()), 29);
+    }
+
+    #[test]
+    fn test_money_cast() {
+        let diagnostics = lint_with_config(
+            &parse_source("SELECT 1.5::money;").stmts,
+            &LintConfig::all(),
+        );
+        assert_eq!(diagnostics.len(), 1);
+    }
+}
@@ -0,0 +1,100 @@
+use cstree::syntax::ResolvedNode;
+use parser::{RawStmt, SyntaxKind};
+use pg_query::NodeEnum;
+
+use super::type_fix::{replace_type, reported_type};
+use super::{LintContext, LintDiagnostic, LintFix, Rule, RuleGroup, Severity};
+use crate::utils::{descendants, type_name};
+
+/// Flags columns of type `serial`, `bigserial` or `smallserial`.
+///
+/// A serial column is an integer column with a default from a sequence that the table owns, but
+/// the sequence has its own permissions and is not copied by `CREATE TABLE ... LIKE`. Identity
+/// columns are standard SQL and tie the sequence to the column. The fix uses
+/// `GENERATED BY DEFAULT AS IDENTITY`, which like `serial` still accepts explicit values.
+pub const RULE: Rule = Rule {
+    name: "prefer-identity",
+    description: "Identity columns are preferred over serial columns",
+    group: RuleGroup::SchemaDesign,
+    severity: Severity::Warning,
+    stmt_kinds: &[],
+    check,
+    fix: Some(fix),
+};
+
+fn check(ctx: &mut LintContext<'_>) {
+    let columns = descendants(&ctx.stmt.stmt)
+        .into_iter()
+        .filter_map(|node| match node {
+            NodeEnum::ColumnDef(c) => c.type_name,
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    for t in columns {
+        if type_name(&t).as_deref().and_then(integer_type).is_some() {
+            ctx.report_at_location(
+                "serial columns depend on a separate sequence. Use an identity column instead.",
+                t.location,
+            );
+        }
+    }
+}
+
+fn fix(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmt: &RawStmt,
+    diagnostic: &LintDiagnostic,
+) -> Option<LintFix> {
+    let source = reported_type(cst, stmt, diagnostic)?;
+    let text = format!(
+        "{} GENERATED BY DEFAULT AS IDENTITY",
+        integer_type(&source.name)?
+    );
+    Some(replace_type("Use an identity column", &source, &text))
+}
+
+/// Returns the integer type of a serial type
+fn integer_type(name: &str) -> Option<&'static str> {
+    match name {
+        "smallserial" | "serial2" => Some("smallint"),
+        "serial" | "serial4" => Some("integer"),
+        "bigserial" | "serial8" => Some("bigint"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use crate::lint::{lint_fix, lint_with_config, LintConfig, RuleGroup};
+
+    #[test]
+    fn test_prefer_identity() {
+        let input = "CREATE TABLE t (id BIGSERIAL PRIMARY KEY, n int);";
+        let parse = parse_source(input);
+        let diagnostics = lint_with_config(
+            &parse.stmts,
+            &LintConfig::default().with_group(RuleGroup::SchemaDesign),
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].rule, "prefer-identity");
+        let fix = lint_fix(&parse.cst, &parse.stmts, &diagnostics[0]).unwrap();
+        let mut text = input.to_string();
+        text.replace_range(std::ops::Range::<usize>::from(fix.range), &fix.text);
+        assert_eq!(
+            text,
+            "CREATE TABLE t (id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY, n int);"
+        );
+
+        let parse = parse_source("alter table t add column n serial;");
+        assert_eq!(
+            lint_with_config(
+                &parse.stmts,
+                &LintConfig::default().with_group(RuleGroup::SchemaDesign)
+            )[0]
+            .rule,
+            "prefer-identity"
+        );
+    }
+}
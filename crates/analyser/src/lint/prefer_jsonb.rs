@@ -0,0 +1,63 @@
+use cstree::syntax::ResolvedNode;
+use parser::{RawStmt, StmtKind, SyntaxKind};
+
+use super::type_fix::{declared_types, replace_type, reported_type};
+use super::{LintContext, LintDiagnostic, LintFix, Rule, RuleGroup, Severity};
+
+/// Flags columns and domains of type `json`.
+///
+/// `json` stores the input text as is and parses it again on every access. `jsonb` is parsed
+/// once, supports indexes and containment operators, and has equality, so it can be used in
+/// `DISTINCT`, `GROUP BY` and unique constraints. Use `json` only if the exact input text,
+/// including key order and duplicate keys, must be kept.
+pub const RULE: Rule = Rule {
+    name: "prefer-jsonb",
+    description: "jsonb is preferred over json",
+    group: RuleGroup::SchemaDesign,
+    severity: Severity::Warning,
+    stmt_kinds: &[StmtKind::Ddl],
+    check,
+    fix: Some(fix),
+};
+
+fn check(ctx: &mut LintContext<'_>) {
+    for (t, name) in declared_types(&ctx.stmt.stmt) {
+        if name == "json" {
+            ctx.report_at_location(
+                "json is parsed on every access and cannot be indexed. Use jsonb instead.",
+                t.location,
+            );
+        }
+    }
+}
+
+fn fix(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmt: &RawStmt,
+    diagnostic: &LintDiagnostic,
+) -> Option<LintFix> {
+    let source = reported_type(cst, stmt, diagnostic)?;
+    Some(replace_type("Use jsonb", &source, "jsonb"))
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use crate::lint::{lint_fix, lint_with_config, LintConfig, RuleGroup};
+
+    #[test]
+    fn test_prefer_jsonb() {
+        let input = "create table t (a json, b jsonb); select '{}'::json;";
+        let parse = parse_source(input);
+        let diagnostics = lint_with_config(
+            &parse.stmts,
+            &LintConfig::default().with_group(RuleGroup::SchemaDesign),
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].rule, "prefer-jsonb");
+        let fix = lint_fix(&parse.cst, &parse.stmts, &diagnostics[0]).unwrap();
+        assert_eq!(&input[fix.range], "json");
+        assert_eq!(fix.text, "jsonb");
+    }
+}
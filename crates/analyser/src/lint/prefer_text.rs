@@ -0,0 +1,66 @@
+use cstree::syntax::ResolvedNode;
+use parser::{RawStmt, StmtKind, SyntaxKind};
+
+use super::type_fix::{declared_types, replace_type, reported_type};
+use super::{LintContext, LintDiagnostic, LintFix, Rule, RuleGroup, Severity};
+
+/// Flags columns and domains of type `varchar(n)`, also spelled `character varying(n)`.
+///
+/// `varchar(n)` is stored exactly like `text`, and its length limit is rarely the actual rule for
+/// the values. Raising the limit later needs an `ALTER TABLE`, and lowering it rewrites the table.
+/// Use `text`, with a check constraint on the length where one is needed. `varchar` without a
+/// length is the same as `text` and is not flagged.
+pub const RULE: Rule = Rule {
+    name: "prefer-text",
+    description: "text is preferred over varchar(n)",
+    group: RuleGroup::SchemaDesign,
+    severity: Severity::Warning,
+    stmt_kinds: &[StmtKind::Ddl],
+    check,
+    fix: Some(fix),
+};
+
+fn check(ctx: &mut LintContext<'_>) {
+    for (t, name) in declared_types(&ctx.stmt.stmt) {
+        if name == "varchar" && !t.typmods.is_empty() {
+            ctx.report_at_location(
+                "varchar(n) is no faster than text and its limit is hard to change. Use text with a check constraint instead.",
+                t.location,
+            );
+        }
+    }
+}
+
+fn fix(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmt: &RawStmt,
+    diagnostic: &LintDiagnostic,
+) -> Option<LintFix> {
+    let source = reported_type(cst, stmt, diagnostic)?;
+    Some(replace_type("Use text", &source, "text"))
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use crate::lint::{lint_fix, lint_with_config, LintConfig, RuleGroup};
+
+    #[test]
+    fn test_prefer_text() {
+        let input = "create table t (a varchar(20), b character varying (10)[], c varchar);";
+        let parse = parse_source(input);
+        let diagnostics = lint_with_config(
+            &parse.stmts,
+            &LintConfig::default().with_group(RuleGroup::SchemaDesign),
+        );
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].rule, "prefer-text");
+        let mut text = input.to_string();
+        for d in diagnostics.iter().rev() {
+            let fix = lint_fix(&parse.cst, &parse.stmts, d).unwrap();
+            text.replace_range(std::ops::Range::<usize>::from(fix.range), &fix.text);
+        }
+        assert_eq!(text, "create table t (a text, b text[], c varchar);");
+    }
+}
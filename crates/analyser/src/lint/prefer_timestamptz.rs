@@ -0,0 +1,88 @@
+use cstree::syntax::ResolvedNode;
+use parser::{RawStmt, StmtKind, SyntaxKind};
+
+use super::type_fix::{declared_types, replace_type, reported_type};
+use super::{LintContext, LintDiagnostic, LintFix, Rule, RuleGroup, Severity};
+
+/// Flags columns and domains of type `timestamp`, also spelled `timestamp without time zone`.
+///
+/// A `timestamp` does not know the time zone it was written in, so the same value means different
+/// points in time for sessions with different `TimeZone` settings, and comparing it with `now()`
+/// silently converts it. `timestamptz` stores a point in time and takes the same space. The fix
+/// keeps the precision, e.g. `timestamp(3)` becomes `timestamptz(3)`.
+pub const RULE: Rule = Rule {
+    name: "prefer-timestamptz",
+    description: "timestamptz is preferred over timestamp",
+    group: RuleGroup::SchemaDesign,
+    severity: Severity::Warning,
+    stmt_kinds: &[StmtKind::Ddl],
+    check,
+    fix: Some(fix),
+};
+
+fn check(ctx: &mut LintContext<'_>) {
+    for (t, name) in declared_types(&ctx.stmt.stmt) {
+        if name == "timestamp" {
+            ctx.report_at_location(
+                "timestamp does not store a time zone. Use timestamptz instead.",
+                t.location,
+            );
+        }
+    }
+}
+
+fn fix(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmt: &RawStmt,
+    diagnostic: &LintDiagnostic,
+) -> Option<LintFix> {
+    let source = reported_type(cst, stmt, diagnostic)?;
+    let text = format!(
+        "timestamptz{}",
+        source.modifiers.as_deref().unwrap_or_default()
+    );
+    Some(replace_type("Use timestamptz", &source, &text))
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use crate::lint::{lint_fix, lint_with_config, LintConfig, RuleGroup};
+
+    #[test]
+    fn test_prefer_timestamptz() {
+        let input = "CREATE TABLE t (a timestamp, b TIMESTAMP(3) WITHOUT TIME ZONE NOT NULL, c timestamptz);";
+        let parse = parse_source(input);
+        let diagnostics = lint_with_config(
+            &parse.stmts,
+            &LintConfig::default().with_group(RuleGroup::SchemaDesign),
+        );
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].rule, "prefer-timestamptz");
+        let mut text = input.to_string();
+        for d in diagnostics.iter().rev() {
+            let fix = lint_fix(&parse.cst, &parse.stmts, d).unwrap();
+            text.replace_range(std::ops::Range::<usize>::from(fix.range), &fix.text);
+        }
+        assert_eq!(
+            text,
+            "CREATE TABLE t (a timestamptz, b TIMESTAMPTZ(3) NOT NULL, c timestamptz);"
+        );
+    }
+
+    #[test]
+    fn test_prefer_timestamptz_declared_types() {
+        let diagnostics = lint_with_config(
+            &parse_source(
+                "alter table t alter column a type timestamp;
+                create domain created_at as timestamp;
+                select now()::timestamp;
+                create function f(at timestamp) returns timestamp language sql as 'select at';",
+            )
+            .stmts,
+            &LintConfig::default().with_group(RuleGroup::SchemaDesign),
+        );
+        assert_eq!(diagnostics.len(), 2);
+    }
+}
@@ -0,0 +1,58 @@
+use parser::StmtKind;
+use pg_query::NodeEnum;
+
+use super::{LintContext, Rule, RuleGroup, Severity};
+
+/// Flags `CREATE RULE`.
+///
+/// Rules rewrite queries before they are planned, which makes their effect on multi-row
+/// statements, `RETURNING` and volatile functions surprising. Triggers cover almost all use cases
+/// of rules that write data, and views cover the ones that rewrite `SELECT`.
+pub const RULE: Rule = Rule {
+    name: "prefer-trigger-over-rule",
+    description: "Triggers and views are preferred over rules",
+    group: RuleGroup::ModernPostgres,
+    severity: Severity::Warning,
+    stmt_kinds: &[StmtKind::Ddl],
+    check,
+    fix: None,
+};
+
+fn check(ctx: &mut LintContext<'_>) {
+    if let NodeEnum::RuleStmt(n) = &ctx.stmt.stmt {
+        // CmdSelect
+        let message = if n.event == 2 {
+            format!(
+                "Rules are rewritten into the query in ways that are hard to reason about. Use a view instead of rule {}.",
+                n.rulename
+            )
+        } else {
+            format!(
+                "Rules are rewritten into the query in ways that are hard to reason about. Use a trigger instead of rule {}.",
+                n.rulename
+            )
+        };
+        ctx.report_stmt(message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use crate::lint::{lint, lint_with_config, LintConfig};
+
+    #[test]
+    fn test_rule() {
+        let stmts = parse_source(
+            "CREATE RULE protect AS ON DELETE TO accounts WHERE old.locked DO INSTEAD NOTHING;",
+        )
+        .stmts;
+        assert!(lint(&stmts).is_empty());
+
+        let diagnostics = lint_with_config(&stmts, &LintConfig::all());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].rule, "prefer-trigger-over-rule");
+        assert!(diagnostics[0].message.contains("trigger"));
+    }
+}
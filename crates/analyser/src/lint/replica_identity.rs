@@ -0,0 +1,122 @@
+use cstree::syntax::ResolvedNode;
+use cstree::text::TextRange;
+use parser::make::quote_ident;
+use parser::{RawStmt, StmtKind, SyntaxKind};
+use pg_query::NodeEnum;
+
+use super::table_without_primary_key::is_derived;
+use super::{LintContext, LintDiagnostic, LintFix, Rule, RuleGroup, Severity};
+use crate::ddl_rewrite::stmt_end;
+use crate::moniker::{qualified_name, DEFAULT_SCHEMA};
+
+/// Flags `CREATE TABLE` of a table that a publication of the workspace publishes, if the table has
+/// neither a primary key nor a replica identity.
+///
+/// Logical replication identifies the rows of updates and deletes by the replica identity of the
+/// table, which is its primary key by default. Without one, Postgres rejects updates and deletes
+/// of the published table. Publications that only publish inserts and truncates are left out. The
+/// fix sets `REPLICA IDENTITY FULL`, which identifies rows by all of their columns.
+pub const RULE: Rule = Rule {
+    name: "published-table-without-replica-identity",
+    description: "Published tables need a primary key or replica identity",
+    group: RuleGroup::Replication,
+    severity: Severity::Warning,
+    stmt_kinds: &[StmtKind::Ddl],
+    check,
+    fix: Some(fix),
+};
+
+fn check(ctx: &mut LintContext<'_>) {
+    if let NodeEnum::CreateStmt(n) = &ctx.stmt.stmt {
+        let Some(relation) = n.relation.as_ref() else {
+            return;
+        };
+        let name = qualified_name(relation);
+        if is_derived(n)
+            || !ctx.tables.is_published(&name)
+            || ctx.tables.has_primary_key(&name)
+            || ctx.tables.has_replica_identity(&name)
+        {
+            return;
+        }
+        ctx.report_at_location(
+            format!(
+                "Table {} is published without a primary key or replica identity. Postgres rejects its updates and deletes.",
+                name
+            ),
+            relation.location,
+        );
+    }
+}
+
+fn fix(cst: &ResolvedNode<SyntaxKind>, stmt: &RawStmt, _: &LintDiagnostic) -> Option<LintFix> {
+    let NodeEnum::CreateStmt(n) = &stmt.stmt else {
+        return None;
+    };
+    let relation = n.relation.as_ref()?;
+    let table = if relation.schemaname.is_empty() || relation.schemaname == DEFAULT_SCHEMA {
+        quote_ident(&relation.relname)
+    } else {
+        format!(
+            "{}.{}",
+            quote_ident(&relation.schemaname),
+            quote_ident(&relation.relname)
+        )
+    };
+    Some(LintFix {
+        title: "Set REPLICA IDENTITY FULL".to_string(),
+        range: TextRange::empty(stmt_end(cst, stmt)),
+        text: format!("\nALTER TABLE {} REPLICA IDENTITY FULL;", table),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use crate::lint::{lint_fix, lint_with_config, LintConfig, LintDiagnostic};
+
+    fn diagnostics(input: &str) -> Vec<LintDiagnostic> {
+        lint_with_config(&parse_source(input).stmts, &LintConfig::all())
+            .into_iter()
+            .filter(|d| d.rule == "published-table-without-replica-identity")
+            .collect()
+    }
+
+    #[test]
+    fn test_replica_identity() {
+        let input = "create table app.note (body text);
+create publication notes for table app.note;";
+        let parse = parse_source(input);
+        let found = diagnostics(input);
+        assert_eq!(found.len(), 1);
+        let fix = lint_fix(&parse.cst, &parse.stmts, &found[0]).unwrap();
+        let mut text = input.to_string();
+        text.insert_str(fix.range.start().into(), &fix.text);
+        assert_eq!(
+            text,
+            "create table app.note (body text);
+ALTER TABLE app.note REPLICA IDENTITY FULL;
+create publication notes for table app.note;"
+        );
+
+        assert_eq!(
+            diagnostics(
+                "create table note (body text);
+                create publication notes for tables in schema public;"
+            )
+            .len(),
+            1
+        );
+        assert!(diagnostics(
+            "create table note (body text);
+            alter table note replica identity full;
+            create table contact (id int primary key);
+            create publication all_tables for all tables;
+            create table event (body text);
+            create publication events for table event with (publish = 'insert');"
+        )
+        .is_empty());
+        assert!(diagnostics("create table note (body text);").is_empty());
+    }
+}
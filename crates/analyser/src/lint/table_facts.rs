@@ -0,0 +1,186 @@
+use std::collections::BTreeSet;
+
+use parser::RawStmt;
+use pg_query::protobuf::Node;
+use pg_query::NodeEnum;
+
+use crate::foreign_key_index::PRIMARY_KEY;
+use crate::migrations::alter_table_cmds;
+use crate::moniker::{qualified_name, DEFAULT_SCHEMA};
+use crate::utils::string_value;
+
+/// `AP_DropObjects`
+const DROP_OBJECTS: i32 = 2;
+/// `PUBLICATIONOBJ_TABLES_IN_SCHEMA`
+const TABLES_IN_SCHEMA: i32 = 2;
+
+/// What the statements of a workspace declare about its tables besides creating them: primary
+/// keys, replica identities and publications. A rule checks a single `CREATE TABLE`, and looks up
+/// here what other statements, possibly of other files, declare about the table.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableFacts {
+    /// The qualified names of the tables that are created with a primary key or get one
+    primary_keys: BTreeSet<String>,
+    /// The qualified names of the tables with a replica identity other than the default one
+    replica_identities: BTreeSet<String>,
+    /// The qualified names of the tables whose updates or deletes a publication publishes
+    published_tables: BTreeSet<String>,
+    /// The schemas whose tables a publication publishes updates or deletes of
+    published_schemas: BTreeSet<String>,
+    /// True if a publication publishes updates or deletes of all tables
+    all_tables_published: bool,
+}
+
+impl TableFacts {
+    /// Collects the facts of the statements of all `files`
+    pub fn collect<'a>(files: impl IntoIterator<Item = &'a [RawStmt]>) -> TableFacts {
+        let mut facts = TableFacts::default();
+        for stmt in files.into_iter().flatten() {
+            facts.add(&stmt.stmt);
+        }
+        facts
+    }
+
+    /// Returns true if `stmt` creates a table that a statement `self` was collected from declares
+    /// anything about, so that changing that statement changes the findings at `stmt`
+    pub fn concerns(&self, stmt: &RawStmt) -> bool {
+        let NodeEnum::CreateStmt(n) = &stmt.stmt else {
+            return false;
+        };
+        n.relation.as_ref().map(qualified_name).is_some_and(|name| {
+            self.has_primary_key(&name)
+                || self.has_replica_identity(&name)
+                || self.is_published(&name)
+        })
+    }
+
+    pub(super) fn has_primary_key(&self, name: &str) -> bool {
+        self.primary_keys.contains(name)
+    }
+
+    pub(super) fn has_replica_identity(&self, name: &str) -> bool {
+        self.replica_identities.contains(name)
+    }
+
+    pub(super) fn is_published(&self, name: &str) -> bool {
+        let schema = name.split_once('.').map_or(DEFAULT_SCHEMA, |(s, _)| s);
+        self.all_tables_published
+            || self.published_tables.contains(name)
+            || self.published_schemas.contains(schema)
+    }
+
+    fn add(&mut self, stmt: &NodeEnum) {
+        match stmt {
+            NodeEnum::CreateStmt(n) => {
+                if let Some(relation) = &n.relation {
+                    if n.table_elts.iter().any(is_primary_key) {
+                        self.primary_keys.insert(qualified_name(relation));
+                    }
+                }
+            }
+            NodeEnum::AlterTableStmt(n) => {
+                let Some(relation) = &n.relation else {
+                    return;
+                };
+                for cmd in alter_table_cmds(n) {
+                    match cmd.def.as_deref() {
+                        Some(def) if is_primary_key(def) => {
+                            self.primary_keys.insert(qualified_name(relation));
+                        }
+                        Some(def) => {
+                            if let Some(NodeEnum::ReplicaIdentityStmt(r)) = def.node.as_ref() {
+                                if r.identity_type != "d" {
+                                    self.replica_identities.insert(qualified_name(relation));
+                                }
+                            }
+                        }
+                        None => {}
+                    }
+                }
+            }
+            NodeEnum::CreatePublicationStmt(n) => {
+                self.add_publication(n.for_all_tables, &n.pubobjects, &n.options)
+            }
+            NodeEnum::AlterPublicationStmt(n) if n.action != DROP_OBJECTS => {
+                self.add_publication(n.for_all_tables, &n.pubobjects, &n.options)
+            }
+            _ => {}
+        }
+    }
+
+    fn add_publication(&mut self, for_all_tables: bool, objects: &[Node], options: &[Node]) {
+        let publishes_changes = options.iter().all(|option| match option.node.as_ref() {
+            Some(NodeEnum::DefElem(d)) if d.defname == "publish" => d
+                .arg
+                .as_deref()
+                .and_then(string_value)
+                .is_some_and(|publish| publish.contains("update") || publish.contains("delete")),
+            _ => true,
+        });
+        if !publishes_changes {
+            return;
+        }
+        self.all_tables_published |= for_all_tables;
+        for object in objects {
+            let Some(NodeEnum::PublicationObjSpec(spec)) = object.node.as_ref() else {
+                continue;
+            };
+            match spec.pubtable.as_ref().and_then(|t| t.relation.as_ref()) {
+                Some(relation) => {
+                    self.published_tables.insert(qualified_name(relation));
+                }
+                None if spec.pubobjtype == TABLES_IN_SCHEMA => {
+                    self.published_schemas.insert(spec.name.clone());
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+/// Returns true if `element` of a table is a primary key constraint or a column with one
+fn is_primary_key(element: &Node) -> bool {
+    match element.node.as_ref() {
+        Some(NodeEnum::Constraint(c)) => c.contype == PRIMARY_KEY,
+        Some(NodeEnum::ColumnDef(c)) => c.constraints.iter().any(|node| {
+            matches!(node.node.as_ref(), Some(NodeEnum::Constraint(c)) if c.contype == PRIMARY_KEY)
+        }),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    #[test]
+    fn test_table_facts() {
+        let migrations = parse_source(
+            "create table note (body text);
+            create publication notes for table note with (publish = 'insert, update');",
+        );
+        let later = parse_source(
+            "alter table note add primary key (body);
+            alter table app.event replica identity full;
+            alter publication events add tables in schema app;",
+        );
+        let facts = TableFacts::collect([migrations.stmts.as_slice(), later.stmts.as_slice()]);
+        assert!(facts.has_primary_key("public.note"));
+        assert!(facts.is_published("public.note"));
+        assert!(facts.has_replica_identity("app.event"));
+        assert!(facts.is_published("app.event"));
+        let creates =
+            parse_source("create table note (body text);\ncreate table contact (id int);");
+        assert!(facts.concerns(&creates.stmts[0]));
+        assert!(!facts.concerns(&creates.stmts[1]));
+
+        let inserts =
+            parse_source("create publication events for all tables with (publish = 'insert');");
+        assert_eq!(
+            TableFacts::collect([inserts.stmts.as_slice()]),
+            TableFacts::default()
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use cstree::syntax::ResolvedNode;
+use cstree::text::{TextRange, TextSize};
+use parser::{RawStmt, StmtKind, SyntaxKind};
+use pg_query::protobuf::CreateStmt;
+use pg_query::NodeEnum;
+
+use super::{LintContext, LintDiagnostic, LintFix, Rule, RuleGroup, Severity};
+use crate::moniker::qualified_name;
+
+/// Flags `CREATE TABLE` without a primary key, unless an `ALTER TABLE` of the workspace adds one.
+///
+/// Rows of a table without a primary key can only be told apart by their values, which makes
+/// duplicates impossible to clean up and leaves updates and deletes of logical replication without
+/// a replica identity. Partitions and tables created with `LIKE` are left out, as they get their
+/// key from the parent or the copied table. The fix adds a `bigint` identity column as the key.
+pub const RULE: Rule = Rule {
+    name: "table-without-primary-key",
+    description: "Tables need a primary key",
+    group: RuleGroup::Replication,
+    severity: Severity::Warning,
+    stmt_kinds: &[StmtKind::Ddl],
+    check,
+    fix: Some(fix),
+};
+
+const IDENTITY_COLUMN: &str = "id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY";
+
+fn check(ctx: &mut LintContext<'_>) {
+    if let NodeEnum::CreateStmt(n) = &ctx.stmt.stmt {
+        let Some(relation) = n.relation.as_ref() else {
+            return;
+        };
+        if is_derived(n) || ctx.tables.has_primary_key(&qualified_name(relation)) {
+            return;
+        }
+        ctx.report_at_location(
+            format!(
+                "Table {} has no primary key. Add one so that its rows can be identified.",
+                qualified_name(relation)
+            ),
+            relation.location,
+        );
+    }
+}
+
+fn fix(cst: &ResolvedNode<SyntaxKind>, stmt: &RawStmt, _: &LintDiagnostic) -> Option<LintFix> {
+    let NodeEnum::CreateStmt(n) = &stmt.stmt else {
+        return None;
+    };
+    let has_id = n.table_elts.iter().any(|element| {
+        matches!(element.node.as_ref(), Some(NodeEnum::ColumnDef(c)) if c.colname == "id")
+    });
+    if has_id {
+        return None;
+    }
+    let relation = n.relation.as_ref()?;
+    let start = stmt.range.start() + TextSize::from(relation.location.max(0) as u32);
+    let tokens = cst
+        .descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| token.text_range().start() >= start)
+        .take_while(|token| token.text_range().end() <= stmt.range.end())
+        .collect::<Vec<_>>();
+    let open = tokens
+        .iter()
+        .position(|token| token.kind() == SyntaxKind::Ascii40)?;
+
+    // continue the layout of the column list: on a line of its own if the first column is
+    let after = &tokens[open + 1..];
+    let first = after.iter().position(|token| !token.kind().is_trivia())?;
+    let text = if n.table_elts.is_empty() {
+        IDENTITY_COLUMN.to_string()
+    } else if after[..first]
+        .iter()
+        .any(|token| token.kind() == SyntaxKind::Newline)
+    {
+        let indent = after[..first]
+            .iter()
+            .rev()
+            .take_while(|token| token.kind() == SyntaxKind::Whitespace)
+            .map(|token| token.text())
+            .collect::<String>();
+        format!("\n{}{},", indent, IDENTITY_COLUMN)
+    } else {
+        format!("{}, ", IDENTITY_COLUMN)
+    };
+    Some(LintFix {
+        title: "Add an identity column as primary key".to_string(),
+        range: TextRange::empty(tokens[open].text_range().end()),
+        text,
+    })
+}
+
+/// Returns true for partitions and tables that copy another table with `LIKE`, which get their
+/// primary key from elsewhere
+pub(super) fn is_derived(n: &CreateStmt) -> bool {
+    n.partbound.is_some()
+        || n.table_elts
+            .iter()
+            .any(|element| matches!(element.node, Some(NodeEnum::TableLikeClause(_))))
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use parser::Cancellation;
+
+    use crate::lint::{lint_cancellable, lint_fix, lint_with_config, LintConfig, TableFacts};
+
+    fn fixed(input: &str) -> Vec<String> {
+        let parse = parse_source(input);
+        lint_with_config(&parse.stmts, &LintConfig::all())
+            .iter()
+            .filter(|d| d.rule == "table-without-primary-key")
+            .map(|d| {
+                let fix = lint_fix(&parse.cst, &parse.stmts, d).unwrap();
+                let mut text = input.to_string();
+                text.replace_range(std::ops::Range::<usize>::from(fix.range), &fix.text);
+                text
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_table_without_primary_key() {
+        assert_eq!(
+            fixed("CREATE TABLE app.note (body text);"),
+            vec!["CREATE TABLE app.note (id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY, body text);"]
+        );
+        assert_eq!(
+            fixed("create table note (\n    body text\n);"),
+            vec!["create table note (\n    id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,\n    body text\n);"]
+        );
+        assert!(fixed(
+            "create table contact (id int primary key);
+            create table note (contact_id int, body text);
+            alter table note add primary key (contact_id);
+            create table contact_2024 partition of contact for values in (2024);"
+        )
+        .is_empty());
+
+        let parse = parse_source("create table note (id int);");
+        assert!(lint_fix(
+            &parse.cst,
+            &parse.stmts,
+            &lint_with_config(&parse.stmts, &LintConfig::all())[0]
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_primary_key_of_other_file() {
+        let create = parse_source("create table note (body text);");
+        let alter = parse_source("alter table note add primary key (body);");
+        let tables = TableFacts::collect([create.stmts.as_slice(), alter.stmts.as_slice()]);
+        let diagnostics = lint_cancellable(
+            &create.stmts,
+            &LintConfig::all(),
+            &tables,
+            &Cancellation::new(),
+        )
+        .unwrap();
+        assert!(diagnostics
+            .iter()
+            .all(|d| d.rule != "table-without-primary-key"));
+    }
+}
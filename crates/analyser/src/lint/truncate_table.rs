@@ -0,0 +1,68 @@
+use parser::StmtKind;
+use pg_query::NodeEnum;
+
+use super::{LintContext, Rule, RuleGroup, Severity};
+use crate::moniker::qualified_name;
+
+/// Flags `TRUNCATE`.
+///
+/// Truncating deletes all rows of the tables at once, without firing `ON DELETE` triggers, and
+/// takes an `ACCESS EXCLUSIVE` lock on every table. With `CASCADE`, it also empties all tables
+/// that reference them with foreign keys. In a migration or a script that runs against
+/// production, it is rarely meant.
+pub const RULE: Rule = Rule {
+    name: "truncate-table",
+    description: "TRUNCATE deletes all rows without firing delete triggers",
+    group: RuleGroup::Recommended,
+    severity: Severity::Warning,
+    stmt_kinds: &[StmtKind::Utility],
+    check,
+    fix: None,
+};
+
+fn check(ctx: &mut LintContext<'_>) {
+    if let NodeEnum::TruncateStmt(n) = &ctx.stmt.stmt {
+        let tables = n
+            .relations
+            .iter()
+            .filter_map(|r| match r.node.as_ref()? {
+                NodeEnum::RangeVar(r) => Some(qualified_name(r)),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        // DropCascade
+        let message = if n.behavior == 2 {
+            format!(
+                "TRUNCATE deletes all rows of {}, and of all tables that reference them, without firing delete triggers. Make sure this is not run against data that must be kept.",
+                tables
+            )
+        } else {
+            format!(
+                "TRUNCATE deletes all rows of {} without firing delete triggers. Make sure this is not run against data that must be kept.",
+                tables
+            )
+        };
+        ctx.report_stmt(message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use crate::lint::lint;
+
+    #[test]
+    fn test_truncate_table() {
+        let diagnostics =
+            lint(&parse_source("TRUNCATE orders, app.items RESTART IDENTITY CASCADE;").stmts);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].rule, "truncate-table");
+        assert!(diagnostics[0].message.starts_with(
+            "TRUNCATE deletes all rows of public.orders, app.items, and of all tables"
+        ));
+
+        assert!(lint(&parse_source("DELETE FROM orders;").stmts).is_empty());
+    }
+}
@@ -0,0 +1,130 @@
+//! The types that columns and domains are declared with, and fixes that replace the type name
+//! that a rule reported with another type.
+
+use cstree::syntax::ResolvedNode;
+use cstree::text::{TextRange, TextSize};
+use parser::{RawStmt, SyntaxKind};
+use pg_query::protobuf::TypeName;
+use pg_query::NodeEnum;
+
+use super::{LintDiagnostic, LintFix};
+use crate::utils::{descendants, type_name, type_names};
+
+/// The words of the multi-word spellings of the types that are replaced, e.g.
+/// `character varying` or `timestamp without time zone`
+const TYPE_WORDS: &[&str] = &[
+    "pg_catalog",
+    "character",
+    "varying",
+    "varchar",
+    "timestamp",
+    "without",
+    "time",
+    "zone",
+    "json",
+    "serial",
+    "serial2",
+    "serial4",
+    "serial8",
+    "smallserial",
+    "bigserial",
+];
+
+/// Returns the types of the columns and domains that `stmt` declares, including those of `ALTER
+/// COLUMN TYPE`, with their normalized names. The types of casts and of function signatures are
+/// left out, as they do not decide how values are stored.
+pub(super) fn declared_types(stmt: &NodeEnum) -> Vec<(TypeName, String)> {
+    descendants(stmt)
+        .into_iter()
+        .filter_map(|node| match node {
+            NodeEnum::ColumnDef(c) => c.type_name,
+            NodeEnum::CreateDomainStmt(d) => d.type_name,
+            _ => None,
+        })
+        .filter_map(|t| {
+            let name = type_name(&t)?;
+            Some((t, name))
+        })
+        .collect()
+}
+
+/// A type name in the source
+pub(super) struct SourceType {
+    /// The normalized name, e.g. `varchar`
+    pub name: String,
+    /// The range of the name and its modifiers, without array bounds
+    pub range: TextRange,
+    /// The modifiers including the parentheses, e.g. `(3)`
+    pub modifiers: Option<String>,
+    /// True if the name is written in upper case
+    pub is_upper: bool,
+}
+
+/// Returns the type of `stmt` that `diagnostic` was reported at
+pub(super) fn reported_type(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmt: &RawStmt,
+    diagnostic: &LintDiagnostic,
+) -> Option<SourceType> {
+    let (_, name) = type_names(&stmt.stmt).into_iter().find(|(t, _)| {
+        t.location >= 0
+            && stmt.range.start() + TextSize::from(t.location as u32) == diagnostic.range.start()
+    })?;
+    let tokens = cst
+        .descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| token.text_range().start() >= diagnostic.range.start())
+        .take_while(|token| token.text_range().end() <= stmt.range.end())
+        .filter(|token| !token.kind().is_trivia())
+        .collect::<Vec<_>>();
+    let is_word = |idx: usize| {
+        tokens.get(idx).is_some_and(|token| {
+            token.kind() == SyntaxKind::Ascii46
+                || TYPE_WORDS.contains(&token.text().trim_matches('"').to_lowercase().as_str())
+        })
+    };
+
+    let mut end = 0;
+    while is_word(end) {
+        end += 1;
+    }
+    let mut modifiers = None;
+    if tokens
+        .get(end)
+        .is_some_and(|t| t.kind() == SyntaxKind::Ascii40)
+    {
+        let close = end
+            + tokens[end..]
+                .iter()
+                .position(|t| t.kind() == SyntaxKind::Ascii41)?;
+        modifiers = Some(tokens[end..=close].iter().map(|t| t.text()).collect());
+        end = close + 1;
+        // `timestamp(3) without time zone`
+        while is_word(end) {
+            end += 1;
+        }
+    }
+    let first = tokens.first()?;
+    Some(SourceType {
+        name,
+        range: TextRange::new(
+            first.text_range().start(),
+            tokens[end.checked_sub(1)?].text_range().end(),
+        ),
+        modifiers,
+        is_upper: first.text().chars().all(|c| !c.is_lowercase()),
+    })
+}
+
+/// Returns the fix that replaces the type with `text`, in the case of the original
+pub(super) fn replace_type(title: &str, source: &SourceType, text: &str) -> LintFix {
+    LintFix {
+        title: title.to_string(),
+        range: source.range,
+        text: if source.is_upper {
+            text.to_uppercase()
+        } else {
+            text.to_lowercase()
+        },
+    }
+}
@@ -0,0 +1,57 @@
+use parser::StmtKind;
+use pg_query::NodeEnum;
+
+use super::{LintContext, Rule, RuleGroup, Severity};
+
+/// Flags the `oids` storage parameter in `CREATE TABLE ... WITH (oids = ...)`.
+///
+/// Tables with OIDs are not supported since Postgres 12, and `WITH (oids = false)` has been the
+/// default for much longer. Use an identity column if the table needs a generated key.
+pub const RULE: Rule = Rule {
+    name: "with-oids",
+    description: "Tables with OIDs are not supported since Postgres 12",
+    group: RuleGroup::ModernPostgres,
+    severity: Severity::Warning,
+    stmt_kinds: &[StmtKind::Ddl],
+    check,
+    fix: None,
+};
+
+fn check(ctx: &mut LintContext<'_>) {
+    if let NodeEnum::CreateStmt(n) = &ctx.stmt.stmt {
+        let locations = n
+            .options
+            .iter()
+            .filter_map(|o| match o.node.as_ref() {
+                Some(NodeEnum::DefElem(d)) if d.defname.eq_ignore_ascii_case("oids") => {
+                    Some(d.location)
+                }
+                _ => None,
+            })
+            .collect::<Vec<i32>>();
+        for location in locations {
+            ctx.report_at_location(
+                "Tables with OIDs are not supported since Postgres 12. Remove the option and use an identity column instead.",
+                location,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use crate::lint::{lint_with_config, LintConfig, RuleGroup};
+
+    #[test]
+    fn test_with_oids() {
+        let diagnostics = lint_with_config(
+            &parse_source("CREATE TABLE t (a int) WITH (fillfactor = 70, oids = true);").stmts,
+            &LintConfig::default().with_group(RuleGroup::ModernPostgres),
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].rule, "with-oids");
+        assert_eq!(usize::from(diagnostics[0].range.start()), 46);
+    }
+}
@@ -0,0 +1,368 @@
+//! Translations of diagnostic messages and lint rule descriptions.
+//!
+//! Messages are written in English in the code, and English is the source language of the
+//! catalogs: a catalog maps English messages to their translation, like a gettext PO file, and
+//! messages without a translation stay in English. Since messages name the objects they are
+//! about, a message id can contain `{}` for any text, which the translation takes over in order,
+//! or in the order of positional placeholders such as `{1}`, e.g.
+//!
+//! ```text
+//! msgid "{} is denied by the policy"
+//! msgstr "{} ist durch die Richtlinie verboten"
+//! ```
+//!
+//! `locales/messages.pot` lists the messages to translate. Translations are added as
+//! `locales/<locale>.po` and registered in `CATALOGS`, or loaded from any file with
+//! [`Catalog::parse`].
+
+use std::fmt;
+
+/// The bundled catalogs by locale, e.g. `pt-BR`
+const CATALOGS: &[(&str, &str)] = &[];
+
+/// A message id split at its placeholders
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pattern {
+    /// The text around the placeholders, one more than there are placeholders
+    parts: Vec<String>,
+}
+
+impl Pattern {
+    fn new(msgid: &str) -> Pattern {
+        Pattern {
+            parts: msgid.split("{}").map(str::to_string).collect(),
+        }
+    }
+
+    /// Returns the texts of the placeholders if `message` matches the pattern
+    fn captures<'a>(&self, message: &'a str) -> Option<Vec<&'a str>> {
+        let (first, rest) = self.parts.split_first()?;
+        let mut remaining = message.strip_prefix(first.as_str())?;
+        if rest.is_empty() {
+            return remaining.is_empty().then(Vec::new);
+        }
+        let mut captures = Vec::new();
+        for (idx, part) in rest.iter().enumerate() {
+            let end = if idx + 1 == rest.len() {
+                // the last part ends the message
+                remaining.strip_suffix(part.as_str())?.len()
+            } else {
+                remaining.find(part.as_str())?
+            };
+            captures.push(&remaining[..end]);
+            remaining = &remaining[end + part.len()..];
+        }
+        Some(captures)
+    }
+}
+
+/// An error in the text of a catalog
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatalogError {
+    /// The 1-based line of the error
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// The translations of messages into one locale
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Catalog {
+    pub locale: String,
+    entries: Vec<(Pattern, String)>,
+}
+
+impl Catalog {
+    /// Parses the `msgid` and `msgstr` pairs of a PO file. Comments and the header, whose `msgid`
+    /// is empty, are skipped.
+    pub fn parse(locale: &str, text: &str) -> Result<Catalog, CatalogError> {
+        let mut entries = Vec::new();
+        let mut msgid: Option<String> = None;
+        let mut msgstr: Option<String> = None;
+        let mut finish = |msgid: &mut Option<String>, msgstr: &mut Option<String>| {
+            if let (Some(id), Some(translation)) = (msgid.take(), msgstr.take()) {
+                if !id.is_empty() {
+                    entries.push((Pattern::new(&id), translation));
+                }
+            }
+        };
+        for (idx, line) in text.lines().enumerate() {
+            let error = |message: &str| CatalogError {
+                line: idx + 1,
+                message: message.to_string(),
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(rest) = line.strip_prefix("msgid ") {
+                finish(&mut msgid, &mut msgstr);
+                msgid = Some(unquote(rest).ok_or_else(|| error("expected a quoted string"))?);
+            } else if let Some(rest) = line.strip_prefix("msgstr ") {
+                if msgid.is_none() || msgstr.is_some() {
+                    return Err(error("msgstr without msgid"));
+                }
+                msgstr = Some(unquote(rest).ok_or_else(|| error("expected a quoted string"))?);
+            } else if line.starts_with('"') {
+                // a continuation of the string of the line before
+                let text = unquote(line).ok_or_else(|| error("expected a quoted string"))?;
+                match (&mut msgid, &mut msgstr) {
+                    (_, Some(msgstr)) => msgstr.push_str(&text),
+                    (Some(msgid), None) => msgid.push_str(&text),
+                    (None, None) => return Err(error("string without msgid")),
+                }
+            } else {
+                return Err(error("expected msgid, msgstr or a comment"));
+            }
+        }
+        finish(&mut msgid, &mut msgstr);
+        Ok(Catalog {
+            locale: locale.to_string(),
+            entries,
+        })
+    }
+
+    /// Returns the bundled catalog of `locale`, e.g. `de-AT`, or of its language, e.g. `de`, if
+    /// there is one
+    pub fn bundled(locale: &str) -> Option<Catalog> {
+        let language = locale.split(['-', '_']).next().unwrap_or(locale);
+        let (locale, text) = CATALOGS
+            .iter()
+            .find(|(l, _)| l.eq_ignore_ascii_case(locale))
+            .or_else(|| {
+                CATALOGS
+                    .iter()
+                    .find(|(l, _)| l.eq_ignore_ascii_case(language))
+            })?;
+        Catalog::parse(locale, text).ok()
+    }
+
+    /// Returns the translation of `message`, or `None` if the catalog has none
+    pub fn translate(&self, message: &str) -> Option<String> {
+        // an empty `msgstr` has not been translated yet
+        let mut entries = self.entries.iter().filter(|(_, t)| !t.is_empty());
+        entries.find_map(|(pattern, translation)| {
+            let captures = pattern.captures(message)?;
+            Some(fill(translation, &captures))
+        })
+    }
+}
+
+/// Replaces the placeholders of `translation` with `captures`
+fn fill(translation: &str, captures: &[&str]) -> String {
+    let mut result = String::new();
+    let mut next = 0;
+    let mut rest = translation;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+            break;
+        };
+        let placeholder = &rest[start + 1..end];
+        let idx = if placeholder.is_empty() {
+            next += 1;
+            Some(next - 1)
+        } else {
+            placeholder.parse::<usize>().ok()
+        };
+        result.push_str(&rest[..start]);
+        match idx.and_then(|idx| captures.get(idx)) {
+            Some(capture) => result.push_str(capture),
+            None => result.push_str(&rest[start..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Returns the text of a quoted PO string with its escapes resolved
+fn unquote(text: &str) -> Option<String> {
+    let inner = text.strip_prefix('"')?.strip_suffix('"')?;
+    let mut result = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => result.push('\n'),
+            't' => result.push('\t'),
+            c => result.push(c),
+        }
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lint::RULES;
+
+    const GERMAN: &str = r#"
+# a translation for the tests
+msgid ""
+msgstr "Content-Type: text/plain; charset=UTF-8\n"
+
+msgid "{} is denied by the policy"
+msgstr "{} ist durch die Richtlinie verboten"
+
+msgid "the policy requires that indexes on {} are created with CONCURRENTLY"
+msgstr ""
+"die Richtlinie verlangt, dass Indexe auf {} mit CONCURRENTLY "
+"erstellt werden"
+
+msgid "column {} of {} is not known"
+msgstr "{1} hat keine Spalte {0}"
+
+msgid "not translated yet"
+msgstr ""
+"#;
+
+    #[test]
+    fn test_translate() {
+        let catalog = Catalog::parse("de", GERMAN).unwrap();
+        assert_eq!(
+            catalog
+                .translate("DROP DATABASE is denied by the policy")
+                .as_deref(),
+            Some("DROP DATABASE ist durch die Richtlinie verboten")
+        );
+        assert_eq!(
+            catalog
+                .translate("the policy requires that indexes on public.event are created with CONCURRENTLY")
+                .as_deref(),
+            Some("die Richtlinie verlangt, dass Indexe auf public.event mit CONCURRENTLY erstellt werden")
+        );
+        assert_eq!(
+            catalog
+                .translate("column name of public.contact is not known")
+                .as_deref(),
+            Some("public.contact hat keine Spalte name")
+        );
+        assert_eq!(catalog.translate("not translated yet"), None);
+        assert_eq!(catalog.translate("relation contact does not exist"), None);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let error = Catalog::parse("de", "msgid \"a\"\nmsgstr b").unwrap_err();
+        assert_eq!(error.line, 2);
+        assert!(Catalog::bundled("en-US").is_none());
+    }
+
+    /// The sources of the diagnostics that the language server translates
+    const SOURCES: &[&str] = &[
+        include_str!("lint/char_type.rs"),
+        include_str!("lint/column_padding.rs"),
+        include_str!("lint/implicit_text_cast.rs"),
+        include_str!("lint/money_type.rs"),
+        include_str!("lint/prefer_identity.rs"),
+        include_str!("lint/prefer_jsonb.rs"),
+        include_str!("lint/prefer_text.rs"),
+        include_str!("lint/prefer_timestamptz.rs"),
+        include_str!("lint/prefer_trigger_over_rule.rs"),
+        include_str!("lint/replica_identity.rs"),
+        include_str!("lint/table_without_primary_key.rs"),
+        include_str!("lint/truncate_table.rs"),
+        include_str!("lint/with_oids.rs"),
+        include_str!("ambiguous_columns.rs"),
+        include_str!("comments.rs"),
+        include_str!("fillfactor.rs"),
+        include_str!("foreign_key_index.rs"),
+        include_str!("migrations.rs"),
+        include_str!("name_arguments.rs"),
+        include_str!("partition_pruning.rs"),
+        include_str!("policy.rs"),
+        include_str!("read_only.rs"),
+        include_str!("spelling.rs"),
+        include_str!("table_rewrite.rs"),
+    ];
+
+    fn template_msgids() -> Vec<String> {
+        let template = Catalog::parse("en", include_str!("../locales/messages.pot")).unwrap();
+        template
+            .entries
+            .iter()
+            .map(|(pattern, _)| pattern.parts.join("{}"))
+            .collect()
+    }
+
+    /// Returns the format strings of the `format!` calls of `source` outside of its tests
+    fn format_strings(source: &str) -> Vec<String> {
+        let source = source.split("#[cfg(test)]").next().unwrap_or(source);
+        source
+            .match_indices("format!(")
+            .filter_map(|(idx, call)| string_literal(source[idx + call.len()..].trim_start()))
+            .collect()
+    }
+
+    /// Returns the text of the string literal that `source` starts with, with its escapes and line
+    /// continuations resolved
+    fn string_literal(source: &str) -> Option<String> {
+        let mut chars = source.strip_prefix('"')?.chars();
+        let mut text = String::new();
+        loop {
+            match chars.next()? {
+                '"' => return Some(text),
+                '\\' => match chars.next()? {
+                    '\n' => chars = chars.as_str().trim_start().chars(),
+                    'n' => text.push('\n'),
+                    c => text.push(c),
+                },
+                c => text.push(c),
+            }
+        }
+    }
+
+    /// Returns true if `text` reads like a sentence rather than SQL, i.e. it has three words in a
+    /// row and one of them is a lowercase word
+    fn is_sentence(text: &str) -> bool {
+        let words = text.split(' ').collect::<Vec<_>>();
+        words.windows(3).any(|words| {
+            words
+                .iter()
+                .all(|word| !word.is_empty() && word.chars().all(|c| c.is_ascii_alphabetic()))
+                && words
+                    .iter()
+                    .any(|word| word.len() > 1 && word.chars().all(|c| c.is_ascii_lowercase()))
+        })
+    }
+
+    #[test]
+    fn test_template_has_messages() {
+        let msgids = template_msgids();
+        let format_strings = SOURCES
+            .iter()
+            .flat_map(|source| format_strings(source))
+            .filter(|text| is_sentence(text))
+            .collect::<Vec<_>>();
+        assert!(format_strings.len() > 40);
+        for text in format_strings {
+            assert!(
+                msgids.contains(&text),
+                "the message {:?} is not in messages.pot",
+                text
+            );
+        }
+        assert!(!is_sentence("ALTER TABLE t ADD {}"));
+        assert!(!is_sentence("{} GENERATED BY DEFAULT AS IDENTITY"));
+    }
+
+    #[test]
+    fn test_template_has_rule_descriptions() {
+        let msgids = template_msgids();
+        for rule in RULES {
+            assert!(
+                msgids.iter().any(|msgid| msgid == rule.description),
+                "the description of {} is not in messages.pot",
+                rule.name
+            );
+        }
+    }
+}
@@ -0,0 +1,2651 @@
+//! Replay of migrations into the schema model.
+//!
+//! Migrations are SQL files whose name starts with a version, e.g. `20240101120000_contact.sql`
+//! or `V3__contact.sql`. Replaying the statements of all migrations up to a version reconstructs
+//! the tables as they were at that version, so that a migration can be checked against the schema
+//! of its time instead of the current one.
+//!
+//! Only the parts of a schema that [`Schema`] models are replayed: tables with their columns,
+//! indexes, constraints, access method, clustering index and owner, including the columns that
+//! tables take from others with `INHERITS` or `LIKE`. Defaults and definitions are stored as
+//! deparsed by pg_query, which makes them independent of the formatting of the migration. Other
+//! relations such as views are only tracked by name, so that statements using them are not
+//! reported. Sequences are tracked with the column that owns them, if any, so that dropping the
+//! column or its table drops them as well.
+//!
+//! [`squash`] turns the difference between two replayed states back into DDL, which replaces the
+//! migrations between them with a single one.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use cstree::text::{TextRange, TextSize};
+use parser::make::quote_ident;
+use parser::{Cancellation, Cancelled, RawStmt};
+use pg_query::protobuf::a_const::Val;
+use pg_query::protobuf::{
+    AlterTableCmd, AlterTableStmt, ClusterStmt, ColumnDef, Constraint, CreateStmt, DropStmt,
+    IndexStmt, Node, PartitionSpec, RangeVar, RenameStmt, ResTarget, RoleSpec, SelectStmt,
+    TypeName,
+};
+use pg_query::NodeEnum;
+
+use crate::cast_graph::CastGraph;
+use crate::lint::{LintDiagnostic, Severity};
+use crate::moniker::DEFAULT_SCHEMA;
+use crate::schema::{partition_columns, Column, Deferral, Schema, Table};
+use crate::schema_diff::{diff, SchemaChange};
+use crate::utils::{sequence_owned_by, string_value, type_name};
+
+const UNKNOWN_RELATION: &str = "migration-unknown-relation";
+const UNKNOWN_COLUMN: &str = "migration-unknown-column";
+const DUPLICATE_OBJECT: &str = "migration-duplicate-object";
+const TRUNCATE_REFERENCED: &str = "migration-truncate-referenced";
+/// Not an error, but tables that `TRUNCATE ... CASCADE` empties without naming them
+const TRUNCATE_CASCADE: &str = "migration-truncate-cascade";
+const UNKNOWN_CONSTRAINT: &str = "migration-unknown-constraint";
+const NO_CLUSTERED_INDEX: &str = "migration-no-clustered-index";
+const NOT_DEFERRABLE: &str = "migration-not-deferrable";
+/// Not an error if the rows that a foreign key requires already exist, but rows of the referenced
+/// table are only inserted later in the script
+const UNDEFERRED_FOREIGN_KEY: &str = "migration-undeferred-foreign-key";
+const INSERT_ARITY: &str = "migration-insert-arity";
+
+/// The types of columns that own a sequence of their own
+const SERIAL_TYPES: &[&str] = &[
+    "smallserial",
+    "serial",
+    "bigserial",
+    "serial2",
+    "serial4",
+    "serial8",
+];
+
+/// `CREATE_TABLE_LIKE_CONSTRAINTS` of the `INCLUDING` options of a `LIKE` clause
+const LIKE_CONSTRAINTS: u32 = 1 << 2;
+/// `CREATE_TABLE_LIKE_DEFAULTS` of the `INCLUDING` options of a `LIKE` clause
+const LIKE_DEFAULTS: u32 = 1 << 3;
+
+/// Returns the version of a migration file, i.e. the digits its name starts with, optionally
+/// after the `V` of Flyway
+pub fn migration_version(file_name: &str) -> Option<u128> {
+    let name = file_name.strip_prefix('V').unwrap_or(file_name);
+    let len = name
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(name.len());
+    name[..len].parse().ok()
+}
+
+/// The schema of a database after a sequence of migrations
+#[derive(Debug, Clone, Default)]
+pub struct MigrationState {
+    /// The tables by schema name
+    pub schemas: BTreeMap<String, Schema>,
+    /// The qualified names of relations that are not modeled as tables, e.g. views
+    other_relations: BTreeSet<String>,
+    /// The casts of the database, e.g. as loaded from `pg_cast`, and those of `CREATE CAST`
+    pub casts: CastGraph,
+    /// The schemas of `SET search_path`, or `None` for the default search path, which only
+    /// consists of `public`
+    search_path: Option<Vec<String>>,
+}
+
+/// The constraints that `SET CONSTRAINTS ... DEFERRED` deferred in the current transaction.
+/// Outside of `BEGIN ... COMMIT`, every statement is a transaction of its own, so nothing is
+/// deferred past it.
+#[derive(Debug, Default)]
+struct DeferredConstraints {
+    in_transaction: bool,
+    all: bool,
+    names: BTreeSet<String>,
+}
+
+impl DeferredConstraints {
+    /// Applies `stmt` if it sets constraints, begins or ends the transaction
+    fn apply(&mut self, stmt: &NodeEnum) {
+        match stmt {
+            NodeEnum::ConstraintsSetStmt(n) if self.in_transaction => {
+                let names = n.constraints.iter().filter_map(|c| match c.node.as_ref()? {
+                    NodeEnum::RangeVar(r) => Some(r.relname.clone()),
+                    _ => None,
+                });
+                match (n.constraints.is_empty(), n.deferred) {
+                    (true, deferred) => {
+                        self.all = deferred;
+                        self.names.clear();
+                    }
+                    (false, true) => self.names.extend(names),
+                    (false, false) => {
+                        for name in names {
+                            self.names.remove(&name);
+                        }
+                    }
+                }
+            }
+            // TransStmtBegin, TransStmtStart
+            NodeEnum::TransactionStmt(n) if n.kind == 1 || n.kind == 2 => {
+                self.in_transaction = true;
+            }
+            // TransStmtCommit, TransStmtRollback, TransStmtPrepare
+            NodeEnum::TransactionStmt(n) if matches!(n.kind, 3 | 4 | 8) => {
+                *self = DeferredConstraints::default();
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns true if the constraint `name` with the deferral `deferral` is checked at the end of
+    /// a transaction that spans more than the current statement
+    fn is_deferred(&self, name: &str, deferral: Deferral) -> bool {
+        if !self.in_transaction {
+            return false;
+        }
+        match deferral {
+            Deferral::NotDeferrable => false,
+            Deferral::InitiallyImmediate => self.all || self.names.contains(name),
+            Deferral::InitiallyDeferred => true,
+        }
+    }
+}
+
+/// What a relation name refers to
+enum Relation<'a> {
+    Table(&'a Table),
+    /// A relation that is not modeled as a table, e.g. a view
+    Other,
+    Missing,
+    /// A relation in a schema that the migrations did not create objects in
+    Unknown,
+}
+
+impl MigrationState {
+    /// Returns the state of a database whose tables are `schemas`, e.g. as loaded from a live
+    /// database, by schema name
+    pub fn from_schemas(schemas: BTreeMap<String, Schema>) -> Self {
+        Self {
+            schemas,
+            ..Self::default()
+        }
+    }
+
+    /// Applies the changes of `stmts` to the schema
+    pub fn replay(&mut self, stmts: &[RawStmt]) {
+        for stmt in stmts {
+            self.apply(&stmt.stmt);
+            self.casts.add_stmt(stmt);
+        }
+    }
+
+    /// Resets the settings of the session, i.e. `SET search_path`, e.g. after a migration file, as
+    /// every migration runs in a session of its own
+    pub fn end_session(&mut self) {
+        self.search_path = None;
+    }
+
+    /// Checks every statement of `stmts` against the schema it is applied to, and applies it
+    /// afterwards
+    ///
+    /// Relations are only reported as missing within schemas that the migrations created objects
+    /// in, because other schemas are usually managed outside of them, e.g. by extensions.
+    pub fn check(&mut self, stmts: &[RawStmt]) -> Vec<LintDiagnostic> {
+        self.check_cancellable(stmts, &Cancellation::new())
+            .unwrap_or_default()
+    }
+
+    /// Like `check`, but stops before the next statement once `cancellation` has been cancelled.
+    /// The statements up to then have been applied.
+    pub fn check_cancellable(
+        &mut self,
+        stmts: &[RawStmt],
+        cancellation: &Cancellation,
+    ) -> Result<Vec<LintDiagnostic>, Cancelled> {
+        let inserted = stmts
+            .iter()
+            .map(|stmt| match &stmt.stmt {
+                NodeEnum::InsertStmt(n) => n.relation.as_ref().map(|r| self.relation_name(r)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let mut deferred = DeferredConstraints::default();
+        let mut diagnostics = Vec::new();
+        for (idx, stmt) in stmts.iter().enumerate() {
+            cancellation.check()?;
+            let mut problems = self.check_stmt(&stmt.stmt);
+            if let Some(table) = &inserted[idx] {
+                problems.extend(self.check_insert_order(
+                    table,
+                    &inserted[..idx],
+                    &inserted[idx + 1..],
+                    &deferred,
+                ));
+            }
+            deferred.apply(&stmt.stmt);
+            for (rule, message, location) in problems {
+                let range = if location >= 0 {
+                    TextRange::empty(stmt.range.start() + TextSize::from(location as u32))
+                } else {
+                    stmt.range
+                };
+                let severity = if rule == TRUNCATE_CASCADE || rule == UNDEFERRED_FOREIGN_KEY {
+                    Severity::Warning
+                } else {
+                    Severity::Error
+                };
+                diagnostics.push(LintDiagnostic {
+                    rule,
+                    message,
+                    severity,
+                    range,
+                });
+            }
+            self.apply(&stmt.stmt);
+            self.casts.add_stmt(stmt);
+        }
+        Ok(diagnostics)
+    }
+
+    /// Returns the rule, message and location of all problems of `stmt`
+    fn check_stmt(&self, stmt: &NodeEnum) -> Vec<(&'static str, String, i32)> {
+        let mut problems = Vec::new();
+        match stmt {
+            NodeEnum::CreateStmt(n) => {
+                if let Some(r) = n.relation.as_ref().filter(|_| !n.if_not_exists) {
+                    let (schema, name) = self.created_name(r);
+                    if matches!(
+                        self.relation_named(&schema, &name),
+                        Relation::Table(_) | Relation::Other
+                    ) {
+                        problems.push((
+                            DUPLICATE_OBJECT,
+                            format!("relation {} already exists", r.relname),
+                            r.location,
+                        ));
+                    }
+                }
+                for parent in parent_relations(n) {
+                    self.check_columns(parent, &[], &mut problems);
+                }
+            }
+            // ObjectTable
+            NodeEnum::AlterTableStmt(n) if n.objtype == 42 => {
+                let Some(r) = &n.relation else {
+                    return problems;
+                };
+                if n.missing_ok && matches!(self.relation(r), Relation::Missing) {
+                    return problems;
+                }
+                let Relation::Table(table) = self.relation(r) else {
+                    self.check_columns(r, &[], &mut problems);
+                    return problems;
+                };
+                // every command sees the changes of the commands before it, e.g. a column that
+                // an earlier command added or dropped
+                let mut table = table.clone();
+                for cmd in alter_table_cmds(n) {
+                    problems.extend(check_alter_table_cmd(&table, r, cmd));
+                    apply_alter_table_cmd(&mut table, cmd);
+                }
+            }
+            // ObjectSequence, ObjectTable
+            NodeEnum::DropStmt(n) if matches!(n.remove_type, 38 | 42) && !n.missing_ok => {
+                for (schema, name) in n
+                    .objects
+                    .iter()
+                    .filter_map(|object| self.object_name(object))
+                {
+                    if matches!(self.relation_named(&schema, &name), Relation::Missing) {
+                        problems.push((
+                            UNKNOWN_RELATION,
+                            format!("relation {} does not exist", name),
+                            -1,
+                        ));
+                    }
+                }
+            }
+            NodeEnum::IndexStmt(n) => {
+                if let Some(r) = &n.relation {
+                    let columns = n
+                        .index_params
+                        .iter()
+                        .filter_map(|p| match p.node.as_ref() {
+                            Some(NodeEnum::IndexElem(e)) if !e.name.is_empty() => {
+                                Some((e.name.as_str(), r.location))
+                            }
+                            _ => None,
+                        })
+                        .collect::<Vec<_>>();
+                    self.check_columns(r, &columns, &mut problems);
+                }
+            }
+            NodeEnum::CreateSeqStmt(n) => {
+                let owner = sequence_owned_by(&n.options);
+                if let (Some(r), Some(owner)) = (&n.sequence, owner) {
+                    self.check_owned_by(r, &owner, &mut problems);
+                }
+            }
+            NodeEnum::AlterSeqStmt(n) => {
+                let owner = sequence_owned_by(&n.options);
+                if let (Some(r), Some(owner)) = (&n.sequence, owner) {
+                    self.check_owned_by(r, &owner, &mut problems);
+                }
+            }
+            NodeEnum::ClusterStmt(n) => {
+                let Some(r) = &n.relation else {
+                    return problems;
+                };
+                let Relation::Table(table) = self.relation(r) else {
+                    self.check_columns(r, &[], &mut problems);
+                    return problems;
+                };
+                if n.indexname.is_empty() {
+                    if table.clustered_index.is_none() {
+                        problems.push((
+                            NO_CLUSTERED_INDEX,
+                            format!(
+                                "there is no previously clustered index for table {}, add USING",
+                                r.relname
+                            ),
+                            r.location,
+                        ));
+                    }
+                } else {
+                    problems.extend(unknown_index(table, r, &n.indexname));
+                }
+            }
+            NodeEnum::RenameStmt(n) if !n.missing_ok => match (n.rename_type, &n.relation) {
+                // ObjectTable
+                (42, Some(r)) => self.check_columns(r, &[], &mut problems),
+                // ObjectColumn
+                (7, Some(r)) => {
+                    self.check_columns(r, &[(n.subname.as_str(), r.location)], &mut problems)
+                }
+                _ => {}
+            },
+            NodeEnum::TruncateStmt(n) => {
+                let relations = n
+                    .relations
+                    .iter()
+                    .filter_map(|r| match r.node.as_ref()? {
+                        NodeEnum::RangeVar(r) => Some(r),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                for r in &relations {
+                    self.check_columns(r, &[], &mut problems);
+                }
+                let truncated = relations
+                    .iter()
+                    .map(|r| self.relation_name(r))
+                    .collect::<Vec<_>>();
+                let cascaded = self.truncate_cascade(&truncated);
+                if cascaded.is_empty() {
+                    return problems;
+                }
+                let names = cascaded
+                    .iter()
+                    .map(|(schema, name)| match schema.as_str() {
+                        DEFAULT_SCHEMA => name.clone(),
+                        _ => format!("{}.{}", schema, name),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                // DropCascade
+                if n.behavior == 2 {
+                    problems.push((
+                        TRUNCATE_CASCADE,
+                        format!("TRUNCATE ... CASCADE also truncates {}", names),
+                        -1,
+                    ));
+                } else {
+                    problems.push((
+                        TRUNCATE_REFERENCED,
+                        format!(
+                            "cannot truncate a table referenced in a foreign key constraint, truncate {} as well or use CASCADE",
+                            names
+                        ),
+                        -1,
+                    ));
+                }
+            }
+            NodeEnum::InsertStmt(n) => {
+                if let Some(r) = &n.relation {
+                    self.check_columns(r, &targets(&n.cols), &mut problems);
+                    problems.extend(self.check_insert_arity(r, &n.cols, n.select_stmt.as_deref()));
+                }
+            }
+            NodeEnum::UpdateStmt(n) => {
+                if let Some(r) = &n.relation {
+                    self.check_columns(r, &targets(&n.target_list), &mut problems);
+                }
+            }
+            NodeEnum::ConstraintsSetStmt(n) => {
+                for constraint in &n.constraints {
+                    let Some(NodeEnum::RangeVar(r)) = constraint.node.as_ref() else {
+                        continue;
+                    };
+                    match self.constraint_deferral(r) {
+                        Some(Some(Deferral::NotDeferrable)) => problems.push((
+                            NOT_DEFERRABLE,
+                            format!("constraint {} is not deferrable", r.relname),
+                            r.location,
+                        )),
+                        Some(None) => problems.push((
+                            UNKNOWN_CONSTRAINT,
+                            format!("constraint {} does not exist", r.relname),
+                            r.location,
+                        )),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+        problems
+    }
+
+    /// Checks that the column of `OWNED BY` exists and belongs to a table in the schema of the
+    /// `sequence`
+    fn check_owned_by(
+        &self,
+        sequence: &RangeVar,
+        owned_by: &[&str],
+        problems: &mut Vec<(&'static str, String, i32)>,
+    ) {
+        let Some((schema, table, column)) = self.owning_column(owned_by) else {
+            return;
+        };
+        if schema != self.relation_name(sequence).0 {
+            problems.push((
+                UNKNOWN_RELATION,
+                format!(
+                    "sequence {} must be in the same schema as table {}",
+                    sequence.relname, table
+                ),
+                sequence.location,
+            ));
+            return;
+        }
+        let relation = RangeVar {
+            schemaname: schema,
+            relname: table,
+            location: sequence.location,
+            ..RangeVar::default()
+        };
+        self.check_columns(&relation, &[(column.as_str(), sequence.location)], problems);
+    }
+
+    /// Returns the deferral of the constraint named by `constraint` in any table of its schema,
+    /// `Some(None)` if there is no such constraint, or `None` if the schema is not modeled
+    fn constraint_deferral(&self, constraint: &RangeVar) -> Option<Option<Deferral>> {
+        let (schema, name) = self.relation_name(constraint);
+        let schema = self.schemas.get(&schema)?;
+        Some(
+            schema
+                .tables
+                .values()
+                .find_map(|table| table.constraint_deferral(&name)),
+        )
+    }
+
+    /// Checks that every row of `VALUES` has a value for each target column, which are all columns
+    /// of the table without a column list
+    fn check_insert_arity(
+        &self,
+        relation: &RangeVar,
+        cols: &[Node],
+        query: Option<&Node>,
+    ) -> Option<(&'static str, String, i32)> {
+        let Some(NodeEnum::SelectStmt(select)) = query.and_then(|q| q.node.as_ref()) else {
+            return None;
+        };
+        let targets = if cols.is_empty() {
+            let Relation::Table(table) = self.relation(relation) else {
+                return None;
+            };
+            table.columns.len()
+        } else {
+            cols.len()
+        };
+        select.values_lists.iter().find_map(|row| {
+            let Some(NodeEnum::List(row)) = row.node.as_ref() else {
+                return None;
+            };
+            if row.items.len() > targets {
+                Some((
+                    INSERT_ARITY,
+                    format!(
+                        "INSERT has more expressions than target columns: {} values for {} column(s) of {}",
+                        row.items.len(),
+                        targets,
+                        relation.relname
+                    ),
+                    relation.location,
+                ))
+            } else if row.items.len() < targets && !cols.is_empty() {
+                Some((
+                    INSERT_ARITY,
+                    format!(
+                        "INSERT has more target columns than expressions: {} values for {} column(s) of {}",
+                        row.items.len(),
+                        targets,
+                        relation.relname
+                    ),
+                    relation.location,
+                ))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Reports the foreign keys of `table` that reference tables which are not inserted into
+    /// `before` this insert, but only `after` it, unless the foreign keys are deferred
+    fn check_insert_order(
+        &self,
+        table: &(String, String),
+        before: &[Option<(String, String)>],
+        after: &[Option<(String, String)>],
+        deferred: &DeferredConstraints,
+    ) -> Vec<(&'static str, String, i32)> {
+        let Relation::Table(t) = self.relation_named(&table.0, &table.1) else {
+            return Vec::new();
+        };
+        let mut problems = Vec::new();
+        for (name, definition) in &t.constraints {
+            let Some(referenced) = referenced_table(definition, &table.0) else {
+                continue;
+            };
+            let is_inserted = |tables: &[Option<(String, String)>]| {
+                tables.iter().flatten().any(|t| *t == referenced)
+            };
+            if referenced == *table || is_inserted(before) || !is_inserted(after) {
+                continue;
+            }
+            let deferral = Deferral::of(definition);
+            if deferred.is_deferred(name, deferral) {
+                continue;
+            }
+            let referenced_name = &referenced.1;
+            let message = match deferral {
+                Deferral::NotDeferrable => format!(
+                    "foreign key {} requires rows of {} that are only inserted later, make it \
+                     DEFERRABLE and defer it with SET CONSTRAINTS or insert into {} first",
+                    name, referenced_name, referenced_name
+                ),
+                Deferral::InitiallyImmediate if deferred.in_transaction => format!(
+                    "foreign key {} requires rows of {} that are only inserted later, run SET \
+                     CONSTRAINTS {} DEFERRED before it or insert into {} first",
+                    name, referenced_name, name, referenced_name
+                ),
+                Deferral::InitiallyImmediate => format!(
+                    "foreign key {} requires rows of {} that are only inserted later, run both \
+                     within BEGIN and COMMIT after SET CONSTRAINTS {} DEFERRED or insert into {} \
+                     first",
+                    name, referenced_name, name, referenced_name
+                ),
+                Deferral::InitiallyDeferred => format!(
+                    "foreign key {} requires rows of {} that are only inserted later, run both \
+                     within BEGIN and COMMIT or insert into {} first",
+                    name, referenced_name, referenced_name
+                ),
+            };
+            problems.push((UNDEFERRED_FOREIGN_KEY, message, -1));
+        }
+        problems
+    }
+
+    /// Reports `relation` if it does not exist, and the `columns` it does not have
+    fn check_columns(
+        &self,
+        relation: &RangeVar,
+        columns: &[(&str, i32)],
+        problems: &mut Vec<(&'static str, String, i32)>,
+    ) {
+        match self.relation(relation) {
+            Relation::Table(table) => {
+                for (column, location) in columns {
+                    if table.column(column).is_none() {
+                        problems.push((
+                            UNKNOWN_COLUMN,
+                            format!(
+                                "column {} of relation {} does not exist",
+                                column, relation.relname
+                            ),
+                            *location,
+                        ));
+                    }
+                }
+            }
+            Relation::Missing => problems.push((
+                UNKNOWN_RELATION,
+                format!("relation {} does not exist", relation.relname),
+                relation.location,
+            )),
+            Relation::Other | Relation::Unknown => {}
+        }
+    }
+
+    /// Returns the tables that `TRUNCATE ... CASCADE` of the tables `truncated` empties as well,
+    /// i.e. the tables that reference them with foreign keys, directly or through other such tables
+    fn truncate_cascade(&self, truncated: &[(String, String)]) -> Vec<(String, String)> {
+        let references = self
+            .schemas
+            .iter()
+            .flat_map(|(schema, s)| {
+                s.tables.values().flat_map(move |table| {
+                    table.constraints.values().filter_map(move |definition| {
+                        let referenced = referenced_table(definition, schema)?;
+                        Some(((schema.clone(), table.name.clone()), referenced))
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut emptied = truncated.to_vec();
+        let mut cascaded = Vec::new();
+        while let Some((table, _)) = references
+            .iter()
+            .find(|(table, referenced)| emptied.contains(referenced) && !emptied.contains(table))
+        {
+            emptied.push(table.clone());
+            cascaded.push(table.clone());
+        }
+        cascaded
+    }
+
+    fn relation(&self, relation: &RangeVar) -> Relation<'_> {
+        let (schema, name) = self.relation_name(relation);
+        self.relation_named(&schema, &name)
+    }
+
+    fn relation_named(&self, schema: &str, name: &str) -> Relation<'_> {
+        if let Some(table) = self.schemas.get(schema).and_then(|s| s.table(name)) {
+            Relation::Table(table)
+        } else if self
+            .other_relations
+            .contains(&format!("{}.{}", schema, name))
+            || self.schemas.get(schema).is_some_and(|s| {
+                s.tables
+                    .values()
+                    .any(|t| t.owned_sequences.contains_key(name))
+            })
+        {
+            Relation::Other
+        } else if self.schemas.contains_key(schema) {
+            Relation::Missing
+        } else {
+            Relation::Unknown
+        }
+    }
+
+    fn apply(&mut self, stmt: &NodeEnum) {
+        let qualified;
+        let stmt = match self.qualify_references(stmt) {
+            Some(stmt) => {
+                qualified = stmt;
+                &qualified
+            }
+            None => stmt,
+        };
+        match stmt {
+            NodeEnum::CreateSchemaStmt(n) => {
+                self.schema_mut(&n.schemaname);
+            }
+            NodeEnum::CreateStmt(n) => self.create_table(n),
+            NodeEnum::CreateForeignTableStmt(n) => {
+                if let Some(r) = n.base_stmt.as_ref().and_then(|s| s.relation.as_ref()) {
+                    self.add_other_relation(r);
+                }
+            }
+            NodeEnum::ViewStmt(n) => {
+                if let Some(r) = &n.view {
+                    self.add_other_relation(r);
+                }
+            }
+            NodeEnum::CreateTableAsStmt(n) => {
+                if let Some(r) = n.into.as_ref().and_then(|i| i.rel.as_ref()) {
+                    self.add_other_relation(r);
+                }
+            }
+            NodeEnum::CreateSeqStmt(n) => {
+                if let Some(r) = &n.sequence {
+                    let (schema, name) = self.created_name(r);
+                    self.schema_mut(&schema);
+                    self.own_sequence(&schema, &name, sequence_owned_by(&n.options));
+                }
+            }
+            NodeEnum::AlterSeqStmt(n) => {
+                if let (Some(r), Some(owner)) = (&n.sequence, sequence_owned_by(&n.options)) {
+                    let (schema, name) = self.relation_name(r);
+                    self.own_sequence(&schema, &name, Some(owner));
+                }
+            }
+            NodeEnum::IndexStmt(n) => self.create_index(n),
+            NodeEnum::ClusterStmt(n) => self.cluster(n),
+            NodeEnum::DropStmt(n) => self.drop(n),
+            // ObjectTable
+            NodeEnum::AlterTableStmt(n) if n.objtype == 42 => self.alter_table(n),
+            NodeEnum::RenameStmt(n) => self.rename(n),
+            NodeEnum::VariableSetStmt(n) if n.name == "search_path" => {
+                match n.kind {
+                    // VarSetValue
+                    1 => {
+                        self.search_path = Some(
+                            n.args
+                                .iter()
+                                .filter_map(|arg| match arg.node.as_ref()? {
+                                    NodeEnum::AConst(c) => match c.val.as_ref()? {
+                                        Val::Sval(s) => Some(s.sval.clone()),
+                                        _ => None,
+                                    },
+                                    _ => None,
+                                })
+                                .collect(),
+                        )
+                    }
+                    // VarSetDefault, VarReset and VarResetAll
+                    2 | 5 | 6 => self.search_path = None,
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns `stmt` with the schema of the search path added to the unqualified tables that its
+    /// foreign keys reference, or `None` if it has none or the search path is the default one.
+    /// The definitions of the constraints then refer to the same tables without the search path,
+    /// e.g. in the DDL of [`squash`].
+    fn qualify_references(&self, stmt: &NodeEnum) -> Option<NodeEnum> {
+        self.search_path.as_ref()?;
+        let mut stmt = stmt.clone();
+        let mut constraints = Vec::new();
+        match &mut stmt {
+            NodeEnum::CreateStmt(n) => {
+                for element in &mut n.table_elts {
+                    match element.node.as_mut() {
+                        Some(NodeEnum::Constraint(c)) => constraints.push(c),
+                        Some(NodeEnum::ColumnDef(def)) => {
+                            constraints.extend(column_def_constraints(def))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            NodeEnum::AlterTableStmt(n) => {
+                for cmd in &mut n.cmds {
+                    let Some(NodeEnum::AlterTableCmd(cmd)) = cmd.node.as_mut() else {
+                        continue;
+                    };
+                    match cmd.def.as_mut().and_then(|def| def.node.as_mut()) {
+                        Some(NodeEnum::Constraint(c)) => constraints.push(c),
+                        Some(NodeEnum::ColumnDef(def)) => {
+                            constraints.extend(column_def_constraints(def))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => return None,
+        }
+        let mut changed = false;
+        // ConstrForeign
+        for constraint in constraints.into_iter().filter(|c| c.contype == 10) {
+            let Some(pktable) = constraint.pktable.as_mut() else {
+                continue;
+            };
+            if pktable.schemaname.is_empty() {
+                pktable.schemaname = self.schema_of(&pktable.relname);
+                changed = true;
+            }
+        }
+        changed.then_some(stmt)
+    }
+
+    /// Returns the schemas of the search path in which unqualified names are looked up, without
+    /// `$user` and the system schemas
+    fn search_path(&self) -> impl Iterator<Item = &str> {
+        let path = match &self.search_path {
+            Some(path) => path.as_slice(),
+            None => &[],
+        };
+        let default = self.search_path.is_none().then_some(DEFAULT_SCHEMA);
+        path.iter()
+            .map(String::as_str)
+            .filter(|schema| !matches!(*schema, "$user" | "pg_catalog" | "pg_temp"))
+            .chain(default)
+    }
+
+    /// Returns the schema that `CREATE` statements create unqualified objects in
+    fn creation_schema(&self) -> String {
+        self.search_path()
+            .next()
+            .unwrap_or(DEFAULT_SCHEMA)
+            .to_string()
+    }
+
+    /// Returns the schema of the relation or index `name` that an unqualified name refers to: the
+    /// first schema of the search path that has one, or the schema that it would be created in
+    fn schema_of(&self, name: &str) -> String {
+        self.search_path()
+            .find(|schema| {
+                matches!(
+                    self.relation_named(schema, name),
+                    Relation::Table(_) | Relation::Other
+                ) || self
+                    .schemas
+                    .get(*schema)
+                    .is_some_and(|s| s.tables.values().any(|t| t.has_index(name)))
+            })
+            .map_or_else(|| self.creation_schema(), str::to_string)
+    }
+
+    /// Returns the schema and name of the relation that `relation` refers to
+    pub(crate) fn relation_name(&self, relation: &RangeVar) -> (String, String) {
+        let schema = if relation.schemaname.is_empty() {
+            self.schema_of(&relation.relname)
+        } else {
+            relation.schemaname.clone()
+        };
+        (schema, relation.relname.clone())
+    }
+
+    /// Returns the schema and name of the relation that `CREATE` creates as `relation`
+    fn created_name(&self, relation: &RangeVar) -> (String, String) {
+        let schema = if relation.schemaname.is_empty() {
+            self.creation_schema()
+        } else {
+            relation.schemaname.clone()
+        };
+        (schema, relation.relname.clone())
+    }
+
+    /// Returns the schema and name of an object of a `DROP` statement, given as a list of names
+    fn object_name(&self, object: &Node) -> Option<(String, String)> {
+        let Some(NodeEnum::List(list)) = object.node.as_ref() else {
+            return None;
+        };
+        match list
+            .items
+            .iter()
+            .filter_map(string_value)
+            .collect::<Vec<_>>()
+            .as_slice()
+        {
+            [name] => Some((self.schema_of(name), name.to_string())),
+            [.., schema, name] => Some((schema.to_string(), name.to_string())),
+            [] => None,
+        }
+    }
+
+    /// Returns the schema, table and column of the names of `OWNED BY`, or `None` for
+    /// `OWNED BY NONE`
+    fn owning_column(&self, names: &[&str]) -> Option<(String, String, String)> {
+        match names {
+            [table, column] => Some((self.schema_of(table), table.to_string(), column.to_string())),
+            [.., schema, table, column] => {
+                Some((schema.to_string(), table.to_string(), column.to_string()))
+            }
+            _ => None,
+        }
+    }
+
+    fn schema_mut(&mut self, name: &str) -> &mut Schema {
+        self.schemas
+            .entry(name.to_string())
+            .or_insert_with(|| Schema::new(name))
+    }
+
+    fn table_mut(&mut self, schema: &str, name: &str) -> Option<&mut Table> {
+        self.schemas.get_mut(schema)?.tables.get_mut(name)
+    }
+
+    fn add_other_relation(&mut self, relation: &RangeVar) {
+        let (schema, name) = self.created_name(relation);
+        self.schema_mut(&schema);
+        self.other_relations.insert(format!("{}.{}", schema, name));
+    }
+
+    /// Makes the sequence `name` owned by the column that `owned_by` names, or a sequence of its
+    /// own if it names none, e.g. with `OWNED BY NONE`
+    fn own_sequence(&mut self, schema: &str, name: &str, owned_by: Option<Vec<&str>>) {
+        self.drop_sequence(schema, name);
+        let owner = owned_by
+            .as_deref()
+            .and_then(|names| self.owning_column(names));
+        if let Some((owner_schema, table, column)) = owner.filter(|(s, _, _)| s == schema) {
+            let table = self
+                .table_mut(&owner_schema, &table)
+                .filter(|t| t.column(&column).is_some());
+            if let Some(table) = table {
+                table.owned_sequences.insert(name.to_string(), column);
+                return;
+            }
+        }
+        self.other_relations.insert(format!("{}.{}", schema, name));
+    }
+
+    fn drop_sequence(&mut self, schema: &str, name: &str) {
+        self.other_relations.remove(&format!("{}.{}", schema, name));
+        if let Some(schema) = self.schemas.get_mut(schema) {
+            for table in schema.tables.values_mut() {
+                table.owned_sequences.remove(name);
+            }
+        }
+    }
+
+    fn create_table(&mut self, n: &CreateStmt) {
+        let Some(r) = &n.relation else {
+            return;
+        };
+        let (schema, name) = self.created_name(r);
+        if self.table_mut(&schema, &name).is_some() {
+            // either `IF NOT EXISTS` or an error
+            return;
+        }
+
+        let mut table = Table {
+            name: name.clone(),
+            access_method: access_method(&n.access_method),
+            partition_key: n
+                .partspec
+                .as_ref()
+                .and_then(|spec| deparse_partition_key(spec)),
+            partition_columns: n.partspec.as_ref().map(partition_columns),
+            fillfactor: fillfactor(&n.options),
+            ..Table::default()
+        };
+        // columns of parent tables and partitioned tables come first
+        for parent in &n.inh_relations {
+            if let Some(NodeEnum::RangeVar(parent)) = parent.node.as_ref() {
+                if let Relation::Table(parent) = self.relation(parent) {
+                    table.columns.extend(parent.columns.iter().cloned());
+                }
+            }
+        }
+        for element in &n.table_elts {
+            match element.node.as_ref() {
+                Some(NodeEnum::ColumnDef(def)) => add_column(&mut table, def),
+                Some(NodeEnum::Constraint(c)) => add_constraint(&mut table, c, None),
+                Some(NodeEnum::TableLikeClause(like)) => {
+                    if let Some(Relation::Table(parent)) =
+                        like.relation.as_ref().map(|r| self.relation(r))
+                    {
+                        add_like(&mut table, parent, like.options);
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.schema_mut(&schema).tables.insert(name, table);
+    }
+
+    fn create_index(&mut self, n: &IndexStmt) {
+        let Some(r) = &n.relation else {
+            return;
+        };
+        let (schema, name) = self.relation_name(r);
+        let Some(table) = self.table_mut(&schema, &name) else {
+            return;
+        };
+
+        let mut index = n.clone();
+        if index.idxname.is_empty() {
+            index.idxname = default_index_name(&table.name, n);
+        }
+        if table.indexes.contains_key(&index.idxname) {
+            return;
+        }
+        let index_name = index.idxname.clone();
+        index.if_not_exists = false;
+        index.concurrent = false;
+        if let Some(relation) = &mut index.relation {
+            relation.schemaname = String::new();
+        }
+        if let Some(definition) = deparse(NodeEnum::IndexStmt(index.into())) {
+            table.indexes.insert(index_name, definition);
+        }
+    }
+
+    /// Marks the index of `CLUSTER ... USING` as the clustering index of its table
+    fn cluster(&mut self, n: &ClusterStmt) {
+        let Some(r) = n.relation.as_ref().filter(|_| !n.indexname.is_empty()) else {
+            return;
+        };
+        let (schema, name) = self.relation_name(r);
+        if let Some(table) = self.table_mut(&schema, &name) {
+            if table.has_index(&n.indexname) {
+                table.clustered_index = Some(n.indexname.clone());
+            }
+        }
+    }
+
+    fn drop(&mut self, n: &DropStmt) {
+        let objects = n
+            .objects
+            .iter()
+            .filter_map(|object| self.object_name(object))
+            .collect::<Vec<_>>();
+        for (schema, name) in objects {
+            match n.remove_type {
+                // ObjectTable
+                42 => {
+                    if let Some(schema) = self.schemas.get_mut(&schema) {
+                        schema.tables.remove(&name);
+                    }
+                }
+                // ObjectIndex
+                21 => {
+                    let tables = self
+                        .schemas
+                        .get_mut(&schema)
+                        .into_iter()
+                        .flat_map(|s| s.tables.values_mut());
+                    for table in tables {
+                        table.indexes.remove(&name);
+                        if table.clustered_index.as_ref() == Some(&name) {
+                            table.clustered_index = None;
+                        }
+                    }
+                }
+                // ObjectSequence
+                38 => self.drop_sequence(&schema, &name),
+                // ObjectForeignTable, ObjectMatview, ObjectView
+                19 | 24 | 52 => {
+                    self.other_relations.remove(&format!("{}.{}", schema, name));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn alter_table(&mut self, n: &AlterTableStmt) {
+        let Some(r) = &n.relation else {
+            return;
+        };
+        let (schema, name) = self.relation_name(r);
+        let Some(table) = self.table_mut(&schema, &name) else {
+            return;
+        };
+
+        for cmd in alter_table_cmds(n) {
+            apply_alter_table_cmd(table, cmd);
+        }
+    }
+
+    fn rename(&mut self, n: &RenameStmt) {
+        let Some(r) = &n.relation else {
+            return;
+        };
+        let (schema, name) = self.relation_name(r);
+        match n.rename_type {
+            // ObjectTable
+            42 => {
+                if let Some(schema) = self.schemas.get_mut(&schema) {
+                    if let Some(mut table) = schema.tables.remove(&name) {
+                        table.name = n.newname.clone();
+                        schema.tables.insert(n.newname.clone(), table);
+                    }
+                }
+            }
+            // ObjectColumn
+            7 => {
+                let column = self
+                    .table_mut(&schema, &name)
+                    .and_then(|table| column_mut(table, &n.subname));
+                if let Some(column) = column {
+                    column.name = n.newname.clone();
+                }
+                if let Some(table) = self.table_mut(&schema, &name) {
+                    for column in table.owned_sequences.values_mut() {
+                        if *column == n.subname {
+                            *column = n.newname.clone();
+                        }
+                    }
+                }
+            }
+            // ObjectTabconstraint
+            41 => {
+                if let Some(table) = self.table_mut(&schema, &name) {
+                    if let Some(definition) = table.constraints.remove(&n.subname) {
+                        table.constraints.insert(n.newname.clone(), definition);
+                    }
+                }
+            }
+            // ObjectForeignTable, ObjectMatview, ObjectView
+            19 | 24 | 52 => {
+                if self.other_relations.remove(&format!("{}.{}", schema, name)) {
+                    self.other_relations
+                        .insert(format!("{}.{}", schema, n.newname));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Returns true if the replay captures the complete effect of `stmt` on the schema, so that
+/// [`squash`] does not lose anything of it. Statements without effect on the schema, such as `SET`,
+/// are squashable as well. The effect of `SET search_path` is captured by the schemas that the
+/// replay puts objects in and adds to the tables that foreign keys reference.
+pub fn is_squashable(stmt: &NodeEnum) -> bool {
+    match stmt {
+        NodeEnum::CreateSchemaStmt(n) => n.schema_elts.is_empty(),
+        NodeEnum::CreateStmt(n) => {
+            n.inh_relations.is_empty() && n.partbound.is_none() && n.partspec.is_none()
+        }
+        NodeEnum::IndexStmt(_) | NodeEnum::VariableSetStmt(_) | NodeEnum::TransactionStmt(_) => {
+            true
+        }
+        // the order of the rows is not part of the schema, only the clustering index is
+        NodeEnum::ClusterStmt(_) => true,
+        // ObjectIndex, ObjectTable
+        NodeEnum::DropStmt(n) => matches!(n.remove_type, 21 | 42),
+        // ObjectTable
+        NodeEnum::AlterTableStmt(n) => {
+            n.objtype == 42
+                && alter_table_cmds(n).all(|cmd| {
+                    // AtAddColumn, AtColumnDefault, AtDropNotNull, AtSetNotNull, AtDropColumn,
+                    // AtAddConstraint, AtDropConstraint, AtAlterColumnType, AtClusterOn,
+                    // AtDropCluster
+                    matches!(cmd.subtype, 1 | 4 | 6 | 7 | 15 | 19 | 27 | 30 | 33 | 34)
+                })
+        }
+        // ObjectColumn, ObjectTabconstraint, ObjectTable
+        NodeEnum::RenameStmt(n) => matches!(n.rename_type, 7 | 41 | 42),
+        _ => false,
+    }
+}
+
+/// Returns DDL that turns the schema of `base` into the schema of `target`
+///
+/// Renamed objects are dropped and created again, so the DDL is meant for databases without data,
+/// e.g. when replacing old migrations with a single one that new databases start from.
+pub fn squash(base: &MigrationState, target: &MigrationState) -> String {
+    // dropped constraints may reference dropped tables, and added constraints may reference tables
+    // that are added after the table of the constraint
+    let mut dropped_constraints = Vec::new();
+    let mut drops = Vec::new();
+    let mut creates = Vec::new();
+    let mut constraints = Vec::new();
+    let mut indexes = Vec::new();
+    // the clustering index must exist before the table is clustered on it
+    let mut clusters = Vec::new();
+
+    for (schema_name, schema) in &target.schemas {
+        let empty = Schema::new(schema_name);
+        let old = match base.schemas.get(schema_name) {
+            Some(old) => old,
+            None => {
+                if schema_name != DEFAULT_SCHEMA {
+                    creates.push(format!("CREATE SCHEMA {};", quote_ident(schema_name)));
+                }
+                &empty
+            }
+        };
+        let qualified = |name: &str| format!("{}.{}", quote_ident(schema_name), quote_ident(name));
+        let add_constraint = |table: &str, name: &str, definition: &str| {
+            format!(
+                "ALTER TABLE {} ADD CONSTRAINT {} {};",
+                qualified(table),
+                quote_ident(name),
+                definition
+            )
+        };
+        let create_index =
+            |definition: &str| format!("{};", qualify_index(definition, schema_name));
+
+        for change in diff(old, schema) {
+            let table = qualified(change.table());
+            match change {
+                SchemaChange::TableAdded { table: name } => {
+                    let new = &schema.tables[&name];
+                    let columns = new
+                        .columns
+                        .iter()
+                        .map(|c| format!("    {}", column_sql(c)))
+                        .collect::<Vec<_>>();
+                    let mut using = match &new.access_method {
+                        Some(access_method) => format!(" USING {}", quote_ident(access_method)),
+                        None => String::new(),
+                    };
+                    if let Some(fillfactor) = new.fillfactor {
+                        using.push_str(&format!(" WITH (fillfactor = {})", fillfactor));
+                    }
+                    creates.push(format!(
+                        "CREATE TABLE {} (\n{}\n){};",
+                        table,
+                        columns.join(",\n"),
+                        using
+                    ));
+                    if let Some(owner) = &new.owner {
+                        creates.push(format!(
+                            "ALTER TABLE {} OWNER TO {};",
+                            table,
+                            quote_ident(owner)
+                        ));
+                    }
+                    for (constraint, definition) in &new.constraints {
+                        constraints.push(add_constraint(&name, constraint, definition));
+                    }
+                    indexes.extend(new.indexes.values().map(|d| create_index(d)));
+                }
+                SchemaChange::TableRemoved { .. } => drops.push(format!("DROP TABLE {};", table)),
+                SchemaChange::ColumnAdded { column, .. } => creates.push(format!(
+                    "ALTER TABLE {} ADD COLUMN {};",
+                    table,
+                    column_sql(&column)
+                )),
+                SchemaChange::ColumnRemoved { column, .. } => drops.push(format!(
+                    "ALTER TABLE {} DROP COLUMN {};",
+                    table,
+                    quote_ident(&column.name)
+                )),
+                SchemaChange::ColumnChanged { old, new, .. } => {
+                    let alter = format!(
+                        "ALTER TABLE {} ALTER COLUMN {}",
+                        table,
+                        quote_ident(&new.name)
+                    );
+                    if old.data_type != new.data_type {
+                        creates.push(format!("{} TYPE {};", alter, new.data_type));
+                    }
+                    if old.not_null != new.not_null {
+                        let action = if new.not_null { "SET" } else { "DROP" };
+                        creates.push(format!("{} {} NOT NULL;", alter, action));
+                    }
+                    if old.default_expr != new.default_expr {
+                        creates.push(match &new.default_expr {
+                            Some(default) => format!("{} SET DEFAULT {};", alter, default),
+                            None => format!("{} DROP DEFAULT;", alter),
+                        });
+                    }
+                }
+                SchemaChange::IndexAdded { definition, .. } => {
+                    indexes.push(create_index(&definition))
+                }
+                SchemaChange::IndexRemoved { name, .. } => {
+                    drops.push(format!("DROP INDEX {};", qualified(&name)))
+                }
+                SchemaChange::IndexChanged { name, new, .. } => {
+                    drops.push(format!("DROP INDEX {};", qualified(&name)));
+                    indexes.push(create_index(&new));
+                }
+                SchemaChange::ConstraintAdded {
+                    table: table_name,
+                    name,
+                    definition,
+                } => constraints.push(add_constraint(&table_name, &name, &definition)),
+                SchemaChange::ConstraintRemoved { name, .. } => dropped_constraints.push(format!(
+                    "ALTER TABLE {} DROP CONSTRAINT {};",
+                    table,
+                    quote_ident(&name)
+                )),
+                SchemaChange::ConstraintChanged {
+                    table: table_name,
+                    name,
+                    new,
+                    ..
+                } => {
+                    dropped_constraints.push(format!(
+                        "ALTER TABLE {} DROP CONSTRAINT {};",
+                        table,
+                        quote_ident(&name)
+                    ));
+                    constraints.push(add_constraint(&table_name, &name, &new));
+                }
+            }
+        }
+
+        for (name, table) in &schema.tables {
+            // added tables are created with their fillfactor
+            let old_table = old.tables.get(name);
+            if old_table.is_some_and(|t| t.fillfactor != table.fillfactor) {
+                creates.push(match table.fillfactor {
+                    Some(fillfactor) => format!(
+                        "ALTER TABLE {} SET (fillfactor = {});",
+                        qualified(name),
+                        fillfactor
+                    ),
+                    None => format!("ALTER TABLE {} RESET (fillfactor);", qualified(name)),
+                });
+            }
+            let old_index = old_table.and_then(|t| t.clustered_index.as_ref());
+            if table.clustered_index.as_ref() == old_index {
+                continue;
+            }
+            clusters.push(match &table.clustered_index {
+                Some(index) => format!(
+                    "ALTER TABLE {} CLUSTER ON {};",
+                    qualified(name),
+                    quote_ident(index)
+                ),
+                None => format!("ALTER TABLE {} SET WITHOUT CLUSTER;", qualified(name)),
+            });
+        }
+    }
+
+    [
+        dropped_constraints,
+        drops,
+        creates,
+        constraints,
+        indexes,
+        clusters,
+    ]
+    .into_iter()
+    .filter(|stmts| !stmts.is_empty())
+    .map(|stmts| stmts.join("\n") + "\n")
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+/// Returns the definition of `column` as in `CREATE TABLE`
+fn column_sql(column: &Column) -> String {
+    let mut sql = format!("{} {}", quote_ident(&column.name), column.data_type);
+    if column.not_null {
+        sql.push_str(" NOT NULL");
+    }
+    if let Some(default) = &column.default_expr {
+        sql.push_str(" DEFAULT ");
+        sql.push_str(default);
+    }
+    sql
+}
+
+/// Returns the schema and name of the table that a foreign key constraint references, given its
+/// `definition`, or `None` for other constraints. Unqualified tables are in `schema`, the schema of
+/// the referencing table.
+fn referenced_table(definition: &str, schema: &str) -> Option<(String, String)> {
+    if !definition.starts_with("FOREIGN KEY") {
+        return None;
+    }
+    let stmt = pg_query::parse(&format!("ALTER TABLE t ADD {}", definition))
+        .ok()
+        .and_then(|result| result.protobuf.stmts.into_iter().next())
+        .and_then(|stmt| stmt.stmt)
+        .and_then(|stmt| stmt.node);
+    let Some(NodeEnum::AlterTableStmt(n)) = stmt else {
+        return None;
+    };
+    alter_table_cmds(&n).find_map(|cmd| match cmd.def.as_ref()?.node.as_ref()? {
+        // ConstrForeign
+        NodeEnum::Constraint(c) if c.contype == 10 => {
+            let pktable = c.pktable.as_ref()?;
+            let schema = if pktable.schemaname.is_empty() {
+                schema
+            } else {
+                &pktable.schemaname
+            };
+            Some((schema.to_string(), pktable.relname.clone()))
+        }
+        _ => None,
+    })
+}
+
+/// Qualifies the table of an index definition with `schema`
+fn qualify_index(definition: &str, schema: &str) -> String {
+    let stmt = pg_query::parse(definition)
+        .ok()
+        .and_then(|result| result.protobuf.stmts.into_iter().next())
+        .and_then(|stmt| stmt.stmt)
+        .and_then(|stmt| stmt.node);
+    match stmt {
+        Some(NodeEnum::IndexStmt(mut index)) => {
+            if let Some(relation) = &mut index.relation {
+                relation.schemaname = schema.to_string();
+            }
+            deparse(NodeEnum::IndexStmt(index)).unwrap_or_else(|| definition.to_string())
+        }
+        _ => definition.to_string(),
+    }
+}
+
+/// Returns the problem of `cmd`, a command of an `ALTER TABLE` of `relation`, if any. `table` is
+/// the table as changed by the commands before `cmd`.
+fn check_alter_table_cmd(
+    table: &Table,
+    relation: &RangeVar,
+    cmd: &AlterTableCmd,
+) -> Option<(&'static str, String, i32)> {
+    if cmd.missing_ok {
+        return None;
+    }
+    match cmd.subtype {
+        // AtAddColumn
+        1 => {
+            let def = column_def(cmd)?;
+            table.column(&def.colname).is_some().then(|| {
+                (
+                    DUPLICATE_OBJECT,
+                    format!(
+                        "column {} of relation {} already exists",
+                        def.colname, relation.relname
+                    ),
+                    relation.location,
+                )
+            })
+        }
+        // AtColumnDefault, AtDropNotNull, AtSetNotNull, AtDropColumn, AtAlterColumnType
+        4 | 6 | 7 | 15 | 30 => table.column(&cmd.name).is_none().then(|| {
+            (
+                UNKNOWN_COLUMN,
+                format!(
+                    "column {} of relation {} does not exist",
+                    cmd.name, relation.relname
+                ),
+                relation.location,
+            )
+        }),
+        // AtClusterOn
+        33 => unknown_index(table, relation, &cmd.name),
+        _ => None,
+    }
+}
+
+/// Returns the problem if `table`, named by `relation`, has no index `index`
+fn unknown_index(
+    table: &Table,
+    relation: &RangeVar,
+    index: &str,
+) -> Option<(&'static str, String, i32)> {
+    (!table.has_index(index)).then(|| {
+        (
+            UNKNOWN_RELATION,
+            format!(
+                "index {} of relation {} does not exist",
+                index, relation.relname
+            ),
+            relation.location,
+        )
+    })
+}
+
+/// Applies a command of an `ALTER TABLE` to `table`
+fn apply_alter_table_cmd(table: &mut Table, cmd: &AlterTableCmd) {
+    let def = cmd.def.as_ref().and_then(|d| d.node.as_ref());
+    match (cmd.subtype, def) {
+        // AtAddColumn
+        (1, Some(NodeEnum::ColumnDef(def))) => {
+            if table.column(&def.colname).is_none() {
+                add_column(table, def);
+            }
+        }
+        // AtColumnDefault
+        (4, expr) => {
+            if let Some(column) = column_mut(table, &cmd.name) {
+                column.default_expr = expr.and_then(deparse_expr);
+            }
+        }
+        // AtDropNotNull
+        (6, _) => {
+            if let Some(column) = column_mut(table, &cmd.name) {
+                column.not_null = false;
+            }
+        }
+        // AtSetNotNull
+        (7, _) => {
+            if let Some(column) = column_mut(table, &cmd.name) {
+                column.not_null = true;
+            }
+        }
+        // AtDropColumn
+        (15, _) => {
+            table.columns.retain(|c| c.name != cmd.name);
+            table
+                .owned_sequences
+                .retain(|_, column| *column != cmd.name);
+        }
+        // AtAddConstraint
+        (19, Some(NodeEnum::Constraint(c))) => add_constraint(table, c, None),
+        // AtDropConstraint
+        (27, _) => {
+            table.constraints.remove(&cmd.name);
+            if table.clustered_index.as_ref() == Some(&cmd.name) {
+                table.clustered_index = None;
+            }
+        }
+        // AtAlterColumnType
+        (30, Some(NodeEnum::ColumnDef(def))) => {
+            if let (Some(column), Some(t)) = (column_mut(table, &cmd.name), &def.type_name) {
+                column.data_type = column_type(t);
+            }
+        }
+        // AtChangeOwner
+        (32, _) => table.owner = cmd.newowner.as_ref().and_then(role_name),
+        // AtClusterOn
+        (33, _) => {
+            if table.has_index(&cmd.name) {
+                table.clustered_index = Some(cmd.name.clone());
+            }
+        }
+        // AtDropCluster
+        (34, _) => table.clustered_index = None,
+        // AtSetAccessMethod
+        (38, _) => table.access_method = access_method(&cmd.name),
+        // AtSetRelOptions
+        (40, Some(NodeEnum::List(options))) => {
+            if let Some(fillfactor) = fillfactor(&options.items) {
+                table.fillfactor = Some(fillfactor);
+            }
+        }
+        // AtResetRelOptions
+        (41, Some(NodeEnum::List(options))) => {
+            let resets_fillfactor = options.items.iter().any(|option| {
+                matches!(option.node.as_ref(), Some(NodeEnum::DefElem(d)) if d.defname == "fillfactor")
+            });
+            if resets_fillfactor {
+                table.fillfactor = None;
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Returns the table access method `name` as modeled by [`Table`], i.e. `None` for the default
+fn access_method(name: &str) -> Option<String> {
+    match name {
+        "" | "heap" => None,
+        name => Some(name.to_string()),
+    }
+}
+
+/// Returns the storage parameter `fillfactor` of the `options` of a table, if they set it
+fn fillfactor(options: &[Node]) -> Option<u32> {
+    options
+        .iter()
+        .find_map(|option| match option.node.as_ref()? {
+            NodeEnum::DefElem(d) if d.defname == "fillfactor" => {
+                match d.arg.as_ref()?.node.as_ref()? {
+                    NodeEnum::Integer(i) => u32::try_from(i.ival).ok(),
+                    NodeEnum::String(s) => s.sval.parse().ok(),
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+}
+
+/// Adds the columns of `parent` to `table` like the clause `LIKE parent` with the `INCLUDING`
+/// `options` does
+///
+/// Not null constraints are always copied, defaults and check constraints only if they are
+/// included. Indexes are not copied, because Postgres chooses new names for them.
+fn add_like(table: &mut Table, parent: &Table, options: u32) {
+    for column in &parent.columns {
+        table.columns.push(Column {
+            default_expr: column
+                .default_expr
+                .clone()
+                .filter(|_| options & LIKE_DEFAULTS != 0),
+            ..column.clone()
+        });
+    }
+    if options & LIKE_CONSTRAINTS != 0 {
+        table.constraints.extend(
+            parent
+                .constraints
+                .iter()
+                .filter(|(_, definition)| definition.starts_with("CHECK"))
+                .map(|(name, definition)| (name.clone(), definition.clone())),
+        );
+    }
+}
+
+/// Adds the column defined by `def` to `table`, or applies the constraints of `def` to a column
+/// that `table` inherited
+fn add_column(table: &mut Table, def: &ColumnDef) {
+    let idx = match table.columns.iter().position(|c| c.name == def.colname) {
+        Some(idx) => idx,
+        None => {
+            table.columns.push(Column {
+                name: def.colname.clone(),
+                data_type: String::new(),
+                not_null: false,
+                default_expr: None,
+            });
+            table.columns.len() - 1
+        }
+    };
+    if let Some(t) = &def.type_name {
+        table.columns[idx].data_type = column_type(t);
+        let is_serial = t
+            .names
+            .last()
+            .and_then(string_value)
+            .is_some_and(|name| SERIAL_TYPES.contains(&name));
+        if is_serial {
+            add_owned_sequence(table, &def.colname);
+        }
+    }
+
+    for constraint in &column_constraints(def) {
+        match constraint.contype {
+            // ConstrNull
+            1 => table.columns[idx].not_null = false,
+            // ConstrNotnull
+            2 => table.columns[idx].not_null = true,
+            // ConstrDefault
+            3 => {
+                table.columns[idx].default_expr = constraint
+                    .raw_expr
+                    .as_ref()
+                    .and_then(|e| e.node.as_ref())
+                    .and_then(deparse_expr);
+            }
+            // ConstrIdentity
+            4 => add_owned_sequence(table, &def.colname),
+            // ConstrPrimary
+            7 => {
+                table.columns[idx].not_null = true;
+                add_constraint(table, constraint, Some(&def.colname));
+            }
+            _ => add_constraint(table, constraint, Some(&def.colname)),
+        }
+    }
+}
+
+/// Adds the sequence that Postgres creates for the `serial` or identity column `column`
+fn add_owned_sequence(table: &mut Table, column: &str) {
+    table
+        .owned_sequences
+        .insert(format!("{}_{}_seq", table.name, column), column.to_string());
+}
+
+/// Returns the constraints of the column definition `def` for changing them
+fn column_def_constraints(def: &mut ColumnDef) -> impl Iterator<Item = &mut Box<Constraint>> {
+    def.constraints
+        .iter_mut()
+        .filter_map(|constraint| match constraint.node.as_mut() {
+            Some(NodeEnum::Constraint(c)) => Some(c),
+            _ => None,
+        })
+}
+
+/// Returns the constraints of the column `def`. Attributes such as `DEFERRABLE`, which the parser
+/// returns as constraints of their own, are applied to the constraint before them.
+fn column_constraints(def: &ColumnDef) -> Vec<Constraint> {
+    let mut constraints = Vec::<Constraint>::new();
+    for constraint in &def.constraints {
+        let Some(NodeEnum::Constraint(constraint)) = constraint.node.as_ref() else {
+            continue;
+        };
+        let Some(last) = constraints.last_mut().filter(|_| constraint.contype >= 11) else {
+            constraints.push((**constraint).clone());
+            continue;
+        };
+        match constraint.contype {
+            // ConstrAttrDeferrable
+            11 => last.deferrable = true,
+            // ConstrAttrNotDeferrable
+            12 => last.deferrable = false,
+            // ConstrAttrDeferred
+            13 => {
+                last.deferrable = true;
+                last.initdeferred = true;
+            }
+            // ConstrAttrImmediate
+            _ => last.initdeferred = false,
+        }
+    }
+    constraints
+}
+
+/// Adds a table constraint to `table`. The constraint of a column, given as `column`, is turned
+/// into the equivalent table constraint.
+fn add_constraint(table: &mut Table, constraint: &Constraint, column: Option<&str>) {
+    // ConstrCheck through ConstrForeign
+    if !(6..=10).contains(&constraint.contype) {
+        return;
+    }
+    let mut constraint = constraint.clone();
+    if let Some(column) = column {
+        let keys = vec![Node {
+            node: Some(NodeEnum::String(pg_query::protobuf::String {
+                sval: column.to_string(),
+            })),
+        }];
+        match constraint.contype {
+            // ConstrPrimary, ConstrUnique
+            7 | 8 => constraint.keys = keys,
+            // ConstrForeign
+            10 => constraint.fk_attrs = keys,
+            _ => {}
+        }
+    }
+
+    let name = if constraint.conname.is_empty() {
+        default_constraint_name(&table.name, &constraint, column)
+    } else {
+        constraint.conname.clone()
+    };
+    // the name is not part of the definition
+    constraint.conname = String::new();
+    if let Some(definition) = deparse_constraint(constraint) {
+        table.constraints.insert(name, definition);
+    }
+}
+
+/// Returns the name that Postgres chooses for an unnamed constraint
+///
+/// The names of check and exclusion constraints only approximate it, because Postgres derives
+/// them from the columns used in their expressions.
+fn default_constraint_name(table: &str, constraint: &Constraint, column: Option<&str>) -> String {
+    let columns = |keys: &[Node]| {
+        keys.iter()
+            .filter_map(string_value)
+            .collect::<Vec<_>>()
+            .join("_")
+    };
+    match constraint.contype {
+        // ConstrPrimary
+        7 => format!("{}_pkey", table),
+        // ConstrUnique
+        8 => format!("{}_{}_key", table, columns(&constraint.keys)),
+        // ConstrExclusion
+        9 => format!("{}_excl", table),
+        // ConstrForeign
+        10 => format!("{}_{}_fkey", table, columns(&constraint.fk_attrs)),
+        _ => match column {
+            Some(column) => format!("{}_{}_check", table, column),
+            None => format!("{}_check", table),
+        },
+    }
+}
+
+/// Returns the name that Postgres chooses for an unnamed index
+fn default_index_name(table: &str, index: &IndexStmt) -> String {
+    let columns = index
+        .index_params
+        .iter()
+        .map(|p| match p.node.as_ref() {
+            Some(NodeEnum::IndexElem(e)) if !e.name.is_empty() => e.name.as_str(),
+            _ => "expr",
+        })
+        .collect::<Vec<_>>()
+        .join("_");
+    if index.primary {
+        format!("{}_pkey", table)
+    } else {
+        format!("{}_{}_idx", table, columns)
+    }
+}
+
+fn column_mut<'a>(table: &'a mut Table, name: &str) -> Option<&'a mut Column> {
+    table.columns.iter_mut().find(|c| c.name == name)
+}
+
+/// Returns the normalized type of a column, e.g. `varchar(20)` or `int4[]`
+pub(crate) fn column_type(t: &TypeName) -> String {
+    let mut name = type_name(t).unwrap_or_default();
+    let typmods = t
+        .typmods
+        .iter()
+        .filter_map(|m| match m.node.as_ref() {
+            Some(NodeEnum::AConst(c)) => match &c.val {
+                Some(Val::Ival(i)) => Some(i.ival.to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if !typmods.is_empty() {
+        name = format!("{}({})", name, typmods.join(","));
+    }
+    if !t.array_bounds.is_empty() {
+        name.push_str("[]");
+    }
+    name
+}
+
+/// Returns the name of the role of `OWNER TO`, or `None` for `CURRENT_USER` and the like, which
+/// depend on the role that runs the migration
+fn role_name(role: &RoleSpec) -> Option<String> {
+    // RolespecCstring
+    (role.roletype == 1).then(|| role.rolename.clone())
+}
+
+/// Returns the tables that `n` takes columns from, i.e. the parents of `INHERITS` or
+/// `PARTITION OF` and the tables of `LIKE` clauses
+fn parent_relations(n: &CreateStmt) -> impl Iterator<Item = &RangeVar> {
+    let inherited = n
+        .inh_relations
+        .iter()
+        .filter_map(|parent| match parent.node.as_ref() {
+            Some(NodeEnum::RangeVar(r)) => Some(r),
+            _ => None,
+        });
+    let like = n
+        .table_elts
+        .iter()
+        .filter_map(|element| match element.node.as_ref() {
+            Some(NodeEnum::TableLikeClause(like)) => like.relation.as_ref(),
+            _ => None,
+        });
+    inherited.chain(like)
+}
+
+pub(crate) fn alter_table_cmds(n: &AlterTableStmt) -> impl Iterator<Item = &AlterTableCmd> {
+    n.cmds.iter().filter_map(|c| match c.node.as_ref() {
+        Some(NodeEnum::AlterTableCmd(cmd)) => {
+            let cmd: &AlterTableCmd = cmd;
+            Some(cmd)
+        }
+        _ => None,
+    })
+}
+
+fn column_def(cmd: &AlterTableCmd) -> Option<&ColumnDef> {
+    match cmd.def.as_ref()?.node.as_ref()? {
+        NodeEnum::ColumnDef(def) => {
+            let def: &ColumnDef = def;
+            Some(def)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the names of the columns that `targets` assign to, with their location
+fn targets(targets: &[Node]) -> Vec<(&str, i32)> {
+    targets
+        .iter()
+        .filter_map(|t| match t.node.as_ref() {
+            Some(NodeEnum::ResTarget(t)) => Some((t.name.as_str(), t.location)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the SQL of `stmt` as printed by the deparser of pg_query
+fn deparse(stmt: NodeEnum) -> Option<String> {
+    let stmt = pg_query::protobuf::RawStmt {
+        stmt: Some(Node { node: Some(stmt) }.into()),
+        ..Default::default()
+    };
+    pg_query::deparse(&pg_query::protobuf::ParseResult {
+        stmts: vec![stmt],
+        ..Default::default()
+    })
+    .ok()
+}
+
+/// Returns the SQL of the expression `expr`
+fn deparse_expr(expr: &NodeEnum) -> Option<String> {
+    let target = ResTarget {
+        val: Some(
+            Node {
+                node: Some(expr.clone()),
+            }
+            .into(),
+        ),
+        ..Default::default()
+    };
+    let select = SelectStmt {
+        target_list: vec![Node {
+            node: Some(NodeEnum::ResTarget(target.into())),
+        }],
+        ..Default::default()
+    };
+    let sql = deparse(NodeEnum::SelectStmt(select.into()))?;
+    sql.strip_prefix("SELECT ").map(str::to_string)
+}
+
+/// Returns the SQL of a table constraint, e.g. `PRIMARY KEY (id)`
+fn deparse_constraint(constraint: Constraint) -> Option<String> {
+    let cmd = AlterTableCmd {
+        // AtAddConstraint
+        subtype: 19,
+        def: Some(
+            Node {
+                node: Some(NodeEnum::Constraint(constraint.into())),
+            }
+            .into(),
+        ),
+        ..Default::default()
+    };
+    let stmt = AlterTableStmt {
+        relation: Some(
+            RangeVar {
+                relname: "t".to_string(),
+                inh: true,
+                relpersistence: "p".to_string(),
+                ..Default::default()
+            }
+            .into(),
+        ),
+        cmds: vec![Node {
+            node: Some(NodeEnum::AlterTableCmd(cmd.into())),
+        }],
+        // ObjectTable
+        objtype: 42,
+        ..Default::default()
+    };
+    let sql = deparse(NodeEnum::AlterTableStmt(stmt.into()))?;
+    sql.split_once(" ADD ")
+        .map(|(_, definition)| definition.to_string())
+}
+
+/// Returns the SQL of the partition key of `PARTITION BY`, e.g. `RANGE (created_at)`
+fn deparse_partition_key(spec: &PartitionSpec) -> Option<String> {
+    let stmt = CreateStmt {
+        relation: Some(
+            RangeVar {
+                relname: "t".to_string(),
+                inh: true,
+                relpersistence: "p".to_string(),
+                ..Default::default()
+            }
+            .into(),
+        ),
+        partspec: Some(spec.clone().into()),
+        // OncommitNoop
+        oncommit: 1,
+        ..Default::default()
+    };
+    let sql = deparse(NodeEnum::CreateStmt(stmt))?;
+    sql.split_once(" PARTITION BY ")
+        .map(|(_, key)| key.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    fn replay(sql: &str) -> MigrationState {
+        let mut state = MigrationState::default();
+        state.replay(&parse_source(sql).stmts);
+        state
+    }
+
+    fn check(state: &mut MigrationState, sql: &str) -> Vec<(&'static str, String)> {
+        state
+            .check(&parse_source(sql).stmts)
+            .into_iter()
+            .map(|d| (d.rule, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_migration_version() {
+        assert_eq!(
+            migration_version("20240101120000_contact.sql"),
+            Some(20240101120000)
+        );
+        assert_eq!(migration_version("V3__contact.sql"), Some(3));
+        assert_eq!(migration_version("seed.sql"), None);
+    }
+
+    #[test]
+    fn test_replay() {
+        let state = replay(
+            "create table contact (id int primary key, name text not null, email text);
+            alter table contact add column org_id bigint, alter column email set not null;
+            alter table contact drop column name;
+            alter table contact rename column email to mail;
+            create index on contact (org_id);
+            create table app.orders (id int);
+            drop table app.orders;",
+        );
+
+        let contact = state.schemas["public"].table("contact").unwrap();
+        assert_eq!(
+            contact
+                .columns
+                .iter()
+                .map(|c| (c.name.as_str(), c.data_type.as_str(), c.not_null))
+                .collect::<Vec<_>>(),
+            vec![
+                ("id", "int4", true),
+                ("mail", "text", true),
+                ("org_id", "int8", false)
+            ]
+        );
+        assert!(contact.constraints.contains_key("contact_pkey"));
+        assert!(contact.indexes.contains_key("contact_org_id_idx"));
+        assert!(state.schemas["app"].tables.is_empty());
+    }
+
+    #[test]
+    fn test_replay_parents() {
+        let state = replay(
+            "create table base (id int not null default 0, check (id > 0));
+            create table audit (at timestamptz) inherits (base);
+            create table draft (like base, note text);
+            create table snapshot (like base including all);",
+        );
+
+        let columns = |name: &str| {
+            state.schemas["public"]
+                .table(name)
+                .unwrap()
+                .columns
+                .iter()
+                .map(|c| (c.name.clone(), c.not_null, c.default_expr.is_some()))
+                .collect::<Vec<_>>()
+        };
+        let id = |default| ("id".to_string(), true, default);
+        assert_eq!(
+            columns("audit"),
+            vec![id(true), ("at".to_string(), false, false)]
+        );
+        assert_eq!(
+            columns("draft"),
+            vec![id(false), ("note".to_string(), false, false)]
+        );
+        assert_eq!(columns("snapshot"), vec![id(true)]);
+        assert!(state.schemas["public"]
+            .table("draft")
+            .unwrap()
+            .constraints
+            .is_empty());
+        assert_eq!(
+            state.schemas["public"]
+                .table("snapshot")
+                .unwrap()
+                .constraints
+                .len(),
+            1
+        );
+
+        let mut state = state;
+        assert_eq!(
+            check(&mut state, "create table other (like missing);"),
+            vec![(
+                UNKNOWN_RELATION,
+                "relation missing does not exist".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_replay_partition_key() {
+        let state = replay(
+            "create table event (id int, created_at timestamptz) partition by range (created_at);
+            create table event_2024 partition of event
+                for values from ('2024-01-01') to ('2025-01-01');
+            create table metric (id int, name text) partition by hash (lower(name), id);",
+        );
+        let table = |name: &str| state.schemas["public"].table(name).unwrap();
+        assert_eq!(
+            table("event").partition_key.as_deref(),
+            Some("RANGE (created_at)")
+        );
+        assert_eq!(
+            table("event").partition_columns,
+            Some(vec![Some("created_at".to_string())])
+        );
+        assert_eq!(table("event_2024").partition_columns, None);
+        assert_eq!(
+            table("metric").partition_columns,
+            Some(vec![None, Some("id".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_replay_fillfactor() {
+        let state = replay(
+            "create table contact (id int) with (fillfactor = 80);
+            create table event (id int);
+            alter table event set (fillfactor = 70, autovacuum_enabled = false);
+            create table metric (id int) with (fillfactor = 90);
+            alter table metric reset (fillfactor);",
+        );
+        let fillfactor = |name: &str| state.schemas["public"].table(name).unwrap().fillfactor;
+        assert_eq!(fillfactor("contact"), Some(80));
+        assert_eq!(fillfactor("event"), Some(70));
+        assert_eq!(fillfactor("metric"), None);
+    }
+
+    #[test]
+    fn test_squash() {
+        let base = replay("create table contact (id int primary key, email text);");
+        let mut target = base.clone();
+        target.replay(
+            &parse_source(
+                "create table tmp (id int);
+                drop table tmp;
+                alter table contact drop column email, add column name varchar(20) not null;
+                create schema app;
+                create table app.orders (id int);
+                alter table contact cluster on contact_pkey;",
+            )
+            .stmts,
+        );
+
+        let sql = squash(&base, &target);
+        assert_eq!(
+            sql,
+            "ALTER TABLE public.contact DROP COLUMN email;
+
+CREATE SCHEMA app;
+CREATE TABLE app.orders (
+    id int4
+);
+ALTER TABLE public.contact ADD COLUMN name varchar(20) NOT NULL;
+
+ALTER TABLE public.contact CLUSTER ON contact_pkey;
+"
+        );
+
+        let mut squashed = base.clone();
+        squashed.replay(&parse_source(&sql).stmts);
+        assert_eq!(squashed.schemas, target.schemas);
+    }
+
+    #[test]
+    fn test_squash_fillfactor() {
+        let base = replay(
+            "create table contact (id int);
+            create table event (id int) with (fillfactor = 80);",
+        );
+        let mut target = base.clone();
+        target.replay(
+            &parse_source(
+                "alter table contact set (fillfactor = 90);
+                alter table event reset (fillfactor);",
+            )
+            .stmts,
+        );
+
+        let sql = squash(&base, &target);
+        assert_eq!(
+            sql,
+            "ALTER TABLE public.contact SET (fillfactor = 90);
+ALTER TABLE public.event RESET (fillfactor);
+"
+        );
+
+        let mut squashed = base.clone();
+        squashed.replay(&parse_source(&sql).stmts);
+        assert_eq!(squashed.schemas, target.schemas);
+    }
+
+    #[test]
+    fn test_is_squashable() {
+        let squashable = |sql: &str| is_squashable(&parse_source(sql).stmts[0].stmt);
+        assert!(squashable("alter table contact add column email text;"));
+        assert!(squashable("cluster contact using contact_pkey;"));
+        assert!(!squashable(
+            "alter table contact enable row level security;"
+        ));
+        assert!(!squashable("create view active as select 1;"));
+    }
+
+    #[test]
+    fn test_check() {
+        let mut state = replay("create table contact (id int); create view active as select 1;");
+        assert_eq!(
+            check(
+                &mut state,
+                "alter table contact add column id int, drop column email;
+                insert into orders (id) values (1);
+                update contact set name = 'x';
+                select * from active;
+                alter table app.orders add column id int;"
+            ),
+            vec![
+                (
+                    DUPLICATE_OBJECT,
+                    "column id of relation contact already exists".to_string()
+                ),
+                (
+                    UNKNOWN_COLUMN,
+                    "column email of relation contact does not exist".to_string()
+                ),
+                (
+                    UNKNOWN_RELATION,
+                    "relation orders does not exist".to_string()
+                ),
+                (
+                    UNKNOWN_COLUMN,
+                    "column name of relation contact does not exist".to_string()
+                ),
+            ]
+        );
+
+        // statements see the changes of the statements before them
+        assert!(check(
+            &mut state,
+            "alter table contact add column email text; alter table contact drop column email;"
+        )
+        .is_empty());
+
+        // and commands the changes of the commands before them
+        assert_eq!(
+            check(
+                &mut state,
+                "alter table contact add column a int, alter column a set not null,
+                    drop column id, alter column id type text;"
+            ),
+            vec![(
+                UNKNOWN_COLUMN,
+                "column id of relation contact does not exist".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_check_cancellable() {
+        let mut state = replay("create table contact (id int);");
+        let stmts = parse_source("alter table contact add column email text;").stmts;
+        let cancellation = Cancellation::new();
+        cancellation.cancel();
+        assert_eq!(
+            state.check_cancellable(&stmts, &cancellation),
+            Err(Cancelled)
+        );
+        // the statement has not been applied
+        assert!(state.check(&stmts).is_empty());
+    }
+
+    #[test]
+    fn test_check_truncate() {
+        let mut state = replay(
+            "create table org (id int primary key);
+            create table contact (id int primary key, org int references org (id));
+            create table note (contact int, foreign key (contact) references contact (id));
+            create table tag (id int);",
+        );
+        assert_eq!(
+            check(&mut state, "truncate org;"),
+            vec![(
+                TRUNCATE_REFERENCED,
+                "cannot truncate a table referenced in a foreign key constraint, truncate contact, note as well or use CASCADE".to_string()
+            )]
+        );
+        assert_eq!(
+            check(&mut state, "truncate org cascade;"),
+            vec![(
+                TRUNCATE_CASCADE,
+                "TRUNCATE ... CASCADE also truncates contact, note".to_string()
+            )]
+        );
+        assert!(check(&mut state, "truncate org, contact, note, tag;").is_empty());
+        assert_eq!(
+            check(&mut state, "truncate orders;"),
+            vec![(
+                UNKNOWN_RELATION,
+                "relation orders does not exist".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_replay_cluster() {
+        let mut state = replay(
+            "create table visit (id int primary key, started timestamptz) using columnar;
+            create index on visit (started);
+            cluster visit using visit_started_idx;",
+        );
+        let visit =
+            |state: &MigrationState| state.schemas["public"].table("visit").unwrap().clone();
+        assert_eq!(visit(&state).access_method.as_deref(), Some("columnar"));
+        assert_eq!(
+            visit(&state).clustered_index.as_deref(),
+            Some("visit_started_idx")
+        );
+        assert_eq!(
+            squash(&MigrationState::default(), &state).lines().nth(3),
+            Some(") USING columnar;")
+        );
+
+        state.replay(
+            &parse_source(
+                "alter table visit cluster on visit_pkey, set access method heap;
+                drop index visit_started_idx;",
+            )
+            .stmts,
+        );
+        assert_eq!(visit(&state).access_method, None);
+        assert_eq!(visit(&state).clustered_index.as_deref(), Some("visit_pkey"));
+
+        state.replay(&parse_source("alter table visit set without cluster;").stmts);
+        assert_eq!(visit(&state).clustered_index, None);
+    }
+
+    #[test]
+    fn test_check_cluster() {
+        let mut state = replay("create table visit (id int primary key);");
+        assert_eq!(
+            check(
+                &mut state,
+                "cluster visit;
+                cluster visit using visit_id_idx;
+                alter table visit cluster on visit_id_idx;
+                cluster orders using orders_pkey;"
+            ),
+            vec![
+                (
+                    NO_CLUSTERED_INDEX,
+                    "there is no previously clustered index for table visit, add USING".to_string()
+                ),
+                (
+                    UNKNOWN_RELATION,
+                    "index visit_id_idx of relation visit does not exist".to_string()
+                ),
+                (
+                    UNKNOWN_RELATION,
+                    "index visit_id_idx of relation visit does not exist".to_string()
+                ),
+                (
+                    UNKNOWN_RELATION,
+                    "relation orders does not exist".to_string()
+                ),
+            ]
+        );
+        assert!(check(&mut state, "cluster visit using visit_pkey; cluster visit;").is_empty());
+    }
+
+    #[test]
+    fn test_replay_ownership() {
+        let mut state = replay(
+            "create table orders (id serial, note_id int, total numeric);
+            alter table orders owner to billing;
+            create sequence note_seq owned by orders.note_id;
+            create sequence invoice_seq;",
+        );
+        let orders =
+            |state: &MigrationState| state.schemas["public"].table("orders").unwrap().clone();
+        assert_eq!(orders(&state).owner.as_deref(), Some("billing"));
+        assert_eq!(
+            orders(&state).owned_sequences,
+            BTreeMap::from([
+                ("note_seq".to_string(), "note_id".to_string()),
+                ("orders_id_seq".to_string(), "id".to_string()),
+            ])
+        );
+        assert!(squash(&MigrationState::default(), &state)
+            .contains("ALTER TABLE public.orders OWNER TO billing;"));
+
+        state.replay(
+            &parse_source(
+                "alter sequence invoice_seq owned by orders.total;
+                alter sequence note_seq owned by none;
+                alter table orders rename column total to amount;
+                alter table orders drop column id, owner to current_user;",
+            )
+            .stmts,
+        );
+        assert_eq!(orders(&state).owner, None);
+        assert_eq!(
+            orders(&state).owned_sequences,
+            BTreeMap::from([("invoice_seq".to_string(), "amount".to_string())])
+        );
+        assert!(state.other_relations.contains("public.note_seq"));
+
+        state.replay(&parse_source("drop table orders;").stmts);
+        assert!(matches!(
+            state.relation_named("public", "invoice_seq"),
+            Relation::Missing
+        ));
+        assert!(matches!(
+            state.relation_named("public", "note_seq"),
+            Relation::Other
+        ));
+    }
+
+    #[test]
+    fn test_search_path() {
+        let state = replay(
+            "create schema app;
+            set search_path = app, public;
+            create table orders (id int);
+            create index orders_id on orders (id);
+            alter table orders add column total numeric;
+            reset search_path;
+            create table notes (id int);
+            set search_path to app;
+            drop index orders_id;",
+        );
+        let orders = state.schemas["app"].table("orders").unwrap();
+        assert_eq!(orders.columns.len(), 2);
+        assert!(!orders.has_index("orders_id"));
+        assert!(state.schemas["public"].table("orders").is_none());
+        assert!(state.schemas["public"].table("notes").is_some());
+    }
+
+    #[test]
+    fn test_squash_search_path() {
+        let base = MigrationState::default();
+        let target = replay(
+            "create schema app;
+            set search_path = app;
+            create table customer (id int primary key);
+            create table orders (id int, customer_id int references customer);",
+        );
+        let sql = squash(&base, &target);
+        assert!(sql.contains("CREATE TABLE app.orders"));
+        assert!(sql.contains("REFERENCES app.customer"));
+
+        let mut squashed = base.clone();
+        squashed.replay(&parse_source(&sql).stmts);
+        assert_eq!(squashed.schemas, target.schemas);
+    }
+
+    #[test]
+    fn test_check_ownership() {
+        let mut state = replay(
+            "create schema app;
+            create table orders (id int generated always as identity, total numeric);",
+        );
+        assert_eq!(
+            check(
+                &mut state,
+                "create sequence total_seq owned by orders.amount;
+                create sequence app.total_seq owned by orders.total;
+                alter sequence total_seq owned by invoices.total;
+                alter table orders drop column id;
+                drop sequence orders_id_seq;"
+            ),
+            vec![
+                (
+                    UNKNOWN_COLUMN,
+                    "column amount of relation orders does not exist".to_string()
+                ),
+                (
+                    UNKNOWN_RELATION,
+                    "sequence total_seq must be in the same schema as table orders".to_string()
+                ),
+                (
+                    UNKNOWN_RELATION,
+                    "relation invoices does not exist".to_string()
+                ),
+                (
+                    UNKNOWN_RELATION,
+                    "relation orders_id_seq does not exist".to_string()
+                ),
+            ]
+        );
+        assert!(check(
+            &mut state,
+            "alter sequence total_seq owned by none; drop sequence total_seq, app.total_seq;"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_replay_deferrable() {
+        let state = replay(
+            "create table org (id int primary key);
+            create table contact (
+                org int references org (id) deferrable initially deferred,
+                parent int references org (id) deferrable,
+                owner int references org (id),
+                constraint parent_fkey foreign key (parent) references org (id) initially deferred
+            );",
+        );
+        let table = state.schemas["public"].table("contact").unwrap();
+        assert_eq!(
+            table.constraint_deferral("contact_org_fkey"),
+            Some(Deferral::InitiallyDeferred)
+        );
+        assert_eq!(
+            table.constraint_deferral("contact_parent_fkey"),
+            Some(Deferral::InitiallyImmediate)
+        );
+        assert_eq!(
+            table.constraint_deferral("contact_owner_fkey"),
+            Some(Deferral::NotDeferrable)
+        );
+        assert_eq!(
+            table.constraint_deferral("parent_fkey"),
+            Some(Deferral::InitiallyDeferred)
+        );
+    }
+
+    #[test]
+    fn test_check_set_constraints() {
+        let mut state = replay(
+            "create table org (id int primary key);
+            create table contact (
+                org int references org (id) deferrable,
+                owner int references org (id)
+            );",
+        );
+        assert_eq!(
+            check(
+                &mut state,
+                "set constraints contact_org_fkey, contact_owner_fkey, contact_fkey deferred;"
+            ),
+            vec![
+                (
+                    NOT_DEFERRABLE,
+                    "constraint contact_owner_fkey is not deferrable".to_string()
+                ),
+                (
+                    UNKNOWN_CONSTRAINT,
+                    "constraint contact_fkey does not exist".to_string()
+                ),
+            ]
+        );
+        assert!(check(&mut state, "set constraints all immediate;").is_empty());
+    }
+
+    #[test]
+    fn test_check_insert_order() {
+        let mut state = replay(
+            "create table org (id int primary key);
+            create table contact (
+                org int references org (id) deferrable,
+                owner int references org (id)
+            );",
+        );
+        assert_eq!(
+            check(
+                &mut state,
+                "insert into contact values (1, 1); insert into org values (1);"
+            ),
+            vec![
+                (
+                    UNDEFERRED_FOREIGN_KEY,
+                    "foreign key contact_org_fkey requires rows of org that are only inserted later, run both within BEGIN and COMMIT after SET CONSTRAINTS contact_org_fkey DEFERRED or insert into org first".to_string()
+                ),
+                (
+                    UNDEFERRED_FOREIGN_KEY,
+                    "foreign key contact_owner_fkey requires rows of org that are only inserted later, make it DEFERRABLE and defer it with SET CONSTRAINTS or insert into org first".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            check(
+                &mut state,
+                "begin;
+                set constraints all deferred;
+                insert into contact values (1, 1);
+                insert into org values (1);
+                commit;"
+            ),
+            vec![(
+                UNDEFERRED_FOREIGN_KEY,
+                "foreign key contact_owner_fkey requires rows of org that are only inserted later, make it DEFERRABLE and defer it with SET CONSTRAINTS or insert into org first".to_string()
+            )]
+        );
+        assert!(check(
+            &mut state,
+            "insert into org values (1); insert into contact values (1, 1);"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_check_insert_order_outside_transaction() {
+        let mut state = replay(
+            "create table org (id int primary key);
+            create table contact (
+                org int references org (id) deferrable,
+                parent int references org (id) deferrable initially deferred
+            );",
+        );
+        // each statement is a transaction of its own, so SET CONSTRAINTS has no effect
+        assert_eq!(
+            check(
+                &mut state,
+                "set constraints all deferred;
+                insert into contact values (1, 1);
+                insert into org values (1);"
+            ),
+            vec![
+                (
+                    UNDEFERRED_FOREIGN_KEY,
+                    "foreign key contact_org_fkey requires rows of org that are only inserted later, run both within BEGIN and COMMIT after SET CONSTRAINTS contact_org_fkey DEFERRED or insert into org first".to_string()
+                ),
+                (
+                    UNDEFERRED_FOREIGN_KEY,
+                    "foreign key contact_parent_fkey requires rows of org that are only inserted later, run both within BEGIN and COMMIT or insert into org first".to_string()
+                ),
+            ]
+        );
+        assert!(check(
+            &mut state,
+            "start transaction;
+            set constraints contact_org_fkey deferred;
+            insert into contact values (1, 1);
+            insert into org values (1);
+            commit;"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_replay_alter_table_cmds() {
+        let state = replay(
+            "create table contact (a int, b int, c int);
+            alter table contact add column d int, drop column b, alter column c type text;",
+        );
+        let table = state.schemas["public"].table("contact").unwrap();
+        assert_eq!(
+            table
+                .columns
+                .iter()
+                .map(|c| (c.name.as_str(), c.data_type.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("a", "int4"), ("c", "text"), ("d", "int4")]
+        );
+    }
+
+    #[test]
+    fn test_check_insert_arity() {
+        let mut state = replay("create table contact (id int, name text);");
+        assert_eq!(
+            check(
+                &mut state,
+                "insert into contact values (1), (2, 'b');
+                insert into contact values (1, 'a', 'x');
+                insert into contact (id, name) values (1);"
+            )
+            .into_iter()
+            .map(|(rule, message)| (rule, message.split(':').next().unwrap().to_string()))
+            .collect::<Vec<_>>(),
+            vec![
+                (
+                    INSERT_ARITY,
+                    "INSERT has more expressions than target columns".to_string()
+                ),
+                (
+                    INSERT_ARITY,
+                    "INSERT has more target columns than expressions".to_string()
+                ),
+            ]
+        );
+    }
+}
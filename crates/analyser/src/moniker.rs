@@ -0,0 +1,376 @@
+//! Stable identities of tables and columns.
+//!
+//! An identity is the qualified name of the object, e.g. `public.contact.name` for the column
+//! `name` of the table `contact`. Unqualified tables are assumed to be in the `public` schema. Tools
+//! that index several repositories use the identity to link the definition of a table in a
+//! migration to its usages in the queries of an application.
+//!
+//! Tables and columns are resolved by their location within the statement, so that the same name
+//! in different schemas or qualified by different aliases gets the identity of the object that it
+//! names at that position. The schema of a qualified table name is part of the identity of the
+//! table, but is not a symbol itself. Columns are resolved through the relations of the statement
+//! they are used in. A column that is not qualified by a table or alias only has an identity if the
+//! statement uses a single relation.
+
+use cstree::syntax::{ResolvedNode, ResolvedToken};
+use cstree::text::{TextRange, TextSize};
+use parser::{RawStmt, SyntaxKind};
+use pg_query::protobuf::RangeVar;
+use pg_query::NodeEnum;
+
+use crate::rename::normalize_identifier;
+use crate::utils::{descendants, string_value};
+
+/// The schema of relations that are not qualified
+pub const DEFAULT_SCHEMA: &str = "public";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Table,
+    Column,
+}
+
+/// A table or column referenced or defined in the source text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    /// The qualified name, e.g. `public.contact.name`
+    pub identifier: String,
+    pub kind: SymbolKind,
+    /// True if the symbol is defined by a `CREATE TABLE` or `ALTER TABLE .. ADD COLUMN` statement
+    /// at this position
+    pub is_definition: bool,
+}
+
+/// Returns the symbol of the identifier at `offset`, if it names a table or column
+pub fn symbol_at(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmts: &[RawStmt],
+    offset: TextSize,
+) -> Option<Symbol> {
+    let token = identifiers(cst).find(|token| token.text_range().contains_inclusive(offset))?;
+    let stmt = stmts
+        .iter()
+        .find(|stmt| stmt.range.contains_inclusive(token.text_range().start()))?;
+    token_symbol(token, stmt, &StmtNodes::of(stmt))
+}
+
+/// Returns the symbols of all identifiers that name a table or column, together with their range
+pub fn symbols(cst: &ResolvedNode<SyntaxKind>, stmts: &[RawStmt]) -> Vec<(TextRange, Symbol)> {
+    let mut symbols = Vec::new();
+    // the statements and the identifiers are both in the order of the source text, so the
+    // statement of an identifier is found by moving on from the statement of the previous one
+    let mut stmt_idx = 0;
+    let mut cached = None::<(usize, StmtNodes)>;
+    for token in identifiers(cst) {
+        let start = token.text_range().start();
+        while stmts
+            .get(stmt_idx)
+            .is_some_and(|stmt| stmt.range.end() < start)
+        {
+            stmt_idx += 1;
+        }
+        let Some(stmt) = stmts
+            .get(stmt_idx)
+            .filter(|stmt| stmt.range.contains_inclusive(start))
+        else {
+            continue;
+        };
+        if cached.as_ref().map(|(idx, _)| *idx) != Some(stmt_idx) {
+            cached = Some((stmt_idx, StmtNodes::of(stmt)));
+        }
+        let Some((_, nodes)) = &cached else {
+            continue;
+        };
+        symbols.extend(token_symbol(token, stmt, nodes).map(|symbol| (token.text_range(), symbol)));
+    }
+    symbols
+}
+
+/// The nodes of a statement that symbols are resolved with, collected once for all of its
+/// identifiers
+struct StmtNodes {
+    relations: Vec<RangeVar>,
+    /// The location and names of every column reference
+    column_refs: Vec<(i32, Vec<String>)>,
+}
+
+impl StmtNodes {
+    fn of(stmt: &RawStmt) -> StmtNodes {
+        let column_refs = descendants(&stmt.stmt)
+            .into_iter()
+            .filter_map(|n| match n {
+                NodeEnum::ColumnRef(c) => Some(c),
+                _ => None,
+            })
+            .map(|c| {
+                let fields = c
+                    .fields
+                    .iter()
+                    .filter_map(string_value)
+                    .map(str::to_string)
+                    .collect();
+                (c.location, fields)
+            })
+            .collect();
+        StmtNodes {
+            relations: relations(&stmt.stmt),
+            column_refs,
+        }
+    }
+}
+
+fn identifiers(cst: &ResolvedNode<SyntaxKind>) -> impl Iterator<Item = &ResolvedToken<SyntaxKind>> {
+    cst.descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| token.kind() == SyntaxKind::Ident)
+}
+
+fn token_symbol(
+    token: &ResolvedToken<SyntaxKind>,
+    stmt: &RawStmt,
+    nodes: &StmtNodes,
+) -> Option<Symbol> {
+    let offset = token.text_range().start();
+    let name = normalize_identifier(token.text());
+    let context = token.parent().ancestors().map(|n| n.kind()).find(|kind| {
+        matches!(
+            kind,
+            SyntaxKind::RangeVar | SyntaxKind::ColumnDef | SyntaxKind::ColumnRef
+        )
+    })?;
+    // the offset of the token relative to the statement, which is what pg_query locations are;
+    // the node of the token is the last one of its kind that starts at or before it
+    let location = i32::try_from(u32::from(offset - stmt.range.start())).ok()?;
+    let relations = &nodes.relations;
+
+    match context {
+        SyntaxKind::RangeVar => {
+            // the schema or database of a qualified name
+            if is_qualifier(token) {
+                return None;
+            }
+            let relation = relations
+                .iter()
+                .filter(|r| r.relname == name && r.location <= location)
+                .max_by_key(|r| r.location)?;
+            Some(Symbol {
+                identifier: qualified_name(relation),
+                kind: SymbolKind::Table,
+                is_definition: matches!(
+                    &stmt.stmt,
+                    NodeEnum::CreateStmt(n) if n.relation.as_ref() == Some(relation)
+                ),
+            })
+        }
+        SyntaxKind::ColumnDef => {
+            let relation = match &stmt.stmt {
+                NodeEnum::CreateStmt(n) => n.relation.as_ref()?,
+                NodeEnum::AlterTableStmt(n) => n.relation.as_ref()?,
+                _ => return None,
+            };
+            Some(Symbol {
+                identifier: format!("{}.{}", qualified_name(relation), name),
+                kind: SymbolKind::Column,
+                is_definition: true,
+            })
+        }
+        _ => {
+            let (_, qualifier) = nodes
+                .column_refs
+                .iter()
+                .filter_map(|(column_location, fields)| {
+                    let (column, qualifier) = fields.split_last()?;
+                    (*column == name && *column_location <= location)
+                        .then_some((*column_location, qualifier))
+                })
+                .max_by_key(|(column_location, _)| *column_location)?;
+            let relation = match qualifier {
+                [] => match relations.as_slice() {
+                    [relation] => relation,
+                    _ => return None,
+                },
+                [schema, table] => relations.iter().find(|r| {
+                    r.alias.is_none() && qualified_name(r) == format!("{}.{}", schema, table)
+                })?,
+                [.., qualifier] => relations.iter().find(|r| match &r.alias {
+                    Some(alias) => alias.aliasname == *qualifier,
+                    None => r.relname == *qualifier,
+                })?,
+            };
+            Some(Symbol {
+                identifier: format!("{}.{}", qualified_name(relation), name),
+                kind: SymbolKind::Column,
+                is_definition: false,
+            })
+        }
+    }
+}
+
+/// Returns true if `token` is followed by a `.` within its relation, i.e. it is the schema or
+/// database of a qualified name
+fn is_qualifier(token: &ResolvedToken<SyntaxKind>) -> bool {
+    let Some(relation) = token
+        .parent()
+        .ancestors()
+        .find(|node| node.kind() == SyntaxKind::RangeVar)
+    else {
+        return false;
+    };
+    let mut tokens = relation
+        .descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|t| !t.kind().is_trivia())
+        .skip_while(|t| t.text_range() != token.text_range());
+    tokens.next();
+    tokens
+        .next()
+        .is_some_and(|t| t.kind() == SyntaxKind::Ascii46)
+}
+
+/// Returns the qualified name of `relation`
+pub fn qualified_name(relation: &RangeVar) -> String {
+    let schema = if relation.schemaname.is_empty() {
+        DEFAULT_SCHEMA
+    } else {
+        &relation.schemaname
+    };
+    format!("{}.{}", schema, relation.relname)
+}
+
+/// Returns the relations used within `stmt`
+pub(crate) fn relations(stmt: &NodeEnum) -> Vec<RangeVar> {
+    descendants(stmt)
+        .into_iter()
+        .filter_map(|n| match n {
+            NodeEnum::RangeVar(r) => Some(r),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    fn symbol(input: &str, at: &str) -> Option<Symbol> {
+        let parse = parse_source(input);
+        let offset = TextSize::try_from(input.find(at).unwrap()).unwrap();
+        symbol_at(&parse.cst, &parse.stmts, offset)
+    }
+
+    #[test]
+    fn test_definitions() {
+        let input = "create table app.contact (id int, name text);";
+        assert_eq!(
+            symbol(input, "contact"),
+            Some(Symbol {
+                identifier: "app.contact".to_string(),
+                kind: SymbolKind::Table,
+                is_definition: true
+            })
+        );
+        assert_eq!(
+            symbol(input, "name"),
+            Some(Symbol {
+                identifier: "app.contact.name".to_string(),
+                kind: SymbolKind::Column,
+                is_definition: true
+            })
+        );
+        assert_eq!(
+            symbol("alter table contact add column email text;", "email"),
+            Some(Symbol {
+                identifier: "public.contact.email".to_string(),
+                kind: SymbolKind::Column,
+                is_definition: true
+            })
+        );
+    }
+
+    #[test]
+    fn test_parents() {
+        for input in [
+            "create table audit (at timestamptz) inherits (contact);",
+            "create table draft (like contact including defaults);",
+        ] {
+            assert_eq!(
+                symbol(input, "contact"),
+                Some(Symbol {
+                    identifier: "public.contact".to_string(),
+                    kind: SymbolKind::Table,
+                    is_definition: false
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_references() {
+        assert_eq!(
+            symbol("select name from contact;", "contact").map(|s| s.identifier),
+            Some("public.contact".to_string())
+        );
+        assert_eq!(
+            symbol("select name from contact;", "name"),
+            Some(Symbol {
+                identifier: "public.contact.name".to_string(),
+                kind: SymbolKind::Column,
+                is_definition: false
+            })
+        );
+        assert_eq!(
+            symbol(
+                "select c.name from contact c join orders o on o.contact = c.id;",
+                "name"
+            )
+            .map(|s| s.identifier),
+            Some("public.contact.name".to_string())
+        );
+        // ambiguous without a qualifier
+        assert_eq!(symbol("select name from contact, orders;", "name"), None);
+    }
+
+    #[test]
+    fn test_resolved_by_location() {
+        let input = "select c.name, a.name from app.contact a join contact c on c.id = a.id;";
+        let parse = parse_source(input);
+        assert_eq!(
+            symbols(&parse.cst, &parse.stmts)
+                .into_iter()
+                .map(|(range, symbol)| (&input[range], symbol.identifier))
+                .collect::<Vec<_>>(),
+            vec![
+                ("name", "public.contact.name".to_string()),
+                ("name", "app.contact.name".to_string()),
+                ("contact", "app.contact".to_string()),
+                ("contact", "public.contact".to_string()),
+                ("id", "public.contact.id".to_string()),
+                ("id", "app.contact.id".to_string()),
+            ]
+        );
+        assert_eq!(
+            symbol("select app.contact.name from app.contact;", "name").map(|s| s.identifier),
+            Some("app.contact.name".to_string())
+        );
+    }
+
+    #[test]
+    fn test_symbols() {
+        let input = "create table contact (id int);\nselect id from contact;";
+        let parse = parse_source(input);
+        assert_eq!(
+            symbols(&parse.cst, &parse.stmts)
+                .into_iter()
+                .map(|(range, symbol)| (&input[range], symbol.identifier, symbol.is_definition))
+                .collect::<Vec<_>>(),
+            vec![
+                ("contact", "public.contact".to_string(), true),
+                ("id", "public.contact.id".to_string(), true),
+                ("id", "public.contact.id".to_string(), false),
+                ("contact", "public.contact".to_string(), false),
+            ]
+        );
+    }
+}
@@ -0,0 +1,431 @@
+//! Object names that functions take as strings, e.g. `nextval('orders_id_seq')`.
+//!
+//! The first argument of these functions is cast to `regclass`, which parses it like a name in
+//! the source text: a qualified name is split at its dots, and only parts that are not quoted are
+//! folded to lowercase. Unqualified names are looked up in the schemas of the `search_path` that
+//! is in effect at the call. Names of relations and sequences can be completed within the string,
+//! and relations that neither the workspace nor the database defines are reported, like the
+//! objects of [`comments`](crate::comments). Only the database tells which relations exist, so
+//! nothing is reported without one. Sequences are never reported, since those that are not owned
+//! by a column are not loaded from the database.
+
+use cstree::syntax::{ResolvedNode, ResolvedToken};
+use cstree::text::{TextRange, TextSize};
+use parser::make::quote_ident;
+use parser::{RawStmt, SyntaxKind};
+
+use crate::comments::Catalog;
+use crate::completion::{Completion, CompletionKind};
+use crate::definitions::{Definition, ObjectKind};
+use crate::restore::SearchPath;
+use crate::{LintDiagnostic, Severity};
+
+const UNKNOWN_OBJECT_NAME: &str = "unknown-object-name";
+
+/// Functions whose first argument is the name of a relation
+const RELATION_FUNCTIONS: &[&str] = &[
+    "to_regclass",
+    "pg_total_relation_size",
+    "pg_relation_size",
+    "pg_table_size",
+    "pg_indexes_size",
+    "pg_get_serial_sequence",
+];
+
+/// Functions whose first argument is the name of a sequence
+const SEQUENCE_FUNCTIONS: &[&str] = &["nextval", "currval", "setval"];
+
+/// Schemas of the system catalogs, of which `pg_catalog` is implicitly searched first
+const SYSTEM_SCHEMAS: &[&str] = &["pg_catalog", "information_schema"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameKind {
+    /// A table or view
+    Relation,
+    Sequence,
+}
+
+impl NameKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            NameKind::Relation => "relation",
+            NameKind::Sequence => "sequence",
+        }
+    }
+
+    fn of_function(name: &str) -> Option<NameKind> {
+        if RELATION_FUNCTIONS.contains(&name) {
+            Some(NameKind::Relation)
+        } else if SEQUENCE_FUNCTIONS.contains(&name) {
+            Some(NameKind::Sequence)
+        } else {
+            None
+        }
+    }
+}
+
+/// A string that a function takes as the name of an object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameArgument {
+    pub kind: NameKind,
+    /// The parts of the name, e.g. `["app", "orders_id_seq"]`, or `None` if it is not a valid name
+    pub names: Option<Vec<String>>,
+    /// The range of the contents of the string, without its quotes
+    pub range: TextRange,
+    /// The schemas that an unqualified name is looked up in, in order
+    pub search_path: Vec<String>,
+}
+
+impl NameArgument {
+    /// Returns the qualified name, e.g. `public.orders_id_seq`, with an unqualified name in the
+    /// first schema of the search path
+    pub fn identifier(&self) -> Option<String> {
+        self.identifiers().into_iter().next()
+    }
+
+    /// Returns the qualified names that the name may refer to, one per schema of the search path
+    /// if it is not qualified
+    pub fn identifiers(&self) -> Vec<String> {
+        match self.names.as_deref() {
+            Some([name]) => self
+                .search_path
+                .iter()
+                .map(|schema| format!("{}.{}", schema, name))
+                .collect(),
+            Some([schema, name]) => vec![format!("{}.{}", schema, name)],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns true if the name refers to a system catalog, such as `pg_class`
+    fn is_system(&self) -> bool {
+        match self.names.as_deref() {
+            Some([name]) => name.starts_with("pg_"),
+            Some([schema, _]) => SYSTEM_SCHEMAS.contains(&schema.as_str()),
+            _ => false,
+        }
+    }
+
+    /// Returns true if `definition` defines the object
+    pub fn is_defined_by(&self, definition: &Definition) -> bool {
+        let kinds: &[ObjectKind] = match self.kind {
+            NameKind::Relation => &[ObjectKind::Table, ObjectKind::View],
+            NameKind::Sequence => &[ObjectKind::Sequence],
+        };
+        kinds.contains(&definition.kind) && self.identifiers().contains(&definition.identifier)
+    }
+}
+
+/// Returns the strings that are passed as object names to the functions of the source text, whose
+/// statements are `stmts`
+pub fn name_arguments(cst: &ResolvedNode<SyntaxKind>, stmts: &[RawStmt]) -> Vec<NameArgument> {
+    let tokens = cst
+        .descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| !token.kind().is_trivia())
+        .collect::<Vec<_>>();
+    let mut search_path = SearchPath::default();
+    // the statements before the current argument, whose changes of the search path are applied
+    let mut applied = 0;
+    let mut arguments = Vec::new();
+    for window in tokens.windows(3) {
+        let [function, open, string] = window else {
+            continue;
+        };
+        if function.kind() != SyntaxKind::Ident
+            || open.kind() != SyntaxKind::Ascii40
+            || string.kind() != SyntaxKind::Sconst
+        {
+            continue;
+        }
+        let Some(kind) = NameKind::of_function(&function.text().to_lowercase()) else {
+            continue;
+        };
+        let Some((contents, range)) = string_contents(string) else {
+            continue;
+        };
+        while let Some(stmt) = stmts
+            .get(applied)
+            .filter(|stmt| stmt.range.end() <= range.start())
+        {
+            search_path.update(&stmt.stmt);
+            applied += 1;
+        }
+        arguments.push(NameArgument {
+            kind,
+            names: parse_name(contents),
+            range,
+            search_path: search_path.schemas().to_vec(),
+        });
+    }
+    arguments
+}
+
+/// Returns the contents of a plain string literal, which may not be closed yet, and their range
+fn string_contents(token: &ResolvedToken<SyntaxKind>) -> Option<(&str, TextRange)> {
+    let rest = token.text().strip_prefix('\'')?;
+    let contents = rest.strip_suffix('\'').unwrap_or(rest);
+    let start = token.text_range().start() + TextSize::from(1);
+    let range = TextRange::at(start, TextSize::try_from(contents.len()).ok()?);
+    Some((contents, range))
+}
+
+/// Splits `text` into the parts of a qualified name, or returns `None` if it is not one
+fn parse_name(text: &str) -> Option<Vec<String>> {
+    let mut names = Vec::new();
+    let mut chars = text.trim().chars().peekable();
+    loop {
+        let mut name = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next()? {
+                    '"' if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        name.push('"');
+                    }
+                    '"' => break,
+                    c => name.push(c),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| *c != '.') {
+                name.extend(c.to_lowercase());
+            }
+        }
+        if name.is_empty() {
+            return None;
+        }
+        names.push(name);
+        match chars.next() {
+            Some('.') => continue,
+            Some(_) => return None,
+            None => break,
+        }
+    }
+    (names.len() <= 2).then_some(names)
+}
+
+/// Returns the `objects` of the kind that the string at `offset` names, if it is the name argument
+/// of a function. Objects are given by their kind and qualified name, e.g. `public.contact`, and
+/// inserted without their schema if the search path finds them without it.
+pub fn name_argument_completions(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmts: &[RawStmt],
+    offset: TextSize,
+    objects: &[(NameKind, String)],
+) -> Vec<Completion> {
+    let Some(argument) = name_arguments(cst, stmts)
+        .into_iter()
+        .find(|argument| argument.range.contains_inclusive(offset))
+    else {
+        return Vec::new();
+    };
+    let objects = objects
+        .iter()
+        .filter(|(kind, _)| *kind == argument.kind)
+        .filter_map(|(kind, identifier)| Some((kind, identifier.split_once('.')?)))
+        .collect::<Vec<_>>();
+    // the schema of the search path that an unqualified `name` refers to
+    let found_in = |name: &str| {
+        argument.search_path.iter().find(|schema| {
+            objects
+                .iter()
+                .any(|(_, (s, n))| *s == schema.as_str() && *n == name)
+        })
+    };
+    let mut completions = objects
+        .iter()
+        .map(|(kind, (schema, name))| {
+            let text = if found_in(name).is_some_and(|s| s == schema) {
+                quote_ident(name)
+            } else {
+                format!("{}.{}", quote_ident(schema), quote_ident(name))
+            };
+            Completion {
+                label: text.clone(),
+                kind: CompletionKind::ObjectName,
+                // quotes of the string are doubled within it
+                insert_text: text.replace('\'', "''"),
+                detail: Some(kind.name().to_string()),
+                range: argument.range,
+            }
+        })
+        .collect::<Vec<_>>();
+    completions.sort_by(|a, b| a.label.cmp(&b.label));
+    completions.dedup_by(|a, b| a.label == b.label);
+    completions
+}
+
+/// Returns a diagnostic for every relation that a name argument names if neither the workspace,
+/// as told by `is_defined`, nor the database of the `catalog` has it. System catalogs are never
+/// reported.
+pub fn check_name_arguments(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmts: &[RawStmt],
+    is_defined: impl Fn(&NameArgument) -> bool,
+    catalog: &Catalog<'_>,
+) -> Vec<LintDiagnostic> {
+    name_arguments(cst, stmts)
+        .into_iter()
+        .filter(|argument| {
+            argument.kind == NameKind::Relation
+                && !argument.is_system()
+                && !is_defined(argument)
+                && !contains(catalog, argument)
+        })
+        .map(|argument| LintDiagnostic {
+            rule: UNKNOWN_OBJECT_NAME,
+            message: match (argument.kind, argument.identifier()) {
+                (NameKind::Relation, Some(identifier)) => {
+                    format!("relation {} does not exist", identifier)
+                }
+                (NameKind::Sequence, Some(identifier)) => {
+                    format!("sequence {} does not exist", identifier)
+                }
+                (NameKind::Relation, None) => "invalid relation name".to_string(),
+                (NameKind::Sequence, None) => "invalid sequence name".to_string(),
+            },
+            severity: Severity::Warning,
+            range: argument.range,
+        })
+        .collect()
+}
+
+/// Returns true if the database of `catalog` has the object of `argument` in any of the schemas
+/// that it may refer to
+fn contains(catalog: &Catalog<'_>, argument: &NameArgument) -> bool {
+    argument.identifiers().iter().any(|identifier| {
+        let Some((schema, name)) = identifier.split_once('.') else {
+            return false;
+        };
+        let tables = catalog.schemas.get(schema).map(|s| &s.tables);
+        match argument.kind {
+            NameKind::Relation => {
+                tables.is_some_and(|t| t.contains_key(name))
+                    || catalog
+                        .views
+                        .iter()
+                        .any(|v| v.schema_name == schema && v.name == name)
+            }
+            NameKind::Sequence => {
+                tables.is_some_and(|t| t.values().any(|t| t.owned_sequences.contains_key(name)))
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+    use crate::migrations::MigrationState;
+
+    #[test]
+    fn test_name_arguments() {
+        let input = "select nextval('app.orders_id_seq'), pg_total_relation_size('\"Contact\"'),
+            lower('x'), to_regclass('a.b.c');
+            set search_path to app, \"$user\", public;
+            select to_regclass('orders');";
+        let parse = parse_source(input);
+        let arguments = name_arguments(&parse.cst, &parse.stmts);
+        assert_eq!(
+            arguments
+                .iter()
+                .map(|a| (a.kind, a.identifiers(), &input[a.range]))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    NameKind::Sequence,
+                    vec!["app.orders_id_seq".to_string()],
+                    "app.orders_id_seq"
+                ),
+                (
+                    NameKind::Relation,
+                    vec!["public.Contact".to_string()],
+                    "\"Contact\""
+                ),
+                (NameKind::Relation, Vec::new(), "a.b.c"),
+                (
+                    NameKind::Relation,
+                    vec!["app.orders".to_string(), "public.orders".to_string()],
+                    "orders"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_completions() {
+        let input = "set search_path = app, public; select nextval('ord');";
+        let parse = parse_source(input);
+        let offset = TextSize::try_from(input.find("ord").unwrap() + 3).unwrap();
+        let objects = [
+            (NameKind::Sequence, "public.orders_id_seq".to_string()),
+            (NameKind::Sequence, "app.Invoice_seq".to_string()),
+            (NameKind::Sequence, "app.orders_id_seq".to_string()),
+            (NameKind::Sequence, "billing.invoice_seq".to_string()),
+            (NameKind::Relation, "public.orders".to_string()),
+        ];
+        let completions = name_argument_completions(&parse.cst, &parse.stmts, offset, &objects);
+        assert_eq!(
+            completions
+                .iter()
+                .map(|c| c.insert_text.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "\"Invoice_seq\"",
+                "billing.invoice_seq",
+                "orders_id_seq",
+                "public.orders_id_seq"
+            ]
+        );
+        assert_eq!(&input[completions[0].range], "ord");
+    }
+
+    #[test]
+    fn test_check_name_arguments() {
+        let input = "select nextval('orders_id_seq'), to_regclass('contact'),
+            to_regclass('orders'), pg_relation_size('pg_class'),
+            to_regclass('information_schema.tables');
+            set search_path = app;
+            select to_regclass('orders'), to_regclass('invoice');";
+        let parse = parse_source(input);
+        let mut state = MigrationState::default();
+        state.replay(
+            &parse_source(
+                "create table orders (id serial);
+                create schema app;
+                create table app.invoice (id int);",
+            )
+            .stmts,
+        );
+        let catalog = Catalog {
+            schemas: &state.schemas,
+            views: &[],
+            functions: &[],
+        };
+        let diagnostics = check_name_arguments(&parse.cst, &parse.stmts, |_| false, &catalog);
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| (&input[d.range], d.message.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("contact", "relation public.contact does not exist"),
+                ("orders", "relation app.orders does not exist"),
+            ]
+        );
+
+        let defined = |argument: &NameArgument| {
+            argument
+                .identifiers()
+                .contains(&"public.contact".to_string())
+        };
+        assert_eq!(
+            check_name_arguments(&parse.cst, &parse.stmts, defined, &catalog).len(),
+            1
+        );
+    }
+}
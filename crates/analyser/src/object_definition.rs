@@ -0,0 +1,188 @@
+//! The DDL of the objects of a live database, e.g. for an editor to show in a database explorer.
+//!
+//! Views, functions, indexes, triggers and constraints are printed by the `pg_get_*def` functions
+//! of the database with the query of their [`ObjectKind`]. Postgres cannot print tables, so their
+//! DDL is generated from the [`Schema`] model instead, like squashed migrations are. Views that
+//! have already been loaded can be printed without a query as well.
+
+use parser::make::quote_ident;
+
+use crate::migrations::{squash, MigrationState};
+use crate::schema::{Schema, View};
+
+/// The kinds of objects whose definition can be shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectKind {
+    Table,
+    /// A view or materialized view
+    View,
+    /// A function or procedure, of which all overloads are shown
+    Function,
+    Index,
+    Trigger,
+    Constraint,
+}
+
+impl ObjectKind {
+    pub fn from_name(name: &str) -> Option<ObjectKind> {
+        Some(match name {
+            "table" => ObjectKind::Table,
+            "view" => ObjectKind::View,
+            "function" => ObjectKind::Function,
+            "index" => ObjectKind::Index,
+            "trigger" => ObjectKind::Trigger,
+            "constraint" => ObjectKind::Constraint,
+            _ => return None,
+        })
+    }
+
+    /// Returns the query that loads the definitions of the objects of this kind whose schema is
+    /// `$1` and whose name is `$2`, one per row in a `definition` column. Tables have none.
+    pub fn definition_query(self) -> Option<&'static str> {
+        Some(match self {
+            ObjectKind::Table => return None,
+            ObjectKind::View => VIEW_DEFINITION_QUERY,
+            ObjectKind::Function => FUNCTION_DEFINITION_QUERY,
+            ObjectKind::Index => INDEX_DEFINITION_QUERY,
+            ObjectKind::Trigger => TRIGGER_DEFINITION_QUERY,
+            ObjectKind::Constraint => CONSTRAINT_DEFINITION_QUERY,
+        })
+    }
+}
+
+const VIEW_DEFINITION_QUERY: &str = "select
+    format(
+        E'CREATE %s %I.%I AS\\n%s',
+        case c.relkind when 'm' then 'MATERIALIZED VIEW' else 'OR REPLACE VIEW' end,
+        n.nspname,
+        c.relname,
+        pg_catalog.pg_get_viewdef(c.oid, true)
+    ) as definition
+from pg_catalog.pg_class c
+    join pg_catalog.pg_namespace n on n.oid = c.relnamespace
+where n.nspname = $1
+    and c.relname = $2
+    and c.relkind in ('v', 'm')";
+
+/// Aggregates are left out since `pg_get_functiondef` cannot print them
+const FUNCTION_DEFINITION_QUERY: &str = "select
+    rtrim(pg_catalog.pg_get_functiondef(p.oid), E'\\n') || ';' as definition
+from pg_catalog.pg_proc p
+    join pg_catalog.pg_namespace n on n.oid = p.pronamespace
+where n.nspname = $1
+    and p.proname = $2
+    and p.prokind in ('f', 'p', 'w')
+order by p.oid";
+
+const INDEX_DEFINITION_QUERY: &str = "select
+    pg_catalog.pg_get_indexdef(c.oid) || ';' as definition
+from pg_catalog.pg_class c
+    join pg_catalog.pg_namespace n on n.oid = c.relnamespace
+where n.nspname = $1
+    and c.relname = $2
+    and c.relkind in ('i', 'I')";
+
+/// Triggers are named per table, so the triggers of all tables with that name are shown
+const TRIGGER_DEFINITION_QUERY: &str = "select
+    pg_catalog.pg_get_triggerdef(t.oid, true) || ';' as definition
+from pg_catalog.pg_trigger t
+    join pg_catalog.pg_class c on c.oid = t.tgrelid
+    join pg_catalog.pg_namespace n on n.oid = c.relnamespace
+where n.nspname = $1
+    and t.tgname = $2
+    and not t.tgisinternal
+order by c.relname";
+
+/// Constraints are named per table, like triggers
+const CONSTRAINT_DEFINITION_QUERY: &str = "select
+    format(
+        'ALTER TABLE %I.%I ADD CONSTRAINT %I %s;',
+        n.nspname,
+        c.relname,
+        x.conname,
+        pg_catalog.pg_get_constraintdef(x.oid, true)
+    ) as definition
+from pg_catalog.pg_constraint x
+    join pg_catalog.pg_class c on c.oid = x.conrelid
+    join pg_catalog.pg_namespace n on n.oid = c.relnamespace
+where n.nspname = $1
+    and x.conname = $2
+order by c.relname";
+
+/// Returns the DDL that creates the table `name` of `schema` with its constraints and indexes
+pub fn table_definition(schema: &Schema, name: &str) -> Option<String> {
+    let table = schema.table(name)?;
+    let mut base = MigrationState::default();
+    base.schemas
+        .insert(schema.name.clone(), Schema::new(&schema.name));
+    let mut target = base.clone();
+    target
+        .schemas
+        .get_mut(&schema.name)?
+        .tables
+        .insert(name.to_string(), table.clone());
+    Some(squash(&base, &target))
+}
+
+/// Returns the DDL that creates `view` with the query that Postgres reconstructed
+pub fn view_definition(view: &View) -> String {
+    format!(
+        "CREATE {} {}.{} AS\n{}",
+        if view.is_materialized {
+            "MATERIALIZED VIEW"
+        } else {
+            "OR REPLACE VIEW"
+        },
+        quote_ident(&view.schema_name),
+        quote_ident(&view.name),
+        view.definition
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::schema::{Column, Table};
+
+    use super::*;
+
+    #[test]
+    fn test_table_definition() {
+        let mut schema = Schema::new("app");
+        schema.tables.insert(
+            "contact".to_string(),
+            Table {
+                name: "contact".to_string(),
+                columns: vec![Column {
+                    name: "id".to_string(),
+                    data_type: "integer".to_string(),
+                    not_null: true,
+                    default_expr: None,
+                }],
+                constraints: [("contact_pkey".to_string(), "PRIMARY KEY (id)".to_string())]
+                    .into_iter()
+                    .collect(),
+                ..Table::default()
+            },
+        );
+        let definition = table_definition(&schema, "contact").unwrap();
+        assert!(!definition.contains("CREATE SCHEMA"));
+        assert!(definition.contains("CREATE TABLE app.contact ("));
+        assert!(definition
+            .contains("ALTER TABLE app.contact ADD CONSTRAINT contact_pkey PRIMARY KEY (id);"));
+        assert_eq!(table_definition(&schema, "orders"), None);
+    }
+
+    #[test]
+    fn test_view_definition() {
+        let view = View {
+            schema_name: "app".to_string(),
+            name: "Active".to_string(),
+            definition: " SELECT contact.id\n   FROM app.contact;".to_string(),
+            is_materialized: true,
+        };
+        assert_eq!(
+            view_definition(&view),
+            "CREATE MATERIALIZED VIEW app.\"Active\" AS\n SELECT contact.id\n   FROM app.contact;"
+        );
+    }
+}
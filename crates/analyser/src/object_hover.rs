@@ -0,0 +1,267 @@
+//! Definitions of the tables, views, columns and functions named in the source text.
+//!
+//! Relations and columns are resolved like the symbols of [`moniker`](crate::moniker), and
+//! functions like the calls of [`signature_help`](crate::signature_help). Their definitions are
+//! then looked up in the catalog of a live database: the columns, access method and clustering
+//! index of a table, the query of a view, and the signature and comment of every overload of a
+//! function.
+
+use std::collections::BTreeMap;
+
+use cstree::syntax::ResolvedNode;
+use cstree::text::{TextRange, TextSize};
+use parser::make::quote_ident;
+use parser::{RawStmt, SyntaxKind};
+
+use crate::moniker::{symbol_at, SymbolKind};
+use crate::signature_help::function_name_at;
+use crate::{Column, Function, Schema, View};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectHover {
+    /// The range of the name
+    pub range: TextRange,
+    /// The definition of the object as markdown
+    pub contents: String,
+}
+
+/// Returns the definition of the object whose name is at `offset`, or `None` if the name does
+/// not resolve to an object of the catalog
+pub fn object_hover(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmts: &[RawStmt],
+    offset: TextSize,
+    schemas: &BTreeMap<String, Schema>,
+    views: &[View],
+    functions: &[Function],
+) -> Option<ObjectHover> {
+    if let Some(symbol) = symbol_at(cst, stmts, offset) {
+        let range = cst
+            .descendants_with_tokens()
+            .filter_map(|element| element.into_token())
+            .find(|token| {
+                token.kind() == SyntaxKind::Ident && token.text_range().contains_inclusive(offset)
+            })?
+            .text_range();
+        let contents = match symbol.kind {
+            SymbolKind::Table => relation_definition(&symbol.identifier, schemas, views),
+            SymbolKind::Column => column_definition(&symbol.identifier, schemas),
+        }?;
+        return Some(ObjectHover { range, contents });
+    }
+
+    let (range, call) = function_name_at(cst, offset)?;
+    let overloads = functions
+        .iter()
+        .filter(|f| f.name == call.name && call.schema.iter().all(|s| *s == f.schema_name))
+        .map(function_definition)
+        .collect::<Vec<_>>();
+    if overloads.is_empty() {
+        return None;
+    }
+    Some(ObjectHover {
+        range,
+        contents: overloads.join("\n\n---\n\n"),
+    })
+}
+
+/// Returns the definition of the table or view `identifier`, e.g. `public.contact`
+fn relation_definition(
+    identifier: &str,
+    schemas: &BTreeMap<String, Schema>,
+    views: &[View],
+) -> Option<String> {
+    let (schema, name) = identifier.split_once('.')?;
+    let qualified = format!("{}.{}", quote_ident(schema), quote_ident(name));
+    if let Some(table) = schemas.get(schema).and_then(|s| s.table(name)) {
+        let columns = table
+            .columns
+            .iter()
+            .map(|c| format!("    {}", column_sql(c)))
+            .collect::<Vec<_>>()
+            .join(",\n");
+        let mut definition = format!("```sql\ncreate table {} (\n{}\n)", qualified, columns);
+        if let Some(access_method) = &table.access_method {
+            definition.push_str(&format!(" using {}", quote_ident(access_method)));
+        }
+        if let Some(fillfactor) = table.fillfactor {
+            definition.push_str(&format!(" with (fillfactor = {})", fillfactor));
+        }
+        definition.push_str("\n```");
+        if let Some(index) = &table.clustered_index {
+            definition.push_str(&format!("\n\nClustered on `{}`", index));
+        }
+        if let Some(owner) = &table.owner {
+            definition.push_str(&format!("\n\nOwned by `{}`", owner));
+        }
+        return Some(definition);
+    }
+    let view = views
+        .iter()
+        .find(|v| v.schema_name == schema && v.name == name)?;
+    Some(format!(
+        "```sql\ncreate {}view {} as\n{}\n```",
+        if view.is_materialized {
+            "materialized "
+        } else {
+            ""
+        },
+        qualified,
+        view.definition.trim()
+    ))
+}
+
+/// Returns the definition of the column `identifier`, e.g. `public.contact.name`
+fn column_definition(identifier: &str, schemas: &BTreeMap<String, Schema>) -> Option<String> {
+    let (relation, name) = identifier.rsplit_once('.')?;
+    let (schema, table) = relation.split_once('.')?;
+    let column = schemas.get(schema)?.table(table)?.column(name)?;
+    Some(format!(
+        "```sql\n{}\n```\n\nColumn of `{}`",
+        column_sql(column),
+        relation
+    ))
+}
+
+fn column_sql(column: &Column) -> String {
+    let mut sql = format!("{} {}", quote_ident(&column.name), column.data_type);
+    if column.not_null {
+        sql.push_str(" not null");
+    }
+    if let Some(default_expr) = &column.default_expr {
+        sql.push_str(&format!(" default {}", default_expr));
+    }
+    sql
+}
+
+fn function_definition(function: &Function) -> String {
+    let mut definition = format!(
+        "```sql\n{} {}.{}({}) returns {}\n```",
+        if function.is_aggregate {
+            "aggregate"
+        } else {
+            "function"
+        },
+        quote_ident(&function.schema_name),
+        quote_ident(&function.name),
+        function.arguments,
+        function.result_type
+    );
+    if let Some(comment) = &function.comment {
+        definition.push_str(&format!("\n\n{}", comment));
+    }
+    definition
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+    use crate::{CatalogColumn, CatalogIndex};
+
+    fn hover(input: &str) -> Option<String> {
+        let offset = TextSize::from(input.find('|').unwrap() as u32);
+        let text = input.replace('|', "");
+        let parse = parse_source(&text);
+        let column = |name: &str, data_type: &str, not_null: bool| CatalogColumn {
+            schema_name: "public".to_string(),
+            table_name: "contact".to_string(),
+            column_name: name.to_string(),
+            data_type: data_type.to_string(),
+            not_null,
+            default_expr: None,
+            access_method: None,
+            owner: None,
+            owned_sequence: None,
+            partition_key: None,
+            fillfactor: None,
+        };
+        let visit = CatalogColumn {
+            table_name: "visit".to_string(),
+            access_method: Some("columnar".to_string()),
+            owner: Some("analytics".to_string()),
+            ..column("started", "timestamp with time zone", false)
+        };
+        let schemas = Schema::from_catalog(
+            vec![
+                column("id", "integer", true),
+                column("name", "text", false),
+                visit,
+            ],
+            vec![CatalogIndex {
+                schema_name: "public".to_string(),
+                table_name: "visit".to_string(),
+                index_name: "visit_started_idx".to_string(),
+                definition: "CREATE INDEX visit_started_idx ON public.visit USING btree (started)"
+                    .to_string(),
+                is_clustered: true,
+            }],
+            vec![],
+        );
+        let views = [View {
+            schema_name: "public".to_string(),
+            name: "active".to_string(),
+            definition: " SELECT contact.id\n   FROM contact;".to_string(),
+            is_materialized: false,
+        }];
+        let functions = [Function {
+            schema_name: "public".to_string(),
+            name: "full_name".to_string(),
+            arguments: "c contact".to_string(),
+            result_type: "text".to_string(),
+            is_aggregate: false,
+            comment: Some("The name to greet the contact with".to_string()),
+        }];
+        object_hover(
+            &parse.cst,
+            &parse.stmts,
+            offset,
+            &schemas,
+            &views,
+            &functions,
+        )
+        .map(|h| h.contents)
+    }
+
+    #[test]
+    fn test_object_hover() {
+        assert_eq!(
+            hover("select * from con|tact;").as_deref(),
+            Some("```sql\ncreate table public.contact (\n    id integer not null,\n    name text\n)\n```")
+        );
+        assert_eq!(
+            hover("select i|d from contact;").as_deref(),
+            Some("```sql\nid integer not null\n```\n\nColumn of `public.contact`")
+        );
+        assert_eq!(
+            hover("select * from act|ive;").as_deref(),
+            Some("```sql\ncreate view public.active as\nSELECT contact.id\n   FROM contact;\n```")
+        );
+        assert_eq!(
+            hover("select full_na|me(c) from contact c;").as_deref(),
+            Some(
+                "```sql\nfunction public.full_name(c contact) returns text\n```\n\n\
+                 The name to greet the contact with"
+            )
+        );
+    }
+
+    #[test]
+    fn test_table_storage() {
+        assert_eq!(
+            hover("select * from vis|it;").as_deref(),
+            Some(
+                "```sql\ncreate table public.visit (\n    started timestamp with time zone\n) using columnar\n```\n\n\
+                 Clustered on `visit_started_idx`\n\nOwned by `analytics`"
+            )
+        );
+    }
+
+    #[test]
+    fn test_unknown_object() {
+        assert_eq!(hover("select * from or|ders;"), None);
+        assert_eq!(hover("select lo|wer(name) from contact;"), None);
+        assert!(hover("select * from contact where |id = 1;").is_some());
+    }
+}
@@ -0,0 +1,254 @@
+//! Outline of the statements of a document.
+//!
+//! Every statement is an item labeled with its kind and the object it creates or changes, e.g.
+//! `CREATE TABLE contact`, or with its text if it has no such object. The items of a statement are
+//! its common table expressions, the columns of `CREATE TABLE` and the parameters of
+//! `CREATE FUNCTION`.
+
+use cstree::syntax::{ResolvedNode, ResolvedToken};
+use cstree::text::TextRange;
+use parser::{RawStmt, StmtKind, SyntaxKind};
+use pg_query::protobuf::RangeVar;
+use pg_query::NodeEnum;
+
+use crate::utils::string_value;
+
+/// The number of characters of a statement that are shown as the label of its item
+const LABEL_LEN: usize = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutlineKind {
+    Statement(Option<StmtKind>),
+    CommonTableExpr,
+    Column,
+    Parameter,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineItem {
+    pub label: String,
+    /// The type of a column or parameter
+    pub detail: Option<String>,
+    pub kind: OutlineKind,
+    pub range: TextRange,
+    /// The range of the name, or the range of the item if it has none
+    pub selection_range: TextRange,
+    pub children: Vec<OutlineItem>,
+}
+
+/// Returns an item for every statement, in the order of the source text
+pub fn outline(cst: &ResolvedNode<SyntaxKind>, stmts: &[RawStmt]) -> Vec<OutlineItem> {
+    let text = cst.text().to_string();
+    let mut items = stmts
+        .iter()
+        .map(|stmt| OutlineItem {
+            label: statement_label(&stmt.stmt).unwrap_or_else(|| text_label(&text[stmt.range])),
+            detail: None,
+            kind: OutlineKind::Statement(SyntaxKind::from(&stmt.stmt).stmt_kind()),
+            range: stmt.range,
+            selection_range: stmt.range,
+            children: Vec::new(),
+        })
+        .collect::<Vec<_>>();
+
+    for node in cst.descendants() {
+        let parent = node.parent().map(|p| p.kind());
+        let child = match (node.kind(), parent) {
+            (SyntaxKind::CommonTableExpr, _) => {
+                name_token(node).map(|name| item(node, name, OutlineKind::CommonTableExpr))
+            }
+            (SyntaxKind::ColumnDef, Some(SyntaxKind::CreateStmt)) => {
+                name_token(node).map(|name| item(node, name, OutlineKind::Column))
+            }
+            (SyntaxKind::FunctionParameter, Some(SyntaxKind::CreateFunctionStmt)) => {
+                Some(parameter(node))
+            }
+            _ => None,
+        };
+        let Some(child) = child else {
+            continue;
+        };
+        if let Some(stmt) = items
+            .iter_mut()
+            .find(|item| item.range.contains_range(child.range))
+        {
+            stmt.children.push(child);
+        }
+    }
+    items
+}
+
+/// Returns the label of a statement that creates or changes a named object
+fn statement_label(stmt: &NodeEnum) -> Option<String> {
+    let label = match stmt {
+        NodeEnum::CreateStmt(n) => format!("CREATE TABLE {}", relation_label(n.relation.as_ref()?)),
+        NodeEnum::ViewStmt(n) => format!("CREATE VIEW {}", relation_label(n.view.as_ref()?)),
+        NodeEnum::CreateTableAsStmt(n) => {
+            let relation = relation_label(n.into.as_ref()?.rel.as_ref()?);
+            // ObjectMatview
+            if n.objtype == 24 {
+                format!("CREATE MATERIALIZED VIEW {}", relation)
+            } else {
+                format!("CREATE TABLE {} AS", relation)
+            }
+        }
+        NodeEnum::CreateFunctionStmt(n) => format!(
+            "CREATE {} {}",
+            if n.is_procedure {
+                "PROCEDURE"
+            } else {
+                "FUNCTION"
+            },
+            n.funcname
+                .iter()
+                .filter_map(string_value)
+                .collect::<Vec<_>>()
+                .join(".")
+        ),
+        NodeEnum::IndexStmt(n) if n.idxname.is_empty() => {
+            format!("CREATE INDEX ON {}", relation_label(n.relation.as_ref()?))
+        }
+        NodeEnum::IndexStmt(n) => format!("CREATE INDEX {}", n.idxname),
+        NodeEnum::CreateSchemaStmt(n) => format!("CREATE SCHEMA {}", n.schemaname),
+        // ObjectTable
+        NodeEnum::AlterTableStmt(n) if n.objtype == 42 => {
+            format!("ALTER TABLE {}", relation_label(n.relation.as_ref()?))
+        }
+        NodeEnum::InsertStmt(n) => format!("INSERT INTO {}", relation_label(n.relation.as_ref()?)),
+        NodeEnum::UpdateStmt(n) => format!("UPDATE {}", relation_label(n.relation.as_ref()?)),
+        NodeEnum::DeleteStmt(n) => format!("DELETE FROM {}", relation_label(n.relation.as_ref()?)),
+        _ => return None,
+    };
+    Some(label)
+}
+
+/// Returns the name of `relation` as written, i.e. qualified only if it is in the source text
+fn relation_label(relation: &RangeVar) -> String {
+    if relation.schemaname.is_empty() {
+        relation.relname.clone()
+    } else {
+        format!("{}.{}", relation.schemaname, relation.relname)
+    }
+}
+
+/// Returns the start of `stmt` with all whitespace collapsed
+fn text_label(stmt: &str) -> String {
+    let label = stmt.split_whitespace().collect::<Vec<_>>().join(" ");
+    match label.char_indices().nth(LABEL_LEN) {
+        Some((idx, _)) => format!("{}…", &label[..idx]),
+        None => label,
+    }
+}
+
+/// Returns the first token of `node`, which is the name of a column or common table expression
+fn name_token(node: &ResolvedNode<SyntaxKind>) -> Option<&ResolvedToken<SyntaxKind>> {
+    node.descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .find(|token| !token.kind().is_trivia())
+}
+
+fn item(
+    node: &ResolvedNode<SyntaxKind>,
+    name: &ResolvedToken<SyntaxKind>,
+    kind: OutlineKind,
+) -> OutlineItem {
+    OutlineItem {
+        label: name.text().to_string(),
+        detail: type_label(node),
+        kind,
+        range: node.text_range(),
+        selection_range: name.text_range(),
+        children: Vec::new(),
+    }
+}
+
+/// Returns the item of a function parameter, which is labeled with its type if it has no name
+fn parameter(node: &ResolvedNode<SyntaxKind>) -> OutlineItem {
+    // the name is the last token before the type that is not the mode, e.g. `OUT`
+    let name = node
+        .children_with_tokens()
+        .take_while(|element| element.kind() != SyntaxKind::TypeName)
+        .filter_map(|element| element.into_token())
+        .filter(|token| {
+            !token.kind().is_trivia()
+                && !matches!(
+                    token.kind(),
+                    SyntaxKind::InP | SyntaxKind::OutP | SyntaxKind::Inout | SyntaxKind::Variadic
+                )
+        })
+        .last();
+    match name {
+        Some(name) => item(node, name, OutlineKind::Parameter),
+        None => OutlineItem {
+            label: type_label(node).unwrap_or_default(),
+            detail: None,
+            kind: OutlineKind::Parameter,
+            range: node.text_range(),
+            selection_range: node.text_range(),
+            children: Vec::new(),
+        },
+    }
+}
+
+/// Returns the type of a column or parameter as written, with all whitespace collapsed
+fn type_label(node: &ResolvedNode<SyntaxKind>) -> Option<String> {
+    let type_name = node
+        .children()
+        .find(|child| child.kind() == SyntaxKind::TypeName)?;
+    let text = type_name.text().to_string();
+    Some(text.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    fn labels(items: &[OutlineItem]) -> Vec<(&str, Option<&str>)> {
+        items
+            .iter()
+            .map(|item| (item.label.as_str(), item.detail.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn test_outline() {
+        let input = "create table app.contact (id int primary key, email text not null);
+with recent as (select * from app.contact), active as (select 1)
+select * from recent, active;
+create function full_name(c contact, out name text) returns text as 'select 1' language sql;
+set search_path = app;";
+        let parse = parse_source(input);
+        let items = outline(&parse.cst, &parse.stmts);
+        assert_eq!(
+            labels(&items),
+            vec![
+                ("CREATE TABLE app.contact", None),
+                (
+                    "with recent as (select * from app.contact), active as (selec…",
+                    None
+                ),
+                ("CREATE FUNCTION full_name", None),
+                ("set search_path = app;", None),
+            ]
+        );
+        assert_eq!(items[0].kind, OutlineKind::Statement(Some(StmtKind::Ddl)));
+
+        assert_eq!(
+            labels(&items[0].children),
+            vec![("id", Some("int")), ("email", Some("text"))]
+        );
+        assert_eq!(&input[items[0].children[1].selection_range], "email");
+        assert_eq!(
+            labels(&items[1].children),
+            vec![("recent", None), ("active", None)]
+        );
+        assert_eq!(
+            labels(&items[2].children),
+            vec![("c", Some("contact")), ("name", Some("text"))]
+        );
+        assert_eq!(items[2].children[1].kind, OutlineKind::Parameter);
+        assert!(items[3].children.is_empty());
+    }
+}
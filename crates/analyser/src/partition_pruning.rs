@@ -0,0 +1,360 @@
+//! Queries on partitioned tables that Postgres cannot prune partitions for.
+//!
+//! Postgres only skips the partitions of a partitioned table whose bounds rule out the conditions
+//! on its partition key. A query that filters a partitioned table on other columns only scans all
+//! of its partitions, and so does one that compares a function or a cast of a key column instead
+//! of the column itself, e.g. `date_trunc('day', created_at) = $1`. The partition keys are taken
+//! from the schema model, in which unqualified names are looked up with the search path that the
+//! statements before the query set. Only the conditions of the `WHERE` clause that are combined with `AND`
+//! count as filters on the key, and tables whose key has expressions are left out. Queries without
+//! a `WHERE` clause are expected to scan all partitions and are not reported.
+
+use cstree::syntax::ResolvedNode;
+use cstree::text::{TextRange, TextSize};
+use parser::{RawStmt, SyntaxKind};
+use pg_query::protobuf::{ColumnRef, Node, RangeVar};
+use pg_query::NodeEnum;
+
+use crate::lint::{LintDiagnostic, Severity};
+use crate::migrations::MigrationState;
+use crate::schema::Table;
+use crate::utils::{descendants, string_value};
+
+pub const PARTITION_PRUNING: &str = "partition-pruning";
+
+/// The operators that Postgres prunes partitions with
+const PRUNING_OPERATORS: &[&str] = &["=", "<", "<=", ">", ">="];
+
+/// Returns an advice for every partitioned table in the queries of `stmts` that the query filters
+/// without pruning its partitions, given the `state` of the database before `stmts`. Every
+/// statement is applied to `state` once it has been checked.
+pub fn check_partition_pruning(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmts: &[RawStmt],
+    state: &mut MigrationState,
+) -> Vec<LintDiagnostic> {
+    let mut diagnostics = Vec::new();
+    for stmt in stmts {
+        check_stmt(cst, stmt, state, &mut diagnostics);
+        state.replay(std::slice::from_ref(stmt));
+    }
+    diagnostics
+}
+
+/// Adds the advice on the queries of `stmt` to `diagnostics`
+fn check_stmt(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmt: &RawStmt,
+    state: &MigrationState,
+    diagnostics: &mut Vec<LintDiagnostic>,
+) {
+    for (relations, where_clause) in queries(&stmt.stmt) {
+        let Some(where_clause) = where_clause.as_ref().and_then(|w| w.node.as_ref()) else {
+            continue;
+        };
+        let mut conditions = Vec::new();
+        conjuncts(where_clause, &mut conditions);
+        for relation in &relations {
+            let Some((name, table, key)) = partition_key(relation, state) else {
+                continue;
+            };
+            let filter = Filter {
+                relation,
+                table,
+                key: &key,
+                is_only_relation: relations.len() == 1,
+            };
+            if conditions.iter().any(|c| filter.prunes(c)) {
+                continue;
+            }
+            let (message, location) = match conditions.iter().find_map(|c| filter.wrapper(c)) {
+                Some((Some(function), column, location)) => (
+                    format!(
+                        "The partition key {} of {} is wrapped in {}(), which prevents \
+                         partition pruning. Compare the column itself instead.",
+                        column, name, function
+                    ),
+                    location,
+                ),
+                Some((None, column, location)) => (
+                    format!(
+                        "The partition key {} of {} is cast, which prevents partition \
+                         pruning. Compare the column itself instead.",
+                        column, name
+                    ),
+                    location,
+                ),
+                None if conditions.iter().any(|c| filter.references(c)) => (
+                    format!(
+                        "{} is partitioned by {}, but the query does not filter on it, so all \
+                         partitions are scanned.",
+                        name,
+                        table.partition_key.as_deref().unwrap_or_default()
+                    ),
+                    relation.location,
+                ),
+                None => continue,
+            };
+            let start = stmt.range.start() + TextSize::from(location.max(0) as u32);
+            let range = cst
+                .descendants_with_tokens()
+                .filter_map(|element| element.into_token())
+                .find(|token| token.text_range().start() == start)
+                .map_or(TextRange::empty(start), |token| token.text_range());
+            diagnostics.push(LintDiagnostic {
+                rule: PARTITION_PRUNING,
+                message,
+                severity: Severity::Information,
+                range,
+            });
+        }
+    }
+}
+
+/// Returns the tables and the `WHERE` clause of every query within `stmt`
+fn queries(stmt: &NodeEnum) -> Vec<(Vec<RangeVar>, Option<Node>)> {
+    descendants(stmt)
+        .into_iter()
+        .filter_map(|node| match node {
+            NodeEnum::SelectStmt(n) => {
+                Some((relations(&n.from_clause), n.where_clause.map(|w| *w)))
+            }
+            NodeEnum::UpdateStmt(n) => {
+                let mut relations = relations(&n.from_clause);
+                relations.extend(n.relation.as_ref().map(|r| RangeVar::clone(r)));
+                Some((relations, n.where_clause.map(|w| *w)))
+            }
+            NodeEnum::DeleteStmt(n) => {
+                let mut relations = relations(&n.using_clause);
+                relations.extend(n.relation.as_ref().map(|r| RangeVar::clone(r)));
+                Some((relations, n.where_clause.map(|w| *w)))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the tables of a `FROM` clause, including those of its joins
+fn relations<'a>(items: impl IntoIterator<Item = &'a Node>) -> Vec<RangeVar> {
+    let mut relations = Vec::new();
+    for item in items {
+        match item.node.as_ref() {
+            Some(NodeEnum::RangeVar(r)) => relations.push(r.clone()),
+            Some(NodeEnum::JoinExpr(j)) => {
+                relations.extend(self::relations(
+                    j.larg.as_deref().into_iter().chain(j.rarg.as_deref()),
+                ));
+            }
+            _ => {}
+        }
+    }
+    relations
+}
+
+/// A partitioned table of a query
+struct Filter<'a> {
+    relation: &'a RangeVar,
+    table: &'a Table,
+    /// The columns of the partition key
+    key: &'a [String],
+    /// Whether unqualified columns can only refer to the table
+    is_only_relation: bool,
+}
+
+impl Filter<'_> {
+    /// Returns the column of the table that `column` refers to, if it does
+    fn column<'c>(&self, column: &'c ColumnRef) -> Option<&'c str> {
+        let qualifier = self
+            .relation
+            .alias
+            .as_ref()
+            .map_or(&self.relation.relname, |a| &a.aliasname);
+        match column.fields.as_slice() {
+            [name] => {
+                let name = string_value(name)?;
+                (self.is_only_relation || self.table.column(name).is_some()).then_some(name)
+            }
+            [table, name] if string_value(table) == Some(qualifier.as_str()) => string_value(name),
+            _ => None,
+        }
+    }
+
+    /// Returns the key column that `node` is, if it is one
+    fn key_column<'c>(&self, node: Option<&'c Node>) -> Option<&'c str> {
+        match node?.node.as_ref()? {
+            NodeEnum::ColumnRef(c) => self
+                .column(c)
+                .filter(|name| self.key.iter().any(|k| k == *name)),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the condition `node` compares a key column as it is, so that Postgres can
+    /// prune partitions with it
+    fn prunes(&self, node: &NodeEnum) -> bool {
+        match node {
+            NodeEnum::AExpr(e) => {
+                let operator = e.name.last().and_then(string_value).unwrap_or_default();
+                let is_key = |side: &Option<Box<Node>>| self.key_column(side.as_deref()).is_some();
+                match e.kind {
+                    // AexprOp
+                    1 => {
+                        PRUNING_OPERATORS.contains(&operator)
+                            && (is_key(&e.lexpr) || is_key(&e.rexpr))
+                    }
+                    // AexprOpAny, AexprIn, AexprBetween and AexprBetweenSym
+                    2 | 7 | 11 | 13 => is_key(&e.lexpr),
+                    _ => false,
+                }
+            }
+            NodeEnum::NullTest(n) => self.key_column(n.arg.as_deref()).is_some(),
+            // OrExpr, which prunes if all of its branches do
+            NodeEnum::BoolExpr(b) if b.boolop == 2 => b
+                .args
+                .iter()
+                .all(|arg| arg.node.as_ref().is_some_and(|arg| self.prunes(arg))),
+            _ => false,
+        }
+    }
+
+    /// Returns the name of the function that wraps a key column in the condition `node`, or `None`
+    /// if a cast wraps it, with the name of the column and the location of the wrapper
+    fn wrapper(&self, node: &NodeEnum) -> Option<(Option<String>, String, i32)> {
+        descendants(node).into_iter().find_map(|node| {
+            let (wrapper, location) = match &node {
+                NodeEnum::FuncCall(f) => {
+                    let name = f.funcname.last().and_then(string_value)?;
+                    (Some(name.to_string()), f.location)
+                }
+                NodeEnum::TypeCast(c) => (None, c.location),
+                _ => return None,
+            };
+            let column = descendants(&node).into_iter().find_map(|n| match n {
+                NodeEnum::ColumnRef(c) => self
+                    .column(&c)
+                    .filter(|name| self.key.iter().any(|k| k == *name))
+                    .map(str::to_string),
+                _ => None,
+            })?;
+            Some((wrapper, column, location))
+        })
+    }
+
+    /// Returns true if the condition `node` refers to any column of the table
+    fn references(&self, node: &NodeEnum) -> bool {
+        descendants(node).iter().any(|node| match node {
+            NodeEnum::ColumnRef(c) => self.column(c).is_some(),
+            _ => false,
+        })
+    }
+}
+
+/// Collects the conditions of `node` that are combined with `AND`
+fn conjuncts(node: &NodeEnum, conditions: &mut Vec<NodeEnum>) {
+    match node {
+        // AndExpr
+        NodeEnum::BoolExpr(b) if b.boolop == 1 => {
+            for arg in b.args.iter().filter_map(|arg| arg.node.as_ref()) {
+                conjuncts(arg, conditions);
+            }
+        }
+        _ => conditions.push(node.clone()),
+    }
+}
+
+/// Returns the qualified name and the table of `relation` and the columns of its partition key,
+/// if it is partitioned by columns only
+fn partition_key<'a>(
+    relation: &RangeVar,
+    state: &'a MigrationState,
+) -> Option<(String, &'a Table, Vec<String>)> {
+    let (schema, name) = state.relation_name(relation);
+    let table = state.schemas.get(&schema)?.table(&name)?;
+    let key = table
+        .partition_columns
+        .as_ref()?
+        .iter()
+        .cloned()
+        .collect::<Option<Vec<_>>>()?;
+    Some((format!("{}.{}", schema, name), table, key))
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    fn advice(query: &str) -> Vec<(String, String)> {
+        let input = format!(
+            "create table event (id int, region text, created_at timestamptz)
+                partition by range (created_at);
+            create table metric (id int, name text) partition by hash (lower(name));
+            create table contact (id int, name text);
+            {}",
+            query
+        );
+        let parse = parse_source(&input);
+        check_partition_pruning(&parse.cst, &parse.stmts, &mut MigrationState::default())
+            .into_iter()
+            .map(|d| (input[d.range].to_string(), d.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_partition_pruning() {
+        assert_eq!(
+            advice("select * from event where region = 'eu';"),
+            vec![(
+                "event".to_string(),
+                "public.event is partitioned by RANGE (created_at), but the query does not \
+                 filter on it, so all partitions are scanned."
+                    .to_string()
+            )]
+        );
+        assert_eq!(
+            advice("delete from event e where date_trunc('day', e.created_at) = '2024-01-01';"),
+            vec![(
+                "date_trunc".to_string(),
+                "The partition key created_at of public.event is wrapped in date_trunc(), which \
+                 prevents partition pruning. Compare the column itself instead."
+                    .to_string()
+            )]
+        );
+        assert_eq!(
+            advice("select * from event where created_at::date = current_date;").len(),
+            1
+        );
+        // unqualified names are looked up in the search path
+        assert_eq!(
+            advice(
+                "create table app.log (id int, logged_at timestamptz)
+                    partition by range (logged_at);
+                set search_path = app;
+                select * from log where id = 1;"
+            ),
+            vec![(
+                "log".to_string(),
+                "app.log is partitioned by RANGE (logged_at), but the query does not filter on it, \
+                 so all partitions are scanned."
+                    .to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_partition_pruning_filters() {
+        for query in [
+            "select * from event where created_at >= now() - interval '1 day' and region = 'eu';",
+            "select * from event where created_at between $1 and $2;",
+            "select * from event e join contact c on c.id = e.id
+                where c.name = 'a' or c.name = 'b';",
+            "update event set region = 'eu' where created_at = $1 or created_at = $2;",
+            // no filter at all, or on a key of expressions
+            "select * from event;",
+            "select * from metric where id = 1;",
+        ] {
+            assert!(advice(query).is_empty(), "{}", query);
+        }
+    }
+}
@@ -0,0 +1,250 @@
+//! Query plans as `EXPLAIN (FORMAT JSON)` returns them, rendered as text.
+//!
+//! The text follows the format of `EXPLAIN` itself: every node shows its estimated cost, rows and
+//! width, followed by its conditions and its child nodes, which are indented below it with an
+//! arrow. Statements with placeholders such as `$1` are explained with the placeholders bound to
+//! literal values, or to `NULL` if no value is given, since the server cannot plan them otherwise.
+//! Only the statements that `EXPLAIN` accepts can be explained, see [`is_explainable`].
+
+use cstree::syntax::ResolvedNode;
+use parser::make::quote_literal;
+use parser::{RawStmt, SyntaxKind};
+use pg_query::NodeEnum;
+
+/// A node of a query plan
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlanNode {
+    /// The type of the node, e.g. `Seq Scan` or `Hash Join`
+    pub node_type: String,
+    /// The kind of join, e.g. `Left`
+    pub join_type: Option<String>,
+    /// The strategy of an aggregate, e.g. `Hashed`
+    pub strategy: Option<String>,
+    pub relation_name: Option<String>,
+    pub alias: Option<String>,
+    pub index_name: Option<String>,
+    /// The name of an init plan or sub plan, e.g. `SubPlan 1`
+    pub subplan_name: Option<String>,
+    /// Whether an aggregate computes partial results or combines them, `Partial` or `Finalize`
+    pub partial_mode: Option<String>,
+    /// True if the node is run by several parallel workers
+    pub parallel_aware: bool,
+    pub startup_cost: f64,
+    pub total_cost: f64,
+    pub plan_rows: f64,
+    pub plan_width: i64,
+    /// The conditions and keys of the node in the order they are shown, e.g. `Filter`
+    pub details: Vec<(String, String)>,
+    pub children: Vec<PlanNode>,
+}
+
+impl PlanNode {
+    /// Returns the node as its line of the plan starts, e.g. `Index Scan using contact_pkey on
+    /// contact c`
+    fn label(&self) -> String {
+        let mut label = match (&self.join_type, &self.strategy) {
+            (Some(join_type), _) if join_type != "Inner" => {
+                let node_type = self
+                    .node_type
+                    .strip_suffix(" Join")
+                    .unwrap_or(&self.node_type);
+                format!("{} {} Join", node_type, join_type)
+            }
+            (_, Some(strategy)) if self.node_type == "Aggregate" => match strategy.as_str() {
+                "Hashed" => "HashAggregate".to_string(),
+                "Sorted" => "GroupAggregate".to_string(),
+                "Mixed" => "MixedAggregate".to_string(),
+                _ => self.node_type.clone(),
+            },
+            _ => self.node_type.clone(),
+        };
+        if let Some(mode) = self
+            .partial_mode
+            .as_ref()
+            .filter(|mode| matches!(mode.as_str(), "Partial" | "Finalize"))
+        {
+            label = format!("{} {}", mode, label);
+        }
+        if self.parallel_aware {
+            label = format!("Parallel {}", label);
+        }
+        if let Some(index) = &self.index_name {
+            label.push_str(&format!(" using {}", index));
+        }
+        if let Some(relation) = &self.relation_name {
+            label.push_str(&format!(" on {}", relation));
+            if let Some(alias) = self.alias.as_ref().filter(|alias| *alias != relation) {
+                label.push_str(&format!(" {}", alias));
+            }
+        }
+        label
+    }
+}
+
+/// Renders `plan` as the text that `EXPLAIN` prints for it
+pub fn render_plan(plan: &PlanNode) -> String {
+    let mut lines = Vec::new();
+    render_node(plan, 0, true, &mut lines);
+    lines.join("\n")
+}
+
+/// Renders `node`, whose label starts at the column `indent`
+fn render_node(node: &PlanNode, indent: usize, is_root: bool, lines: &mut Vec<String>) {
+    let prefix = if is_root {
+        " ".repeat(indent)
+    } else {
+        format!("{}->  ", " ".repeat(indent - 4))
+    };
+    lines.push(format!(
+        "{}{}  (cost={:.2}..{:.2} rows={:.0} width={})",
+        prefix,
+        node.label(),
+        node.startup_cost,
+        node.total_cost,
+        node.plan_rows,
+        node.plan_width
+    ));
+    for (key, value) in &node.details {
+        lines.push(format!("{}{}: {}", " ".repeat(indent + 2), key, value));
+    }
+    for child in &node.children {
+        if let Some(name) = &child.subplan_name {
+            lines.push(format!("{}{}", " ".repeat(indent + 2), name));
+        }
+        render_node(child, indent + 6, false, lines);
+    }
+}
+
+/// Returns the text of `stmt` with every placeholder `$n` replaced by the literal of the `n`th of
+/// `values`, or by `NULL` if there is no such value
+pub fn bind_parameters(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmt: &RawStmt,
+    values: &[Option<String>],
+) -> String {
+    cst.descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        // without the semicolon, if the range of the statement includes it
+        .filter(|token| {
+            stmt.range.contains_range(token.text_range()) && token.kind() != SyntaxKind::Ascii59
+        })
+        .map(|token| {
+            if token.kind() != SyntaxKind::Param {
+                return token.text().to_string();
+            }
+            let value = token.text()[1..]
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| values.get(n.checked_sub(1)?)?.as_deref());
+            value.map_or_else(|| "NULL".to_string(), quote_literal)
+        })
+        .collect()
+}
+
+/// Returns true for the statements that `EXPLAIN` accepts
+pub fn is_explainable(stmt: &NodeEnum) -> bool {
+    matches!(
+        stmt,
+        NodeEnum::SelectStmt(_)
+            | NodeEnum::InsertStmt(_)
+            | NodeEnum::UpdateStmt(_)
+            | NodeEnum::DeleteStmt(_)
+            | NodeEnum::MergeStmt(_)
+            | NodeEnum::CreateTableAsStmt(_)
+            | NodeEnum::ExecuteStmt(_)
+            | NodeEnum::DeclareCursorStmt(_)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    fn node(node_type: &str, relation: Option<&str>, alias: Option<&str>) -> PlanNode {
+        PlanNode {
+            node_type: node_type.to_string(),
+            relation_name: relation.map(str::to_string),
+            alias: alias.map(str::to_string),
+            startup_cost: 1.5,
+            total_cost: 20.25,
+            plan_rows: 10.0,
+            plan_width: 36,
+            ..PlanNode::default()
+        }
+    }
+
+    #[test]
+    fn test_render_plan() {
+        let mut hash = node("Hash", None, None);
+        hash.children = vec![node("Seq Scan", Some("contact"), Some("c"))];
+        let mut index_scan = node("Index Scan", Some("orders"), Some("orders"));
+        index_scan.index_name = Some("orders_pkey".to_string());
+        index_scan.details = vec![("Index Cond".to_string(), "(id = 1)".to_string())];
+        let mut join = node("Hash Join", None, None);
+        join.join_type = Some("Left".to_string());
+        join.details = vec![(
+            "Hash Cond".to_string(),
+            "(orders.contact_id = c.id)".to_string(),
+        )];
+        join.children = vec![index_scan, hash];
+
+        assert_eq!(
+            render_plan(&join),
+            "Hash Left Join  (cost=1.50..20.25 rows=10 width=36)
+  Hash Cond: (orders.contact_id = c.id)
+  ->  Index Scan using orders_pkey on orders  (cost=1.50..20.25 rows=10 width=36)
+        Index Cond: (id = 1)
+  ->  Hash  (cost=1.50..20.25 rows=10 width=36)
+        ->  Seq Scan on contact c  (cost=1.50..20.25 rows=10 width=36)"
+        );
+    }
+
+    #[test]
+    fn test_render_parallel_plan() {
+        let mut scan = node("Seq Scan", Some("contact"), Some("contact"));
+        scan.parallel_aware = true;
+        let mut partial = node("Aggregate", None, None);
+        partial.strategy = Some("Hashed".to_string());
+        partial.partial_mode = Some("Partial".to_string());
+        partial.children = vec![scan];
+        let mut gather = node("Gather", None, None);
+        gather.children = vec![partial];
+        let mut finalize = node("Aggregate", None, None);
+        finalize.strategy = Some("Plain".to_string());
+        finalize.partial_mode = Some("Finalize".to_string());
+        finalize.children = vec![gather];
+
+        assert_eq!(
+            render_plan(&finalize),
+            "Finalize Aggregate  (cost=1.50..20.25 rows=10 width=36)
+  ->  Gather  (cost=1.50..20.25 rows=10 width=36)
+        ->  Partial HashAggregate  (cost=1.50..20.25 rows=10 width=36)
+              ->  Parallel Seq Scan on contact  (cost=1.50..20.25 rows=10 width=36)"
+        );
+    }
+
+    #[test]
+    fn test_bind_parameters() {
+        let input = "select 1;\nselect * from contact where name = $1 and team_id = $2;";
+        let parse = parse_source(input);
+        assert_eq!(
+            bind_parameters(&parse.cst, &parse.stmts[1], &[Some("O'Brien".to_string())]),
+            "select * from contact where name = 'O''Brien' and team_id = NULL"
+        );
+    }
+
+    #[test]
+    fn test_is_explainable() {
+        let stmts = parse_source(
+            "select 1; update t set a = 1; create table t (a int); create table u as select 1;",
+        )
+        .stmts;
+        let explainable = stmts
+            .iter()
+            .map(|stmt| is_explainable(&stmt.stmt))
+            .collect::<Vec<_>>();
+        assert_eq!(explainable, [true, true, false, true]);
+    }
+}
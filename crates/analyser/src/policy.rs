@@ -0,0 +1,261 @@
+//! Organization-level guardrails for the statements of a project.
+//!
+//! A policy denies kinds of statements outright, e.g. `DROP DATABASE` or `GRANT ... TO PUBLIC`,
+//! and can require that the indexes of large or busy tables are created with `CONCURRENTLY`. The
+//! tables are given as patterns in which `*` matches any characters, e.g. `events_*`. A pattern
+//! with a schema matches the qualified name of a table, one without matches its name. Indexes on
+//! tables that are created in the same file can be built without `CONCURRENTLY`, since no one
+//! uses the table yet. Violations are errors: `pglsp exec` runs no statement of a file that has
+//! one, and the language server does not execute or explain a statement that violates the policy.
+//! pglsp has no migration runner of its own, so migrations applied with other tools are only
+//! checked by `pglsp lint`.
+
+use std::collections::HashSet;
+
+use parser::RawStmt;
+use pg_query::protobuf::Node;
+use pg_query::NodeEnum;
+
+use crate::lint::{LintDiagnostic, Severity};
+use crate::moniker::qualified_name;
+
+pub const POLICY_VIOLATION: &str = "policy-violation";
+
+/// A kind of statement that a policy can deny
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeniedStatement {
+    DropDatabase,
+    DropSchema,
+    DropTable,
+    Truncate,
+    GrantToPublic,
+    AlterSystem,
+}
+
+impl DeniedStatement {
+    pub const ALL: &'static [DeniedStatement] = &[
+        DeniedStatement::DropDatabase,
+        DeniedStatement::DropSchema,
+        DeniedStatement::DropTable,
+        DeniedStatement::Truncate,
+        DeniedStatement::GrantToPublic,
+        DeniedStatement::AlterSystem,
+    ];
+
+    /// Returns the name of the statement in the `deny` list of the policy, e.g. `drop-database`
+    pub fn name(self) -> &'static str {
+        match self {
+            DeniedStatement::DropDatabase => "drop-database",
+            DeniedStatement::DropSchema => "drop-schema",
+            DeniedStatement::DropTable => "drop-table",
+            DeniedStatement::Truncate => "truncate",
+            DeniedStatement::GrantToPublic => "grant-to-public",
+            DeniedStatement::AlterSystem => "alter-system",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<DeniedStatement> {
+        DeniedStatement::ALL
+            .iter()
+            .copied()
+            .find(|statement| statement.name() == name)
+    }
+
+    /// Returns the statement as it is written, e.g. `DROP DATABASE`
+    fn label(self) -> &'static str {
+        match self {
+            DeniedStatement::DropDatabase => "DROP DATABASE",
+            DeniedStatement::DropSchema => "DROP SCHEMA",
+            DeniedStatement::DropTable => "DROP TABLE",
+            DeniedStatement::Truncate => "TRUNCATE",
+            DeniedStatement::GrantToPublic => "GRANT ... TO PUBLIC",
+            DeniedStatement::AlterSystem => "ALTER SYSTEM",
+        }
+    }
+
+    fn matches(self, stmt: &NodeEnum) -> bool {
+        match (self, stmt) {
+            (DeniedStatement::DropDatabase, NodeEnum::DropdbStmt(_)) => true,
+            // ObjectSchema
+            (DeniedStatement::DropSchema, NodeEnum::DropStmt(n)) => n.remove_type == 37,
+            // ObjectTable
+            (DeniedStatement::DropTable, NodeEnum::DropStmt(n)) => n.remove_type == 42,
+            (DeniedStatement::Truncate, NodeEnum::TruncateStmt(_)) => true,
+            (DeniedStatement::GrantToPublic, NodeEnum::GrantStmt(n)) => {
+                n.is_grant && n.grantees.iter().any(is_public)
+            }
+            (DeniedStatement::GrantToPublic, NodeEnum::AlterDefaultPrivilegesStmt(n)) => n
+                .action
+                .as_ref()
+                .is_some_and(|grant| grant.is_grant && grant.grantees.iter().any(is_public)),
+            (DeniedStatement::GrantToPublic, NodeEnum::GrantRoleStmt(n)) => {
+                n.is_grant && n.grantee_roles.iter().any(is_public)
+            }
+            (DeniedStatement::AlterSystem, NodeEnum::AlterSystemStmt(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Returns true if `role` is `PUBLIC`
+fn is_public(role: &Node) -> bool {
+    // RolespecPublic
+    matches!(&role.node, Some(NodeEnum::RoleSpec(r)) if r.roletype == 5)
+}
+
+/// The statements that a project allows
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Policy {
+    pub denied: Vec<DeniedStatement>,
+    /// The patterns of the tables whose indexes must be created concurrently
+    pub concurrent_index_tables: Vec<String>,
+}
+
+impl Policy {
+    pub fn is_empty(&self) -> bool {
+        self.denied.is_empty() && self.concurrent_index_tables.is_empty()
+    }
+}
+
+/// Reports the statements of a file that `policy` denies
+pub fn check_policy(stmts: &[RawStmt], policy: &Policy) -> Vec<LintDiagnostic> {
+    let mut created = HashSet::new();
+    let mut diagnostics = Vec::new();
+    for stmt in stmts {
+        let message = match &stmt.stmt {
+            NodeEnum::IndexStmt(n) if !n.concurrent => n
+                .relation
+                .as_ref()
+                .map(qualified_name)
+                .filter(|name| !created.contains(name))
+                .filter(|name| {
+                    policy
+                        .concurrent_index_tables
+                        .iter()
+                        .any(|pattern| matches_table(pattern, name))
+                })
+                .map(|name| {
+                    format!(
+                        "the policy requires that indexes on {} are created with CONCURRENTLY",
+                        name
+                    )
+                }),
+            stmt => policy
+                .denied
+                .iter()
+                .find(|denied| denied.matches(stmt))
+                .map(|denied| format!("{} is denied by the policy", denied.label())),
+        };
+        if let NodeEnum::CreateStmt(n) = &stmt.stmt {
+            created.extend(n.relation.as_ref().map(qualified_name));
+        }
+        diagnostics.extend(message.map(|message| LintDiagnostic {
+            rule: POLICY_VIOLATION,
+            message,
+            severity: Severity::Error,
+            range: stmt.range,
+        }));
+    }
+    diagnostics
+}
+
+/// Returns true if `pattern` matches the table `qualified_name`
+fn matches_table(pattern: &str, qualified_name: &str) -> bool {
+    if pattern.contains('.') {
+        matches_pattern(pattern, qualified_name)
+    } else {
+        let name = qualified_name
+            .split_once('.')
+            .map_or(qualified_name, |(_, name)| name);
+        matches_pattern(pattern, name)
+    }
+}
+
+/// Returns true if `text` matches `pattern`, in which `*` matches any characters
+fn matches_pattern(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => {
+            let Some(text) = text.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=text.len())
+                .filter(|&idx| text.is_char_boundary(idx))
+                .any(|idx| matches_pattern(rest, &text[idx..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    fn messages(input: &str, policy: &Policy) -> Vec<String> {
+        check_policy(&parse_source(input).stmts, policy)
+            .into_iter()
+            .map(|d| d.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_denied_statements() {
+        let policy = Policy {
+            denied: vec![
+                DeniedStatement::DropDatabase,
+                DeniedStatement::GrantToPublic,
+            ],
+            ..Policy::default()
+        };
+        assert_eq!(
+            messages(
+                "drop database app;
+grant select on contact to public;
+grant select on contact to reporting;
+revoke select on contact from public;
+drop table contact;
+alter default privileges in schema app grant select on tables to public;
+alter default privileges in schema app revoke select on tables from public;
+grant reporting to public;
+grant reporting to analyst;",
+                &policy
+            ),
+            [
+                "DROP DATABASE is denied by the policy",
+                "GRANT ... TO PUBLIC is denied by the policy",
+                "GRANT ... TO PUBLIC is denied by the policy",
+                "GRANT ... TO PUBLIC is denied by the policy"
+            ]
+        );
+        assert_eq!(
+            DeniedStatement::from_name("grant-to-public"),
+            Some(DeniedStatement::GrantToPublic)
+        );
+    }
+
+    #[test]
+    fn test_concurrent_index_tables() {
+        let policy = Policy {
+            concurrent_index_tables: vec!["events_*".to_string(), "billing.invoice".to_string()],
+            ..Policy::default()
+        };
+        assert_eq!(
+            messages(
+                "create index on events_2024 (created_at);
+create index concurrently on events_2025 (created_at);
+create index on billing.invoice (customer_id);
+create index on invoice (customer_id);
+create table events_archive (created_at timestamptz);
+create index on events_archive (created_at);",
+                &policy
+            ),
+            [
+                "the policy requires that indexes on public.events_2024 are created with CONCURRENTLY",
+                "the policy requires that indexes on billing.invoice are created with CONCURRENTLY"
+            ]
+        );
+        assert!(matches_pattern("a*c*", "abcde"));
+        assert!(!matches_pattern("a*c", "abcde"));
+    }
+}
@@ -0,0 +1,166 @@
+//! Checks that the query files of read-only directories only read.
+//!
+//! Codebases that route reads to replicas keep the queries that may run there in their own
+//! directories. A replica refuses every statement that writes, which includes not only `INSERT`,
+//! `UPDATE` and schema changes but also data-modifying `WITH` queries, `SELECT INTO`, row locks
+//! with `FOR UPDATE` and calls of `nextval`. `EXPLAIN` only writes if it runs the statement with
+//! `ANALYZE`. The statements that write are reported for files that are configured to only read.
+
+use parser::{RawStmt, SyntaxKind};
+use pg_query::NodeEnum;
+
+use crate::lint::{LintDiagnostic, Severity};
+use crate::utils::{descendants, string_value};
+
+pub const WRITE_IN_READ_ONLY_FILE: &str = "write-in-read-only-file";
+
+/// The functions that write, although they can be called in a `SELECT`
+const WRITING_FUNCTIONS: &[&str] = &["nextval", "setval"];
+
+/// Reports the statements of a file that is configured to only read that write
+pub fn check_read_only(stmts: &[RawStmt]) -> Vec<LintDiagnostic> {
+    stmts
+        .iter()
+        .filter_map(|stmt| {
+            Some(LintDiagnostic {
+                rule: WRITE_IN_READ_ONLY_FILE,
+                message: write_reason(&stmt.stmt)?.to_string(),
+                severity: Severity::Error,
+                range: stmt.range,
+            })
+        })
+        .collect()
+}
+
+/// Returns the message about why `stmt` writes, or `None` if it only reads
+fn write_reason(stmt: &NodeEnum) -> Option<&'static str> {
+    match stmt {
+        NodeEnum::InsertStmt(_)
+        | NodeEnum::UpdateStmt(_)
+        | NodeEnum::DeleteStmt(_)
+        | NodeEnum::MergeStmt(_) => Some(
+            "the statement modifies rows, \
+             but the queries of read-only directories may run on a replica, which refuses writes",
+        ),
+        NodeEnum::ExplainStmt(n) => {
+            // `ANALYZE` without a value is on, and `ANALYZE false` or `ANALYZE off` is off
+            let analyze = n.options.iter().any(|option| match &option.node {
+                Some(NodeEnum::DefElem(d)) if d.defname == "analyze" => {
+                    match d.arg.as_ref().and_then(|arg| arg.node.as_ref()) {
+                        None => true,
+                        Some(NodeEnum::Integer(i)) => i.ival != 0,
+                        Some(NodeEnum::String(s)) => {
+                            !matches!(s.sval.to_lowercase().as_str(), "false" | "off" | "no" | "0")
+                        }
+                        Some(_) => true,
+                    }
+                }
+                _ => false,
+            });
+            if !analyze {
+                return None;
+            }
+            write_reason(n.query.as_ref()?.node.as_ref()?)
+        }
+        NodeEnum::CopyStmt(n) if n.is_from => Some(
+            "COPY FROM modifies rows, \
+             but the queries of read-only directories may run on a replica, which refuses writes",
+        ),
+        NodeEnum::SelectStmt(_)
+        | NodeEnum::CopyStmt(_)
+        | NodeEnum::DeclareCursorStmt(_)
+        | NodeEnum::PrepareStmt(_)
+        | NodeEnum::ExecuteStmt(_)
+        | NodeEnum::FetchStmt(_)
+        | NodeEnum::ClosePortalStmt(_)
+        | NodeEnum::DeallocateStmt(_)
+        | NodeEnum::VariableSetStmt(_)
+        | NodeEnum::VariableShowStmt(_)
+        | NodeEnum::TransactionStmt(_)
+        | NodeEnum::DiscardStmt(_) => query_write_reason(stmt),
+        _ if SyntaxKind::from(stmt).is_ddl_stmt() => Some(
+            "the statement changes the schema, \
+             but the queries of read-only directories may run on a replica, which refuses writes",
+        ),
+        _ => Some(
+            "the statement writes, \
+             but the queries of read-only directories may run on a replica, which refuses writes",
+        ),
+    }
+}
+
+/// Returns the message about why a query within `stmt` writes, if one does
+fn query_write_reason(stmt: &NodeEnum) -> Option<&'static str> {
+    descendants(stmt).iter().find_map(|node| match node {
+        NodeEnum::InsertStmt(_)
+        | NodeEnum::UpdateStmt(_)
+        | NodeEnum::DeleteStmt(_)
+        | NodeEnum::MergeStmt(_) => Some(
+            "the data-modifying WITH query modifies rows, \
+             but the queries of read-only directories may run on a replica, which refuses writes",
+        ),
+        NodeEnum::SelectStmt(n) if n.into_clause.is_some() => Some(
+            "SELECT INTO creates a table, \
+             but the queries of read-only directories may run on a replica, which refuses writes",
+        ),
+        NodeEnum::SelectStmt(n) if !n.locking_clause.is_empty() => Some(
+            "the locking clause locks rows, \
+             but the queries of read-only directories may run on a replica, which refuses writes",
+        ),
+        NodeEnum::FuncCall(f) => {
+            let name = f.funcname.last().and_then(string_value)?.to_lowercase();
+            WRITING_FUNCTIONS.contains(&name.as_str()).then_some(
+                "the call of a sequence function changes the sequence, \
+             but the queries of read-only directories may run on a replica, which refuses writes",
+            )
+        }
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    fn writes(input: &str) -> bool {
+        !check_read_only(&parse_source(input).stmts).is_empty()
+    }
+
+    #[test]
+    fn test_write_reason() {
+        assert!(!writes(
+            "select * from contact where id = $1; explain select 1; show search_path;"
+        ));
+        assert!(writes("explain (analyze) delete from contact;"));
+        assert!(!writes(
+            "explain (analyze false, costs off) delete from contact;"
+        ));
+        assert!(writes("explain (analyze on) delete from contact;"));
+        assert!(writes(
+            "with moved as (delete from inbox returning *) select * from moved;"
+        ));
+        assert!(writes("select * from contact for update skip locked;"));
+        assert!(writes("select nextval('contact_id_seq');"));
+        assert!(!writes("copy (select * from contact) to stdout;"));
+    }
+
+    #[test]
+    fn test_check_read_only() {
+        let input =
+            "select * from contact;\nselect * into archive from contact;\ncreate table t ();";
+        let parse = parse_source(input);
+        let diagnostics = check_read_only(&parse.stmts);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].range, parse.stmts[1].range);
+        assert!(diagnostics[0].message.starts_with(
+            "SELECT INTO creates a table, \
+             but the queries of read-only directories may run on a replica, which refuses writes"
+        ));
+        assert!(diagnostics[1].message.starts_with(
+            "the statement changes the schema, \
+             but the queries of read-only directories may run on a replica, which refuses writes"
+        ));
+    }
+}
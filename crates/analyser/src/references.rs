@@ -0,0 +1,499 @@
+//! The occurrences of tables, columns, functions, common table expressions and aliases.
+//!
+//! Tables and columns are identified like the symbols of [`moniker`](crate::moniker), and
+//! functions by their name and the schema that qualifies them, if any. Since the search path is
+//! not known, a function that is called without a schema may be any function of that name.
+//! Common table expressions and aliases are only known within the statement that declares them.
+//!
+//! Every occurrence is either the declaration of the object, e.g. the name in `CREATE TABLE`, a
+//! read, or a write. Tables are written by `INSERT`, `UPDATE`, `DELETE`, `TRUNCATE` and
+//! `ALTER TABLE`, and columns by the column list of `INSERT` and the `SET` clause of `UPDATE`.
+//! The objects of `COMMENT ON` and `SECURITY LABEL` are read.
+
+use std::collections::{BTreeSet, HashMap};
+
+use cstree::syntax::{ResolvedNode, ResolvedToken};
+use cstree::text::{TextRange, TextSize};
+use parser::{RawStmt, SyntaxKind};
+use pg_query::NodeEnum;
+
+use crate::comments::commented_objects;
+use crate::moniker::{qualified_name, symbols, SymbolKind, DEFAULT_SCHEMA};
+use crate::rename::normalize_identifier;
+use crate::utils::descendants;
+
+/// An object that names in the source text can refer to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// A table or view, by its qualified name
+    Relation(String),
+    /// A column, by its qualified name, e.g. `public.contact.name`
+    Column(String),
+    Function {
+        schema: Option<String>,
+        name: String,
+    },
+    /// A common table expression or alias of the statement at `stmt`
+    Local { name: String, stmt: TextRange },
+}
+
+impl Target {
+    /// Returns true if the target is only known within a single statement
+    pub fn is_local(&self) -> bool {
+        matches!(self, Target::Local { .. })
+    }
+
+    /// Returns true if `other` may be the same object
+    pub fn matches(&self, other: &Target) -> bool {
+        match (self, other) {
+            (
+                Target::Function { schema, name },
+                Target::Function {
+                    schema: other_schema,
+                    name: other_name,
+                },
+            ) => {
+                name == other_name
+                    && (schema.is_none() || other_schema.is_none() || schema == other_schema)
+            }
+            _ => self == other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Declaration,
+    Read,
+    Write,
+}
+
+/// A name in the source text that refers to a target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Occurrence {
+    pub target: Target,
+    pub range: TextRange,
+    pub access: Access,
+}
+
+/// Returns the occurrence at `offset`, if any
+pub fn occurrence_at(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmts: &[RawStmt],
+    offset: TextSize,
+) -> Option<Occurrence> {
+    occurrences(cst, stmts)
+        .into_iter()
+        .find(|o| o.range.contains_inclusive(offset))
+}
+
+/// Returns the occurrences of the target at `offset` within the statement at `offset`, in the
+/// order of the source text
+pub fn statement_occurrences(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmts: &[RawStmt],
+    offset: TextSize,
+) -> Vec<Occurrence> {
+    let Some(stmt) = stmts
+        .iter()
+        .find(|stmt| stmt.range.contains_inclusive(offset))
+    else {
+        return Vec::new();
+    };
+    let occurrences = occurrences(cst, stmts)
+        .into_iter()
+        .filter(|o| stmt.range.contains_range(o.range))
+        .collect::<Vec<_>>();
+    let Some(target) = occurrences
+        .iter()
+        .find(|o| o.range.contains_inclusive(offset))
+        .map(|o| o.target.clone())
+    else {
+        return Vec::new();
+    };
+    occurrences
+        .into_iter()
+        .filter(|o| o.target.matches(&target))
+        .collect()
+}
+
+/// Returns the occurrences of all targets, in the order of the source text
+pub fn occurrences(cst: &ResolvedNode<SyntaxKind>, stmts: &[RawStmt]) -> Vec<Occurrence> {
+    let symbols = symbols(cst, stmts).into_iter().collect::<HashMap<_, _>>();
+    let commented = commented_objects(cst, stmts)
+        .into_iter()
+        .filter_map(|object| Some((object.range, object.target()?)))
+        .collect::<HashMap<_, _>>();
+    let locals = stmts
+        .iter()
+        .map(|stmt| Locals::of(&stmt.stmt))
+        .collect::<Vec<_>>();
+    let tokens = cst
+        .descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| !token.kind().is_trivia())
+        .collect::<Vec<_>>();
+
+    let mut occurrences = Vec::new();
+    // the statements whose function has been declared
+    let mut declared = BTreeSet::new();
+    // the statements and the tokens are both in the order of the source text, so the statement of
+    // a token is found by moving on from the statement of the previous one
+    let mut stmt_idx = 0;
+    for (idx, token) in tokens.iter().enumerate() {
+        let kind = token.kind();
+        let is_name = kind == SyntaxKind::Ident
+            || kind
+                .keyword_category()
+                .is_some_and(|c| c.is_type_func_name());
+        if !is_name {
+            continue;
+        }
+        let start = token.text_range().start();
+        while stmts
+            .get(stmt_idx)
+            .is_some_and(|stmt| stmt.range.end() < start)
+        {
+            stmt_idx += 1;
+        }
+        let Some(stmt) = stmts
+            .get(stmt_idx)
+            .filter(|stmt| stmt.range.contains_inclusive(start))
+        else {
+            continue;
+        };
+        let kind_at = |idx: Option<usize>| idx.and_then(|idx| tokens.get(idx)).map(|t| t.kind());
+        let previous = kind_at(idx.checked_sub(1));
+        let next = kind_at(Some(idx + 1));
+        let range = token.text_range();
+        let name = normalize_identifier(token.text());
+
+        let local = (kind == SyntaxKind::Ident)
+            .then(|| locals[stmt_idx].access(token, &name, previous, next))
+            .flatten();
+        let (target, access) = if let Some(access) = local {
+            let target = Target::Local {
+                name,
+                stmt: stmt.range,
+            };
+            (target, access)
+        } else if let Some(symbol) = symbols.get(&range) {
+            let access = if symbol.is_definition {
+                Access::Declaration
+            } else if symbol.kind == SymbolKind::Table && is_written_table(token) {
+                Access::Write
+            } else {
+                Access::Read
+            };
+            let target = match symbol.kind {
+                SymbolKind::Table => Target::Relation(symbol.identifier.clone()),
+                SymbolKind::Column => Target::Column(symbol.identifier.clone()),
+            };
+            (target, access)
+        } else if let Some(target) = commented.get(&range) {
+            (target.clone(), Access::Read)
+        } else if let Some(column) = written_column(token, &stmt.stmt, &name, previous) {
+            (Target::Column(column), Access::Write)
+        } else if next == Some(SyntaxKind::Ascii40) {
+            let schema = match (previous, idx.checked_sub(2).map(|idx| tokens[idx])) {
+                (Some(SyntaxKind::Ascii46), Some(schema)) => {
+                    Some(normalize_identifier(schema.text()))
+                }
+                _ => None,
+            };
+            let is_call = token
+                .parent()
+                .ancestors()
+                .any(|node| node.kind() == SyntaxKind::FuncCall);
+            if is_call {
+                (Target::Function { schema, name }, Access::Read)
+            } else if matches!(stmt.stmt, NodeEnum::CreateFunctionStmt(_))
+                && declared.insert(stmt.range.start())
+            {
+                let schema = schema.unwrap_or_else(|| DEFAULT_SCHEMA.to_string());
+                let target = Target::Function {
+                    schema: Some(schema),
+                    name,
+                };
+                (target, Access::Declaration)
+            } else {
+                continue;
+            }
+        } else {
+            continue;
+        };
+        occurrences.push(Occurrence {
+            target,
+            range,
+            access,
+        });
+    }
+    occurrences
+}
+
+/// The names of the common table expressions and aliases that a statement declares
+struct Locals {
+    ctes: Vec<String>,
+    aliases: Vec<String>,
+}
+
+impl Locals {
+    fn of(stmt: &NodeEnum) -> Locals {
+        let mut locals = Locals {
+            ctes: Vec::new(),
+            aliases: Vec::new(),
+        };
+        for node in descendants(stmt) {
+            let alias = match node {
+                NodeEnum::CommonTableExpr(n) => {
+                    locals.ctes.push(n.ctename);
+                    continue;
+                }
+                NodeEnum::RangeVar(n) => n.alias,
+                NodeEnum::RangeSubselect(n) => n.alias,
+                NodeEnum::RangeFunction(n) => n.alias,
+                _ => continue,
+            };
+            locals.aliases.extend(alias.map(|a| a.aliasname));
+        }
+        locals
+    }
+
+    /// Returns how `token` accesses the common table expression or alias `name`, or `None` if it
+    /// does not refer to one
+    fn access(
+        &self,
+        token: &ResolvedToken<SyntaxKind>,
+        name: &str,
+        previous: Option<SyntaxKind>,
+        next: Option<SyntaxKind>,
+    ) -> Option<Access> {
+        let is_cte = self.ctes.iter().any(|cte| cte == name);
+        let is_alias = self.aliases.iter().any(|alias| alias == name);
+        if !is_cte && !is_alias {
+            return None;
+        }
+
+        let context = token.parent().ancestors().map(|n| n.kind()).find(|kind| {
+            matches!(
+                kind,
+                SyntaxKind::CommonTableExpr
+                    | SyntaxKind::RangeVar
+                    | SyntaxKind::RangeSubselect
+                    | SyntaxKind::RangeFunction
+                    | SyntaxKind::ColumnRef
+            )
+        });
+        let is_qualified = previous == Some(SyntaxKind::Ascii46);
+        match context {
+            // the name of `name AS (...)` or `name (columns) AS (...)`
+            Some(SyntaxKind::CommonTableExpr)
+                if is_cte && matches!(next, Some(SyntaxKind::As | SyntaxKind::Ascii40)) =>
+            {
+                Some(Access::Declaration)
+            }
+            // the alias of `table alias`, `table AS alias` or `(subquery) alias`
+            Some(SyntaxKind::RangeVar | SyntaxKind::RangeSubselect | SyntaxKind::RangeFunction)
+                if is_alias
+                    && matches!(
+                        previous,
+                        Some(SyntaxKind::Ident | SyntaxKind::As | SyntaxKind::Ascii41)
+                    ) =>
+            {
+                Some(Access::Declaration)
+            }
+            Some(SyntaxKind::RangeVar)
+                if is_cte && !is_qualified && next != Some(SyntaxKind::Ascii46) =>
+            {
+                Some(Access::Read)
+            }
+            // the qualifier of a column
+            Some(context)
+                if context != SyntaxKind::RangeVar
+                    && !is_qualified
+                    && next == Some(SyntaxKind::Ascii46) =>
+            {
+                Some(Access::Read)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Returns true if `token` names the table that an `INSERT`, `UPDATE`, `DELETE`, `TRUNCATE` or
+/// `ALTER TABLE` statement writes to
+fn is_written_table(token: &ResolvedToken<SyntaxKind>) -> bool {
+    let Some(relation) = token
+        .parent()
+        .ancestors()
+        .find(|node| node.kind() == SyntaxKind::RangeVar)
+    else {
+        return false;
+    };
+    let Some(stmt) = relation.parent() else {
+        return false;
+    };
+    match stmt.kind() {
+        SyntaxKind::TruncateStmt => true,
+        SyntaxKind::InsertStmt
+        | SyntaxKind::UpdateStmt
+        | SyntaxKind::DeleteStmt
+        | SyntaxKind::AlterTableStmt => stmt
+            .children()
+            .find(|child| child.kind() == SyntaxKind::RangeVar)
+            .is_some_and(|target| target.text_range() == relation.text_range()),
+        _ => false,
+    }
+}
+
+/// Returns the identifier of the column if `token` names one in the column list of an `INSERT` or
+/// the `SET` clause of an `UPDATE`
+fn written_column(
+    token: &ResolvedToken<SyntaxKind>,
+    stmt: &NodeEnum,
+    name: &str,
+    previous: Option<SyntaxKind>,
+) -> Option<String> {
+    let target = token.parent();
+    if target.kind() != SyntaxKind::ResTarget
+        || !matches!(
+            target.parent()?.kind(),
+            SyntaxKind::InsertStmt | SyntaxKind::UpdateStmt
+        )
+        // `RETURNING value AS label` is not a column
+        || !matches!(
+            previous,
+            Some(SyntaxKind::Ascii40 | SyntaxKind::Ascii44 | SyntaxKind::Set)
+        )
+    {
+        return None;
+    }
+    let relation = match stmt {
+        NodeEnum::InsertStmt(n) => n.relation.as_ref()?,
+        NodeEnum::UpdateStmt(n) => n.relation.as_ref()?,
+        _ => return None,
+    };
+    Some(format!("{}.{}", qualified_name(relation), name))
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    fn accesses(input: &str, target: &Target) -> Vec<(String, Access)> {
+        let parse = parse_source(input);
+        occurrences(&parse.cst, &parse.stmts)
+            .into_iter()
+            .filter(|o| o.target.matches(target))
+            .map(|o| (input[o.range].to_string(), o.access))
+            .collect()
+    }
+
+    #[test]
+    fn test_tables_and_columns() {
+        let input = "create table contact (id int, email text);
+            insert into contact (email) values ('x');
+            update contact set email = lower(email) where id = 1;
+            select c.email from contact c;
+            comment on column contact.email is 'primary address';";
+        assert_eq!(
+            accesses(input, &Target::Relation("public.contact".to_string())),
+            vec![
+                ("contact".to_string(), Access::Declaration),
+                ("contact".to_string(), Access::Write),
+                ("contact".to_string(), Access::Write),
+                ("contact".to_string(), Access::Read),
+            ]
+        );
+        assert_eq!(
+            accesses(input, &Target::Column("public.contact.email".to_string()))
+                .into_iter()
+                .map(|(_, access)| access)
+                .collect::<Vec<_>>(),
+            vec![
+                Access::Declaration,
+                Access::Write,
+                Access::Write,
+                Access::Read,
+                Access::Read,
+                Access::Read,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        let input =
+            "create function app.full_name(c app.contact) returns text language sql as 'select 1';
+            select app.full_name(c), full_name(c), lower(name) from app.contact c;";
+        let function = Target::Function {
+            schema: Some("app".to_string()),
+            name: "full_name".to_string(),
+        };
+        assert_eq!(
+            accesses(input, &function),
+            vec![
+                ("full_name".to_string(), Access::Declaration),
+                ("full_name".to_string(), Access::Read),
+                ("full_name".to_string(), Access::Read),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_statement_occurrences() {
+        let input = "update contact set email = lower(email) where id = 1;
+            select email from contact;";
+        let parse = parse_source(input);
+        let offset = TextSize::from(input.find("email)").unwrap() as u32);
+        assert_eq!(
+            statement_occurrences(&parse.cst, &parse.stmts, offset)
+                .into_iter()
+                .map(|o| (input[o.range].to_string(), o.access))
+                .collect::<Vec<_>>(),
+            vec![
+                ("email".to_string(), Access::Write),
+                ("email".to_string(), Access::Read),
+            ]
+        );
+
+        let offset = TextSize::from(input.find("1;").unwrap() as u32);
+        assert!(statement_occurrences(&parse.cst, &parse.stmts, offset).is_empty());
+    }
+
+    #[test]
+    fn test_locals() {
+        let input = "with recent as (select id from orders) select r.id from recent r;";
+        let parse = parse_source(input);
+        let offset = TextSize::from(input.rfind("recent").unwrap() as u32);
+        let cte = occurrence_at(&parse.cst, &parse.stmts, offset).unwrap();
+        assert_eq!(
+            cte.target,
+            Target::Local {
+                name: "recent".to_string(),
+                stmt: parse.stmts[0].range
+            }
+        );
+        assert_eq!(
+            accesses(input, &cte.target)
+                .into_iter()
+                .map(|(_, access)| access)
+                .collect::<Vec<_>>(),
+            vec![Access::Declaration, Access::Read]
+        );
+
+        let alias = Target::Local {
+            name: "r".to_string(),
+            stmt: parse.stmts[0].range,
+        };
+        assert_eq!(
+            accesses(input, &alias)
+                .into_iter()
+                .map(|(_, access)| access)
+                .collect::<Vec<_>>(),
+            vec![Access::Read, Access::Declaration]
+        );
+    }
+}
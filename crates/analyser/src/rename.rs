@@ -0,0 +1,197 @@
+//! Renaming of identifiers.
+//!
+//! The occurrences of the renamed object are found by [`references`](crate::references), so that
+//! only names that refer to the same table, column, function, common table expression or alias are
+//! renamed, not every identifier that happens to be spelled the same. Unquoted identifiers are
+//! folded to lowercase the way Postgres does, so `Contact` and `contact` are the same name while
+//! `"Contact"` is a different one.
+//!
+//! Renaming a table or column that is defined in the workspace also changes the schema of
+//! databases that the definition has already been applied to. For those,
+//! [`rename_table_migration`] and [`rename_column_migration`] return the `ALTER TABLE` statement
+//! that performs the same rename.
+
+use cstree::syntax::ResolvedNode;
+use cstree::text::{TextRange, TextSize};
+use parser::make::quote_ident;
+use parser::{RawStmt, SyntaxKind};
+use pg_query::protobuf::{Node, RangeVar};
+use pg_query::NodeEnum;
+
+use crate::moniker::qualified_name;
+
+/// An identifier in the source text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identifier {
+    pub range: TextRange,
+    /// The name the identifier refers to, i.e. without quotes or folded to lowercase
+    pub name: String,
+}
+
+/// Returns the identifier at `offset`, if any
+pub fn identifier_at(cst: &ResolvedNode<SyntaxKind>, offset: TextSize) -> Option<Identifier> {
+    cst.descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| token.kind() == SyntaxKind::Ident)
+        .find(|token| token.text_range().contains_inclusive(offset))
+        .map(|token| Identifier {
+            range: token.text_range(),
+            name: normalize_identifier(token.text()),
+        })
+}
+
+/// Returns the name an identifier refers to
+pub fn normalize_identifier(text: &str) -> String {
+    match text
+        .strip_prefix('"')
+        .and_then(|quoted| quoted.strip_suffix('"'))
+    {
+        Some(quoted) => quoted.replace("\"\"", "\""),
+        None => text.to_lowercase(),
+    }
+}
+
+/// Returns the statement that renames the table `identifier`, e.g. `public.contact`, to
+/// `new_name` if it is defined with `CREATE TABLE` in `stmts`
+pub fn rename_table_migration(
+    stmts: &[RawStmt],
+    identifier: &str,
+    new_name: &str,
+) -> Option<String> {
+    stmts.iter().find_map(|stmt| {
+        let NodeEnum::CreateStmt(create) = &stmt.stmt else {
+            return None;
+        };
+        let relation = create.relation.as_ref()?;
+        (qualified_name(relation) == identifier).then(|| {
+            format!(
+                "ALTER TABLE {} RENAME TO {};",
+                table_sql(relation),
+                quote_ident(new_name)
+            )
+        })
+    })
+}
+
+/// Returns the statement that renames the column `identifier`, e.g. `public.contact.name`, to
+/// `new_name` if its table is created or the column is added in `stmts`
+pub fn rename_column_migration(
+    stmts: &[RawStmt],
+    identifier: &str,
+    new_name: &str,
+) -> Option<String> {
+    let (table, column) = identifier.rsplit_once('.')?;
+    stmts.iter().find_map(|stmt| {
+        let (relation, columns) = match &stmt.stmt {
+            NodeEnum::CreateStmt(n) => (n.relation.as_ref()?, column_defs(&n.table_elts)),
+            // AtAddColumn
+            NodeEnum::AlterTableStmt(n) => (
+                n.relation.as_ref()?,
+                n.cmds
+                    .iter()
+                    .filter_map(|cmd| match cmd.node.as_ref()? {
+                        NodeEnum::AlterTableCmd(cmd) if cmd.subtype == 1 => cmd.def.as_deref(),
+                        _ => None,
+                    })
+                    .filter_map(|def| match def.node.as_ref()? {
+                        NodeEnum::ColumnDef(c) => Some(c.colname.as_str()),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            _ => return None,
+        };
+        (qualified_name(relation) == table && columns.contains(&column)).then(|| {
+            format!(
+                "ALTER TABLE {} RENAME COLUMN {} TO {};",
+                table_sql(relation),
+                quote_ident(column),
+                quote_ident(new_name)
+            )
+        })
+    })
+}
+
+/// Returns the names of the columns that `elements` of `CREATE TABLE` define
+fn column_defs(elements: &[Node]) -> Vec<&str> {
+    elements
+        .iter()
+        .filter_map(|element| match element.node.as_ref()? {
+            NodeEnum::ColumnDef(c) => Some(c.colname.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the name of `relation` as written in SQL
+fn table_sql(relation: &RangeVar) -> String {
+    if relation.schemaname.is_empty() {
+        quote_ident(&relation.relname)
+    } else {
+        format!(
+            "{}.{}",
+            quote_ident(&relation.schemaname),
+            quote_ident(&relation.relname)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    #[test]
+    fn test_identifier_at() {
+        let input = "create table Contact (id int);\nselect \"Contact\".id from \"Contact\";";
+        let parse = parse_source(input);
+
+        let ident = identifier_at(&parse.cst, TextSize::from(15)).unwrap();
+        assert_eq!(ident.name, "contact");
+        assert_eq!(&input[ident.range], "Contact");
+
+        let offset = TextSize::from(input.find("\"Contact\"").unwrap() as u32);
+        assert_eq!(identifier_at(&parse.cst, offset).unwrap().name, "Contact");
+        assert_eq!(identifier_at(&parse.cst, TextSize::from(2)), None);
+    }
+
+    #[test]
+    fn test_rename_migration() {
+        let parse = parse_source(
+            "create table app.contact (id int, name text);
+            create table orders (contact int);
+            alter table orders add column total numeric;",
+        );
+        assert_eq!(
+            rename_table_migration(&parse.stmts, "app.contact", "Person").as_deref(),
+            Some("ALTER TABLE app.contact RENAME TO \"Person\";")
+        );
+        assert_eq!(
+            rename_table_migration(&parse.stmts, "public.orders", "purchase").as_deref(),
+            Some("ALTER TABLE orders RENAME TO purchase;")
+        );
+        // the column contact of orders is not the table app.contact
+        assert_eq!(
+            rename_table_migration(&parse.stmts, "public.contact", "person"),
+            None
+        );
+
+        assert_eq!(
+            rename_column_migration(&parse.stmts, "public.orders.contact", "person").as_deref(),
+            Some("ALTER TABLE orders RENAME COLUMN contact TO person;")
+        );
+        assert_eq!(
+            rename_column_migration(&parse.stmts, "public.orders.total", "amount").as_deref(),
+            Some("ALTER TABLE orders RENAME COLUMN total TO amount;")
+        );
+        assert_eq!(
+            rename_column_migration(&parse.stmts, "app.contact.id", "key").as_deref(),
+            Some("ALTER TABLE app.contact RENAME COLUMN id TO key;")
+        );
+        assert_eq!(
+            rename_column_migration(&parse.stmts, "public.orders.id", "key"),
+            None
+        );
+    }
+}
@@ -0,0 +1,766 @@
+//! Pre-flight checks for restore scripts, e.g. plain-text dumps that have been edited by hand.
+//!
+//! A script passes if
+//! - its `SET` preamble configures the session the way a restore needs it, e.g. without a
+//!   `statement_timeout`,
+//! - every role referenced by ownership and privilege statements exists in the target database or
+//!   is created earlier in the script, and
+//! - every object is created before the first statement that uses it.
+//!
+//! Dependencies are derived from the names that appear in a statement: relations, types, schemas
+//! and called functions, as well as the objects whose owner is changed and the tables that own
+//! sequences with `OWNED BY`. Function bodies are not inspected, because they are not parsed.
+//! Unqualified names are resolved in the first schema of the `search_path` that is in effect,
+//! which is tracked through `SET search_path` and `set_config('search_path', ...)`.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+
+use cstree::text::{TextRange, TextSize};
+use parser::RawStmt;
+use pg_query::protobuf::a_const::Val;
+use pg_query::protobuf::{Node, VariableSetStmt};
+use pg_query::NodeEnum;
+
+use crate::lint::{LintDiagnostic, Severity};
+use crate::utils::{descendants, sequence_owned_by, string_value};
+
+/// Query to load the names of all roles of a live database
+pub const ROLES_QUERY: &str = "select rolname from pg_catalog.pg_roles order by rolname";
+
+const SET_PREAMBLE: &str = "restore-set-preamble";
+const UNKNOWN_ROLE: &str = "restore-unknown-role";
+const DEPENDENCY_ORDER: &str = "restore-dependency-order";
+
+/// Settings that a restore relies on, together with their required value and what goes wrong
+/// with any other value
+const REQUIRED_SETTINGS: &[(&str, &str, &str)] = &[
+    (
+        "statement_timeout",
+        "0",
+        "long running statements such as index builds would be cancelled",
+    ),
+    (
+        "lock_timeout",
+        "0",
+        "statements would fail while other sessions hold locks",
+    ),
+    (
+        "idle_in_transaction_session_timeout",
+        "0",
+        "a single transaction restore would be terminated while waiting for input",
+    ),
+    (
+        "check_function_bodies",
+        "off",
+        "functions that use objects created later in the script could not be created",
+    ),
+    (
+        "row_security",
+        "off",
+        "data in tables with row level security could be restored incompletely",
+    ),
+];
+
+/// Settings that change how the statements after them are read. They belong into the preamble.
+const PREAMBLE_ONLY_SETTINGS: &[&str] = &["client_encoding", "standard_conforming_strings"];
+
+/// Configures the checks of a restore script
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RestoreConfig {
+    /// The roles of the target database, as loaded with [`ROLES_QUERY`]. If `None`, roles are only
+    /// checked for being created before they are used.
+    pub existing_roles: Option<BTreeSet<String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum ObjectKind {
+    Schema,
+    Relation,
+    Type,
+    Function,
+}
+
+/// A database object, identified by its kind and schema-qualified name
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct Object {
+    pub(crate) kind: ObjectKind,
+    pub(crate) name: String,
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            ObjectKind::Schema => "schema",
+            ObjectKind::Relation => "relation",
+            ObjectKind::Type => "type",
+            ObjectKind::Function => "function",
+        };
+        write!(f, "{} {}", kind, self.name)
+    }
+}
+
+/// The objects and roles a single statement creates and uses
+#[derive(Debug, Default)]
+pub(crate) struct Dependencies {
+    pub(crate) creates: Vec<Object>,
+    pub(crate) uses: Vec<(Object, Option<i32>)>,
+    pub(crate) creates_role: Option<String>,
+    pub(crate) uses_roles: Vec<(String, Option<i32>)>,
+}
+
+/// Checks the restore script `stmts` and returns all problems found
+pub fn check_restore_script(stmts: &[RawStmt], config: &RestoreConfig) -> Vec<LintDiagnostic> {
+    let mut diagnostics = check_preamble(stmts);
+    let dependencies = stmt_dependencies(stmts);
+
+    let mut created_objects = HashMap::<&Object, usize>::new();
+    let mut created_roles = HashMap::<&str, usize>::new();
+    for (idx, deps) in dependencies.iter().enumerate() {
+        for object in &deps.creates {
+            created_objects.entry(object).or_insert(idx);
+        }
+        if let Some(role) = &deps.creates_role {
+            created_roles.entry(role).or_insert(idx);
+        }
+    }
+
+    for (idx, (stmt, deps)) in stmts.iter().zip(&dependencies).enumerate() {
+        for (object, location) in &deps.uses {
+            if created_objects.get(object).is_some_and(|&i| i > idx) {
+                diagnostics.push(diagnostic(
+                    DEPENDENCY_ORDER,
+                    format!("{} is used before it is created", object),
+                    Severity::Error,
+                    stmt,
+                    *location,
+                ));
+            }
+        }
+        for (role, location) in &deps.uses_roles {
+            let message = match created_roles.get(role.as_str()) {
+                Some(&i) if i > idx => format!("role {} is used before it is created", role),
+                Some(_) => continue,
+                None => match &config.existing_roles {
+                    Some(roles) if !roles.contains(role) => {
+                        format!("role {} does not exist in the target database", role)
+                    }
+                    _ => continue,
+                },
+            };
+            diagnostics.push(diagnostic(
+                UNKNOWN_ROLE,
+                message,
+                Severity::Error,
+                stmt,
+                *location,
+            ));
+        }
+    }
+
+    diagnostics.sort_by_key(|d| d.range.start());
+    diagnostics
+}
+
+/// Returns the objects and roles created and used by each of `stmts`, with unqualified names
+/// resolved in the `search_path` that is in effect at the statement
+pub(crate) fn stmt_dependencies(stmts: &[RawStmt]) -> Vec<Dependencies> {
+    let mut search_path = SearchPath::default();
+    stmts
+        .iter()
+        .map(|stmt| {
+            search_path.update(&stmt.stmt);
+            dependencies(&stmt.stmt, &search_path)
+        })
+        .collect()
+}
+
+fn diagnostic(
+    rule: &'static str,
+    message: String,
+    severity: Severity,
+    stmt: &RawStmt,
+    location: Option<i32>,
+) -> LintDiagnostic {
+    let range = match location {
+        Some(location) if location >= 0 => {
+            TextRange::empty(stmt.range.start() + TextSize::from(location as u32))
+        }
+        _ => stmt.range,
+    };
+    LintDiagnostic {
+        rule,
+        message,
+        severity,
+        range,
+    }
+}
+
+/// Checks the `SET` statements of the script
+///
+/// The preamble consists of all `SET` statements and `set_config` calls before the first other
+/// statement.
+fn check_preamble(stmts: &[RawStmt]) -> Vec<LintDiagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut in_preamble = true;
+    let mut in_transaction = false;
+    let mut preamble_settings = HashSet::new();
+
+    for stmt in stmts {
+        match &stmt.stmt {
+            NodeEnum::VariableSetStmt(n) => {
+                let name = n.name.to_lowercase();
+                if n.is_local && !in_transaction {
+                    diagnostics.push(diagnostic(
+                        SET_PREAMBLE,
+                        format!("SET LOCAL {} outside of a transaction has no effect", name),
+                        Severity::Warning,
+                        stmt,
+                        None,
+                    ));
+                }
+                if !in_preamble && PREAMBLE_ONLY_SETTINGS.contains(&name.as_str()) {
+                    diagnostics.push(diagnostic(
+                        SET_PREAMBLE,
+                        format!("{} changes how all following statements are read and should only be set in the preamble", name),
+                        Severity::Warning,
+                        stmt,
+                        None,
+                    ));
+                }
+                if let Some((_, required, reason)) =
+                    REQUIRED_SETTINGS.iter().find(|(s, _, _)| *s == name)
+                {
+                    match setting_value(n) {
+                        Some(value) if !is_value(&value, required) => {
+                            diagnostics.push(diagnostic(
+                                SET_PREAMBLE,
+                                format!(
+                                    "{} is set to {}, but a restore needs {}: otherwise {}",
+                                    name, value, required, reason
+                                ),
+                                Severity::Warning,
+                                stmt,
+                                None,
+                            ));
+                        }
+                        _ => {}
+                    }
+                }
+                if in_preamble {
+                    preamble_settings.insert(name);
+                }
+            }
+            NodeEnum::TransactionStmt(n) => match n.kind {
+                // TransStmtBegin, TransStmtStart
+                1 | 2 => in_transaction = true,
+                // TransStmtCommit, TransStmtRollback, TransStmtPrepare
+                3 | 4 | 8 => in_transaction = false,
+                _ => {}
+            },
+            node if set_config_call(node).is_some() => {}
+            _ => in_preamble = false,
+        }
+    }
+
+    if !preamble_settings.contains("client_encoding") {
+        if let Some(first) = stmts.first() {
+            diagnostics.push(diagnostic(
+                SET_PREAMBLE,
+                "the preamble does not set client_encoding, so the script is read in the encoding of the client that restores it".to_string(),
+                Severity::Warning,
+                first,
+                None,
+            ));
+        }
+    }
+    diagnostics
+}
+
+/// Returns the value of `SET name = value` in lowercase, if it is a single constant
+fn setting_value(n: &VariableSetStmt) -> Option<String> {
+    // VarSetValue
+    if n.kind != 1 || n.args.len() != 1 {
+        return None;
+    }
+    match n.args[0].node.as_ref()? {
+        NodeEnum::AConst(c) => match c.val.as_ref()? {
+            Val::Ival(i) => Some(i.ival.to_string()),
+            Val::Fval(f) => Some(f.fval.clone()),
+            Val::Sval(s) => Some(s.sval.to_lowercase()),
+            Val::Boolval(b) => Some(b.boolval.to_string()),
+            Val::Bsval(_) => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns true if the setting `value` is equivalent to `required`, which is either `0`, `on` or
+/// `off`
+fn is_value(value: &str, required: &str) -> bool {
+    match required {
+        "on" => matches!(value, "on" | "true" | "yes" | "1"),
+        "off" => matches!(value, "off" | "false" | "no" | "0"),
+        // a timeout of zero, with or without a unit
+        "0" => value.trim_end_matches(char::is_alphabetic).trim() == "0",
+        _ => value == required,
+    }
+}
+
+/// Returns the arguments of `SELECT set_config(name, value, ...)`
+fn set_config_call(node: &NodeEnum) -> Option<(String, String)> {
+    let NodeEnum::SelectStmt(s) = node else {
+        return None;
+    };
+    if !s.from_clause.is_empty() || s.target_list.len() != 1 {
+        return None;
+    }
+    let NodeEnum::ResTarget(target) = s.target_list[0].node.as_ref()? else {
+        return None;
+    };
+    let NodeEnum::FuncCall(call) = target.val.as_ref()?.node.as_ref()? else {
+        return None;
+    };
+    if call.funcname.last().and_then(string_value) != Some("set_config") || call.args.len() < 2 {
+        return None;
+    }
+    Some((
+        string_constant(&call.args[0])?.to_string(),
+        string_constant(&call.args[1])?.to_string(),
+    ))
+}
+
+fn string_constant(node: &Node) -> Option<&str> {
+    match node.node.as_ref()? {
+        NodeEnum::AConst(c) => match c.val.as_ref()? {
+            Val::Sval(s) => Some(s.sval.as_str()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The schemas that unqualified names are looked up in, without the implicit ones such as
+/// `pg_catalog`. Objects are created in the first one.
+#[derive(Debug, Clone)]
+pub(crate) struct SearchPath(Vec<String>);
+
+impl Default for SearchPath {
+    fn default() -> Self {
+        SearchPath(vec!["public".to_string()])
+    }
+}
+
+impl SearchPath {
+    /// Applies changes of the `search_path` made by `node`
+    pub(crate) fn update(&mut self, node: &NodeEnum) {
+        let schemas = match node {
+            NodeEnum::VariableSetStmt(n) if n.name.eq_ignore_ascii_case("search_path") => {
+                match n.kind {
+                    // VarSetValue
+                    1 => n
+                        .args
+                        .iter()
+                        .filter_map(string_constant)
+                        .map(|s| s.to_string())
+                        .collect::<Vec<String>>(),
+                    // VarSetDefault, VarReset, VarResetAll
+                    2 | 5 | 6 => vec!["public".to_string()],
+                    _ => return,
+                }
+            }
+            node => match set_config_call(node) {
+                Some((name, value)) if name.eq_ignore_ascii_case("search_path") => value
+                    .split(',')
+                    .map(|s| s.trim().trim_matches('"').to_string())
+                    .collect(),
+                _ => return,
+            },
+        };
+        self.0 = schemas
+            .into_iter()
+            .filter(|s| !s.is_empty() && !matches!(s.as_str(), "$user" | "pg_catalog" | "pg_temp"))
+            .collect();
+        if self.0.is_empty() {
+            *self = SearchPath::default();
+        }
+    }
+
+    /// Returns the schemas in the order that they are searched
+    pub(crate) fn schemas(&self) -> &[String] {
+        &self.0
+    }
+
+    /// Qualifies the possibly qualified name given as its parts
+    fn qualify(&self, parts: &[&str]) -> Option<(Option<String>, String)> {
+        match parts {
+            [name] => Some((None, format!("{}.{}", self.0[0], name))),
+            [.., schema, name] => Some((Some(schema.to_string()), format!("{}.{}", schema, name))),
+            [] => None,
+        }
+    }
+}
+
+/// Collects the objects and roles created and used by the statement `node`
+fn dependencies(node: &NodeEnum, search_path: &SearchPath) -> Dependencies {
+    let mut deps = Dependencies::default();
+    if let NodeEnum::DropRoleStmt(_) = node {
+        return deps;
+    }
+
+    let object = |kind: ObjectKind, parts: &[&str]| {
+        let (schema, name) = search_path.qualify(parts)?;
+        Some((Object { kind, name }, schema))
+    };
+    let range_var_parts = |schema: &'_ str, name: &'_ str| -> Vec<String> {
+        if schema.is_empty() {
+            vec![name.to_string()]
+        } else {
+            vec![schema.to_string(), name.to_string()]
+        }
+    };
+
+    let mut creates = Vec::new();
+    match node {
+        NodeEnum::CreateSchemaStmt(n) => deps.creates.push(Object {
+            kind: ObjectKind::Schema,
+            name: n.schemaname.clone(),
+        }),
+        NodeEnum::CreateRoleStmt(n) => deps.creates_role = Some(n.role.clone()),
+        NodeEnum::CreateStmt(n) => {
+            if let Some(r) = &n.relation {
+                // every table also defines a composite type with the same name
+                creates.push((
+                    ObjectKind::Relation,
+                    range_var_parts(&r.schemaname, &r.relname),
+                ));
+                creates.push((ObjectKind::Type, range_var_parts(&r.schemaname, &r.relname)));
+            }
+        }
+        NodeEnum::CreateForeignTableStmt(n) => {
+            if let Some(r) = n.base_stmt.as_ref().and_then(|b| b.relation.as_ref()) {
+                creates.push((
+                    ObjectKind::Relation,
+                    range_var_parts(&r.schemaname, &r.relname),
+                ));
+            }
+        }
+        NodeEnum::CreateSeqStmt(n) => {
+            if let Some(r) = &n.sequence {
+                creates.push((
+                    ObjectKind::Relation,
+                    range_var_parts(&r.schemaname, &r.relname),
+                ));
+            }
+        }
+        NodeEnum::ViewStmt(n) => {
+            if let Some(r) = &n.view {
+                creates.push((
+                    ObjectKind::Relation,
+                    range_var_parts(&r.schemaname, &r.relname),
+                ));
+            }
+        }
+        NodeEnum::CreateTableAsStmt(n) => {
+            if let Some(r) = n.into.as_ref().and_then(|i| i.rel.as_ref()) {
+                creates.push((
+                    ObjectKind::Relation,
+                    range_var_parts(&r.schemaname, &r.relname),
+                ));
+            }
+        }
+        NodeEnum::CompositeTypeStmt(n) => {
+            if let Some(r) = &n.typevar {
+                creates.push((ObjectKind::Type, range_var_parts(&r.schemaname, &r.relname)));
+            }
+        }
+        NodeEnum::CreateEnumStmt(n) => creates.push((ObjectKind::Type, owned(names(&n.type_name)))),
+        NodeEnum::CreateRangeStmt(n) => {
+            creates.push((ObjectKind::Type, owned(names(&n.type_name))))
+        }
+        NodeEnum::CreateDomainStmt(n) => {
+            creates.push((ObjectKind::Type, owned(names(&n.domainname))))
+        }
+        // ObjectType
+        NodeEnum::DefineStmt(n) if n.kind == 50 => {
+            creates.push((ObjectKind::Type, owned(names(&n.defnames))))
+        }
+        NodeEnum::CreateFunctionStmt(n) => {
+            creates.push((ObjectKind::Function, owned(names(&n.funcname))))
+        }
+        NodeEnum::AlterOwnerStmt(n) => {
+            let target = n.object.as_ref().and_then(|o| o.node.as_ref());
+            let changed = match (n.object_type, target) {
+                // ObjectFunction, ObjectProcedure
+                (20 | 30, Some(NodeEnum::ObjectWithArgs(f))) => {
+                    object(ObjectKind::Function, &names(&f.objname))
+                }
+                // ObjectType
+                (50, Some(NodeEnum::List(l))) => object(ObjectKind::Type, &names(&l.items)),
+                // ObjectSchema
+                (37, Some(NodeEnum::String(s))) => Some((
+                    Object {
+                        kind: ObjectKind::Schema,
+                        name: s.sval.clone(),
+                    },
+                    None,
+                )),
+                _ => None,
+            };
+            deps.uses.extend(changed.map(|(o, _)| (o, None)));
+        }
+        NodeEnum::CreateTrigStmt(n) => deps
+            .uses
+            .extend(object(ObjectKind::Function, &names(&n.funcname)).map(|(o, _)| (o, None))),
+        NodeEnum::GrantRoleStmt(n) => {
+            for role in &n.granted_roles {
+                if let Some(NodeEnum::AccessPriv(p)) = role.node.as_ref() {
+                    deps.uses_roles.push((p.priv_name.clone(), None));
+                }
+            }
+        }
+        NodeEnum::VariableSetStmt(n) => {
+            let is_role = n.name == "role" || n.name == "session_authorization";
+            // VarSetValue
+            if is_role && n.kind == 1 {
+                if let Some(role) = n.args.first().and_then(string_constant) {
+                    if role != "none" {
+                        deps.uses_roles.push((role.to_string(), None));
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+
+    // a sequence can only be owned by a column of a table that exists
+    let owned_by = match node {
+        NodeEnum::CreateSeqStmt(n) => sequence_owned_by(&n.options),
+        NodeEnum::AlterSeqStmt(n) => sequence_owned_by(&n.options),
+        _ => None,
+    };
+    if let Some([table @ .., _column]) = owned_by.as_deref() {
+        deps.uses
+            .extend(object(ObjectKind::Relation, table).map(|(o, _)| (o, None)));
+    }
+
+    let mut uses_schemas = Vec::new();
+    for (kind, parts) in creates {
+        let parts = parts.iter().map(|p| p.as_str()).collect::<Vec<&str>>();
+        if let Some((o, schema)) = object(kind, &parts) {
+            deps.creates.push(o);
+            uses_schemas.extend(schema.map(|s| (s, None)));
+        }
+    }
+
+    for child in descendants(node) {
+        let (used, location) = match &child {
+            NodeEnum::RangeVar(r) => (
+                object(
+                    ObjectKind::Relation,
+                    &range_var_parts(&r.schemaname, &r.relname)
+                        .iter()
+                        .map(|p| p.as_str())
+                        .collect::<Vec<&str>>(),
+                ),
+                r.location,
+            ),
+            NodeEnum::TypeName(t) => (object(ObjectKind::Type, &names(&t.names)), t.location),
+            NodeEnum::FuncCall(f) => (
+                object(ObjectKind::Function, &names(&f.funcname)),
+                f.location,
+            ),
+            // e.g. `nextval('public.orders_id_seq'::regclass)`
+            NodeEnum::TypeCast(c) => {
+                let is_regclass = c
+                    .type_name
+                    .as_ref()
+                    .and_then(|t| t.names.last())
+                    .and_then(string_value)
+                    == Some("regclass");
+                match c.arg.as_deref().and_then(string_constant) {
+                    Some(name) if is_regclass => {
+                        let parts = name
+                            .split('.')
+                            .map(|p| p.trim_matches('"'))
+                            .collect::<Vec<&str>>();
+                        (object(ObjectKind::Relation, &parts), c.location)
+                    }
+                    _ => continue,
+                }
+            }
+            NodeEnum::RoleSpec(r) => {
+                // RolespecCstring
+                if r.roletype == 1 {
+                    deps.uses_roles.push((r.rolename.clone(), Some(r.location)));
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        if let Some((o, schema)) = used {
+            uses_schemas.extend(schema.map(|s| (s, Some(location))));
+            if !deps.creates.contains(&o) {
+                deps.uses.push((o, Some(location)));
+            }
+        }
+    }
+
+    for (schema, location) in uses_schemas {
+        let o = Object {
+            kind: ObjectKind::Schema,
+            name: schema,
+        };
+        if !deps.creates.contains(&o) {
+            deps.uses.push((o, location));
+        }
+    }
+    deps
+}
+
+/// Returns the parts of a possibly qualified name given as a list of `String` nodes
+fn names(nodes: &[Node]) -> Vec<&str> {
+    nodes.iter().filter_map(string_value).collect()
+}
+
+fn owned(parts: Vec<&str>) -> Vec<String> {
+    parts.into_iter().map(|p| p.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    fn check(sql: &str, config: &RestoreConfig) -> Vec<(&'static str, String)> {
+        check_restore_script(&parse_source(sql).stmts, config)
+            .into_iter()
+            .map(|d| (d.rule, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_dump() {
+        let sql = "SET statement_timeout = 0;
+SET client_encoding = 'UTF8';
+SET standard_conforming_strings = on;
+SELECT pg_catalog.set_config('search_path', '', false);
+SET check_function_bodies = false;
+CREATE SCHEMA app;
+CREATE TYPE app.status AS ENUM ('open', 'closed');
+CREATE TABLE app.orders (id integer NOT NULL, status app.status);
+ALTER TABLE app.orders OWNER TO app_owner;
+CREATE SEQUENCE app.orders_id_seq;
+ALTER TABLE ONLY app.orders ALTER COLUMN id SET DEFAULT nextval('app.orders_id_seq'::regclass);
+GRANT SELECT ON TABLE app.orders TO PUBLIC;
+";
+        let config = RestoreConfig {
+            existing_roles: Some(BTreeSet::from(["app_owner".to_string()])),
+        };
+        assert_eq!(check(sql, &config), vec![]);
+    }
+
+    #[test]
+    fn test_preamble() {
+        let diagnostics = check(
+            "SET statement_timeout = '5min';
+SET check_function_bodies = true;
+SET LOCAL lock_timeout = 0;
+CREATE TABLE t (a int);
+SET client_encoding = 'LATIN1';
+",
+            &RestoreConfig::default(),
+        );
+        assert_eq!(
+            diagnostics,
+            vec![
+                (SET_PREAMBLE, "statement_timeout is set to 5min, but a restore needs 0: otherwise long running statements such as index builds would be cancelled".to_string()),
+                (SET_PREAMBLE, "the preamble does not set client_encoding, so the script is read in the encoding of the client that restores it".to_string()),
+                (SET_PREAMBLE, "check_function_bodies is set to true, but a restore needs off: otherwise functions that use objects created later in the script could not be created".to_string()),
+                (SET_PREAMBLE, "SET LOCAL lock_timeout outside of a transaction has no effect".to_string()),
+                (SET_PREAMBLE, "client_encoding changes how all following statements are read and should only be set in the preamble".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_roles() {
+        let sql = "SET client_encoding = 'UTF8';
+ALTER TABLE orders OWNER TO late_role;
+GRANT SELECT ON orders TO reporting, PUBLIC;
+GRANT admins TO alice;
+CREATE ROLE late_role;
+";
+        assert_eq!(
+            check(sql, &RestoreConfig::default()),
+            vec![(
+                UNKNOWN_ROLE,
+                "role late_role is used before it is created".to_string()
+            )]
+        );
+
+        let config = RestoreConfig {
+            existing_roles: Some(BTreeSet::from(["alice".to_string()])),
+        };
+        assert_eq!(
+            check(sql, &config)
+                .into_iter()
+                .map(|(_, message)| message)
+                .collect::<Vec<String>>(),
+            vec![
+                "role late_role is used before it is created",
+                "role reporting does not exist in the target database",
+                "role admins does not exist in the target database",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dependency_order() {
+        let sql = "SET client_encoding = 'UTF8';
+SET search_path = app, public;
+CREATE TABLE orders (id int, customer_id int REFERENCES customers (id));
+CREATE VIEW open_orders AS SELECT * FROM app.orders;
+CREATE TABLE customers (id int PRIMARY KEY);
+CREATE TABLE public.items (price app.money_amount);
+CREATE DOMAIN money_amount AS numeric;
+CREATE SCHEMA app;
+";
+        assert_eq!(
+            check(sql, &RestoreConfig::default())
+                .into_iter()
+                .map(|(_, message)| message)
+                .collect::<Vec<String>>(),
+            vec![
+                "relation app.customers is used before it is created",
+                "schema app is used before it is created",
+                "type app.money_amount is used before it is created",
+                "schema app is used before it is created",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ownership_order() {
+        let sql = "SET client_encoding = 'UTF8';
+CREATE SEQUENCE orders_id_seq OWNED BY orders.id;
+ALTER FUNCTION total(int) OWNER TO app;
+CREATE TABLE orders (id int);
+CREATE FUNCTION total(int) RETURNS int AS 'select 1' LANGUAGE sql;
+CREATE ROLE app;
+ALTER SEQUENCE orders_id_seq OWNED BY NONE;
+";
+        assert_eq!(
+            check(sql, &RestoreConfig::default())
+                .into_iter()
+                .map(|(_, message)| message)
+                .collect::<Vec<String>>(),
+            vec![
+                "relation public.orders is used before it is created",
+                "function public.total is used before it is created",
+                "role app is used before it is created",
+            ]
+        );
+    }
+}
@@ -0,0 +1,500 @@
+use std::collections::BTreeMap;
+
+use pg_query::protobuf::PartitionSpec;
+use pg_query::NodeEnum;
+
+/// Query to load the columns of all tables in the schemas given as a `text[]` in `$1`.
+///
+/// Every row can be converted into a [`CatalogColumn`].
+///
+/// The properties of the tables are computed in a subquery once per table rather than once per
+/// column, which `offset 0` keeps Postgres from merging into the outer query.
+pub const SCHEMA_COLUMNS_QUERY: &str = "select
+    t.schema_name,
+    t.table_name,
+    a.attname as column_name,
+    pg_catalog.format_type(a.atttypid, a.atttypmod) as data_type,
+    a.attnotnull as not_null,
+    pg_catalog.pg_get_expr(d.adbin, d.adrelid) as default_expr,
+    t.access_method,
+    t.owner,
+    pg_catalog.pg_get_serial_sequence(t.oid::regclass::text, a.attname) as owned_sequence,
+    t.partition_key,
+    t.fillfactor
+from (
+    select
+        c.oid,
+        n.nspname as schema_name,
+        c.relname as table_name,
+        nullif(am.amname, 'heap') as access_method,
+        pg_catalog.pg_get_userbyid(c.relowner) as owner,
+        case when c.relkind = 'p' then pg_catalog.pg_get_partkeydef(c.oid) end as partition_key,
+        (select o.option_value::int
+            from pg_catalog.pg_options_to_table(c.reloptions) o
+            where o.option_name = 'fillfactor') as fillfactor
+    from pg_catalog.pg_class c
+        join pg_catalog.pg_namespace n on n.oid = c.relnamespace
+        left join pg_catalog.pg_am am on am.oid = c.relam
+    where n.nspname = any($1)
+        and c.relkind in ('r', 'p')
+    offset 0
+) t
+    join pg_catalog.pg_attribute a on a.attrelid = t.oid
+    left join pg_catalog.pg_attrdef d on d.adrelid = a.attrelid and d.adnum = a.attnum
+where a.attnum > 0
+    and not a.attisdropped
+order by t.schema_name, t.table_name, a.attnum";
+
+/// Query to load the indexes of all tables in the schemas given as a `text[]` in `$1`.
+///
+/// Every row can be converted into a [`CatalogIndex`].
+pub const SCHEMA_INDEXES_QUERY: &str = "select
+    n.nspname as schema_name,
+    t.relname as table_name,
+    i.relname as index_name,
+    pg_catalog.pg_get_indexdef(x.indexrelid) as definition,
+    x.indisclustered as is_clustered
+from pg_catalog.pg_index x
+    join pg_catalog.pg_class i on i.oid = x.indexrelid
+    join pg_catalog.pg_class t on t.oid = x.indrelid
+    join pg_catalog.pg_namespace n on n.oid = t.relnamespace
+where n.nspname = any($1)
+order by n.nspname, t.relname, i.relname";
+
+/// Query to load the constraints of all tables in the schemas given as a `text[]` in `$1`.
+///
+/// Every row can be converted into a [`CatalogConstraint`].
+pub const SCHEMA_CONSTRAINTS_QUERY: &str = "select
+    n.nspname as schema_name,
+    t.relname as table_name,
+    c.conname as constraint_name,
+    pg_catalog.pg_get_constraintdef(c.oid) as definition
+from pg_catalog.pg_constraint c
+    join pg_catalog.pg_class t on t.oid = c.conrelid
+    join pg_catalog.pg_namespace n on n.oid = t.relnamespace
+where n.nspname = any($1)
+order by n.nspname, t.relname, c.conname";
+
+/// Query to load the views and materialized views of the schemas given as a `text[]` in `$1`.
+///
+/// Every row can be converted into a [`View`].
+pub const SCHEMA_VIEWS_QUERY: &str = "select
+    n.nspname as schema_name,
+    c.relname as view_name,
+    pg_catalog.pg_get_viewdef(c.oid) as definition,
+    c.relkind = 'm' as is_materialized
+from pg_catalog.pg_class c
+    join pg_catalog.pg_namespace n on n.oid = c.relnamespace
+where n.nspname = any($1)
+    and c.relkind in ('v', 'm')
+order by n.nspname, c.relname";
+
+/// A row returned by [`SCHEMA_COLUMNS_QUERY`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatalogColumn {
+    pub schema_name: String,
+    pub table_name: String,
+    pub column_name: String,
+    pub data_type: String,
+    pub not_null: bool,
+    pub default_expr: Option<String>,
+    /// The access method of the table, `None` for the default `heap`
+    pub access_method: Option<String>,
+    /// The role that owns the table
+    pub owner: Option<String>,
+    /// The qualified name of the sequence that the column owns, e.g. of a `serial` column
+    pub owned_sequence: Option<String>,
+    /// The partition key of the table if it is partitioned, e.g. `RANGE (created_at)`
+    pub partition_key: Option<String>,
+    /// The fillfactor of the table if it has been set
+    pub fillfactor: Option<i32>,
+}
+
+/// A row returned by [`SCHEMA_INDEXES_QUERY`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatalogIndex {
+    pub schema_name: String,
+    pub table_name: String,
+    pub index_name: String,
+    pub definition: String,
+    /// True if the table was last clustered on the index
+    pub is_clustered: bool,
+}
+
+/// A row returned by [`SCHEMA_CONSTRAINTS_QUERY`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatalogConstraint {
+    pub schema_name: String,
+    pub table_name: String,
+    pub constraint_name: String,
+    pub definition: String,
+}
+
+/// A row returned by [`SCHEMA_VIEWS_QUERY`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct View {
+    pub schema_name: String,
+    pub name: String,
+    /// The query of the view as reconstructed by Postgres
+    pub definition: String,
+    pub is_materialized: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    pub name: String,
+    pub data_type: String,
+    pub not_null: bool,
+    pub default_expr: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Table {
+    pub name: String,
+    /// The columns in the order of their definition
+    pub columns: Vec<Column>,
+    /// Index definitions by index name
+    pub indexes: BTreeMap<String, String>,
+    /// Constraint definitions by constraint name
+    pub constraints: BTreeMap<String, String>,
+    /// The table access method of `USING`, `None` for the default `heap`
+    pub access_method: Option<String>,
+    /// The index that `CLUSTER` orders the table by
+    pub clustered_index: Option<String>,
+    /// The role of `OWNER TO`, `None` if the table is owned by the role that created it
+    pub owner: Option<String>,
+    /// The sequences that are owned by a column, e.g. with `OWNED BY`, by sequence name with the
+    /// name of their column. They are dropped together with the column.
+    pub owned_sequences: BTreeMap<String, String>,
+    /// The partition key of `PARTITION BY` as returned by `pg_get_partkeydef`, e.g.
+    /// `RANGE (created_at)`, `None` if the table is not partitioned
+    pub partition_key: Option<String>,
+    /// The parts of the partition key, which are the names of columns or `None` for expressions,
+    /// `None` if the table is not partitioned
+    pub partition_columns: Option<Vec<Option<String>>>,
+    /// The storage parameter `fillfactor`, `None` for the default of 100
+    pub fillfactor: Option<u32>,
+}
+
+impl Table {
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+
+    /// Returns the names of the sequences owned by `column`
+    pub fn sequences_of(&self, column: &str) -> impl Iterator<Item = &str> {
+        self.owned_sequences
+            .iter()
+            .filter(move |(_, c)| *c == column)
+            .map(|(sequence, _)| sequence.as_str())
+    }
+
+    /// Returns true if the table has the index `name`, including the indexes that primary key,
+    /// unique and exclusion constraints create under their own name
+    pub fn has_index(&self, name: &str) -> bool {
+        self.indexes.contains_key(name)
+            || self.constraints.get(name).is_some_and(|d| {
+                ["PRIMARY KEY", "UNIQUE", "EXCLUDE"]
+                    .iter()
+                    .any(|prefix| d.starts_with(prefix))
+            })
+    }
+
+    /// Returns when the constraint `name` is checked, or `None` if the table has no such
+    /// constraint
+    pub fn constraint_deferral(&self, name: &str) -> Option<Deferral> {
+        self.constraints.get(name).map(|d| Deferral::of(d))
+    }
+}
+
+/// Returns the parts of the partition key `spec`, which are the names of columns or `None` for
+/// expressions
+pub(crate) fn partition_columns(spec: &PartitionSpec) -> Vec<Option<String>> {
+    spec.part_params
+        .iter()
+        .map(|param| match param.node.as_ref() {
+            Some(NodeEnum::PartitionElem(elem)) if !elem.name.is_empty() => Some(elem.name.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the parts of a partition key as returned by `pg_get_partkeydef`
+fn parse_partition_key(key: &str) -> Option<Vec<Option<String>>> {
+    let stmt = pg_query::parse(&format!("CREATE TABLE t () PARTITION BY {}", key))
+        .ok()
+        .and_then(|result| result.protobuf.stmts.into_iter().next())
+        .and_then(|stmt| stmt.stmt)
+        .and_then(|stmt| stmt.node);
+    match stmt {
+        Some(NodeEnum::CreateStmt(n)) => n.partspec.as_ref().map(partition_columns),
+        _ => None,
+    }
+}
+
+/// When a constraint is checked within a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Deferral {
+    /// After every statement
+    NotDeferrable,
+    /// After every statement, unless it is deferred with `SET CONSTRAINTS`
+    InitiallyImmediate,
+    /// At the end of the transaction
+    InitiallyDeferred,
+}
+
+impl Deferral {
+    /// Returns the deferral of a constraint given its definition, which ends with its deferral
+    /// both as deparsed by pg_query and as returned by `pg_get_constraintdef`
+    pub fn of(definition: &str) -> Deferral {
+        let definition = definition.trim_end();
+        let definition = definition.strip_suffix(" NOT VALID").unwrap_or(definition);
+        if definition.ends_with(" DEFERRABLE INITIALLY DEFERRED") {
+            Deferral::InitiallyDeferred
+        } else if definition.ends_with(" DEFERRABLE")
+            || definition.ends_with(" DEFERRABLE INITIALLY IMMEDIATE")
+        {
+            Deferral::InitiallyImmediate
+        } else {
+            Deferral::NotDeferrable
+        }
+    }
+
+    pub fn is_deferrable(&self) -> bool {
+        *self != Deferral::NotDeferrable
+    }
+}
+
+/// The tables of a single schema.
+///
+/// All definitions are stored without the qualifier of the schema itself, so that two schemas
+/// with the same structure compare equal. References to other schemas stay qualified.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Schema {
+    pub name: String,
+    pub tables: BTreeMap<String, Table>,
+}
+
+impl Schema {
+    pub fn new(name: impl Into<String>) -> Schema {
+        Schema {
+            name: name.into(),
+            tables: BTreeMap::new(),
+        }
+    }
+
+    /// Builds all schemas contained in the rows loaded from the catalog of a live database
+    pub fn from_catalog(
+        columns: Vec<CatalogColumn>,
+        indexes: Vec<CatalogIndex>,
+        constraints: Vec<CatalogConstraint>,
+    ) -> BTreeMap<String, Schema> {
+        let mut schemas = BTreeMap::<String, Schema>::new();
+        for c in columns {
+            let table = schemas
+                .entry(c.schema_name.clone())
+                .or_insert_with(|| Schema::new(&c.schema_name))
+                .table_mut(&c.table_name);
+            table.access_method = c.access_method;
+            table.owner = c.owner;
+            // the partition key is the same in the rows of all columns of the table
+            if table.columns.is_empty() {
+                table.partition_columns = c.partition_key.as_deref().and_then(parse_partition_key);
+            }
+            table.partition_key = c.partition_key;
+            table.fillfactor = c.fillfactor.map(|f| f as u32);
+            if let Some(sequence) = c.owned_sequence {
+                let sequence = unqualify(&sequence, &c.schema_name);
+                table.owned_sequences.insert(
+                    sequence.trim_matches('"').to_string(),
+                    c.column_name.clone(),
+                );
+            }
+            table.columns.push(Column {
+                name: c.column_name,
+                data_type: unqualify(&c.data_type, &c.schema_name),
+                not_null: c.not_null,
+                default_expr: c.default_expr.map(|d| unqualify(&d, &c.schema_name)),
+            });
+        }
+        for i in indexes {
+            let definition = unqualify(&i.definition, &i.schema_name);
+            if let Some(schema) = schemas.get_mut(&i.schema_name) {
+                let table = schema.table_mut(&i.table_name);
+                if i.is_clustered {
+                    table.clustered_index = Some(i.index_name.clone());
+                }
+                table.indexes.insert(i.index_name, definition);
+            }
+        }
+        for c in constraints {
+            let definition = unqualify(&c.definition, &c.schema_name);
+            if let Some(schema) = schemas.get_mut(&c.schema_name) {
+                schema
+                    .table_mut(&c.table_name)
+                    .constraints
+                    .insert(c.constraint_name, definition);
+            }
+        }
+        schemas
+    }
+
+    pub fn table(&self, name: &str) -> Option<&Table> {
+        self.tables.get(name)
+    }
+
+    fn table_mut(&mut self, name: &str) -> &mut Table {
+        self.tables
+            .entry(name.to_string())
+            .or_insert_with(|| Table {
+                name: name.to_string(),
+                ..Table::default()
+            })
+    }
+}
+
+/// Removes all qualifiers of `schema` from a definition, e.g. `ON tenant_a.orders` becomes
+/// `ON orders`
+fn unqualify(definition: &str, schema: &str) -> String {
+    let quoted = format!("\"{}\".", schema.replace('"', "\"\""));
+    let plain = format!("{}.", schema);
+    let mut result = String::with_capacity(definition.len());
+    let mut rest = definition;
+    while !rest.is_empty() {
+        // only strip qualifiers at the start of an identifier
+        let at_identifier_start = !matches!(
+            result.chars().next_back(),
+            Some(c) if c.is_alphanumeric() || c == '_' || c == '"'
+        );
+        if at_identifier_start && rest.starts_with(&quoted) {
+            rest = &rest[quoted.len()..];
+        } else if at_identifier_start && rest.starts_with(&plain) {
+            rest = &rest[plain.len()..];
+        } else {
+            let c = rest.chars().next().unwrap();
+            result.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unqualify() {
+        assert_eq!(
+            unqualify(
+                "CREATE INDEX orders_idx ON tenant_a.orders USING btree (id)",
+                "tenant_a"
+            ),
+            "CREATE INDEX orders_idx ON orders USING btree (id)"
+        );
+        assert_eq!(
+            unqualify(
+                "nextval('\"tenant_a\".orders_id_seq'::regclass)",
+                "tenant_a"
+            ),
+            "nextval('orders_id_seq'::regclass)"
+        );
+        assert_eq!(
+            unqualify("REFERENCES other_tenant_a.users(id)", "tenant_a"),
+            "REFERENCES other_tenant_a.users(id)"
+        );
+    }
+
+    #[test]
+    fn test_deferral() {
+        assert_eq!(
+            Deferral::of("FOREIGN KEY (org) REFERENCES org(id)"),
+            Deferral::NotDeferrable
+        );
+        assert_eq!(
+            Deferral::of("FOREIGN KEY (org) REFERENCES org(id) DEFERRABLE"),
+            Deferral::InitiallyImmediate
+        );
+        assert_eq!(
+            Deferral::of(
+                "FOREIGN KEY (org) REFERENCES org(id) DEFERRABLE INITIALLY DEFERRED NOT VALID"
+            ),
+            Deferral::InitiallyDeferred
+        );
+        assert_eq!(
+            Deferral::of("CHECK (note <> ' DEFERRABLE')"),
+            Deferral::NotDeferrable
+        );
+    }
+
+    #[test]
+    fn test_from_catalog() {
+        let column = |schema: &str, table: &str, name: &str| CatalogColumn {
+            schema_name: schema.to_string(),
+            table_name: table.to_string(),
+            column_name: name.to_string(),
+            data_type: "integer".to_string(),
+            not_null: true,
+            default_expr: None,
+            access_method: None,
+            owner: Some("app".to_string()),
+            owned_sequence: None,
+            partition_key: None,
+            fillfactor: None,
+        };
+        let schemas = Schema::from_catalog(
+            vec![
+                CatalogColumn {
+                    owned_sequence: Some("tenant_a.orders_id_seq".to_string()),
+                    ..column("tenant_a", "orders", "id")
+                },
+                column("tenant_a", "orders", "total"),
+                column("tenant_b", "orders", "id"),
+                CatalogColumn {
+                    partition_key: Some("RANGE (created_at, lower(region))".to_string()),
+                    ..column("tenant_b", "event", "created_at")
+                },
+            ],
+            vec![CatalogIndex {
+                schema_name: "tenant_b".to_string(),
+                table_name: "orders".to_string(),
+                index_name: "orders_pkey".to_string(),
+                definition: "CREATE UNIQUE INDEX orders_pkey ON tenant_b.orders USING btree (id)"
+                    .to_string(),
+                is_clustered: true,
+            }],
+            vec![],
+        );
+
+        assert_eq!(schemas.len(), 2);
+        let orders = schemas["tenant_a"].table("orders").unwrap();
+        assert_eq!(orders.columns.len(), 2);
+        assert!(orders.column("total").is_some());
+        assert_eq!(
+            schemas["tenant_b"].table("orders").unwrap().indexes["orders_pkey"],
+            "CREATE UNIQUE INDEX orders_pkey ON orders USING btree (id)"
+        );
+        assert_eq!(
+            schemas["tenant_b"]
+                .table("orders")
+                .unwrap()
+                .clustered_index
+                .as_deref(),
+            Some("orders_pkey")
+        );
+        assert_eq!(orders.clustered_index, None);
+        assert_eq!(orders.owner.as_deref(), Some("app"));
+        assert_eq!(
+            orders.sequences_of("id").collect::<Vec<_>>(),
+            vec!["orders_id_seq"]
+        );
+        assert_eq!(orders.partition_columns, None);
+        assert_eq!(
+            schemas["tenant_b"]
+                .table("event")
+                .unwrap()
+                .partition_columns,
+            Some(vec![Some("created_at".to_string()), None])
+        );
+    }
+}
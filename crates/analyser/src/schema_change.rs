@@ -0,0 +1,620 @@
+//! Classification of the schema changes made by migration statements.
+//!
+//! Every statement that adds, drops or alters an object is described by a `SchemaChange`, together
+//! with the table lock it takes and an estimate of how risky it is to run against a live database.
+//! Lock levels follow the "Explicit Locking" chapter of the Postgres manual. The risk is derived
+//! from the lock and from whether the statement loses data, rewrites or scans a table while
+//! holding the lock:
+//! - `High`: data is lost, or a table is rewritten under an `ACCESS EXCLUSIVE` lock
+//! - `Medium`: reads or writes are blocked while a table is scanned, or dependent objects may break
+//! - `Low`: everything else, e.g. new objects or metadata-only changes
+//!
+//! Statements that do not change the schema, such as queries, are not classified. `LOCK` is,
+//! since the lock it takes is held until the end of the migration's transaction.
+//!
+//! A statement that waits for its lock blocks all later queries on the table until it gets it, so
+//! migrations that take strong locks are expected to set `lock_timeout` first.
+
+use std::fmt;
+
+use cstree::text::TextRange;
+use parser::RawStmt;
+use pg_query::protobuf::a_const::Val;
+use pg_query::protobuf::{AlterTableCmd, Node};
+use pg_query::NodeEnum;
+
+use crate::lint::{LintDiagnostic, Severity};
+use crate::moniker::qualified_name;
+use crate::utils::string_value;
+
+const MISSING_LOCK_TIMEOUT: &str = "missing-lock-timeout";
+
+/// A table-level lock mode, from the weakest to the strongest one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LockMode {
+    AccessShare,
+    RowShare,
+    RowExclusive,
+    ShareUpdateExclusive,
+    Share,
+    ShareRowExclusive,
+    Exclusive,
+    AccessExclusive,
+}
+
+impl fmt::Display for LockMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LockMode::AccessShare => "ACCESS SHARE",
+            LockMode::RowShare => "ROW SHARE",
+            LockMode::RowExclusive => "ROW EXCLUSIVE",
+            LockMode::ShareUpdateExclusive => "SHARE UPDATE EXCLUSIVE",
+            LockMode::Share => "SHARE",
+            LockMode::ShareRowExclusive => "SHARE ROW EXCLUSIVE",
+            LockMode::Exclusive => "EXCLUSIVE",
+            LockMode::AccessExclusive => "ACCESS EXCLUSIVE",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Risk {
+    Low,
+    Medium,
+    High,
+}
+
+impl fmt::Display for Risk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Risk::Low => "low",
+            Risk::Medium => "medium",
+            Risk::High => "high",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChangeAction {
+    Added,
+    Dropped,
+    Altered,
+    /// Locked explicitly with `LOCK`
+    Locked,
+}
+
+/// A change of the schema made by a single statement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaChange {
+    pub action: ChangeAction,
+    /// The kind and name of the changed object, e.g. `table public.contact`
+    pub object: String,
+    /// The strongest lock taken on an existing table, if any
+    pub lock: Option<LockMode>,
+    pub risk: Risk,
+    /// Why the change has its risk. Empty for low risk changes.
+    pub reasons: Vec<String>,
+    /// The range of the statement
+    pub range: TextRange,
+}
+
+/// Returns the schema changes made by `stmts`
+pub fn schema_changes(stmts: &[RawStmt]) -> Vec<SchemaChange> {
+    stmts
+        .iter()
+        .filter_map(|stmt| schema_change(&stmt.stmt, stmt.range))
+        .collect()
+}
+
+/// Reports the first statement of every transaction of a migration that takes a lock stronger
+/// than `ROW EXCLUSIVE` on an existing table without a `lock_timeout` set before it. Statements
+/// outside of `BEGIN` and `COMMIT` count as one transaction. `SET LOCAL` only sets the timeout until
+/// the end of its transaction.
+pub fn check_lock_timeout(stmts: &[RawStmt]) -> Vec<LintDiagnostic> {
+    let mut diagnostics = Vec::new();
+    // whether the session has a lock_timeout, and whether the transaction has one of its own
+    let mut session_timeout = false;
+    let mut local_timeout = None;
+    let mut reported = false;
+    for stmt in stmts {
+        match &stmt.stmt {
+            NodeEnum::VariableSetStmt(n) if n.name.eq_ignore_ascii_case("lock_timeout") => {
+                // VarSetValue, all other kinds reset the timeout
+                let timeout = n.kind == 1 && !n.args.iter().any(is_zero);
+                if n.is_local {
+                    local_timeout = Some(timeout);
+                } else {
+                    session_timeout = timeout;
+                    local_timeout = None;
+                }
+            }
+            NodeEnum::TransactionStmt(n) => match n.kind {
+                // TransStmtBegin, TransStmtStart
+                1 | 2 => reported = false,
+                // TransStmtCommit, TransStmtRollback, TransStmtPrepare
+                3 | 4 | 8 => {
+                    local_timeout = None;
+                    reported = false;
+                }
+                _ => {}
+            },
+            node => {
+                if reported || local_timeout.unwrap_or(session_timeout) {
+                    continue;
+                }
+                let Some(change) = schema_change(node, stmt.range) else {
+                    continue;
+                };
+                if let Some(lock) = change.lock.filter(|lock| *lock > LockMode::RowExclusive) {
+                    reported = true;
+                    diagnostics.push(LintDiagnostic {
+                        rule: MISSING_LOCK_TIMEOUT,
+                        message: format!(
+                            "while this statement waits for the {} lock, all queries on the \
+                            table are blocked; set lock_timeout before it, and statement_timeout \
+                            to limit how long it holds the lock",
+                            lock
+                        ),
+                        severity: Severity::Information,
+                        range: stmt.range,
+                    });
+                }
+            }
+        }
+    }
+    diagnostics
+}
+
+/// Returns true if `node` is the constant `0`, which disables a timeout
+fn is_zero(node: &Node) -> bool {
+    match node.node.as_ref() {
+        Some(NodeEnum::AConst(c)) => match c.val.as_ref() {
+            Some(Val::Ival(i)) => i.ival == 0,
+            Some(Val::Sval(s)) => s.sval.trim() == "0",
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn schema_change(node: &NodeEnum, range: TextRange) -> Option<SchemaChange> {
+    let mut change = SchemaChange {
+        action: ChangeAction::Added,
+        object: String::new(),
+        lock: None,
+        risk: Risk::Low,
+        reasons: Vec::new(),
+        range,
+    };
+    match node {
+        NodeEnum::CreateStmt(n) => {
+            change.object = format!("table {}", qualified_name(n.relation.as_ref()?));
+        }
+        NodeEnum::ViewStmt(n) => {
+            change.object = format!("view {}", qualified_name(n.view.as_ref()?));
+        }
+        NodeEnum::CreateTableAsStmt(n) => {
+            let relation = n.into.as_ref()?.rel.as_ref()?;
+            change.object = format!("table {}", qualified_name(relation));
+        }
+        NodeEnum::CreateSeqStmt(n) => {
+            change.object = format!("sequence {}", qualified_name(n.sequence.as_ref()?));
+        }
+        NodeEnum::CreateSchemaStmt(n) => change.object = format!("schema {}", n.schemaname),
+        NodeEnum::CreateEnumStmt(n) => change.object = format!("type {}", names(&n.type_name)),
+        NodeEnum::CreateDomainStmt(n) => change.object = format!("domain {}", names(&n.domainname)),
+        NodeEnum::CompositeTypeStmt(n) => {
+            change.object = format!("type {}", qualified_name(n.typevar.as_ref()?));
+        }
+        NodeEnum::CreateFunctionStmt(n) => {
+            change.object = format!("function {}", names(&n.funcname))
+        }
+        NodeEnum::CreateTrigStmt(n) => {
+            let table = qualified_name(n.relation.as_ref()?);
+            change.object = format!("trigger {} on {}", n.trigname, table);
+            change.lock = Some(LockMode::ShareRowExclusive);
+        }
+        NodeEnum::IndexStmt(n) => {
+            let table = qualified_name(n.relation.as_ref()?);
+            change.object = if n.idxname.is_empty() {
+                format!("index on {}", table)
+            } else {
+                format!("index {} on {}", n.idxname, table)
+            };
+            if n.concurrent {
+                change.lock = Some(LockMode::ShareUpdateExclusive);
+            } else {
+                change.lock = Some(LockMode::Share);
+                change.raise(
+                    Risk::Medium,
+                    "writes to the table are blocked while the index is built, use CONCURRENTLY",
+                );
+            }
+        }
+        NodeEnum::DropStmt(n) => {
+            let kind = object_kind(n.remove_type);
+            change.action = ChangeAction::Dropped;
+            change.object = format!(
+                "{} {}",
+                kind,
+                n.objects
+                    .iter()
+                    .filter_map(|o| object_name(o.node.as_ref()?))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            match kind {
+                "table" | "materialized view" => {
+                    change.lock = Some(LockMode::AccessExclusive);
+                    change.raise(Risk::High, "the data of the table is lost");
+                }
+                "index" if n.concurrent => change.lock = Some(LockMode::ShareUpdateExclusive),
+                "index" => {
+                    change.lock = Some(LockMode::AccessExclusive);
+                    change.raise(
+                        Risk::Medium,
+                        "reads and writes of the table are blocked, use CONCURRENTLY",
+                    );
+                }
+                _ => change.raise(Risk::Medium, "objects that use it stop working"),
+            }
+        }
+        NodeEnum::AlterTableStmt(n) => {
+            change.action = ChangeAction::Altered;
+            change.object = format!("table {}", qualified_name(n.relation.as_ref()?));
+            for cmd in &n.cmds {
+                if let Some(NodeEnum::AlterTableCmd(cmd)) = cmd.node.as_ref() {
+                    alter_table_cmd(cmd, &mut change);
+                }
+            }
+        }
+        NodeEnum::RenameStmt(n) => {
+            change.action = ChangeAction::Altered;
+            let name = match &n.relation {
+                Some(relation) if n.subname.is_empty() => qualified_name(relation),
+                Some(relation) => format!("{}.{}", qualified_name(relation), n.subname),
+                None => n
+                    .object
+                    .as_ref()
+                    .and_then(|o| object_name(o.node.as_ref()?))
+                    .unwrap_or_default(),
+            };
+            change.object = format!("{} {}", object_kind(n.rename_type), name);
+            if n.relation.is_some() {
+                change.lock = Some(LockMode::AccessExclusive);
+            }
+            change.raise(Risk::Medium, "queries that use the old name fail");
+        }
+        NodeEnum::TruncateStmt(n) => {
+            change.action = ChangeAction::Altered;
+            change.object = format!("table {}", relation_names(&n.relations));
+            change.lock = Some(LockMode::AccessExclusive);
+            change.raise(Risk::High, "the data of the table is lost");
+        }
+        NodeEnum::ClusterStmt(n) => {
+            change.action = ChangeAction::Altered;
+            change.object = format!("table {}", qualified_name(n.relation.as_ref()?));
+            change.lock = Some(LockMode::AccessExclusive);
+            change.raise(Risk::High, "the table is rewritten while it is locked");
+        }
+        NodeEnum::LockStmt(n) => {
+            change.action = ChangeAction::Locked;
+            change.object = format!("table {}", relation_names(&n.relations));
+            let lock = lock_mode(n.mode);
+            change.lock = Some(lock);
+            if lock == LockMode::AccessExclusive {
+                change.raise(
+                    Risk::Medium,
+                    "reads and writes of the table are blocked until the end of the transaction",
+                );
+            } else if lock >= LockMode::Share {
+                change.raise(
+                    Risk::Medium,
+                    "writes to the table are blocked until the end of the transaction",
+                );
+            }
+        }
+        _ => return None,
+    }
+    Some(change)
+}
+
+impl SchemaChange {
+    /// Raises the risk to at least `risk` for `reason`
+    fn raise(&mut self, risk: Risk, reason: impl Into<String>) {
+        self.risk = self.risk.max(risk);
+        self.reasons.push(reason.into());
+    }
+
+    /// Raises the lock to at least `lock`
+    fn take_lock(&mut self, lock: LockMode) {
+        self.lock = self.lock.max(Some(lock));
+    }
+}
+
+fn alter_table_cmd(cmd: &AlterTableCmd, change: &mut SchemaChange) {
+    let constraint = match cmd.def.as_ref().and_then(|d| d.node.as_ref()) {
+        Some(NodeEnum::Constraint(c)) => Some(c),
+        _ => None,
+    };
+    match cmd.subtype {
+        // AtSetStatistics, AtValidateConstraint, AtClusterOn, AtDropCluster, AtAttachPartition,
+        // AtDetachPartitionFinalize
+        10 | 24 | 33 | 34 | 65 | 67 => change.take_lock(LockMode::ShareUpdateExclusive),
+        // AtEnableTrig through AtDisableTrigUser
+        43..=50 => change.take_lock(LockMode::ShareRowExclusive),
+        // AtAddConstraint
+        19 => {
+            // ConstrForeign
+            let is_foreign_key = constraint.is_some_and(|c| c.contype == 10);
+            change.take_lock(if is_foreign_key {
+                LockMode::ShareRowExclusive
+            } else {
+                LockMode::AccessExclusive
+            });
+            // ConstrCheck, ConstrForeign
+            let can_skip_validation = constraint.is_some_and(|c| c.contype == 6 || is_foreign_key);
+            if constraint.is_some_and(|c| !c.skip_validation) {
+                change.raise(
+                    Risk::Medium,
+                    if can_skip_validation {
+                        "the table is scanned to validate the constraint while it is locked, add it NOT VALID and validate it separately"
+                    } else {
+                        "the table is scanned to validate the constraint while it is locked"
+                    },
+                );
+            }
+        }
+        // AtSetNotNull
+        7 => {
+            change.take_lock(LockMode::AccessExclusive);
+            change.raise(
+                Risk::Medium,
+                format!(
+                    "the table is scanned to check that {} has no nulls while it is locked",
+                    cmd.name
+                ),
+            );
+        }
+        // AtDropColumn
+        15 => {
+            change.take_lock(LockMode::AccessExclusive);
+            change.raise(
+                Risk::High,
+                format!("the data of column {} is lost", cmd.name),
+            );
+        }
+        // AtAlterColumnType
+        30 => {
+            change.take_lock(LockMode::AccessExclusive);
+            change.raise(
+                Risk::High,
+                format!(
+                    "changing the type of {} may rewrite the table while it is locked",
+                    cmd.name
+                ),
+            );
+        }
+        // AtSetTableSpace, AtSetAccessMethod, AtSetLogged, AtSetUnLogged
+        39 | 38 | 35 | 36 => {
+            change.take_lock(LockMode::AccessExclusive);
+            change.raise(Risk::High, "the table is rewritten while it is locked");
+        }
+        _ => change.take_lock(LockMode::AccessExclusive),
+    }
+}
+
+/// Returns the lock mode with the number `mode` of a `LOCK` statement, from `AccessShareLock` (1) to
+/// `AccessExclusiveLock` (8)
+fn lock_mode(mode: i32) -> LockMode {
+    match mode {
+        1 => LockMode::AccessShare,
+        2 => LockMode::RowShare,
+        3 => LockMode::RowExclusive,
+        4 => LockMode::ShareUpdateExclusive,
+        5 => LockMode::Share,
+        6 => LockMode::ShareRowExclusive,
+        7 => LockMode::Exclusive,
+        _ => LockMode::AccessExclusive,
+    }
+}
+
+/// Returns the name of the kind of objects with the `ObjectType` `object_type`
+fn object_kind(object_type: i32) -> &'static str {
+    match object_type {
+        7 => "column",
+        13 => "domain",
+        19 => "foreign table",
+        20 => "function",
+        21 => "index",
+        24 => "materialized view",
+        30 => "procedure",
+        37 => "schema",
+        38 => "sequence",
+        41 => "constraint",
+        42 => "table",
+        45 => "trigger",
+        50 => "type",
+        52 => "view",
+        _ => "object",
+    }
+}
+
+/// Returns the name of an object as it is given in a `DROP` or `ALTER ... RENAME` statement
+fn object_name(node: &NodeEnum) -> Option<String> {
+    match node {
+        NodeEnum::List(l) => Some(names(&l.items)),
+        NodeEnum::TypeName(t) => Some(names(&t.names)),
+        NodeEnum::ObjectWithArgs(o) => Some(names(&o.objname)),
+        NodeEnum::String(s) => Some(s.sval.clone()),
+        _ => None,
+    }
+}
+
+/// Returns the qualified names of the tables of a statement such as `TRUNCATE`
+fn relation_names(relations: &[Node]) -> String {
+    relations
+        .iter()
+        .filter_map(|r| match r.node.as_ref()? {
+            NodeEnum::RangeVar(r) => Some(qualified_name(r)),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn names(nodes: &[Node]) -> String {
+    nodes
+        .iter()
+        .filter_map(string_value)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    fn changes(sql: &str) -> Vec<(ChangeAction, String, Option<LockMode>, Risk)> {
+        schema_changes(&parse_source(sql).stmts)
+            .into_iter()
+            .map(|c| (c.action, c.object, c.lock, c.risk))
+            .collect()
+    }
+
+    #[test]
+    fn test_schema_changes() {
+        assert_eq!(
+            changes(
+                "create table contact (id int);\nselect 1;\ncreate index concurrently contact_id on contact (id);\ndrop table app.orders;"
+            ),
+            vec![
+                (
+                    ChangeAction::Added,
+                    "table public.contact".to_string(),
+                    None,
+                    Risk::Low
+                ),
+                (
+                    ChangeAction::Added,
+                    "index contact_id on public.contact".to_string(),
+                    Some(LockMode::ShareUpdateExclusive),
+                    Risk::Low
+                ),
+                (
+                    ChangeAction::Dropped,
+                    "table app.orders".to_string(),
+                    Some(LockMode::AccessExclusive),
+                    Risk::High
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_alter_table() {
+        assert_eq!(
+            changes("alter table contact add column email text;"),
+            vec![(
+                ChangeAction::Altered,
+                "table public.contact".to_string(),
+                Some(LockMode::AccessExclusive),
+                Risk::Low
+            )]
+        );
+        assert_eq!(
+            changes("alter table contact add constraint fk foreign key (org) references org (id) not valid;")[0]
+                .2,
+            Some(LockMode::ShareRowExclusive)
+        );
+        assert_eq!(
+            changes("alter table contact alter column id set not null;")[0].3,
+            Risk::Medium
+        );
+        assert_eq!(
+            changes("alter table contact alter column id type bigint;")[0].3,
+            Risk::High
+        );
+        assert_eq!(
+            changes("cluster contact using contact_pkey;"),
+            vec![(
+                ChangeAction::Altered,
+                "table public.contact".to_string(),
+                Some(LockMode::AccessExclusive),
+                Risk::High
+            )]
+        );
+        assert_eq!(
+            changes("alter table contact cluster on contact_pkey;")[0].2,
+            Some(LockMode::ShareUpdateExclusive)
+        );
+    }
+
+    #[test]
+    fn test_lock() {
+        assert_eq!(
+            changes("lock table contact, app.orders in share update exclusive mode;"),
+            vec![(
+                ChangeAction::Locked,
+                "table public.contact, app.orders".to_string(),
+                Some(LockMode::ShareUpdateExclusive),
+                Risk::Low
+            )]
+        );
+        assert_eq!(
+            changes("lock contact;"),
+            vec![(
+                ChangeAction::Locked,
+                "table public.contact".to_string(),
+                Some(LockMode::AccessExclusive),
+                Risk::Medium
+            )]
+        );
+        assert_eq!(
+            changes("lock contact in share mode nowait;")[0].2,
+            Some(LockMode::Share)
+        );
+
+        let parse = parse_source("lock contact in exclusive mode;");
+        assert_eq!(check_lock_timeout(&parse.stmts).len(), 1);
+    }
+
+    #[test]
+    fn test_check_lock_timeout() {
+        let parse = parse_source(
+            "create table contact (id int);\nalter table org add column name text;\nalter table org drop column name;",
+        );
+        let diagnostics = check_lock_timeout(&parse.stmts);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].rule, MISSING_LOCK_TIMEOUT);
+        assert_eq!(diagnostics[0].range, parse.stmts[1].range);
+
+        let parse = parse_source("set lock_timeout = '1s';\nalter table org add column name text;");
+        assert!(check_lock_timeout(&parse.stmts).is_empty());
+    }
+
+    #[test]
+    fn test_check_lock_timeout_in_transactions() {
+        let parse = parse_source(
+            "begin;
+            set local lock_timeout = '1s';
+            alter table org add column name text;
+            commit;
+            begin;
+            alter table org drop column name;
+            alter table org add column title text;
+            commit;
+            set lock_timeout = 0;
+            alter table org drop column title;",
+        );
+        let diagnostics = check_lock_timeout(&parse.stmts);
+        assert_eq!(
+            diagnostics.iter().map(|d| d.range).collect::<Vec<_>>(),
+            vec![parse.stmts[5].range, parse.stmts[9].range]
+        );
+    }
+}
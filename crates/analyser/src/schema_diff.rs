@@ -0,0 +1,350 @@
+//! Structural comparison of two schemas.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::schema::{Column, Schema, Table};
+
+/// A single difference between two schemas, described as the change that turns the old schema
+/// into the new one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaChange {
+    TableAdded {
+        table: String,
+    },
+    TableRemoved {
+        table: String,
+    },
+    ColumnAdded {
+        table: String,
+        column: Column,
+    },
+    ColumnRemoved {
+        table: String,
+        column: Column,
+    },
+    ColumnChanged {
+        table: String,
+        old: Column,
+        new: Column,
+    },
+    IndexAdded {
+        table: String,
+        name: String,
+        definition: String,
+    },
+    IndexRemoved {
+        table: String,
+        name: String,
+        definition: String,
+    },
+    IndexChanged {
+        table: String,
+        name: String,
+        old: String,
+        new: String,
+    },
+    ConstraintAdded {
+        table: String,
+        name: String,
+        definition: String,
+    },
+    ConstraintRemoved {
+        table: String,
+        name: String,
+        definition: String,
+    },
+    ConstraintChanged {
+        table: String,
+        name: String,
+        old: String,
+        new: String,
+    },
+}
+
+impl SchemaChange {
+    /// The table affected by the change
+    pub fn table(&self) -> &str {
+        match self {
+            SchemaChange::TableAdded { table }
+            | SchemaChange::TableRemoved { table }
+            | SchemaChange::ColumnAdded { table, .. }
+            | SchemaChange::ColumnRemoved { table, .. }
+            | SchemaChange::ColumnChanged { table, .. }
+            | SchemaChange::IndexAdded { table, .. }
+            | SchemaChange::IndexRemoved { table, .. }
+            | SchemaChange::IndexChanged { table, .. }
+            | SchemaChange::ConstraintAdded { table, .. }
+            | SchemaChange::ConstraintRemoved { table, .. }
+            | SchemaChange::ConstraintChanged { table, .. } => table,
+        }
+    }
+}
+
+impl fmt::Display for SchemaChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaChange::TableAdded { table } => write!(f, "table {} was added", table),
+            SchemaChange::TableRemoved { table } => write!(f, "table {} was removed", table),
+            SchemaChange::ColumnAdded { table, column } => write!(
+                f,
+                "column {}.{} ({}) was added",
+                table, column.name, column.data_type
+            ),
+            SchemaChange::ColumnRemoved { table, column } => write!(
+                f,
+                "column {}.{} ({}) was removed",
+                table, column.name, column.data_type
+            ),
+            SchemaChange::ColumnChanged { table, old, new } => {
+                let mut changes = Vec::new();
+                if old.data_type != new.data_type {
+                    changes.push(format!("type {} -> {}", old.data_type, new.data_type));
+                }
+                if old.not_null != new.not_null {
+                    changes.push(if new.not_null {
+                        "now NOT NULL".to_string()
+                    } else {
+                        "now nullable".to_string()
+                    });
+                }
+                if old.default_expr != new.default_expr {
+                    changes.push(format!(
+                        "default {} -> {}",
+                        old.default_expr.as_deref().unwrap_or("none"),
+                        new.default_expr.as_deref().unwrap_or("none")
+                    ));
+                }
+                write!(
+                    f,
+                    "column {}.{} changed: {}",
+                    table,
+                    old.name,
+                    changes.join(", ")
+                )
+            }
+            SchemaChange::IndexAdded { name, .. } => write!(f, "index {} was added", name),
+            SchemaChange::IndexRemoved { name, .. } => write!(f, "index {} was removed", name),
+            SchemaChange::IndexChanged { name, old, new, .. } => {
+                write!(f, "index {} changed from `{}` to `{}`", name, old, new)
+            }
+            SchemaChange::ConstraintAdded { table, name, .. } => {
+                write!(f, "constraint {} on {} was added", name, table)
+            }
+            SchemaChange::ConstraintRemoved { table, name, .. } => {
+                write!(f, "constraint {} on {} was removed", name, table)
+            }
+            SchemaChange::ConstraintChanged {
+                table,
+                name,
+                old,
+                new,
+            } => write!(
+                f,
+                "constraint {} on {} changed from `{}` to `{}`",
+                name, table, old, new
+            ),
+        }
+    }
+}
+
+/// Returns all changes that turn `old` into `new`, ordered by table name
+pub fn diff(old: &Schema, new: &Schema) -> Vec<SchemaChange> {
+    let mut changes = Vec::new();
+    for (name, old_table) in &old.tables {
+        match new.table(name) {
+            Some(new_table) => diff_table(old_table, new_table, &mut changes),
+            None => changes.push(SchemaChange::TableRemoved {
+                table: name.clone(),
+            }),
+        }
+    }
+    for name in new.tables.keys() {
+        if old.table(name).is_none() {
+            changes.push(SchemaChange::TableAdded {
+                table: name.clone(),
+            });
+        }
+    }
+    changes.sort_by(|a, b| a.table().cmp(b.table()));
+    changes
+}
+
+fn diff_table(old: &Table, new: &Table, changes: &mut Vec<SchemaChange>) {
+    let table = || old.name.clone();
+
+    for old_column in &old.columns {
+        match new.column(&old_column.name) {
+            Some(new_column) if new_column != old_column => {
+                changes.push(SchemaChange::ColumnChanged {
+                    table: table(),
+                    old: old_column.clone(),
+                    new: new_column.clone(),
+                })
+            }
+            Some(_) => {}
+            None => changes.push(SchemaChange::ColumnRemoved {
+                table: table(),
+                column: old_column.clone(),
+            }),
+        }
+    }
+    for new_column in &new.columns {
+        if old.column(&new_column.name).is_none() {
+            changes.push(SchemaChange::ColumnAdded {
+                table: table(),
+                column: new_column.clone(),
+            });
+        }
+    }
+
+    diff_definitions(
+        &old.indexes,
+        &new.indexes,
+        |name, definition| SchemaChange::IndexAdded {
+            table: table(),
+            name,
+            definition,
+        },
+        |name, definition| SchemaChange::IndexRemoved {
+            table: table(),
+            name,
+            definition,
+        },
+        |name, old, new| SchemaChange::IndexChanged {
+            table: table(),
+            name,
+            old,
+            new,
+        },
+        changes,
+    );
+    diff_definitions(
+        &old.constraints,
+        &new.constraints,
+        |name, definition| SchemaChange::ConstraintAdded {
+            table: table(),
+            name,
+            definition,
+        },
+        |name, definition| SchemaChange::ConstraintRemoved {
+            table: table(),
+            name,
+            definition,
+        },
+        |name, old, new| SchemaChange::ConstraintChanged {
+            table: table(),
+            name,
+            old,
+            new,
+        },
+        changes,
+    );
+}
+
+/// Compares named definitions such as indexes or constraints
+fn diff_definitions(
+    old: &BTreeMap<String, String>,
+    new: &BTreeMap<String, String>,
+    added: impl Fn(String, String) -> SchemaChange,
+    removed: impl Fn(String, String) -> SchemaChange,
+    changed: impl Fn(String, String, String) -> SchemaChange,
+    changes: &mut Vec<SchemaChange>,
+) {
+    for (name, old_definition) in old {
+        match new.get(name) {
+            Some(new_definition) if new_definition != old_definition => changes.push(changed(
+                name.clone(),
+                old_definition.clone(),
+                new_definition.clone(),
+            )),
+            Some(_) => {}
+            None => changes.push(removed(name.clone(), old_definition.clone())),
+        }
+    }
+    for (name, new_definition) in new {
+        if !old.contains_key(name) {
+            changes.push(added(name.clone(), new_definition.clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(name: &str, data_type: &str) -> Column {
+        Column {
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            not_null: false,
+            default_expr: None,
+        }
+    }
+
+    fn schema(name: &str, tables: Vec<(&str, Vec<Column>)>) -> Schema {
+        let mut schema = Schema::new(name);
+        for (table, columns) in tables {
+            schema.tables.insert(
+                table.to_string(),
+                Table {
+                    name: table.to_string(),
+                    columns,
+                    ..Table::default()
+                },
+            );
+        }
+        schema
+    }
+
+    #[test]
+    fn test_same_structure() {
+        let a = schema("a", vec![("orders", vec![column("id", "integer")])]);
+        let b = schema("b", vec![("orders", vec![column("id", "integer")])]);
+        assert!(diff(&a, &b).is_empty());
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut old = schema(
+            "old",
+            vec![
+                (
+                    "orders",
+                    vec![column("id", "integer"), column("total", "numeric")],
+                ),
+                ("invoices", vec![column("id", "integer")]),
+            ],
+        );
+        old.tables.get_mut("orders").unwrap().indexes.insert(
+            "orders_total_idx".to_string(),
+            "CREATE INDEX orders_total_idx ON orders USING btree (total)".to_string(),
+        );
+        let new = schema(
+            "new",
+            vec![
+                (
+                    "orders",
+                    vec![
+                        column("id", "bigint"),
+                        column("total", "numeric"),
+                        column("note", "text"),
+                    ],
+                ),
+                ("customers", vec![column("id", "integer")]),
+            ],
+        );
+
+        let changes = diff(&old, &new);
+        assert_eq!(
+            changes.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+            vec![
+                "table customers was added",
+                "table invoices was removed",
+                "column orders.id changed: type integer -> bigint",
+                "column orders.note (text) was added",
+                "index orders_total_idx was removed",
+            ]
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Expanding selections along the syntax tree.
+//!
+//! A selection grows from the token at the cursor to the nodes around it, e.g. an expression, then
+//! to the clause of the statement it is part of, e.g. `WHERE a = 1`, and finally to the statement
+//! and the whole document. A clause is not a node of its own, so it is derived from the children
+//! of the statement: it starts at the keywords before the node, e.g. `ORDER BY`, and ends before
+//! the keywords of the next clause.
+
+use cstree::syntax::ResolvedNode;
+use cstree::text::{TextRange, TextSize};
+use parser::SyntaxKind;
+
+/// Returns the ranges that a selection at `offset` expands to, from the innermost to the whole
+/// document, each strictly containing the one before
+pub fn selection_ranges(cst: &ResolvedNode<SyntaxKind>, offset: TextSize) -> Vec<TextRange> {
+    let tokens = cst
+        .descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| token.text_range().contains_inclusive(offset))
+        .collect::<Vec<_>>();
+    let Some(token) = tokens
+        .iter()
+        .find(|token| !token.kind().is_trivia())
+        .or(tokens.first())
+    else {
+        return vec![cst.text_range()];
+    };
+
+    let mut ranges = vec![token.text_range()];
+    let mut child = None::<&ResolvedNode<SyntaxKind>>;
+    // the root is the whole document, including its trivia
+    for node in token
+        .parent()
+        .ancestors()
+        .take_while(|node| node.parent().is_some())
+    {
+        if node.kind().stmt_kind().is_some() {
+            if let Some(child) = child {
+                ranges.extend(clause_range(node, child));
+            }
+        }
+        ranges.extend(trimmed_range(node));
+        child = Some(node);
+    }
+    ranges.push(cst.text_range());
+
+    // nodes that only wrap another one, e.g. a statement around its expression, add nothing
+    let mut expanding = Vec::<TextRange>::new();
+    for range in ranges {
+        if expanding
+            .last()
+            .iter()
+            .all(|last| range.contains_range(**last) && range != **last)
+        {
+            expanding.push(range);
+        }
+    }
+    expanding
+}
+
+/// Returns the range of the clause of `stmt` that its child `node` is part of
+fn clause_range(
+    stmt: &ResolvedNode<SyntaxKind>,
+    node: &ResolvedNode<SyntaxKind>,
+) -> Option<TextRange> {
+    // the range of every child with whether it is a keyword, which only tokens are
+    let elements = stmt
+        .children_with_tokens()
+        .filter(|element| !element.kind().is_trivia() && element.kind() != SyntaxKind::Ascii59)
+        .map(|element| {
+            (
+                element.text_range(),
+                element.kind().keyword_category().is_some(),
+            )
+        })
+        .collect::<Vec<_>>();
+    let idx = elements
+        .iter()
+        .position(|(range, _)| *range == node.text_range())?;
+
+    // back over the other parts of the clause, e.g. the items of a list, and then its keywords
+    let mut start = idx;
+    while start > 0 && !elements[start - 1].1 {
+        start -= 1;
+    }
+    while start > 0 && elements[start - 1].1 {
+        start -= 1;
+    }
+    let end = elements[idx..]
+        .iter()
+        .position(|(_, is_keyword)| *is_keyword)
+        .map_or(elements.len(), |len| idx + len);
+    Some(TextRange::new(
+        elements[start].0.start(),
+        elements[end - 1].0.end(),
+    ))
+}
+
+/// Returns the range of `node` without leading and trailing trivia and the `;` of a statement, if
+/// it has any other token
+fn trimmed_range(node: &ResolvedNode<SyntaxKind>) -> Option<TextRange> {
+    let mut tokens = node
+        .descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| !token.kind().is_trivia() && token.kind() != SyntaxKind::Ascii59);
+    let first = tokens.next()?.text_range();
+    let last = tokens.last().map_or(first, |token| token.text_range());
+    Some(first.cover(last))
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    fn selections(input: &str, at: &str) -> Vec<String> {
+        let parse = parse_source(input);
+        let offset = TextSize::try_from(input.find(at).unwrap()).unwrap();
+        selection_ranges(&parse.cst, offset)
+            .into_iter()
+            .map(|range| input[range].to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_selection_ranges() {
+        let input = "select 1;\nselect id, email from contact where id = 1 order by email;";
+        let ranges = selections(input, "id = 1");
+        assert_eq!(ranges.first().map(String::as_str), Some("id"));
+        assert!(ranges.contains(&"id = 1".to_string()));
+        assert!(ranges.contains(&"where id = 1".to_string()));
+        assert_eq!(
+            &ranges[ranges.len() - 2..],
+            [
+                "select id, email from contact where id = 1 order by email".to_string(),
+                input.to_string(),
+            ]
+        );
+
+        let ranges = selections(input, "email from");
+        assert!(ranges.contains(&"select id, email".to_string()));
+    }
+}
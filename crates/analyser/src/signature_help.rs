@@ -0,0 +1,270 @@
+//! Signatures of the function call around the cursor.
+//!
+//! Like completion, the call is found from the tokens before the cursor, because the statement is
+//! usually incomplete while it is being typed: every opening parenthesis after a name starts a
+//! call, and commas at its level separate the arguments. An argument passed by name, e.g.
+//! `years => 1`, selects the parameter of that name instead of the one at its position.
+
+use std::ops::Range;
+
+use cstree::syntax::ResolvedNode;
+use cstree::text::{TextRange, TextSize};
+use parser::SyntaxKind;
+
+use crate::function::argument_name;
+use crate::rename::normalize_identifier;
+use crate::Function;
+
+/// A function call that the cursor is within
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Call {
+    /// The schema that qualifies the function name, if any
+    pub schema: Option<String>,
+    pub name: String,
+    /// The position of the argument at the cursor
+    pub argument: usize,
+    /// The name of the argument at the cursor if it is passed as `name => value`
+    pub named_argument: Option<String>,
+}
+
+/// An overload of the called function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    /// The signature with the result type, e.g. `left(text, integer) → text`
+    pub label: String,
+    /// The byte ranges of the parameters within `label`
+    pub parameters: Vec<Range<usize>>,
+    /// The parameter of the argument at the cursor, if the overload has one
+    pub active_parameter: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureHelp {
+    pub signatures: Vec<Signature>,
+    /// The first overload that has a parameter for the argument at the cursor
+    pub active_signature: usize,
+}
+
+/// Returns the innermost function call around `offset`
+pub fn call_at(cst: &ResolvedNode<SyntaxKind>, offset: TextSize) -> Option<Call> {
+    // a call for every open parenthesis, or `None` if the parenthesis does not follow a name
+    let mut calls: Vec<Option<Call>> = Vec::new();
+    // the kinds and texts of the significant tokens before the current one, the last one first
+    let mut previous: Vec<(SyntaxKind, &str)> = Vec::new();
+    for token in cst
+        .descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+    {
+        if token.text_range().start() >= offset {
+            break;
+        }
+        let kind = token.kind();
+        if kind.is_trivia() {
+            continue;
+        }
+        let is_name =
+            |(kind, _): &(SyntaxKind, &str)| *kind == SyntaxKind::Ident || kind.is_keyword();
+        match kind {
+            SyntaxKind::Ascii59 => calls.clear(),
+            SyntaxKind::Ascii40 => {
+                let call = previous.first().filter(|t| is_name(t)).map(|(_, name)| {
+                    let schema = match previous.get(1..3) {
+                        Some([(_, "."), schema]) if is_name(schema) => {
+                            Some(normalize_identifier(schema.1))
+                        }
+                        _ => None,
+                    };
+                    Call {
+                        schema,
+                        name: normalize_identifier(name),
+                        argument: 0,
+                        named_argument: None,
+                    }
+                });
+                calls.push(call);
+            }
+            SyntaxKind::Ascii41 => {
+                calls.pop();
+            }
+            SyntaxKind::Ascii44 => {
+                if let Some(Some(call)) = calls.last_mut() {
+                    call.argument += 1;
+                    call.named_argument = None;
+                }
+            }
+            _ if token.text() == "=>" => {
+                if let (Some(Some(call)), Some(name)) =
+                    (calls.last_mut(), previous.first().filter(|t| is_name(t)))
+                {
+                    call.named_argument = Some(normalize_identifier(name.1));
+                }
+            }
+            _ => {}
+        }
+        previous.insert(0, (kind, token.text()));
+        previous.truncate(3);
+    }
+    calls.into_iter().rev().flatten().next()
+}
+
+/// Returns the range of the function name at `offset` together with the call that it starts, if
+/// the name is followed by an opening parenthesis
+pub fn function_name_at(
+    cst: &ResolvedNode<SyntaxKind>,
+    offset: TextSize,
+) -> Option<(TextRange, Call)> {
+    let mut tokens = cst
+        .descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .skip_while(|token| {
+            let is_name = token.kind() == SyntaxKind::Ident || token.kind().is_keyword();
+            !(is_name && token.text_range().contains_inclusive(offset))
+        });
+    let range = tokens.next()?.text_range();
+    let paren = tokens
+        .find(|token| !token.kind().is_trivia())
+        .filter(|token| token.kind() == SyntaxKind::Ascii40)?;
+    Some((range, call_at(cst, paren.text_range().end())?))
+}
+
+/// Returns the overloads of `functions` that `call` may refer to, or `None` if there are none
+pub fn signature_help(call: &Call, functions: &[Function]) -> Option<SignatureHelp> {
+    let signatures = functions
+        .iter()
+        .filter(|f| f.name == call.name && call.schema.iter().all(|s| *s == f.schema_name))
+        .map(|f| signature(call, f))
+        .collect::<Vec<_>>();
+    if signatures.is_empty() {
+        return None;
+    }
+    let active_signature = signatures
+        .iter()
+        .position(|s| s.active_parameter.is_some())
+        .unwrap_or(0);
+    Some(SignatureHelp {
+        signatures,
+        active_signature,
+    })
+}
+
+fn signature(call: &Call, function: &Function) -> Signature {
+    let arguments = function.argument_list();
+    let mut label = format!("{}(", function.name);
+    let mut parameters = Vec::new();
+    for (idx, argument) in arguments.iter().enumerate() {
+        if idx > 0 {
+            label.push_str(", ");
+        }
+        parameters.push(label.len()..label.len() + argument.len());
+        label.push_str(argument);
+    }
+    label.push_str(&format!(") → {}", function.result_type));
+
+    let active_parameter = match &call.named_argument {
+        Some(name) => arguments
+            .iter()
+            .position(|a| argument_name(a) == Some(name.as_str())),
+        None if call.argument < arguments.len() => Some(call.argument),
+        // any further arguments are passed to the variadic parameter
+        None if function.is_variadic() => Some(arguments.len() - 1),
+        None => None,
+    };
+    Signature {
+        label,
+        parameters,
+        active_parameter,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    fn call(input: &str) -> Option<Call> {
+        let offset = TextSize::from(input.find('|').unwrap() as u32);
+        let text = input.replace('|', "");
+        call_at(&parse_source(&text).cst, offset)
+    }
+
+    fn function(name: &str, arguments: &str) -> Function {
+        Function {
+            schema_name: "pg_catalog".to_string(),
+            name: name.to_string(),
+            arguments: arguments.to_string(),
+            result_type: "text".to_string(),
+            is_aggregate: false,
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn test_call_at() {
+        assert_eq!(
+            call("select left(name, |"),
+            Some(Call {
+                schema: None,
+                name: "left".to_string(),
+                argument: 1,
+                named_argument: None,
+            })
+        );
+        assert_eq!(
+            call("select lower(trim(name), |)").map(|c| (c.name, c.argument)),
+            Some(("lower".to_string(), 1))
+        );
+        assert_eq!(
+            call("select app.f(1, (2 + 3)|").map(|c| (c.schema, c.argument)),
+            Some((Some("app".to_string()), 1))
+        );
+        assert_eq!(
+            call("select make_interval(days => |").and_then(|c| c.named_argument),
+            Some("days".to_string())
+        );
+        assert_eq!(call("select lower(name) |"), None);
+        assert_eq!(
+            function_name_at(&parse_source("select app.f(1);").cst, TextSize::from(12))
+                .map(|(range, c)| (range, c.schema, c.name)),
+            Some((
+                TextRange::new(11.into(), 12.into()),
+                Some("app".to_string()),
+                "f".to_string()
+            ))
+        );
+        assert_eq!(call("select lower(name); select |"), None);
+    }
+
+    #[test]
+    fn test_signature_help() {
+        let functions = [
+            function("left", "text, integer"),
+            function("concat", "VARIADIC \"any\""),
+            function(
+                "make_interval",
+                "years integer, months integer, days integer",
+            ),
+        ];
+        let help = |input: &str| signature_help(&call(input).unwrap(), &functions).unwrap();
+
+        let left = help("select left(name, |");
+        let signature = &left.signatures[0];
+        assert_eq!(signature.label, "left(text, integer) → text");
+        assert_eq!(&signature.label[signature.parameters[1].clone()], "integer");
+        assert_eq!(signature.active_parameter, Some(1));
+
+        assert_eq!(
+            help("select concat(a, b, |").signatures[0].active_parameter,
+            Some(0)
+        );
+        assert_eq!(
+            help("select make_interval(days => |").signatures[0].active_parameter,
+            Some(2)
+        );
+        assert_eq!(
+            help("select left(a, 1, |").signatures[0].active_parameter,
+            None
+        );
+        assert!(signature_help(&call("select coalesce(|").unwrap(), &functions).is_none());
+    }
+}
@@ -0,0 +1,447 @@
+//! Spell check of comments and of the identifiers of DDL statements.
+//!
+//! A typo in the name of a column is hard to fix once the schema is deployed, since every query
+//! has to keep it. Identifiers are split into words at underscores and at the humps of camelCase,
+//! so that `recieved_at` and `recievedAt` both contain `recieved`. Only the names that DDL
+//! statements define are checked, since queries and references have to use the names of the schema
+//! as they are. Common misspellings are always reported. With a dictionary, every word that it
+//! does not contain is reported, with the closest word of the dictionary as suggestion.
+
+use std::collections::{BTreeMap, HashSet};
+
+use cstree::syntax::ResolvedNode;
+use cstree::text::{TextRange, TextSize};
+use parser::{RawStmt, SyntaxKind};
+use pg_query::protobuf::Node;
+use pg_query::NodeEnum;
+
+use crate::lint::{LintDiagnostic, Severity};
+use crate::migrations::alter_table_cmds;
+use crate::rename::normalize_identifier;
+use crate::utils::string_value;
+
+pub const MISSPELLING: &str = "misspelling";
+
+/// Common misspellings of English words with their correct spelling
+const MISSPELLINGS: &[(&str, &str)] = &[
+    ("accomodate", "accommodate"),
+    ("accross", "across"),
+    ("acheive", "achieve"),
+    ("acheived", "achieved"),
+    ("adress", "address"),
+    ("adresses", "addresses"),
+    ("ammount", "amount"),
+    ("aquire", "acquire"),
+    ("arguement", "argument"),
+    ("begining", "beginning"),
+    ("beleive", "believe"),
+    ("buisness", "business"),
+    ("calender", "calendar"),
+    ("catagory", "category"),
+    ("collegue", "colleague"),
+    ("commited", "committed"),
+    ("commiting", "committing"),
+    ("completly", "completely"),
+    ("craeted", "created"),
+    ("curreny", "currency"),
+    ("definately", "definitely"),
+    ("delted", "deleted"),
+    ("dependancy", "dependency"),
+    ("descripton", "description"),
+    ("enviroment", "environment"),
+    ("existance", "existence"),
+    ("explaination", "explanation"),
+    ("foriegn", "foreign"),
+    ("goverment", "government"),
+    ("happend", "happened"),
+    ("immediatly", "immediately"),
+    ("independant", "independent"),
+    ("lastest", "latest"),
+    ("lenght", "length"),
+    ("maintainance", "maintenance"),
+    ("neccessary", "necessary"),
+    ("noticable", "noticeable"),
+    ("occured", "occurred"),
+    ("occurence", "occurrence"),
+    ("occuring", "occurring"),
+    ("paramater", "parameter"),
+    ("pasword", "password"),
+    ("persistant", "persistent"),
+    ("prefered", "preferred"),
+    ("priviledge", "privilege"),
+    ("publically", "publicly"),
+    ("recieve", "receive"),
+    ("recieved", "received"),
+    ("reciever", "receiver"),
+    ("recieving", "receiving"),
+    ("recipt", "receipt"),
+    ("recomend", "recommend"),
+    ("refered", "referred"),
+    ("referance", "reference"),
+    ("relevent", "relevant"),
+    ("responce", "response"),
+    ("retreive", "retrieve"),
+    ("retreived", "retrieved"),
+    ("seperate", "separate"),
+    ("seperated", "separated"),
+    ("seperator", "separator"),
+    ("shedule", "schedule"),
+    ("sheduled", "scheduled"),
+    ("succesful", "successful"),
+    ("sucess", "success"),
+    ("sucessful", "successful"),
+    ("timestmap", "timestamp"),
+    ("transfered", "transferred"),
+    ("udpated", "updated"),
+    ("untill", "until"),
+    ("upated", "updated"),
+    ("wierd", "weird"),
+];
+
+/// Words of Postgres and of schemas that are not in the dictionaries of a language, e.g. the
+/// names of types and common abbreviations
+const POSTGRES_WORDS: &[&str] = &[
+    "api",
+    "bigint",
+    "bigserial",
+    "bool",
+    "bpchar",
+    "bytea",
+    "cidr",
+    "citext",
+    "fkey",
+    "hstore",
+    "idx",
+    "ids",
+    "inet",
+    "int",
+    "jsonb",
+    "macaddr",
+    "oid",
+    "pkey",
+    "plpgsql",
+    "seq",
+    "smallint",
+    "sql",
+    "timestamptz",
+    "timetz",
+    "tsquery",
+    "tsvector",
+    "uri",
+    "url",
+    "utc",
+    "uuid",
+    "varchar",
+];
+
+/// The correctly spelled words
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dictionary {
+    /// The words in lower case
+    words: HashSet<String>,
+    /// The same words by their number of characters, so that only the words of a similar length
+    /// are candidates for a suggestion
+    by_length: BTreeMap<usize, Vec<String>>,
+}
+
+impl Dictionary {
+    /// Adds the words of `text`, one per line. Empty lines and lines that start with `#` are
+    /// skipped.
+    pub fn add_words(&mut self, text: &str) {
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .for_each(|word| self.add_word(word));
+    }
+
+    pub fn add_word(&mut self, word: &str) {
+        let word = word.to_lowercase();
+        if self.words.insert(word.clone()) {
+            self.by_length
+                .entry(word.chars().count())
+                .or_default()
+                .push(word);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Returns the word of the dictionary that is closest to `word`, if one is close enough to
+    /// be a misspelling of it
+    fn suggestion(&self, word: &str) -> Option<&str> {
+        let length = word.chars().count();
+        let max_distance = if length <= 4 { 1 } else { 2 };
+        self.by_length
+            .range(length.saturating_sub(max_distance)..=length + max_distance)
+            .flat_map(|(_, candidates)| candidates)
+            .filter_map(|candidate| {
+                let distance = edit_distance(word, candidate);
+                (distance <= max_distance).then_some((distance, candidate.as_str()))
+            })
+            .min()
+            .map(|(_, candidate)| candidate)
+    }
+}
+
+/// Reports the misspelled words of the comments of a file and of the names that its DDL
+/// statements define
+pub fn check_spelling(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmts: &[RawStmt],
+    dictionary: &Dictionary,
+) -> Vec<LintDiagnostic> {
+    let definitions = stmts
+        .iter()
+        .map(|stmt| (stmt.range, defined_names(&stmt.stmt)))
+        .filter(|(_, names)| !names.is_empty())
+        .collect::<Vec<_>>();
+    cst.descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| match token.kind() {
+            SyntaxKind::LineComment | SyntaxKind::BlockComment => true,
+            SyntaxKind::Ident => definitions.iter().any(|(range, names)| {
+                range.contains_range(token.text_range())
+                    && names.contains(&normalize_identifier(token.text()))
+            }),
+            _ => false,
+        })
+        .flat_map(|token| {
+            let start = token.text_range().start();
+            words(token.text())
+                .into_iter()
+                .filter_map(|(offset, word)| {
+                    let message = misspelling(word, dictionary)?;
+                    let start = start + TextSize::from(offset as u32);
+                    Some(LintDiagnostic {
+                        rule: MISSPELLING,
+                        message,
+                        severity: Severity::Information,
+                        range: TextRange::at(start, TextSize::of(word)),
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Returns the names of the objects, columns, constraints and parameters that `stmt` defines
+fn defined_names(stmt: &NodeEnum) -> HashSet<String> {
+    let mut names = HashSet::new();
+    let mut add = |name: &str| {
+        if !name.is_empty() {
+            names.insert(name.to_string());
+        }
+    };
+    match stmt {
+        NodeEnum::CreateStmt(n) => {
+            n.relation.iter().for_each(|r| add(&r.relname));
+            n.table_elts.iter().for_each(|e| add(&element_name(e)));
+        }
+        NodeEnum::CreateForeignTableStmt(n) => {
+            if let Some(n) = &n.base_stmt {
+                n.relation.iter().for_each(|r| add(&r.relname));
+                n.table_elts.iter().for_each(|e| add(&element_name(e)));
+            }
+        }
+        NodeEnum::AlterTableStmt(n) => alter_table_cmds(n)
+            .filter_map(|cmd| cmd.def.as_deref())
+            .for_each(|def| add(&element_name(def))),
+        NodeEnum::RenameStmt(n) => add(&n.newname),
+        NodeEnum::ViewStmt(n) => {
+            n.view.iter().for_each(|r| add(&r.relname));
+            n.aliases.iter().filter_map(string_value).for_each(add);
+        }
+        NodeEnum::CreateTableAsStmt(n) => {
+            if let Some(into) = &n.into {
+                into.rel.iter().for_each(|r| add(&r.relname));
+                into.col_names.iter().filter_map(string_value).for_each(add);
+            }
+        }
+        NodeEnum::IndexStmt(n) => add(&n.idxname),
+        NodeEnum::CreateFunctionStmt(n) => {
+            add(last_name(&n.funcname));
+            n.parameters.iter().for_each(|p| {
+                if let Some(NodeEnum::FunctionParameter(p)) = p.node.as_ref() {
+                    add(&p.name);
+                }
+            });
+        }
+        NodeEnum::CreateSeqStmt(n) => n.sequence.iter().for_each(|r| add(&r.relname)),
+        NodeEnum::CompositeTypeStmt(n) => {
+            n.typevar.iter().for_each(|r| add(&r.relname));
+            n.coldeflist.iter().for_each(|e| add(&element_name(e)));
+        }
+        NodeEnum::CreateEnumStmt(n) => add(last_name(&n.type_name)),
+        NodeEnum::CreateRangeStmt(n) => add(last_name(&n.type_name)),
+        NodeEnum::CreateDomainStmt(n) => add(last_name(&n.domainname)),
+        NodeEnum::CreateSchemaStmt(n) => add(&n.schemaname),
+        NodeEnum::CreateTrigStmt(n) => add(&n.trigname),
+        NodeEnum::CreatePolicyStmt(n) => add(&n.policy_name),
+        _ => {}
+    }
+    names
+}
+
+/// Returns the unqualified name of an object whose name is given as a list of strings
+fn last_name(names: &[Node]) -> &str {
+    names.last().and_then(string_value).unwrap_or_default()
+}
+
+/// Returns the name of a column or constraint of a table, or an empty string for other elements
+fn element_name(element: &Node) -> String {
+    match element.node.as_ref() {
+        Some(NodeEnum::ColumnDef(c)) => c.colname.clone(),
+        Some(NodeEnum::Constraint(c)) => c.conname.clone(),
+        _ => String::new(),
+    }
+}
+
+/// Returns why `word` is misspelled, or `None` if it is not
+fn misspelling(word: &str, dictionary: &Dictionary) -> Option<String> {
+    let lower = word.to_lowercase();
+    if dictionary.words.contains(&lower) || POSTGRES_WORDS.contains(&lower.as_str()) {
+        return None;
+    }
+    if let Some((_, correct)) = MISSPELLINGS.iter().find(|(wrong, _)| *wrong == lower) {
+        return Some(format!("{} is a misspelling of {}", word, correct));
+    }
+    // short words and acronyms are mostly abbreviations
+    let is_acronym = word.len() > 1 && word.chars().all(|c| c.is_uppercase());
+    if dictionary.is_empty() || word.chars().count() < 3 || is_acronym {
+        return None;
+    }
+    Some(match dictionary.suggestion(&lower) {
+        Some(suggestion) => format!(
+            "{} is not in the dictionary. Did you mean {}?",
+            word, suggestion
+        ),
+        None => format!("{} is not in the dictionary", word),
+    })
+}
+
+/// Returns the words of `text` with their byte offsets. Words are split at every character that
+/// is not a letter, and at the humps of camelCase, e.g. `recievedAt` into `recieved` and `At`.
+fn words(text: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let chars = text.char_indices().collect::<Vec<_>>();
+    for (idx, &(offset, c)) in chars.iter().enumerate() {
+        if !c.is_alphabetic() {
+            if let Some(start) = start.take() {
+                words.push((start, &text[start..offset]));
+            }
+            continue;
+        }
+        let Some(word_start) = start else {
+            start = Some(offset);
+            continue;
+        };
+        let previous = chars[idx - 1].1;
+        let next = chars.get(idx + 1).map(|(_, c)| *c);
+        // `aB` and the `Bc` of `ABc` start a word
+        let is_hump = c.is_uppercase()
+            && (previous.is_lowercase()
+                || (previous.is_uppercase() && next.is_some_and(char::is_lowercase)));
+        if is_hump {
+            words.push((word_start, &text[word_start..offset]));
+            start = Some(offset);
+        }
+    }
+    if let Some(start) = start {
+        words.push((start, &text[start..]));
+    }
+    words
+}
+
+/// Returns the number of insertions, deletions, substitutions and transpositions of adjacent
+/// characters that turn `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    // the distances of the prefixes of `a` to the prefixes of `b`, row by row
+    let mut rows = vec![(0..=b.len()).collect::<Vec<_>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (rows[i - 1][j] + 1)
+                .min(row[j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    fn messages(input: &str, dictionary: &Dictionary) -> Vec<String> {
+        let parse = parse_source(input);
+        check_spelling(&parse.cst, &parse.stmts, dictionary)
+            .into_iter()
+            .map(|d| format!("{}: {}", &input[d.range], d.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_misspellings() {
+        assert_eq!(
+            messages(
+                "-- the adress of a contact
+create table contact (id bigint, recievedAt timestamptz, \"Seperator\" text);
+select recieved_at from contact;",
+                &Dictionary::default()
+            ),
+            [
+                "adress: adress is a misspelling of address",
+                "recieved: recieved is a misspelling of received",
+                "Seperator: Seperator is a misspelling of separator"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dictionary() {
+        let mut dictionary = Dictionary::default();
+        dictionary.add_words("# the words of the tests\ncontact\nemail\nreceived\ntext\n");
+        assert_eq!(
+            messages(
+                "create table contact (emial text, received_at timestamptz, ID bigint);",
+                &dictionary
+            ),
+            ["emial: emial is not in the dictionary. Did you mean email?"]
+        );
+        // only the names that a statement defines are checked, not those it refers to
+        assert_eq!(
+            messages(
+                "create table contact (email_id bigint references contcat (id));
+alter table contcat rename column emial to email;",
+                &dictionary
+            ),
+            Vec::<String>::new()
+        );
+        assert_eq!(dictionary.suggestion("recieved"), Some("received"));
+        assert_eq!(dictionary.suggestion("tex"), Some("text"));
+        assert_eq!(dictionary.suggestion("em"), None);
+    }
+
+    #[test]
+    fn test_words() {
+        assert_eq!(
+            words("HTTPServer_recievedAt2"),
+            [(0, "HTTP"), (4, "Server"), (11, "recieved"), (19, "At")]
+        );
+        assert_eq!(edit_distance("recieved", "received"), 1);
+        assert_eq!(edit_distance("emial", "email"), 1);
+        assert_eq!(edit_distance("contact", "content"), 2);
+    }
+}
@@ -0,0 +1,232 @@
+//! Expansion of the `*` of a select list into the columns it stands for.
+//!
+//! The relations of the `FROM` clause are looked up in a schema model, so `*` is only expanded if
+//! all of them are known tables. Joins with `USING` or `NATURAL` merge their join columns, which a
+//! plain column list cannot express, so their `*` is left alone. If a query selects from several
+//! relations, every column is qualified by the alias or name of its relation, and the qualifier of
+//! e.g. `c.*` is kept as written. A `*` at the start of a line is expanded into one column per line.
+
+use std::collections::BTreeMap;
+
+use cstree::syntax::{ResolvedNode, ResolvedToken};
+use cstree::text::{TextRange, TextSize};
+use parser::make::quote_ident;
+use parser::{RawStmt, SyntaxKind};
+use pg_query::protobuf::{Node, RangeVar};
+use pg_query::NodeEnum;
+
+use crate::moniker::DEFAULT_SCHEMA;
+use crate::schema::{Column, Schema};
+use crate::utils::{descendants, string_value};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StarExpansion {
+    /// The range of the `*`, including its qualifier
+    pub range: TextRange,
+    /// The column list that replaces it
+    pub text: String,
+}
+
+/// Returns the columns that the `*` of the select list at `offset` stands for, or `None` if there
+/// is no `*` there or its relations are not all tables of `schemas`
+pub fn expand_star(
+    cst: &ResolvedNode<SyntaxKind>,
+    stmts: &[RawStmt],
+    offset: TextSize,
+    schemas: &BTreeMap<String, Schema>,
+) -> Option<StarExpansion> {
+    let stmt = stmts
+        .iter()
+        .find(|stmt| stmt.range.contains_inclusive(offset))?;
+    let tokens = cst
+        .descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| stmt.range.contains_range(token.text_range()))
+        .collect::<Vec<_>>();
+
+    descendants(&stmt.stmt).into_iter().find_map(|node| {
+        let NodeEnum::SelectStmt(select) = node else {
+            return None;
+        };
+        select.target_list.iter().find_map(|target| {
+            let NodeEnum::ResTarget(target) = target.node.as_ref()? else {
+                return None;
+            };
+            let NodeEnum::ColumnRef(column) = target.val.as_ref()?.node.as_ref()? else {
+                return None;
+            };
+            if !matches!(column.fields.last()?.node.as_ref()?, NodeEnum::AStar(_)) {
+                return None;
+            }
+            let start = stmt.range.start() + TextSize::from(column.location.max(0) as u32);
+            let star = tokens.iter().find(|token| {
+                token.kind() == SyntaxKind::Ascii42 && token.text_range().start() >= start
+            })?;
+            let range = TextRange::new(start, star.text_range().end());
+            if !range.contains_inclusive(offset) {
+                return None;
+            }
+
+            let mut relations = Vec::new();
+            from_relations(&select.from_clause, &mut relations);
+            let qualifier = column.fields.iter().filter_map(string_value).last();
+            let columns = match qualifier {
+                Some(qualifier) => {
+                    let relation = relations.iter().flatten().find(|r| {
+                        r.alias
+                            .as_ref()
+                            .map_or(r.relname == qualifier, |a| a.aliasname == qualifier)
+                    })?;
+                    // keep the qualifier as written, e.g. `"Contact".`
+                    let prefix = tokens
+                        .iter()
+                        .filter(|token| {
+                            token.text_range().start() >= start
+                                && token.text_range().end() <= star.text_range().start()
+                        })
+                        .map(|token| token.text())
+                        .collect::<String>();
+                    table_columns(relation, schemas)?
+                        .iter()
+                        .map(|c| format!("{}{}", prefix, quote_ident(&c.name)))
+                        .collect::<Vec<_>>()
+                }
+                None => {
+                    let relations = relations.into_iter().collect::<Option<Vec<_>>>()?;
+                    let is_qualified = relations.len() > 1;
+                    let mut columns = Vec::new();
+                    for relation in &relations {
+                        let prefix = if is_qualified {
+                            let name = relation
+                                .alias
+                                .as_ref()
+                                .map_or(&relation.relname, |a| &a.aliasname);
+                            format!("{}.", quote_ident(name))
+                        } else {
+                            String::new()
+                        };
+                        columns.extend(
+                            table_columns(relation, schemas)?
+                                .iter()
+                                .map(|c| format!("{}{}", prefix, quote_ident(&c.name))),
+                        );
+                    }
+                    columns
+                }
+            };
+            if columns.is_empty() {
+                return None;
+            }
+            Some(StarExpansion {
+                range,
+                text: columns.join(&separator(&tokens, start)),
+            })
+        })
+    })
+}
+
+/// Collects the relations of a `FROM` clause in their order, with `None` for the items that are
+/// not plain relations or that merge columns
+fn from_relations<'a>(
+    items: impl IntoIterator<Item = &'a Node>,
+    relations: &mut Vec<Option<RangeVar>>,
+) {
+    for item in items {
+        match item.node.as_ref() {
+            Some(NodeEnum::RangeVar(r)) => relations.push(Some(r.clone())),
+            Some(NodeEnum::JoinExpr(j)) => {
+                if j.is_natural || !j.using_clause.is_empty() {
+                    relations.push(None);
+                }
+                from_relations(
+                    j.larg.as_deref().into_iter().chain(j.rarg.as_deref()),
+                    relations,
+                );
+            }
+            _ => relations.push(None),
+        }
+    }
+}
+
+fn table_columns<'a>(
+    relation: &RangeVar,
+    schemas: &'a BTreeMap<String, Schema>,
+) -> Option<&'a [Column]> {
+    let schema = if relation.schemaname.is_empty() {
+        DEFAULT_SCHEMA
+    } else {
+        &relation.schemaname
+    };
+    Some(&schemas.get(schema)?.table(&relation.relname)?.columns)
+}
+
+/// Returns the separator of the columns: one per line if the `*` at `start` begins a line
+fn separator(tokens: &[&ResolvedToken<SyntaxKind>], start: TextSize) -> String {
+    let before = tokens
+        .iter()
+        .take_while(|token| token.text_range().end() <= start)
+        .collect::<Vec<_>>();
+    match before.as_slice() {
+        [.., newline, indent]
+            if newline.kind() == SyntaxKind::Newline && indent.kind() == SyntaxKind::Whitespace =>
+        {
+            format!(",\n{}", indent.text())
+        }
+        [.., newline] if newline.kind() == SyntaxKind::Newline => ",\n".to_string(),
+        _ => ", ".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+    use crate::migrations::MigrationState;
+
+    fn expansion(input: &str, at: &str) -> Option<StarExpansion> {
+        let parse = parse_source(input);
+        let offset = TextSize::try_from(input.rfind(at).unwrap()).unwrap();
+        let mut state = MigrationState::default();
+        state.replay(&parse.stmts);
+        expand_star(&parse.cst, &parse.stmts, offset, &state.schemas)
+    }
+
+    #[test]
+    fn test_expand_star() {
+        let ddl = "create table contact (id int, \"Name\" text);
+            create table orders (id int, contact_id int);";
+
+        let input = format!("{}\nselect * from contact;", ddl);
+        let star = expansion(&input, "*").unwrap();
+        assert_eq!(star.text, "id, \"Name\"");
+        assert_eq!(&input[star.range], "*");
+
+        let input = format!(
+            "{}\nselect\n    *\nfrom contact c join orders on orders.contact_id = c.id;",
+            ddl
+        );
+        assert_eq!(
+            expansion(&input, "*").unwrap().text,
+            "c.id,\n    c.\"Name\",\n    orders.id,\n    orders.contact_id"
+        );
+
+        let input = format!("{}\nselect c.*, 1 from contact c, orders;", ddl);
+        let star = expansion(&input, "*").unwrap();
+        assert_eq!(star.text, "c.id, c.\"Name\"");
+        assert_eq!(&input[star.range], "c.*");
+    }
+
+    #[test]
+    fn test_unknown_relations() {
+        let ddl = "create table contact (id int);";
+        for query in [
+            "select * from invoice;",
+            "select * from contact join contact c2 using (id);",
+            "select * from (select 1) s;",
+            "select count(*) from contact;",
+        ] {
+            assert_eq!(expansion(&format!("{}\n{}", ddl, query), "*"), None);
+        }
+    }
+}
@@ -0,0 +1,455 @@
+//! Schema changes that rewrite every row of an existing table.
+//!
+//! Since Postgres 11, adding a column with a default only stores the default in the catalog,
+//! unless the default is volatile, e.g. `random()` or `gen_random_uuid()`. Then it is evaluated
+//! for every row, which rewrites the table, and identity, serial and stored generated columns do
+//! the same. Changing the type of a column rewrites the table unless the old type can be read as
+//! the new one, such as `varchar(20)` as `varchar(50)` or `text`, or a cast of the migration state
+//! makes them binary coercible, e.g. one created `WITHOUT FUNCTION`. Both hold an `ACCESS EXCLUSIVE`
+//! lock for as long as the rewrite takes, so the estimated size of the table from
+//! [`TABLE_SIZES_QUERY`] is part of the warning. Tables that the same statements create are empty
+//! and not reported.
+
+use std::collections::BTreeSet;
+
+use cstree::text::{TextRange, TextSize};
+use parser::RawStmt;
+use pg_query::protobuf::a_const::Val;
+use pg_query::protobuf::{AlterTableStmt, ColumnDef, Node, TypeName};
+use pg_query::NodeEnum;
+
+use crate::cast_graph::CastGraph;
+use crate::lint::{LintDiagnostic, Severity};
+use crate::migrations::{alter_table_cmds, column_type, MigrationState};
+use crate::moniker::{qualified_name, DEFAULT_SCHEMA};
+use crate::utils::{descendants, string_value, type_name};
+
+const VOLATILE_COLUMN_DEFAULT: &str = "volatile-column-default";
+const COLUMN_TYPE_REWRITE: &str = "column-type-rewrite";
+
+/// `CONSTR_DEFAULT`, `CONSTR_IDENTITY` and `CONSTR_GENERATED`
+const DEFAULT: i32 = 3;
+const IDENTITY: i32 = 4;
+const GENERATED: i32 = 5;
+
+/// Built-in functions that are volatile and commonly used as column defaults
+const VOLATILE_FUNCTIONS: &[&str] = &[
+    "clock_timestamp",
+    "gen_random_uuid",
+    "nextval",
+    "random",
+    "timeofday",
+    "uuid_generate_v1",
+    "uuid_generate_v1mc",
+    "uuid_generate_v4",
+];
+
+/// Types whose modifier is a maximum that can be raised without a rewrite
+const LIMITED_TYPES: &[&str] = &[
+    "varchar",
+    "varbit",
+    "numeric",
+    "time",
+    "timetz",
+    "timestamp",
+    "timestamptz",
+    "interval",
+];
+
+/// Returns the estimated size of every table of the schemas given as a `text[]` in `$1` as
+/// [`TableSize`]s
+pub const TABLE_SIZES_QUERY: &str = "select
+    n.nspname as schema_name,
+    c.relname as table_name,
+    greatest(c.reltuples, 0)::int8 as row_estimate,
+    pg_catalog.pg_table_size(c.oid) as table_bytes
+from pg_catalog.pg_class c
+    join pg_catalog.pg_namespace n on n.oid = c.relnamespace
+where n.nspname = any($1)
+    and c.relkind in ('r', 'p', 'm')
+order by 1, 2";
+
+/// The estimated size of a table, as loaded with [`TABLE_SIZES_QUERY`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableSize {
+    pub schema_name: String,
+    pub table_name: String,
+    /// The number of rows as of the last `ANALYZE` or `VACUUM`
+    pub row_estimate: i64,
+    /// The size of the table including TOAST, but without indexes
+    pub table_bytes: i64,
+}
+
+impl TableSize {
+    /// Returns the name of the table as `schema.table`
+    pub fn qualified_name(&self) -> String {
+        format!("{}.{}", self.schema_name, self.table_name)
+    }
+}
+
+/// Reports the statements of `stmts` that rewrite a table of `state`, which is the schema before
+/// them, with the size of the table if it is one of `sizes`
+pub fn check_table_rewrites(
+    stmts: &[RawStmt],
+    state: &MigrationState,
+    sizes: &[TableSize],
+) -> Vec<LintDiagnostic> {
+    let mut state = state.clone();
+    let mut created = BTreeSet::new();
+    let mut diagnostics = Vec::new();
+    for stmt in stmts {
+        match &stmt.stmt {
+            NodeEnum::CreateStmt(n) => {
+                created.extend(n.relation.as_ref().map(qualified_name));
+            }
+            NodeEnum::AlterTableStmt(n) => {
+                let is_created = n
+                    .relation
+                    .as_ref()
+                    .is_some_and(|r| created.contains(&qualified_name(r)));
+                if !is_created {
+                    diagnostics.extend(alter_table_rewrites(stmt, n, &state, sizes));
+                }
+            }
+            _ => {}
+        }
+        state.replay(std::slice::from_ref(stmt));
+    }
+    diagnostics
+}
+
+fn alter_table_rewrites(
+    stmt: &RawStmt,
+    n: &AlterTableStmt,
+    state: &MigrationState,
+    sizes: &[TableSize],
+) -> Vec<LintDiagnostic> {
+    let Some(relation) = n.relation.as_ref() else {
+        return Vec::new();
+    };
+    let name = qualified_name(relation);
+    let size = sizes.iter().find(|size| size.qualified_name() == name);
+    let mut diagnostics = Vec::new();
+    for cmd in alter_table_cmds(n) {
+        let def = cmd.def.as_ref().and_then(|def| def.node.as_ref());
+        let (rule, rewrite, location) = match (cmd.subtype, def) {
+            // AtAddColumn
+            (1, Some(NodeEnum::ColumnDef(column))) => {
+                let Some(rewrite) = rewriting_column(column) else {
+                    continue;
+                };
+                (VOLATILE_COLUMN_DEFAULT, rewrite, column.location)
+            }
+            // AtAlterColumnType
+            (30, Some(NodeEnum::ColumnDef(column))) => {
+                let old = state
+                    .schemas
+                    .get(schema_name(&relation.schemaname))
+                    .and_then(|s| s.table(&relation.relname))
+                    .and_then(|t| t.column(&cmd.name))
+                    .map(|c| c.data_type.clone());
+                let (Some(old), Some(new)) = (old, column.type_name.as_ref()) else {
+                    continue;
+                };
+                let has_using = column.raw_default.is_some();
+                if !has_using && !type_change_rewrites(&old, new, &state.casts) {
+                    continue;
+                }
+                (
+                    COLUMN_TYPE_REWRITE,
+                    Rewrite::TypeChange(cmd.name.clone(), old, column_type(new)),
+                    // the column definition of the new type has no location
+                    relation.location,
+                )
+            }
+            _ => continue,
+        };
+        let offset = stmt.range.start() + TextSize::from(location.max(0) as u32);
+        diagnostics.push(LintDiagnostic {
+            rule,
+            message: rewrite.message(&name, size),
+            severity: Severity::Warning,
+            range: TextRange::empty(offset),
+        });
+    }
+    diagnostics
+}
+
+fn schema_name(schema: &str) -> &str {
+    if schema.is_empty() {
+        DEFAULT_SCHEMA
+    } else {
+        schema
+    }
+}
+
+/// Why a command of `ALTER TABLE` rewrites the table
+enum Rewrite {
+    /// Adding a column of a serial type, with the type, e.g. `bigserial`, and the column
+    Serial(String, String),
+    /// Adding an identity column
+    Identity(String),
+    /// Adding a stored generated column
+    Generated(String),
+    /// Adding a column whose default calls the volatile function
+    VolatileDefault(String, String),
+    /// Changing the type of the column from the old to the new type
+    TypeChange(String, String, String),
+}
+
+impl Rewrite {
+    /// Returns the warning about rewriting the table `name`, with its `size` if it is known
+    fn message(&self, name: &str, size: Option<&TableSize>) -> String {
+        let Some(size) = size else {
+            return match self {
+                Rewrite::Serial(serial, column) => format!(
+                    "adding the {} column {}, which is filled from a sequence, rewrites every row \
+                     of {} while it holds an ACCESS EXCLUSIVE lock",
+                    serial, column, name
+                ),
+                Rewrite::Identity(column) => format!(
+                    "adding the identity column {}, which is filled from a sequence, rewrites \
+                     every row of {} while it holds an ACCESS EXCLUSIVE lock",
+                    column, name
+                ),
+                Rewrite::Generated(column) => format!(
+                    "adding the stored generated column {} rewrites every row of {} while it \
+                     holds an ACCESS EXCLUSIVE lock",
+                    column, name
+                ),
+                Rewrite::VolatileDefault(column, function) => format!(
+                    "adding {} with the volatile default {}(), which is evaluated for every row, \
+                     rewrites every row of {} while it holds an ACCESS EXCLUSIVE lock",
+                    column, function, name
+                ),
+                Rewrite::TypeChange(column, old, new) => format!(
+                    "changing the type of {} from {} to {} rewrites every row of {} while it \
+                     holds an ACCESS EXCLUSIVE lock",
+                    column, old, new, name
+                ),
+            };
+        };
+        let (rows, megabytes) = (size.row_estimate, size.table_bytes / (1024 * 1024));
+        match self {
+            Rewrite::Serial(serial, column) => format!(
+                "adding the {} column {}, which is filled from a sequence, rewrites every row of \
+                 {} ({} rows, {} MB) while it holds an ACCESS EXCLUSIVE lock",
+                serial, column, name, rows, megabytes
+            ),
+            Rewrite::Identity(column) => format!(
+                "adding the identity column {}, which is filled from a sequence, rewrites every \
+                 row of {} ({} rows, {} MB) while it holds an ACCESS EXCLUSIVE lock",
+                column, name, rows, megabytes
+            ),
+            Rewrite::Generated(column) => format!(
+                "adding the stored generated column {} rewrites every row of {} ({} rows, {} MB) \
+                 while it holds an ACCESS EXCLUSIVE lock",
+                column, name, rows, megabytes
+            ),
+            Rewrite::VolatileDefault(column, function) => format!(
+                "adding {} with the volatile default {}(), which is evaluated for every row, \
+                 rewrites every row of {} ({} rows, {} MB) while it holds an ACCESS EXCLUSIVE lock",
+                column, function, name, rows, megabytes
+            ),
+            Rewrite::TypeChange(column, old, new) => format!(
+                "changing the type of {} from {} to {} rewrites every row of {} ({} rows, {} MB) \
+                 while it holds an ACCESS EXCLUSIVE lock",
+                column, old, new, name, rows, megabytes
+            ),
+        }
+    }
+}
+
+/// Returns why adding `column` rewrites the table, if it does
+fn rewriting_column(column: &ColumnDef) -> Option<Rewrite> {
+    let name = column.colname.clone();
+    if let Some(serial) = column
+        .type_name
+        .as_ref()
+        .and_then(type_name)
+        .filter(|t| matches!(t.as_str(), "serial" | "bigserial" | "smallserial"))
+    {
+        return Some(Rewrite::Serial(serial, name));
+    }
+    column
+        .constraints
+        .iter()
+        .find_map(|c| match c.node.as_ref()? {
+            NodeEnum::Constraint(c) if c.contype == IDENTITY => {
+                Some(Rewrite::Identity(name.clone()))
+            }
+            NodeEnum::Constraint(c) if c.contype == GENERATED => {
+                Some(Rewrite::Generated(name.clone()))
+            }
+            NodeEnum::Constraint(c) if c.contype == DEFAULT => {
+                let function = volatile_function(c.raw_expr.as_deref()?)?;
+                Some(Rewrite::VolatileDefault(name.clone(), function))
+            }
+            _ => None,
+        })
+}
+
+/// Returns the name of a volatile function that `expr` calls, if any
+fn volatile_function(expr: &Node) -> Option<String> {
+    descendants(expr.node.as_ref()?)
+        .into_iter()
+        .find_map(|node| match node {
+            NodeEnum::FuncCall(f) => {
+                let name = f.funcname.last().and_then(string_value)?.to_lowercase();
+                VOLATILE_FUNCTIONS.contains(&name.as_str()).then_some(name)
+            }
+            _ => None,
+        })
+}
+
+/// Returns true if changing a column of the type `old`, as the catalog or a migration names it,
+/// to `new` rewrites the table, given the casts of the database
+fn type_change_rewrites(old: &str, new: &TypeName, casts: &CastGraph) -> bool {
+    let Some((old_name, old_mods, old_array)) = parse_type(old) else {
+        return true;
+    };
+    let (new_name, new_mods, new_array) = type_parts(new);
+    if old_array != new_array {
+        return true;
+    }
+    match (old_name.as_str(), new_name.as_str()) {
+        ("varchar", "text") | ("text", "varchar") if new_mods.is_empty() => false,
+        ("cidr", "inet") => false,
+        (old, new) if old != new && new_mods.is_empty() && casts.is_binary_coercible(old, new) => {
+            false
+        }
+        (old, new) if old == new && LIMITED_TYPES.contains(&old) => {
+            if new_mods.is_empty() {
+                return false;
+            }
+            if old_mods.is_empty() || old_mods.len() != new_mods.len() {
+                return true;
+            }
+            // the scale of `numeric(precision, scale)` has to stay the same
+            if old == "numeric" && old_mods.get(1) != new_mods.get(1) {
+                return true;
+            }
+            new_mods[0] < old_mods[0]
+        }
+        (old, new) => old != new || old_mods != new_mods,
+    }
+}
+
+/// Returns the normalized name, the modifiers and whether it is an array of the type `data_type`,
+/// e.g. `character varying(20)`
+fn parse_type(data_type: &str) -> Option<(String, Vec<i32>, bool)> {
+    let stmt = pg_query::parse(&format!("select null::{}", data_type))
+        .ok()?
+        .protobuf
+        .stmts
+        .into_iter()
+        .next()?
+        .stmt?
+        .node?;
+    descendants(&stmt).into_iter().find_map(|node| match node {
+        NodeEnum::TypeName(t) => Some(type_parts(&t)),
+        _ => None,
+    })
+}
+
+fn type_parts(t: &TypeName) -> (String, Vec<i32>, bool) {
+    let mods = t
+        .typmods
+        .iter()
+        .filter_map(|m| match m.node.as_ref()? {
+            NodeEnum::AConst(c) => match &c.val {
+                Some(Val::Ival(i)) => Some(i.ival),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    (
+        type_name(t).unwrap_or_default(),
+        mods,
+        !t.array_bounds.is_empty(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    fn rewrites(input: &str) -> Vec<(&'static str, String)> {
+        let parse = parse_source(
+            "create table orders (id int, code varchar(20), total numeric(10,2), note text);",
+        );
+        let mut state = MigrationState::default();
+        state.replay(&parse.stmts);
+        let sizes = [TableSize {
+            schema_name: "public".to_string(),
+            table_name: "orders".to_string(),
+            row_estimate: 120_000,
+            table_bytes: 50 * 1024 * 1024,
+        }];
+        check_table_rewrites(&parse_source(input).stmts, &state, &sizes)
+            .into_iter()
+            .map(|d| (d.rule, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_volatile_column_default() {
+        assert_eq!(
+            rewrites("alter table orders add column token uuid default gen_random_uuid();"),
+            vec![(
+                VOLATILE_COLUMN_DEFAULT,
+                "adding token with the volatile default gen_random_uuid(), which is evaluated for every row, rewrites every row of public.orders (120000 rows, 50 MB) while it holds an ACCESS EXCLUSIVE lock".to_string()
+            )]
+        );
+        assert_eq!(
+            rewrites("alter table orders add column seq bigserial;")[0].0,
+            VOLATILE_COLUMN_DEFAULT
+        );
+        assert!(rewrites(
+            "alter table orders add column created_at timestamptz not null default now();
+            create table items (id int);
+            alter table items add column token uuid default gen_random_uuid();"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_column_type_rewrite() {
+        assert!(rewrites(
+            "alter table orders alter column code type varchar(50);
+            alter table orders alter column code type text;
+            alter table orders alter column total type numeric(12,2);"
+        )
+        .is_empty());
+        let found = rewrites(
+            "alter table orders alter column id type bigint;
+            alter table orders alter column total type numeric(12,4);
+            alter table orders alter column note type int using note::int;",
+        );
+        assert_eq!(found.len(), 3);
+        assert!(found[0]
+            .1
+            .starts_with("changing the type of id from int4 to int8 rewrites every row"));
+    }
+
+    #[test]
+    fn test_binary_coercible_type_change() {
+        assert!(rewrites(
+            "create type code as (value text);
+            create cast (varchar as code) without function;
+            alter table orders alter column code type code;"
+        )
+        .is_empty());
+        assert_eq!(
+            rewrites(
+                "create cast (varchar as code) with inout;
+                alter table orders alter column code type code;"
+            )
+            .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Validation of multi-tenant databases that use one schema per tenant.
+//!
+//! A designated template schema (e.g. `tenant_template`) describes the structure every tenant
+//! schema is expected to have. Each tenant is compared against the template with the schema diff
+//! engine, and every difference is reported as drift of that tenant.
+
+use std::collections::BTreeMap;
+
+use crate::schema::Schema;
+use crate::schema_diff::{diff, SchemaChange};
+
+/// Schemas that are never considered tenants
+const SYSTEM_SCHEMAS: &[&str] = &["pg_catalog", "information_schema", "pg_toast"];
+
+/// Query to load the names of all schemas that may be tenants.
+///
+/// Takes a `LIKE` pattern for the schema names as `$1`.
+pub const TENANT_SCHEMAS_QUERY: &str = "select nspname
+from pg_catalog.pg_namespace
+where nspname like $1
+    and nspname not like 'pg\\_temp\\_%'
+    and nspname not like 'pg\\_toast\\_temp\\_%'
+order by nspname";
+
+/// The differences of a tenant schema from the template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TenantDrift {
+    pub tenant: String,
+    /// The changes that turn the template into the tenant schema
+    pub changes: Vec<SchemaChange>,
+}
+
+/// Returns true if a schema named `name` may be a tenant of `template`
+pub fn is_tenant_schema(name: &str, template: &str) -> bool {
+    name != template && !SYSTEM_SCHEMAS.contains(&name)
+}
+
+/// Compares every tenant schema with the template and returns the drift of all tenants that do
+/// not match it, ordered by tenant name
+///
+/// `schemas` contains the loaded schemas by name. A tenant without an entry has no tables at all.
+/// The template and system schemas are skipped if they are part of `tenants`.
+pub fn check_tenants<'a>(
+    template: &Schema,
+    tenants: impl IntoIterator<Item = &'a str>,
+    schemas: &BTreeMap<String, Schema>,
+) -> Vec<TenantDrift> {
+    let mut tenants = tenants
+        .into_iter()
+        .filter(|name| is_tenant_schema(name, &template.name))
+        .collect::<Vec<&str>>();
+    tenants.sort_unstable();
+    tenants.dedup();
+
+    tenants
+        .into_iter()
+        .filter_map(|name| {
+            let empty = Schema::new(name);
+            let tenant = schemas.get(name).unwrap_or(&empty);
+            let changes = diff(template, tenant);
+            if changes.is_empty() {
+                None
+            } else {
+                Some(TenantDrift {
+                    tenant: name.to_string(),
+                    changes,
+                })
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::schema::{CatalogColumn, Schema};
+
+    use super::*;
+
+    fn column(schema: &str, table: &str, name: &str, data_type: &str) -> CatalogColumn {
+        CatalogColumn {
+            schema_name: schema.to_string(),
+            table_name: table.to_string(),
+            column_name: name.to_string(),
+            data_type: data_type.to_string(),
+            not_null: false,
+            default_expr: None,
+            access_method: None,
+            owner: None,
+            owned_sequence: None,
+            partition_key: None,
+            fillfactor: None,
+        }
+    }
+
+    #[test]
+    fn test_check_tenants() {
+        let schemas = Schema::from_catalog(
+            vec![
+                column("tenant_template", "orders", "id", "integer"),
+                column("tenant_template", "orders", "total", "numeric"),
+                column("tenant_a", "orders", "id", "integer"),
+                column("tenant_a", "orders", "total", "numeric"),
+                column("tenant_b", "orders", "id", "integer"),
+                column("tenant_b", "orders", "total", "money"),
+                column("tenant_c", "orders", "id", "integer"),
+            ],
+            vec![],
+            vec![],
+        );
+
+        let drift = check_tenants(
+            &schemas["tenant_template"],
+            [
+                "tenant_template",
+                "tenant_a",
+                "tenant_b",
+                "tenant_c",
+                "tenant_d",
+            ],
+            &schemas,
+        );
+
+        assert_eq!(drift.len(), 3);
+        assert_eq!(drift[0].tenant, "tenant_b");
+        assert_eq!(
+            drift[0].changes[0].to_string(),
+            "column orders.total changed: type numeric -> money"
+        );
+        assert_eq!(drift[1].tenant, "tenant_c");
+        assert_eq!(
+            drift[1].changes[0].to_string(),
+            "column orders.total (numeric) was removed"
+        );
+        assert_eq!(drift[2].tenant, "tenant_d");
+        assert_eq!(drift[2].changes[0].to_string(), "table orders was removed");
+    }
+
+    #[test]
+    fn test_is_tenant_schema() {
+        assert!(is_tenant_schema("tenant_a", "tenant_template"));
+        assert!(!is_tenant_schema("tenant_template", "tenant_template"));
+        assert!(!is_tenant_schema("pg_catalog", "tenant_template"));
+    }
+}
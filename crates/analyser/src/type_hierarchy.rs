@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+
+use cstree::text::TextRange;
+use parser::RawStmt;
+use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+use pg_query::protobuf::RangeVar;
+use pg_query::NodeEnum;
+
+use crate::utils::{normalize_type_name, string_value, type_name};
+
+/// The kind of a user-defined type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TypeKind {
+    /// `CREATE DOMAIN`
+    Domain,
+    /// `CREATE TYPE ... AS (...)`
+    Composite,
+    /// `CREATE TABLE`, which implicitly defines a composite type of the same name
+    Table,
+}
+
+/// A type defined in the source text
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TypeDefinition {
+    pub kind: TypeKind,
+    /// The range of the defining statement
+    pub range: TextRange,
+}
+
+/// How a type is built on top of another type
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TypeRelation {
+    /// The type is a domain over the other type
+    DomainOf,
+    /// The type has a column or attribute of the other type
+    Column(String),
+}
+
+/// A directed graph with types as nodes and an edge from every domain to its base type and from
+/// every composite type or table to the types of its columns.
+///
+/// The types a type is built on are its supertypes, and the types that are built on a type are its
+/// subtypes. Built-in types only appear as the supertypes of user-defined types.
+#[derive(Debug, Default)]
+pub struct TypeHierarchy {
+    graph: DiGraph<String, TypeRelation>,
+    types: HashMap<String, NodeIndex>,
+    definitions: HashMap<String, TypeDefinition>,
+}
+
+impl TypeHierarchy {
+    pub fn new() -> TypeHierarchy {
+        TypeHierarchy::default()
+    }
+
+    /// Builds a type hierarchy from all type definitions in `stmts`
+    pub fn from_stmts(stmts: &[RawStmt]) -> TypeHierarchy {
+        let mut hierarchy = TypeHierarchy::new();
+        stmts.iter().for_each(|stmt| hierarchy.add_stmt(stmt));
+        hierarchy
+    }
+
+    /// Adds the type defined by `stmt` if it is a `CREATE DOMAIN`, `CREATE TYPE ... AS` or
+    /// `CREATE TABLE` statement
+    pub fn add_stmt(&mut self, stmt: &RawStmt) {
+        let Some((name, kind)) = defined_type(&stmt.stmt) else {
+            return;
+        };
+        self.definitions.insert(
+            name.clone(),
+            TypeDefinition {
+                kind,
+                range: stmt.range,
+            },
+        );
+        let source = self.type_node(&name);
+
+        let mut relations = Vec::new();
+        match &stmt.stmt {
+            NodeEnum::CreateDomainStmt(n) => {
+                if let Some(base) = n.type_name.as_ref().and_then(type_name) {
+                    relations.push((base, TypeRelation::DomainOf));
+                }
+            }
+            NodeEnum::CompositeTypeStmt(n) => relations.extend(column_types(&n.coldeflist)),
+            NodeEnum::CreateStmt(n) => relations.extend(column_types(&n.table_elts)),
+            _ => {}
+        }
+        for (target, relation) in relations {
+            let target = self.type_node(&target);
+            self.graph.add_edge(source, target, relation);
+        }
+    }
+
+    /// Returns the definition of the type `name`, if it is defined in the source text
+    pub fn definition(&self, name: &str) -> Option<&TypeDefinition> {
+        self.definitions.get(name)
+    }
+
+    /// Returns the type that `name` refers to. An unqualified name also refers to a type of the
+    /// same name in any schema.
+    pub fn resolve<'a>(&'a self, name: &str) -> Option<&'a str> {
+        self.types
+            .get_key_value(name)
+            .map(|(t, _)| t.as_str())
+            .or_else(|| {
+                self.types
+                    .keys()
+                    .filter(|t| t.rsplit_once('.').is_some_and(|(_, n)| n == name))
+                    .min()
+                    .map(String::as_str)
+            })
+    }
+
+    /// Returns the types that `name` is built on, together with the relation to each of them
+    pub fn supertypes<'a>(&'a self, name: &str) -> Vec<(&'a str, &'a TypeRelation)> {
+        self.related(name, Direction::Outgoing)
+    }
+
+    /// Returns the types that are built on `name`, together with their relation to it
+    pub fn subtypes<'a>(&'a self, name: &str) -> Vec<(&'a str, &'a TypeRelation)> {
+        self.related(name, Direction::Incoming)
+    }
+
+    fn related<'a>(&'a self, name: &str, direction: Direction) -> Vec<(&'a str, &'a TypeRelation)> {
+        let Some(idx) = self.types.get(name) else {
+            return Vec::new();
+        };
+        let mut related = self
+            .graph
+            .edges_directed(*idx, direction)
+            .map(|edge| {
+                let other = match direction {
+                    Direction::Outgoing => edge.target(),
+                    Direction::Incoming => edge.source(),
+                };
+                (self.graph[other].as_str(), edge.weight())
+            })
+            .collect::<Vec<_>>();
+        // petgraph returns the most recently added edge first
+        related.reverse();
+        related
+    }
+
+    fn type_node(&mut self, name: &str) -> NodeIndex {
+        if let Some(idx) = self.types.get(name) {
+            return *idx;
+        }
+        let idx = self.graph.add_node(name.to_string());
+        self.types.insert(name.to_string(), idx);
+        idx
+    }
+}
+
+/// Returns the normalized name and kind of the type defined by `stmt`, if any
+pub fn defined_type(stmt: &NodeEnum) -> Option<(String, TypeKind)> {
+    match stmt {
+        NodeEnum::CreateDomainStmt(n) => Some((
+            normalize_type_name(
+                &n.domainname
+                    .iter()
+                    .filter_map(string_value)
+                    .collect::<Vec<&str>>(),
+            )?,
+            TypeKind::Domain,
+        )),
+        NodeEnum::CompositeTypeStmt(n) => {
+            Some((relation_name(n.typevar.as_ref()?)?, TypeKind::Composite))
+        }
+        NodeEnum::CreateStmt(n) => Some((relation_name(n.relation.as_ref()?)?, TypeKind::Table)),
+        _ => None,
+    }
+}
+
+fn relation_name(relation: &RangeVar) -> Option<String> {
+    if relation.schemaname.is_empty() {
+        normalize_type_name(&[&relation.relname])
+    } else {
+        normalize_type_name(&[&relation.schemaname, &relation.relname])
+    }
+}
+
+/// Returns the types of the `ColumnDef`s in `elements`, together with the column name
+fn column_types(elements: &[pg_query::protobuf::Node]) -> Vec<(String, TypeRelation)> {
+    elements
+        .iter()
+        .filter_map(|element| match element.node.as_ref()? {
+            NodeEnum::ColumnDef(column) => Some((
+                type_name(column.type_name.as_ref()?)?,
+                TypeRelation::Column(column.colname.clone()),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+
+    use super::*;
+
+    #[test]
+    fn test_domain_chain() {
+        let hierarchy = TypeHierarchy::from_stmts(
+            &parse_source(
+                "CREATE DOMAIN positive_int AS int CHECK (VALUE > 0);\nCREATE DOMAIN quantity AS positive_int;",
+            )
+            .stmts,
+        );
+        assert_eq!(
+            hierarchy.supertypes("quantity"),
+            vec![("positive_int", &TypeRelation::DomainOf)]
+        );
+        assert_eq!(
+            hierarchy.supertypes("positive_int"),
+            vec![("int4", &TypeRelation::DomainOf)]
+        );
+        assert_eq!(
+            hierarchy.subtypes("int4"),
+            vec![("positive_int", &TypeRelation::DomainOf)]
+        );
+        assert_eq!(
+            hierarchy.definition("quantity").map(|d| d.kind),
+            Some(TypeKind::Domain)
+        );
+        assert_eq!(hierarchy.definition("int4"), None);
+        assert_eq!(hierarchy.resolve("int4"), Some("int4"));
+    }
+
+    #[test]
+    fn test_composite_usage() {
+        let hierarchy = TypeHierarchy::from_stmts(
+            &parse_source(
+                "CREATE TYPE app.address AS (street text, zip text);\nCREATE TABLE contact (id int, home app.address, work app.address);",
+            )
+            .stmts,
+        );
+        assert_eq!(
+            hierarchy.definition("app.address").map(|d| d.kind),
+            Some(TypeKind::Composite)
+        );
+        assert_eq!(
+            hierarchy.subtypes("app.address"),
+            vec![
+                ("contact", &TypeRelation::Column("home".to_string())),
+                ("contact", &TypeRelation::Column("work".to_string()))
+            ]
+        );
+        assert_eq!(
+            hierarchy
+                .supertypes("contact")
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<&str>>(),
+            vec!["int4", "app.address", "app.address"]
+        );
+        assert!(hierarchy.supertypes("missing").is_empty());
+        assert_eq!(hierarchy.resolve("address"), Some("app.address"));
+        assert_eq!(hierarchy.resolve("missing"), None);
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::VecDeque;
+
+use parser::get_children;
+use pg_query::{protobuf::TypeName, NodeEnum};
+
+/// Schemas that are implicitly part of every search path. Type names from these schemas are
+/// stored unqualified.
+const IMPLICIT_SCHEMAS: &[&str] = &["pg_catalog"];
+
+/// Returns the value of a `String` node
+pub fn string_value(node: &pg_query::protobuf::Node) -> Option<&str> {
+    match node.node.as_ref()? {
+        NodeEnum::String(s) => Some(s.sval.as_str()),
+        _ => None,
+    }
+}
+
+/// Returns the names of the `OWNED BY` option of `CREATE SEQUENCE` or `ALTER SEQUENCE`, e.g.
+/// `["orders", "id"]`, or `["none"]` for `OWNED BY NONE`
+pub fn sequence_owned_by(options: &[pg_query::protobuf::Node]) -> Option<Vec<&str>> {
+    options
+        .iter()
+        .find_map(|option| match option.node.as_ref()? {
+            NodeEnum::DefElem(d) if d.defname == "owned_by" => {
+                match d.arg.as_ref()?.node.as_ref()? {
+                    NodeEnum::List(list) => {
+                        Some(list.items.iter().filter_map(string_value).collect())
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+}
+
+/// Returns the normalized name of a type, e.g. `int4` or `public.my_type`
+///
+/// pg_query already resolves aliases such as `int` or `integer` to `pg_catalog.int4`.
+pub fn type_name(type_name: &TypeName) -> Option<String> {
+    let names = type_name
+        .names
+        .iter()
+        .filter_map(string_value)
+        .collect::<Vec<&str>>();
+    normalize_type_name(&names)
+}
+
+/// Normalizes a possibly qualified type name given as its parts
+pub fn normalize_type_name(parts: &[&str]) -> Option<String> {
+    let parts = match parts {
+        [] => return None,
+        [schema, rest @ ..] if !rest.is_empty() && IMPLICIT_SCHEMAS.contains(schema) => rest,
+        _ => parts,
+    };
+    Some(
+        parts
+            .iter()
+            .map(|p| p.to_lowercase())
+            .collect::<Vec<String>>()
+            .join("."),
+    )
+}
+
+/// Returns `node` and all nodes within it in breadth-first order
+pub fn descendants(node: &NodeEnum) -> Vec<NodeEnum> {
+    let mut nodes = Vec::new();
+    let mut queue = VecDeque::from(vec![node.clone()]);
+    while let Some(node) = queue.pop_front() {
+        queue.extend(get_children(&node));
+        nodes.push(node);
+    }
+    nodes
+}
+
+/// Returns all type names used within `node` together with their normalized name
+pub fn type_names(node: &NodeEnum) -> Vec<(TypeName, String)> {
+    descendants(node)
+        .into_iter()
+        .filter_map(|n| match n {
+            NodeEnum::TypeName(t) => {
+                let name = type_name(&t)?;
+                Some((t, name))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_type_name() {
+        assert_eq!(
+            normalize_type_name(&["pg_catalog", "int4"]),
+            Some("int4".to_string())
+        );
+        assert_eq!(
+            normalize_type_name(&["public", "My_Type"]),
+            Some("public.my_type".to_string())
+        );
+        assert_eq!(normalize_type_name(&["text"]), Some("text".to_string()));
+        assert_eq!(normalize_type_name(&[]), None);
+    }
+
+    #[test]
+    fn test_type_names() {
+        let stmt = parser::parse_source("CREATE TABLE t (a int, b public.my_type, c money[]);")
+            .stmts
+            .remove(0)
+            .stmt;
+        assert_eq!(
+            type_names(&stmt)
+                .into_iter()
+                .map(|(_, name)| name)
+                .collect::<Vec<String>>(),
+            vec!["int4", "public.my_type", "money"]
+        );
+    }
+}
@@ -0,0 +1,38 @@
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+use crate::parser::parser_mod;
+
+/// Writes the `codegen` module generated from the proto file at `proto_path`, e.g. as returned by
+/// [`proto_path`](crate::proto_path), to `out_file`, for the build script of the parser. The
+/// module is only regenerated if the proto file or the generator changed since it was last
+/// written. The build script tells cargo when to run it again.
+pub fn write_parser_mod(proto_path: &Path, out_file: &Path) -> io::Result<()> {
+    let hash = generator_hash(proto_path)?;
+    let hash_file = out_file.with_extension("hash");
+    if out_file.exists() && fs::read_to_string(&hash_file).is_ok_and(|h| h == hash) {
+        return Ok(());
+    }
+
+    fs::write(out_file, parser_mod(proto_path).to_string())?;
+    // formatting only helps readers of the output, e.g. in an IDE, so a missing rustfmt is fine
+    let _ = Command::new(env::var("RUSTFMT").unwrap_or_else(|_| "rustfmt".to_string()))
+        .args(["--edition", "2021"])
+        .arg(out_file)
+        .status();
+    fs::write(hash_file, hash)
+}
+
+/// Hashes the proto file together with the running build script, which is rebuilt whenever the
+/// generator changes
+fn generator_hash(proto_path: &Path) -> io::Result<String> {
+    let mut hasher = DefaultHasher::new();
+    fs::read(proto_path)?.hash(&mut hasher);
+    fs::read(env::current_exe()?)?.hash(&mut hasher);
+    Ok(format!("{:016x}", hasher.finish()))
+}
@@ -0,0 +1,253 @@
+use proc_macro2::{Ident, Literal};
+use quote::{format_ident, quote};
+
+pub fn expr_info_mod() -> proc_macro2::TokenStream {
+    let (operators, operator_precedences) = operator_precedences();
+    let (a_expr_kinds, a_expr_kind_operators, a_expr_kind_precedences, a_expr_kind_arities) =
+        a_expr_kinds();
+
+    quote! {
+        /// The precedence levels of operators, from the loosest to the tightest binding one, as
+        /// documented in the "Operator Precedence" section of the Postgres manual
+        #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+        pub enum Precedence {
+            Or,
+            And,
+            Not,
+            /// `IS`, `ISNULL`, `NOTNULL`
+            Is,
+            /// `<`, `>`, `=`, `<=`, `>=`, `<>`
+            Comparison,
+            /// `BETWEEN`, `IN`, `LIKE`, `ILIKE`, `SIMILAR`
+            Pattern,
+            /// all other native and user-defined operators
+            Other,
+            /// binary `+` and `-`
+            Addition,
+            /// `*`, `/`, `%`
+            Multiplication,
+            /// `^`
+            Exponentiation,
+            /// `AT TIME ZONE`
+            At,
+            Collate,
+            /// unary `+` and `-`
+            UnaryMinus,
+            /// `::`
+            Typecast,
+            /// function-like expressions such as `NULLIF(a, b)`, which never need parentheses
+            Primary,
+        }
+
+        /// Operator information of an expression node
+        #[derive(Clone, PartialEq, Eq, Debug)]
+        pub struct ExprInfo {
+            /// The operator as written in sql, e.g. `+`, `NOT LIKE` or `IS NOT NULL`
+            pub operator: String,
+            /// The number of operands
+            pub arity: usize,
+            pub precedence: Precedence,
+        }
+
+        impl ExprInfo {
+            /// Returns true if an operand with operator info `child` must be parenthesized within
+            /// an expression with this operator info. `is_right` is true for the right operand of
+            /// a binary operator.
+            ///
+            /// Binary operators are left-associative, except for comparisons, which are not
+            /// associative at all.
+            pub fn requires_parens(&self, child: &ExprInfo, is_right: bool) -> bool {
+                match child.precedence.cmp(&self.precedence) {
+                    Ordering::Less => true,
+                    Ordering::Greater => false,
+                    Ordering::Equal => {
+                        self.arity == 2
+                            && child.arity == 2
+                            && (is_right
+                                || matches!(
+                                    self.precedence,
+                                    Precedence::Comparison | Precedence::Pattern | Precedence::Is
+                                ))
+                    }
+                }
+            }
+        }
+
+        /// Returns the precedence of the binary operator `op`
+        fn operator_precedence(op: &str) -> Precedence {
+            match op {
+                #(#operators => Precedence::#operator_precedences),*,
+                _ => Precedence::Other,
+            }
+        }
+
+        /// Returns operator information for expression nodes
+        pub fn expr_info(node: &NodeEnum) -> Option<ExprInfo> {
+            let info = match node {
+                NodeEnum::AExpr(n) => {
+                    let op = n
+                        .name
+                        .last()
+                        .and_then(|n| match n.node.as_ref()? {
+                            NodeEnum::String(s) => Some(s.sval.clone()),
+                            _ => None,
+                        })
+                        .unwrap_or_default();
+                    // `NOT LIKE` and friends use the negated operator, e.g. `!~~`
+                    let negated = op.starts_with('!') || op == "<>";
+                    let not = if negated { "NOT " } else { "" };
+                    let binary = if n.lexpr.is_some() { 2 } else { 1 };
+                    match n.kind {
+                        // AexprOp
+                        1 if binary == 1 => ExprInfo {
+                            precedence: if op == "+" || op == "-" {
+                                Precedence::UnaryMinus
+                            } else {
+                                Precedence::Other
+                            },
+                            operator: op,
+                            arity: 1,
+                        },
+                        1 => ExprInfo {
+                            precedence: operator_precedence(&op),
+                            operator: op,
+                            arity: 2,
+                        },
+                        // AexprOpAny, AexprOpAll
+                        2 | 3 => ExprInfo {
+                            precedence: operator_precedence(&op),
+                            operator: format!("{} {}", op, if n.kind == 2 { "ANY" } else { "ALL" }),
+                            arity: 2,
+                        },
+                        #(#a_expr_kinds => ExprInfo {
+                            operator: #a_expr_kind_operators.replace("{not}", not),
+                            arity: #a_expr_kind_arities,
+                            precedence: Precedence::#a_expr_kind_precedences,
+                        }),*,
+                        _ => return None,
+                    }
+                }
+                NodeEnum::BoolExpr(n) => {
+                    let (operator, precedence) = match n.boolop {
+                        // AndExpr
+                        1 => ("AND", Precedence::And),
+                        // OrExpr
+                        2 => ("OR", Precedence::Or),
+                        // NotExpr
+                        3 => ("NOT", Precedence::Not),
+                        _ => return None,
+                    };
+                    ExprInfo {
+                        operator: operator.to_string(),
+                        arity: n.args.len(),
+                        precedence,
+                    }
+                }
+                NodeEnum::NullTest(n) => ExprInfo {
+                    // IsNull, IsNotNull
+                    operator: if n.nulltesttype == 2 { "IS NOT NULL" } else { "IS NULL" }.to_string(),
+                    arity: 1,
+                    precedence: Precedence::Is,
+                },
+                NodeEnum::BooleanTest(n) => ExprInfo {
+                    operator: match n.booltesttype {
+                        1 => "IS TRUE",
+                        2 => "IS NOT TRUE",
+                        3 => "IS FALSE",
+                        4 => "IS NOT FALSE",
+                        5 => "IS UNKNOWN",
+                        6 => "IS NOT UNKNOWN",
+                        _ => return None,
+                    }
+                    .to_string(),
+                    arity: 1,
+                    precedence: Precedence::Is,
+                },
+                NodeEnum::TypeCast(_) => ExprInfo {
+                    operator: "::".to_string(),
+                    arity: 1,
+                    precedence: Precedence::Typecast,
+                },
+                NodeEnum::CollateClause(_) => ExprInfo {
+                    operator: "COLLATE".to_string(),
+                    arity: 1,
+                    precedence: Precedence::Collate,
+                },
+                _ => return None,
+            };
+            Some(info)
+        }
+    }
+}
+
+/// The binary operators with a precedence other than `Other`
+fn operator_precedences() -> (Vec<Literal>, Vec<Ident>) {
+    [
+        ("^", "Exponentiation"),
+        ("*", "Multiplication"),
+        ("/", "Multiplication"),
+        ("%", "Multiplication"),
+        ("+", "Addition"),
+        ("-", "Addition"),
+        ("<", "Comparison"),
+        (">", "Comparison"),
+        ("=", "Comparison"),
+        ("<=", "Comparison"),
+        (">=", "Comparison"),
+        ("<>", "Comparison"),
+        ("!=", "Comparison"),
+    ]
+    .iter()
+    .map(|(op, precedence)| (Literal::string(op), format_ident!("{}", precedence)))
+    .unzip()
+}
+
+/// The `AExprKind`s of pg_query, except for plain operators, with their sql operator and
+/// precedence. `{not}` is replaced with `NOT ` if the operator is negated.
+fn a_expr_kinds() -> (
+    Vec<Literal>,
+    Vec<Literal>,
+    Vec<Ident>,
+    Vec<proc_macro2::TokenStream>,
+) {
+    let kinds = [
+        // AexprDistinct
+        (4, "IS DISTINCT FROM", "Is"),
+        // AexprNotDistinct
+        (5, "IS NOT DISTINCT FROM", "Is"),
+        // AexprNullif
+        (6, "NULLIF", "Primary"),
+        // AexprIn
+        (7, "{not}IN", "Pattern"),
+        // AexprLike
+        (8, "{not}LIKE", "Pattern"),
+        // AexprIlike
+        (9, "{not}ILIKE", "Pattern"),
+        // AexprSimilar
+        (10, "{not}SIMILAR TO", "Pattern"),
+        // AexprBetween
+        (11, "BETWEEN", "Pattern"),
+        // AexprNotBetween
+        (12, "NOT BETWEEN", "Pattern"),
+        // AexprBetweenSym
+        (13, "BETWEEN SYMMETRIC", "Pattern"),
+        // AexprNotBetweenSym
+        (14, "NOT BETWEEN SYMMETRIC", "Pattern"),
+    ];
+    let mut values = Vec::new();
+    let mut operators = Vec::new();
+    let mut precedences = Vec::new();
+    let mut arities = Vec::new();
+    for (value, operator, precedence) in kinds {
+        values.push(Literal::i32_unsuffixed(value));
+        operators.push(Literal::string(operator));
+        precedences.push(format_ident!("{}", precedence));
+        // `BETWEEN` takes the lower and upper bound as a list in `rexpr`
+        arities.push(if operator.contains("BETWEEN") {
+            quote! { 3 }
+        } else {
+            quote! { binary }
+        });
+    }
+    (values, operators, precedences, arities)
+}
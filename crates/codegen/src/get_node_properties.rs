@@ -1,13 +1,14 @@
 use pg_query_proto_parser::{FieldType, Node, ProtoFile};
-use proc_macro2::{Ident, TokenStream};
+use proc_macro2::{Ident, Literal, TokenStream};
 use quote::{format_ident, quote};
 
 pub fn get_node_properties_mod(proto_file: &ProtoFile) -> proc_macro2::TokenStream {
     let node_identifiers = node_identifiers(&proto_file.nodes);
-    let node_handlers = node_handlers(&proto_file.nodes);
+    let node_handlers = node_handlers(proto_file);
 
     quote! {
         #[derive(Debug, Clone, PartialEq)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
         pub struct TokenProperty {
             pub value: Option<String>,
             pub kind: Option<SyntaxKind>,
@@ -147,12 +148,13 @@ fn node_identifiers(nodes: &[Node]) -> Vec<Ident> {
         .collect()
 }
 
-fn node_handlers(nodes: &[Node]) -> Vec<TokenStream> {
-    nodes
+fn node_handlers(proto_file: &ProtoFile) -> Vec<TokenStream> {
+    proto_file
+        .nodes
         .iter()
         .map(|node| {
             let string_property_handlers = string_property_handlers(&node);
-            let custom_handlers = custom_handlers(&node);
+            let custom_handlers = custom_handlers(proto_file, &node);
             quote! {
                 #custom_handlers
                 #(#string_property_handlers)*
@@ -161,7 +163,31 @@ fn node_handlers(nodes: &[Node]) -> Vec<TokenStream> {
         .collect()
 }
 
-fn custom_handlers(node: &Node) -> TokenStream {
+/// The keywords of the object types of `COMMENT ON` and `SECURITY LABEL ON`. Constraints and
+/// triggers are followed by the `ON` of their table.
+const COMMENTED_OBJECT_TYPES: &[(&str, &[&str])] = &[
+    ("ObjectAggregate", &["Aggregate"]),
+    ("ObjectColumn", &["Column"]),
+    ("ObjectDomain", &["DomainP"]),
+    ("ObjectExtension", &["Extension"]),
+    ("ObjectForeignTable", &["Foreign", "Table"]),
+    ("ObjectFunction", &["Function"]),
+    ("ObjectIndex", &["Index"]),
+    ("ObjectMatview", &["Materialized", "View"]),
+    ("ObjectProcedure", &["Procedure"]),
+    ("ObjectRole", &["Role"]),
+    ("ObjectSchema", &["Schema"]),
+    ("ObjectSequence", &["Sequence"]),
+    ("ObjectTabconstraint", &["Constraint", "On"]),
+    ("ObjectTable", &["Table"]),
+    ("ObjectTrigger", &["Trigger", "On"]),
+    ("ObjectType", &["TypeP"]),
+    ("ObjectView", &["View"]),
+];
+
+fn custom_handlers(proto_file: &ProtoFile, node: &Node) -> TokenStream {
+    let enum_field =
+        |field: &str, variants: &[(&str, &[&str])]| enum_handler(proto_file, node, field, variants);
     match node.name.as_str() {
         "SelectStmt" => quote! {
             tokens.push(TokenProperty::from(Token::Select));
@@ -182,37 +208,30 @@ fn custom_handlers(node: &Node) -> TokenStream {
                 tokens.push(TokenProperty::from(Token::By));
             }
         },
-        "BoolExpr" => quote! {
-            match n.boolop {
-                // AndExpr = 1
-                1 => tokens.push(TokenProperty::from(Token::And)),
-                // OrExpr = 2
-                2 => tokens.push(TokenProperty::from(Token::Or)),
-                // NotExpr = 3
-                3 => tokens.push(TokenProperty::from(Token::Not)),
-                _ => panic!("Unknown BoolExpr {:#?}", n.boolop),
-            }
-        },
-        "JoinExpr" => quote! {
-            tokens.push(TokenProperty::from(Token::Join));
-            tokens.push(TokenProperty::from(Token::On));
-            match n.jointype {
-                // JoinInner = 1
-                1 => tokens.push(TokenProperty::from(Token::InnerP)),
-                // JoinLeft = 2
-                2 => tokens.push(TokenProperty::from(Token::Left)),
-                // JoinFull = 3
-                3 => tokens.push(TokenProperty::from(Token::Full)),
-                // JoinRight = 4
-                4 => tokens.push(TokenProperty::from(Token::Right)),
-                // JoinSemi = 5
-                // JoinAnti = 6
-                // JoinUniqueOuter = 7
-                // JoinUniqueInner = 8
-                _ => panic!("Unknown JoinExpr jointype {:#?}", n.jointype),
+        "BoolExpr" => enum_field(
+            "boolop",
+            &[
+                ("AndExpr", &["And"]),
+                ("OrExpr", &["Or"]),
+                ("NotExpr", &["Not"]),
+            ],
+        ),
+        "JoinExpr" => {
+            let jointype = enum_field(
+                "jointype",
+                &[
+                    ("JoinInner", &["InnerP"]),
+                    ("JoinLeft", &["Left"]),
+                    ("JoinFull", &["Full"]),
+                    ("JoinRight", &["Right"]),
+                ],
+            );
+            quote! {
+                tokens.push(TokenProperty::from(Token::Join));
+                tokens.push(TokenProperty::from(Token::On));
+                #jointype
             }
-
-        },
+        }
         "ResTarget" => quote! {
             if n.name.len() > 0 {
                 tokens.push(TokenProperty::from(Token::As));
@@ -221,42 +240,14 @@ fn custom_handlers(node: &Node) -> TokenStream {
         "Integer" => quote! {
             tokens.push(TokenProperty::from(n));
         },
-        "DefElem" => quote! {
-            match n.defaction {
-                1 => tokens.push(TokenProperty::from(Token::Ascii61)),
-                _ => panic!("Unknown DefElem {:#?}", n.defaction),
-            }
-        },
+        "DefElem" => enum_field("defaction", &[("DefelemUnspec", &["Ascii61"])]),
         "Alias" => quote! {
             tokens.push(TokenProperty::from(Token::As));
         },
         "CollateClause" => quote! {
             tokens.push(TokenProperty::from(Token::Collate));
         },
-        "AExpr" => quote! {
-            match n.kind {
-                // AexprOp = 1,
-                1 => {
-                    // do nothing
-                },
-                // AexprOpAny = 2,
-                2 => tokens.push(TokenProperty::from(Token::Any)),
-                // AexprOpAll = 3,
-                // AexprDistinct = 4,
-                // AexprNotDistinct = 5,
-                // AexprNullif = 6,
-                // AexprIn = 7,
-                7 => tokens.push(TokenProperty::from(Token::InP)),
-                // AexprLike = 8,
-                // AexprIlike = 9,
-                // AexprSimilar = 10,
-                // AexprBetween = 11,
-                // AexprNotBetween = 12,
-                // AexprBetweenSym = 13,
-                // AexprNotBetweenSym = 14,
-                _ => panic!("Unknown AExpr kind {:#?}", n.kind),
-            }
-        },
+        "AExpr" => enum_field("kind", &[("AexprOpAny", &["Any"]), ("AexprIn", &["InP"])]),
         "WindowDef" => quote! {
             if n.partition_clause.len() > 0 || n.order_clause.len() > 0 {
                 tokens.push(TokenProperty::from(Token::Window));
@@ -292,37 +283,24 @@ fn custom_handlers(node: &Node) -> TokenStream {
                 tokens.push(TokenProperty::from(Token::Over));
             }
         },
-        "SqlvalueFunction" => quote! {
-            match n.op {
-                // 1 SvfopCurrentDate
-                // 2 SvfopCurrentTime
-                // 3 SvfopCurrentTimeN
-                // 4 SvfopCurrentTimestamp
-                // 5 SvfopCurrentTimestampN
-                // 6 SvfopLocaltime
-                // 7 SvfopLocaltimeN
-                // 8 SvfopLocaltimestamp
-                // 9 SvfopLocaltimestampN
-                // 10 SvfopCurrentRole
-                10 => tokens.push(TokenProperty::from(Token::CurrentRole)),
-                // 11 SvfopCurrentUser
-                11 => tokens.push(TokenProperty::from(Token::CurrentUser)),
-                // 12 SvfopUser
-                // 13 SvfopSessionUser
-                // 14 SvfopCurrentCatalog
-                // 15 SvfopCurrentSchema
-                _ => panic!("Unknown SqlvalueFunction {:#?}", n.op),
-            }
-        },
-        "SortBy" => quote! {
-            tokens.push(TokenProperty::from(Token::Order));
-            tokens.push(TokenProperty::from(Token::By));
-            match n.sortby_dir {
-                2 => tokens.push(TokenProperty::from(Token::Asc)),
-                3 => tokens.push(TokenProperty::from(Token::Desc)),
-                _ => {}
+        "SqlvalueFunction" => enum_field(
+            "op",
+            &[
+                ("SvfopCurrentRole", &["CurrentRole"]),
+                ("SvfopCurrentUser", &["CurrentUser"]),
+            ],
+        ),
+        "SortBy" => {
+            let sortby_dir = enum_field(
+                "sortby_dir",
+                &[("SortbyAsc", &["Asc"]), ("SortbyDesc", &["Desc"])],
+            );
+            quote! {
+                tokens.push(TokenProperty::from(Token::Order));
+                tokens.push(TokenProperty::from(Token::By));
+                #sortby_dir
             }
-        },
+        }
         "AConst" => quote! {
             if n.isnull {
                 tokens.push(TokenProperty::from(Token::NullP));
@@ -332,39 +310,81 @@ fn custom_handlers(node: &Node) -> TokenStream {
             tokens.push(TokenProperty::from(Token::Alter));
             tokens.push(TokenProperty::from(Token::Table));
         },
-        "AlterTableCmd" => quote! {
-            tokens.push(TokenProperty::from(Token::Alter));
-            match n.subtype {
-                4 => {
-                    tokens.push(TokenProperty::from(Token::Column));
-                    tokens.push(TokenProperty::from(Token::Set));
-                    tokens.push(TokenProperty::from(Token::Default));
-                },
-                // AtAddConstraint
-                19 => tokens.push(TokenProperty::from(Token::AddP)),
-                // AtAlterColumnType
-                30 => {
-                    tokens.push(TokenProperty::from(Token::Alter));
-                    tokens.push(TokenProperty::from(Token::Column));
-                    tokens.push(TokenProperty::from(Token::TypeP));
-                },
-                _ => panic!("Unknown AlterTableCmd {:#?}", n.subtype),
+        // every command of a statement such as `ALTER TABLE t ADD COLUMN a int, DROP COLUMN b` is
+        // its own node, which only has the tokens of its own clause
+        "AlterTableCmd" => enum_field(
+            "subtype",
+            &[
+                ("AtAddColumn", &["AddP", "Column"]),
+                ("AtColumnDefault", &["Alter", "Column", "Set", "Default"]),
+                (
+                    "AtDropNotNull",
+                    &["Alter", "Column", "Drop", "Not", "NullP"],
+                ),
+                ("AtSetNotNull", &["Alter", "Column", "Set", "Not", "NullP"]),
+                ("AtDropColumn", &["Drop", "Column"]),
+                ("AtAddConstraint", &["AddP"]),
+                ("AtDropConstraint", &["Drop", "Constraint"]),
+                ("AtAlterColumnType", &["Alter", "Column", "TypeP"]),
+                ("AtClusterOn", &["Cluster", "On"]),
+                ("AtDropCluster", &["Set", "Without", "Cluster"]),
+                ("AtSetAccessMethod", &["Set", "Access", "Method"]),
+            ],
+        ),
+        "TruncateStmt" => {
+            let behavior = enum_field(
+                "behavior",
+                &[
+                    ("DropRestrict", &["Restrict"]),
+                    ("DropCascade", &["Cascade"]),
+                ],
+            );
+            quote! {
+                tokens.push(TokenProperty::from(Token::Truncate));
+                if n.restart_seqs {
+                    tokens.push(TokenProperty::from(Token::Restart));
+                    tokens.push(TokenProperty::from(Token::IdentityP));
+                }
+                #behavior
+            }
+        }
+        "ClusterStmt" => quote! {
+            tokens.push(TokenProperty::from(Token::Cluster));
+            if n.indexname.len() > 0 {
+                tokens.push(TokenProperty::from(Token::Using));
             }
         },
-        "VariableSetStmt" => quote! {
-            tokens.push(TokenProperty::from(Token::Set));
-            match n.kind {
-                // Undefined = 0,
-                // VarSetValue = 1,
-                1 => tokens.push(TokenProperty::from(Token::To)),
-                // VarSetDefault = 2,
-                // VarSetCurrent = 3,
-                // VarSetMulti = 4,
-                // VarReset = 5,
-                // VarResetAll = 6,
-                _ => panic!("Unknown VariableSetStmt {:#?}", n.kind),
+        // `mode` is a plain lock mode number, from `AccessShareLock` (1) to `AccessExclusiveLock`
+        // (8), so it cannot be handled by `enum_field`
+        "LockStmt" => quote! {
+            tokens.push(TokenProperty::from(Token::LockP));
+            tokens.push(TokenProperty::from(Token::Table));
+            let mode: &[Token] = match n.mode {
+                1 => &[Token::Access, Token::Share],
+                2 => &[Token::Row, Token::Share],
+                3 => &[Token::Row, Token::Exclusive],
+                4 => &[Token::Share, Token::Update, Token::Exclusive],
+                5 => &[Token::Share],
+                6 => &[Token::Share, Token::Row, Token::Exclusive],
+                7 => &[Token::Exclusive],
+                _ => &[Token::Access, Token::Exclusive],
+            };
+            tokens.push(TokenProperty::from(Token::InP));
+            for token in mode {
+                tokens.push(TokenProperty::from(*token));
+            }
+            tokens.push(TokenProperty::from(Token::Mode));
+            if n.nowait {
+                tokens.push(TokenProperty::from(Token::Nowait));
             }
         },
+        "VariableSetStmt" => {
+            let kind = enum_field("kind", &[("VarSetValue", &["To"])]);
+            quote! {
+                tokens.push(TokenProperty::from(Token::Set));
+                #kind
+            }
+        }
         "CreatePolicyStmt" => quote! {
             tokens.push(TokenProperty::from(Token::Create));
             tokens.push(TokenProperty::from(Token::Policy));
@@ -390,25 +410,72 @@ fn custom_handlers(node: &Node) -> TokenStream {
             tokens.push(TokenProperty::from(Token::Rename));
             tokens.push(TokenProperty::from(Token::To));
         },
-        "Constraint" => quote! {
-            match n.contype {
-                // ConstrNotnull
-                2 => {
-                    tokens.push(TokenProperty::from(Token::Not));
+        "Constraint" => {
+            // the attributes of a column constraint, e.g. `REFERENCES t DEFERRABLE`, are
+            // constraints of their own, while those of a table constraint are its fields
+            let contype = enum_field(
+                "contype",
+                &[
+                    ("ConstrNotnull", &["Not", "NullP"]),
+                    ("ConstrDefault", &["Default"]),
+                    ("ConstrCheck", &["Check"]),
+                    ("ConstrPrimary", &["Primary", "Key"]),
+                    ("ConstrForeign", &["References"]),
+                    ("ConstrAttrDeferrable", &["Deferrable"]),
+                    ("ConstrAttrNotDeferrable", &["Not", "Deferrable"]),
+                    ("ConstrAttrDeferred", &["Initially", "Deferred"]),
+                    ("ConstrAttrImmediate", &["Initially", "Immediate"]),
+                ],
+            );
+            quote! {
+                #contype
+                if n.deferrable {
+                    tokens.push(TokenProperty::from(Token::Deferrable));
+                }
+                if n.initdeferred {
+                    tokens.push(TokenProperty::from(Token::Initially));
+                    tokens.push(TokenProperty::from(Token::Deferred));
+                }
+            }
+        }
+        "CommentStmt" => {
+            let objtype = enum_field("objtype", COMMENTED_OBJECT_TYPES);
+            quote! {
+                tokens.push(TokenProperty::from(Token::Comment));
+                tokens.push(TokenProperty::from(Token::On));
+                #objtype
+                tokens.push(TokenProperty::from(Token::Is));
+                if n.comment.len() == 0 {
                     tokens.push(TokenProperty::from(Token::NullP));
-                },
-                // ConstrDefault
-                3 => tokens.push(TokenProperty::from(Token::Default)),
-                // ConstrCheck
-                6 => tokens.push(TokenProperty::from(Token::Check)),
-                // ConstrPrimary
-                7 => {
-                    tokens.push(TokenProperty::from(Token::Primary));
-                    tokens.push(TokenProperty::from(Token::Key));
-                },
-                // ConstrForeign
-                10 => tokens.push(TokenProperty::from(Token::References)),
-                _ => panic!("Unknown Constraint {:#?}", n.contype),
+                }
+            }
+        }
+        "SecLabelStmt" => {
+            let objtype = enum_field("objtype", COMMENTED_OBJECT_TYPES);
+            quote! {
+                tokens.push(TokenProperty::from(Token::Security));
+                tokens.push(TokenProperty::from(Token::Label));
+                if n.provider.len() > 0 {
+                    tokens.push(TokenProperty::from(Token::For));
+                }
+                tokens.push(TokenProperty::from(Token::On));
+                #objtype
+                tokens.push(TokenProperty::from(Token::Is));
+                if n.label.len() == 0 {
+                    tokens.push(TokenProperty::from(Token::NullP));
+                }
+            }
+        }
+        "ConstraintsSetStmt" => quote! {
+            tokens.push(TokenProperty::from(Token::Set));
+            tokens.push(TokenProperty::from(Token::Constraints));
+            if n.constraints.len() == 0 {
+                tokens.push(TokenProperty::from(Token::All));
+            }
+            if n.deferred {
+                tokens.push(TokenProperty::from(Token::Deferred));
+            } else {
+                tokens.push(TokenProperty::from(Token::Immediate));
             }
         },
         "PartitionSpec" => quote! {
@@ -446,6 +513,9 @@ fn custom_handlers(node: &Node) -> TokenStream {
             if n.tablespacename.len() > 0 {
                 tokens.push(TokenProperty::from(Token::Tablespace));
             }
+            if n.access_method.len() > 0 {
+                tokens.push(TokenProperty::from(Token::Using));
+            }
             if n.options.len() > 0 {
                 tokens.push(TokenProperty::from(Token::With));
             }
@@ -472,19 +542,16 @@ fn custom_handlers(node: &Node) -> TokenStream {
                 tokens.push(TokenProperty::from(Token::Else));
             }
         },
-        "NullTest" => quote! {
-            match n.nulltesttype {
-                // IsNull
-                1 => tokens.push(TokenProperty::from(Token::Is)),
-                // IsNotNull
-                2 => {
-                    tokens.push(TokenProperty::from(Token::Is));
-                    tokens.push(TokenProperty::from(Token::Not));
-                },
-                _ => panic!("Unknown NullTest {:#?}", n.nulltesttype),
-            }
-            tokens.push(TokenProperty::from(Token::NullP));
-        },
+        "NullTest" => {
+            let nulltesttype = enum_field(
+                "nulltesttype",
+                &[("IsNull", &["Is"]), ("IsNotNull", &["Is", "Not"])],
+            );
+            quote! {
+                #nulltesttype
+                tokens.push(TokenProperty::from(Token::NullP));
+            }
+        }
         "CreateFunctionStmt" => quote! {
             tokens.push(TokenProperty::from(Token::Create));
             tokens.push(TokenProperty::from(Token::Function));
@@ -496,28 +563,23 @@ fn custom_handlers(node: &Node) -> TokenStream {
                 tokens.push(TokenProperty::from(Token::Returns));
             }
         },
-        "FunctionParameter" => quote! {
-            match n.mode {
-                // FuncParamIn = 1,
-                1 => tokens.push(TokenProperty::from(Token::InP)),
-                // FuncParamOut = 2,
-                2 => tokens.push(TokenProperty::from(Token::OutP)),
-                // FuncParamInout = 3,
-                3 => tokens.push(TokenProperty::from(Token::Inout)),
-                // FuncParamVariadic = 4,
-                4 => tokens.push(TokenProperty::from(Token::Variadic)),
-                // FuncParamTable = 5,
-                // 5 => tokens.push(TokenProperty::from(Token::Table)),
-                // FuncParamDefault = 6,
-                6 => {
-                    // do nothing
-                },
-                _ => panic!("Unknown FunctionParameter {:#?}", n.mode),
-            };
-            if n.defexpr.is_some() {
-                tokens.push(TokenProperty::from(Token::Default));
+        "FunctionParameter" => {
+            let mode = enum_field(
+                "mode",
+                &[
+                    ("FuncParamIn", &["InP"]),
+                    ("FuncParamOut", &["OutP"]),
+                    ("FuncParamInout", &["Inout"]),
+                    ("FuncParamVariadic", &["Variadic"]),
+                ],
+            );
+            quote! {
+                #mode
+                if n.defexpr.is_some() {
+                    tokens.push(TokenProperty::from(Token::Default));
+                }
             }
-        },
+        }
         "NamedArgExpr" => quote! {
             // =>
             tokens.push(TokenProperty::from(Token::EqualsGreater));
@@ -529,10 +591,117 @@ fn custom_handlers(node: &Node) -> TokenStream {
         "TypeCast" => quote! {
             tokens.push(TokenProperty::from(Token::Typecast));
         },
+        "CreateCastStmt" => {
+            let context = enum_field(
+                "context",
+                &[
+                    ("CoercionImplicit", &["As", "ImplicitP"]),
+                    ("CoercionAssignment", &["As", "Assignment"]),
+                ],
+            );
+            quote! {
+                tokens.push(TokenProperty::from(Token::Create));
+                tokens.push(TokenProperty::from(Token::Cast));
+                tokens.push(TokenProperty::from(Token::As));
+                if n.inout {
+                    tokens.push(TokenProperty::from(Token::With));
+                    tokens.push(TokenProperty::from(Token::Inout));
+                } else if n.func.is_some() {
+                    tokens.push(TokenProperty::from(Token::With));
+                    tokens.push(TokenProperty::from(Token::Function));
+                } else {
+                    tokens.push(TokenProperty::from(Token::Without));
+                    tokens.push(TokenProperty::from(Token::Function));
+                }
+                #context
+            }
+        }
+        "RuleStmt" => {
+            let event = enum_field(
+                "event",
+                &[
+                    ("CmdSelect", &["Select"]),
+                    ("CmdUpdate", &["Update"]),
+                    ("CmdInsert", &["Insert"]),
+                    ("CmdDelete", &["DeleteP"]),
+                ],
+            );
+            quote! {
+                tokens.push(TokenProperty::from(Token::Create));
+                if n.replace {
+                    tokens.push(TokenProperty::from(Token::Or));
+                    tokens.push(TokenProperty::from(Token::Replace));
+                }
+                tokens.push(TokenProperty::from(Token::Rule));
+                tokens.push(TokenProperty::from(Token::As));
+                tokens.push(TokenProperty::from(Token::On));
+                #event
+                tokens.push(TokenProperty::from(Token::To));
+                if n.where_clause.is_some() {
+                    tokens.push(TokenProperty::from(Token::Where));
+                }
+                tokens.push(TokenProperty::from(Token::Do));
+                if n.instead {
+                    tokens.push(TokenProperty::from(Token::Instead));
+                } else {
+                    tokens.push(TokenProperty::from(Token::Also));
+                }
+                if n.actions.len() == 0 {
+                    tokens.push(TokenProperty::from(Token::Nothing));
+                }
+            }
+        }
+        "NotifyStmt" => quote! {
+            tokens.push(TokenProperty::from(Token::Notify));
+        },
         _ => quote! {},
     }
 }
 
+/// Returns a match on the enum field `field` of `node` that pushes the tokens of each of
+/// `variants`, given by the name of the variant in the proto file. All other values, including
+/// variants added by later libpg_query releases, push no tokens.
+fn enum_handler(
+    proto_file: &ProtoFile,
+    node: &Node,
+    field: &str,
+    variants: &[(&str, &[&str])],
+) -> TokenStream {
+    let enum_name = node
+        .fields
+        .iter()
+        .find(|f| f.name == field && f.field_type == FieldType::Enum)
+        .and_then(|f| f.node_name.as_ref())
+        .unwrap_or_else(|| panic!("{}.{} is not an enum field", node.name, field));
+    let enum_type = proto_file
+        .enum_type(enum_name)
+        .unwrap_or_else(|| panic!("Unknown enum {}", enum_name));
+
+    let arms = variants.iter().map(|(variant, tokens)| {
+        let value = enum_type
+            .values
+            .iter()
+            .find(|v| v.name == *variant)
+            .unwrap_or_else(|| panic!("Unknown variant {} of enum {}", variant, enum_name))
+            .value;
+        let value = Literal::i32_unsuffixed(value);
+        let tokens = tokens.iter().map(|t| format_ident!("{}", t));
+        quote! {
+            #value => {
+                #(tokens.push(TokenProperty::from(Token::#tokens));)*
+            }
+        }
+    });
+    let field = format_ident!("{}", field);
+
+    quote! {
+        match n.#field {
+            #(#arms)*
+            _ => {}
+        }
+    }
+}
+
 fn string_property_handlers(node: &Node) -> Vec<TokenStream> {
     node.fields
         .iter()
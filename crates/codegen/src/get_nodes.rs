@@ -10,6 +10,7 @@ pub fn get_nodes_mod(proto_file: &ProtoFile) -> proc_macro2::TokenStream {
 
     quote! {
         #[derive(Debug, Clone)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
         pub struct Node {
             pub kind: SyntaxKind,
             pub depth: usize,
@@ -17,6 +18,29 @@ pub fn get_nodes_mod(proto_file: &ProtoFile) -> proc_macro2::TokenStream {
             pub location: Option<usize>,
         }
 
+        /// Returns the direct children of the node
+        pub fn get_children(node: &NodeEnum) -> Vec<NodeEnum> {
+            let mut children = Vec::new();
+            let mut handle_child = |c: NodeEnum| children.push(c);
+            match node {
+                // `AConst` is the only node with a `one of` property, so we handle it manually
+                // if you need to handle other nodes manually, add them to the `manual_node_names` function below
+                NodeEnum::AConst(n) => {
+                    if n.val.is_some() {
+                        handle_child(match n.val.to_owned().unwrap() {
+                            pg_query::protobuf::a_const::Val::Ival(v) => NodeEnum::Integer(v),
+                            pg_query::protobuf::a_const::Val::Fval(v) => NodeEnum::Float(v),
+                            pg_query::protobuf::a_const::Val::Boolval(v) => NodeEnum::Boolean(v),
+                            pg_query::protobuf::a_const::Val::Sval(v) => NodeEnum::String(v),
+                            pg_query::protobuf::a_const::Val::Bsval(v) => NodeEnum::BitString(v),
+                        });
+                    }
+                }
+                #(NodeEnum::#node_identifiers(n) => {#node_handlers}),*,
+            };
+            children
+        }
+
         /// Returns all children of the node, recursively
         /// location is resolved manually
         pub fn get_nodes(node: &NodeEnum, at_depth: usize) -> StableGraph<Node, ()> {
@@ -35,7 +59,7 @@ pub fn get_nodes_mod(proto_file: &ProtoFile) -> proc_macro2::TokenStream {
             while !stack.is_empty() {
                 let (parent_idx, node, depth) = stack.pop_front().unwrap();
                 let current_depth = depth + 1;
-                let mut handle_child = |c: NodeEnum| {
+                for c in get_children(&node) {
                     if match &c {
                         // all "simple nodes" are not handled individually but merged with their parent
                         NodeEnum::String(n) => true,
@@ -56,23 +80,7 @@ pub fn get_nodes_mod(proto_file: &ProtoFile) -> proc_macro2::TokenStream {
                         g.add_edge(parent_idx, node_idx, ());
                         stack.push_back((node_idx, c.to_owned(), current_depth));
                     }
-                };
-                match &node {
-                    // `AConst` is the only node with a `one of` property, so we handle it manually
-                    // if you need to handle other nodes manually, add them to the `manual_node_names` function below
-                    NodeEnum::AConst(n) => {
-                        if n.val.is_some() {
-                            handle_child(match n.val.to_owned().unwrap() {
-                                pg_query::protobuf::a_const::Val::Ival(v) => NodeEnum::Integer(v),
-                                pg_query::protobuf::a_const::Val::Fval(v) => NodeEnum::Float(v),
-                                pg_query::protobuf::a_const::Val::Boolval(v) => NodeEnum::Boolean(v),
-                                pg_query::protobuf::a_const::Val::Sval(v) => NodeEnum::String(v),
-                                pg_query::protobuf::a_const::Val::Bsval(v) => NodeEnum::BitString(v),
-                            });
-                        }
-                    }
-                    #(NodeEnum::#node_identifiers(n) => {#node_handlers}),*,
-                };
+                }
             }
             g
         }
@@ -0,0 +1,253 @@
+use pg_query_proto_parser::ProtoFile;
+use proc_macro2::Ident;
+use quote::{format_ident, quote};
+
+pub fn keyword_category_mod(proto_file: &ProtoFile) -> proc_macro2::TokenStream {
+    let token_value = |name: &str| {
+        proto_file
+            .tokens
+            .iter()
+            .find(|t| t.name == name)
+            .map(|t| t.value)
+            .unwrap()
+    };
+    // all keywords are listed alphabetically between these tokens
+    let keywords = token_value("AbortP")..=token_value("Zone");
+
+    let categorized = [
+        reserved_keywords(),
+        col_name_keywords(),
+        type_func_name_keywords(),
+    ]
+    .concat();
+    let category_identifiers = |names: Vec<&str>| -> Vec<Ident> {
+        names
+            .into_iter()
+            .filter(|name| proto_file.tokens.iter().any(|t| t.name == *name))
+            .map(|name| format_ident!("{}", name))
+            .collect()
+    };
+
+    let reserved = category_identifiers(reserved_keywords());
+    let col_name = category_identifiers(col_name_keywords());
+    let type_func_name = category_identifiers(type_func_name_keywords());
+    let unreserved = proto_file
+        .tokens
+        .iter()
+        .filter(|t| keywords.contains(&t.value) && !categorized.contains(&t.name.as_str()))
+        .map(|t| format_ident!("{}", t.name))
+        .collect::<Vec<_>>();
+
+    quote! {
+        /// The category of a keyword, which determines where it can be used as an identifier
+        /// without quoting, as defined in Postgres' `kwlist.h`
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        pub enum KeywordCategory {
+            /// Can be used as any identifier
+            Unreserved,
+            /// Can be used as a column or table name, but not as a function or type name
+            ColName,
+            /// Can be used as a function or type name, but not as a column or table name
+            TypeFuncName,
+            /// Can only be used as a column label after `AS`
+            Reserved,
+        }
+
+        impl KeywordCategory {
+            /// Returns true if a keyword of this category can be used as a column or table name
+            /// without quoting
+            pub fn is_col_name(&self) -> bool {
+                matches!(self, KeywordCategory::Unreserved | KeywordCategory::ColName)
+            }
+
+            /// Returns true if a keyword of this category can be used as a function or type name
+            /// without quoting
+            pub fn is_type_func_name(&self) -> bool {
+                matches!(self, KeywordCategory::Unreserved | KeywordCategory::TypeFuncName)
+            }
+        }
+
+        impl SyntaxKind {
+            /// Returns the category of the keyword, or `None` if this is not a keyword
+            pub fn keyword_category(&self) -> Option<KeywordCategory> {
+                match self {
+                    #(SyntaxKind::#unreserved)|* => Some(KeywordCategory::Unreserved),
+                    #(SyntaxKind::#col_name)|* => Some(KeywordCategory::ColName),
+                    #(SyntaxKind::#type_func_name)|* => Some(KeywordCategory::TypeFuncName),
+                    #(SyntaxKind::#reserved)|* => Some(KeywordCategory::Reserved),
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+/// Keywords of the category `RESERVED_KEYWORD`
+fn reserved_keywords() -> Vec<&'static str> {
+    vec![
+        "All",
+        "Analyse",
+        "Analyze",
+        "And",
+        "Any",
+        "Array",
+        "As",
+        "Asc",
+        "Asymmetric",
+        "Both",
+        "Case",
+        "Cast",
+        "Check",
+        "Collate",
+        "Column",
+        "Constraint",
+        "Create",
+        "CurrentCatalog",
+        "CurrentDate",
+        "CurrentRole",
+        "CurrentTime",
+        "CurrentTimestamp",
+        "CurrentUser",
+        "Default",
+        "Deferrable",
+        "Desc",
+        "Distinct",
+        "Do",
+        "Else",
+        "EndP",
+        "Except",
+        "FalseP",
+        "Fetch",
+        "For",
+        "Foreign",
+        "From",
+        "Grant",
+        "GroupP",
+        "Having",
+        "InP",
+        "Initially",
+        "Intersect",
+        "Into",
+        "LateralP",
+        "Leading",
+        "Limit",
+        "Localtime",
+        "Localtimestamp",
+        "Not",
+        "NullP",
+        "Offset",
+        "On",
+        "Only",
+        "Or",
+        "Order",
+        "Placing",
+        "Primary",
+        "References",
+        "Returning",
+        "Select",
+        "SessionUser",
+        "Some",
+        "Symmetric",
+        "Table",
+        "Then",
+        "To",
+        "Trailing",
+        "TrueP",
+        "Union",
+        "Unique",
+        "User",
+        "Using",
+        "Variadic",
+        "When",
+        "Where",
+        "Window",
+        "With",
+    ]
+}
+
+/// Keywords of the category `COL_NAME_KEYWORD`
+fn col_name_keywords() -> Vec<&'static str> {
+    vec![
+        "Between",
+        "Bigint",
+        "Bit",
+        "BooleanP",
+        "CharP",
+        "Character",
+        "Coalesce",
+        "Dec",
+        "DecimalP",
+        "Exists",
+        "Extract",
+        "FloatP",
+        "Greatest",
+        "Grouping",
+        "Inout",
+        "IntP",
+        "Integer",
+        "Interval",
+        "Least",
+        "National",
+        "Nchar",
+        "None",
+        "Normalize",
+        "Nullif",
+        "Numeric",
+        "OutP",
+        "Overlay",
+        "Position",
+        "Precision",
+        "Real",
+        "Row",
+        "Setof",
+        "Smallint",
+        "Substring",
+        "Time",
+        "Timestamp",
+        "Treat",
+        "Trim",
+        "Values",
+        "Varchar",
+        "Xmlattributes",
+        "Xmlconcat",
+        "Xmlelement",
+        "Xmlexists",
+        "Xmlforest",
+        "Xmlnamespaces",
+        "Xmlparse",
+        "Xmlpi",
+        "Xmlroot",
+        "Xmlserialize",
+        "Xmltable",
+    ]
+}
+
+/// Keywords of the category `TYPE_FUNC_NAME_KEYWORD`
+fn type_func_name_keywords() -> Vec<&'static str> {
+    vec![
+        "Authorization",
+        "Binary",
+        "Collation",
+        "Concurrently",
+        "Cross",
+        "CurrentSchema",
+        "Freeze",
+        "Full",
+        "Ilike",
+        "InnerP",
+        "Is",
+        "Isnull",
+        "Join",
+        "Left",
+        "Like",
+        "Natural",
+        "Notnull",
+        "OuterP",
+        "Overlaps",
+        "Right",
+        "Similar",
+        "Tablesample",
+        "Verbose",
+    ]
+}
@@ -1,12 +1,22 @@
+//! Generation of the `codegen` module of the parser from the protobuf definition of pg_query.
+//!
+//! The module is either expanded in place by the `parser_codegen!` macro of `codegen_macros`, or
+//! written to `OUT_DIR` by the build script of the parser with [`write_parser_mod`] if its
+//! `codegen_build_script` feature is enabled. Both find the proto file with [`proto_path`].
+
+mod build;
+mod expr_info;
 mod get_location;
 mod get_node_properties;
 mod get_nodes;
+mod keyword_category;
+mod node_accessors;
 mod parser;
+mod pg_version;
+mod predicates;
+mod proto_path;
 mod syntax_kind;
 
-use parser::parser_mod;
-
-#[proc_macro]
-pub fn parser_codegen(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    parser_mod(item.into()).into()
-}
+pub use crate::build::write_parser_mod;
+pub use crate::parser::parser_mod;
+pub use crate::proto_path::{proto_path, PROTO_PATH_VAR};
@@ -0,0 +1,164 @@
+use pg_query_proto_parser::{Field, FieldType, Node, ProtoFile};
+use proc_macro2::{Ident, TokenStream};
+use quote::{format_ident, quote};
+
+pub fn node_accessors_mod(proto_file: &ProtoFile) -> proc_macro2::TokenStream {
+    let typed_lists = typed_lists();
+
+    let traits = proto_file
+        .nodes
+        .iter()
+        .filter_map(|node| node_trait(node, &typed_lists));
+
+    quote! {
+        /// Typed accessors for the children of the nodes, e.g. `SelectStmt::target_list()`.
+        ///
+        /// There is one trait per node, so the accessors are best imported with
+        /// `use parser::accessors::*`.
+        pub mod accessors {
+            use pg_query::NodeEnum;
+
+            #(#traits)*
+        }
+    }
+}
+
+/// Lists of generic nodes whose items are always of the same node, as
+/// (node name, field name, item node name)
+///
+/// The accessors of these lists yield the items as the concrete node. All other lists of generic
+/// nodes yield `NodeEnum`s.
+fn typed_lists() -> Vec<(&'static str, &'static str, &'static str)> {
+    vec![
+        ("SelectStmt", "target_list", "ResTarget"),
+        ("SelectStmt", "window_clause", "WindowDef"),
+        ("SelectStmt", "sort_clause", "SortBy"),
+        ("SelectStmt", "locking_clause", "LockingClause"),
+        ("InsertStmt", "cols", "ResTarget"),
+        ("InsertStmt", "returning_list", "ResTarget"),
+        ("UpdateStmt", "target_list", "ResTarget"),
+        ("UpdateStmt", "returning_list", "ResTarget"),
+        ("DeleteStmt", "returning_list", "ResTarget"),
+        ("MergeStmt", "merge_when_clauses", "MergeWhenClause"),
+        ("WithClause", "ctes", "CommonTableExpr"),
+        ("CreateStmt", "inh_relations", "RangeVar"),
+        ("CreateStmt", "constraints", "Constraint"),
+        ("CreateStmt", "options", "DefElem"),
+        ("ColumnDef", "constraints", "Constraint"),
+        ("AlterTableStmt", "cmds", "AlterTableCmd"),
+        ("IndexStmt", "index_params", "IndexElem"),
+        ("IndexStmt", "index_including_params", "IndexElem"),
+        ("CompositeTypeStmt", "coldeflist", "ColumnDef"),
+        ("CreateEnumStmt", "type_name", "String"),
+        ("CreateEnumStmt", "vals", "String"),
+        ("CreateFunctionStmt", "funcname", "String"),
+        ("CreateFunctionStmt", "parameters", "FunctionParameter"),
+        ("CreateFunctionStmt", "options", "DefElem"),
+        ("ViewStmt", "aliases", "String"),
+        ("TypeName", "names", "String"),
+        ("FuncCall", "funcname", "String"),
+        ("FuncCall", "agg_order", "SortBy"),
+    ]
+}
+
+fn node_trait(node: &Node, typed_lists: &[(&str, &str, &str)]) -> Option<TokenStream> {
+    let accessors = node
+        .fields
+        .iter()
+        // `one of` properties such as the value of `AConst` are left out
+        .filter(|field| field.field_type == FieldType::Node && !field.is_one_of)
+        .map(|field| {
+            let item = typed_lists
+                .iter()
+                .find(|(n, f, _)| *n == node.name && *f == field.name)
+                .map(|(_, _, item)| format_ident!("{}", item));
+            accessor(field, item)
+        })
+        .collect::<Vec<_>>();
+    if accessors.is_empty() {
+        return None;
+    }
+
+    let node_identifier = format_ident!("{}", node.name);
+    let trait_identifier = format_ident!("{}Children", node.name);
+    let doc = format!(
+        "Accessors for the children of [`pg_query::protobuf::{}`]",
+        node.name
+    );
+    let signatures = accessors.iter().map(|(signature, _)| signature);
+    let methods = accessors
+        .iter()
+        .map(|(signature, body)| quote! { #signature #body });
+
+    Some(quote! {
+        #[doc = #doc]
+        pub trait #trait_identifier {
+            #(#signatures;)*
+        }
+
+        impl #trait_identifier for pg_query::protobuf::#node_identifier {
+            #(#methods)*
+        }
+    })
+}
+
+/// Returns the signature and the body of the accessor for `field`. `item` is the node of the items
+/// of a typed list.
+fn accessor(field: &Field, item: Option<Ident>) -> (TokenStream, TokenStream) {
+    let field_name = format_ident!("{}", field.name.as_str());
+    if field.repeated {
+        match item {
+            Some(item) => (
+                quote! {
+                    fn #field_name(&self) -> impl Iterator<Item = &pg_query::protobuf::#item>
+                },
+                quote! {
+                    {
+                        self.#field_name.iter().filter_map(|n| {
+                            let item: &pg_query::protobuf::#item = match n.node.as_ref()? {
+                                NodeEnum::#item(item) => item,
+                                _ => return None,
+                            };
+                            Some(item)
+                        })
+                    }
+                },
+            ),
+            None => (
+                quote! {
+                    fn #field_name(&self) -> impl Iterator<Item = &NodeEnum>
+                },
+                quote! {
+                    {
+                        self.#field_name.iter().filter_map(|n| n.node.as_ref())
+                    }
+                },
+            ),
+        }
+    } else if field.node_name == Some("Node".to_owned()) {
+        (
+            quote! {
+                fn #field_name(&self) -> Option<&NodeEnum>
+            },
+            quote! {
+                {
+                    self.#field_name.as_ref().and_then(|n| n.node.as_ref())
+                }
+            },
+        )
+    } else {
+        let node = format_ident!("{}", field.enum_variant_name.as_ref().unwrap().as_str());
+        (
+            quote! {
+                fn #field_name(&self) -> Option<&pg_query::protobuf::#node>
+            },
+            quote! {
+                {
+                    // some nodes are boxed, so let deref coercion handle both cases
+                    let n: &pg_query::protobuf::#node = self.#field_name.as_ref()?;
+                    Some(n)
+                }
+            },
+        )
+    }
+}
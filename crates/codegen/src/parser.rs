@@ -1,19 +1,30 @@
+use std::path::Path;
+
 use pg_query_proto_parser::ProtoParser;
 use quote::quote;
 
 use crate::{
-    get_location::get_location_mod, get_node_properties::get_node_properties_mod,
-    get_nodes::get_nodes_mod, syntax_kind::syntax_kind_mod,
+    expr_info::expr_info_mod, get_location::get_location_mod,
+    get_node_properties::get_node_properties_mod, get_nodes::get_nodes_mod,
+    keyword_category::keyword_category_mod, node_accessors::node_accessors_mod,
+    pg_version::pg_version_mod, predicates::predicates_mod, syntax_kind::syntax_kind_mod,
 };
 
-pub fn parser_mod(_item: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
-    let parser = ProtoParser::new("libpg_query/protobuf/pg_query.proto");
+/// Returns the code of the `codegen` module of the parser, generated from the proto file of
+/// pg_query at `proto_path`
+pub fn parser_mod(proto_path: &Path) -> proc_macro2::TokenStream {
+    let parser = ProtoParser::new(proto_path.to_str().expect("non-UTF-8 proto path"));
     let proto_file = parser.parse();
 
     let syntax_kind = syntax_kind_mod(&proto_file);
     let get_location = get_location_mod(&proto_file);
     let get_node_properties = get_node_properties_mod(&proto_file);
     let get_nodes = get_nodes_mod(&proto_file);
+    let pg_version = pg_version_mod(&proto_file);
+    let expr_info = expr_info_mod();
+    let keyword_category = keyword_category_mod(&proto_file);
+    let predicates = predicates_mod(&proto_file);
+    let node_accessors = node_accessors_mod(&proto_file);
 
     quote! {
         use std::collections::VecDeque;
@@ -30,5 +41,10 @@ pub fn parser_mod(_item: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
         #get_location
         #get_node_properties
         #get_nodes
+        #pg_version
+        #expr_info
+        #keyword_category
+        #predicates
+        #node_accessors
     }
 }
@@ -0,0 +1,65 @@
+use pg_query_proto_parser::ProtoFile;
+use proc_macro2::Literal;
+use quote::{format_ident, quote};
+
+pub fn pg_version_mod(proto_file: &ProtoFile) -> proc_macro2::TokenStream {
+    let versioned_nodes = versioned_nodes()
+        .into_iter()
+        .filter(|(name, _, _)| proto_file.nodes.iter().any(|n| n.name == *name))
+        .collect::<Vec<_>>();
+
+    let node_identifiers = versioned_nodes
+        .iter()
+        .map(|(name, _, _)| format_ident!("{}", name));
+    let features = versioned_nodes
+        .iter()
+        .map(|(_, feature, _)| Literal::string(feature));
+    let versions = versioned_nodes
+        .iter()
+        .map(|(_, _, version)| Literal::u32_unsuffixed(*version));
+
+    quote! {
+        impl SyntaxKind {
+            /// Returns the name of the feature represented by this node together with the major
+            /// version of Postgres that introduced it, if it is not available in all supported
+            /// versions
+            pub fn required_pg_version(&self) -> Option<(&'static str, u32)> {
+                match self {
+                    #(SyntaxKind::#node_identifiers => Some((#features, #versions))),*,
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+/// Nodes that represent syntax which is only valid starting with a specific major version, as
+/// (node name, feature, major version)
+///
+/// Nodes that only ever appear within another node of the list, e.g. the `MergeWhenClause` of a
+/// `MergeStmt`, are omitted.
+fn versioned_nodes() -> Vec<(&'static str, &'static str, u32)> {
+    vec![
+        ("PartitionSpec", "PARTITION BY", 10),
+        ("PartitionBoundSpec", "PARTITION OF", 10),
+        ("CreatePublicationStmt", "CREATE PUBLICATION", 10),
+        ("CreateSubscriptionStmt", "CREATE SUBSCRIPTION", 10),
+        ("CreateStatsStmt", "CREATE STATISTICS", 10),
+        (
+            "AlterCollationStmt",
+            "ALTER COLLATION ... REFRESH VERSION",
+            10,
+        ),
+        ("CallStmt", "CALL", 11),
+        ("AlterStatsStmt", "ALTER STATISTICS", 13),
+        ("ReturnStmt", "SQL-standard function bodies", 14),
+        ("CtesearchClause", "SEARCH clause", 14),
+        ("CtecycleClause", "CYCLE clause", 14),
+        ("MergeStmt", "MERGE", 15),
+        (
+            "AlterDatabaseRefreshCollStmt",
+            "ALTER DATABASE ... REFRESH COLLATION VERSION",
+            15,
+        ),
+    ]
+}
@@ -0,0 +1,225 @@
+use pg_query_proto_parser::ProtoFile;
+use proc_macro2::Ident;
+use quote::{format_ident, quote};
+
+pub fn predicates_mod(proto_file: &ProtoFile) -> proc_macro2::TokenStream {
+    let first_keyword = proto_file
+        .tokens
+        .iter()
+        .find(|t| t.name == "AbortP")
+        .map(|t| t.value)
+        .unwrap();
+
+    let token_names = proto_file
+        .tokens
+        .iter()
+        .map(|t| t.name.as_str())
+        .collect::<Vec<_>>();
+    let literals = identifiers(&token_names, literal_tokens());
+    let trivia = trivia_tokens()
+        .into_iter()
+        .map(|name| format_ident!("{}", name))
+        .collect::<Vec<_>>();
+    // all tokens before the keywords are punctuation, except for the named ones that carry text
+    let puncts = proto_file
+        .tokens
+        .iter()
+        .filter(|t| {
+            t.value > 0
+                && t.value < first_keyword
+                && !literal_tokens().contains(&t.name.as_str())
+                && !trivia_tokens().contains(&t.name.as_str())
+                && !["Ident", "Uident", "Param"].contains(&t.name.as_str())
+        })
+        .map(|t| format_ident!("{}", t.name))
+        .collect::<Vec<_>>();
+
+    let stmts = proto_file
+        .nodes
+        .iter()
+        .map(|n| n.name.as_str())
+        .filter(|name| name.ends_with("Stmt") && *name != "RawStmt")
+        .collect::<Vec<_>>();
+    let is_ddl = |name: &str| {
+        let is_create_alter_drop = ["Create", "Alter", "Drop"]
+            .iter()
+            .any(|prefix| name.starts_with(prefix));
+        (is_create_alter_drop || other_ddl_stmts().contains(&name))
+            && !non_ddl_stmts().contains(&name)
+            && !dcl_stmts().contains(&name)
+    };
+    let ddl = stmts
+        .iter()
+        .filter(|name| is_ddl(name))
+        .map(|name| format_ident!("{}", name))
+        .collect::<Vec<_>>();
+    let dml = identifiers(&stmts, dml_stmts());
+    let dcl = identifiers(&stmts, dcl_stmts());
+    let tcl = identifiers(&stmts, tcl_stmts());
+    let utility = stmts
+        .iter()
+        .filter(|name| {
+            !is_ddl(name)
+                && !dml_stmts().contains(name)
+                && !dcl_stmts().contains(name)
+                && !tcl_stmts().contains(name)
+        })
+        .map(|name| format_ident!("{}", name))
+        .collect::<Vec<_>>();
+
+    quote! {
+        /// The category of a statement
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        pub enum StmtKind {
+            /// Data definition, e.g. `CREATE TABLE` or `ALTER INDEX`
+            Ddl,
+            /// Data manipulation, e.g. `SELECT` or `UPDATE`
+            Dml,
+            /// Data control, i.e. privileges and roles, e.g. `GRANT` or `CREATE ROLE`
+            Dcl,
+            /// Transaction control, e.g. `BEGIN` or `COMMIT`
+            Tcl,
+            /// Everything else, e.g. `SET`, `VACUUM` or `EXPLAIN`
+            Utility,
+        }
+
+        impl Display for StmtKind {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                let name = match self {
+                    StmtKind::Ddl => "DDL",
+                    StmtKind::Dml => "DML",
+                    StmtKind::Dcl => "DCL",
+                    StmtKind::Tcl => "TCL",
+                    StmtKind::Utility => "utility",
+                };
+                write!(f, "{}", name)
+            }
+        }
+
+        impl SyntaxKind {
+            /// Returns true if this is a keyword token, reserved or not
+            pub fn is_keyword(&self) -> bool {
+                self.keyword_category().is_some()
+            }
+
+            /// Returns true if this is a numeric, string or bit string literal token
+            pub fn is_literal(&self) -> bool {
+                matches!(self, #(SyntaxKind::#literals)|*)
+            }
+
+            /// Returns true if this is a punctuation or operator token, e.g. `(`, `::` or `+`
+            pub fn is_punct(&self) -> bool {
+                matches!(self, #(SyntaxKind::#puncts)|*)
+            }
+
+            /// Returns the category of this statement, or `None` if this is not a statement
+            pub fn stmt_kind(&self) -> Option<StmtKind> {
+                match self {
+                    #(SyntaxKind::#ddl)|* => Some(StmtKind::Ddl),
+                    #(SyntaxKind::#dml)|* => Some(StmtKind::Dml),
+                    #(SyntaxKind::#dcl)|* => Some(StmtKind::Dcl),
+                    #(SyntaxKind::#tcl)|* => Some(StmtKind::Tcl),
+                    #(SyntaxKind::#utility)|* => Some(StmtKind::Utility),
+                    _ => None,
+                }
+            }
+
+            /// Returns true if this is a statement that defines, changes or removes a database
+            /// object other than a role or a privilege
+            pub fn is_ddl_stmt(&self) -> bool {
+                self.stmt_kind() == Some(StmtKind::Ddl)
+            }
+
+            /// Returns true if this is a statement that reads or modifies the rows of a table
+            pub fn is_dml_stmt(&self) -> bool {
+                self.stmt_kind() == Some(StmtKind::Dml)
+            }
+
+            /// Returns true if this is a token without meaning for the parser, i.e. whitespace and
+            /// comments
+            pub fn is_trivia(&self) -> bool {
+                matches!(self, #(SyntaxKind::#trivia)|*)
+            }
+        }
+    }
+}
+
+/// Returns the identifiers of those `names` that are in `existing`
+fn identifiers(existing: &[&str], names: Vec<&str>) -> Vec<Ident> {
+    names
+        .into_iter()
+        .filter(|name| existing.contains(name))
+        .map(|name| format_ident!("{}", name))
+        .collect()
+}
+
+fn literal_tokens() -> Vec<&'static str> {
+    vec!["Iconst", "Fconst", "Sconst", "Usconst", "Bconst", "Xconst"]
+}
+
+/// The comment tokens of pg_query and the custom trivia kinds of the lexer
+fn trivia_tokens() -> Vec<&'static str> {
+    vec![
+        "SqlComment",
+        "CComment",
+        "Whitespace",
+        "Newline",
+        "Tab",
+        "LineComment",
+        "BlockComment",
+        "Shebang",
+    ]
+}
+
+/// DDL statements that do not start with `Create`, `Alter` or `Drop`
+fn other_ddl_stmts() -> Vec<&'static str> {
+    vec![
+        "DefineStmt",
+        "IndexStmt",
+        "ViewStmt",
+        "CompositeTypeStmt",
+        "RuleStmt",
+        "RenameStmt",
+        "CommentStmt",
+        "SecLabelStmt",
+        "GrantStmt",
+        "GrantRoleStmt",
+        "ImportForeignSchemaStmt",
+        "RefreshMatViewStmt",
+    ]
+}
+
+/// Statements that start with `Alter` but change the configuration instead of an object
+fn non_ddl_stmts() -> Vec<&'static str> {
+    vec!["AlterSystemStmt"]
+}
+
+/// Statements that manage roles and their privileges
+fn dcl_stmts() -> Vec<&'static str> {
+    vec![
+        "GrantStmt",
+        "GrantRoleStmt",
+        "AlterDefaultPrivilegesStmt",
+        "CreateRoleStmt",
+        "AlterRoleStmt",
+        "AlterRoleSetStmt",
+        "DropRoleStmt",
+        "ReassignOwnedStmt",
+        "DropOwnedStmt",
+    ]
+}
+
+fn tcl_stmts() -> Vec<&'static str> {
+    vec!["TransactionStmt"]
+}
+
+fn dml_stmts() -> Vec<&'static str> {
+    vec![
+        "SelectStmt",
+        "InsertStmt",
+        "UpdateStmt",
+        "DeleteStmt",
+        "MergeStmt",
+    ]
+}
@@ -0,0 +1,44 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// The environment variable that overrides the proto file to generate the code from
+pub const PROTO_PATH_VAR: &str = "PG_QUERY_PROTO";
+
+/// Returns the proto file of pg_query to generate the code from, which is the file given by
+/// `$PG_QUERY_PROTO`, or else `path`
+///
+/// Relative paths are resolved against the manifest directory of the crate being built, so that
+/// they also work when the crate is built as a dependency.
+///
+/// Panics if neither is set or the file does not exist. pg_query does not tell dependent crates
+/// where its bundled proto file is, so outside of the repository, e.g. when the parser is built
+/// from a registry, `$PG_QUERY_PROTO` has to point to it.
+pub fn proto_path(path: Option<&str>) -> PathBuf {
+    let path = match env::var(PROTO_PATH_VAR) {
+        Ok(path) => manifest_relative(&path),
+        Err(_) => path.map(manifest_relative).unwrap_or_else(|| {
+            panic!(
+                "no proto file of pg_query given, set ${} to the path of pg_query.proto",
+                PROTO_PATH_VAR
+            )
+        }),
+    };
+    if !path.exists() {
+        panic!(
+            "the proto file of pg_query was not found at {}, set ${} to the path of \
+            pg_query.proto, e.g. libpg_query/protobuf/pg_query.proto in the sources of the \
+            pg_query crate",
+            path.display(),
+            PROTO_PATH_VAR
+        );
+    }
+    path
+}
+
+fn manifest_relative(path: &str) -> PathBuf {
+    match env::var_os("CARGO_MANIFEST_DIR") {
+        // joining an absolute path replaces the directory
+        Some(dir) => Path::new(&dir).join(path),
+        None => PathBuf::from(path),
+    }
+}
@@ -27,6 +27,7 @@ pub fn syntax_kind_mod(proto_file: &ProtoFile) -> proc_macro2::TokenStream {
         /// sql dialect, and a few custom ones that are not parsed by pg_query.rs, such
         /// as `Whitespace`.
         #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Syntax)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
         #[repr(u32)]
         pub enum SyntaxKind {
             #(#unique_enum_variants),*,
@@ -39,7 +40,12 @@ pub fn syntax_kind_mod(proto_file: &ProtoFile) -> proc_macro2::TokenStream {
 fn custom_node_names() -> Vec<&'static str> {
     vec![
         "SourceFile",
-        "Comment",
+        "LineComment",
+        "BlockComment",
+        "Shebang",
+        "CopyData",
+        "CopyDataRow",
+        "CopyDataEnd",
         "Whitespace",
         "Newline",
         "Tab",
@@ -0,0 +1,29 @@
+use codegen::{parser_mod, proto_path};
+use proc_macro::{TokenStream, TokenTree};
+
+/// Expands the `codegen` module of the parser in place
+///
+/// Takes the path of the proto file of pg_query as an optional string literal, relative to the
+/// manifest of the invoking crate. See [`proto_path`] for how it is resolved.
+#[proc_macro]
+pub fn parser_codegen(item: TokenStream) -> TokenStream {
+    let path = item.into_iter().next().map(|token| match token {
+        TokenTree::Literal(literal) => literal
+            .to_string()
+            .strip_prefix('"')
+            .and_then(|path| path.strip_suffix('"'))
+            .map(|path| path.to_string())
+            .expect("expected the path of the proto file as a string literal"),
+        _ => panic!("expected the path of the proto file as a string literal"),
+    });
+    let proto_path = proto_path(path.as_deref());
+
+    let parser_mod = parser_mod(&proto_path);
+    let proto_path = proto_path.to_string_lossy();
+    quote::quote! {
+        // recompile when the proto file changes
+        const _: &[u8] = include_bytes!(#proto_path);
+        #parser_mod
+    }
+    .into()
+}
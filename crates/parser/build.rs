@@ -0,0 +1,23 @@
+/// The proto file within the repository, which is used unless `$PG_QUERY_PROTO` is set. Outside
+/// of the repository, `$PG_QUERY_PROTO` has to be set.
+#[cfg(feature = "codegen_build_script")]
+const PROTO_PATH: &str = "../../libpg_query/protobuf/pg_query.proto";
+
+fn main() {
+    // without the feature, the code is expanded by the `parser_codegen!` macro instead
+    #[cfg(feature = "codegen_build_script")]
+    {
+        use std::env;
+        use std::path::PathBuf;
+
+        // before the proto file is resolved, so that setting the variable after a failed build
+        // runs the script again
+        println!("cargo:rerun-if-env-changed={}", codegen::PROTO_PATH_VAR);
+        let proto_path = codegen::proto_path(Some(PROTO_PATH));
+        println!("cargo:rerun-if-changed={}", proto_path.display());
+
+        let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+        codegen::write_parser_mod(&proto_path, &out_dir.join("codegen.rs"))
+            .expect("failed to generate the codegen module");
+    }
+}
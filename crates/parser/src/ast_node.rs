@@ -1,10 +1,70 @@
 use cstree::text::TextRange;
+use pg_query::protobuf::{Node, ParseResult};
 use pg_query::NodeEnum;
 
 // TODO: implement serde for node: https://serde.rs/remote-derive.html
 
+/// A statement of a parse. `stmt` is the protobuf node of pg_query itself, there is no typed AST
+/// on top of it.
 #[derive(Debug)]
 pub struct RawStmt {
     pub stmt: NodeEnum,
     pub range: TextRange,
 }
+
+impl RawStmt {
+    /// Wraps the statement into the protobuf `RawStmt` of pg_query, located at `range`. The node is
+    /// cloned as it is, nothing is converted.
+    pub fn to_protobuf(&self) -> pg_query::protobuf::RawStmt {
+        pg_query::protobuf::RawStmt {
+            stmt: Some(
+                Node {
+                    node: Some(self.stmt.clone()),
+                }
+                .into(),
+            ),
+            stmt_location: u32::from(self.range.start()) as i32,
+            stmt_len: u32::from(self.range.len()) as i32,
+        }
+    }
+
+    /// Returns the SQL of the statement as printed by the deparser of pg_query, so that changes to
+    /// `stmt` are reflected in the text
+    pub fn deparse(&self) -> pg_query::Result<String> {
+        deparse(std::slice::from_ref(self))
+    }
+}
+
+/// Returns the SQL of `stmts` as printed by the deparser of pg_query, separated by semicolons.
+/// Comments and formatting of the source text are lost.
+pub fn deparse(stmts: &[RawStmt]) -> pg_query::Result<String> {
+    pg_query::deparse(&ParseResult {
+        stmts: stmts.iter().map(RawStmt::to_protobuf).collect(),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_source;
+
+    use super::*;
+
+    #[test]
+    fn test_deparse() {
+        let mut parse = parse_source("select a from contact;\nselect b from org;");
+        let NodeEnum::SelectStmt(select) = &mut parse.stmts[0].stmt else {
+            panic!("expected a select statement");
+        };
+        let Some(NodeEnum::RangeVar(relation)) = select.from_clause[0].node.as_mut() else {
+            panic!("expected a table");
+        };
+        relation.relname = "person".to_string();
+
+        assert_eq!(parse.stmts[0].deparse().unwrap(), "SELECT a FROM person");
+        assert_eq!(
+            deparse(&parse.stmts).unwrap(),
+            "SELECT a FROM person; SELECT b FROM org"
+        );
+    }
+}
@@ -0,0 +1,90 @@
+//! Construction of syntax trees by hand, e.g. to synthesize sql in refactorings.
+//!
+//! Trees must be built with the `NodeCache` of the tree they are inserted into, so that their
+//! token text can be resolved. See the `make` module for helpers that build common fragments.
+
+use cstree::build::GreenNodeBuilder;
+use cstree::green::GreenNode;
+
+use crate::codegen::SyntaxKind;
+use crate::node_cache::{NodeCache, SharedInterner};
+
+/// Builds a green tree from nodes and tokens
+#[derive(Debug)]
+pub struct SyntaxTreeBuilder {
+    inner: GreenNodeBuilder<'static, 'static, SyntaxKind, SharedInterner>,
+    cache: NodeCache,
+    /// Whether the builder has the shared node cache of `cache`, which it puts back
+    shared: bool,
+    depth: usize,
+}
+
+impl SyntaxTreeBuilder {
+    pub fn new(cache: &NodeCache) -> Self {
+        let (nodes, shared) = cache.take();
+        Self {
+            inner: GreenNodeBuilder::from_cache(nodes),
+            cache: cache.clone(),
+            shared,
+            depth: 0,
+        }
+    }
+
+    /// start a new node of `kind`
+    pub fn start_node(&mut self, kind: SyntaxKind) {
+        self.inner.start_node(kind);
+        self.depth += 1;
+    }
+
+    /// add a token of `kind` with `text` to the current node
+    pub fn token(&mut self, kind: SyntaxKind, text: &str) {
+        self.inner.token(kind, text);
+    }
+
+    /// finish the current node
+    pub fn finish_node(&mut self) {
+        assert!(self.depth > 0, "no node to finish");
+        self.inner.finish_node();
+        self.depth -= 1;
+    }
+
+    /// Returns the root node of the built tree
+    ///
+    /// Panics if a node has not been finished.
+    pub fn finish(self) -> GreenNode {
+        assert_eq!(self.depth, 0, "all nodes must be finished");
+        let (tree, cache) = self.inner.finish();
+        self.cache.put_back(cache.unwrap(), self.shared);
+        tree
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder() {
+        let cache = NodeCache::new();
+        let mut builder = SyntaxTreeBuilder::new(&cache);
+        builder.start_node(SyntaxKind::SourceFile);
+        builder.token(SyntaxKind::Select, "select");
+        builder.token(SyntaxKind::Whitespace, " ");
+        builder.token(SyntaxKind::Iconst, "1");
+        builder.finish_node();
+
+        let root = cache.new_root(builder.finish());
+        assert_eq!(root.kind(), SyntaxKind::SourceFile);
+        assert_eq!(root.text(), "select 1");
+        assert_eq!(root.children_with_tokens().count(), 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_unfinished_node() {
+        let cache = NodeCache::new();
+        let mut builder = SyntaxTreeBuilder::new(&cache);
+        builder.start_node(SyntaxKind::SourceFile);
+        builder.finish();
+    }
+}
@@ -0,0 +1,125 @@
+//! Cancellation of long analyses.
+//!
+//! Parsing and analysing a large document takes long enough that its result can be outdated before
+//! it is done, e.g. because the document changed again or because the request that asked for it
+//! has been cancelled. A `Cancellation` is shared between the analysis and whoever may cancel it,
+//! and the analysis checks it between statements to stop early. It can also carry a deadline, after
+//! which it counts as cancelled without anyone cancelling it, and be linked with other tokens, e.g.
+//! of the document and of the request, so that it is cancelled with either of them.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A token that tells an analysis to stop. Clones share whether it has been cancelled.
+#[derive(Debug, Clone, Default)]
+pub struct Cancellation {
+    cancelled: Arc<AtomicBool>,
+    /// Whether the tokens that this one is linked with have been cancelled
+    linked: Vec<Arc<AtomicBool>>,
+    deadline: Option<Instant>,
+}
+
+/// The error of an analysis that stopped since its `Cancellation` has been cancelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl Cancellation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a token that is cancelled with this one, and in addition once `budget` passed
+    pub fn with_budget(&self, budget: Duration) -> Self {
+        let deadline = Instant::now() + budget;
+        Self {
+            cancelled: self.cancelled.clone(),
+            linked: self.linked.clone(),
+            deadline: Some(self.deadline.map_or(deadline, |d| d.min(deadline))),
+        }
+    }
+
+    /// Returns a token that is cancelled with this one and with `other`, and has the earlier of
+    /// their deadlines
+    pub fn linked_with(&self, other: &Cancellation) -> Self {
+        let mut linked = self.linked.clone();
+        linked.push(other.cancelled.clone());
+        linked.extend(other.linked.iter().cloned());
+        let deadline = match (self.deadline, other.deadline) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        Self {
+            cancelled: self.cancelled.clone(),
+            linked,
+            deadline,
+        }
+    }
+
+    /// Cancels the token and all its clones
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.was_cancelled() || self.deadline.is_some_and(|d| Instant::now() >= d)
+    }
+
+    /// Returns true if the token only counts as cancelled since its deadline passed, and no one
+    /// cancelled it
+    pub fn is_expired(&self) -> bool {
+        !self.was_cancelled() && self.deadline.is_some_and(|d| Instant::now() >= d)
+    }
+
+    fn was_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+            || self
+                .linked
+                .iter()
+                .any(|cancelled| cancelled.load(Ordering::Relaxed))
+    }
+
+    /// Returns `Err(Cancelled)` if the token has been cancelled, to stop with `?`
+    pub fn check(&self) -> Result<(), Cancelled> {
+        if self.is_cancelled() {
+            Err(Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancellation() {
+        let cancellation = Cancellation::new();
+        let budget = cancellation.with_budget(Duration::from_secs(60));
+        assert_eq!(budget.check(), Ok(()));
+        cancellation.cancel();
+        assert_eq!(budget.check(), Err(Cancelled));
+
+        let expired = Cancellation::new().with_budget(Duration::ZERO);
+        assert!(expired.is_cancelled());
+        assert!(expired.is_expired());
+        assert!(!budget.is_expired());
+    }
+
+    #[test]
+    fn test_linked_cancellation() {
+        let document = Cancellation::new();
+        let request = Cancellation::new();
+        let linked = document.linked_with(&request.with_budget(Duration::from_secs(60)));
+        assert_eq!(linked.check(), Ok(()));
+        request.cancel();
+        assert_eq!(linked.check(), Err(Cancelled));
+        assert!(!linked.is_expired());
+        assert!(!document.is_cancelled());
+
+        let expired =
+            Cancellation::new().linked_with(&Cancellation::new().with_budget(Duration::ZERO));
+        assert!(expired.is_expired());
+    }
+}
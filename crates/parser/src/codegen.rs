@@ -1,12 +1,20 @@
-use codegen::parser_codegen;
+// the proto file within the repository, `$PG_QUERY_PROTO` has to be set outside of it
+#[cfg(not(feature = "codegen_build_script"))]
+codegen_macros::parser_codegen!("../../libpg_query/protobuf/pg_query.proto");
 
-parser_codegen!();
+// written by the build script, see `build.rs`
+#[cfg(feature = "codegen_build_script")]
+include!(concat!(env!("OUT_DIR"), "/codegen.rs"));
 
 #[cfg(test)]
 mod tests {
     use log::debug;
 
-    use crate::codegen::{get_nodes, SyntaxKind, TokenProperty};
+    use crate::codegen::accessors::*;
+    use crate::codegen::{
+        expr_info, get_nodes, ExprInfo, KeywordCategory, Precedence, StmtKind, SyntaxKind,
+        TokenProperty,
+    };
 
     fn init() {
         let _ = env_logger::builder().is_test(true).try_init();
@@ -87,4 +95,348 @@ mod tests {
             ],
         )
     }
+
+    #[test]
+    fn test_left_join() {
+        test_get_node_properties(
+            "select 1 from contact left join org on true;",
+            SyntaxKind::JoinExpr,
+            vec![
+                TokenProperty::from(SyntaxKind::Join),
+                TokenProperty::from(SyntaxKind::On),
+                TokenProperty::from(SyntaxKind::Left),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_enum_variant_without_tokens() {
+        test_get_node_properties("select 1 is distinct from 2;", SyntaxKind::AExpr, vec![])
+    }
+
+    #[test]
+    fn test_alter_table_cmds() {
+        test_get_node_properties(
+            "alter table contact add column a int, drop column b;",
+            SyntaxKind::AlterTableCmd,
+            vec![
+                TokenProperty::from(SyntaxKind::AddP),
+                TokenProperty::from(SyntaxKind::Column),
+            ],
+        );
+        test_get_node_properties(
+            "alter table contact drop column b;",
+            SyntaxKind::AlterTableCmd,
+            vec![
+                TokenProperty::from(SyntaxKind::Drop),
+                TokenProperty::from(SyntaxKind::Column),
+                TokenProperty::from("b".to_string()),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_truncate() {
+        test_get_node_properties(
+            "truncate contact restart identity cascade;",
+            SyntaxKind::TruncateStmt,
+            vec![
+                TokenProperty::from(SyntaxKind::Truncate),
+                TokenProperty::from(SyntaxKind::Restart),
+                TokenProperty::from(SyntaxKind::IdentityP),
+                TokenProperty::from(SyntaxKind::Cascade),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_lock() {
+        test_get_node_properties(
+            "lock table contact in share row exclusive mode nowait;",
+            SyntaxKind::LockStmt,
+            vec![
+                TokenProperty::from(SyntaxKind::LockP),
+                TokenProperty::from(SyntaxKind::Table),
+                TokenProperty::from(SyntaxKind::InP),
+                TokenProperty::from(SyntaxKind::Share),
+                TokenProperty::from(SyntaxKind::Row),
+                TokenProperty::from(SyntaxKind::Exclusive),
+                TokenProperty::from(SyntaxKind::Mode),
+                TokenProperty::from(SyntaxKind::Nowait),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_cluster() {
+        test_get_node_properties(
+            "cluster contact using contact_pkey;",
+            SyntaxKind::ClusterStmt,
+            vec![
+                TokenProperty::from(SyntaxKind::Cluster),
+                TokenProperty::from(SyntaxKind::Using),
+                TokenProperty::from("contact_pkey".to_string()),
+            ],
+        );
+        test_get_node_properties(
+            "create table contact (id int) using columnar;",
+            SyntaxKind::CreateStmt,
+            vec![
+                TokenProperty::from(SyntaxKind::Create),
+                TokenProperty::from(SyntaxKind::Table),
+                TokenProperty::from(SyntaxKind::Using),
+                TokenProperty::from("columnar".to_string()),
+            ],
+        );
+        test_get_node_properties(
+            "alter table contact set without cluster;",
+            SyntaxKind::AlterTableCmd,
+            vec![
+                TokenProperty::from(SyntaxKind::Set),
+                TokenProperty::from(SyntaxKind::Without),
+                TokenProperty::from(SyntaxKind::Cluster),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_deferrable_constraints() {
+        test_get_node_properties(
+            "alter table contact add constraint uq unique (email) deferrable initially deferred;",
+            SyntaxKind::Constraint,
+            vec![
+                TokenProperty::from(SyntaxKind::Deferrable),
+                TokenProperty::from(SyntaxKind::Initially),
+                TokenProperty::from(SyntaxKind::Deferred),
+                TokenProperty::from("uq".to_string()),
+            ],
+        );
+        test_get_node_properties(
+            "set constraints all deferred;",
+            SyntaxKind::ConstraintsSetStmt,
+            vec![
+                TokenProperty::from(SyntaxKind::Set),
+                TokenProperty::from(SyntaxKind::Constraints),
+                TokenProperty::from(SyntaxKind::All),
+                TokenProperty::from(SyntaxKind::Deferred),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_comment() {
+        test_get_node_properties(
+            "comment on constraint contact_pkey on contact is null;",
+            SyntaxKind::CommentStmt,
+            vec![
+                TokenProperty::from(SyntaxKind::Comment),
+                TokenProperty::from(SyntaxKind::On),
+                TokenProperty::from(SyntaxKind::Constraint),
+                TokenProperty::from(SyntaxKind::On),
+                TokenProperty::from(SyntaxKind::Is),
+                TokenProperty::from(SyntaxKind::NullP),
+            ],
+        );
+        test_get_node_properties(
+            "security label for selinux on table contact is 'unclassified';",
+            SyntaxKind::SecLabelStmt,
+            vec![
+                TokenProperty::from(SyntaxKind::Security),
+                TokenProperty::from(SyntaxKind::Label),
+                TokenProperty::from(SyntaxKind::For),
+                TokenProperty::from(SyntaxKind::On),
+                TokenProperty::from(SyntaxKind::Table),
+                TokenProperty::from(SyntaxKind::Is),
+                TokenProperty::from("selinux".to_string()),
+                TokenProperty::from("unclassified".to_string()),
+            ],
+        )
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let property = TokenProperty::from(SyntaxKind::Select);
+        let json = serde_json::to_string(&property).unwrap();
+        assert_eq!(json, r#"{"value":null,"kind":"Select"}"#);
+        assert_eq!(
+            serde_json::from_str::<TokenProperty>(&json).unwrap(),
+            property
+        );
+    }
+
+    fn expr_infos(input: &str) -> Vec<ExprInfo> {
+        let mut infos = pg_query::parse(input)
+            .unwrap()
+            .protobuf
+            .nodes()
+            .iter()
+            .filter_map(|n| expr_info(&n.0.to_enum()))
+            .collect::<Vec<_>>();
+        infos.sort_by_key(|info| (info.precedence, info.operator.clone()));
+        infos
+    }
+
+    #[test]
+    fn test_expr_info() {
+        init();
+
+        let infos = expr_infos(
+            "select -a + b * c, x not like 'y', z between 1 and 2 from t where not d is null and e or f;",
+        );
+        assert_eq!(
+            infos
+                .into_iter()
+                .map(|info| (info.operator, info.arity, info.precedence))
+                .collect::<Vec<_>>(),
+            vec![
+                ("OR".to_string(), 2, Precedence::Or),
+                ("AND".to_string(), 2, Precedence::And),
+                ("NOT".to_string(), 1, Precedence::Not),
+                ("IS NULL".to_string(), 1, Precedence::Is),
+                ("BETWEEN".to_string(), 3, Precedence::Pattern),
+                ("NOT LIKE".to_string(), 2, Precedence::Pattern),
+                ("+".to_string(), 2, Precedence::Addition),
+                ("*".to_string(), 2, Precedence::Multiplication),
+                ("-".to_string(), 1, Precedence::UnaryMinus),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_requires_parens() {
+        init();
+
+        let add_mul = expr_infos("select a + b * c;");
+        assert!(!add_mul[0].requires_parens(&add_mul[1], true));
+        assert!(add_mul[1].requires_parens(&add_mul[0], false));
+
+        let sub = expr_infos("select a - b;");
+        assert!(sub[0].requires_parens(&sub[0], true));
+        assert!(!sub[0].requires_parens(&sub[0], false));
+
+        let eq = expr_infos("select a = b;");
+        assert!(eq[0].requires_parens(&eq[0], false));
+    }
+
+    #[test]
+    fn test_accessors() {
+        init();
+
+        let stmt = pg_query::parse("select a, b as c from t where a > 1;")
+            .unwrap()
+            .protobuf
+            .stmts
+            .remove(0)
+            .stmt
+            .unwrap()
+            .node
+            .unwrap();
+        let pg_query::NodeEnum::SelectStmt(select) = stmt else {
+            panic!("expected a select statement");
+        };
+
+        assert_eq!(
+            select
+                .target_list()
+                .map(|target| target.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["", "c"]
+        );
+        assert_eq!(select.from_clause().count(), 1);
+        assert!(matches!(
+            select.where_clause(),
+            Some(pg_query::NodeEnum::AExpr(_))
+        ));
+        assert!(select.with_clause().is_none());
+    }
+
+    #[test]
+    fn test_keyword_category() {
+        assert_eq!(
+            SyntaxKind::Select.keyword_category(),
+            Some(KeywordCategory::Reserved)
+        );
+        assert_eq!(
+            SyntaxKind::Integer.keyword_category(),
+            Some(KeywordCategory::ColName)
+        );
+        assert_eq!(
+            SyntaxKind::Left.keyword_category(),
+            Some(KeywordCategory::TypeFuncName)
+        );
+        assert_eq!(
+            SyntaxKind::AbortP.keyword_category(),
+            Some(KeywordCategory::Unreserved)
+        );
+        assert_eq!(SyntaxKind::Ident.keyword_category(), None);
+        assert_eq!(SyntaxKind::SelectStmt.keyword_category(), None);
+
+        // the categories agree with the lexer of pg_query
+        for token in pg_query::scan("select left integer abort zone x")
+            .unwrap()
+            .tokens
+        {
+            let kind = SyntaxKind::from(&token);
+            let expected = match token.keyword_kind() {
+                pg_query::protobuf::KeywordKind::NoKeyword => None,
+                pg_query::protobuf::KeywordKind::UnreservedKeyword => {
+                    Some(KeywordCategory::Unreserved)
+                }
+                pg_query::protobuf::KeywordKind::ColNameKeyword => Some(KeywordCategory::ColName),
+                pg_query::protobuf::KeywordKind::TypeFuncNameKeyword => {
+                    Some(KeywordCategory::TypeFuncName)
+                }
+                pg_query::protobuf::KeywordKind::ReservedKeyword => Some(KeywordCategory::Reserved),
+            };
+            assert_eq!(kind.keyword_category(), expected, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn test_predicates() {
+        assert!(SyntaxKind::Select.is_keyword());
+        assert!(SyntaxKind::AbortP.is_keyword());
+        assert!(!SyntaxKind::Ident.is_keyword());
+
+        assert!(SyntaxKind::Iconst.is_literal());
+        assert!(SyntaxKind::Sconst.is_literal());
+        assert!(!SyntaxKind::Ident.is_literal());
+
+        assert!(SyntaxKind::Ascii40.is_punct());
+        assert!(SyntaxKind::Typecast.is_punct());
+        assert!(!SyntaxKind::Param.is_punct());
+
+        assert!(SyntaxKind::CreateStmt.is_ddl_stmt());
+        assert!(SyntaxKind::IndexStmt.is_ddl_stmt());
+        assert!(!SyntaxKind::AlterSystemStmt.is_ddl_stmt());
+        assert!(!SyntaxKind::SelectStmt.is_ddl_stmt());
+
+        assert!(!SyntaxKind::GrantStmt.is_ddl_stmt());
+
+        assert!(SyntaxKind::SelectStmt.is_dml_stmt());
+        assert!(!SyntaxKind::CreateStmt.is_dml_stmt());
+
+        assert!(SyntaxKind::Whitespace.is_trivia());
+        assert!(SyntaxKind::LineComment.is_trivia());
+        assert!(!SyntaxKind::Select.is_trivia());
+    }
+
+    #[test]
+    fn test_stmt_kind() {
+        assert_eq!(SyntaxKind::AlterTableStmt.stmt_kind(), Some(StmtKind::Ddl));
+        assert_eq!(SyntaxKind::RuleStmt.stmt_kind(), Some(StmtKind::Ddl));
+        assert_eq!(SyntaxKind::UpdateStmt.stmt_kind(), Some(StmtKind::Dml));
+        assert_eq!(SyntaxKind::GrantStmt.stmt_kind(), Some(StmtKind::Dcl));
+        assert_eq!(SyntaxKind::CreateRoleStmt.stmt_kind(), Some(StmtKind::Dcl));
+        assert_eq!(SyntaxKind::TransactionStmt.stmt_kind(), Some(StmtKind::Tcl));
+        assert_eq!(SyntaxKind::VacuumStmt.stmt_kind(), Some(StmtKind::Utility));
+        assert_eq!(
+            SyntaxKind::AlterSystemStmt.stmt_kind(),
+            Some(StmtKind::Utility)
+        );
+        assert_eq!(SyntaxKind::RawStmt.stmt_kind(), None);
+        assert_eq!(SyntaxKind::Select.stmt_kind(), None);
+        assert_eq!(StmtKind::Ddl.to_string(), "DDL");
+    }
 }
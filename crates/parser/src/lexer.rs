@@ -20,8 +20,8 @@ pub enum TokenType {
 impl From<&ScanToken> for TokenType {
     fn from(token: &ScanToken) -> TokenType {
         match token.token {
-            // SqlComment
-            275 => TokenType::Whitespace,
+            // SqlComment, CComment
+            275 | 276 => TokenType::Whitespace,
             _ => match token.keyword_kind() {
                 KeywordKind::NoKeyword => TokenType::NoKeyword,
                 KeywordKind::UnreservedKeyword => TokenType::UnreservedKeyword,
@@ -97,18 +97,163 @@ fn whitespace_tokens(input: &str) -> VecDeque<Token> {
     tokens
 }
 
+/// The start of a `COPY ... FROM STDIN` statement up to the end of its line, after which the data
+/// block starts. A match is only a candidate, which may as well be within a comment, a string or
+/// another statement, until [`is_copy_from_stdin`] confirms it with the tokens of the text.
+static PATTERN_COPY_FROM_STDIN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\bcopy\b[^;]*?\bfrom\s+stdin\b[^;]*;[^\n]*\n").unwrap());
+
 /// Turn a string of potentially valid sql code into a list of tokens, including their range in the source text.
 ///
 /// The implementation is primarily using libpg_querys `scan` method, and fills in the gaps with tokens that are not parsed by the library, e.g. whitespace.
 pub fn lex(text: &str) -> Vec<Token> {
-    let mut whitespace_tokens = whitespace_tokens(text);
-
-    // tokens from pg_query.rs
-    let mut pg_query_tokens = match pg_query::scan(text) {
-        Ok(scanned) => VecDeque::from(scanned.tokens),
+    match try_lex(text) {
+        Ok(tokens) => tokens,
         // this _should_ never fail
         _ => panic!("pg_query::scan failed"),
+    }
+}
+
+/// Like `lex`, but returns an error if `text` cannot be scanned, e.g. because it ends within a
+/// quoted string or a block comment.
+///
+/// A `#!` line at the very start of `text`, which makes a file executable e.g. with psql, becomes a
+/// single `Shebang` token.
+///
+/// The data block that follows a `COPY ... FROM STDIN` statement, e.g. in the output of pg_dump, is
+/// not sql. Every line of it becomes a `CopyDataRow` token, and the terminating `\.` line a
+/// `CopyDataEnd` token.
+pub fn try_lex(text: &str) -> pg_query::Result<Vec<Token>> {
+    let shebang_len = if text.starts_with("#!") {
+        text.find('\n').unwrap_or(text.len())
+    } else {
+        0
+    };
+    let offset = TextSize::try_from(shebang_len).unwrap();
+
+    let mut tokens = Vec::new();
+    if shebang_len > 0 {
+        tokens.push(Token {
+            kind: SyntaxKind::Shebang,
+            text: text[..shebang_len].to_string(),
+            span: TextRange::up_to(offset),
+            token_type: TokenType::Whitespace,
+        });
+    }
+    tokens.extend(
+        lex_copy_data(&text[shebang_len..])?
+            .into_iter()
+            .map(|token| Token {
+                span: token.span + offset,
+                ..token
+            }),
+    );
+    Ok(tokens)
+}
+
+/// Lexes `text`, which may contain data blocks of `COPY ... FROM STDIN` statements
+fn lex_copy_data(text: &str) -> pg_query::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let push = |tokens: &mut Vec<Token>, kind: SyntaxKind, start: usize, end: usize| {
+        tokens.push(Token {
+            kind,
+            text: text[start..end].to_string(),
+            span: TextRange::new(
+                TextSize::try_from(start).unwrap(),
+                TextSize::try_from(end).unwrap(),
+            ),
+            token_type: if kind == SyntaxKind::Newline {
+                TokenType::Whitespace
+            } else {
+                TokenType::NoKeyword
+            },
+        })
+    };
+
+    let mut pos = 0;
+    let mut search = 0;
+    while let Some(m) = PATTERN_COPY_FROM_STDIN.find_at(text, search) {
+        if !is_copy_from_stdin(text, pos, m.start(), m.end()) {
+            search = m.start() + text[m.start()..].chars().next().map_or(1, char::len_utf8);
+            continue;
+        }
+        let offset = TextSize::try_from(pos).unwrap();
+        tokens.extend(
+            scan_tokens(&text[pos..m.end()])?
+                .into_iter()
+                .map(|token| Token {
+                    span: token.span + offset,
+                    ..token
+                }),
+        );
+
+        pos = m.end();
+        while pos < text.len() {
+            let line_end = text[pos..].find('\n').map_or(text.len(), |idx| pos + idx);
+            let line = &text[pos..line_end];
+            if line.trim_end_matches('\r') == "\\." {
+                push(&mut tokens, SyntaxKind::CopyDataEnd, pos, line_end);
+                pos = line_end;
+                break;
+            }
+            if !line.is_empty() {
+                push(&mut tokens, SyntaxKind::CopyDataRow, pos, line_end);
+            }
+            if line_end < text.len() {
+                push(&mut tokens, SyntaxKind::Newline, line_end, line_end + 1);
+            }
+            pos = (line_end + 1).min(text.len());
+        }
+        search = pos;
+    }
+
+    if pos < text.len() {
+        let offset = TextSize::try_from(pos).unwrap();
+        tokens.extend(scan_tokens(&text[pos..])?.into_iter().map(|token| Token {
+            span: token.span + offset,
+            ..token
+        }));
+    }
+    Ok(tokens)
+}
+
+/// Returns true if the `COPY` at `start` is the first token of a `COPY ... FROM STDIN` statement
+/// that ends with the last token before `end`, given that `text` is lexed from `pos` on
+fn is_copy_from_stdin(text: &str, pos: usize, start: usize, end: usize) -> bool {
+    // a candidate within a string or a comment that is not closed before `end` cannot be scanned
+    let Ok(result) = pg_query::scan(&text[pos..end]) else {
+        return false;
     };
+    let tokens = result
+        .tokens
+        .iter()
+        // SqlComment, CComment
+        .filter(|token| !matches!(token.token, 275 | 276))
+        .map(|token| {
+            let text = &text[pos + token.start as usize..pos + token.end as usize];
+            (token.start as usize + pos, SyntaxKind::from(token), text)
+        })
+        .collect::<Vec<_>>();
+    let Some(idx) = tokens.iter().position(|(at, _, _)| *at == start) else {
+        return false;
+    };
+    let statement = &tokens[idx..];
+    let is_statement_start = idx == 0 || tokens[idx - 1].1 == SyntaxKind::Ascii59;
+    let ends_with_semicolon = statement.last().is_some_and(|t| t.1 == SyntaxKind::Ascii59)
+        && statement[..statement.len() - 1]
+            .iter()
+            .all(|t| t.1 != SyntaxKind::Ascii59);
+    let reads_stdin = statement
+        .windows(2)
+        .any(|w| w[0].1 == SyntaxKind::From && w[1].2.eq_ignore_ascii_case("stdin"));
+    is_statement_start && statement[0].1 == SyntaxKind::Copy && ends_with_semicolon && reads_stdin
+}
+
+fn scan_tokens(text: &str) -> pg_query::Result<Vec<Token>> {
+    let mut whitespace_tokens = whitespace_tokens(text);
+
+    // tokens from pg_query.rs
+    let mut pg_query_tokens = VecDeque::from(pg_query::scan(text)?.tokens);
 
     // merge the two token lists
     let mut tokens: Vec<Token> = Vec::new();
@@ -117,19 +262,21 @@ pub fn lex(text: &str) -> Vec<Token> {
     while pos < text.len() {
         if !pg_query_tokens.is_empty() && pg_query_tokens[0].start == i32::try_from(pos).unwrap() {
             let pg_query_token = pg_query_tokens.pop_front().unwrap();
-            let token_text: String = text
-                .chars()
-                .skip(usize::try_from(pg_query_token.start).unwrap())
-                .take(
-                    usize::try_from(pg_query_token.end).unwrap()
-                        - usize::try_from(pg_query_token.start).unwrap(),
-                )
-                .collect();
+            // the locations reported by pg_query are byte offsets
+            let token_text = text[usize::try_from(pg_query_token.start).unwrap()
+                ..usize::try_from(pg_query_token.end).unwrap()]
+                .to_string();
             let len = token_text.len();
             let has_whitespace = token_text.contains(" ") || token_text.contains("\n");
             tokens.push(Token {
                 token_type: TokenType::from(&pg_query_token),
-                kind: SyntaxKind::from(&pg_query_token),
+                kind: match pg_query_token.token {
+                    // SqlComment
+                    275 => SyntaxKind::LineComment,
+                    // CComment
+                    276 => SyntaxKind::BlockComment,
+                    _ => SyntaxKind::from(&pg_query_token),
+                },
                 text: token_text,
                 span: TextRange::new(
                     TextSize::try_from(u32::try_from(pg_query_token.start).unwrap()).unwrap(),
@@ -163,7 +310,7 @@ pub fn lex(text: &str) -> Vec<Token> {
         panic!("No token found at position {}", pos);
     }
 
-    tokens
+    Ok(tokens)
 }
 
 #[cfg(test)]
@@ -207,7 +354,7 @@ mod tests {
         assert_eq!(token.kind, SyntaxKind::Whitespace);
 
         let token = tokens_iter.next().unwrap();
-        assert_eq!(token.kind, SyntaxKind::SqlComment);
+        assert_eq!(token.kind, SyntaxKind::LineComment);
         assert_eq!(token.text, "-- some comment ");
 
         let token = tokens_iter.next().unwrap();
@@ -227,4 +374,110 @@ mod tests {
         assert_eq!(token.kind, SyntaxKind::Iconst);
         assert_eq!(token.text, "2");
     }
+
+    #[test]
+    fn test_lexer_multibyte() {
+        let tokens = lex("select 'ä', 1;");
+        assert_eq!(tokens[2].text, "'ä'");
+        assert_eq!(tokens[3].kind, SyntaxKind::Ascii44);
+        assert_eq!(tokens[5].text, "1");
+    }
+
+    #[test]
+    fn test_try_lex_incomplete() {
+        assert!(try_lex("select 'abc").is_err());
+        assert!(try_lex("select 1; /* comment").is_err());
+        assert!(try_lex("select 'abc';").is_ok());
+    }
+
+    #[test]
+    fn test_comments_and_shebang() {
+        let tokens = lex("#!/usr/bin/env psql -f\nselect /* a */ 1; -- b");
+        let kinds = tokens
+            .iter()
+            .filter(|t| t.kind != SyntaxKind::Whitespace)
+            .map(|t| (t.kind, t.text.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                (SyntaxKind::Shebang, "#!/usr/bin/env psql -f"),
+                (SyntaxKind::Newline, "\n"),
+                (SyntaxKind::Select, "select"),
+                (SyntaxKind::BlockComment, "/* a */"),
+                (SyntaxKind::Iconst, "1"),
+                (SyntaxKind::Ascii59, ";"),
+                (SyntaxKind::LineComment, "-- b"),
+            ]
+        );
+        assert_eq!(usize::from(tokens[2].span.start()), 23);
+        assert!(tokens
+            .iter()
+            .filter(|t| t.kind == SyntaxKind::BlockComment)
+            .all(|t| t.token_type == TokenType::Whitespace));
+
+        // only a `#!` at the very start is a shebang
+        assert!(lex("select 1;\n#!x")
+            .iter()
+            .all(|t| t.kind != SyntaxKind::Shebang));
+    }
+
+    #[test]
+    fn test_copy_data() {
+        let input = "COPY t (a, b) FROM stdin;\n1\tO'Brien\n\n2\t\\N\n\\.\nselect 1;";
+        let tokens = lex(input);
+        assert_eq!(
+            tokens
+                .iter()
+                .filter(|t| matches!(
+                    t.kind,
+                    SyntaxKind::CopyDataRow | SyntaxKind::CopyDataEnd | SyntaxKind::Select
+                ))
+                .map(|t| (t.kind, t.text.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (SyntaxKind::CopyDataRow, "1\tO'Brien"),
+                (SyntaxKind::CopyDataRow, "2\t\\N"),
+                (SyntaxKind::CopyDataEnd, "\\."),
+                (SyntaxKind::Select, "select"),
+            ]
+        );
+        assert_eq!(
+            tokens.iter().map(|t| t.text.as_str()).collect::<String>(),
+            input
+        );
+
+        // the data block of an unterminated copy extends to the end of the input
+        let tokens = lex("copy t from stdin;\n1\t'");
+        assert_eq!(tokens.last().unwrap().kind, SyntaxKind::CopyDataRow);
+    }
+
+    #[test]
+    fn test_copy_in_comments_and_strings() {
+        for input in [
+            "-- copy t from stdin;\nselect 1;\n",
+            "/* copy t from stdin;\n */ select 1;\n",
+            "select 'copy t from stdin;\n';\nselect 1;\n",
+            "select $$\ncopy t from stdin;\n$$;\nselect 1;\n",
+            "comment on table t is 'copy t from stdin;';\nselect 1;\n",
+        ] {
+            let tokens = lex(input);
+            assert!(
+                tokens.iter().all(|t| t.kind != SyntaxKind::CopyDataRow),
+                "{}",
+                input
+            );
+            assert!(tokens.iter().any(|t| t.kind == SyntaxKind::Select));
+        }
+
+        // a real copy after a candidate within a comment
+        let tokens = lex("-- copy t from stdin;\ncopy t from stdin;\n1\n\\.\n");
+        assert_eq!(
+            tokens
+                .iter()
+                .filter(|t| t.kind == SyntaxKind::CopyDataRow)
+                .count(),
+            1
+        );
+    }
 }
@@ -18,20 +18,40 @@
 #![feature(lazy_cell, is_sorted)]
 
 mod ast_node;
+mod builder;
+mod cancellation;
 mod codegen;
 mod lexer;
+pub mod make;
+mod mutation;
+mod node_cache;
 mod parse;
+mod parse_cache;
 mod parser;
+mod pg_version;
 mod sibling_token;
+mod stream;
 mod syntax_error;
 mod syntax_node;
 
 use lexer::lex;
-use parse::source::source;
+use parse::source::{source, source_cancellable, source_incremental, source_parallel};
 
-pub use crate::codegen::SyntaxKind;
+pub use crate::ast_node::{deparse, RawStmt};
+pub use crate::builder::SyntaxTreeBuilder;
+pub use crate::cancellation::{Cancellation, Cancelled};
+pub use crate::codegen::{
+    accessors, expr_info, get_children, ExprInfo, KeywordCategory, Precedence, StmtKind, SyntaxKind,
+};
+pub use crate::mutation::{text_edit, GreenElement, SyntaxNodeMutation, TextEdit};
+pub use crate::node_cache::{NodeCache, SharedInterner};
+pub use crate::parse_cache::ParseCache;
 pub use crate::parser::{Parse, Parser};
-pub use crate::syntax_node::{SyntaxElement, SyntaxNode, SyntaxToken};
+pub use crate::pg_version::PgVersion;
+pub use crate::stream::{StatementStream, StreamedStatement};
+pub use crate::syntax_error::SyntaxError;
+pub use crate::syntax_node::{dump_cst, SyntaxElement, SyntaxNode, SyntaxToken};
+pub use cstree::text::{TextRange, TextSize};
 
 // TODO: I think we should add some kind of `EntryPoint` enum and make the api more flexible
 // maybe have an intermediate struct that takes &str inputs, lexes the input and then calls the parser
@@ -40,3 +60,72 @@ pub fn parse_source(text: &str) -> Parse {
     source(&mut p);
     p.finish()
 }
+
+/// Like `parse_source`, but reports syntax that is not available in `version` as errors
+pub fn parse_source_with_version(text: &str, version: PgVersion) -> Parse {
+    let mut p = Parser::new(lex(text)).with_version(version);
+    source(&mut p);
+    p.finish()
+}
+
+/// Like `parse_source`, but shares tokens and nodes with all other trees built with `cache`
+pub fn parse_source_with_cache(text: &str, cache: &NodeCache) -> Parse {
+    let mut p = Parser::with_cache(lex(text), cache);
+    source(&mut p);
+    p.finish()
+}
+
+/// Like `parse_source_with_cache`, but parses the statements in parallel on the global rayon thread
+/// pool. Produces the same tree as the serial parser.
+pub fn parse_source_parallel(text: &str, cache: &NodeCache) -> Parse {
+    source_parallel(lex(text), cache, PgVersion::default())
+}
+
+/// Like `parse_source_parallel`, but reports syntax that is not available in `version` as errors
+pub fn parse_source_parallel_with_version(
+    text: &str,
+    cache: &NodeCache,
+    version: PgVersion,
+) -> Parse {
+    source_parallel(lex(text), cache, version)
+}
+
+/// Like `parse_source_parallel`, but reuses the statements of the previous parse that was run with
+/// `parse_cache` if their text did not change
+pub fn parse_source_incremental(text: &str, cache: &NodeCache, parse_cache: &ParseCache) -> Parse {
+    source_incremental(lex(text), cache, PgVersion::default(), parse_cache)
+}
+
+/// Like `parse_source_incremental`, but stops between statements once `cancellation` has been
+/// cancelled. `parse_cache` is left as it was then.
+pub fn parse_source_cancellable(
+    text: &str,
+    cache: &NodeCache,
+    parse_cache: &ParseCache,
+    cancellation: &Cancellation,
+) -> Result<Parse, Cancelled> {
+    source_cancellable(
+        lex(text),
+        cache,
+        PgVersion::default(),
+        parse_cache,
+        cancellation,
+    )
+}
+
+/// Like `parse_source_cancellable`, but reports syntax that is not available in `version` as errors
+pub fn parse_source_cancellable_with_version(
+    text: &str,
+    cache: &NodeCache,
+    parse_cache: &ParseCache,
+    cancellation: &Cancellation,
+    version: PgVersion,
+) -> Result<Parse, Cancelled> {
+    source_cancellable(lex(text), cache, version, parse_cache, cancellation)
+}
+
+/// Parses the sql read from `reader` one statement at a time, without holding the entire input
+/// or its tree in memory
+pub fn parse_stream<R: std::io::Read>(reader: R) -> StatementStream<R> {
+    StatementStream::new(reader)
+}
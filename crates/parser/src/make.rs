@@ -0,0 +1,185 @@
+//! Constructors for common sql fragments.
+//!
+//! Fragments are produced by formatting sql text and parsing it, so they have exactly the shape the
+//! parser would produce for the same text. The resulting green nodes can be inserted into an
+//! existing tree that has been built with the same `NodeCache`, e.g. with
+//! [`SyntaxNodeMutation::replace_with`](crate::SyntaxNodeMutation::replace_with).
+
+use cstree::green::GreenNode;
+use cstree::util::NodeOrToken;
+
+use crate::builder::SyntaxTreeBuilder;
+use crate::codegen::{KeywordCategory, SyntaxKind};
+use crate::lexer::lex;
+use crate::mutation::GreenElement;
+use crate::node_cache::NodeCache;
+use crate::parse_source_with_cache;
+
+/// Returns `name` as an identifier, quoted if it would not be read back as the same name otherwise
+pub fn quote_ident(name: &str) -> String {
+    let is_simple = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '$');
+    let is_keyword = is_simple
+        && lex(name)
+            .first()
+            .and_then(|t| t.kind.keyword_category())
+            .is_some_and(|category| category != KeywordCategory::Unreserved);
+    if is_simple && !is_keyword {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+/// Returns `value` as a string literal
+pub fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Parses `text`, which must contain a single statement, into a statement node
+///
+/// Panics if `text` does not contain exactly one valid statement.
+pub fn stmt_from_text(cache: &NodeCache, text: &str) -> GreenNode {
+    let parse = parse_source_with_cache(text, cache);
+    assert!(
+        parse.errors.is_empty(),
+        "invalid statement {}: {:?}",
+        text,
+        parse.errors
+    );
+    let mut stmts = parse.cst.children();
+    let stmt = stmts.next().expect("no statement found");
+    assert!(
+        stmts.next().is_none(),
+        "more than one statement in {}",
+        text
+    );
+    stmt.green().clone()
+}
+
+/// `SELECT columns FROM from WHERE where_clause;`
+///
+/// `columns` and `where_clause` are sql expressions, `from` is a possibly qualified table name.
+pub fn select_stmt(
+    cache: &NodeCache,
+    columns: &[&str],
+    from: Option<&[&str]>,
+    where_clause: Option<&str>,
+) -> GreenNode {
+    let mut text = format!("select {}", columns.join(", "));
+    if let Some(from) = from {
+        text.push_str(&format!(" from {}", qualified_name(from)));
+    }
+    if let Some(where_clause) = where_clause {
+        text.push_str(&format!(" where {}", where_clause));
+    }
+    text.push(';');
+    stmt_from_text(cache, &text)
+}
+
+/// `INSERT INTO table (columns) VALUES (values);`
+///
+/// `table` is a possibly qualified name, `values` are sql expressions.
+pub fn insert_stmt(
+    cache: &NodeCache,
+    table: &[&str],
+    columns: &[&str],
+    values: &[&str],
+) -> GreenNode {
+    let columns = columns
+        .iter()
+        .map(|c| quote_ident(c))
+        .collect::<Vec<String>>();
+    stmt_from_text(
+        cache,
+        &format!(
+            "insert into {} ({}) values ({});",
+            qualified_name(table),
+            columns.join(", "),
+            values.join(", ")
+        ),
+    )
+}
+
+/// A whitespace token, e.g. to separate an inserted statement from its siblings
+pub fn whitespace(cache: &NodeCache, text: &str) -> GreenElement {
+    let kind = if text.contains('\n') {
+        SyntaxKind::Newline
+    } else {
+        SyntaxKind::Whitespace
+    };
+    let mut builder = SyntaxTreeBuilder::new(cache);
+    builder.start_node(SyntaxKind::SourceFile);
+    builder.token(kind, text);
+    builder.finish_node();
+    match builder.finish().children().next() {
+        Some(NodeOrToken::Token(t)) => NodeOrToken::Token(t.clone()),
+        _ => unreachable!("the node contains a single token"),
+    }
+}
+
+fn qualified_name(parts: &[&str]) -> String {
+    parts
+        .iter()
+        .map(|p| quote_ident(p))
+        .collect::<Vec<String>>()
+        .join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mutation::SyntaxNodeMutation;
+
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote_ident("contact"), "contact");
+        assert_eq!(quote_ident("Contact"), "\"Contact\"");
+        assert_eq!(quote_ident("select"), "\"select\"");
+        assert_eq!(quote_ident("my \"table\""), "\"my \"\"table\"\"\"");
+        assert_eq!(quote_literal("it's"), "'it''s'");
+    }
+
+    #[test]
+    fn test_select_stmt() {
+        let cache = NodeCache::new();
+        let stmt = cache.new_root(select_stmt(
+            &cache,
+            &["id", "name"],
+            Some(&["public", "contact"]),
+            Some("id = 1"),
+        ));
+        assert_eq!(stmt.kind(), SyntaxKind::SelectStmt);
+        assert_eq!(
+            stmt.text(),
+            "select id, name from public.contact where id = 1;"
+        );
+    }
+
+    #[test]
+    fn test_insert_into_tree() {
+        let cache = NodeCache::new();
+        let parse = parse_source_with_cache("select 1;", &cache);
+        let insert = insert_stmt(&cache, &["Contact"], &["id"], &["1"]);
+
+        let stmt = parse.cst.first_child().unwrap();
+        let with_newline = cache.new_root(stmt.insert_after(whitespace(&cache, "\n")).unwrap());
+        let root = cache.new_root(
+            with_newline
+                .first_child()
+                .unwrap()
+                .insert_after(NodeOrToken::Node(insert))
+                .unwrap(),
+        );
+        assert_eq!(
+            root.text(),
+            "select 1;insert into \"Contact\" (id) values (1);\n"
+        );
+    }
+}
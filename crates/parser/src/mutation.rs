@@ -0,0 +1,250 @@
+//! Editing of syntax trees.
+//!
+//! Syntax trees are immutable. An edit creates new green nodes for the edited node and all of its
+//! ancestors, while all other nodes are shared with the original tree. The result is the green
+//! root of the new tree, which is turned into a syntax tree with recalculated offsets by
+//! [`NodeCache::new_root`](crate::NodeCache::new_root).
+//!
+//! Inserted elements must have been built with the same `NodeCache` as the edited tree, because
+//! their token text is resolved with its interner.
+//!
+//! Code actions need text edits instead of trees. [`text_edit`] derives one from the text of the
+//! original and the edited tree.
+
+use cstree::green::{GreenNode, GreenToken};
+use cstree::text::{TextRange, TextSize};
+use cstree::util::NodeOrToken;
+
+use crate::syntax_node::SyntaxNode;
+
+/// An owned green node or token
+pub type GreenElement = NodeOrToken<GreenNode, GreenToken>;
+
+/// Operations that produce an edited copy of the tree a node belongs to
+pub trait SyntaxNodeMutation {
+    /// Returns the root of a tree in which the child at `index` is replaced with `new`
+    ///
+    /// Panics if there is no child at `index`.
+    fn replace_child(&self, index: usize, new: GreenElement) -> GreenNode;
+
+    /// Returns the root of a tree in which `new` is inserted as the child at `index`
+    ///
+    /// Panics if `index` is larger than the number of children.
+    fn insert_child(&self, index: usize, new: GreenElement) -> GreenNode;
+
+    /// Returns the root of a tree in which `new` is inserted right before this node, or `None` if
+    /// this node is the root
+    fn insert_before(&self, new: GreenElement) -> Option<GreenNode>;
+
+    /// Returns the root of a tree in which `new` is inserted right after this node, or `None` if
+    /// this node is the root
+    fn insert_after(&self, new: GreenElement) -> Option<GreenNode>;
+
+    /// Returns the root of a tree in which this node is replaced with `new`
+    fn replace_with(&self, new: GreenNode) -> GreenNode;
+
+    /// Returns the root of a tree in which this node is removed, or `None` if this node is the
+    /// root
+    fn detach(&self) -> Option<GreenNode>;
+}
+
+impl SyntaxNodeMutation for SyntaxNode {
+    fn replace_child(&self, index: usize, new: GreenElement) -> GreenNode {
+        edit(self, |children| children[index] = new)
+    }
+
+    fn insert_child(&self, index: usize, new: GreenElement) -> GreenNode {
+        edit(self, |children| children.insert(index, new))
+    }
+
+    fn insert_before(&self, new: GreenElement) -> Option<GreenNode> {
+        let index = index_in_parent(self)?;
+        Some(self.parent()?.insert_child(index, new))
+    }
+
+    fn insert_after(&self, new: GreenElement) -> Option<GreenNode> {
+        let index = index_in_parent(self)?;
+        Some(self.parent()?.insert_child(index + 1, new))
+    }
+
+    fn replace_with(&self, new: GreenNode) -> GreenNode {
+        match (self.parent(), index_in_parent(self)) {
+            (Some(parent), Some(index)) => parent.replace_child(index, NodeOrToken::Node(new)),
+            _ => new,
+        }
+    }
+
+    fn detach(&self) -> Option<GreenNode> {
+        let index = index_in_parent(self)?;
+        Some(edit(self.parent()?, |children| {
+            children.remove(index);
+        }))
+    }
+}
+
+/// Returns the index of `node` within the children of its parent, including tokens
+fn index_in_parent(node: &SyntaxNode) -> Option<usize> {
+    node.parent()?
+        .children_with_tokens()
+        .position(|child| child.into_node() == Some(node))
+}
+
+/// Applies `f` to the children of `node` and returns the root of the resulting tree
+fn edit(node: &SyntaxNode, f: impl FnOnce(&mut Vec<GreenElement>)) -> GreenNode {
+    // the path of child indices from the root to `node`
+    let mut path = Vec::new();
+    let mut current = node;
+    while let Some(parent) = current.parent() {
+        path.push(index_in_parent(current).expect("a node is a child of its parent"));
+        current = parent;
+    }
+    path.reverse();
+    rebuild(current.green(), &path, f)
+}
+
+fn rebuild(green: &GreenNode, path: &[usize], f: impl FnOnce(&mut Vec<GreenElement>)) -> GreenNode {
+    let mut children = green
+        .children()
+        .map(|child| match child {
+            NodeOrToken::Node(n) => NodeOrToken::Node(n.clone()),
+            NodeOrToken::Token(t) => NodeOrToken::Token(t.clone()),
+        })
+        .collect::<Vec<GreenElement>>();
+    match path.split_first() {
+        Some((&index, rest)) => {
+            let child = match &children[index] {
+                NodeOrToken::Node(n) => n.clone(),
+                NodeOrToken::Token(_) => unreachable!("the path only contains nodes"),
+            };
+            children[index] = NodeOrToken::Node(rebuild(&child, rest, f));
+        }
+        None => f(&mut children),
+    }
+    GreenNode::new(green.kind(), children)
+}
+
+/// A replacement of a range of the source text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    /// The range in the original text
+    pub range: TextRange,
+    /// The text that replaces the range
+    pub text: String,
+}
+
+/// Returns the single edit that turns `old` into `new`, or `None` if they are equal
+///
+/// The edit covers everything between the longest common prefix and suffix of both texts, e.g. the
+/// text of a replaced node.
+pub fn text_edit(old: &str, new: &str) -> Option<TextEdit> {
+    if old == new {
+        return None;
+    }
+    let prefix = old
+        .char_indices()
+        .zip(new.chars())
+        .find(|((_, a), b)| a != b)
+        .map(|((idx, _), _)| idx)
+        .unwrap_or(old.len().min(new.len()));
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let suffix = old[prefix..]
+        .chars()
+        .rev()
+        .zip(new[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .scan(0, |len, c| {
+            *len += c;
+            Some(*len)
+        })
+        .take_while(|len| *len <= max_suffix)
+        .last()
+        .unwrap_or(0);
+
+    Some(TextEdit {
+        range: TextRange::new(
+            TextSize::try_from(prefix).unwrap(),
+            TextSize::try_from(old.len() - suffix).unwrap(),
+        ),
+        text: new[prefix..new.len() - suffix].to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::codegen::SyntaxKind;
+    use crate::{parse_source_with_cache, NodeCache};
+
+    use super::*;
+
+    fn apply(text: &str, edit: &TextEdit) -> String {
+        let mut text = text.to_string();
+        text.replace_range(std::ops::Range::<usize>::from(edit.range), &edit.text);
+        text
+    }
+
+    #[test]
+    fn test_replace_and_detach() {
+        let cache = NodeCache::new();
+        let parse = parse_source_with_cache("select 1;\nselect 2;", &cache);
+        let insert = parse_source_with_cache("insert into contact (id) values (1);", &cache);
+        let insert_stmt = insert.cst.first_child().unwrap().green().clone();
+
+        let second = parse.cst.children().nth(1).unwrap();
+        assert_eq!(second.kind(), SyntaxKind::SelectStmt);
+
+        let replaced = cache.new_root(second.replace_with(insert_stmt.clone()));
+        assert_eq!(
+            replaced.text(),
+            "select 1;\ninsert into contact (id) values (1);"
+        );
+        let new_stmt = replaced.children().nth(1).unwrap();
+        assert_eq!(new_stmt.kind(), SyntaxKind::InsertStmt);
+        assert_eq!(usize::from(new_stmt.text_range().start()), 10);
+
+        let edit = text_edit(&parse.cst.text().to_string(), &replaced.text().to_string()).unwrap();
+        assert_eq!(
+            apply(&parse.cst.text().to_string(), &edit),
+            replaced.text().to_string()
+        );
+
+        let detached = cache.new_root(parse.cst.first_child().unwrap().detach().unwrap());
+        assert_eq!(detached.text(), "\nselect 2;");
+        assert!(parse.cst.detach().is_none());
+
+        let inserted = cache.new_root(
+            parse
+                .cst
+                .first_child()
+                .unwrap()
+                .insert_after(NodeOrToken::Node(insert_stmt))
+                .unwrap(),
+        );
+        assert_eq!(
+            inserted.text(),
+            "select 1;insert into contact (id) values (1);\nselect 2;"
+        );
+    }
+
+    #[test]
+    fn test_text_edit() {
+        assert_eq!(text_edit("select 1;", "select 1;"), None);
+        for (old, new) in [
+            ("select 1;", "select 12;"),
+            ("select a, b;", "select b;"),
+            ("aaa", "aa"),
+            ("select 'ä';", "select 'ö';"),
+            ("", "select 1;"),
+        ] {
+            let edit = text_edit(old, new).unwrap();
+            assert_eq!(apply(old, &edit), new, "{} -> {}", old, new);
+        }
+        assert_eq!(
+            text_edit("aaa", "aa").unwrap(),
+            TextEdit {
+                range: TextRange::new(TextSize::from(2), TextSize::from(3)),
+                text: String::new()
+            }
+        );
+    }
+}
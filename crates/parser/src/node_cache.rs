@@ -0,0 +1,217 @@
+//! A cache to deduplicate syntax trees across documents.
+//!
+//! `cstree` deduplicates identical tokens and small nodes (e.g. the `(1, 'a')` of thousands of
+//! similar `INSERT` rows) while building a tree, but only within the scope of a single
+//! `cstree::build::NodeCache`. This module wraps that cache into a handle that can be shared by all
+//! documents of a workspace, so that their trees structurally share as much as possible.
+//!
+//! The token text interner is thread-safe and always shared. The node cache itself requires
+//! exclusive access while a tree is built. If it is in use by another parse, a fresh node cache
+//! backed by the shared interner is used instead, so that concurrent parses never block each other.
+//! Only the shared node cache is put back afterwards. The workers of a parallel parse build their
+//! trees with a [`fork`](NodeCache::fork) each, and the trees are then rebuilt one after the
+//! other with the shared node cache by [`merge`](NodeCache::merge).
+//!
+//! The node cache keeps every node it has built alive, including those of outdated versions of a
+//! document, until it is dropped with [`NodeCache::evict`]. The trees built so far keep their
+//! nodes, and the interner keeps the token texts, which are shared by all versions.
+
+use std::sync::{Arc, Mutex};
+
+use cstree::green::GreenNode;
+use cstree::interning::{
+    new_threaded_interner, Interner, MultiThreadedTokenInterner, Resolver, TokenKey,
+};
+use cstree::syntax::ResolvedNode;
+use cstree::util::NodeOrToken;
+
+use crate::builder::SyntaxTreeBuilder;
+use crate::codegen::SyntaxKind;
+use crate::syntax_node::SyntaxNode;
+
+pub(crate) type GreenNodeCache = cstree::build::NodeCache<'static, SharedInterner>;
+
+/// A thread-safe string interner for token text that is shared by all trees built with the same
+/// `NodeCache`
+#[derive(Debug, Clone)]
+pub struct SharedInterner(Arc<MultiThreadedTokenInterner>);
+
+impl Resolver<TokenKey> for SharedInterner {
+    fn try_resolve(&self, key: TokenKey) -> Option<&str> {
+        self.0.try_resolve(key)
+    }
+}
+
+impl Interner<TokenKey> for SharedInterner {
+    type Error = <MultiThreadedTokenInterner as Interner<TokenKey>>::Error;
+
+    fn try_get_or_intern(&mut self, text: &str) -> Result<TokenKey, Self::Error> {
+        (&*self.0).try_get_or_intern(text)
+    }
+}
+
+/// A handle to a node cache that can be shared across documents and threads
+///
+/// Cloning the handle is cheap. All clones refer to the same cache.
+#[derive(Debug, Clone)]
+pub struct NodeCache {
+    nodes: Arc<Mutex<Option<GreenNodeCache>>>,
+    interner: SharedInterner,
+}
+
+impl Default for NodeCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NodeCache {
+    pub fn new() -> Self {
+        let interner = SharedInterner(Arc::new(new_threaded_interner()));
+        Self {
+            nodes: Arc::new(Mutex::new(Some(GreenNodeCache::from_interner(
+                interner.clone(),
+            )))),
+            interner,
+        }
+    }
+
+    /// The interner that resolves the token text of all trees built with this cache
+    pub fn interner(&self) -> SharedInterner {
+        self.interner.clone()
+    }
+
+    /// Creates the root of a syntax tree from a `green` node that has been built with this cache,
+    /// e.g. the result of an edit
+    pub fn new_root(&self, green: GreenNode) -> ResolvedNode<SyntaxKind> {
+        SyntaxNode::new_root_with_resolver(green, self.interner())
+    }
+
+    /// Returns a handle to a new node cache that shares the interner, e.g. for a worker of a
+    /// parallel parse
+    pub fn fork(&self) -> NodeCache {
+        Self {
+            nodes: Arc::new(Mutex::new(Some(GreenNodeCache::from_interner(
+                self.interner.clone(),
+            )))),
+            interner: self.interner.clone(),
+        }
+    }
+
+    /// Rebuilds the tree of `node`, which has been built with a [`fork`](NodeCache::fork) of this
+    /// cache, with this cache, so that it shares its nodes with the other trees built with it
+    pub(crate) fn merge(&self, node: &ResolvedNode<SyntaxKind>) -> GreenNode {
+        let mut builder = SyntaxTreeBuilder::new(self);
+        rebuild(&mut builder, node);
+        builder.finish()
+    }
+
+    /// Takes the node cache out of the handle for exclusive use while building a tree, and returns
+    /// whether it is the shared one
+    ///
+    /// If the cache is currently in use, a new one that shares the interner is returned.
+    pub(crate) fn take(&self) -> (GreenNodeCache, bool) {
+        match self.nodes.lock().unwrap().take() {
+            Some(cache) => (cache, true),
+            None => (GreenNodeCache::from_interner(self.interner.clone()), false),
+        }
+    }
+
+    /// Returns a node cache that has been taken with [`take`](NodeCache::take). The new caches of
+    /// concurrent parses are dropped, so that they never replace the shared one.
+    pub(crate) fn put_back(&self, cache: GreenNodeCache, shared: bool) {
+        let mut nodes = self.nodes.lock().unwrap();
+        if shared && nodes.is_none() {
+            *nodes = Some(cache);
+        }
+    }
+
+    /// Drops the cached nodes, so that the nodes that only outdated trees use are freed together
+    /// with them. A cache that is in use while it is evicted is dropped once it is put back.
+    pub fn evict(&self) {
+        *self.nodes.lock().unwrap() = Some(GreenNodeCache::from_interner(self.interner.clone()));
+    }
+}
+
+/// Adds the tree of `node` to `builder`
+fn rebuild(builder: &mut SyntaxTreeBuilder, node: &ResolvedNode<SyntaxKind>) {
+    builder.start_node(node.kind());
+    for child in node.children_with_tokens() {
+        match child {
+            NodeOrToken::Node(n) => rebuild(builder, n),
+            NodeOrToken::Token(t) => builder.token(t.kind(), t.text()),
+        }
+    }
+    builder.finish_node();
+}
+
+/// Returns the address of the first child of `node`, which two green nodes share exactly if they
+/// are the same node rather than equal ones
+#[cfg(test)]
+pub(crate) fn green_address(node: &GreenNode) -> Option<*const ()> {
+    match node.children().next()? {
+        NodeOrToken::Node(n) => Some(n as *const _ as *const ()),
+        NodeOrToken::Token(t) => Some(t as *const _ as *const ()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_source_with_cache;
+
+    use super::*;
+
+    #[test]
+    fn test_shared_node_cache() {
+        let cache = NodeCache::new();
+
+        let first = parse_source_with_cache("insert into contact (id) values (1);", &cache);
+        let second = parse_source_with_cache("insert into contact (id) values (1);", &cache);
+
+        let first_tokens = first
+            .cst
+            .descendants_with_tokens()
+            .filter_map(|e| e.into_token().and_then(|t| t.text_key()))
+            .collect::<Vec<TokenKey>>();
+        let second_tokens = second
+            .cst
+            .descendants_with_tokens()
+            .filter_map(|e| e.into_token().and_then(|t| t.text_key()))
+            .collect::<Vec<TokenKey>>();
+
+        assert!(!first_tokens.is_empty());
+        assert_eq!(first_tokens, second_tokens);
+        assert_eq!(first.cst.green(), second.cst.green());
+    }
+
+    #[test]
+    fn test_concurrent_use() {
+        let cache = NodeCache::new();
+
+        let (taken, shared) = cache.take();
+        assert!(shared);
+        // the cache is in use, so parsing falls back to a fresh node cache, which is not kept
+        let result = parse_source_with_cache("select 1;", &cache);
+        assert_eq!(result.cst.text(), "select 1;");
+        assert!(cache.nodes.lock().unwrap().is_none());
+        cache.put_back(taken, shared);
+
+        assert!(cache.nodes.lock().unwrap().is_some());
+    }
+
+    #[test]
+    fn test_evict() {
+        let cache = NodeCache::new();
+
+        let first = parse_source_with_cache("select 1;", &cache);
+        let (taken, shared) = cache.take();
+        cache.evict();
+        // the evicted cache is not put back
+        cache.put_back(taken, shared);
+
+        // the interner is kept, so trees built before and after the eviction share token texts
+        let second = parse_source_with_cache("select 1;", &cache);
+        assert_eq!(first.cst.text(), second.cst.text());
+        assert_eq!(first.cst.green(), second.cst.green());
+    }
+}
@@ -0,0 +1,36 @@
+use crate::codegen::SyntaxKind;
+use crate::lexer::Token;
+use crate::Parser;
+
+/// checks if the parser is at the data block of a `COPY ... FROM STDIN` statement
+pub fn at_copy_data(parser: &Parser) -> bool {
+    parser.at_any(&[SyntaxKind::CopyDataRow, SyntaxKind::CopyDataEnd])
+}
+
+/// returns the end of the data block that starts at `pos`, including the terminating `\.`
+pub fn copy_data_end(tokens: &[Token], pos: usize) -> usize {
+    let mut end = pos;
+    while tokens
+        .get(end)
+        .is_some_and(|t| matches!(t.kind, SyntaxKind::CopyDataRow | SyntaxKind::Newline))
+    {
+        end += 1;
+    }
+    if tokens
+        .get(end)
+        .is_some_and(|t| t.kind == SyntaxKind::CopyDataEnd)
+    {
+        end += 1;
+    }
+    end
+}
+
+/// wraps the data block at the current position in a `CopyData` node
+pub fn copy_data(parser: &mut Parser) {
+    let end = copy_data_end(&parser.tokens, parser.pos);
+    parser.start_node(SyntaxKind::CopyData);
+    while parser.pos < end {
+        parser.advance();
+    }
+    parser.finish_node();
+}
@@ -3,6 +3,7 @@ use std::{assert_eq, ops::Range};
 use crate::{
     codegen::{get_nodes, Node, SyntaxKind},
     lexer::TokenType,
+    parse::location_resolver::LocationResolver,
 };
 use log::{debug, log_enabled};
 use petgraph::{
@@ -40,6 +41,7 @@ struct LibpgQueryNodeParser<'p> {
     parser: &'p mut Parser,
     token_range: &'p Range<usize>,
     node_graph: StableGraph<Node, ()>,
+    resolver: LocationResolver,
     current_node: NodeIndex<DefaultIx>,
     open_nodes: Vec<NodeIndex<DefaultIx>>,
 }
@@ -52,10 +54,13 @@ impl<'p> LibpgQueryNodeParser<'p> {
     ) -> LibpgQueryNodeParser<'p> {
         let current_depth = parser.depth.clone();
         debug!("Parsing node {:#?}", node);
+        let node_graph = get_nodes(&node, current_depth);
+        let resolver = LocationResolver::new(&node_graph, &parser.tokens[token_range.clone()]);
         Self {
             parser,
             token_range,
-            node_graph: get_nodes(&node, current_depth),
+            node_graph,
+            resolver,
             current_node: NodeIndex::<DefaultIx>::new(0),
             open_nodes: Vec::new(),
         }
@@ -144,11 +149,9 @@ impl<'p> LibpgQueryNodeParser<'p> {
 
                 self.parser.advance();
             } else {
-                panic!(
-                    "could not find node for token {:?} at depth {}",
-                    self.current_token(),
-                    self.parser.depth,
-                );
+                debug!("resolving token {:?} by its location", self.current_token());
+                self.finish_nodes_until_location();
+                self.parser.advance();
             }
         }
         // close all remaining nodes
@@ -284,12 +287,15 @@ impl<'p> LibpgQueryNodeParser<'p> {
                 // leaf node. We can thereby reduce the search space to the next n non-whitespace token
                 // where n is the number of remaining properties of the current node
                 let num_of_properties = self.node_graph[self.current_node].properties.len();
+                let statement_end = self.parser.tokens[self.token_range.end - 1].span.end();
                 self.node_graph[self.current_node].properties.retain(|p| {
                     let mut idx = 0;
                     let mut left_pull = 0;
                     while idx < num_of_properties + left_pull {
                         let token = self.parser.nth(idx, true);
-                        if token.kind == SyntaxKind::Eof {
+                        // do not look beyond the statement, so that the tree does not depend on
+                        // what follows it
+                        if token.kind == SyntaxKind::Eof || token.span.start() >= statement_end {
                             break;
                         }
                         if cmp_tokens(&p, token) {
@@ -344,6 +350,24 @@ impl<'p> LibpgQueryNodeParser<'p> {
         }
     }
 
+    /// finish open nodes until the innermost open node spans the current location, and make it the
+    /// current node
+    ///
+    /// used for tokens that no node expects as a property, e.g. the `HAVING` keyword
+    fn finish_nodes_until_location(&mut self) {
+        let location = self.current_location();
+        while self.open_nodes.len() > 1
+            && !self
+                .resolver
+                .contains(*self.open_nodes.last().unwrap(), location)
+        {
+            self.finish_node();
+        }
+        if let Some(node) = self.open_nodes.last() {
+            self.current_node = *node;
+        }
+    }
+
     fn finish_node(&mut self) {
         let node_to_remove = self.open_nodes.pop().unwrap();
         assert_eq!(
@@ -0,0 +1,147 @@
+//! Resolution of tokens to nodes by location.
+//!
+//! Only some tokens of a statement are expected as `TokenProperty`s of a node, e.g. the `HAVING`,
+//! `LIMIT` or `RETURNING` keywords are not. Those are resolved through the locations that libpg_query
+//! reports instead: every node spans the range from the first to the last token located within its
+//! subtree, and a token belongs to the deepest node whose range contains it. Tokens between two
+//! children, such as the keyword that introduces a clause, thereby belong to their parent.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use petgraph::stable_graph::{DefaultIx, NodeIndex, StableGraph};
+use petgraph::Direction;
+
+use crate::codegen::Node;
+use crate::lexer::Token;
+
+/// The ranges of the statement text that the nodes of a node graph span
+pub struct LocationResolver {
+    ranges: HashMap<NodeIndex<DefaultIx>, Range<usize>>,
+}
+
+impl LocationResolver {
+    /// Computes the ranges of all nodes of `graph`. `tokens` are the tokens of the statement, which
+    /// starts at the beginning of the first token.
+    pub fn new(graph: &StableGraph<Node, ()>, tokens: &[Token]) -> Self {
+        let Some(first) = tokens.first() else {
+            return Self {
+                ranges: HashMap::new(),
+            };
+        };
+        let offset = first.span.start();
+        // the end of the token that starts at a location
+        let token_ends = tokens
+            .iter()
+            .map(|t| {
+                (
+                    usize::from(t.span.start() - offset),
+                    usize::from(t.span.end() - offset),
+                )
+            })
+            .collect::<HashMap<usize, usize>>();
+        let stmt_len = usize::from(tokens.last().unwrap().span.end() - offset);
+
+        let mut resolver = Self {
+            ranges: HashMap::new(),
+        };
+        for root in graph.node_indices().filter(|n| {
+            graph
+                .neighbors_directed(*n, Direction::Incoming)
+                .next()
+                .is_none()
+        }) {
+            resolver.collect_ranges(graph, root, &token_ends);
+            // the root spans the entire statement, including leading and trailing keywords
+            resolver.ranges.insert(root, 0..stmt_len);
+        }
+        resolver
+    }
+
+    /// Computes the ranges of `node` and its descendants and returns the range of `node`, if any
+    /// node within its subtree has a location
+    fn collect_ranges(
+        &mut self,
+        graph: &StableGraph<Node, ()>,
+        node: NodeIndex<DefaultIx>,
+        token_ends: &HashMap<usize, usize>,
+    ) -> Option<Range<usize>> {
+        let mut range = graph[node]
+            .location
+            .map(|location| location..token_ends.get(&location).copied().unwrap_or(location + 1));
+        for child in graph.neighbors_directed(node, Direction::Outgoing) {
+            if let Some(child_range) = self.collect_ranges(graph, child, token_ends) {
+                range = Some(match range {
+                    Some(r) => r.start.min(child_range.start)..r.end.max(child_range.end),
+                    None => child_range,
+                });
+            }
+        }
+        if let Some(r) = &range {
+            self.ranges.insert(node, r.clone());
+        }
+        range
+    }
+
+    /// Returns the range of `node`, if any node within its subtree has a location
+    pub fn range(&self, node: NodeIndex<DefaultIx>) -> Option<&Range<usize>> {
+        self.ranges.get(&node)
+    }
+
+    /// Returns true if `location` is within the range of `node`. Nodes without a range contain
+    /// any location, since there is nothing to tell otherwise.
+    pub fn contains(&self, node: NodeIndex<DefaultIx>, location: usize) -> bool {
+        match self.range(node) {
+            Some(range) => range.contains(&location),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::codegen::{get_nodes, SyntaxKind};
+    use crate::lexer::lex;
+
+    use super::*;
+
+    #[test]
+    fn test_resolve_clause_keywords() {
+        let input = "select a, count(*) from t group by a having count(*) > 1 limit 10 offset 5";
+        let root = pg_query::parse(input)
+            .unwrap()
+            .protobuf
+            .nodes()
+            .iter()
+            .find(|n| n.1 == 1)
+            .unwrap()
+            .0
+            .to_enum();
+        let graph = get_nodes(&root, 0);
+        let resolver = LocationResolver::new(&graph, &lex(input));
+
+        let node = |kind: SyntaxKind| {
+            graph
+                .node_indices()
+                .find(|n| graph[*n].kind == kind)
+                .unwrap()
+        };
+        let location = |text: &str| input.find(text).unwrap();
+
+        let select = node(SyntaxKind::SelectStmt);
+        assert!(resolver.contains(select, location("having")));
+        assert!(resolver.contains(select, location("limit")));
+        assert!(resolver.contains(select, location("offset")));
+
+        let having = node(SyntaxKind::AExpr);
+        assert!(!resolver.contains(having, location("having")));
+        assert!(resolver.contains(having, location("> 1")));
+        assert!(!resolver.contains(having, location("limit")));
+
+        let relation = node(SyntaxKind::RangeVar);
+        assert_eq!(
+            resolver.range(relation),
+            Some(&(location("t group")..location(" group")))
+        );
+    }
+}
@@ -1,4 +1,7 @@
+pub mod copy_data;
 pub mod libpg_query_node;
+pub mod location_resolver;
 pub mod source;
 pub mod statement;
 pub mod statement_start;
+pub mod syntax_hint;
@@ -1,13 +1,34 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use cstree::green::GreenNode;
+use cstree::text::TextSize;
+use cstree::util::NodeOrToken;
+use cstree::Syntax;
+use rayon::prelude::*;
+
+use crate::ast_node::RawStmt;
+use crate::cancellation::{Cancellation, Cancelled};
 use crate::codegen::SyntaxKind;
-use crate::Parser;
+use crate::lexer::Token;
+use crate::node_cache::NodeCache;
+use crate::parse_cache::{CachedSegment, ParseCache, SegmentKey};
+use crate::pg_version::PgVersion;
+use crate::syntax_node::SyntaxNode;
+use crate::{Parse, Parser};
 
-use super::statement::statement;
+use super::copy_data::{at_copy_data, copy_data, copy_data_end};
+use super::statement::{collect_statement_token_range, statement, statement_at_token_range};
 use super::statement_start::is_at_stmt_start;
 
 pub fn source(parser: &mut Parser) {
     parser.start_node(SyntaxKind::SourceFile);
 
     while !parser.eof() {
+        if at_copy_data(parser) {
+            copy_data(parser);
+            continue;
+        }
         match is_at_stmt_start(parser) {
             Some(stmt) => {
                 statement(parser, stmt);
@@ -18,5 +39,414 @@ pub fn source(parser: &mut Parser) {
         }
     }
 
+    parser.flush_token_buffer();
     parser.finish_node();
 }
+
+/// A contiguous range of tokens within a source file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Segment {
+    /// The tokens of a statement
+    Statement(Range<usize>),
+    /// Tokens between statements, e.g. whitespace and comments
+    Gap(Range<usize>),
+    /// The data block of a `COPY ... FROM STDIN` statement
+    CopyData(Range<usize>),
+}
+
+/// Like `source`, but parses all statements in parallel.
+///
+/// The tokens are first split into statements, which is cheap compared to parsing them. Every
+/// statement is then parsed into a separate tree on the global rayon thread pool. Finally, the
+/// children of all trees are assembled into the `SourceFile` node.
+pub fn source_parallel(tokens: Vec<Token>, cache: &NodeCache, version: PgVersion) -> Parse {
+    source_incremental(tokens, cache, version, &ParseCache::new())
+}
+
+/// Like `source_parallel`, but only parses the statements and gaps whose text is not in
+/// `parse_cache`. The cache is left with the segments of this parse.
+pub fn source_incremental(
+    tokens: Vec<Token>,
+    cache: &NodeCache,
+    version: PgVersion,
+    parse_cache: &ParseCache,
+) -> Parse {
+    match source_cancellable(tokens, cache, version, parse_cache, &Cancellation::new()) {
+        Ok(parse) => parse,
+        Err(Cancelled) => unreachable!("a new cancellation is never cancelled"),
+    }
+}
+
+/// Like `source_incremental`, but stops before the next segment once `cancellation` has been
+/// cancelled. `parse_cache` is only updated by a parse that completed.
+pub fn source_cancellable(
+    tokens: Vec<Token>,
+    cache: &NodeCache,
+    version: PgVersion,
+    parse_cache: &ParseCache,
+    cancellation: &Cancellation,
+) -> Result<Parse, Cancelled> {
+    // the splitter never builds a tree, so it does not need the shared cache
+    let mut splitter = Parser::new(tokens);
+    let segments = split(&mut splitter);
+
+    let keyed = segments
+        .iter()
+        .map(|segment| {
+            let (kind, range) = match segment {
+                Segment::Statement(range) => (0, range),
+                Segment::Gap(range) => (1, range),
+                Segment::CopyData(range) => (2, range),
+            };
+            let tokens = &splitter.tokens[range.clone()];
+            let key = SegmentKey {
+                kind,
+                text: tokens.iter().map(|t| t.text.as_str()).collect(),
+                version,
+            };
+            (segment, key, tokens[0].span.start())
+        })
+        .collect::<Vec<_>>();
+
+    // every worker builds its trees with its own node cache, so that they do not replace the
+    // shared one when they put it back
+    let parsed = keyed
+        .par_iter()
+        .map_init(
+            || cache.fork(),
+            |worker, (segment, key, _)| {
+                // the segments of the parse cache are `Ok`, and the new parses `Err`
+                if let Some(cached) = parse_cache.get(key) {
+                    return Ok(Ok(cached));
+                }
+                cancellation.check()?;
+                let parse = match segment {
+                    Segment::Statement(range) => {
+                        parse_segment(&splitter.tokens[range.clone()], worker, version, |p| {
+                            statement_at_token_range(p, 0..p.tokens.len())
+                        })
+                    }
+                    Segment::Gap(range) => {
+                        parse_segment(&splitter.tokens[range.clone()], worker, version, |p| {
+                            while !p.eof() {
+                                p.advance();
+                            }
+                        })
+                    }
+                    Segment::CopyData(range) => {
+                        parse_segment(&splitter.tokens[range.clone()], worker, version, copy_data)
+                    }
+                };
+                Ok(Err(parse))
+            },
+        )
+        .collect::<Result<Vec<_>, Cancelled>>()?;
+
+    // the new trees are rebuilt with the shared node cache one after the other, so that they share
+    // their nodes with those of the other trees
+    let parsed = keyed
+        .iter()
+        .zip(parsed)
+        .map(|((_, _, offset), parsed)| match parsed {
+            Ok(cached) => cached,
+            Err(parse) => Arc::new(cached_segment(parse, cache, *offset)),
+        })
+        .collect::<Vec<_>>();
+
+    let children = parsed
+        .iter()
+        .flat_map(|segment| segment.children.iter().cloned())
+        .collect::<Vec<_>>();
+
+    let mut errors = splitter.into_errors();
+    let mut stmts = Vec::new();
+    for ((_, _, offset), segment) in keyed.iter().zip(&parsed) {
+        errors.extend(segment.errors_at(*offset));
+        stmts.extend(segment.stmts_at(*offset));
+    }
+    parse_cache.replace(
+        keyed
+            .into_iter()
+            .zip(parsed)
+            .map(|((_, key, _), segment)| (key, segment))
+            .collect(),
+    );
+
+    Ok(Parse {
+        cst: SyntaxNode::new_root_with_resolver(
+            GreenNode::new(SyntaxKind::SourceFile.into_raw(), children),
+            cache.interner(),
+        ),
+        errors,
+        stmts,
+    })
+}
+
+/// Returns the `parse` of the segment at `offset` with ranges relative to its start and its tree
+/// rebuilt with `cache`
+fn cached_segment(parse: Parse, cache: &NodeCache, offset: TextSize) -> CachedSegment {
+    let green = cache.merge(&parse.cst);
+    let children = green
+        .children()
+        .map(|child| match child {
+            NodeOrToken::Node(n) => NodeOrToken::Node(n.clone()),
+            NodeOrToken::Token(t) => NodeOrToken::Token(t.clone()),
+        })
+        .collect();
+    CachedSegment {
+        children,
+        stmts: parse
+            .stmts
+            .into_iter()
+            .map(|stmt| RawStmt {
+                stmt: stmt.stmt,
+                range: stmt.range - offset,
+            })
+            .collect(),
+        errors: parse
+            .errors
+            .into_iter()
+            .map(|error| {
+                let range = error.range() - offset;
+                error.with_range(range)
+            })
+            .collect(),
+    }
+}
+
+/// Splits the token stream into statements and the gaps between them without building any nodes
+pub(crate) fn split(parser: &mut Parser) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut gap_start = parser.pos;
+
+    while !parser.eof() {
+        if at_copy_data(parser) {
+            if gap_start < parser.pos {
+                segments.push(Segment::Gap(gap_start..parser.pos));
+            }
+            let end = copy_data_end(&parser.tokens, parser.pos);
+            segments.push(Segment::CopyData(parser.pos..end));
+            parser.pos = end;
+            gap_start = end;
+            continue;
+        }
+        match is_at_stmt_start(parser) {
+            Some(stmt) => {
+                if gap_start < parser.pos {
+                    segments.push(Segment::Gap(gap_start..parser.pos));
+                }
+                let token_range = collect_statement_token_range(parser, stmt);
+                parser.pos = token_range.end;
+                parser.whitespace_token_buffer = None;
+                gap_start = parser.pos;
+                segments.push(Segment::Statement(token_range));
+            }
+            None => {
+                parser.pos += 1;
+            }
+        }
+    }
+    if gap_start < parser.pos {
+        segments.push(Segment::Gap(gap_start..parser.pos));
+    }
+
+    segments
+}
+
+/// Parses `tokens` with `f` into a temporary `SourceFile` node, whose children are then moved into
+/// the actual `SourceFile` node
+pub(crate) fn parse_segment(
+    tokens: &[Token],
+    cache: &NodeCache,
+    version: PgVersion,
+    f: impl FnOnce(&mut Parser),
+) -> Parse {
+    let mut parser = Parser::with_cache(tokens.to_vec(), cache).with_version(version);
+    parser.start_node(SyntaxKind::SourceFile);
+    f(&mut parser);
+    parser.flush_token_buffer();
+    parser.finish_node();
+    parser.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::lexer::lex;
+    use crate::node_cache::green_address;
+
+    use super::*;
+
+    fn assert_same_parse(input: &str) {
+        let serial = crate::parse_source(input);
+        let parallel = source_parallel(lex(input), &NodeCache::new(), PgVersion::default());
+
+        assert_eq!(serial.cst.text(), input);
+        assert_eq!(parallel.cst.text(), input);
+        assert_eq!(
+            format!("{:#?}", serial.cst),
+            format!("{:#?}", parallel.cst),
+            "{}",
+            input
+        );
+        assert_eq!(serial.stmts.len(), parallel.stmts.len());
+        assert_eq!(
+            serial.stmts.iter().map(|s| s.range).collect::<Vec<_>>(),
+            parallel.stmts.iter().map(|s| s.range).collect::<Vec<_>>()
+        );
+
+        let mut serial_errors = serial.errors.clone();
+        let mut parallel_errors = parallel.errors.clone();
+        serial_errors.sort_by_key(|e| e.range().start());
+        parallel_errors.sort_by_key(|e| e.range().start());
+        assert_eq!(serial_errors, parallel_errors);
+    }
+
+    #[test]
+    fn test_split() {
+        let mut parser = Parser::new(lex("select 1;  \n select 2;\n"));
+        assert_eq!(
+            split(&mut parser),
+            vec![
+                Segment::Statement(0..4),
+                Segment::Gap(4..7),
+                Segment::Statement(7..11),
+                Segment::Gap(11..12),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_copy_data() {
+        let input = "COPY t (a) FROM stdin;\n1\n2\n\\.\nselect 1;\n";
+        assert_same_parse(input);
+
+        let parse = crate::parse_source(input);
+        assert!(parse.errors.is_empty(), "{:?}", parse.errors);
+        assert_eq!(parse.stmts.len(), 2);
+        let kinds = parse.cst.children().map(|n| n.kind()).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                SyntaxKind::CopyStmt,
+                SyntaxKind::CopyData,
+                SyntaxKind::SelectStmt
+            ]
+        );
+        let data = parse.cst.children().nth(1).unwrap();
+        assert_eq!(data.text(), "1\n2\n\\.");
+    }
+
+    #[test]
+    fn test_parallel_source() {
+        assert_same_parse(
+            "select 1;  select 2;\n-- comment\ninsert into contact (id) values (1);  ",
+        );
+        assert_same_parse("select 1 from; select 2;");
+    }
+
+    #[test]
+    fn test_incremental_source() {
+        let cache = NodeCache::new();
+        let parse_cache = ParseCache::new();
+        let before = "select 1;\nselect 2 from;\n\ninsert into contact (id) values (1);";
+        source_incremental(lex(before), &cache, PgVersion::default(), &parse_cache);
+        assert_eq!(parse_cache.len(), 5);
+
+        let after = "select 10;\nselect 2 from;\n\ninsert into contact (id) values (1);";
+        let incremental =
+            source_incremental(lex(after), &cache, PgVersion::default(), &parse_cache);
+        let parallel = source_parallel(lex(after), &cache, PgVersion::default());
+        assert_eq!(
+            format!("{:#?}", incremental.cst),
+            format!("{:#?}", parallel.cst)
+        );
+        assert_eq!(
+            incremental
+                .stmts
+                .iter()
+                .map(|s| s.range)
+                .collect::<Vec<_>>(),
+            parallel.stmts.iter().map(|s| s.range).collect::<Vec<_>>()
+        );
+        assert_eq!(incremental.errors, parallel.errors);
+        assert_eq!(parse_cache.len(), 5);
+    }
+
+    #[test]
+    fn test_parallel_shared_cache() {
+        let cache = NodeCache::new();
+        let input = (0..64)
+            .map(|i| format!("select {} from contact where id = {};", i % 4, i))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let first = source_parallel(lex(&input), &cache, PgVersion::default());
+        let second = source_parallel(lex(&input), &cache, PgVersion::default());
+
+        // the nodes that the node cache deduplicates, which have at most three children, are the
+        // same nodes in both trees, whichever worker built them
+        let cached_nodes = |parse: &Parse| {
+            parse
+                .cst
+                .descendants()
+                .filter(|n| n.green().children().count() <= 3)
+                .filter_map(|n| green_address(n.green()))
+                .collect::<Vec<_>>()
+        };
+        assert!(!cached_nodes(&first).is_empty());
+        assert_eq!(cached_nodes(&first), cached_nodes(&second));
+    }
+
+    #[test]
+    fn test_cancelled_source() {
+        let cache = NodeCache::new();
+        let parse_cache = ParseCache::new();
+        let input = "select 1;\nselect 2;";
+        let cancellation = Cancellation::new();
+        cancellation.cancel();
+        let cancelled = source_cancellable(
+            lex(input),
+            &cache,
+            PgVersion::default(),
+            &parse_cache,
+            &cancellation,
+        );
+        assert_eq!(cancelled.err(), Some(Cancelled));
+        assert!(parse_cache.is_empty());
+
+        // cached segments are reused without checking the cancellation
+        source_incremental(lex(input), &cache, PgVersion::default(), &parse_cache);
+        let cached = source_cancellable(
+            lex(input),
+            &cache,
+            PgVersion::default(),
+            &parse_cache,
+            &cancellation,
+        );
+        assert_eq!(cached.map(|parse| parse.stmts.len()), Ok(2));
+    }
+
+    #[test]
+    fn test_incomplete_input() {
+        for input in ["insert", "select 1; create", "select ", "select 1;\n\n"] {
+            assert_eq!(crate::parse_source(input).cst.text(), input);
+        }
+    }
+
+    #[test]
+    fn test_parallel_statements() {
+        let mut paths = fs::read_dir("tests/data/statements/valid/")
+            .unwrap()
+            .map(|r| r.unwrap().path())
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        let input = paths
+            .iter()
+            .map(|path| fs::read_to_string(path).unwrap())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_same_parse(&input);
+    }
+}
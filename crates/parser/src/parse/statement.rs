@@ -3,13 +3,27 @@ use std::ops::Range;
 use cstree::text::{TextRange, TextSize};
 
 use super::statement_start::{is_at_stmt_start, TokenStatement, STATEMENT_START_TOKEN_MAPS};
+use super::syntax_hint::syntax_error_hint;
 use crate::codegen::SyntaxKind;
+use crate::lexer::Token;
 use crate::parse::libpg_query_node::libpg_query_node;
+use crate::pg_version::{unsupported_syntax, version_errors};
 use crate::Parser;
 
 pub fn statement(parser: &mut Parser, kind: SyntaxKind) {
+    // apply the whitespace before the statement, it would be skipped while the buffer is open
+    parser.flush_token_buffer();
     let token_range = collect_statement_token_range(parser, kind);
+    statement_at_token_range(parser, token_range);
+}
+
+/// parses the statement within `token_range`, which has been collected beforehand
+pub fn statement_at_token_range(parser: &mut Parser, token_range: Range<usize>) {
     let tokens = parser.tokens.get(token_range.clone()).unwrap().to_vec();
+    let range = TextRange::new(
+        tokens.first().unwrap().span.start(),
+        tokens.last().unwrap().span.end(),
+    );
     match pg_query::parse(
         tokens
             .iter()
@@ -18,27 +32,46 @@ pub fn statement(parser: &mut Parser, kind: SyntaxKind) {
             .as_str(),
     ) {
         Ok(result) => {
-            libpg_query_node(
-                parser,
-                result
-                    .protobuf
-                    .nodes()
-                    .iter()
-                    .find(|n| n.1 == 1)
-                    .unwrap()
-                    .0
-                    .to_enum(),
-                &token_range,
-            );
+            let root = result
+                .protobuf
+                .nodes()
+                .iter()
+                .find(|n| n.1 == 1)
+                .unwrap()
+                .0
+                .to_enum();
+            // collect the statement together with its range in the source text
+            parser.stmt(root.clone(), range);
+            for (message, location) in version_errors(&root, parser.version()) {
+                match location {
+                    Some(location) => parser
+                        .error_at_offset(message, range.start() + TextSize::from(location as u32)),
+                    None => parser.error(message, range),
+                }
+            }
+            libpg_query_node(parser, root, &token_range);
         }
         Err(err) => {
-            parser.error(
-                err.to_string(),
-                TextRange::new(
-                    TextSize::from(u32::try_from(token_range.start).unwrap()),
-                    TextSize::from(u32::try_from(token_range.end).unwrap()),
-                ),
-            );
+            match unsupported_syntax(&tokens, parser.version()) {
+                Some((message, range)) => parser.error(message, range),
+                None => {
+                    let message = err.to_string();
+                    let error_range = syntax_error_range(&tokens, &message).unwrap_or(range);
+                    // lead with the hint, and keep the message of pg_query for reference
+                    let message =
+                        match syntax_error_hint(&parser.tokens, token_range.clone(), error_range) {
+                            Some(hint) => format!(
+                                "{} ({})",
+                                hint,
+                                message
+                                    .strip_prefix("Invalid statement: ")
+                                    .unwrap_or(&message)
+                            ),
+                            None => message,
+                        };
+                    parser.error(message, error_range)
+                }
+            }
             while parser.pos < token_range.end {
                 parser.advance();
             }
@@ -48,7 +81,39 @@ pub fn statement(parser: &mut Parser, kind: SyntaxKind) {
     assert_eq!(parser.pos, token_range.end);
 }
 
-fn collect_statement_token_range(parser: &mut Parser, kind: SyntaxKind) -> Range<usize> {
+/// Returns the range of the token that the syntax error `message` of the statement made of `tokens`
+/// points at, or `None` if the message does not name a token
+///
+/// pg_query does not return the cursor position of an error, but the message names the token
+/// at the cursor, e.g. `syntax error at or near "from"`. If the statement contains that text
+/// more than once, the error is at the first occurrence where the statement up to and including
+/// it fails with the same message, since the server reports the first token it cannot parse.
+fn syntax_error_range(tokens: &[Token], message: &str) -> Option<TextRange> {
+    let significant = tokens.iter().filter(|t| !t.kind.is_trivia());
+    if message.ends_with("at end of input") {
+        return significant.last().map(|t| TextRange::empty(t.span.end()));
+    }
+    let (_, near) = message.split_once("at or near \"")?;
+    let near = near.strip_suffix('"')?;
+    let candidates = tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| t.text == near)
+        .collect::<Vec<_>>();
+    if let [(_, token)] = candidates.as_slice() {
+        return Some(token.span);
+    }
+    candidates.into_iter().find_map(|(idx, token)| {
+        let prefix = tokens[..=idx]
+            .iter()
+            .map(|t| t.text.as_str())
+            .collect::<String>();
+        let err = pg_query::parse(&prefix).err()?;
+        (err.to_string() == message).then_some(token.span)
+    })
+}
+
+pub fn collect_statement_token_range(parser: &mut Parser, kind: SyntaxKind) -> Range<usize> {
     parser.open_buffer();
 
     // advance with all start tokens of statement
@@ -56,6 +121,8 @@ fn collect_statement_token_range(parser: &mut Parser, kind: SyntaxKind) -> Range
 
     let mut is_parsing_sub_stmt = false;
     let mut ignore_next_non_whitespace = false;
+    // the event and actions of a rule are statement keywords that belong to the rule
+    let contains_stmts = kind == SyntaxKind::RuleStmt;
     while !parser.at(SyntaxKind::Ascii59) && !parser.eof() {
         match parser.nth(0, false).kind {
             // opening brackets "(", consume until closing bracket ")"
@@ -77,6 +144,7 @@ fn collect_statement_token_range(parser: &mut Parser, kind: SyntaxKind) -> Range
                 // ignore if parsing sub stmt
                 if ignore_next_non_whitespace == false
                     && is_parsing_sub_stmt == false
+                    && !contains_stmts
                     && is_at_stmt_start(parser).is_some()
                 {
                     break;
@@ -118,3 +186,33 @@ fn advance_over_start_tokens(parser: &mut Parser, kind: SyntaxKind) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_source;
+
+    fn error_range(input: &str) -> (usize, usize) {
+        let parse = parse_source(input);
+        let error = parse
+            .errors
+            .iter()
+            .find(|e| e.to_string().contains("syntax error"))
+            .unwrap();
+        let range = error.range();
+        (range.start().into(), range.end().into())
+    }
+
+    #[test]
+    fn test_syntax_error_range() {
+        let input = "select 1;\nselect a, from contact;";
+        let start = input.rfind("from").unwrap();
+        assert_eq!(error_range(input), (start, start + 4));
+
+        let input = "select a from contact where from = 1;";
+        let start = input.rfind("from").unwrap();
+        assert_eq!(error_range(input), (start, start + 4));
+
+        let input = "select 1 +";
+        assert_eq!(error_range(input), (input.len(), input.len()));
+    }
+}
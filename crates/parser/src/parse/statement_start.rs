@@ -968,10 +968,15 @@ pub static STATEMENT_START_TOKEN_MAPS: LazyLock<Vec<HashMap<SyntaxKind, Vec<Toke
 
 /// Returns the statement at which the parser is currently at, if any
 pub fn is_at_stmt_start(parser: &mut Parser) -> Option<SyntaxKind> {
-    let mut options = Vec::new();
+    let mut options: Vec<TokenStatement> = Vec::new();
     for i in 0..STATEMENT_START_TOKEN_MAPS.len() {
         // important, else infinite loop: only ignore whitespaces after first token
         let token = parser.nth(i, i != 0).kind;
+        if token == SyntaxKind::Eof {
+            // the input ends before the statement is known, e.g. within `INSERT`
+            options.retain(|o| o.is_eos());
+            break;
+        }
         if let Some(result) = STATEMENT_START_TOKEN_MAPS[i].get(&token) {
             if i == 0 {
                 options = result.clone();
@@ -0,0 +1,209 @@
+use std::ops::Range;
+
+use cstree::text::TextRange;
+
+use crate::codegen::SyntaxKind;
+use crate::lexer::Token;
+
+/// Keywords that start a statement or clause, which misspelled identifiers are compared with
+const CLAUSE_KEYWORDS: &[&str] = &[
+    "select",
+    "from",
+    "where",
+    "group",
+    "having",
+    "order",
+    "limit",
+    "offset",
+    "insert",
+    "into",
+    "values",
+    "update",
+    "delete",
+    "returning",
+    "create",
+    "alter",
+    "drop",
+    "table",
+    "index",
+    "join",
+    "inner",
+    "outer",
+    "union",
+    "except",
+    "intersect",
+];
+
+/// Returns a hint on what is wrong with the statement at `stmt` within `tokens`, whose syntax
+/// error is at `error`, or `None` if there is no better guess than the message of pg_query
+///
+/// The hints are heuristics on the tokens around the error, e.g. `did you mean GROUP BY?` if
+/// `BY` is missing after `GROUP`. Lines are counted from the start of the statement, since the
+/// parse of a statement is reused wherever its text moves to.
+pub fn syntax_error_hint(tokens: &[Token], stmt: Range<usize>, error: TextRange) -> Option<String> {
+    let significant = tokens[stmt.clone()]
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| !t.kind.is_trivia())
+        .map(|(idx, t)| (stmt.start + idx, t))
+        .collect::<Vec<_>>();
+    // the index of the token that the error is at within `significant`, which is past the end if
+    // the error is at the end of the input
+    let at = significant
+        .iter()
+        .position(|(_, t)| t.span.start() >= error.start())
+        .unwrap_or(significant.len());
+    let before = &significant[..at];
+    let token = significant.get(at).map(|(_, t)| *t);
+    let prev = before.last().map(|(_, t)| *t);
+
+    // parentheses
+    let mut open = Vec::new();
+    for (idx, t) in before {
+        match t.kind {
+            SyntaxKind::Ascii40 => open.push(*idx),
+            SyntaxKind::Ascii41 => {
+                open.pop();
+            }
+            _ => {}
+        }
+    }
+    match token.map(|t| t.kind) {
+        Some(SyntaxKind::Ascii41) if open.is_empty() => {
+            return Some("unmatched closing parenthesis".to_string());
+        }
+        None | Some(SyntaxKind::Ascii59) => {
+            if let Some(idx) = open.first() {
+                let line = tokens[stmt.start..*idx]
+                    .iter()
+                    .map(|t| t.text.matches('\n').count())
+                    .sum::<usize>()
+                    + 1;
+                return Some(format!(
+                    "unclosed parenthesis opened at line {} of the statement",
+                    line
+                ));
+            }
+        }
+        _ => {}
+    }
+
+    // clauses of two keywords
+    let by = token.is_some_and(|t| t.kind == SyntaxKind::By);
+    match prev.map(|t| t.kind) {
+        Some(SyntaxKind::GroupP) if !by => return Some("did you mean GROUP BY?".to_string()),
+        Some(SyntaxKind::Order) if !by => return Some("did you mean ORDER BY?".to_string()),
+        _ => {}
+    }
+
+    // misspelled keywords, which are either the error or parsed as an alias right before it
+    if let Some(keyword) = [token, prev]
+        .into_iter()
+        .flatten()
+        .filter(|t| t.kind == SyntaxKind::Ident)
+        .find_map(|t| misspelled_keyword(&t.text))
+    {
+        return Some(format!("did you mean {}?", keyword.to_uppercase()));
+    }
+
+    // a list whose items are not separated
+    let is_word = |t: &Token| {
+        t.kind == SyntaxKind::Ident
+            || t.kind.is_literal()
+            || t.kind.keyword_category().is_some_and(|c| c.is_col_name())
+    };
+    if token.is_some_and(is_word) && prev.is_some_and(is_word) {
+        let in_select_list = significant
+            .first()
+            .is_some_and(|(_, t)| t.kind == SyntaxKind::Select)
+            && !before.iter().any(|(_, t)| t.kind == SyntaxKind::From)
+            && before.len() >= 3
+            && is_word(before[before.len() - 2].1);
+        if !open.is_empty() || in_select_list {
+            return Some("missing comma in column list".to_string());
+        }
+    }
+
+    None
+}
+
+/// Returns the clause keyword that `word` is a likely misspelling of
+fn misspelled_keyword(word: &str) -> Option<&'static str> {
+    let word = word.to_lowercase();
+    if word.len() < 3 {
+        return None;
+    }
+    CLAUSE_KEYWORDS.iter().copied().find(|keyword| {
+        let max_distance = if keyword.len() <= 5 { 1 } else { 2 };
+        *keyword != word && edit_distance(&word, keyword) <= max_distance
+    })
+}
+
+/// Returns the number of insertions, deletions, substitutions and transpositions of adjacent
+/// characters that turn `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_source;
+
+    use super::*;
+
+    fn error_message(input: &str) -> String {
+        parse_source(input)
+            .errors
+            .iter()
+            .find(|e| e.to_string().contains("syntax error"))
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_syntax_error_hint() {
+        assert!(
+            error_message("select a from contact group a;").starts_with("did you mean GROUP BY?")
+        );
+        assert!(error_message("select a form contact;").starts_with("did you mean FROM?"));
+        assert!(error_message("select a from contact limt 1;").starts_with("did you mean LIMIT?"));
+        assert!(
+            error_message("select a b c from contact;").starts_with("missing comma in column list")
+        );
+        assert!(error_message("insert into contact (a b) values (1, 2);")
+            .starts_with("missing comma in column list"));
+        assert!(error_message("select 1;\nselect\n  count(\n  a;")
+            .starts_with("unclosed parenthesis opened at line 2 of the statement"));
+        assert!(
+            error_message("select a) from contact;").starts_with("unmatched closing parenthesis")
+        );
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("form", "from"), 1);
+        assert_eq!(edit_distance("selct", "select"), 1);
+        assert_eq!(edit_distance("from", "from"), 0);
+        assert!(edit_distance("contact", "select") > 2);
+    }
+}
@@ -0,0 +1,183 @@
+//! A cache of the parse results of the statements of a document.
+//!
+//! Most edits change a single statement, but a document is always parsed as a whole. The parse
+//! results of all statements and the gaps between them are therefore kept by their text, and
+//! reused by the next parse of the document if their text did not change. Their ranges are stored
+//! relative to the start of the statement, so that they can be moved when text before them has
+//! been edited.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use cstree::text::TextSize;
+
+use crate::ast_node::RawStmt;
+use crate::mutation::GreenElement;
+use crate::pg_version::PgVersion;
+use crate::syntax_error::SyntaxError;
+
+/// The parse results of the segments of a document, by their text
+///
+/// Only the segments of the latest parse are kept, so the cache is as large as one tree of the
+/// document. Use one cache per document.
+#[derive(Debug, Default)]
+pub struct ParseCache {
+    segments: Mutex<HashMap<SegmentKey, Arc<CachedSegment>>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct SegmentKey {
+    /// The kind of `Segment` as its index
+    pub(crate) kind: u8,
+    pub(crate) text: String,
+    pub(crate) version: PgVersion,
+}
+
+/// The parse result of a segment with ranges relative to its start
+#[derive(Debug)]
+pub(crate) struct CachedSegment {
+    pub(crate) children: Vec<GreenElement>,
+    pub(crate) stmts: Vec<RawStmt>,
+    pub(crate) errors: Vec<SyntaxError>,
+}
+
+impl CachedSegment {
+    /// Returns the statements of the segment if it starts at `offset`
+    pub(crate) fn stmts_at(&self, offset: TextSize) -> impl Iterator<Item = RawStmt> + '_ {
+        self.stmts.iter().map(move |stmt| RawStmt {
+            stmt: stmt.stmt.clone(),
+            range: stmt.range + offset,
+        })
+    }
+
+    /// Returns the errors of the segment if it starts at `offset`
+    pub(crate) fn errors_at(&self, offset: TextSize) -> impl Iterator<Item = SyntaxError> + '_ {
+        self.errors.iter().map(move |error| {
+            let range = error.range() + offset;
+            error.clone().with_range(range)
+        })
+    }
+}
+
+impl ParseCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of cached segments
+    pub fn len(&self) -> usize {
+        self.segments.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn get(&self, key: &SegmentKey) -> Option<Arc<CachedSegment>> {
+        self.segments.lock().unwrap().get(key).cloned()
+    }
+
+    /// Replaces the cached segments with those of the latest parse
+    pub(crate) fn replace(&self, segments: HashMap<SegmentKey, Arc<CachedSegment>>) {
+        *self.segments.lock().unwrap() = segments;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cancellation::Cancellation;
+    use crate::node_cache::{green_address, NodeCache};
+    use crate::{parse_source_cancellable_with_version, parse_source_incremental, Parse};
+
+    use super::*;
+
+    /// Returns the cached segment whose text starts with `text`
+    fn segment(parse_cache: &ParseCache, text: &str) -> Option<Arc<CachedSegment>> {
+        let segments = parse_cache.segments.lock().unwrap();
+        segments
+            .iter()
+            .find(|(key, _)| key.text.starts_with(text))
+            .map(|(_, segment)| segment.clone())
+    }
+
+    /// Returns the address of the tree of the statement that starts with `text`
+    fn stmt_address(parse: &Parse, text: &str) -> Option<*const ()> {
+        parse
+            .cst
+            .children()
+            .find(|node| node.text().to_string().starts_with(text))
+            .and_then(|node| green_address(node.green()))
+    }
+
+    #[test]
+    fn test_unchanged_segments_are_reused() {
+        let cache = NodeCache::new();
+        let parse_cache = ParseCache::new();
+        let unchanged = "select name, email from contact where id = 2 order by name;";
+        let before =
+            parse_source_incremental(&format!("select 1;\n{}\n", unchanged), &cache, &parse_cache);
+        let cached = segment(&parse_cache, unchanged).unwrap();
+
+        let after = parse_source_incremental(
+            &format!("select 10;\n\n{}\n", unchanged),
+            &cache,
+            &parse_cache,
+        );
+        // the statement moved, but its segment and tree are the same
+        assert!(Arc::ptr_eq(
+            &cached,
+            &segment(&parse_cache, unchanged).unwrap()
+        ));
+        assert!(stmt_address(&before, unchanged).is_some());
+        assert_eq!(
+            stmt_address(&before, unchanged),
+            stmt_address(&after, unchanged)
+        );
+        assert_eq!(after.stmts[1].range.start(), TextSize::from(12));
+    }
+
+    #[test]
+    fn test_edited_segments_are_rebuilt() {
+        let cache = NodeCache::new();
+        let parse_cache = ParseCache::new();
+        let before = parse_source_incremental("select 1;\nselect 2 from;", &cache, &parse_cache);
+        assert_eq!(before.errors.len(), 1);
+        let edited = segment(&parse_cache, "select 2 from;").unwrap();
+
+        let after =
+            parse_source_incremental("select 1;\nselect 2 from contact;", &cache, &parse_cache);
+        assert!(after.errors.is_empty());
+        // only the segments of the latest parse are kept
+        assert!(segment(&parse_cache, "select 2 from;").is_none());
+        let rebuilt = segment(&parse_cache, "select 2 from contact;").unwrap();
+        assert!(!Arc::ptr_eq(&edited, &rebuilt));
+        assert_ne!(
+            stmt_address(&before, "select 2"),
+            stmt_address(&after, "select 2")
+        );
+    }
+
+    #[test]
+    fn test_segments_are_rebuilt_for_another_version() {
+        let cache = NodeCache::new();
+        let parse_cache = ParseCache::new();
+        let text = "merge into t using s on t.id = s.id when matched then delete;";
+        let parse = |version| {
+            parse_source_cancellable_with_version(
+                text,
+                &cache,
+                &parse_cache,
+                &Cancellation::new(),
+                PgVersion::new(version),
+            )
+            .unwrap()
+        };
+        parse(14);
+        let cached = segment(&parse_cache, text).unwrap();
+        assert_eq!(cached.errors.len(), 1);
+        parse(15);
+        let rebuilt = segment(&parse_cache, text).unwrap();
+        assert!(!Arc::ptr_eq(&cached, &rebuilt));
+        assert!(rebuilt.errors.is_empty());
+    }
+}
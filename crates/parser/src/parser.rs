@@ -10,21 +10,20 @@ use std::ops::Range;
 use crate::ast_node::RawStmt;
 use crate::codegen::SyntaxKind;
 use crate::lexer::{Token, TokenType};
+use crate::node_cache::{NodeCache, SharedInterner};
+use crate::pg_version::PgVersion;
 use crate::syntax_error::SyntaxError;
 use crate::syntax_node::SyntaxNode;
 
-pub static WHITESPACE_TOKENS: &[SyntaxKind] = &[
-    SyntaxKind::Whitespace,
-    SyntaxKind::Tab,
-    SyntaxKind::Newline,
-    SyntaxKind::SqlComment,
-];
-
 /// Main parser that exposes the `cstree` api, and collects errors and statements
 #[derive(Debug)]
 pub struct Parser {
     /// The cst builder
-    inner: GreenNodeBuilder<'static, 'static, SyntaxKind>,
+    inner: GreenNodeBuilder<'static, 'static, SyntaxKind, SharedInterner>,
+    /// The cache shared with other parsers that the builder was taken from
+    cache: NodeCache,
+    /// Whether the builder has the shared node cache of `cache`, which it puts back
+    shared: bool,
     /// The syntax errors accumulated during parsing
     errors: Vec<SyntaxError>,
     /// The pg_query statements representing the abstract syntax tree
@@ -40,6 +39,9 @@ pub struct Parser {
 
     pub depth: usize,
 
+    /// The version of Postgres the parsed source targets
+    version: PgVersion,
+
     eof_token: Token,
 }
 
@@ -56,9 +58,17 @@ pub struct Parse {
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
+        Self::with_cache(tokens, &NodeCache::new())
+    }
+
+    /// Creates a parser that deduplicates tokens and nodes using a shared `cache`
+    pub fn with_cache(tokens: Vec<Token>, cache: &NodeCache) -> Self {
+        let (nodes, shared) = cache.take();
         Self {
             eof_token: Token::eof(usize::from(tokens.last().unwrap().span.end())),
-            inner: GreenNodeBuilder::new(),
+            inner: GreenNodeBuilder::from_cache(nodes),
+            cache: cache.clone(),
+            shared,
             errors: Vec::new(),
             stmts: Vec::new(),
             tokens,
@@ -66,9 +76,21 @@ impl Parser {
             whitespace_token_buffer: None,
             token_buffer: None,
             depth: 0,
+            version: PgVersion::default(),
         }
     }
 
+    /// Reports syntax that is not available in `version` as errors
+    pub fn with_version(mut self, version: PgVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// The version of Postgres the parsed source targets
+    pub fn version(&self) -> PgVersion {
+        self.version
+    }
+
     /// start a new node of `SyntaxKind`
     pub fn start_node(&mut self, kind: SyntaxKind) {
         debug!("start_node: {:?}", kind);
@@ -113,13 +135,19 @@ impl Parser {
     /// finish cstree and return `Parse`
     pub fn finish(self) -> Parse {
         let (tree, cache) = self.inner.finish();
+        self.cache.put_back(cache.unwrap(), self.shared);
         Parse {
-            cst: SyntaxNode::new_root_with_resolver(tree, cache.unwrap().into_interner().unwrap()),
+            cst: SyntaxNode::new_root_with_resolver(tree, self.cache.interner()),
             stmts: self.stmts,
             errors: self.errors,
         }
     }
 
+    /// Consumes the parser without building a tree and returns the collected errors
+    pub fn into_errors(self) -> Vec<SyntaxError> {
+        self.errors
+    }
+
     /// Prepare for maybe wrapping the next node with a surrounding node.
     ///
     /// The way wrapping works is that you first get a checkpoint, then you add nodes and tokens as
@@ -198,7 +226,7 @@ impl Parser {
     }
 
     pub fn eat_whitespace(&mut self) {
-        while self.nth(0, false).token_type == TokenType::Whitespace {
+        while !self.eof() && self.nth(0, false).token_type == TokenType::Whitespace {
             self.advance();
         }
     }
@@ -217,7 +245,7 @@ impl Parser {
             loop {
                 match self.tokens.get(self.pos + idx) {
                     Some(token) => {
-                        if !WHITESPACE_TOKENS.contains(&token.kind) {
+                        if !token.kind.is_trivia() {
                             if non_whitespace_token_ctr == lookahead {
                                 return token;
                             }
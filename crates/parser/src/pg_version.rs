@@ -0,0 +1,187 @@
+//! Handling of syntax that is only valid in some versions of Postgres.
+//!
+//! The parser always understands the syntax of the bundled libpg_query (`PgVersion::PARSER`). If
+//! the targeted version is older, nodes that were introduced later are reported as errors, using
+//! the versions that codegen attaches to them in `SyntaxKind::required_pg_version`. Syntax that is
+//! newer than the bundled libpg_query fails to parse, and is recognized from its tokens so that
+//! the error names the feature instead of just the unexpected token.
+
+use std::fmt;
+use std::str::FromStr;
+
+use cstree::text::TextRange;
+use pg_query::NodeEnum;
+
+use crate::codegen::{get_children, get_location, SyntaxKind};
+use crate::lexer::Token;
+
+/// A major version of Postgres
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PgVersion(u32);
+
+impl PgVersion {
+    /// The version of the bundled libpg_query, i.e. the newest syntax that can be parsed
+    pub const PARSER: PgVersion = PgVersion(15);
+
+    pub const fn new(major: u32) -> Self {
+        Self(major)
+    }
+
+    pub fn major(&self) -> u32 {
+        self.0
+    }
+}
+
+impl Default for PgVersion {
+    fn default() -> Self {
+        Self::PARSER
+    }
+}
+
+impl fmt::Display for PgVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PostgreSQL {}", self.0)
+    }
+}
+
+impl FromStr for PgVersion {
+    type Err = String;
+
+    /// Parses a version such as `15`, `15.4`, `9.6`, `16beta1`, `PostgreSQL 14.2 on x86_64...` or
+    /// a `server_version_num` such as `150004`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let s = s.strip_prefix("PostgreSQL ").unwrap_or(s);
+        let digits = s
+            .split(|c: char| !c.is_ascii_digit())
+            .next()
+            .unwrap_or_default();
+        match digits.parse::<u32>() {
+            // e.g. 150004
+            Ok(num) if num >= 10000 => Ok(Self(num / 10000)),
+            Ok(major) if major > 0 => Ok(Self(major)),
+            _ => Err(format!("invalid Postgres version: {}", s)),
+        }
+    }
+}
+
+/// Functions with syntax that is newer than the bundled libpg_query, as (function name, feature,
+/// major version)
+const UNSUPPORTED_FUNCTIONS: &[(&str, &str, u32)] = &[
+    ("json_object", "JSON_OBJECT", 16),
+    ("json_array", "JSON_ARRAY", 16),
+    ("json_objectagg", "JSON_OBJECTAGG", 16),
+    ("json_arrayagg", "JSON_ARRAYAGG", 16),
+    ("json_exists", "JSON_EXISTS", 17),
+    ("json_query", "JSON_QUERY", 17),
+    ("json_value", "JSON_VALUE", 17),
+    ("json_table", "JSON_TABLE", 17),
+    ("json_serialize", "JSON_SERIALIZE", 17),
+    ("merge_action", "MERGE ... RETURNING merge_action()", 17),
+];
+
+/// Returns an error for every feature used within `root` that is not available in `version`,
+/// together with the location of the node that uses it
+pub(crate) fn version_errors(root: &NodeEnum, version: PgVersion) -> Vec<(String, Option<usize>)> {
+    let mut errors = Vec::new();
+    let mut nodes = vec![root.clone()];
+    while let Some(node) = nodes.pop() {
+        if let Some((feature, required)) = SyntaxKind::from(&node).required_pg_version() {
+            if version.major() < required {
+                errors.push((
+                    format!("{} requires PostgreSQL {}+", feature, required),
+                    get_location(&node),
+                ));
+            }
+        }
+        nodes.extend(get_children(&node));
+    }
+    errors.sort_by_key(|(_, location)| *location);
+    errors
+}
+
+/// Returns an error that names the feature if the statement `tokens` failed to parse because it
+/// uses syntax that is newer than the bundled libpg_query
+pub(crate) fn unsupported_syntax(
+    tokens: &[Token],
+    version: PgVersion,
+) -> Option<(String, TextRange)> {
+    let significant = tokens
+        .iter()
+        .filter(|t| !t.kind.is_trivia())
+        .collect::<Vec<&Token>>();
+    significant.windows(2).find_map(|w| {
+        if w[1].kind != SyntaxKind::Ascii40 {
+            return None;
+        }
+        let (_, feature, required) = UNSUPPORTED_FUNCTIONS
+            .iter()
+            .find(|(name, _, _)| w[0].text.eq_ignore_ascii_case(name))?;
+        let message = if version.major() < *required {
+            format!("{} requires PostgreSQL {}+", feature, required)
+        } else {
+            format!(
+                "{} requires PostgreSQL {}+, but the parser only supports {}",
+                feature,
+                required,
+                PgVersion::PARSER
+            )
+        };
+        Some((message, w[0].span))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_source_with_version;
+
+    /// Returns the version related errors of `input`
+    fn errors(input: &str, version: u32) -> Vec<String> {
+        parse_source_with_version(input, PgVersion::new(version))
+            .errors
+            .iter()
+            .map(|e| e.to_string())
+            .filter(|e| e.contains("requires PostgreSQL"))
+            .collect()
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("15".parse(), Ok(PgVersion::new(15)));
+        assert_eq!("14.2".parse(), Ok(PgVersion::new(14)));
+        assert_eq!("16beta1".parse(), Ok(PgVersion::new(16)));
+        assert_eq!("150004".parse(), Ok(PgVersion::new(15)));
+        assert_eq!(
+            "PostgreSQL 13.1 on x86_64-pc-linux-gnu".parse(),
+            Ok(PgVersion::new(13))
+        );
+        assert!("latest".parse::<PgVersion>().is_err());
+    }
+
+    #[test]
+    fn test_newer_syntax() {
+        let input = "merge into t using s on t.id = s.id when matched then delete;";
+        assert_eq!(errors(input, 15), Vec::<String>::new());
+        assert_eq!(errors(input, 14), vec!["MERGE requires PostgreSQL 15+"]);
+
+        let input = "with recursive t(n) as (select 1 union all select n + 1 from t) search depth first by n set ord select * from t;";
+        assert_eq!(
+            errors(input, 13),
+            vec!["SEARCH clause requires PostgreSQL 14+"]
+        );
+    }
+
+    #[test]
+    fn test_unsupported_syntax() {
+        let input = "select * from json_table('[]', '$[*]' columns (a int path '$.a'));";
+        assert_eq!(
+            errors(input, 16),
+            vec!["JSON_TABLE requires PostgreSQL 17+"]
+        );
+        assert_eq!(
+            errors(input, 17),
+            vec!["JSON_TABLE requires PostgreSQL 17+, but the parser only supports PostgreSQL 15"]
+        );
+    }
+}
@@ -0,0 +1,361 @@
+//! Parsing of inputs that are too large to be held in memory as a whole, e.g. production dumps.
+//!
+//! The input is read in chunks. Every chunk is appended to a buffer, which is lexed and split into
+//! statements. All statements but the last one are parsed and emitted, because the last one might
+//! continue in the next chunk. Once a statement has been emitted, its text is dropped from the
+//! buffer. The memory used is thereby bounded by the size of the largest statement instead of the
+//! size of the input.
+//!
+//! If a chunk ends within a quoted string or a block comment, the buffer cannot be lexed and more
+//! input is read until it can. If the input ends within one, all statements before it are emitted
+//! followed by an error.
+
+use std::collections::VecDeque;
+use std::io::{self, Read};
+use std::mem;
+
+use cstree::text::{TextRange, TextSize};
+
+use crate::lexer::{try_lex, Token};
+use crate::node_cache::NodeCache;
+use crate::parse::source::{parse_segment, split, Segment};
+use crate::parse::statement::statement_at_token_range;
+use crate::pg_version::PgVersion;
+use crate::syntax_node::SyntaxNode;
+use crate::{Parse, Parser};
+
+/// The number of bytes read from the input at once
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// A statement emitted by a [`StatementStream`]
+#[derive(Debug)]
+pub struct StreamedStatement {
+    /// The offset of the statement in the input
+    pub offset: TextSize,
+    /// The parse result of the statement.
+    ///
+    /// The root of the cst is the statement node and its text ranges are relative to `offset`.
+    /// If the statement could not be parsed, the root is a `SourceFile` node that contains its
+    /// tokens. The ranges of errors and statements are absolute.
+    pub parse: Parse,
+}
+
+/// An iterator that reads sql from `reader` and parses one statement at a time
+///
+/// Whitespace and comments between statements are skipped. Every statement is parsed with its
+/// own node cache, so no memory is retained after a statement has been dropped.
+pub struct StatementStream<R> {
+    reader: R,
+    /// The text that has been read, but not yet emitted
+    buffer: String,
+    /// Bytes of an incomplete utf-8 character at the end of the last chunk
+    pending: Vec<u8>,
+    /// The offset of `buffer` in the input
+    offset: usize,
+    /// Parsed statements that have not been emitted yet
+    ready: VecDeque<StreamedStatement>,
+    /// An error that is emitted after all statements before it
+    failure: Option<io::Error>,
+    /// The number of bytes to read next. Grows while the buffer does not contain a statement.
+    read_size: usize,
+    eof: bool,
+    /// The version of Postgres the input targets
+    version: PgVersion,
+}
+
+impl<R: Read> StatementStream<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: String::new(),
+            pending: Vec::new(),
+            offset: 0,
+            ready: VecDeque::new(),
+            failure: None,
+            read_size: CHUNK_SIZE,
+            eof: false,
+            version: PgVersion::default(),
+        }
+    }
+
+    /// Reports syntax that is not available in `version` as errors
+    pub fn with_version(mut self, version: PgVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Reads the next chunk into the buffer
+    fn read_chunk(&mut self) -> io::Result<()> {
+        let mut chunk = Vec::with_capacity(self.read_size);
+        let read = (&mut self.reader)
+            .take(self.read_size as u64)
+            .read_to_end(&mut chunk)?;
+        if read == 0 {
+            self.eof = true;
+            if !self.pending.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "stream did not contain valid UTF-8",
+                ));
+            }
+            return Ok(());
+        }
+
+        let mut bytes = mem::take(&mut self.pending);
+        bytes.extend(chunk);
+        let valid = match std::str::from_utf8(&bytes) {
+            Ok(_) => bytes.len(),
+            // an error without length is an incomplete character at the end
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "stream did not contain valid UTF-8",
+                ))
+            }
+        };
+        self.pending = bytes.split_off(valid);
+        self.buffer
+            .push_str(std::str::from_utf8(&bytes).expect("validated above"));
+        Ok(())
+    }
+
+    /// Parses all statements in the buffer that are complete and drops their text from the buffer
+    ///
+    /// Returns false if more input is required.
+    fn parse_buffer(&mut self) -> bool {
+        if self.buffer.is_empty() {
+            return false;
+        }
+
+        match try_lex(&self.buffer) {
+            Ok(tokens) => {
+                let consumed = self.parse_tokens(tokens, self.eof);
+                self.buffer.drain(..consumed);
+                self.offset += consumed;
+                consumed > 0
+            }
+            Err(_) if !self.eof => false,
+            Err(err) => {
+                // emit the statements before the one that cannot be tokenized
+                let valid = self
+                    .buffer
+                    .rmatch_indices(';')
+                    .map(|(idx, _)| idx + 1)
+                    .find_map(|end| try_lex(&self.buffer[..end]).ok().map(|t| (end, t)));
+                let mut consumed = 0;
+                if let Some((end, tokens)) = valid {
+                    consumed = self.parse_tokens(tokens, true);
+                    debug_assert_eq!(consumed, end);
+                }
+                self.failure = Some(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "input at offset {} could not be tokenized: {}",
+                        self.offset + consumed,
+                        err
+                    ),
+                ));
+                self.buffer.clear();
+                true
+            }
+        }
+    }
+
+    /// Parses the statements within `tokens`, which have been lexed from the start of the buffer,
+    /// and returns the number of bytes of the buffer that have been consumed
+    ///
+    /// If `complete` is false, the last statement is kept because it may continue in the next
+    /// chunk.
+    fn parse_tokens(&mut self, mut tokens: Vec<Token>, complete: bool) -> usize {
+        let offset = TextSize::try_from(self.offset).expect("input is too large");
+        let len = usize::from(tokens.last().unwrap().span.end());
+        tokens.iter_mut().for_each(|t| t.span += offset);
+
+        let mut splitter = Parser::new(tokens);
+        let mut segments = split(&mut splitter);
+        let tokens = mem::take(&mut splitter.tokens);
+
+        let consumed = if complete {
+            len
+        } else {
+            match segments
+                .iter()
+                .rposition(|s| matches!(s, Segment::Statement(_)))
+            {
+                Some(idx) => {
+                    let start = match segments.drain(idx..).next() {
+                        Some(Segment::Statement(range)) => range.start,
+                        _ => unreachable!(),
+                    };
+                    usize::from(tokens[start].span.start()) - self.offset
+                }
+                // only whitespace and comments, which may also continue in the next chunk
+                None => {
+                    segments.clear();
+                    0
+                }
+            }
+        };
+
+        for segment in segments {
+            if let Segment::Statement(range) = segment {
+                self.ready
+                    .push_back(parse_statement(&tokens[range], self.version));
+            }
+        }
+        consumed
+    }
+}
+
+/// Parses the tokens of a single statement into a tree with the statement as its root
+fn parse_statement(tokens: &[Token], version: PgVersion) -> StreamedStatement {
+    let cache = NodeCache::new();
+    let mut parse = parse_segment(tokens, &cache, version, |p| {
+        statement_at_token_range(p, 0..p.tokens.len())
+    });
+    let offset = tokens[0].span.start();
+    if let Some(stmt) = parse.cst.first_child() {
+        parse.cst = SyntaxNode::new_root_with_resolver(stmt.green().clone(), cache.interner());
+    }
+    StreamedStatement { offset, parse }
+}
+
+impl<R: Read> Iterator for StatementStream<R> {
+    type Item = io::Result<StreamedStatement>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(statement) = self.ready.pop_front() {
+                return Some(Ok(statement));
+            }
+            if let Some(err) = self.failure.take() {
+                return Some(Err(err));
+            }
+            if self.eof && self.buffer.is_empty() {
+                return None;
+            }
+
+            if let Err(err) = self.read_chunk() {
+                self.buffer.clear();
+                self.eof = true;
+                return Some(Err(err));
+            }
+            if self.parse_buffer() {
+                self.read_size = CHUNK_SIZE;
+            } else {
+                // read more data the next time if the buffer does not contain a complete statement
+                self.read_size = self.read_size.max(self.buffer.len());
+            }
+        }
+    }
+}
+
+impl StreamedStatement {
+    /// The range of the statement in the input
+    pub fn range(&self) -> TextRange {
+        TextRange::at(self.offset, self.parse.cst.text_range().len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::codegen::SyntaxKind;
+
+    use super::*;
+
+    /// A reader that returns at most `n` bytes per read
+    struct Trickle<'a>(&'a [u8], usize);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.1.min(buf.len()).min(self.0.len());
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    fn stream_with_chunk_size(input: &str, size: usize) -> Vec<StreamedStatement> {
+        let mut stream = StatementStream::new(Trickle(input.as_bytes(), size));
+        stream.read_size = size;
+        stream.map(|s| s.unwrap()).collect()
+    }
+
+    #[test]
+    fn test_stream() {
+        let input =
+            "select 1;\n-- comment\nselect 'a;b'; /* c; */ insert into contact (id) values (1);\n";
+        let statements = stream_with_chunk_size(input, 3);
+
+        assert_eq!(statements.len(), 3);
+        assert_eq!(
+            statements
+                .iter()
+                .map(|s| &input[s.range()])
+                .collect::<Vec<_>>(),
+            vec![
+                "select 1;",
+                "select 'a;b';",
+                "insert into contact (id) values (1);"
+            ]
+        );
+        assert_eq!(statements[1].parse.cst.kind(), SyntaxKind::SelectStmt);
+        assert_eq!(statements[1].parse.stmts[0].range, statements[1].range());
+    }
+
+    #[test]
+    fn test_stream_matches_parse_source() {
+        let mut paths = fs::read_dir("tests/data/statements/valid/")
+            .unwrap()
+            .map(|r| r.unwrap().path())
+            .collect::<Vec<_>>();
+        paths.sort();
+        let input = paths
+            .iter()
+            .map(|path| fs::read_to_string(path).unwrap())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let expected = crate::parse_source(&input);
+        let statements = stream_with_chunk_size(&input, 100);
+
+        assert_eq!(
+            statements
+                .iter()
+                .filter(|s| s.parse.cst.kind() != SyntaxKind::SourceFile)
+                .map(|s| s.parse.cst.text().to_string())
+                .collect::<Vec<_>>(),
+            expected
+                .cst
+                .children()
+                .map(|n| n.text().to_string())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            statements
+                .iter()
+                .flat_map(|s| s.parse.stmts.iter().map(|s| s.range))
+                .collect::<Vec<_>>(),
+            expected.stmts.iter().map(|s| s.range).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_stream_unterminated() {
+        let mut stream = StatementStream::new("select 1; select 'abc".as_bytes());
+        assert!(stream.next().unwrap().is_ok());
+        assert!(stream.next().unwrap().is_err());
+        assert!(stream.next().is_none());
+    }
+
+    #[test]
+    fn test_stream_multibyte() {
+        let input = "select 'äöü';select 'ß';";
+        let statements = stream_with_chunk_size(input, 1);
+        assert_eq!(statements.len(), 2);
+        assert_eq!(&input[statements[1].range()], "select 'ß';");
+    }
+}
@@ -6,8 +6,68 @@
 //! The *real* implementation is in the (language-agnostic) `cstree` crate, this
 //! module just wraps its API.
 
+use std::fmt::Write;
+
+use cstree::syntax::ResolvedNode;
+use cstree::util::NodeOrToken;
+
 use crate::codegen::SyntaxKind;
 
 pub type SyntaxNode = cstree::syntax::SyntaxNode<SyntaxKind>;
 pub type SyntaxToken = cstree::syntax::SyntaxToken<SyntaxKind>;
 pub type SyntaxElement = cstree::syntax::SyntaxElement<SyntaxKind>;
+
+/// Returns the tree of `node` with one element per line, indented by its depth, e.g.
+///
+/// ```text
+/// SourceFile@0..8
+///   SelectStmt@0..8
+///     Select@0..6 "select"
+///     Whitespace@6..7 " "
+///     ...
+/// ```
+///
+/// The text of tokens is escaped, so that whitespace and newlines are visible.
+pub fn dump_cst(node: &ResolvedNode<SyntaxKind>) -> String {
+    let mut dump = String::new();
+    write_node(&mut dump, node, 0);
+    dump
+}
+
+fn write_node(dump: &mut String, node: &ResolvedNode<SyntaxKind>, depth: usize) {
+    let indent = "  ".repeat(depth);
+    writeln!(dump, "{}{:?}@{:?}", indent, node.kind(), node.text_range()).unwrap();
+    for child in node.children_with_tokens() {
+        match child {
+            NodeOrToken::Node(n) => write_node(dump, n, depth + 1),
+            NodeOrToken::Token(t) => writeln!(
+                dump,
+                "{}  {:?}@{:?} {:?}",
+                indent,
+                t.kind(),
+                t.text_range(),
+                t.text()
+            )
+            .unwrap(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_source;
+
+    use super::*;
+
+    #[test]
+    fn test_dump_cst() {
+        let dump = dump_cst(&parse_source("select 1;\n").cst);
+        let lines = dump.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines[0], "SourceFile@0..10");
+        assert_eq!(lines[1], "  SelectStmt@0..9");
+        assert_eq!(lines[2], r#"    Select@0..6 "select""#);
+        assert!(lines.contains(&r#"    Whitespace@6..7 " ""#));
+        assert_eq!(lines.last(), Some(&r#"  Newline@9..10 "\n""#));
+    }
+}
@@ -0,0 +1 @@
+CREATE CAST (text AS mytype) WITH INOUT AS IMPLICIT;
@@ -0,0 +1 @@
+CREATE RULE notify_me AS ON UPDATE TO mytable DO ALSO NOTIFY mytable;
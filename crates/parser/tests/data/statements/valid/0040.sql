@@ -0,0 +1 @@
+CREATE OR REPLACE RULE protect AS ON DELETE TO accounts WHERE old.locked DO INSTEAD NOTHING;
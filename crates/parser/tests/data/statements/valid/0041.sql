@@ -0,0 +1 @@
+CREATE RULE log_insert AS ON INSERT TO shoe DO INSTEAD INSERT INTO shoe_log VALUES (new.sl_name);
@@ -0,0 +1 @@
+SELECT city, count(*) FROM weather GROUP BY city HAVING count(*) > 1;
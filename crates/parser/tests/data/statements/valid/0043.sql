@@ -0,0 +1 @@
+SELECT * FROM weather ORDER BY city LIMIT 10 OFFSET 5;
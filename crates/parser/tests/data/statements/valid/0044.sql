@@ -0,0 +1 @@
+INSERT INTO weather (city) VALUES ('Berlin') RETURNING id;
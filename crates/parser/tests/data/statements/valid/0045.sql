@@ -0,0 +1,5 @@
+ALTER TABLE products
+    ADD COLUMN discount numeric,
+    DROP COLUMN description,
+    ALTER COLUMN price TYPE numeric(10,2),
+    ALTER COLUMN name SET NOT NULL;
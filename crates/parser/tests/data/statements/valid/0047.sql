@@ -0,0 +1,2 @@
+LOCK TABLE orders, app.items IN SHARE UPDATE EXCLUSIVE MODE NOWAIT;
+LOCK contact;
@@ -0,0 +1,3 @@
+ALTER TABLE contact ADD CONSTRAINT contact_org_fkey FOREIGN KEY (org) REFERENCES org (id) DEFERRABLE INITIALLY DEFERRED;
+SET CONSTRAINTS contact_org_fkey, app.item_order_fkey IMMEDIATE;
+SET CONSTRAINTS ALL DEFERRED;
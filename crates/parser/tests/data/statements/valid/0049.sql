@@ -0,0 +1,5 @@
+CREATE TABLE events (id bigint, payload jsonb) USING columnar;
+CLUSTER events USING events_pkey;
+CLUSTER VERBOSE events;
+ALTER TABLE events CLUSTER ON events_created_idx;
+ALTER TABLE events SET WITHOUT CLUSTER, SET ACCESS METHOD heap;
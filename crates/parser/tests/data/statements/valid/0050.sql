@@ -0,0 +1,6 @@
+COMMENT ON TABLE contact IS 'The people we talk to';
+COMMENT ON COLUMN contact.email IS NULL;
+COMMENT ON FUNCTION full_name(contact) IS 'The name to greet the contact with';
+COMMENT ON CONSTRAINT contact_pkey ON contact IS 'One row per person';
+SECURITY LABEL FOR selinux ON TABLE contact IS 'system_u:object_r:sepgsql_table_t:s0';
+SECURITY LABEL ON SCHEMA app IS NULL;
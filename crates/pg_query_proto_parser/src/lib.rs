@@ -5,5 +5,5 @@
 mod proto_file;
 mod proto_parser;
 
-pub use crate::proto_file::{Field, FieldType, Node, ProtoFile, Token};
+pub use crate::proto_file::{Enum, EnumValue, Field, FieldType, Node, ProtoFile, Token};
 pub use crate::proto_parser::ProtoParser;
@@ -29,6 +29,21 @@ pub struct Token {
     pub value: i32,
 }
 
+/// A value of a libg_query enum
+#[derive(Debug)]
+pub struct EnumValue {
+    pub name: String,
+    pub value: i32,
+}
+
+/// A libg_query enum, e.g. the type of the `contype` field of `Constraint`
+#[derive(Debug)]
+pub struct Enum {
+    /// The name as in the proto file, which is also the `node_name` of the fields of this type
+    pub name: String,
+    pub values: Vec<EnumValue>,
+}
+
 /// A libg_query field
 #[derive(Debug)]
 pub struct Field {
@@ -51,10 +66,16 @@ pub struct Node {
 pub struct ProtoFile {
     pub tokens: Vec<Token>,
     pub nodes: Vec<Node>,
+    /// All enums except for the tokens
+    pub enums: Vec<Enum>,
 }
 
 impl ProtoFile {
     pub fn node(&self, name: &str) -> Option<&Node> {
         self.nodes.iter().find(|n| n.name == name)
     }
+
+    pub fn enum_type(&self, name: &str) -> Option<&Enum> {
+        self.enums.iter().find(|e| e.name == name)
+    }
 }
@@ -3,7 +3,7 @@ use protobuf::descriptor::{field_descriptor_proto::Label, FileDescriptorProto};
 use protobuf_parse::Parser;
 use std::path::Path;
 
-use crate::proto_file::{Field, FieldType, Node, ProtoFile, Token};
+use crate::proto_file::{Enum, EnumValue, Field, FieldType, Node, ProtoFile, Token};
 
 /// The parser for the libg_query proto file
 pub struct ProtoParser {
@@ -31,6 +31,7 @@ impl ProtoParser {
         ProtoFile {
             tokens: self.tokens(),
             nodes: self.nodes(),
+            enums: self.enums(),
         }
     }
 
@@ -50,6 +51,27 @@ impl ProtoParser {
             .collect()
     }
 
+    fn enums(&self) -> Vec<Enum> {
+        self.inner
+            .enum_type
+            .iter()
+            .filter(|e| e.name != Some("Token".into()))
+            .map(|e| Enum {
+                name: e.name.clone().unwrap(),
+                values: e
+                    .value
+                    .iter()
+                    .map(|v| EnumValue {
+                        // value names in proto are UPPERCASE_SNAKE_CASE, sometimes mixed with
+                        // UpperCamelCase as in `AT_AddColumn`
+                        name: v.name.clone().unwrap().to_case(Case::UpperCamel),
+                        value: v.number.unwrap(),
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
     fn get_enum_variant_name(&self, type_name: &str) -> Option<String> {
         let variant = self
             .inner
@@ -0,0 +1,83 @@
+use std::fs;
+use std::process::ExitCode;
+
+use analyser::bloat::{bloat_hints, BloatThresholds, TableBloat, TABLE_BLOAT_QUERY};
+use anyhow::Context;
+
+use crate::db::connect;
+use crate::flags;
+use crate::index::collect_sql_files;
+use crate::report::{print_diagnostic, print_syntax_error};
+
+impl flags::Bloat {
+    /// Prints a hint at every query on a bloated table, followed by a summary of these tables
+    pub(crate) fn run(self) -> anyhow::Result<ExitCode> {
+        let defaults = BloatThresholds::default();
+        let thresholds = BloatThresholds {
+            min_dead_ratio: self.min_dead_ratio.unwrap_or(defaults.min_dead_ratio),
+            min_dead_rows: self.min_dead_rows.unwrap_or(defaults.min_dead_rows),
+        };
+
+        let mut client = connect(self.connection.as_deref())?;
+        let tables = client
+            .query(TABLE_BLOAT_QUERY, &[])?
+            .into_iter()
+            .map(|row| TableBloat {
+                schema_name: row.get("schema_name"),
+                table_name: row.get("table_name"),
+                live_rows: row.get("live_rows"),
+                dead_rows: row.get("dead_rows"),
+                table_bytes: row.get("table_bytes"),
+                last_vacuum: row.get("last_vacuum"),
+            })
+            .collect::<Vec<_>>();
+        let bloated = tables
+            .iter()
+            .filter(|table| thresholds.is_bloated(table))
+            .collect::<Vec<_>>();
+
+        let mut paths = Vec::new();
+        collect_sql_files(&self.path, &mut paths)?;
+        paths.sort();
+
+        // the bloated tables that are queried, in the order of `bloated`
+        let mut queried = vec![false; bloated.len()];
+        for path in &paths {
+            let text = fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let parse = parser::parse_source(&text);
+            for error in &parse.errors {
+                print_syntax_error(path, &text, error);
+            }
+            for (idx, table) in bloated.iter().enumerate() {
+                let hints = bloat_hints(&parse.stmts, &[*table]);
+                queried[idx] |= !hints.is_empty();
+                for hint in &hints {
+                    print_diagnostic(path, &text, hint);
+                }
+            }
+        }
+
+        let queried = bloated
+            .iter()
+            .zip(queried)
+            .filter_map(|(table, queried)| queried.then_some(table))
+            .collect::<Vec<_>>();
+        if queried.is_empty() {
+            println!("no queried table is bloated");
+            return Ok(ExitCode::SUCCESS);
+        }
+        println!();
+        for table in queried {
+            println!(
+                "{}: {:.0}% dead rows, {} MB, last vacuumed {}",
+                table.qualified_name(),
+                table.dead_ratio() * 100.0,
+                table.table_bytes / (1024 * 1024),
+                table.last_vacuum.as_deref().unwrap_or("never")
+            );
+            println!("    {}", table.suggestion());
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
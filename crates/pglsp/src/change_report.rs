@@ -0,0 +1,115 @@
+use std::fmt::Write;
+use std::fs;
+use std::process::ExitCode;
+
+use analyser::schema_change::{schema_changes, ChangeAction, Risk, SchemaChange};
+use anyhow::Context;
+
+use crate::flags;
+use crate::git::{changed_lines, overlaps};
+use crate::index::collect_sql_files;
+use crate::report::{line_number, print_syntax_error};
+
+impl flags::ChangeReport {
+    /// Prints the report of all schema changes, or only of those in hunks that changed since
+    /// `--changed-from`
+    pub(crate) fn run(self) -> anyhow::Result<ExitCode> {
+        let mut paths = Vec::new();
+        collect_sql_files(&self.path, &mut paths)?;
+        paths.sort();
+
+        let changes = match &self.changed_from {
+            Some(git_ref) => Some(changed_lines(&self.path, git_ref)?),
+            None => None,
+        };
+
+        // the location of every change as `path:line`
+        let mut report = Vec::<(String, SchemaChange)>::new();
+        for path in &paths {
+            let relative = path.strip_prefix(&self.path).unwrap_or(path);
+            let lines = match &changes {
+                Some(changes) => match changes.get(relative) {
+                    Some(lines) => Some(lines.as_slice()),
+                    None => continue,
+                },
+                None => None,
+            };
+
+            let text = fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let parse = parser::parse_source(&text);
+            for error in &parse.errors {
+                print_syntax_error(path, &text, error);
+            }
+            report.extend(
+                schema_changes(&parse.stmts)
+                    .into_iter()
+                    .filter(|change| {
+                        lines
+                            .iter()
+                            .all(|lines| overlaps(&text, change.range, lines))
+                    })
+                    .map(|change| {
+                        let location = format!(
+                            "{}:{}",
+                            relative.display(),
+                            line_number(&text, change.range.start().into())
+                        );
+                        (location, change)
+                    }),
+            );
+        }
+
+        print!("{}", markdown(&report));
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn markdown(report: &[(String, SchemaChange)]) -> String {
+    let mut md = String::from("## Schema changes\n\n");
+    let Some(risk) = report.iter().map(|(_, change)| change.risk).max() else {
+        md.push_str("No schema changes.\n");
+        return md;
+    };
+
+    writeln!(md, "Estimated risk: **{}**\n", risk).unwrap();
+    md.push_str("| Change | Object | Lock | Risk | Location |\n");
+    md.push_str("| --- | --- | --- | --- | --- |\n");
+    for (location, change) in report {
+        let action = match change.action {
+            ChangeAction::Added => "added",
+            ChangeAction::Dropped => "dropped",
+            ChangeAction::Altered => "altered",
+            ChangeAction::Locked => "locked",
+        };
+        let lock = change
+            .lock
+            .map_or("none".to_string(), |lock| lock.to_string());
+        writeln!(
+            md,
+            "| {} | `{}` | {} | {} | `{}` |",
+            action,
+            change.object.replace('|', "\\|"),
+            lock,
+            change.risk,
+            location
+        )
+        .unwrap();
+    }
+
+    let reasons = report
+        .iter()
+        .filter(|(_, change)| change.risk > Risk::Low)
+        .flat_map(|(location, change)| {
+            change
+                .reasons
+                .iter()
+                .map(move |reason| format!("- `{}`: {}\n", location, reason))
+        })
+        .collect::<String>();
+    if !reasons.is_empty() {
+        md.push_str("\n### Risks\n\n");
+        md.push_str(&reasons);
+    }
+    md
+}
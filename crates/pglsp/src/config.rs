@@ -0,0 +1,125 @@
+//! The `pglsp.toml` of the current directory, which the language server reads from the root of
+//! the workspace.
+
+use std::fs;
+use std::io::ErrorKind;
+
+use analyser::policy::{DeniedStatement, Policy};
+use analyser::spelling::Dictionary;
+use analyser::{rule_group, LintConfig, RuleGroup};
+use anyhow::{bail, Context};
+use workspace::{Config, Lint, CONFIG_FILE};
+
+/// Reads `pglsp.toml` from the current directory, or returns the default config if there is none
+pub(crate) fn load_config() -> anyhow::Result<Config> {
+    let text = match fs::read_to_string(CONFIG_FILE) {
+        Ok(text) => text,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Config::default()),
+        Err(err) => return Err(err).with_context(|| format!("failed to read {}", CONFIG_FILE)),
+    };
+    Config::parse(&text).with_context(|| format!("failed to parse {}", CONFIG_FILE))
+}
+
+/// Returns the policy of the `[policy]` section of `config`. Unlike the language server, an
+/// unknown statement in its `deny` list is an error, since the policy would not be enforced.
+pub(crate) fn policy(config: &Config) -> anyhow::Result<Policy> {
+    let policy = &config.policy;
+    let denied = policy
+        .deny
+        .iter()
+        .map(|name| match DeniedStatement::from_name(name) {
+            Some(statement) => Ok(statement),
+            None => bail!(
+                "unknown statement `{}` in the policy of {}",
+                name,
+                CONFIG_FILE
+            ),
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(Policy {
+        denied,
+        concurrent_index_tables: policy.concurrent_index_tables.clone(),
+    })
+}
+
+/// Returns the lint rules of the `[lint]` section of `config`, with the rule `groups` and without
+/// the `disabled_rules` of the command line
+pub(crate) fn lint_config(
+    config: &Config,
+    groups: &[String],
+    disabled_rules: &[String],
+) -> anyhow::Result<LintConfig> {
+    let mut lint = config.lint.clone();
+    lint.groups.extend(groups.iter().cloned());
+    lint.disabled_rules.extend(disabled_rules.iter().cloned());
+    lint_rules(&lint)
+}
+
+/// Returns the lint rules of `lint`. Unlike the language server, an unknown rule group or rule is
+/// an error, since the rules would not run as intended.
+fn lint_rules(lint: &Lint) -> anyhow::Result<LintConfig> {
+    if let Some(rule) = lint.disabled_rules.iter().find(|r| rule_group(r).is_none()) {
+        bail!("unknown lint rule `{}`", rule);
+    }
+    let mut config = LintConfig {
+        disabled_rules: lint.disabled_rules.clone(),
+        ..LintConfig::default()
+    };
+    for name in &lint.groups {
+        let Some(group) = RuleGroup::from_name(name) else {
+            bail!("unknown rule group `{}`", name);
+        };
+        config = config.with_group(group);
+    }
+    Ok(config)
+}
+
+/// Returns the dictionary of the `[spelling]` section of `config`, or `None` if the spell check
+/// is off
+pub(crate) fn dictionary(config: &Config) -> anyhow::Result<Option<Dictionary>> {
+    let spelling = &config.spelling;
+    if !spelling.enabled {
+        return Ok(None);
+    }
+    let mut dictionary = Dictionary::default();
+    for path in &spelling.dictionaries {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("failed to read the dictionary {}", path.display()))?;
+        dictionary.add_words(&text);
+    }
+    spelling
+        .words
+        .iter()
+        .for_each(|word| dictionary.add_word(word));
+    Ok(Some(dictionary))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint_config() {
+        let lint = Lint {
+            groups: vec!["replication".to_string()],
+            disabled_rules: vec!["truncate-table".to_string()],
+        };
+        assert_eq!(
+            lint_rules(&lint).unwrap(),
+            LintConfig {
+                groups: vec![RuleGroup::Recommended, RuleGroup::Replication],
+                disabled_rules: vec!["truncate-table".to_string()],
+            }
+        );
+        assert!(lint_rules(&Lint {
+            groups: vec!["replicaton".to_string()],
+            disabled_rules: Vec::new(),
+        })
+        .is_err());
+        assert!(lint_rules(&Lint {
+            groups: Vec::new(),
+            disabled_rules: vec!["prefer-txt".to_string()],
+        })
+        .is_err());
+    }
+}
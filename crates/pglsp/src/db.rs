@@ -0,0 +1,83 @@
+//! Loading of catalog information from a live database.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use analyser::{
+    CatalogColumn, CatalogConstraint, CatalogIndex, Schema, SCHEMA_COLUMNS_QUERY,
+    SCHEMA_CONSTRAINTS_QUERY, SCHEMA_INDEXES_QUERY,
+};
+use anyhow::{bail, Context};
+use postgres::{Client, NoTls};
+
+/// Set by `--offline`, after which no connection is opened
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Forbids all connections for the rest of the process
+pub(crate) fn set_offline() {
+    OFFLINE.store(true, Ordering::SeqCst);
+}
+
+pub(crate) fn is_offline() -> bool {
+    OFFLINE.load(Ordering::SeqCst)
+}
+
+/// Connects to the database at `url`, falling back to `$DATABASE_URL`. Fails with `--offline`.
+pub(crate) fn connect(url: Option<&str>) -> anyhow::Result<Client> {
+    if is_offline() {
+        bail!("not connecting to the database with --offline");
+    }
+    let url = match url {
+        Some(url) => url.to_string(),
+        None => env::var("DATABASE_URL")
+            .context("no connection string given with --connection or $DATABASE_URL")?,
+    };
+    Client::connect(&url, NoTls).context("failed to connect to the database")
+}
+
+/// Loads the tables of all `schemas`. Schemas without any table are not part of the result.
+pub(crate) fn load_schemas(
+    client: &mut Client,
+    schemas: &[String],
+) -> anyhow::Result<BTreeMap<String, Schema>> {
+    let columns = client
+        .query(SCHEMA_COLUMNS_QUERY, &[&schemas])?
+        .into_iter()
+        .map(|row| CatalogColumn {
+            schema_name: row.get("schema_name"),
+            table_name: row.get("table_name"),
+            column_name: row.get("column_name"),
+            data_type: row.get("data_type"),
+            not_null: row.get("not_null"),
+            default_expr: row.get("default_expr"),
+            access_method: row.get("access_method"),
+            owner: row.get("owner"),
+            owned_sequence: row.get("owned_sequence"),
+            partition_key: row.get("partition_key"),
+            fillfactor: row.get("fillfactor"),
+        })
+        .collect();
+    let indexes = client
+        .query(SCHEMA_INDEXES_QUERY, &[&schemas])?
+        .into_iter()
+        .map(|row| CatalogIndex {
+            schema_name: row.get("schema_name"),
+            table_name: row.get("table_name"),
+            index_name: row.get("index_name"),
+            definition: row.get("definition"),
+            is_clustered: row.get("is_clustered"),
+        })
+        .collect();
+    let constraints = client
+        .query(SCHEMA_CONSTRAINTS_QUERY, &[&schemas])?
+        .into_iter()
+        .map(|row| CatalogConstraint {
+            schema_name: row.get("schema_name"),
+            table_name: row.get("table_name"),
+            constraint_name: row.get("constraint_name"),
+            definition: row.get("definition"),
+        })
+        .collect();
+    Ok(Schema::from_catalog(columns, indexes, constraints))
+}
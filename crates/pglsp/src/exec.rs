@@ -0,0 +1,450 @@
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::process::ExitCode;
+use std::thread;
+use std::time::Duration;
+
+use analyser::concurrent_index::{
+    check_concurrent_indexes, concurrent_index_table, INVALID_INDEXES_QUERY,
+};
+use analyser::execution_error::{
+    execution_error_diagnostic, ErrorPosition, ServerError, SqlStateMapping,
+};
+use analyser::policy::check_policy;
+use anyhow::Context;
+use parser::make::quote_literal;
+use parser::{RawStmt, SyntaxKind};
+use postgres::error::ErrorPosition as DbErrorPosition;
+use postgres::error::SqlState;
+use postgres::{Client, Transaction};
+
+use crate::config::{load_config, policy};
+use crate::db::connect;
+use crate::flags;
+use crate::report::{line_number, print_diagnostic, print_syntax_error};
+
+impl flags::Exec {
+    /// Executes all statements of the file in a single transaction and reports the first one that
+    /// fails at the range the server pointed at. The transaction is rolled back unless `--commit`
+    /// is given. Nothing is executed if a statement violates the policy of `pglsp.toml`.
+    ///
+    /// `CREATE INDEX CONCURRENTLY` cannot run in a transaction, so it is only executed with
+    /// `--commit`. The statements before it are committed first, and the index is checked for
+    /// validity afterwards.
+    pub(crate) fn run(self) -> anyhow::Result<ExitCode> {
+        let text = fs::read_to_string(&self.path)
+            .with_context(|| format!("failed to read {}", self.path.display()))?;
+
+        let parse = parser::parse_source(&text);
+        if !parse.errors.is_empty() {
+            for error in &parse.errors {
+                print_syntax_error(&self.path, &text, error);
+            }
+            return Ok(ExitCode::FAILURE);
+        }
+        // the policy of `pglsp.toml` is enforced before anything is executed
+        let mut diagnostics = check_policy(&parse.stmts, &policy(&load_config()?)?);
+        diagnostics.extend(check_concurrent_indexes(&parse.stmts));
+        if !diagnostics.is_empty() {
+            for d in &diagnostics {
+                print_diagnostic(&self.path, &text, d);
+            }
+            return Ok(ExitCode::FAILURE);
+        }
+        let concurrent = parse
+            .stmts
+            .iter()
+            .filter(|stmt| concurrent_index_table(&stmt.stmt).is_some())
+            .collect::<Vec<_>>();
+        if !self.commit && !concurrent.is_empty() {
+            for stmt in concurrent {
+                println!(
+                    "{}:{}: error: CREATE INDEX CONCURRENTLY cannot run inside the transaction \
+                    that is rolled back, run the file with --commit",
+                    self.path.display(),
+                    line_number(&text, stmt.range.start().into())
+                );
+            }
+            return Ok(ExitCode::FAILURE);
+        }
+
+        let mut client = connect(self.connection.as_deref())?;
+        for step in steps(&parse.stmts) {
+            let succeeded = match &step {
+                Step::Transaction(range) => {
+                    self.execute_in_transaction(&mut client, &text, &parse.stmts[range.clone()])?
+                }
+                Step::ConcurrentIndex(idx, table) => {
+                    self.create_index_concurrently(&mut client, &text, &parse.stmts[*idx], table)?
+                }
+            };
+            if !succeeded {
+                // the steps before the failed one have been committed
+                self.print_committed(&text, &parse.stmts, step.start());
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+
+        println!("executed {} statement(s)", parse.stmts.len());
+        Ok(ExitCode::SUCCESS)
+    }
+
+    /// Tells that the statements before `committed` remain committed after a failure
+    fn print_committed(&self, text: &str, stmts: &[RawStmt], committed: usize) {
+        if committed > 0 {
+            println!(
+                "{}:{}: note: the statements before this line have been committed",
+                self.path.display(),
+                line_number(text, stmts[committed].range.start().into())
+            );
+        }
+    }
+
+    /// Executes `stmts` in a transaction that is committed if `--commit` is given. If a statement
+    /// runs into the lock timeout, the whole transaction is retried after a backoff, so that the
+    /// locks of the statements before it are not held while waiting. Returns false if a statement
+    /// fails, after reporting it.
+    fn execute_in_transaction(
+        &self,
+        client: &mut Client,
+        text: &str,
+        stmts: &[RawStmt],
+    ) -> anyhow::Result<bool> {
+        let mut retries = Retries::new(self.lock_retries);
+        loop {
+            let mut transaction = client.transaction()?;
+            let failed = stmts.iter().find_map(|stmt| {
+                self.execute_with_timeouts(&mut transaction, text, stmt)
+                    .err()
+                    .map(|err| (stmt, err))
+            });
+            let Some((stmt, err)) = failed else {
+                if self.commit {
+                    transaction.commit()?;
+                } else {
+                    transaction.rollback()?;
+                }
+                return Ok(true);
+            };
+            transaction.rollback()?;
+
+            if is_lock_timeout(err.code()) && retries.retry() {
+                let backoff = retries.backoff();
+                println!(
+                    "{}:{}: lock timeout, retrying the transaction in {}s ({})",
+                    self.path.display(),
+                    line_number(text, stmt.range.start().into()),
+                    backoff.as_secs(),
+                    retries
+                );
+                thread::sleep(backoff);
+                continue;
+            }
+            report_error(&self.path, text, stmt, err)?;
+            return Ok(false);
+        }
+    }
+
+    /// Executes `stmt`, with the timeouts given by `--lock-timeout` and `--statement-timeout` if
+    /// it changes the schema
+    fn execute_with_timeouts(
+        &self,
+        transaction: &mut Transaction<'_>,
+        text: &str,
+        stmt: &RawStmt,
+    ) -> Result<(), postgres::Error> {
+        let timeouts = [
+            ("lock_timeout", &self.lock_timeout),
+            ("statement_timeout", &self.statement_timeout),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value.as_ref()?)))
+        .collect::<Vec<_>>();
+        if timeouts.is_empty() || !SyntaxKind::from(&stmt.stmt).is_ddl_stmt() {
+            return transaction.batch_execute(&text[stmt.range]);
+        }
+
+        // the following statements, e.g. backfills, run with the timeouts from before again,
+        // including those that the migration set itself
+        let mut previous = Vec::new();
+        for (name, _) in &timeouts {
+            let row = transaction.query_one("SELECT current_setting($1)", &[name])?;
+            previous.push((*name, row.get::<_, String>(0)));
+        }
+        transaction.batch_execute(&set_local(timeouts.iter().map(|(n, v)| (*n, v.as_str()))))?;
+        transaction.batch_execute(&text[stmt.range])?;
+        transaction.batch_execute(&set_local(previous.iter().map(|(n, v)| (*n, v.as_str()))))
+    }
+
+    /// Executes a `CREATE INDEX CONCURRENTLY` statement on `table` outside of a transaction.
+    /// Invalid indexes that the statement leaves behind are dropped and the statement is retried
+    /// up to `--retries` times. Returns false if the index could not be created, after reporting
+    /// the steps to clean up.
+    fn create_index_concurrently(
+        &self,
+        client: &mut Client,
+        text: &str,
+        stmt: &RawStmt,
+        table: &str,
+    ) -> anyhow::Result<bool> {
+        // indexes that were invalid before are not ours to drop
+        let invalid_before = invalid_indexes(client, table)?;
+        let line = line_number(text, stmt.range.start().into());
+        let mut retries = Retries::new(self.retries);
+        loop {
+            let succeeded = match client.batch_execute(&text[stmt.range]) {
+                Ok(()) => true,
+                Err(err) => {
+                    report_error(&self.path, text, stmt, err)?;
+                    false
+                }
+            };
+            let invalid = invalid_indexes(client, table)?
+                .into_iter()
+                .filter(|index| !invalid_before.contains(index))
+                .collect::<Vec<_>>();
+            if invalid.is_empty() {
+                // without an invalid index there is nothing to clean up, and a retry would fail
+                // the same way
+                return Ok(succeeded);
+            }
+
+            if retries.retry() {
+                for index in &invalid {
+                    client.batch_execute(&format!("DROP INDEX CONCURRENTLY {}", index))?;
+                    println!(
+                        "{}:{}: dropped invalid index {}, retrying ({})",
+                        self.path.display(),
+                        line,
+                        index,
+                        retries
+                    );
+                }
+                continue;
+            }
+
+            println!(
+                "{}:{}: error: CREATE INDEX CONCURRENTLY left invalid index(es) behind",
+                self.path.display(),
+                line
+            );
+            println!("to clean up, drop them and run the statement again:");
+            for index in &invalid {
+                println!("    DROP INDEX CONCURRENTLY IF EXISTS {};", index);
+            }
+            println!("    {};", text[stmt.range].trim_end_matches(';'));
+            return Ok(false);
+        }
+    }
+}
+
+/// A part of a file that is executed on its own
+#[derive(Debug, PartialEq, Eq)]
+enum Step {
+    /// The statements in the range, which are executed in a transaction
+    Transaction(Range<usize>),
+    /// The `CREATE INDEX CONCURRENTLY` statement at the index, which is executed outside of a
+    /// transaction, with the table it indexes
+    ConcurrentIndex(usize, String),
+}
+
+impl Step {
+    /// Returns the index of the first statement of the step. If the step fails, the statements
+    /// before it remain committed.
+    fn start(&self) -> usize {
+        match self {
+            Step::Transaction(range) => range.start,
+            Step::ConcurrentIndex(idx, _) => *idx,
+        }
+    }
+}
+
+/// Splits `stmts` into the steps that execute them, i.e. transactions between the
+/// `CREATE INDEX CONCURRENTLY` statements
+fn steps(stmts: &[RawStmt]) -> Vec<Step> {
+    let mut steps = Vec::new();
+    let mut start = 0;
+    for (idx, stmt) in stmts.iter().enumerate() {
+        let Some(table) = concurrent_index_table(&stmt.stmt) else {
+            continue;
+        };
+        if start < idx {
+            steps.push(Step::Transaction(start..idx));
+        }
+        steps.push(Step::ConcurrentIndex(idx, table));
+        start = idx + 1;
+    }
+    if start < stmts.len() {
+        steps.push(Step::Transaction(start..stmts.len()));
+    }
+    steps
+}
+
+/// Counts the retries of a step, up to `--retries` or `--lock-retries`
+#[derive(Debug)]
+struct Retries {
+    attempt: usize,
+    max: usize,
+}
+
+impl Retries {
+    fn new(max: Option<usize>) -> Self {
+        Retries {
+            attempt: 0,
+            max: max.unwrap_or(0),
+        }
+    }
+
+    /// Counts another retry, or returns false if all retries have been used up
+    fn retry(&mut self) -> bool {
+        if self.attempt >= self.max {
+            return false;
+        }
+        self.attempt += 1;
+        true
+    }
+
+    /// Returns the backoff before the current retry, which doubles with every retry up to a minute
+    fn backoff(&self) -> Duration {
+        Duration::from_secs(1 << self.attempt.min(6))
+    }
+}
+
+impl fmt::Display for Retries {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.attempt, self.max)
+    }
+}
+
+/// Returns true if a statement failed with `code` since it ran into the lock timeout, in which
+/// case its transaction is retried
+fn is_lock_timeout(code: Option<&SqlState>) -> bool {
+    code == Some(&SqlState::LOCK_NOT_AVAILABLE)
+}
+
+/// Returns the qualified names of the invalid indexes of `table`
+fn invalid_indexes(client: &mut Client, table: &str) -> anyhow::Result<Vec<String>> {
+    Ok(client
+        .query(INVALID_INDEXES_QUERY, &[&table])?
+        .into_iter()
+        .map(|row| row.get("index_name"))
+        .collect())
+}
+
+/// Returns the `SET LOCAL` statements that set the settings to their values until the end of the
+/// transaction
+fn set_local<'a>(settings: impl Iterator<Item = (&'a str, &'a str)>) -> String {
+    settings
+        .map(|(name, value)| format!("SET LOCAL {} = {};", name, quote_literal(value)))
+        .collect()
+}
+
+/// Reports the error of a failed statement at the range the server pointed at. Errors that did
+/// not come from the server are returned.
+fn report_error(
+    path: &Path,
+    text: &str,
+    stmt: &RawStmt,
+    err: postgres::Error,
+) -> anyhow::Result<()> {
+    let Some(db_error) = err.as_db_error() else {
+        return Err(err).context("failed to execute statement");
+    };
+    let error = ServerError {
+        code: db_error.code().code().to_string(),
+        message: db_error.message().to_string(),
+        detail: db_error.detail().map(|d| d.to_string()),
+        hint: db_error.hint().map(|h| h.to_string()),
+        position: db_error.position().map(|p| match p {
+            DbErrorPosition::Original(position) => ErrorPosition::Original(*position),
+            DbErrorPosition::Internal { position, query } => ErrorPosition::Internal {
+                position: *position,
+                query: query.clone(),
+            },
+        }),
+    };
+    print_diagnostic(
+        path,
+        text,
+        &execution_error_diagnostic(stmt, text, &error, &SqlStateMapping::default()),
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps() {
+        let parse = parser::parse_source(
+            "create table contact (id int, name text);
+            insert into contact values (1, 'a');
+            create index concurrently contact_id on contact (id);
+            update contact set name = 'b';
+            create index concurrently contact_name on app.contact (name);
+            create index concurrently \"Contact\" on \"Contact\" (id);",
+        );
+        let steps = steps(&parse.stmts);
+        assert_eq!(
+            steps,
+            [
+                Step::Transaction(0..2),
+                Step::ConcurrentIndex(2, "contact".to_string()),
+                Step::Transaction(3..4),
+                Step::ConcurrentIndex(4, "app.contact".to_string()),
+                Step::ConcurrentIndex(5, "\"Contact\"".to_string()),
+            ]
+        );
+        // a failed step leaves the statements before it committed
+        assert_eq!(
+            steps.iter().map(Step::start).collect::<Vec<_>>(),
+            [0, 2, 3, 4, 5]
+        );
+    }
+
+    #[test]
+    fn test_steps_without_concurrent_indexes() {
+        let parse = parser::parse_source("select 1; create index contact_id on contact (id);");
+        assert_eq!(steps(&parse.stmts), [Step::Transaction(0..2)]);
+        assert!(steps(&[]).is_empty());
+
+        let parse = parser::parse_source("create index concurrently contact_id on contact (id);");
+        assert_eq!(
+            steps(&parse.stmts),
+            [Step::ConcurrentIndex(0, "contact".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_retries() {
+        let mut retries = Retries::new(Some(8));
+        let backoffs = std::iter::from_fn(|| retries.retry().then(|| retries.backoff().as_secs()))
+            .collect::<Vec<_>>();
+        assert_eq!(backoffs, [2, 4, 8, 16, 32, 64, 64, 64]);
+        assert_eq!(retries.to_string(), "8/8");
+
+        // without the flag, nothing is retried
+        let mut retries = Retries::new(None);
+        assert!(!retries.retry());
+        assert_eq!(retries.to_string(), "0/0");
+    }
+
+    #[test]
+    fn test_is_lock_timeout() {
+        assert!(is_lock_timeout(Some(&SqlState::LOCK_NOT_AVAILABLE)));
+        assert!(!is_lock_timeout(Some(&SqlState::QUERY_CANCELED)));
+        assert!(!is_lock_timeout(None));
+    }
+
+    #[test]
+    fn test_set_local() {
+        assert_eq!(set_local(std::iter::empty()), "");
+        assert_eq!(
+            set_local([("lock_timeout", "5s"), ("statement_timeout", "it's")].into_iter()),
+            "SET LOCAL lock_timeout = '5s';SET LOCAL statement_timeout = 'it''s';"
+        );
+    }
+}
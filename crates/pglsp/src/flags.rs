@@ -0,0 +1,341 @@
+#![allow(unreachable_pub)]
+
+use std::path::PathBuf;
+
+xflags::xflags! {
+    src "./src/flags.rs"
+
+    /// Command line tools for Postgres schemas and SQL files.
+    cmd pglsp {
+        /// Never connect to a database, e.g. in security-sensitive CI. Commands that need one fail,
+        /// and `restore preflight` skips the checks against the target database.
+        optional --offline
+        /// Color diagnostics by severity: `auto`, `always` or `never`. Defaults to `auto`, which
+        /// colors them if stdout is a terminal and `NO_COLOR` is not set.
+        optional --color when: String
+
+        /// Multi-tenant databases with one schema per tenant.
+        cmd tenants {
+            /// Verify that every tenant schema in a live database matches the template schema.
+            cmd check {
+                /// The connection string of the database. Defaults to `$DATABASE_URL`.
+                optional --connection url: String
+                /// The schema that all tenant schemas are compared with. Defaults to `tenant_template`.
+                optional --template name: String
+                /// A `LIKE` pattern that selects the tenant schemas. Defaults to all schemas.
+                optional --tenants pattern: String
+            }
+        }
+
+        /// Restore scripts such as plain-text dumps.
+        cmd restore {
+            /// Pre-flight a restore script: check its `SET` preamble, the roles it references and
+            /// the order in which it creates objects.
+            cmd preflight {
+                /// The restore script.
+                required path: PathBuf
+                /// The connection string of the target database. If given, roles are checked to
+                /// exist in it.
+                optional --connection url: String
+            }
+        }
+
+        /// Execute the statements of a file in a transaction against a live database and report
+        /// the statement that fails at the position given by the server. Files with statements
+        /// that the policy of `pglsp.toml` in the current directory denies are not executed.
+        cmd exec {
+            /// The file to execute.
+            required path: PathBuf
+            /// The connection string of the database. Defaults to `$DATABASE_URL`.
+            optional --connection url: String
+            /// Commit the transaction. By default, it is rolled back. Statements before each
+            /// CREATE INDEX CONCURRENTLY are committed separately, since it cannot run in a
+            /// transaction.
+            optional --commit
+            /// Drop the invalid index that a failed CREATE INDEX CONCURRENTLY leaves behind and
+            /// retry it up to the given number of times. Defaults to 0.
+            optional --retries count: usize
+            /// Set lock_timeout to the given value, e.g. `5s`, for every statement that changes
+            /// the schema.
+            optional --lock-timeout duration: String
+            /// Set statement_timeout to the given value for every statement that changes the
+            /// schema.
+            optional --statement-timeout duration: String
+            /// Retry a transaction whose statement ran into the lock timeout up to the given
+            /// number of times, waiting twice as long before every retry. Defaults to 0.
+            optional --lock-retries count: usize
+        }
+
+        /// Lint the SQL files of a directory. Migrations, i.e. files whose name starts with a
+        /// version, are checked against the schema that the migrations before them produce, and
+        /// all files against the lint rules, the policy and the spell check of `pglsp.toml` in the
+        /// current directory.
+        cmd lint check {
+            /// The directory that contains the SQL files, or `-` to check the statements of stdin.
+            required path: PathBuf
+            /// The directory of migrations whose schema the statements of stdin are checked
+            /// against. Only valid when the path is `-`.
+            optional --schema dir: PathBuf
+            /// Only report diagnostics of statements in hunks that changed since the given git
+            /// ref, and of statements in any file that use objects created by them.
+            optional --changed-from ref: String
+            /// Check the files against the schema as of the migration with the given version, and
+            /// ignore the migrations after it.
+            optional --at version: String
+            /// The format of the report: `text`, the default, or `junit` to print JUnit XML with a
+            /// test case per file, e.g. for the test report views of CI systems.
+            optional --output format: String
+            /// The least severe diagnostics that fail the run: `error`, the default, `warning`,
+            /// `info` or `hint`. All diagnostics are reported either way.
+            optional --error-on severity: String
+            /// Fail the run if more than the given number of warnings are reported.
+            optional --max-warnings count: usize
+            /// Only let diagnostics of the given category fail the run: `syntax`, `schema` for the
+            /// checks against the schema of the migrations, `read-only` for writes in read-only
+            /// directories, `policy` for the statements that the policy of `pglsp.toml` denies,
+            /// `spelling` for misspelled words, or the group of a lint rule, e.g. `recommended`.
+            /// Can be given several times. Defaults to all categories.
+            repeated --fail-category category: String
+            /// Report the statements that write in the files of the given directory, relative to
+            /// the linted directory, e.g. queries that are routed to replicas, in addition to the
+            /// `read_only` directories of `pglsp.toml`. Can be given several times.
+            repeated --read-only dir: PathBuf
+            /// Run the lint rules of the given group in addition to those that `pglsp.toml`
+            /// enables, e.g. `schema-design`. Can be given several times.
+            repeated --group group: String
+            /// Do not run the lint rule with the given name, e.g. `prefer-text`. Can be given
+            /// several times.
+            repeated --disable-rule rule: String
+        }
+
+        /// Print a Markdown report of the objects that the SQL files of a directory add, drop and
+        /// alter, with the locks they take and their estimated risk, e.g. to post it as a comment
+        /// on a pull request.
+        cmd change-report {
+            /// The directory that contains the SQL files.
+            required path: PathBuf
+            /// Only report statements in hunks that changed since the given git ref.
+            optional --changed-from ref: String
+        }
+
+        /// Print a report of the health of the project in a directory: the number of objects and
+        /// lint findings, and the tables without a primary key or row level security, the foreign
+        /// keys without an index and the columns without a comment in the schema that its
+        /// migrations produce, e.g. to track them over time.
+        cmd report {
+            /// The directory that contains the SQL files.
+            required path: PathBuf
+            /// The format of the report: `markdown`, the default, or `json`.
+            optional --format format: String
+        }
+
+        /// Write a code intelligence index with the definitions, references and hover texts of the
+        /// tables and columns in the SQL files of a directory.
+        cmd index {
+            /// The directory that contains the SQL files.
+            required path: PathBuf
+            /// The format of the index. Only `scip` is supported, which is also the default.
+            optional --format format: String
+            /// The file to write the index to. Defaults to `index.scip`.
+            optional --output output: PathBuf
+        }
+
+        /// Migrations, i.e. SQL files whose name starts with a version.
+        cmd migrate {
+            /// Replace a range of migrations with a single one that has the same effect on the
+            /// schema, and verify that it does.
+            cmd squash {
+                /// The directory that contains the migrations.
+                required path: PathBuf
+                /// The version of the first migration to squash. Defaults to the first migration.
+                optional --from version: String
+                /// The version of the last migration to squash. Defaults to the last migration.
+                optional --to version: String
+                /// The file to write the squashed migration to. Defaults to stdout.
+                optional --output output: PathBuf
+            }
+        }
+
+        /// Report the tables of a live database with many dead rows that the SQL files of a
+        /// directory query, together with the maintenance to run on them.
+        cmd bloat {
+            /// The directory that contains the SQL files.
+            required path: PathBuf
+            /// The connection string of the database. Defaults to `$DATABASE_URL`.
+            optional --connection url: String
+            /// The share of dead rows from which a table is reported. Defaults to 0.2.
+            optional --min-dead-ratio ratio: f64
+            /// The number of dead rows from which a table is reported. Defaults to 10000.
+            optional --min-dead-rows rows: i64
+        }
+
+        /// Parse a file and report its syntax errors.
+        cmd parse {
+            /// The file to parse, or `-` to parse stdin.
+            required path: PathBuf
+            /// Print the concrete syntax tree with the range of every node and the text of every
+            /// token, e.g. to attach it to a bug report.
+            optional --dump-cst
+        }
+    }
+}
+// generated start
+// The following code is generated by `xflags` macro.
+// Run `env UPDATE_XFLAGS=1 cargo build` to regenerate.
+#[derive(Debug)]
+pub struct Pglsp {
+    pub offline: bool,
+    pub color: Option<String>,
+    pub subcommand: PglspCmd,
+}
+
+#[derive(Debug)]
+pub enum PglspCmd {
+    Tenants(Tenants),
+    Restore(Restore),
+    Exec(Exec),
+    Lint(Lint),
+    ChangeReport(ChangeReport),
+    Report(Report),
+    Index(Index),
+    Migrate(Migrate),
+    Bloat(Bloat),
+    Parse(Parse),
+}
+
+#[derive(Debug)]
+pub struct Tenants {
+    pub subcommand: TenantsCmd,
+}
+
+#[derive(Debug)]
+pub enum TenantsCmd {
+    Check(Check),
+}
+
+#[derive(Debug)]
+pub struct Check {
+    pub connection: Option<String>,
+    pub template: Option<String>,
+    pub tenants: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Restore {
+    pub subcommand: RestoreCmd,
+}
+
+#[derive(Debug)]
+pub enum RestoreCmd {
+    Preflight(Preflight),
+}
+
+#[derive(Debug)]
+pub struct Preflight {
+    pub path: PathBuf,
+
+    pub connection: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Exec {
+    pub path: PathBuf,
+
+    pub connection: Option<String>,
+    pub commit: bool,
+    pub retries: Option<usize>,
+    pub lock_timeout: Option<String>,
+    pub statement_timeout: Option<String>,
+    pub lock_retries: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct Lint {
+    pub path: PathBuf,
+
+    pub schema: Option<PathBuf>,
+    pub changed_from: Option<String>,
+    pub at: Option<String>,
+    pub output: Option<String>,
+    pub error_on: Option<String>,
+    pub max_warnings: Option<usize>,
+    pub fail_category: Vec<String>,
+    pub read_only: Vec<PathBuf>,
+    pub group: Vec<String>,
+    pub disable_rule: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct ChangeReport {
+    pub path: PathBuf,
+
+    pub changed_from: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Report {
+    pub path: PathBuf,
+
+    pub format: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Index {
+    pub path: PathBuf,
+
+    pub format: Option<String>,
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct Migrate {
+    pub subcommand: MigrateCmd,
+}
+
+#[derive(Debug)]
+pub enum MigrateCmd {
+    Squash(Squash),
+}
+
+#[derive(Debug)]
+pub struct Squash {
+    pub path: PathBuf,
+
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct Bloat {
+    pub path: PathBuf,
+
+    pub connection: Option<String>,
+    pub min_dead_ratio: Option<f64>,
+    pub min_dead_rows: Option<i64>,
+}
+
+#[derive(Debug)]
+pub struct Parse {
+    pub path: PathBuf,
+
+    pub dump_cst: bool,
+}
+
+impl Pglsp {
+    #[allow(dead_code)]
+    pub fn from_env_or_exit() -> Self {
+        Self::from_env_or_exit_()
+    }
+
+    #[allow(dead_code)]
+    pub fn from_env() -> xflags::Result<Self> {
+        Self::from_env_()
+    }
+
+    #[allow(dead_code)]
+    pub fn from_vec(args: Vec<std::ffi::OsString>) -> xflags::Result<Self> {
+        Self::from_vec_(args)
+    }
+}
+// generated end
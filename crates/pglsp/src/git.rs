@@ -0,0 +1,147 @@
+//! Changes of a working tree relative to a git ref.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context};
+use parser::TextRange;
+
+use crate::report::line_number;
+
+/// Returns the lines of the files below `dir` that changed since `git_ref`, as 1-based ranges of
+/// lines keyed by the path relative to `dir`. Deleted lines are represented by the lines around
+/// them. Files that are not tracked by git are not part of the result.
+pub(crate) fn changed_lines(
+    dir: &Path,
+    git_ref: &str,
+) -> anyhow::Result<HashMap<PathBuf, Vec<Range<usize>>>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args([
+            "diff",
+            "--relative",
+            "--unified=0",
+            "--no-color",
+            "--no-ext-diff",
+        ])
+        .arg(git_ref)
+        .args(["--", "."])
+        .output()
+        .context("failed to run git")?;
+    if !output.status.success() {
+        bail!(
+            "git diff against {} failed: {}",
+            git_ref,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(parse_diff(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Returns true if the lines of `range` within `text` overlap any of the 1-based `lines`
+pub(crate) fn overlaps(text: &str, range: TextRange, lines: &[Range<usize>]) -> bool {
+    let first = line_number(text, range.start().into());
+    let last = line_number(text, range.end().into());
+    lines
+        .iter()
+        .any(|lines| lines.start <= last && first < lines.end)
+}
+
+fn parse_diff(diff: &str) -> HashMap<PathBuf, Vec<Range<usize>>> {
+    let mut changes = HashMap::<PathBuf, Vec<Range<usize>>>::new();
+    let mut current = None;
+    for line in diff.lines() {
+        if let Some(path) = line.strip_prefix("+++ ") {
+            // deleted files are diffed against /dev/null
+            current = path.strip_prefix("b/").map(PathBuf::from);
+        } else if let Some(hunk) = line.strip_prefix("@@ ") {
+            let (Some(path), Some(range)) = (&current, new_lines(hunk)) else {
+                continue;
+            };
+            changes.entry(path.clone()).or_default().push(range);
+        }
+    }
+    changes
+}
+
+/// Returns the lines of the new file that a hunk header such as `-3,2 +4,0 @@` covers
+fn new_lines(hunk: &str) -> Option<Range<usize>> {
+    let new = hunk.split(' ').find_map(|part| part.strip_prefix('+'))?;
+    let (start, count) = match new.split_once(',') {
+        Some((start, count)) => (start.parse::<usize>().ok()?, count.parse::<usize>().ok()?),
+        None => (new.parse::<usize>().ok()?, 1),
+    };
+    Some(if count == 0 {
+        // lines were deleted after `start`
+        start.max(1)..start + 2
+    } else {
+        start..start + count
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::TextSize;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_diff() {
+        let diff = "diff --git a/migrations/0001_init.sql b/migrations/0001_init.sql
+index 1111111..2222222 100644
+--- a/migrations/0001_init.sql
++++ b/migrations/0001_init.sql
+@@ -3 +3 @@ create table contact (
+-    name text
++    name text not null
+@@ -10,2 +10,0 @@ create index contact_name on contact (name);
+-drop table draft;
+-drop table audit;
+diff --git a/queries/old.sql b/queries/old.sql
+deleted file mode 100644
+--- a/queries/old.sql
++++ /dev/null
+@@ -1,2 +0,0 @@
+-select 1;
+-select 2;
+diff --git a/queries/new.sql b/queries/new.sql
+new file mode 100644
+--- /dev/null
++++ b/queries/new.sql
+@@ -0,0 +1,3 @@
++select 1;
++select 2;
++select 3;
+";
+        let changes = parse_diff(diff);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(
+            changes[&PathBuf::from("migrations/0001_init.sql")],
+            vec![3..4, 10..12]
+        );
+        assert_eq!(changes[&PathBuf::from("queries/new.sql")], vec![1..4]);
+    }
+
+    #[test]
+    fn test_new_lines() {
+        assert_eq!(new_lines("-3,2 +4,3 @@"), Some(4..7));
+        assert_eq!(new_lines("-3 +4 @@ select 1;"), Some(4..5));
+        // deleted lines are represented by the lines around them
+        assert_eq!(new_lines("-3,2 +2,0 @@"), Some(2..4));
+        assert_eq!(new_lines("-1,2 +0,0 @@"), Some(1..2));
+        assert_eq!(new_lines("-1 +x @@"), None);
+    }
+
+    #[test]
+    fn test_overlaps() {
+        let text = "select 1;\nselect 2;\nselect 3;\n";
+        let second = TextRange::at(TextSize::from(10), TextSize::from(9));
+        assert!(overlaps(text, second, &[2..3]));
+        assert!(overlaps(text, second, &[1..3]));
+        assert!(!overlaps(text, second, &[1..2, 3..4]));
+        assert!(!overlaps(text, second, &[]));
+    }
+}
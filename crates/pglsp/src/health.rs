@@ -0,0 +1,295 @@
+//! A report of the health of a project, e.g. to track the quality of its schema over time.
+//!
+//! The report counts the objects and the findings of the lint rules that `pglsp.toml` enables in
+//! the SQL files of a directory, and lists the weak spots of the schema that their migrations
+//! produce, see [`analyser::health`]. Without migrations, the schema of all files in the order of
+//! their paths is reported instead.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::process::ExitCode;
+
+use analyser::comments::commented_objects;
+use analyser::health::{schema_health, SchemaHealth};
+use analyser::migrations::MigrationState;
+use analyser::{lint_cancellable, Severity, TableFacts};
+use anyhow::{bail, Context};
+use parser::{Cancellation, RawStmt};
+
+use crate::config::{lint_config, load_config};
+use crate::flags;
+use crate::index::collect_sql_files;
+use crate::lint::check_migrations;
+use crate::migrate::version;
+use crate::report::severity_label;
+
+/// The severities in the order in which they are reported
+const SEVERITIES: [Severity; 4] = [
+    Severity::Error,
+    Severity::Warning,
+    Severity::Information,
+    Severity::Hint,
+];
+
+impl flags::Report {
+    /// Prints the report of the directory as Markdown or JSON
+    pub(crate) fn run(self) -> anyhow::Result<ExitCode> {
+        let json = match self.format.as_deref() {
+            None | Some("markdown") => false,
+            Some("json") => true,
+            Some(format) => bail!("unsupported report format `{}`", format),
+        };
+
+        let mut paths = Vec::new();
+        collect_sql_files(&self.path, &mut paths)?;
+        paths.sort();
+        let parses = paths
+            .iter()
+            .map(|path| {
+                let text = fs::read_to_string(path)
+                    .with_context(|| format!("failed to read {}", path.display()))?;
+                Ok(parser::parse_source(&text))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let lint_config = lint_config(&load_config()?, &[], &[])?;
+        let tables = TableFacts::collect(parses.iter().map(|parse| parse.stmts.as_slice()));
+        let mut findings = BTreeMap::<&'static str, usize>::new();
+        for parse in &parses {
+            *findings.entry(severity_label(Severity::Error)).or_default() += parse.errors.len();
+            let diagnostics =
+                lint_cancellable(&parse.stmts, &lint_config, &tables, &Cancellation::new())
+                    .unwrap_or_default();
+            for d in diagnostics {
+                *findings.entry(severity_label(d.severity)).or_default() += 1;
+            }
+        }
+        for d in check_migrations(&paths, &parses).iter().flatten() {
+            *findings.entry(severity_label(d.severity)).or_default() += 1;
+        }
+
+        let mut migrations = paths
+            .iter()
+            .zip(&parses)
+            .filter_map(|(path, parse)| Some((version(path)?, parse)))
+            .collect::<Vec<_>>();
+        migrations.sort_by_key(|(version, _)| *version);
+        let files = if migrations.is_empty() {
+            parses.iter().collect::<Vec<_>>()
+        } else {
+            migrations.into_iter().map(|(_, parse)| parse).collect()
+        };
+        let mut state = MigrationState::default();
+        for parse in &files {
+            state.replay(&parse.stmts);
+            state.end_session();
+        }
+        let stmts = files
+            .iter()
+            .map(|parse| parse.stmts.as_slice())
+            .collect::<Vec<&[RawStmt]>>();
+        let commented = parses
+            .iter()
+            .flat_map(|parse| commented_objects(&parse.cst, &parse.stmts))
+            .collect::<Vec<_>>();
+        let health = schema_health(&state, &stmts, &commented);
+
+        if json {
+            print!("{}", json_report(&findings, &health));
+        } else {
+            print!("{}", markdown(&findings, &health));
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// The number of findings of `severity`
+fn count(findings: &BTreeMap<&'static str, usize>, severity: Severity) -> usize {
+    findings.get(severity_label(severity)).copied().unwrap_or(0)
+}
+
+/// Returns the share of `part` in `total` in percent, or 100 if there is nothing
+fn percent(part: usize, total: usize) -> usize {
+    if total == 0 {
+        100
+    } else {
+        part * 100 / total
+    }
+}
+
+fn markdown(findings: &BTreeMap<&'static str, usize>, health: &SchemaHealth) -> String {
+    let mut md = String::from("## Project health\n\n");
+
+    md.push_str("| Objects | Count |\n| --- | --- |\n");
+    for (kind, count) in &health.object_counts {
+        writeln!(md, "| {} | {} |", kind, count).unwrap();
+    }
+
+    md.push_str("\n| Findings | Count |\n| --- | --- |\n");
+    for severity in SEVERITIES {
+        writeln!(
+            md,
+            "| {} | {} |",
+            severity_label(severity),
+            count(findings, severity)
+        )
+        .unwrap();
+    }
+
+    let tables = health.tables_with_row_security.len() + health.tables_without_row_security.len();
+    writeln!(
+        md,
+        "\nRow level security: **{}%** of {} table(s)",
+        percent(health.tables_with_row_security.len(), tables),
+        tables
+    )
+    .unwrap();
+
+    let sections = [
+        (
+            "Tables without a primary key",
+            &health.tables_without_primary_key,
+        ),
+        (
+            "Foreign keys without an index",
+            &health.unindexed_foreign_keys,
+        ),
+        (
+            "Tables without row level security",
+            &health.tables_without_row_security,
+        ),
+        (
+            "Tables with row level security but no policy",
+            &health.row_security_without_policy,
+        ),
+        ("Columns without a comment", &health.columns_without_comment),
+    ];
+    for (title, objects) in sections {
+        if objects.is_empty() {
+            continue;
+        }
+        writeln!(md, "\n### {} ({})\n", title, objects.len()).unwrap();
+        for object in objects {
+            writeln!(md, "- `{}`", object).unwrap();
+        }
+    }
+    md
+}
+
+fn json_report(findings: &BTreeMap<&'static str, usize>, health: &SchemaHealth) -> String {
+    let objects = health
+        .object_counts
+        .iter()
+        .map(|(kind, count)| format!("{}: {}", json_string(kind), count))
+        .collect::<Vec<_>>();
+    let findings = SEVERITIES
+        .iter()
+        .map(|severity| {
+            format!(
+                "{}: {}",
+                json_string(severity_label(*severity)),
+                count(findings, *severity)
+            )
+        })
+        .collect::<Vec<_>>();
+    let list = |names: &[String]| {
+        let names = names.iter().map(|n| json_string(n)).collect::<Vec<_>>();
+        format!("[{}]", names.join(", "))
+    };
+
+    let mut json = String::from("{\n");
+    writeln!(json, "  \"objects\": {{{}}},", objects.join(", ")).unwrap();
+    writeln!(json, "  \"findings\": {{{}}},", findings.join(", ")).unwrap();
+    let fields = [
+        (
+            "tablesWithoutPrimaryKey",
+            &health.tables_without_primary_key,
+        ),
+        ("unindexedForeignKeys", &health.unindexed_foreign_keys),
+        ("tablesWithRowSecurity", &health.tables_with_row_security),
+        (
+            "tablesWithoutRowSecurity",
+            &health.tables_without_row_security,
+        ),
+        (
+            "rowSecurityWithoutPolicy",
+            &health.row_security_without_policy,
+        ),
+        ("columnsWithoutComment", &health.columns_without_comment),
+    ];
+    let fields = fields
+        .iter()
+        .map(|(name, names)| format!("  {}: {}", json_string(name), list(names)))
+        .collect::<Vec<_>>();
+    json.push_str(&fields.join(",\n"));
+    json.push_str("\n}\n");
+    json
+}
+
+/// Returns `text` as a JSON string
+fn json_string(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + 2);
+    escaped.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if c.is_control() => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("public.contact"), "\"public.contact\"");
+        assert_eq!(
+            json_string("say \"hi\"\\\n\tnow"),
+            "\"say \\\"hi\\\"\\\\\\n\\u0009now\""
+        );
+    }
+
+    #[test]
+    fn test_json_report() {
+        let findings = BTreeMap::from([("error", 2), ("hint", 1)]);
+        let health = SchemaHealth {
+            object_counts: BTreeMap::from([("columns", 3), ("tables", 2)]),
+            tables_without_primary_key: vec!["public.\"Log\"".to_string()],
+            tables_with_row_security: vec!["public.contact".to_string()],
+            unindexed_foreign_keys: vec![
+                "public.orders.orders_contact_id_fkey (contact_id)".to_string()
+            ],
+            ..SchemaHealth::default()
+        };
+        assert_eq!(
+            json_report(&findings, &health),
+            r#"{
+  "objects": {"columns": 3, "tables": 2},
+  "findings": {"error": 2, "warning": 0, "info": 0, "hint": 1},
+  "tablesWithoutPrimaryKey": ["public.\"Log\""],
+  "unindexedForeignKeys": ["public.orders.orders_contact_id_fkey (contact_id)"],
+  "tablesWithRowSecurity": ["public.contact"],
+  "tablesWithoutRowSecurity": [],
+  "rowSecurityWithoutPolicy": [],
+  "columnsWithoutComment": []
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_json_report_without_objects() {
+        let report = json_report(&BTreeMap::new(), &SchemaHealth::default());
+        assert!(report.starts_with("{\n  \"objects\": {},\n"));
+        assert!(report
+            .contains("\"findings\": {\"error\": 0, \"warning\": 0, \"info\": 0, \"hint\": 0},"));
+    }
+}
@@ -0,0 +1,361 @@
+//! Writing of [SCIP](https://github.com/sourcegraph/scip) indexes.
+//!
+//! The index records the definitions and references of tables and columns, so that code
+//! intelligence platforms can navigate SQL files without running a language server. Symbols are
+//! derived from the monikers of the `analyser` crate, e.g. `scip-sql . . . public/contact#name.` for
+//! the column `name` of the table `public.contact`. Definitions carry the text of the statement that
+//! creates them as hover documentation.
+//!
+//! Only a handful of protobuf fields are needed, so the index is encoded by hand.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use analyser::moniker::{symbols, Symbol, SymbolKind};
+use anyhow::{bail, Context};
+use parser::{RawStmt, TextSize};
+
+use crate::flags;
+use crate::report::print_syntax_error;
+
+/// The scheme of all symbols in the index
+const SCHEME: &str = "scip-sql";
+
+/// `SymbolRole.Definition`
+const DEFINITION_ROLE: u64 = 1;
+
+/// `TextEncoding.UTF8` and `PositionEncoding.UTF8CodeUnitOffsetFromLineStart`
+const UTF8_ENCODING: u64 = 1;
+
+impl flags::Index {
+    /// Indexes all `.sql` files below the directory and writes the index to the output file
+    pub(crate) fn run(self) -> anyhow::Result<ExitCode> {
+        match self.format.as_deref() {
+            None | Some("scip") => {}
+            Some(format) => bail!("unsupported index format `{}`", format),
+        }
+
+        let mut paths = Vec::new();
+        collect_sql_files(&self.path, &mut paths)?;
+        paths.sort();
+
+        let mut index = Message::default();
+        index.message(1, metadata(&self.path)?);
+        for path in &paths {
+            let text = fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let relative_path = path
+                .strip_prefix(&self.path)
+                .unwrap_or(path)
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            index.message(2, document(path, &relative_path, &text));
+        }
+
+        let output = self.output.unwrap_or_else(|| PathBuf::from("index.scip"));
+        fs::write(&output, index.0)
+            .with_context(|| format!("failed to write {}", output.display()))?;
+        println!("indexed {} file(s) into {}", paths.len(), output.display());
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// Reads the file at `path`, or stdin if the path is `-`
+pub(crate) fn read_input(path: &Path) -> anyhow::Result<String> {
+    if path.as_os_str() == "-" {
+        let mut text = String::new();
+        io::stdin()
+            .read_to_string(&mut text)
+            .context("failed to read stdin")?;
+        return Ok(text);
+    }
+    fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))
+}
+
+/// Collects the `.sql` files below `dir`. Symlinks to files are collected, but symlinks to
+/// directories are not followed, so that a link to a parent directory does not recurse forever.
+pub(crate) fn collect_sql_files(dir: &Path, paths: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    let entries = fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            collect_sql_files(&path, paths)?;
+        } else if path.extension().is_some_and(|ext| ext == "sql") && path.is_file() {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn metadata(root: &Path) -> anyhow::Result<Message> {
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("failed to resolve {}", root.display()))?;
+
+    let mut tool_info = Message::default();
+    tool_info.string(1, env!("CARGO_PKG_NAME"));
+    tool_info.string(2, env!("CARGO_PKG_VERSION"));
+
+    let mut metadata = Message::default();
+    metadata.message(2, tool_info);
+    metadata.string(3, &format!("file://{}", root.display()));
+    metadata.varint(4, UTF8_ENCODING);
+    Ok(metadata)
+}
+
+fn document(path: &Path, relative_path: &str, text: &str) -> Message {
+    let parse = parser::parse_source(text);
+    for error in &parse.errors {
+        print_syntax_error(path, text, error);
+    }
+
+    let line_starts = line_starts(text);
+    let mut document = Message::default();
+    document.string(1, relative_path);
+
+    let mut definitions = BTreeMap::new();
+    for (range, symbol) in symbols(&parse.cst, &parse.stmts) {
+        let name = scip_symbol(&symbol);
+
+        let (start_line, start_column) = position(&line_starts, range.start().into());
+        let (end_line, end_column) = position(&line_starts, range.end().into());
+        let positions = if start_line == end_line {
+            vec![start_line, start_column, end_column]
+        } else {
+            vec![start_line, start_column, end_line, end_column]
+        };
+
+        let mut occurrence = Message::default();
+        occurrence.packed(1, &positions);
+        occurrence.string(2, &name);
+        if symbol.is_definition {
+            occurrence.varint(3, DEFINITION_ROLE);
+            if let Some(stmt) = defining_stmt(&parse.stmts, range.start()) {
+                definitions
+                    .entry(name)
+                    .or_insert_with(|| format!("```sql\n{}\n```", &text[stmt.range]));
+            }
+        }
+        document.message(2, occurrence);
+    }
+
+    for (name, documentation) in definitions {
+        let mut information = Message::default();
+        information.string(1, &name);
+        information.string(3, &documentation);
+        document.message(3, information);
+    }
+
+    document.string(4, "sql");
+    document.varint(6, UTF8_ENCODING);
+    document
+}
+
+/// Returns the SCIP symbol of `symbol`, whose identifier is a qualified name such as
+/// `public.contact.name`
+fn scip_symbol(symbol: &Symbol) -> String {
+    let mut parts = symbol.identifier.splitn(3, '.');
+    let schema = parts.next().unwrap_or_default();
+    let table = parts.next().unwrap_or_default();
+    let mut descriptors = format!("{}/{}#", escape(schema), escape(table));
+    if symbol.kind == SymbolKind::Column {
+        descriptors.push_str(&format!("{}.", escape(parts.next().unwrap_or_default())));
+    }
+    format!("{} . . . {}", SCHEME, descriptors)
+}
+
+/// Escapes `name` with backticks unless it only contains characters allowed in simple identifiers
+fn escape(name: &str) -> String {
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '+' | '-' | '$'))
+    {
+        name.to_string()
+    } else {
+        format!("`{}`", name.replace('`', "``"))
+    }
+}
+
+fn defining_stmt(stmts: &[RawStmt], offset: TextSize) -> Option<&RawStmt> {
+    stmts
+        .iter()
+        .find(|stmt| stmt.range.contains_inclusive(offset))
+}
+
+/// Returns the byte offsets at which the lines of `text` start
+fn line_starts(text: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect()
+}
+
+/// Returns the 0-based line and the byte offset within the line of `offset`
+fn position(line_starts: &[usize], offset: usize) -> (u64, u64) {
+    let line = line_starts.partition_point(|&start| start <= offset) - 1;
+    (line as u64, (offset - line_starts[line]) as u64)
+}
+
+/// An encoded protobuf message
+#[derive(Default)]
+struct Message(Vec<u8>);
+
+impl Message {
+    fn varint(&mut self, field: u32, value: u64) {
+        self.tag(field, 0);
+        write_varint(&mut self.0, value);
+    }
+
+    fn bytes(&mut self, field: u32, bytes: &[u8]) {
+        self.tag(field, 2);
+        write_varint(&mut self.0, bytes.len() as u64);
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn string(&mut self, field: u32, value: &str) {
+        self.bytes(field, value.as_bytes());
+    }
+
+    fn message(&mut self, field: u32, message: Message) {
+        self.bytes(field, &message.0);
+    }
+
+    fn packed(&mut self, field: u32, values: &[u64]) {
+        let mut bytes = Vec::new();
+        values.iter().for_each(|&v| write_varint(&mut bytes, v));
+        self.bytes(field, &bytes);
+    }
+
+    fn tag(&mut self, field: u32, wire_type: u32) {
+        write_varint(&mut self.0, u64::from((field << 3) | wire_type));
+    }
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_varint() {
+        let varint = |value| {
+            let mut buf = Vec::new();
+            write_varint(&mut buf, value);
+            buf
+        };
+        assert_eq!(varint(0), [0x00]);
+        assert_eq!(varint(1), [0x01]);
+        assert_eq!(varint(127), [0x7f]);
+        assert_eq!(varint(150), [0x96, 0x01]);
+        assert_eq!(varint(300), [0xac, 0x02]);
+        assert_eq!(
+            varint(u64::MAX),
+            [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]
+        );
+    }
+
+    #[test]
+    fn test_message() {
+        let mut message = Message::default();
+        message.varint(1, 150);
+        assert_eq!(message.0, [0x08, 0x96, 0x01]);
+
+        let mut message = Message::default();
+        message.string(2, "testing");
+        assert_eq!(message.0, b"\x12\x07testing");
+
+        // the packed positions of an occurrence, as in the protobuf encoding guide
+        let mut message = Message::default();
+        message.packed(1, &[3, 270, 86942]);
+        assert_eq!(message.0, [0x0a, 0x06, 0x03, 0x8e, 0x02, 0x9e, 0xa7, 0x05]);
+
+        let mut inner = Message::default();
+        inner.varint(1, 150);
+        let mut message = Message::default();
+        message.message(3, inner);
+        assert_eq!(message.0, [0x1a, 0x03, 0x08, 0x96, 0x01]);
+    }
+
+    #[test]
+    fn test_position() {
+        let line_starts = line_starts("select 1;\nselect 2;\n");
+        assert_eq!(line_starts, [0, 10, 20]);
+        assert_eq!(position(&line_starts, 0), (0, 0));
+        assert_eq!(position(&line_starts, 9), (0, 9));
+        assert_eq!(position(&line_starts, 10), (1, 0));
+        assert_eq!(position(&line_starts, 17), (1, 7));
+        assert_eq!(position(&line_starts, 20), (2, 0));
+    }
+
+    #[test]
+    fn test_scip_symbol() {
+        let symbol = |identifier: &str, kind| Symbol {
+            identifier: identifier.to_string(),
+            kind,
+            is_definition: false,
+        };
+        assert_eq!(
+            scip_symbol(&symbol("public.contact", SymbolKind::Table)),
+            "scip-sql . . . public/contact#"
+        );
+        assert_eq!(
+            scip_symbol(&symbol("public.contact.name", SymbolKind::Column)),
+            "scip-sql . . . public/contact#name."
+        );
+        assert_eq!(
+            scip_symbol(&symbol("public.Contact List.e-mail", SymbolKind::Column)),
+            "scip-sql . . . public/`Contact List`#e-mail."
+        );
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("contact_2"), "contact_2");
+        assert_eq!(escape("a+b-c$"), "a+b-c$");
+        assert_eq!(escape("my table"), "`my table`");
+        assert_eq!(escape("a`b"), "`a``b`");
+        assert_eq!(escape("kontakt_ä"), "`kontakt_ä`");
+        assert_eq!(escape(""), "``");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_collect_sql_files() {
+        let dir = std::env::temp_dir().join(format!("pglsp-index-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("migrations")).unwrap();
+        fs::write(dir.join("schema.sql"), "select 1;").unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        fs::write(dir.join("migrations/0001.sql"), "select 1;").unwrap();
+        std::os::unix::fs::symlink(dir.join("schema.sql"), dir.join("link.sql")).unwrap();
+        // a link to a parent directory is not followed
+        std::os::unix::fs::symlink(&dir, dir.join("migrations/root")).unwrap();
+
+        let mut paths = Vec::new();
+        let result = collect_sql_files(&dir, &mut paths);
+        paths.sort();
+        let _ = fs::remove_dir_all(&dir);
+        result.unwrap();
+        assert_eq!(
+            paths,
+            [
+                dir.join("link.sql"),
+                dir.join("migrations/0001.sql"),
+                dir.join("schema.sql"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,159 @@
+//! JUnit XML reports, which CI systems such as Jenkins and GitLab show in their test report views.
+//!
+//! Every file is a test case that fails if it has an error. All of its diagnostics are listed in
+//! the failure, or in the output of the test case if it passes, so that warnings are shown too.
+
+use std::fmt::Write;
+
+use analyser::{LintDiagnostic, Severity};
+use parser::{SyntaxError, TextRange};
+
+use crate::report::{line_number, severity_label};
+
+/// The diagnostics of a single file
+pub(crate) struct TestCase {
+    /// The path of the file relative to the checked directory
+    name: String,
+    errors: usize,
+    /// One line per diagnostic, e.g. `3: error: relation "contact" does not exist [rule]`
+    lines: Vec<String>,
+}
+
+impl TestCase {
+    pub(crate) fn new(name: String) -> TestCase {
+        TestCase {
+            name,
+            errors: 0,
+            lines: Vec::new(),
+        }
+    }
+
+    pub(crate) fn diagnostic(&mut self, text: &str, d: &LintDiagnostic) {
+        let message = format!("{} [{}]", d.message, d.rule);
+        self.push(text, d.severity, &message, d.range);
+    }
+
+    pub(crate) fn syntax_error(&mut self, text: &str, error: &SyntaxError) {
+        self.push(text, Severity::Error, &error.to_string(), error.range());
+    }
+
+    fn push(&mut self, text: &str, severity: Severity, message: &str, range: TextRange) {
+        if severity == Severity::Error {
+            self.errors += 1;
+        }
+        self.lines.push(format!(
+            "{}: {}: {}",
+            line_number(text, range.start().into()),
+            severity_label(severity),
+            message
+        ));
+    }
+}
+
+/// Returns the JUnit XML document of a test suite named `suite` with the test `cases`
+pub(crate) fn junit_report(suite: &str, cases: &[TestCase]) -> String {
+    let failures = cases.iter().filter(|case| case.errors > 0).count();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuites name=\"pglsp\" tests=\"{}\" failures=\"{}\">",
+        cases.len(),
+        failures
+    );
+    let _ = writeln!(
+        xml,
+        "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\">",
+        escape(suite),
+        cases.len(),
+        failures
+    );
+    for case in cases {
+        let name = escape(&case.name);
+        let _ = write!(
+            xml,
+            "    <testcase classname=\"{}\" name=\"{}\" file=\"{}\"",
+            escape(suite),
+            name,
+            name
+        );
+        if case.lines.is_empty() {
+            xml.push_str("/>\n");
+            continue;
+        }
+        xml.push_str(">\n");
+        let body = escape(&case.lines.join("\n"));
+        if case.errors > 0 {
+            let _ = writeln!(
+                xml,
+                "      <failure message=\"{} error(s)\" type=\"error\">{}</failure>",
+                case.errors, body
+            );
+        } else {
+            let _ = writeln!(xml, "      <system-out>{}</system-out>", body);
+        }
+        xml.push_str("    </testcase>\n");
+    }
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+/// Escapes `text` for use in attributes and text of XML, dropping the characters that XML 1.0
+/// does not allow even when escaped, such as most control characters
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\t' | '\n' | '\r' => escaped.push(c),
+            '\u{0}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}' => {}
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::TextSize;
+
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("a < b && 'c' > \"d\""),
+            "a &lt; b &amp;&amp; &apos;c&apos; &gt; &quot;d&quot;"
+        );
+        assert_eq!(escape("a\u{0}b\u{1b}[0m\tc\r\nd\u{ffff}"), "ab[0m\tc\r\nd");
+    }
+
+    #[test]
+    fn test_junit_report() {
+        let text = "select 1;\nselect \u{7};";
+        let mut failing = TestCase::new("a.sql".to_string());
+        failing.push(
+            text,
+            Severity::Error,
+            "unexpected \u{7}",
+            TextRange::empty(TextSize::from(11)),
+        );
+        let passing = TestCase::new("b.sql".to_string());
+        assert_eq!(
+            junit_report("migrations", &[failing, passing]),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="pglsp" tests="2" failures="1">
+  <testsuite name="migrations" tests="2" failures="1" errors="0">
+    <testcase classname="migrations" name="a.sql" file="a.sql">
+      <failure message="1 error(s)" type="error">2: error: unexpected </failure>
+    </testcase>
+    <testcase classname="migrations" name="b.sql" file="b.sql"/>
+  </testsuite>
+</testsuites>
+"#
+        );
+    }
+}
@@ -0,0 +1,360 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use analyser::data_migration::check_mixed_migration;
+use analyser::impact::{impacted_stmts, StmtId};
+use analyser::migrations::MigrationState;
+use analyser::policy::{check_policy, POLICY_VIOLATION};
+use analyser::read_only::{check_read_only, WRITE_IN_READ_ONLY_FILE};
+use analyser::schema_change::check_lock_timeout;
+use analyser::spelling::{check_spelling, MISSPELLING};
+use analyser::table_rewrite::check_table_rewrites;
+use analyser::{lint_cancellable, rule_group, LintDiagnostic, RuleGroup, Severity, TableFacts};
+use anyhow::{bail, Context};
+use parser::{Cancellation, Parse, RawStmt};
+
+use crate::config::{dictionary, lint_config, load_config, policy};
+use crate::flags;
+use crate::git::{changed_lines, overlaps};
+use crate::index::{collect_sql_files, read_input};
+use crate::junit::{junit_report, TestCase};
+use crate::migrate::{parse_version, version};
+use crate::report::{print_diagnostic, print_syntax_error};
+
+/// The category of syntax errors
+const SYNTAX: &str = "syntax";
+/// The category of the checks against the schema, e.g. of the migrations
+const SCHEMA: &str = "schema";
+/// The category of the statements that write in read-only directories
+const READ_ONLY: &str = "read-only";
+/// The category of the statements that the policy of `pglsp.toml` denies
+const POLICY: &str = "policy";
+/// The category of the misspelled words of the spell check of `pglsp.toml`
+const SPELLING: &str = "spelling";
+
+impl flags::Lint {
+    /// Reports the diagnostics of all statements, or only of the statements impacted by the
+    /// changes since `--changed-from`. Fails as the [`ExitPolicy`] of the flags tells.
+    pub(crate) fn run(self) -> anyhow::Result<ExitCode> {
+        let at = self.at.as_deref().map(parse_version).transpose()?;
+        let config = load_config()?;
+        let statement_policy = policy(&config)?;
+        let policy = ExitPolicy::from_flags(&self)?;
+        let lint_config = lint_config(&config, &self.group, &self.disable_rule)?;
+        let dictionary = dictionary(&config)?;
+        let current_dir = std::env::current_dir()?;
+        let junit = match self.output.as_deref() {
+            None | Some("text") => false,
+            Some("junit") => true,
+            Some(output) => bail!("unsupported output format `{}`", output),
+        };
+
+        // `-` checks stdin against the schema of the migrations of `--schema`, if any, as if it
+        // was a file after them
+        let stdin = self.path.as_os_str() == "-";
+        if stdin && self.changed_from.is_some() {
+            bail!("--changed-from cannot be used when checking stdin");
+        }
+        if !stdin && self.schema.is_some() {
+            bail!("--schema can only be used when checking stdin with `-`");
+        }
+        let dir = if stdin {
+            self.schema.as_ref()
+        } else {
+            Some(&self.path)
+        };
+
+        let mut paths = Vec::new();
+        if let Some(dir) = dir {
+            collect_sql_files(dir, &mut paths)?;
+        }
+        // the migrations after `--at` had not been written at that time
+        paths.retain(|path| at.iter().all(|&at| version(path).iter().all(|&v| v <= at)));
+        paths.sort();
+
+        let mut texts = paths
+            .iter()
+            .map(|path| {
+                fs::read_to_string(path)
+                    .with_context(|| format!("failed to read {}", path.display()))
+            })
+            .collect::<anyhow::Result<Vec<String>>>()?;
+        if stdin {
+            texts.push(read_input(&self.path)?);
+            paths.push(PathBuf::from("<stdin>"));
+        }
+        // only stdin is reported if it is checked, the files of `--schema` are not
+        let is_reported = |file: usize| !stdin || file + 1 == paths.len();
+        let parses = texts
+            .iter()
+            .map(|text| parser::parse_source(text))
+            .collect::<Vec<_>>();
+        let schema_diagnostics = check_migrations(&paths, &parses);
+        let files = parses
+            .iter()
+            .map(|parse| parse.stmts.as_slice())
+            .collect::<Vec<&[RawStmt]>>();
+        let tables = TableFacts::collect(files.iter().copied());
+
+        // the changed lines of every file, if only changes are checked
+        let changes = match &self.changed_from {
+            Some(git_ref) => {
+                let mut changes = changed_lines(&self.path, git_ref)?;
+                Some(
+                    paths
+                        .iter()
+                        .map(|path| {
+                            let relative = path.strip_prefix(&self.path).unwrap_or(path);
+                            changes.remove(relative).unwrap_or_default()
+                        })
+                        .collect::<Vec<_>>(),
+                )
+            }
+            None => None,
+        };
+        let impacted = changes.as_ref().map(|changes| {
+            let mut changed = BTreeSet::<StmtId>::new();
+            for (file, (text, parse)) in texts.iter().zip(&parses).enumerate() {
+                for (idx, stmt) in parse.stmts.iter().enumerate() {
+                    if overlaps(text, stmt.range, &changes[file]) {
+                        changed.insert((file, idx));
+                    }
+                }
+            }
+            let mut impacted = impacted_stmts(&files, &changed);
+            // the rules at `CREATE TABLE` look at what other statements declare about the table
+            let changed_tables = TableFacts::collect(
+                changed
+                    .iter()
+                    .map(|&(file, idx)| std::slice::from_ref(&files[file][idx])),
+            );
+            for (file, stmts) in files.iter().enumerate() {
+                for (idx, stmt) in stmts.iter().enumerate() {
+                    if changed_tables.concerns(stmt) {
+                        impacted.insert((file, idx));
+                    }
+                }
+            }
+            impacted
+        });
+
+        let mut failed = false;
+        let mut warnings = 0;
+        let mut checked = 0;
+        let mut cases = Vec::new();
+        for (file, ((path, text), parse)) in paths.iter().zip(&texts).zip(&parses).enumerate() {
+            if !is_reported(file) {
+                continue;
+            }
+            let relative = path.strip_prefix(&self.path).unwrap_or(path);
+            let mut case = TestCase::new(relative.display().to_string());
+            let is_impacted = |idx: usize| impacted.iter().all(|i| i.contains(&(file, idx)));
+            checked += (0..parse.stmts.len())
+                .filter(|&idx| is_impacted(idx))
+                .count();
+
+            for error in &parse.errors {
+                if changes
+                    .iter()
+                    .all(|changes| overlaps(text, error.range(), &changes[file]))
+                {
+                    if junit {
+                        case.syntax_error(text, error);
+                    } else {
+                        print_syntax_error(path, text, error);
+                    }
+                    failed |= policy.fails(SYNTAX, Severity::Error);
+                }
+            }
+            // the rules inspect single statements, so the diagnostics of a statement only depend on
+            // the statement itself, the schema it is applied to and the facts of the tables
+            let mut diagnostics =
+                lint_cancellable(&parse.stmts, &lint_config, &tables, &Cancellation::new())
+                    .unwrap_or_default();
+            diagnostics.extend(schema_diagnostics[file].iter().cloned());
+            // the `read_only` directories of `pglsp.toml` are relative to the current directory
+            let workspace_path = path
+                .strip_prefix(&current_dir)
+                .or_else(|_| path.strip_prefix("."))
+                .unwrap_or(path);
+            if self.read_only.iter().any(|dir| relative.starts_with(dir))
+                || config.is_read_only(workspace_path)
+            {
+                diagnostics.extend(check_read_only(&parse.stmts));
+            }
+            diagnostics.extend(check_policy(&parse.stmts, &statement_policy));
+            if let Some(dictionary) = &dictionary {
+                diagnostics.extend(check_spelling(&parse.cst, &parse.stmts, dictionary));
+            }
+            diagnostics.sort_by_key(|d| d.range.start());
+            for d in diagnostics {
+                let stmt = parse
+                    .stmts
+                    .iter()
+                    .position(|stmt| stmt.range.contains_range(d.range));
+                if stmt.iter().all(|&idx| is_impacted(idx)) {
+                    if junit {
+                        case.diagnostic(text, &d);
+                    } else {
+                        print_diagnostic(path, text, &d);
+                    }
+                    let category = match rule_group(d.rule) {
+                        Some(group) => group.name(),
+                        None if d.rule == WRITE_IN_READ_ONLY_FILE => READ_ONLY,
+                        None if d.rule == POLICY_VIOLATION => POLICY,
+                        None if d.rule == MISSPELLING => SPELLING,
+                        None => SCHEMA,
+                    };
+                    failed |= policy.fails(category, d.severity);
+                    warnings +=
+                        usize::from(d.severity == Severity::Warning && policy.counts(category));
+                }
+            }
+            cases.push(case);
+        }
+
+        if junit {
+            print!("{}", junit_report("lint", &cases));
+        } else if impacted.is_some() {
+            println!(
+                "checked {} of {} statement(s)",
+                checked,
+                parses.iter().map(|p| p.stmts.len()).sum::<usize>()
+            );
+        }
+        // the report does not fail for warnings, so exceeding the maximum fails the run without a
+        // failing test case in JUnit XML. The reason goes to stderr there to keep the XML valid.
+        if let Some(max) = policy.max_warnings.filter(|max| warnings > *max) {
+            let message = format!("{} warning(s) exceed the maximum of {}", warnings, max);
+            if junit {
+                eprintln!("{}", message);
+            } else {
+                println!("{}", message);
+            }
+            failed = true;
+        }
+        Ok(if failed {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
+        })
+    }
+}
+
+/// Which diagnostics fail the run, independently of those that are reported
+struct ExitPolicy {
+    /// The least severe diagnostics that fail the run
+    error_on: Severity,
+    max_warnings: Option<usize>,
+    /// The categories whose diagnostics count, all if empty
+    categories: Vec<String>,
+}
+
+impl ExitPolicy {
+    fn from_flags(flags: &flags::Lint) -> anyhow::Result<ExitPolicy> {
+        let error_on = match flags.error_on.as_deref() {
+            None | Some("error") => Severity::Error,
+            Some("warning") => Severity::Warning,
+            Some("info") => Severity::Information,
+            Some("hint") => Severity::Hint,
+            Some(severity) => bail!("unknown severity `{}`", severity),
+        };
+        for category in &flags.fail_category {
+            if ![SYNTAX, SCHEMA, READ_ONLY, POLICY, SPELLING].contains(&category.as_str())
+                && RuleGroup::from_name(category).is_none()
+            {
+                bail!("unknown category `{}`", category);
+            }
+        }
+        Ok(ExitPolicy {
+            error_on,
+            max_warnings: flags.max_warnings,
+            categories: flags.fail_category.clone(),
+        })
+    }
+
+    /// Returns true if diagnostics of `category` count towards the exit status
+    fn counts(&self, category: &str) -> bool {
+        self.categories.is_empty() || self.categories.iter().any(|c| c == category)
+    }
+
+    /// Returns true if a diagnostic of `category` with `severity` fails the run
+    fn fails(&self, category: &str, severity: Severity) -> bool {
+        self.counts(category) && severity <= self.error_on
+    }
+}
+
+/// Checks every migration against the schema that the migrations before it produce, and all other
+/// files against the schema after the last migration. Migrations that change both the schema and
+/// the data, take strong locks without a lock timeout or rewrite tables are reported as well.
+/// Returns the diagnostics of every file.
+pub(crate) fn check_migrations(paths: &[PathBuf], parses: &[Parse]) -> Vec<Vec<LintDiagnostic>> {
+    let mut migrations = paths
+        .iter()
+        .enumerate()
+        .filter_map(|(file, path)| Some((version(path)?, file)))
+        .collect::<Vec<_>>();
+    migrations.sort();
+
+    let mut diagnostics = vec![Vec::new(); paths.len()];
+    let mut state = MigrationState::default();
+    for (_, file) in migrations {
+        // the rewrites are checked against the state before the migration
+        let rewrites = check_table_rewrites(&parses[file].stmts, &state, &[]);
+        diagnostics[file] = state.check(&parses[file].stmts);
+        diagnostics[file].extend(rewrites);
+        diagnostics[file].extend(check_mixed_migration(&parses[file].stmts));
+        diagnostics[file].extend(check_lock_timeout(&parses[file].stmts));
+    }
+    for (file, path) in paths.iter().enumerate() {
+        if version(path).is_none() {
+            diagnostics[file] = state.clone().check(&parses[file].stmts);
+        }
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags(error_on: Option<&str>, fail_category: &[&str]) -> flags::Lint {
+        flags::Lint {
+            path: PathBuf::from("."),
+            schema: None,
+            changed_from: None,
+            at: None,
+            output: None,
+            error_on: error_on.map(str::to_string),
+            max_warnings: None,
+            fail_category: fail_category.iter().map(|c| c.to_string()).collect(),
+            read_only: Vec::new(),
+            group: Vec::new(),
+            disable_rule: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_exit_policy() {
+        let policy = ExitPolicy::from_flags(&flags(None, &[])).unwrap();
+        assert!(policy.fails(SYNTAX, Severity::Error));
+        assert!(!policy.fails("recommended", Severity::Warning));
+        assert!(policy.counts(SPELLING));
+
+        let policy =
+            ExitPolicy::from_flags(&flags(Some("warning"), &[SCHEMA, "replication"])).unwrap();
+        assert!(policy.fails(SCHEMA, Severity::Warning));
+        assert!(policy.fails("replication", Severity::Error));
+        assert!(!policy.fails("replication", Severity::Information));
+        assert!(!policy.fails(SYNTAX, Severity::Error));
+        assert!(!policy.counts("recommended"));
+    }
+
+    #[test]
+    fn test_exit_policy_unknown_flags() {
+        assert!(ExitPolicy::from_flags(&flags(Some("fatal"), &[])).is_err());
+        assert!(ExitPolicy::from_flags(&flags(None, &["style"])).is_err());
+    }
+}
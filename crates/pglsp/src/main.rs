@@ -0,0 +1,231 @@
+//! The `pglsp` command line interface.
+//!
+//! Runs the analyses of the `analyser` crate outside of an editor, e.g. in CI.
+
+#![warn(
+    rust_2018_idioms,
+    unused_lifetimes,
+    semicolon_in_expressions_from_macros
+)]
+
+mod bloat;
+mod change_report;
+mod config;
+mod db;
+mod exec;
+mod flags;
+mod git;
+mod health;
+mod index;
+mod junit;
+mod lint;
+mod migrate;
+mod parse;
+mod report;
+mod restore;
+mod tenants;
+
+use std::process::ExitCode;
+
+fn main() -> anyhow::Result<ExitCode> {
+    let flags = flags::Pglsp::from_env_or_exit();
+    if flags.offline {
+        db::set_offline();
+    }
+    report::set_color(flags.color.as_deref())?;
+
+    match flags.subcommand {
+        flags::PglspCmd::Tenants(cmd) => match cmd.subcommand {
+            flags::TenantsCmd::Check(cmd) => cmd.run(),
+        },
+        flags::PglspCmd::Restore(cmd) => match cmd.subcommand {
+            flags::RestoreCmd::Preflight(cmd) => cmd.run(),
+        },
+        flags::PglspCmd::Exec(cmd) => cmd.run(),
+        flags::PglspCmd::Lint(cmd) => cmd.run(),
+        flags::PglspCmd::ChangeReport(cmd) => cmd.run(),
+        flags::PglspCmd::Report(cmd) => cmd.run(),
+        flags::PglspCmd::Index(cmd) => cmd.run(),
+        flags::PglspCmd::Migrate(cmd) => match cmd.subcommand {
+            flags::MigrateCmd::Squash(cmd) => cmd.run(),
+        },
+        flags::PglspCmd::Bloat(cmd) => cmd.run(),
+        flags::PglspCmd::Parse(cmd) => cmd.run(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn parse(args: &[&str]) -> xflags::Result<flags::Pglsp> {
+        flags::Pglsp::from_vec(args.iter().map(OsString::from).collect())
+    }
+
+    fn subcommand(args: &[&str]) -> flags::PglspCmd {
+        parse(args).unwrap().subcommand
+    }
+
+    #[test]
+    fn test_global_flags() {
+        let flags = parse(&["--offline", "--color", "never", "parse", "-"]).unwrap();
+        assert!(flags.offline);
+        assert_eq!(flags.color.as_deref(), Some("never"));
+        let flags::PglspCmd::Parse(cmd) = flags.subcommand else {
+            panic!("expected the parse command");
+        };
+        assert_eq!(cmd.path, PathBuf::from("-"));
+        assert!(!cmd.dump_cst);
+
+        let flags = parse(&["parse", "schema.sql", "--dump-cst"]).unwrap();
+        assert!(!flags.offline);
+        assert_eq!(flags.color, None);
+    }
+
+    #[test]
+    fn test_tenants_check() {
+        let flags::PglspCmd::Tenants(cmd) = subcommand(&[
+            "tenants",
+            "check",
+            "--connection",
+            "postgres://localhost/app",
+            "--tenants",
+            "tenant_%",
+        ]) else {
+            panic!("expected the tenants command");
+        };
+        let flags::TenantsCmd::Check(check) = cmd.subcommand;
+        assert_eq!(
+            check.connection.as_deref(),
+            Some("postgres://localhost/app")
+        );
+        assert_eq!(check.template, None);
+        assert_eq!(check.tenants.as_deref(), Some("tenant_%"));
+    }
+
+    #[test]
+    fn test_restore_preflight() {
+        let flags::PglspCmd::Restore(cmd) = subcommand(&["restore", "preflight", "dump.sql"])
+        else {
+            panic!("expected the restore command");
+        };
+        let flags::RestoreCmd::Preflight(preflight) = cmd.subcommand;
+        assert_eq!(preflight.path, PathBuf::from("dump.sql"));
+        assert_eq!(preflight.connection, None);
+    }
+
+    #[test]
+    fn test_exec() {
+        let flags::PglspCmd::Exec(exec) = subcommand(&[
+            "exec",
+            "migration.sql",
+            "--commit",
+            "--retries",
+            "2",
+            "--lock-timeout",
+            "5s",
+            "--lock-retries",
+            "3",
+        ]) else {
+            panic!("expected the exec command");
+        };
+        assert_eq!(exec.path, PathBuf::from("migration.sql"));
+        assert!(exec.commit);
+        assert_eq!(exec.retries, Some(2));
+        assert_eq!(exec.lock_timeout.as_deref(), Some("5s"));
+        assert_eq!(exec.statement_timeout, None);
+        assert_eq!(exec.lock_retries, Some(3));
+
+        assert!(parse(&["exec", "migration.sql", "--retries", "many"]).is_err());
+    }
+
+    #[test]
+    fn test_lint() {
+        let flags::PglspCmd::Lint(lint) = subcommand(&[
+            "lint",
+            "migrations",
+            "--fail-category",
+            "schema",
+            "--fail-category",
+            "policy",
+            "--max-warnings",
+            "10",
+            "--read-only",
+            "queries",
+        ]) else {
+            panic!("expected the lint command");
+        };
+        assert_eq!(lint.path, PathBuf::from("migrations"));
+        assert_eq!(lint.fail_category, ["schema", "policy"]);
+        assert_eq!(lint.max_warnings, Some(10));
+        assert_eq!(lint.read_only, [PathBuf::from("queries")]);
+        assert!(lint.group.is_empty());
+        assert_eq!(lint.error_on, None);
+    }
+
+    #[test]
+    fn test_migrate_squash() {
+        let flags::PglspCmd::Migrate(cmd) = subcommand(&[
+            "migrate",
+            "squash",
+            "migrations",
+            "--from",
+            "2",
+            "--output",
+            "squashed.sql",
+        ]) else {
+            panic!("expected the migrate command");
+        };
+        let flags::MigrateCmd::Squash(squash) = cmd.subcommand;
+        assert_eq!(squash.from.as_deref(), Some("2"));
+        assert_eq!(squash.to, None);
+        assert_eq!(squash.output, Some(PathBuf::from("squashed.sql")));
+    }
+
+    #[test]
+    fn test_bloat() {
+        let flags::PglspCmd::Bloat(bloat) = subcommand(&[
+            "bloat",
+            "queries",
+            "--min-dead-ratio",
+            "0.5",
+            "--min-dead-rows",
+            "100",
+        ]) else {
+            panic!("expected the bloat command");
+        };
+        assert_eq!(bloat.min_dead_ratio, Some(0.5));
+        assert_eq!(bloat.min_dead_rows, Some(100));
+        assert_eq!(bloat.connection, None);
+    }
+
+    #[test]
+    fn test_change_report_and_index() {
+        let flags::PglspCmd::ChangeReport(report) =
+            subcommand(&["change-report", "migrations", "--changed-from", "main"])
+        else {
+            panic!("expected the change-report command");
+        };
+        assert_eq!(report.changed_from.as_deref(), Some("main"));
+
+        let flags::PglspCmd::Index(index) = subcommand(&["index", "."]) else {
+            panic!("expected the index command");
+        };
+        assert_eq!(index.format, None);
+        assert_eq!(index.output, None);
+    }
+
+    #[test]
+    fn test_invalid_args() {
+        // a missing command, argument or value, and unknown commands and flags
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["exec"]).is_err());
+        assert!(parse(&["parse", "schema.sql", "--color"]).is_err());
+        assert!(parse(&["vacuum", "."]).is_err());
+        assert!(parse(&["index", ".", "--frobnicate"]).is_err());
+        assert!(parse(&["tenants"]).is_err());
+    }
+}
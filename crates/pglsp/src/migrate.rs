@@ -0,0 +1,137 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use analyser::migrations::{is_squashable, migration_version, squash, MigrationState};
+use analyser::schema_diff::diff;
+use analyser::Schema;
+use anyhow::{bail, Context};
+
+use crate::flags;
+use crate::index::collect_sql_files;
+use crate::report::{line_number, print_syntax_error};
+
+impl flags::Squash {
+    /// Prints or writes the squashed migration. Fails if a migration of the range has statements
+    /// that cannot be squashed, or if the squashed migration does not have the same effect as the
+    /// migrations it replaces.
+    pub(crate) fn run(self) -> anyhow::Result<ExitCode> {
+        let from = self.from.as_deref().map(parse_version).transpose()?;
+        let to = self.to.as_deref().map(parse_version).transpose()?;
+
+        let mut paths = Vec::new();
+        collect_sql_files(&self.path, &mut paths)?;
+        let mut migrations = paths
+            .into_iter()
+            .filter_map(|path| Some((version(&path)?, path)))
+            .filter(|(version, _)| to.iter().all(|to| version <= to))
+            .collect::<Vec<(u128, PathBuf)>>();
+        migrations.sort();
+
+        // the schema before and after the squashed migrations
+        let mut base = MigrationState::default();
+        let mut target = MigrationState::default();
+        let mut squashed = 0;
+        let mut failed = false;
+        for (version, path) in &migrations {
+            let text = fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let parse = parser::parse_source(&text);
+            for error in &parse.errors {
+                print_syntax_error(path, &text, error);
+                failed = true;
+            }
+
+            if from.is_some_and(|from| *version < from) {
+                base.replay(&parse.stmts);
+            } else {
+                squashed += 1;
+                for stmt in parse.stmts.iter().filter(|s| !is_squashable(&s.stmt)) {
+                    println!(
+                        "{}:{}: error: the statement cannot be squashed, because its effect is not part of the schema model",
+                        path.display(),
+                        line_number(&text, stmt.range.start().into())
+                    );
+                    failed = true;
+                }
+            }
+            target.replay(&parse.stmts);
+            base.end_session();
+            target.end_session();
+        }
+        if failed {
+            return Ok(ExitCode::FAILURE);
+        }
+        if squashed == 0 {
+            bail!(
+                "there are no migrations to squash in {}",
+                self.path.display()
+            );
+        }
+
+        let sql = squash(&base, &target);
+        let differences = verify(&base, &target, &sql);
+        if !differences.is_empty() {
+            for difference in differences {
+                eprintln!("{}", difference);
+            }
+            bail!("the squashed migration does not have the same effect as the migrations it replaces");
+        }
+
+        match &self.output {
+            Some(output) => {
+                fs::write(output, &sql)
+                    .with_context(|| format!("failed to write {}", output.display()))?;
+                println!(
+                    "squashed {} migration(s) into {}",
+                    squashed,
+                    output.display()
+                );
+            }
+            None => print!("{}", sql),
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// Returns the version of `path` if it is a migration
+pub(crate) fn version(path: &Path) -> Option<u128> {
+    migration_version(path.file_name()?.to_str()?)
+}
+
+/// Parses a migration version given on the command line
+pub(crate) fn parse_version(version: &str) -> anyhow::Result<u128> {
+    version
+        .parse()
+        .with_context(|| format!("invalid migration version {}", version))
+}
+
+/// Replays `sql` on top of `base` and returns how the result differs from `target`
+fn verify(base: &MigrationState, target: &MigrationState, sql: &str) -> Vec<String> {
+    let parse = parser::parse_source(sql);
+    let mut differences = parse
+        .errors
+        .iter()
+        .map(|error| format!("the squashed migration is invalid: {}", error))
+        .collect::<Vec<_>>();
+
+    let mut squashed = base.clone();
+    squashed.replay(&parse.stmts);
+    let names = squashed
+        .schemas
+        .keys()
+        .chain(target.schemas.keys())
+        .collect::<BTreeSet<_>>();
+    for name in names {
+        let empty = Schema::new(name);
+        let old = squashed.schemas.get(name).unwrap_or(&empty);
+        let new = target.schemas.get(name).unwrap_or(&empty);
+        differences.extend(
+            diff(old, new)
+                .into_iter()
+                .map(|change| format!("{}: {}", name, change)),
+        );
+    }
+    differences
+}
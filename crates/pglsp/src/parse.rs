@@ -0,0 +1,29 @@
+use std::process::ExitCode;
+
+use parser::dump_cst;
+
+use crate::flags;
+use crate::index::read_input;
+use crate::report::print_syntax_error;
+
+impl flags::Parse {
+    /// Reports the syntax errors of the file or stdin, after its tree if `--dump-cst` is given.
+    /// Fails if there are any.
+    pub(crate) fn run(self) -> anyhow::Result<ExitCode> {
+        let text = read_input(&self.path)?;
+
+        let parse = parser::parse_source(&text);
+        if self.dump_cst {
+            print!("{}", dump_cst(&parse.cst));
+        }
+        for error in &parse.errors {
+            print_syntax_error(&self.path, &text, error);
+        }
+
+        Ok(if parse.errors.is_empty() {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        })
+    }
+}
@@ -0,0 +1,151 @@
+//! Printing of diagnostics to the terminal.
+//!
+//! Diagnostics are grouped under the path of their file, which is printed once before the first
+//! of them. Each one shows its severity, message and rule, followed by the line it starts at with
+//! its range underlined:
+//!
+//! ```text
+//! migrations/0002_contact.sql
+//! error: relation "contact" does not exist [migration-unknown-relation]
+//!   3:15 | select * from contact;
+//!        |               ^^^^^^^
+//! ```
+//!
+//! Severities are colored if stdout is a terminal, unless `--color` says otherwise or `NO_COLOR`
+//! is set.
+
+use std::env;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use analyser::{LintDiagnostic, Severity};
+use anyhow::bail;
+use parser::{SyntaxError, TextRange};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+
+/// Set by `--color` or from the terminal, after which severities are colored
+static COLOR: AtomicBool = AtomicBool::new(false);
+
+/// The file of the last printed diagnostic
+static LAST_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Enables colors for `when`, which is `auto`, `always` or `never`. `auto`, the default, colors
+/// the output only if stdout is a terminal and `NO_COLOR` is not set.
+pub(crate) fn set_color(when: Option<&str>) -> anyhow::Result<()> {
+    let color = match when.unwrap_or("auto") {
+        "auto" => io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+        "always" => true,
+        "never" => false,
+        other => bail!(
+            "unknown color mode {}, expected auto, always or never",
+            other
+        ),
+    };
+    COLOR.store(color, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Prints `d` with its rule and the line it starts at
+pub(crate) fn print_diagnostic(path: &Path, text: &str, d: &LintDiagnostic) {
+    let message = format!("{} [{}]", d.message, d.rule);
+    print(path, text, d.severity, &message, d.range);
+}
+
+/// Prints `error` with the line it starts at
+pub(crate) fn print_syntax_error(path: &Path, text: &str, error: &SyntaxError) {
+    print(
+        path,
+        text,
+        Severity::Error,
+        &error.to_string(),
+        error.range(),
+    );
+}
+
+fn print(path: &Path, text: &str, severity: Severity, message: &str, range: TextRange) {
+    let color = COLOR.load(Ordering::SeqCst);
+    let paint = |style: &str, text: &str| {
+        if color {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    };
+
+    let mut last_path = LAST_PATH.lock().unwrap();
+    if last_path.as_deref() != Some(path) {
+        if last_path.is_some() {
+            println!();
+        }
+        println!("{}", paint(BOLD, &path.display().to_string()));
+        *last_path = Some(path.to_path_buf());
+    }
+
+    let style = severity_style(severity);
+    println!("{}: {}", paint(style, severity_label(severity)), message);
+
+    let start = usize::from(range.start()).min(text.len());
+    let end = usize::from(range.end()).min(text.len());
+    let line_start = text[..start].rfind('\n').map_or(0, |idx| idx + 1);
+    let line_end = text[start..]
+        .find('\n')
+        .map_or(text.len(), |idx| start + idx);
+    let line = text[line_start..line_end].trim_end_matches('\r');
+    let prefix = &text[line_start..start];
+
+    let location = format!(
+        "{}:{}",
+        line_number(text, start),
+        prefix.chars().count() + 1
+    );
+    // tabs are kept so that the carets line up with the line above
+    let indent = prefix
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect::<String>();
+    let width = text[start..end.clamp(start, line_end)]
+        .chars()
+        .count()
+        .max(1);
+    println!("  {} {} {}", location, paint(DIM, "|"), line);
+    println!(
+        "  {} {} {}{}",
+        " ".repeat(location.len()),
+        paint(DIM, "|"),
+        indent,
+        paint(style, &"^".repeat(width))
+    );
+}
+
+pub(crate) fn severity_label(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Information => "info",
+        Severity::Hint => "hint",
+    }
+}
+
+/// Returns the style that `severity` is colored with
+fn severity_style(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "\x1b[1;31m",
+        Severity::Warning => "\x1b[1;33m",
+        Severity::Information => "\x1b[1;34m",
+        Severity::Hint => "\x1b[1;36m",
+    }
+}
+
+/// Returns the 1-based line of the byte `offset` in `text`
+pub(crate) fn line_number(text: &str, offset: usize) -> usize {
+    text.as_bytes()[..offset.min(text.len())]
+        .iter()
+        .filter(|&&b| b == b'\n')
+        .count()
+        + 1
+}
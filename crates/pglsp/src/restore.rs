@@ -0,0 +1,52 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::process::ExitCode;
+
+use analyser::restore::{check_restore_script, RestoreConfig, ROLES_QUERY};
+use analyser::Severity;
+use anyhow::Context;
+
+use crate::db::{connect, is_offline};
+use crate::flags;
+use crate::report::{print_diagnostic, print_syntax_error};
+
+impl flags::Preflight {
+    /// Reports the problems found in the restore script. Fails if any of them is an error.
+    pub(crate) fn run(self) -> anyhow::Result<ExitCode> {
+        let text = fs::read_to_string(&self.path)
+            .with_context(|| format!("failed to read {}", self.path.display()))?;
+
+        let existing_roles = match self.connection.as_deref() {
+            Some(_) if is_offline() => {
+                println!("note: not checking the roles against the target database with --offline");
+                None
+            }
+            Some(url) => Some(
+                connect(Some(url))?
+                    .query(ROLES_QUERY, &[])?
+                    .into_iter()
+                    .map(|row| row.get::<_, String>(0))
+                    .collect::<BTreeSet<String>>(),
+            ),
+            None => None,
+        };
+
+        let parse = parser::parse_source(&text);
+        let diagnostics = check_restore_script(&parse.stmts, &RestoreConfig { existing_roles });
+
+        for error in &parse.errors {
+            print_syntax_error(&self.path, &text, error);
+        }
+        for d in &diagnostics {
+            print_diagnostic(&self.path, &text, d);
+        }
+
+        let failed =
+            !parse.errors.is_empty() || diagnostics.iter().any(|d| d.severity == Severity::Error);
+        Ok(if failed {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
+        })
+    }
+}
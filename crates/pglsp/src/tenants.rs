@@ -0,0 +1,77 @@
+use std::process::ExitCode;
+
+use analyser::tenants::{check_tenants, is_tenant_schema, TENANT_SCHEMAS_QUERY};
+use analyser::Schema;
+
+use crate::db::{connect, load_schemas};
+use crate::flags;
+
+const DEFAULT_TEMPLATE: &str = "tenant_template";
+
+impl flags::Check {
+    /// Reports the drift of every tenant schema from the template. Fails if any tenant drifted.
+    pub(crate) fn run(self) -> anyhow::Result<ExitCode> {
+        let template = self.template.as_deref().unwrap_or(DEFAULT_TEMPLATE);
+        let pattern = self.tenants.as_deref().unwrap_or("%");
+
+        let mut client = connect(self.connection.as_deref())?;
+
+        let tenants = client
+            .query(TENANT_SCHEMAS_QUERY, &[&pattern])?
+            .into_iter()
+            .map(|row| row.get::<_, String>(0))
+            .filter(|name| is_tenant_schema(name, template))
+            .collect::<Vec<String>>();
+
+        let mut names = tenants.clone();
+        names.push(template.to_string());
+        let schemas = load_schemas(&mut client, &names)?;
+
+        let template_schema = match schemas.get(template) {
+            Some(schema) => schema.clone(),
+            None => {
+                if !client
+                    .query(
+                        "select 1 from pg_catalog.pg_namespace where nspname = $1",
+                        &[&template],
+                    )?
+                    .is_empty()
+                {
+                    Schema::new(template)
+                } else {
+                    anyhow::bail!("template schema {} does not exist", template);
+                }
+            }
+        };
+
+        let drift = check_tenants(
+            &template_schema,
+            tenants.iter().map(|t| t.as_str()),
+            &schemas,
+        );
+
+        for tenant in &drift {
+            println!(
+                "{}: {} difference(s) from {}",
+                tenant.tenant,
+                tenant.changes.len(),
+                template
+            );
+            for change in &tenant.changes {
+                println!("  {}", change);
+            }
+        }
+        println!(
+            "checked {} tenant(s) against {}, {} drifted",
+            tenants.len(),
+            template,
+            drift.len()
+        );
+
+        Ok(if drift.is_empty() {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        })
+    }
+}
@@ -0,0 +1,160 @@
+//! Runs the subcommands of `pglsp` on the files in `tests/data` with `--offline`, so that no
+//! database is needed. Commands that need one fail before they connect.
+
+use std::fs;
+use std::process::{Command, Output};
+
+/// Runs `pglsp --offline` with `args` in the directory of the crate
+fn pglsp(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_pglsp"))
+        .args(["--offline", "--color", "never"])
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .env_remove("DATABASE_URL")
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+/// Asserts that the command failed since it would have to connect to a database
+fn assert_offline(output: &Output) {
+    assert!(!output.status.success());
+    assert!(
+        stderr(output).contains("not connecting to the database with --offline"),
+        "{}",
+        stderr(output)
+    );
+}
+
+#[test]
+fn test_parse() {
+    let output = pglsp(&["parse", "tests/data/invalid/broken.sql"]);
+    assert!(!output.status.success());
+    assert!(stdout(&output).contains("tests/data/invalid/broken.sql\nerror: "));
+
+    let output = pglsp(&[
+        "parse",
+        "--dump-cst",
+        "tests/data/migrations/0001_create_contact.sql",
+    ]);
+    assert!(output.status.success());
+    assert!(stdout(&output).starts_with("SourceFile@0.."));
+}
+
+#[test]
+fn test_lint() {
+    let output = pglsp(&["lint", "tests/data/migrations"]);
+    assert!(output.status.success(), "{}", stdout(&output));
+
+    let output = pglsp(&["lint", "tests/data/invalid"]);
+    assert!(!output.status.success());
+    assert!(stdout(&output).contains("broken.sql"));
+}
+
+#[test]
+fn test_change_report() {
+    let output = pglsp(&["change-report", "tests/data/migrations"]);
+    assert!(output.status.success());
+    let report = stdout(&output);
+    assert!(report.starts_with("## Schema changes\n"));
+    assert!(report.contains("`0001_create_contact.sql:1`"));
+    assert!(report.contains("`0002_add_email.sql:1`"));
+}
+
+#[test]
+fn test_report() {
+    let output = pglsp(&["report", "tests/data/migrations"]);
+    assert!(output.status.success());
+    assert!(stdout(&output).starts_with("## Project health\n"));
+
+    let output = pglsp(&["report", "tests/data/migrations", "--format", "json"]);
+    assert!(output.status.success());
+    assert!(stdout(&output).starts_with("{\n"));
+
+    let output = pglsp(&["report", "tests/data/migrations", "--format", "html"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("unsupported report format `html`"));
+}
+
+#[test]
+fn test_index() {
+    let index = std::env::temp_dir().join(format!("pglsp-cli-{}.scip", std::process::id()));
+    let output = pglsp(&[
+        "index",
+        "tests/data/migrations",
+        "--output",
+        index.to_str().unwrap(),
+    ]);
+    let written = fs::read(&index);
+    let _ = fs::remove_file(&index);
+    assert!(output.status.success());
+    assert!(stdout(&output).starts_with("indexed 2 file(s) into "));
+    assert!(!written.unwrap().is_empty());
+}
+
+#[test]
+fn test_migrate_squash() {
+    let output = pglsp(&["migrate", "squash", "tests/data/migrations"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let sql = stdout(&output);
+    assert!(sql.contains("contact"));
+    assert!(sql.contains("email"));
+
+    let output = pglsp(&["migrate", "squash", "tests/data/migrations", "--to", "0"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("there are no migrations to squash"));
+}
+
+#[test]
+fn test_restore_preflight() {
+    let output = pglsp(&[
+        "restore",
+        "preflight",
+        "tests/data/restore/dump.sql",
+        "--connection",
+        "postgres://localhost/app",
+    ]);
+    assert!(!output.status.success());
+    let report = stdout(&output);
+    assert!(report.contains("note: not checking the roles against the target database"));
+    assert!(report.contains("is used before it is created"));
+}
+
+#[test]
+fn test_exec() {
+    // CREATE INDEX CONCURRENTLY is rejected before connecting
+    let output = pglsp(&["exec", "tests/data/exec/concurrent_index.sql"]);
+    assert!(!output.status.success());
+    assert!(stdout(&output).contains("run the file with --commit"));
+
+    let output = pglsp(&["exec", "tests/data/invalid/broken.sql"]);
+    assert!(!output.status.success());
+    assert!(stdout(&output).contains("broken.sql"));
+
+    assert_offline(&pglsp(&[
+        "exec",
+        "tests/data/migrations/0001_create_contact.sql",
+    ]));
+}
+
+#[test]
+fn test_tenants_check() {
+    assert_offline(&pglsp(&[
+        "tenants",
+        "check",
+        "--template",
+        "tenant_template",
+    ]));
+}
+
+#[test]
+fn test_bloat() {
+    assert_offline(&pglsp(&["bloat", "tests/data/migrations"]));
+}
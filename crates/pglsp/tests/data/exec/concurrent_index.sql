@@ -0,0 +1 @@
+create index concurrently contact_name on contact (name);
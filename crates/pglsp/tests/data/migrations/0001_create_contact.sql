@@ -0,0 +1,4 @@
+create table contact (
+    id bigint primary key,
+    name text not null
+);
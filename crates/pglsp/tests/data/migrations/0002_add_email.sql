@@ -0,0 +1 @@
+alter table contact add column email text;
@@ -0,0 +1,10 @@
+SET statement_timeout = 0;
+SET lock_timeout = 0;
+SET client_encoding = 'UTF8';
+SET standard_conforming_strings = on;
+SELECT pg_catalog.set_config('search_path', '', false);
+SET check_function_bodies = false;
+
+CREATE VIEW public.active_contact AS SELECT id FROM public.contact;
+CREATE TABLE public.contact (id bigint NOT NULL);
+ALTER TABLE public.contact OWNER TO app;
@@ -0,0 +1,71 @@
+//! Commands that show and interrupt the sessions of the database.
+//!
+//! `pglsp.activity` shows what the sessions are waiting on as the Markdown of
+//! `activity_markdown`, and `pglsp.cancelBackend` and `pglsp.terminateBackend` interrupt the
+//! session whose pid is given as the first argument. The database is given as the last argument
+//! of a command, or by `$DATABASE_URL`, and the commands run as the active role of the settings.
+
+use analyser::activity::{
+    activity_markdown, Activity, ACTIVITY_QUERY, CANCEL_BACKEND_QUERY, TERMINATE_BACKEND_QUERY,
+};
+use tower_lsp::jsonrpc::{Error, Result};
+
+use crate::db::{connect, database_error};
+
+pub const ACTIVITY_COMMAND: &str = "pglsp.activity";
+pub const CANCEL_BACKEND_COMMAND: &str = "pglsp.cancelBackend";
+pub const TERMINATE_BACKEND_COMMAND: &str = "pglsp.terminateBackend";
+
+/// Loads the sessions of the database at `url` as `role`, and renders them as Markdown
+pub async fn activity(url: Option<&str>, role: Option<&str>) -> Result<String> {
+    let client = connect(url, role).await?;
+    let sessions = client
+        .query(ACTIVITY_QUERY, &[])
+        .await
+        .map_err(database_error)?
+        .into_iter()
+        .map(|row| Activity {
+            pid: row.get("pid"),
+            user_name: row.get("user_name"),
+            application_name: row.get("application_name"),
+            state: row.get("state"),
+            wait_event_type: row.get("wait_event_type"),
+            wait_event: row.get("wait_event"),
+            transaction_secs: row.get("transaction_secs"),
+            query_secs: row.get("query_secs"),
+            query: row.get("query"),
+            blocked_by: row.get("blocked_by"),
+        })
+        .collect::<Vec<_>>();
+
+    Ok(activity_markdown(&sessions))
+}
+
+/// Cancels the query of the session `pid`, or terminates the session if `terminate` is true.
+/// Returns a message that tells what has been done.
+pub async fn signal_backend(
+    url: Option<&str>,
+    role: Option<&str>,
+    pid: i32,
+    terminate: bool,
+) -> Result<String> {
+    let client = connect(url, role).await?;
+    let query = if terminate {
+        TERMINATE_BACKEND_QUERY
+    } else {
+        CANCEL_BACKEND_QUERY
+    };
+    let rows = client.query(query, &[&pid]).await.map_err(database_error)?;
+    match rows.first().map(|row| row.get::<_, bool>(0)) {
+        Some(true) if terminate => Ok(format!("terminated session {}", pid)),
+        Some(true) => Ok(format!("cancelled the query of session {}", pid)),
+        Some(false) => Err(Error {
+            message: format!("failed to signal session {}", pid).into(),
+            ..Error::internal_error()
+        }),
+        None => Err(Error::invalid_params(format!(
+            "there is no client session with pid {}",
+            pid
+        ))),
+    }
+}
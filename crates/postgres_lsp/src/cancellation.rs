@@ -0,0 +1,239 @@
+//! Cancellation of requests with `$/cancelRequest`, and their latency budget.
+//!
+//! tower-lsp answers a request that the client cancels and drops the future of its handler, but it
+//! can only drop the future where the handler awaits. An analysis that runs without awaiting would
+//! therefore run to its end and hold up the edits behind it. Handlers that analyse a document call
+//! `checkpoint` between their stages, which yields to tower-lsp, and pass a `Cancellation` to
+//! parsing, schema resolution and linting, which check it between statements. The cancellation of
+//! a request is cancelled as soon as its document changes, once the client cancels the request,
+//! which [`CancelRequests`] tracks, and once the request ran longer than the `latencyBudgetMs`
+//! setting. A request that ran out of its budget is answered with `ServerCancelled`, so that the
+//! client sends it again, and the others with `RequestCancelled`.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use parser::Cancellation;
+use tower_lsp::jsonrpc::{Error, ErrorCode, Id, Request, Result};
+use tower_lsp::lsp_types::DiagnosticServerCancellationData;
+use tower_service::Service;
+
+/// The code of `ServerCancelled`, which LSP 3.17 added after the codes of tower-lsp
+const SERVER_CANCELLED: i64 = -32802;
+
+tokio::task_local! {
+    /// The cancellation of the request whose handler runs in the current task
+    static REQUEST: Cancellation;
+}
+
+/// Returns the cancellation of the request whose handler is running, which is cancelled once the
+/// client cancels the request
+pub fn current_request() -> Option<Cancellation> {
+    REQUEST.try_with(Cancellation::clone).ok()
+}
+
+/// Yields to the runtime, so that tower-lsp can drop a cancelled request, and returns the error of
+/// a cancelled request if `cancellation` has been cancelled
+pub async fn checkpoint(cancellation: &Cancellation) -> Result<()> {
+    tokio::task::yield_now().await;
+    check(cancellation)
+}
+
+/// Returns the error of a cancelled request if `cancellation` has been cancelled
+pub fn check(cancellation: &Cancellation) -> Result<()> {
+    cancellation
+        .check()
+        .map_err(|_| request_cancelled(cancellation))
+}
+
+/// Returns the error of a request that `cancellation` cancelled
+pub fn request_cancelled(cancellation: &Cancellation) -> Error {
+    if cancellation.is_expired() {
+        let data = DiagnosticServerCancellationData {
+            retrigger_request: true,
+        };
+        return Error {
+            code: ErrorCode::ServerError(SERVER_CANCELLED),
+            message: "the request ran out of its latency budget".into(),
+            data: serde_json::to_value(data).ok(),
+        };
+    }
+    Error {
+        code: ErrorCode::RequestCancelled,
+        message: "the request has been cancelled, or its document changed".into(),
+        data: None,
+    }
+}
+
+/// Wraps the service of the server, gives the handler of every request a cancellation in
+/// [`current_request`] and cancels it when the client sends `$/cancelRequest` for the request
+pub struct CancelRequests<S> {
+    inner: S,
+    /// The cancellations of the requests that are being handled, by their id
+    pending: Arc<Mutex<HashMap<Id, Cancellation>>>,
+}
+
+impl<S> CancelRequests<S> {
+    pub fn new(inner: S) -> Self {
+        CancelRequests {
+            inner,
+            pending: Arc::default(),
+        }
+    }
+}
+
+impl<S> Service<Request> for CancelRequests<S>
+where
+    S: Service<Request>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = std::result::Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        if request.method() == "$/cancelRequest" {
+            let id = request
+                .params()
+                .and_then(|params| params.get("id"))
+                .and_then(|id| serde_json::from_value::<Id>(id.clone()).ok());
+            if let Some(cancellation) = id.and_then(|id| self.pending.lock().unwrap().remove(&id)) {
+                cancellation.cancel();
+            }
+            return Box::pin(self.inner.call(request));
+        }
+        let Some(id) = request.id().cloned() else {
+            return Box::pin(self.inner.call(request));
+        };
+        let cancellation = Cancellation::new();
+        self.pending
+            .lock()
+            .unwrap()
+            .insert(id.clone(), cancellation.clone());
+        let pending = self.pending.clone();
+        let response = REQUEST.scope(cancellation, self.inner.call(request));
+        Box::pin(async move {
+            let response = response.await;
+            pending.lock().unwrap().remove(&id);
+            response
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::time::Duration;
+
+    use serde_json::{json, Value};
+    use tower_lsp::jsonrpc::Response;
+
+    use super::*;
+
+    /// A handler that answers `expire` once it ran out of its budget, and other requests once they
+    /// have been cancelled
+    struct Handler;
+
+    impl Service<Request> for Handler {
+        type Response = Option<Response>;
+        type Error = Infallible;
+        type Future =
+            Pin<Box<dyn Future<Output = std::result::Result<Option<Response>, Infallible>> + Send>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<std::result::Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: Request) -> Self::Future {
+            Box::pin(async move {
+                let Some(id) = request.id().cloned() else {
+                    return Ok(None);
+                };
+                let result = match current_request() {
+                    None => Err(Error::internal_error()),
+                    Some(cancellation) if request.method() == "expire" => {
+                        checkpoint(&cancellation.with_budget(Duration::ZERO)).await
+                    }
+                    Some(cancellation) => loop {
+                        if let Err(error) = checkpoint(&cancellation).await {
+                            break Err(error);
+                        }
+                        tokio::time::sleep(Duration::from_millis(1)).await;
+                    },
+                };
+                Ok(Some(match result {
+                    Ok(()) => Response::from_ok(id, Value::Null),
+                    Err(error) => Response::from_error(id, error),
+                }))
+            })
+        }
+    }
+
+    fn error(response: Option<Response>) -> Error {
+        let (_, result) = response.unwrap().into_parts();
+        result.unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn test_cancel_request() {
+        let mut service = CancelRequests::new(Handler);
+        let response =
+            tokio::spawn(service.call(Request::build("wait").id(Id::Number(1)).finish()));
+        assert_eq!(service.pending.lock().unwrap().len(), 1);
+
+        // requests that are not pending are ignored
+        let cancel = |id| {
+            Request::build("$/cancelRequest")
+                .params(json!({ "id": id }))
+                .finish()
+        };
+        assert!(service.call(cancel(2)).await.unwrap().is_none());
+        assert_eq!(service.pending.lock().unwrap().len(), 1);
+
+        assert!(service.call(cancel(1)).await.unwrap().is_none());
+        let error = error(response.await.unwrap().unwrap());
+        assert_eq!(error.code, ErrorCode::RequestCancelled);
+        assert!(service.pending.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_latency_budget() {
+        let mut service = CancelRequests::new(Handler);
+        let request = Request::build("expire").id(Id::Number(1)).finish();
+        let error = error(service.call(request).await.unwrap());
+        assert_eq!(error.code, ErrorCode::ServerError(SERVER_CANCELLED));
+        assert_eq!(error.data, Some(json!({ "retriggerRequest": true })));
+        assert!(service.pending.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint() {
+        // there is no request outside of a handler
+        assert!(current_request().is_none());
+
+        let cancellation = Cancellation::new();
+        assert!(checkpoint(&cancellation).await.is_ok());
+        let budget = cancellation.with_budget(Duration::ZERO);
+        assert_eq!(
+            checkpoint(&budget).await.unwrap_err().code,
+            ErrorCode::ServerError(SERVER_CANCELLED)
+        );
+        // a request that is cancelled is not retriggered, even if its budget ran out too
+        cancellation.cancel();
+        assert_eq!(
+            checkpoint(&cancellation).await.unwrap_err().code,
+            ErrorCode::RequestCancelled
+        );
+        assert_eq!(
+            checkpoint(&budget).await.unwrap_err().code,
+            ErrorCode::RequestCancelled
+        );
+    }
+}
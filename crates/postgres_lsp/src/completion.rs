@@ -0,0 +1,83 @@
+//! Completion of keywords, statement skeletons, functions, roles and the object names within
+//! strings, as computed by the analyser.
+
+use analyser::completion::{completions, function_completions, role_completions, CompletionKind};
+use analyser::name_arguments::{name_argument_completions, NameKind};
+use analyser::Function;
+use parser::{Parse, TextSize};
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+
+use crate::utils::text_range_to_range;
+
+/// Returns the completions at `offset`, including calls of `functions`, the `roles` and the
+/// `objects` whose names functions take as strings. Snippets are left out unless the client
+/// supports them, and functions are inserted without their arguments then.
+pub fn completion(
+    rope: &Rope,
+    parse: &Parse,
+    offset: TextSize,
+    functions: &[Function],
+    roles: &[String],
+    objects: &[(NameKind, String)],
+    snippet_support: bool,
+) -> Option<CompletionResponse> {
+    let items = completions(&parse.cst, offset)
+        .into_iter()
+        .chain(function_completions(&parse.cst, offset, functions))
+        .chain(role_completions(&parse.cst, offset, roles))
+        .chain(name_argument_completions(
+            &parse.cst,
+            &parse.stmts,
+            offset,
+            objects,
+        ))
+        .filter(|c| snippet_support || c.kind != CompletionKind::Snippet)
+        .map(|c| {
+            let (kind, format, new_text) = match c.kind {
+                CompletionKind::Keyword => (
+                    CompletionItemKind::KEYWORD,
+                    InsertTextFormat::PLAIN_TEXT,
+                    c.insert_text,
+                ),
+                CompletionKind::Snippet => (
+                    CompletionItemKind::SNIPPET,
+                    InsertTextFormat::SNIPPET,
+                    c.insert_text,
+                ),
+                CompletionKind::Function if snippet_support => (
+                    CompletionItemKind::FUNCTION,
+                    InsertTextFormat::SNIPPET,
+                    c.insert_text,
+                ),
+                CompletionKind::Function => (
+                    CompletionItemKind::FUNCTION,
+                    InsertTextFormat::PLAIN_TEXT,
+                    c.label[..c.label.find('(').unwrap_or(c.label.len())].to_string(),
+                ),
+                CompletionKind::Role => (
+                    CompletionItemKind::VALUE,
+                    InsertTextFormat::PLAIN_TEXT,
+                    c.insert_text,
+                ),
+                CompletionKind::ObjectName => (
+                    CompletionItemKind::REFERENCE,
+                    InsertTextFormat::PLAIN_TEXT,
+                    c.insert_text,
+                ),
+            };
+            Some(CompletionItem {
+                label: c.label,
+                kind: Some(kind),
+                detail: c.detail,
+                insert_text_format: Some(format),
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                    range: text_range_to_range(c.range, rope)?,
+                    new_text,
+                })),
+                ..CompletionItem::default()
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(CompletionResponse::Array(items))
+}
@@ -0,0 +1,226 @@
+//! Connections of the commands to a live database.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::future::poll_fn;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use analyser::restore::ROLES_QUERY;
+use analyser::table_rewrite::{TableSize, TABLE_SIZES_QUERY};
+use analyser::{
+    CastGraph, CatalogCast, CatalogColumn, CatalogConstraint, CatalogIndex, Function, Schema, View,
+    FUNCTIONS_QUERY, PG_CAST_QUERY, SCHEMA_COLUMNS_QUERY, SCHEMA_CONSTRAINTS_QUERY,
+    SCHEMA_INDEXES_QUERY, SCHEMA_VIEWS_QUERY,
+};
+use parser::make::quote_ident;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio_postgres::error::DbError;
+use tokio_postgres::{AsyncMessage, Client, NoTls};
+use tower_lsp::jsonrpc::{Error, Result};
+
+/// Set by the `offline` setting, after which no connection is opened for the rest of the session
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Forbids all connections for the rest of the session. Offline mode cannot be left again.
+pub fn set_offline() {
+    OFFLINE.store(true, Ordering::SeqCst);
+}
+
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::SeqCst)
+}
+
+/// Connects to the database at `url`, falling back to `$DATABASE_URL`. With a `role`, the session
+/// runs as that role, so that permissions are checked as for it, e.g. for the application role.
+/// Fails in offline mode.
+pub async fn connect(url: Option<&str>, role: Option<&str>) -> Result<Client> {
+    let (client, _) = connect_with_notices(url, role).await?;
+    Ok(client)
+}
+
+/// Connects like [`connect`] and returns the notices that the server sends, e.g. for `RAISE
+/// NOTICE`, which are only logged otherwise
+pub async fn connect_with_notices(
+    url: Option<&str>,
+    role: Option<&str>,
+) -> Result<(Client, UnboundedReceiver<DbError>)> {
+    if is_offline() {
+        return Err(Error {
+            message: "the server is offline and does not connect to databases".into(),
+            ..Error::invalid_request()
+        });
+    }
+    let url = match url {
+        Some(url) => url.to_string(),
+        None => env::var("DATABASE_URL").map_err(|_| {
+            Error::invalid_params("no connection string given as argument or $DATABASE_URL")
+        })?,
+    };
+    let (client, mut connection) = tokio_postgres::connect(&url, NoTls)
+        .await
+        .map_err(database_error)?;
+    let (sender, notices) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        // the messages are polled one by one, since awaiting the connection drops the notices
+        while let Some(message) = poll_fn(|cx| connection.poll_message(cx)).await {
+            match message {
+                Ok(AsyncMessage::Notice(notice)) => {
+                    if let Err(err) = sender.send(notice) {
+                        log::info!("{}: {}", err.0.severity(), err.0.message());
+                    }
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    log::error!("connection error: {}", err);
+                    break;
+                }
+            }
+        }
+    });
+    if let Some(role) = role {
+        client
+            .batch_execute(&format!("SET ROLE {}", quote_ident(role)))
+            .await
+            .map_err(database_error)?;
+    }
+    Ok((client, notices))
+}
+
+/// Loads the tables of all `schemas`. Schemas without any table are not part of the result.
+pub async fn load_schemas(client: &Client, schemas: &[String]) -> Result<BTreeMap<String, Schema>> {
+    let columns = client
+        .query(SCHEMA_COLUMNS_QUERY, &[&schemas])
+        .await
+        .map_err(database_error)?
+        .into_iter()
+        .map(|row| CatalogColumn {
+            schema_name: row.get("schema_name"),
+            table_name: row.get("table_name"),
+            column_name: row.get("column_name"),
+            data_type: row.get("data_type"),
+            not_null: row.get("not_null"),
+            default_expr: row.get("default_expr"),
+            access_method: row.get("access_method"),
+            owner: row.get("owner"),
+            owned_sequence: row.get("owned_sequence"),
+            partition_key: row.get("partition_key"),
+            fillfactor: row.get("fillfactor"),
+        })
+        .collect();
+    let indexes = client
+        .query(SCHEMA_INDEXES_QUERY, &[&schemas])
+        .await
+        .map_err(database_error)?
+        .into_iter()
+        .map(|row| CatalogIndex {
+            schema_name: row.get("schema_name"),
+            table_name: row.get("table_name"),
+            index_name: row.get("index_name"),
+            definition: row.get("definition"),
+            is_clustered: row.get("is_clustered"),
+        })
+        .collect();
+    let constraints = client
+        .query(SCHEMA_CONSTRAINTS_QUERY, &[&schemas])
+        .await
+        .map_err(database_error)?
+        .into_iter()
+        .map(|row| CatalogConstraint {
+            schema_name: row.get("schema_name"),
+            table_name: row.get("table_name"),
+            constraint_name: row.get("constraint_name"),
+            definition: row.get("definition"),
+        })
+        .collect();
+    Ok(Schema::from_catalog(columns, indexes, constraints))
+}
+
+/// Loads the functions of all `schemas` and the built-in ones
+pub async fn load_functions(client: &Client, schemas: &[String]) -> Result<Vec<Function>> {
+    Ok(client
+        .query(FUNCTIONS_QUERY, &[&schemas])
+        .await
+        .map_err(database_error)?
+        .into_iter()
+        .map(|row| Function {
+            schema_name: row.get("schema_name"),
+            name: row.get("function_name"),
+            arguments: row.get("arguments"),
+            result_type: row.get("result_type"),
+            is_aggregate: row.get("is_aggregate"),
+            comment: row.get("comment"),
+        })
+        .collect())
+}
+
+/// Loads the names of all roles except the predefined ones, e.g. `pg_read_all_data`
+pub async fn load_roles(client: &Client) -> Result<Vec<String>> {
+    Ok(client
+        .query(ROLES_QUERY, &[])
+        .await
+        .map_err(database_error)?
+        .into_iter()
+        .map(|row| row.get::<_, String>("rolname"))
+        .filter(|role| !role.starts_with("pg_"))
+        .collect())
+}
+
+/// Loads the views and materialized views of all `schemas`
+pub async fn load_views(client: &Client, schemas: &[String]) -> Result<Vec<View>> {
+    Ok(client
+        .query(SCHEMA_VIEWS_QUERY, &[&schemas])
+        .await
+        .map_err(database_error)?
+        .into_iter()
+        .map(|row| View {
+            schema_name: row.get("schema_name"),
+            name: row.get("view_name"),
+            definition: row.get("definition"),
+            is_materialized: row.get("is_materialized"),
+        })
+        .collect())
+}
+
+/// Loads the estimated sizes of the tables of all `schemas`
+pub async fn load_table_sizes(client: &Client, schemas: &[String]) -> Result<Vec<TableSize>> {
+    Ok(client
+        .query(TABLE_SIZES_QUERY, &[&schemas])
+        .await
+        .map_err(database_error)?
+        .into_iter()
+        .map(|row| TableSize {
+            schema_name: row.get("schema_name"),
+            table_name: row.get("table_name"),
+            row_estimate: row.get("row_estimate"),
+            table_bytes: row.get("table_bytes"),
+        })
+        .collect())
+}
+
+/// Loads all casts of the `pg_cast` catalog into a cast graph
+pub async fn load_casts(client: &Client) -> Result<CastGraph> {
+    let mut casts = CastGraph::new();
+    for row in client
+        .query(PG_CAST_QUERY, &[])
+        .await
+        .map_err(database_error)?
+    {
+        casts.add_catalog_cast(&CatalogCast {
+            source_schema: row.get("source_schema"),
+            source_name: row.get("source_name"),
+            target_schema: row.get("target_schema"),
+            target_name: row.get("target_name"),
+            context: row.get("context"),
+            method: row.get("method"),
+            function: row.get("function"),
+        });
+    }
+    Ok(casts)
+}
+
+pub fn database_error(err: tokio_postgres::Error) -> Error {
+    Error {
+        message: format!("database error: {}", err).into(),
+        ..Error::internal_error()
+    }
+}
@@ -0,0 +1,129 @@
+//! Rewrites of the DDL statements of a document, see [`analyser::ddl_rewrite`].
+//!
+//! The selected statements can be wrapped in a transaction, and the statement at the cursor gets
+//! `IF NOT EXISTS` or `IF EXISTS`. `CONCURRENTLY` is only offered in migration files, i.e. files
+//! whose name starts with a version, because a concurrent index build is slower and is only worth
+//! it on tables that are already in use.
+
+use analyser::ddl_rewrite::{add_concurrently, add_if_exists, wrap_in_transaction, Edit};
+use analyser::migrations::migration_version;
+use parser::TextRange;
+use tower_lsp::lsp_types::*;
+
+use crate::rename::Document;
+use crate::utils::text_range_to_range;
+
+/// Returns the DDL rewrites of the statements within `range` of `document`
+pub fn ddl_actions(document: &Document<'_>, range: TextRange) -> Vec<CodeAction> {
+    let cst = &document.parse.cst;
+    let stmts = &document.parse.stmts;
+    let is_migration = document
+        .uri
+        .path_segments()
+        .and_then(|segments| segments.last())
+        .and_then(migration_version)
+        .is_some();
+
+    let mut actions = Vec::new();
+    if let Some(edits) = wrap_in_transaction(cst, stmts, range) {
+        actions.extend(action("Wrap in a transaction", document, &edits));
+    }
+    if let Some(edit) = add_if_exists(cst, stmts, range.start()) {
+        let title = format!("Add {}", edit.text.trim().to_uppercase());
+        actions.extend(action(&title, document, &[edit]));
+    }
+    if let Some(edit) = add_concurrently(cst, stmts, range.start()).filter(|_| is_migration) {
+        actions.extend(action("Create the index concurrently", document, &[edit]));
+    }
+    actions
+}
+
+fn action(title: &str, document: &Document<'_>, edits: &[Edit]) -> Option<CodeAction> {
+    let edits = edits
+        .iter()
+        .map(|edit| {
+            Some(TextEdit::new(
+                text_range_to_range(edit.range, document.rope)?,
+                edit.text.clone(),
+            ))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(CodeAction {
+        title: title.to_string(),
+        kind: Some(CodeActionKind::REFACTOR_REWRITE),
+        edit: Some(WorkspaceEdit {
+            changes: Some([(document.uri.clone(), edits)].into_iter().collect()),
+            ..WorkspaceEdit::default()
+        }),
+        ..CodeAction::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::{parse_source, TextSize};
+    use ropey::Rope;
+
+    use super::*;
+    use crate::utils::apply_action;
+
+    /// Returns the text of the document `uri` after the action with `title` at `range`, if it is
+    /// offered
+    fn apply(uri: &str, text: &str, range: TextRange, title: &str) -> Option<String> {
+        let uri = Url::parse(uri).unwrap();
+        let rope = Rope::from_str(text);
+        let parse = parse_source(text);
+        let document = Document {
+            uri: uri.clone(),
+            rope: &rope,
+            parse: &parse,
+        };
+        ddl_actions(&document, range)
+            .iter()
+            .find(|action| action.title == title)
+            .map(|action| apply_action(&rope, &uri, action))
+    }
+
+    #[test]
+    fn test_ddl_actions() {
+        let text = "create table note (body text);
+alter table note add column title text;
+create index concurrently on note (title);";
+        let range = TextRange::new(5.into(), 40.into());
+        assert_eq!(
+            apply(
+                "file:///db/schema.sql",
+                text,
+                range,
+                "Wrap in a transaction"
+            )
+            .as_deref(),
+            Some(
+                "begin;
+create table note (body text);
+alter table note add column title text;
+commit;
+create index concurrently on note (title);"
+            )
+        );
+        assert_eq!(
+            apply("file:///db/schema.sql", text, range, "Add IF NOT EXISTS").as_deref(),
+            Some(
+                text.replacen("create table", "create table if not exists", 1)
+                    .as_str()
+            )
+        );
+    }
+
+    #[test]
+    fn test_concurrently_in_migrations() {
+        let text = "create index note_body on note (body);";
+        let at_start = TextRange::empty(TextSize::from(0));
+        let title = "Create the index concurrently";
+        assert_eq!(
+            apply("file:///db/0002_note_body.sql", text, at_start, title).as_deref(),
+            Some("create index concurrently note_body on note (body);")
+        );
+        assert_eq!(apply("file:///db/schema.sql", text, at_start, title), None);
+    }
+}
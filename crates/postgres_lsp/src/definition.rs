@@ -0,0 +1,46 @@
+//! Go to the definition of tables, views, functions and columns.
+//!
+//! Tables, views and functions are defined by the `CREATE` statements of any sql file of the
+//! workspace, as found in the [`WorkspaceIndex`]. This includes the parents of `INHERITS` and the
+//! tables of `LIKE`, so that the columns a table takes from them can be looked up. A column is
+//! defined by the `CREATE TABLE` statement that has the same identity as the reference, in any of
+//! the open documents.
+
+use analyser::definitions::reference_at;
+use analyser::moniker::{symbol_at, symbols};
+use parser::TextSize;
+use tower_lsp::lsp_types::*;
+
+use crate::rename::Document;
+use crate::utils::text_range_to_range;
+use crate::workspace_index::WorkspaceIndex;
+
+/// Returns the definitions of the object at `offset` of `document` within the workspace
+pub fn definition(
+    index: &WorkspaceIndex,
+    documents: &[Document<'_>],
+    document: &Document<'_>,
+    offset: TextSize,
+) -> Option<GotoDefinitionResponse> {
+    if let Some(reference) = reference_at(&document.parse.cst, &document.parse.stmts, offset) {
+        let locations = index.definitions(&reference);
+        return (!locations.is_empty()).then_some(GotoDefinitionResponse::Array(locations));
+    }
+
+    let symbol = symbol_at(&document.parse.cst, &document.parse.stmts, offset)?;
+    let locations = documents
+        .iter()
+        .flat_map(|doc| {
+            symbols(&doc.parse.cst, &doc.parse.stmts)
+                .into_iter()
+                .filter(|(_, s)| s.is_definition && s.identifier == symbol.identifier)
+                .filter_map(|(range, _)| {
+                    Some(Location {
+                        uri: doc.uri.clone(),
+                        range: text_range_to_range(range, doc.rope)?,
+                    })
+                })
+        })
+        .collect::<Vec<_>>();
+    (!locations.is_empty()).then_some(GotoDefinitionResponse::Array(locations))
+}
@@ -0,0 +1,61 @@
+//! Outline of the statements of a document.
+//!
+//! Every statement is a symbol whose detail is the badge of its kind, e.g. `DDL`, so that schema
+//! changes stand out from queries in the outline of a migration. Its children are the common table
+//! expressions, columns and function parameters of the statement.
+
+use analyser::outline::{outline, OutlineItem, OutlineKind};
+use parser::{Parse, StmtKind};
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+
+use crate::utils::text_range_to_range;
+
+/// Returns a symbol for every statement of the document
+pub fn document_symbols(rope: &Rope, parse: &Parse) -> Vec<DocumentSymbol> {
+    outline(&parse.cst, &parse.stmts)
+        .into_iter()
+        .filter_map(|item| document_symbol(item, rope))
+        .collect()
+}
+
+fn document_symbol(item: OutlineItem, rope: &Rope) -> Option<DocumentSymbol> {
+    let (kind, detail) = match item.kind {
+        OutlineKind::Statement(stmt_kind) => (
+            symbol_kind(stmt_kind),
+            stmt_kind.map(|kind| kind.to_string()),
+        ),
+        OutlineKind::CommonTableExpr => (SymbolKind::OBJECT, item.detail),
+        OutlineKind::Column => (SymbolKind::FIELD, item.detail),
+        OutlineKind::Parameter => (SymbolKind::VARIABLE, item.detail),
+    };
+    let children = item
+        .children
+        .into_iter()
+        .filter_map(|child| document_symbol(child, rope))
+        .collect::<Vec<_>>();
+    // `deprecated` has been replaced by `tags`, but has no default
+    #[allow(deprecated)]
+    let symbol = DocumentSymbol {
+        name: item.label,
+        detail,
+        kind,
+        tags: None,
+        deprecated: None,
+        range: text_range_to_range(item.range, rope)?,
+        selection_range: text_range_to_range(item.selection_range, rope)?,
+        children: (!children.is_empty()).then_some(children),
+    };
+    Some(symbol)
+}
+
+/// Picks an icon for the statement kind, since there are no symbol kinds for statements
+fn symbol_kind(stmt_kind: Option<StmtKind>) -> SymbolKind {
+    match stmt_kind {
+        Some(StmtKind::Ddl) => SymbolKind::STRUCT,
+        Some(StmtKind::Dml) => SymbolKind::FUNCTION,
+        Some(StmtKind::Dcl) => SymbolKind::KEY,
+        Some(StmtKind::Tcl) => SymbolKind::EVENT,
+        Some(StmtKind::Utility) | None => SymbolKind::OPERATOR,
+    }
+}
@@ -0,0 +1,347 @@
+//! The `pglsp.executeStatement` command, which runs the statement at a position of a document.
+//!
+//! The arguments are the uri of the document, the position, and optionally a partial result
+//! token. Executing is opt-in with the `pglsp.execution.enabled` setting. The statement runs
+//! against the database of the document as the active role, in a read-only transaction unless
+//! `pglsp.execution.readWrite` is set, which is committed afterwards. Queries are fetched from a
+//! cursor in pages, which are sent with `$/progress` as partial results if the client gave a
+//! token, so that the first rows show up while the rest are still being fetched. The rows stop at
+//! `maxRows`, and the statement is cancelled after `timeoutMs`, which returns the rows fetched so
+//! far and rolls the transaction back. Other statements stream their rows in the same pages. The
+//! notices that the statement raises are reported as diagnostics of the statement. The server
+//! checks the statement against the `[policy]` of `pglsp.toml` before it gets here.
+
+use std::time::{Duration, Instant};
+
+use analyser::execution_error::{execution_error_diagnostic, ServerError, SqlStateMapping};
+use analyser::LintDiagnostic;
+use futures_util::{pin_mut, StreamExt};
+use parser::RawStmt;
+use serde::{Deserialize, Serialize};
+use tokio_postgres::error::{DbError, SqlState};
+use tokio_postgres::{SimpleQueryMessage, SimpleQueryRow};
+use tower_lsp::jsonrpc::Result;
+use tower_lsp::lsp_types::notification::Notification;
+use tower_lsp::lsp_types::ProgressToken;
+
+use crate::db::{connect_with_notices, database_error};
+use crate::settings::ExecutionSettings;
+
+pub const EXECUTE_STATEMENT_COMMAND: &str = "pglsp.executeStatement";
+
+/// The name of the cursor that queries are fetched from
+const CURSOR: &str = "pglsp_execute";
+
+/// Rows as text, with `None` for null
+type Rows = Vec<Vec<Option<String>>>;
+
+/// A page of rows, sent as partial result
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RowPage {
+    pub columns: Vec<String>,
+    pub rows: Rows,
+}
+
+#[derive(Debug)]
+pub enum RowPageNotification {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RowPageParams {
+    /// The partial result token of the command
+    pub token: ProgressToken,
+    pub value: RowPage,
+}
+
+impl Notification for RowPageNotification {
+    type Params = RowPageParams;
+    const METHOD: &'static str = "$/progress";
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecuteResult {
+    pub columns: Vec<String>,
+    /// The rows that have not been sent as partial results, which are all without a token
+    pub rows: Rows,
+    /// Whether the rows stopped at the limit of `maxRows` with more to come
+    pub truncated: bool,
+    /// Whether the rows stopped since the statement ran out of time
+    pub timed_out: bool,
+    /// The number of rows that the statement changed or returned
+    pub row_count: Option<u64>,
+}
+
+/// Executes `sql` against the database at `url` as `role` and returns the result with the
+/// notices that the server raised. `is_query` tells whether `sql` can be fetched from a cursor.
+/// Pages of rows are sent to `client` if there is a `token`.
+pub async fn execute_statement(
+    url: Option<&str>,
+    role: Option<&str>,
+    sql: &str,
+    is_query: bool,
+    settings: &ExecutionSettings,
+    client: &tower_lsp::Client,
+    token: Option<ProgressToken>,
+) -> Result<(ExecuteResult, Vec<DbError>)> {
+    let (db, mut notices) = connect_with_notices(url, role).await?;
+    let access = if settings.read_write {
+        "READ WRITE"
+    } else {
+        "READ ONLY"
+    };
+    db.batch_execute(&format!(
+        "BEGIN {}; SET LOCAL statement_timeout = {}",
+        access, settings.timeout_ms
+    ))
+    .await
+    .map_err(database_error)?;
+
+    let page_size = settings.page_size.max(1);
+    let mut pages = Pages::new(token.is_some(), settings.max_rows);
+    if is_query {
+        db.batch_execute(&format!("DECLARE {} NO SCROLL CURSOR FOR {}", CURSOR, sql))
+            .await
+            .map_err(database_error)?;
+        let deadline = Instant::now() + Duration::from_millis(settings.timeout_ms);
+        loop {
+            if Instant::now() >= deadline {
+                pages.result.timed_out = true;
+                break;
+            }
+            let count = pages.fetch_count(page_size);
+            let messages = match db
+                .simple_query(&format!("FETCH {} FROM {}", count, CURSOR))
+                .await
+            {
+                Ok(messages) => messages,
+                Err(err) if is_timeout(&err) => {
+                    pages.result.timed_out = true;
+                    break;
+                }
+                Err(err) => return Err(database_error(err)),
+            };
+            let (columns, rows) = text_rows(messages);
+            let is_last = rows.len() < count;
+            if let Some(page) = pages.push(columns, rows) {
+                send_page(client, &token, page).await;
+            }
+            if pages.result.truncated || is_last {
+                break;
+            }
+        }
+        pages.result.row_count = Some(pages.count as u64);
+    } else {
+        let messages = db.simple_query_raw(sql).await.map_err(database_error)?;
+        pin_mut!(messages);
+        let mut columns = Vec::new();
+        let mut page = Vec::new();
+        while let Some(message) = messages.next().await {
+            match message {
+                Ok(SimpleQueryMessage::Row(row)) => {
+                    // the rows beyond the limit are skipped until the statement completes
+                    if pages.result.truncated {
+                        continue;
+                    }
+                    if columns.is_empty() {
+                        columns = column_names(&row);
+                    }
+                    page.push(text_row(&row));
+                    if page.len() == page_size {
+                        let rows = std::mem::take(&mut page);
+                        if let Some(page) = pages.push(columns.clone(), rows) {
+                            send_page(client, &token, page).await;
+                        }
+                    }
+                }
+                Ok(SimpleQueryMessage::CommandComplete(count)) => {
+                    pages.result.row_count = Some(count);
+                }
+                Ok(_) => {}
+                Err(err) if is_timeout(&err) => {
+                    pages.result.timed_out = true;
+                    break;
+                }
+                Err(err) => return Err(database_error(err)),
+            }
+        }
+        if !page.is_empty() {
+            if let Some(page) = pages.push(columns, page) {
+                send_page(client, &token, page).await;
+            }
+        }
+    }
+    // the notices arrive before the statements that raised them complete, and a statement that
+    // ran out of time has aborted the transaction
+    let end = if pages.result.timed_out {
+        "ROLLBACK"
+    } else {
+        "COMMIT"
+    };
+    db.batch_execute(end).await.map_err(database_error)?;
+
+    let mut raised = Vec::new();
+    while let Ok(notice) = notices.try_recv() {
+        raised.push(notice);
+    }
+    Ok((pages.result, raised))
+}
+
+/// Returns the notices raised by `stmt` as diagnostics
+///
+/// `source` is the text that `stmt` has been parsed from.
+pub fn notice_diagnostics(
+    stmt: &RawStmt,
+    source: &str,
+    notices: &[DbError],
+) -> Vec<LintDiagnostic> {
+    notices
+        .iter()
+        .map(|notice| {
+            let notice = ServerError {
+                code: notice.code().code().to_string(),
+                message: notice.message().to_string(),
+                detail: notice.detail().map(str::to_string),
+                hint: notice.hint().map(str::to_string),
+                // the statement has been sent in a cursor, so the positions do not match its text
+                position: None,
+            };
+            execution_error_diagnostic(stmt, source, &notice, &SqlStateMapping::default())
+        })
+        .collect()
+}
+
+/// Returns true if `err` cancelled the statement since it exceeded `statement_timeout`
+fn is_timeout(err: &tokio_postgres::Error) -> bool {
+    err.code() == Some(&SqlState::QUERY_CANCELED)
+}
+
+/// Sends `page` as partial result on `token`
+async fn send_page(client: &tower_lsp::Client, token: &Option<ProgressToken>, page: RowPage) {
+    if let Some(token) = token {
+        client
+            .send_notification::<RowPageNotification>(RowPageParams {
+                token: token.clone(),
+                value: page,
+            })
+            .await;
+    }
+}
+
+/// Collects the rows of a statement into pages up to a limit
+struct Pages {
+    /// Whether the pages are sent as partial results rather than added to the result
+    partial: bool,
+    result: ExecuteResult,
+    /// The rows that have been sent or added to the result
+    count: usize,
+    max_rows: usize,
+}
+
+impl Pages {
+    fn new(partial: bool, max_rows: usize) -> Pages {
+        Pages {
+            partial,
+            result: ExecuteResult::default(),
+            count: 0,
+            max_rows,
+        }
+    }
+
+    /// Returns the number of rows to fetch next, which is one more than the limit allows so that
+    /// the result shows whether there are more rows
+    fn fetch_count(&self, page_size: usize) -> usize {
+        page_size.min(self.max_rows + 1 - self.count)
+    }
+
+    /// Adds `rows` up to the limit, and marks the result as truncated if they exceed it. Returns the
+    /// rows as a page to send if the pages are partial results.
+    fn push(&mut self, columns: Vec<String>, mut rows: Rows) -> Option<RowPage> {
+        let remaining = self.max_rows - self.count;
+        if rows.len() > remaining {
+            rows.truncate(remaining);
+            self.result.truncated = true;
+        }
+        self.count += rows.len();
+        if !columns.is_empty() {
+            self.result.columns = columns.clone();
+        }
+        if self.partial && !rows.is_empty() {
+            Some(RowPage { columns, rows })
+        } else {
+            self.result.rows.extend(rows);
+            None
+        }
+    }
+}
+
+/// Returns the columns and the rows of `messages` as text
+fn text_rows(messages: Vec<SimpleQueryMessage>) -> (Vec<String>, Rows) {
+    let mut columns = Vec::new();
+    let mut rows = Vec::new();
+    for message in messages {
+        if let SimpleQueryMessage::Row(row) = message {
+            if columns.is_empty() {
+                columns = column_names(&row);
+            }
+            rows.push(text_row(&row));
+        }
+    }
+    (columns, rows)
+}
+
+fn column_names(row: &SimpleQueryRow) -> Vec<String> {
+    row.columns().iter().map(|c| c.name().to_string()).collect()
+}
+
+fn text_row(row: &SimpleQueryRow) -> Vec<Option<String>> {
+    (0..row.len())
+        .map(|idx| row.get(idx).map(str::to_string))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(count: usize) -> Rows {
+        (0..count).map(|i| vec![Some(i.to_string())]).collect()
+    }
+
+    #[test]
+    fn test_pages_limit() {
+        let columns = vec!["id".to_string()];
+        let mut pages = Pages::new(false, 5);
+        assert_eq!(pages.fetch_count(3), 3);
+        assert_eq!(pages.push(columns.clone(), rows(3)), None);
+        // the last fetch asks for one row more than the limit
+        assert_eq!(pages.fetch_count(3), 3);
+        assert_eq!(pages.push(columns.clone(), rows(3)), None);
+        assert!(pages.result.truncated);
+        assert_eq!(pages.count, 5);
+        assert_eq!(pages.result.rows.len(), 5);
+        assert_eq!(pages.result.columns, columns);
+
+        let mut pages = Pages::new(false, 5);
+        pages.push(columns.clone(), rows(5));
+        assert!(!pages.result.truncated);
+        assert_eq!(pages.fetch_count(3), 1);
+        pages.push(Vec::new(), Vec::new());
+        assert!(!pages.result.truncated);
+        assert_eq!(pages.result.columns, columns);
+    }
+
+    #[test]
+    fn test_pages_partial() {
+        let columns = vec!["id".to_string()];
+        let mut pages = Pages::new(true, 4);
+        let page = pages.push(columns.clone(), rows(3)).unwrap();
+        assert_eq!(page.rows.len(), 3);
+        let page = pages.push(columns.clone(), rows(3)).unwrap();
+        assert_eq!(page.rows, rows(1));
+        assert!(pages.result.truncated);
+        assert!(pages.result.rows.is_empty());
+        assert_eq!(pages.push(columns, Vec::new()), None);
+    }
+}
@@ -0,0 +1,197 @@
+//! The `pglsp.explain` command, which explains the statement at a position of a document.
+//!
+//! The arguments are the uri of the document, the position, and optionally the values of the
+//! placeholders of the statement as strings, numbers or booleans, with `null` for `NULL`. The statement is explained with
+//! `EXPLAIN (FORMAT JSON)` against the database of the document as the active role. The result
+//! has the plan as the server returned it, and its text as `EXPLAIN` prints it, which clients show
+//! in a virtual document.
+
+use analyser::plan::{render_plan, PlanNode};
+use serde::Serialize;
+use serde_json::Value;
+use tokio_postgres::SimpleQueryMessage;
+use tower_lsp::jsonrpc::{Error, Result};
+
+use crate::db::{connect, database_error};
+
+pub const EXPLAIN_COMMAND: &str = "pglsp.explain";
+
+/// The conditions and keys of plan nodes in the order they are shown
+const DETAILS: &[&str] = &[
+    "Hash Cond",
+    "Merge Cond",
+    "Index Cond",
+    "Recheck Cond",
+    "Join Filter",
+    "Filter",
+    "Sort Key",
+    "Group Key",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExplainResult {
+    /// The plan as `EXPLAIN (FORMAT JSON)` returned it
+    pub plan: Value,
+    pub text: String,
+}
+
+/// Explains `sql` against the database at `url` as `role`
+pub async fn explain(url: Option<&str>, role: Option<&str>, sql: &str) -> Result<ExplainResult> {
+    let client = connect(url, role).await?;
+    let messages = client
+        .simple_query(&format!("EXPLAIN (FORMAT JSON) {}", sql))
+        .await
+        .map_err(database_error)?;
+    let json = messages
+        .iter()
+        .find_map(|message| match message {
+            SimpleQueryMessage::Row(row) => row.get(0),
+            _ => None,
+        })
+        .ok_or_else(|| Error {
+            message: "EXPLAIN returned no plan".into(),
+            ..Error::internal_error()
+        })?;
+    let plan = serde_json::from_str::<Value>(json).map_err(|err| Error {
+        message: format!("failed to parse the plan: {}", err).into(),
+        ..Error::internal_error()
+    })?;
+    let text = plan
+        .get(0)
+        .and_then(|explained| explained.get("Plan"))
+        .map(|node| render_plan(&plan_node(node)))
+        .unwrap_or_default();
+    Ok(ExplainResult { plan, text })
+}
+
+/// Returns the values of the placeholders of the `values` argument, or `None` if it is not an
+/// array of strings, numbers, booleans and `null`
+pub fn placeholder_values(values: &Value) -> Option<Vec<Option<String>>> {
+    values
+        .as_array()?
+        .iter()
+        .map(|value| match value {
+            Value::Null => Some(None),
+            Value::String(value) => Some(Some(value.clone())),
+            Value::Number(value) => Some(Some(value.to_string())),
+            Value::Bool(value) => Some(Some(value.to_string())),
+            Value::Array(_) | Value::Object(_) => None,
+        })
+        .collect()
+}
+
+/// Converts a node of the JSON plan
+fn plan_node(node: &Value) -> PlanNode {
+    let text = |key: &str| node.get(key).and_then(Value::as_str).map(str::to_string);
+    let number = |key: &str| node.get(key).and_then(Value::as_f64).unwrap_or_default();
+    PlanNode {
+        node_type: text("Node Type").unwrap_or_default(),
+        join_type: text("Join Type"),
+        strategy: text("Strategy"),
+        relation_name: text("Relation Name"),
+        alias: text("Alias"),
+        index_name: text("Index Name"),
+        subplan_name: text("Subplan Name"),
+        partial_mode: text("Partial Mode"),
+        parallel_aware: node
+            .get("Parallel Aware")
+            .and_then(Value::as_bool)
+            .unwrap_or_default(),
+        startup_cost: number("Startup Cost"),
+        total_cost: number("Total Cost"),
+        plan_rows: number("Plan Rows"),
+        plan_width: node
+            .get("Plan Width")
+            .and_then(Value::as_i64)
+            .unwrap_or_default(),
+        details: DETAILS
+            .iter()
+            .filter_map(|key| {
+                let value = match node.get(*key)? {
+                    Value::String(value) => value.clone(),
+                    // the keys of `Sort Key` and `Group Key`
+                    Value::Array(values) => values
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    _ => return None,
+                };
+                Some((key.to_string(), value))
+            })
+            .collect(),
+        children: node
+            .get("Plans")
+            .and_then(Value::as_array)
+            .map(|plans| plans.iter().map(plan_node).collect())
+            .unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_plan_node() {
+        let plan = json!({
+            "Node Type": "Aggregate",
+            "Strategy": "Sorted",
+            "Partial Mode": "Finalize",
+            "Parallel Aware": false,
+            "Startup Cost": 1000.5,
+            "Total Cost": 1500.25,
+            "Plan Rows": 10,
+            "Plan Width": 16,
+            "Group Key": ["contact.team_id"],
+            "Plans": [{
+                "Node Type": "Seq Scan",
+                "Parallel Aware": true,
+                "Relation Name": "contact",
+                "Alias": "contact",
+                "Startup Cost": 0.0,
+                "Total Cost": 800.0,
+                "Plan Rows": 1000,
+                "Plan Width": 8,
+                "Filter": "(active)"
+            }]
+        });
+        let node = plan_node(&plan);
+        assert_eq!(node.node_type, "Aggregate");
+        assert_eq!(node.strategy.as_deref(), Some("Sorted"));
+        assert_eq!(node.partial_mode.as_deref(), Some("Finalize"));
+        assert_eq!(node.plan_rows, 10.0);
+        assert_eq!(node.plan_width, 16);
+        assert_eq!(
+            node.details,
+            [("Group Key".to_string(), "contact.team_id".to_string())]
+        );
+        let scan = &node.children[0];
+        assert!(scan.parallel_aware);
+        assert_eq!(scan.relation_name.as_deref(), Some("contact"));
+        assert_eq!(
+            scan.details,
+            [("Filter".to_string(), "(active)".to_string())]
+        );
+        assert!(scan.children.is_empty());
+    }
+
+    #[test]
+    fn test_placeholder_values() {
+        assert_eq!(
+            placeholder_values(&json!(["O'Brien", 42, 1.5, true, null])),
+            Some(vec![
+                Some("O'Brien".to_string()),
+                Some("42".to_string()),
+                Some("1.5".to_string()),
+                Some("true".to_string()),
+                None,
+            ])
+        );
+        assert_eq!(placeholder_values(&json!([["nested"]])), None);
+        assert_eq!(placeholder_values(&json!("O'Brien")), None);
+    }
+}
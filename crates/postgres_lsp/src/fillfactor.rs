@@ -0,0 +1,53 @@
+//! Advice on the fillfactor of tables that the workspace updates heavily.
+//!
+//! The `UPDATE` statements are counted in all sql files of the workspace, see [`WorkspaceIndex`].
+//! The indexes and fillfactors are looked up in the schemas of the database of the document as
+//! changed by all open documents, which are only built if the `[lint]` section of `pglsp.toml`
+//! and the `lintRules` setting leave one of the rules of the advice on. See
+//! [`analyser::fillfactor`].
+//!
+//! [`WorkspaceIndex`]: crate::workspace_index::WorkspaceIndex
+
+use std::collections::BTreeMap;
+
+use analyser::fillfactor::{check_fillfactor, is_enabled};
+use analyser::{LintConfig, Schema};
+use parser::PgVersion;
+use tower_lsp::lsp_types::*;
+
+use crate::lint::set_levels;
+use crate::rename::Document;
+use crate::rewrite::schemas_at;
+use crate::settings::RuleLevel;
+use crate::utils::lint_diagnostic_to_diagnostic;
+
+/// Returns an advice of the rules that `config` enables for every update of `document` of a table
+/// that the workspace updates heavily, given the number of `UPDATE` statements of every table in
+/// `update_counts`, with the severities of `levels`
+pub fn fillfactor_diagnostics(
+    documents: &[Document<'_>],
+    document: &Document<'_>,
+    schemas: &BTreeMap<String, Schema>,
+    update_counts: &BTreeMap<String, usize>,
+    config: &LintConfig,
+    levels: &BTreeMap<String, RuleLevel>,
+    version: PgVersion,
+) -> Vec<Diagnostic> {
+    if !is_enabled(config) {
+        return Vec::new();
+    }
+    let schemas = schemas_at(documents, document, None, schemas);
+    let mut diagnostics = check_fillfactor(
+        &document.parse.cst,
+        &document.parse.stmts,
+        &schemas,
+        update_counts,
+        config,
+        version,
+    );
+    set_levels(&mut diagnostics, levels);
+    diagnostics
+        .iter()
+        .filter_map(|d| lint_diagnostic_to_diagnostic(d, document.rope))
+        .collect()
+}
@@ -0,0 +1,232 @@
+//! Warnings about foreign keys without an index, with a quick fix that creates it.
+//!
+//! The indexes are looked up in the schemas of the database of the document as changed by all
+//! open documents and the migrations next to it, in the order of their versions, so an index that
+//! the database or a later migration creates silences the warning. Migrations that are not open
+//! are only read if the open documents leave a foreign key without an index. See
+//! [`analyser::foreign_key_index`].
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use analyser::foreign_key_index::{unindexed_foreign_keys, UnindexedForeignKey};
+use analyser::migrations::{migration_version, MigrationState};
+use analyser::Schema;
+use parser::{parse_source, Parse, RawStmt, TextSize};
+use tower_lsp::lsp_types::*;
+
+use crate::rename::Document;
+use crate::rewrite::replay_order;
+use crate::utils::{byte_offset_to_position, lint_diagnostic_to_diagnostic};
+
+/// Returns a warning for every foreign key of `document` without an index
+pub fn foreign_key_diagnostics(
+    documents: &[Document<'_>],
+    document: &Document<'_>,
+    schemas: &BTreeMap<String, Schema>,
+) -> Vec<Diagnostic> {
+    foreign_keys(documents, document, schemas)
+        .iter()
+        .filter_map(|fk| lint_diagnostic_to_diagnostic(&fk.diagnostic(), document.rope))
+        .collect()
+}
+
+/// Returns the actions that create the missing indexes of the foreign keys of the statement at
+/// `offset` of `document`, below the statement
+pub fn create_index_actions(
+    documents: &[Document<'_>],
+    document: &Document<'_>,
+    offset: TextSize,
+    schemas: &BTreeMap<String, Schema>,
+) -> Vec<CodeAction> {
+    foreign_keys(documents, document, schemas)
+        .into_iter()
+        .filter(|fk| fk.stmt_range.contains_inclusive(offset))
+        .filter_map(|fk| {
+            let diagnostic = lint_diagnostic_to_diagnostic(&fk.diagnostic(), document.rope)?;
+            let edit = create_index_edit(&fk, document)?;
+            Some(CodeAction {
+                title: format!(
+                    "Create an index on {} ({})",
+                    fk.table,
+                    fk.columns.join(", ")
+                ),
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![diagnostic]),
+                edit: Some(WorkspaceEdit {
+                    changes: Some([(document.uri.clone(), vec![edit])].into_iter().collect()),
+                    ..WorkspaceEdit::default()
+                }),
+                is_preferred: Some(true),
+                ..CodeAction::default()
+            })
+        })
+        .collect()
+}
+
+fn foreign_keys(
+    documents: &[Document<'_>],
+    document: &Document<'_>,
+    schemas: &BTreeMap<String, Schema>,
+) -> Vec<UnindexedForeignKey> {
+    let unindexed = |files: &[(&Url, &[RawStmt])]| {
+        let mut files = files.to_vec();
+        files.sort_by_cached_key(|(uri, _)| replay_order(uri));
+        let mut state = MigrationState::from_schemas(schemas.clone());
+        for (_, stmts) in files {
+            state.replay(stmts);
+            state.end_session();
+        }
+        unindexed_foreign_keys(&document.parse.stmts, &state)
+    };
+
+    let mut files = documents
+        .iter()
+        .map(|doc| (&doc.uri, doc.parse.stmts.as_slice()))
+        .collect::<Vec<_>>();
+    if !files.iter().any(|(uri, _)| **uri == document.uri) {
+        files.push((&document.uri, &document.parse.stmts));
+    }
+    let foreign_keys = unindexed(&files);
+    if foreign_keys.is_empty() {
+        return foreign_keys;
+    }
+    let closed = closed_migrations(documents, document);
+    if closed.is_empty() {
+        return foreign_keys;
+    }
+    files.extend(
+        closed
+            .iter()
+            .map(|(uri, parse)| (uri, parse.stmts.as_slice())),
+    );
+    unindexed(&files)
+}
+
+/// Returns the migrations in the directory of `document` that are not open, parsed from disk
+pub(crate) fn closed_migrations(
+    documents: &[Document<'_>],
+    document: &Document<'_>,
+) -> Vec<(Url, Parse)> {
+    let Some(dir) = document
+        .uri
+        .to_file_path()
+        .ok()
+        .and_then(|path| Some(path.parent()?.to_path_buf()))
+    else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            if !path.extension().is_some_and(|e| e == "sql")
+                || migration_version(&entry.file_name().to_string_lossy()).is_none()
+            {
+                return None;
+            }
+            let uri = Url::from_file_path(&path).ok()?;
+            if documents.iter().any(|doc| doc.uri == uri) || uri == document.uri {
+                return None;
+            }
+            let text = fs::read_to_string(&path).ok()?;
+            Some((uri, parse_source(&text)))
+        })
+        .collect()
+}
+
+/// Returns the edit that inserts the `CREATE INDEX` on the line after the statement of `fk`
+fn create_index_edit(fk: &UnindexedForeignKey, document: &Document<'_>) -> Option<TextEdit> {
+    let end = byte_offset_to_position(fk.stmt_range.end(), document.rope)?;
+    let next_line = end.line + 1;
+    if (next_line as usize) < document.rope.len_lines() {
+        Some(TextEdit::new(
+            Range::new(Position::new(next_line, 0), Position::new(next_line, 0)),
+            format!("{}\n", fk.create_index()),
+        ))
+    } else {
+        let line_end = document.rope.line(end.line as usize).len_chars() as u32;
+        let position = Position::new(end.line, line_end);
+        Some(TextEdit::new(
+            Range::new(position, position),
+            format!("\n{}", fk.create_index()),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ropey::Rope;
+
+    use super::*;
+    use crate::utils::apply_action;
+
+    /// Applies the action that creates the index of the foreign key in the statement at `at`,
+    /// and returns its title and the text after it
+    fn create_index(text: &str, at: &str) -> (String, String) {
+        // a directory without migrations
+        let uri = Url::parse("file:///nonexistent/queries.sql").unwrap();
+        let rope = Rope::from_str(text);
+        let parse = parse_source(text);
+        let document = Document {
+            uri: uri.clone(),
+            rope: &rope,
+            parse: &parse,
+        };
+        let offset = TextSize::try_from(text.find(at).unwrap()).unwrap();
+        let actions = create_index_actions(&[], &document, offset, &BTreeMap::new());
+        assert_eq!(actions.len(), 1);
+        (
+            actions[0].title.clone(),
+            apply_action(&rope, &uri, &actions[0]),
+        )
+    }
+
+    /// Returns the number of warnings about foreign keys without an index in `text`
+    fn warnings(text: &str) -> usize {
+        let rope = Rope::from_str(text);
+        let parse = parse_source(text);
+        let document = Document {
+            uri: Url::parse("file:///nonexistent/queries.sql").unwrap(),
+            rope: &rope,
+            parse: &parse,
+        };
+        foreign_key_diagnostics(&[], &document, &BTreeMap::new()).len()
+    }
+
+    #[test]
+    fn test_create_index_action() {
+        let text = "create table contact (id int primary key);
+create table orders (id int, contact_id int references contact (id));
+select 1;";
+        assert_eq!(warnings(text), 1);
+        let (title, fixed) = create_index(text, "create table orders");
+        assert_eq!(title, "Create an index on orders (contact_id)");
+        assert_eq!(
+            fixed,
+            "create table contact (id int primary key);
+create table orders (id int, contact_id int references contact (id));
+CREATE INDEX CONCURRENTLY orders_contact_id_idx ON orders (contact_id);
+select 1;"
+        );
+        assert_eq!(warnings(&fixed), 0);
+    }
+
+    #[test]
+    fn test_create_index_action_on_last_line() {
+        let text = "create table contact (id int primary key);
+create table orders (id int, contact_id int references contact (id));";
+        let (_, fixed) = create_index(text, "create table orders");
+        assert_eq!(
+            fixed,
+            format!(
+                "{}\nCREATE INDEX CONCURRENTLY orders_contact_id_idx ON orders (contact_id);",
+                text
+            )
+        );
+        assert_eq!(warnings(&fixed), 0);
+    }
+}
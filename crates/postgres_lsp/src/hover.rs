@@ -0,0 +1,44 @@
+//! Documentation of the objects and syntax constructs of a document.
+//!
+//! Hovering over the name of a table, view, column or function shows its definition as loaded
+//! from the database of the document. Hovering over a keyword explains the clause it belongs to
+//! and links to the Postgres documentation on it.
+
+use std::collections::BTreeMap;
+
+use analyser::keyword_help::keyword_help;
+use analyser::object_hover::object_hover;
+use analyser::{Function, Schema, View};
+use parser::{Parse, TextRange, TextSize};
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+
+use crate::utils::text_range_to_range;
+
+/// Returns the definition of the object named at `offset`, or else the explanation of the keyword
+/// at `offset`
+pub fn hover(
+    rope: &Rope,
+    parse: &Parse,
+    offset: TextSize,
+    schemas: &BTreeMap<String, Schema>,
+    views: &[View],
+    functions: &[Function],
+) -> Option<Hover> {
+    if let Some(object) = object_hover(&parse.cst, &parse.stmts, offset, schemas, views, functions)
+    {
+        return Some(markdown_hover(object.contents, object.range, rope));
+    }
+    let help = keyword_help(&parse.cst, offset)?;
+    Some(markdown_hover(help.to_markdown(), help.range, rope))
+}
+
+fn markdown_hover(value: String, range: TextRange, rope: &Rope) -> Hover {
+    Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }),
+        range: text_range_to_range(range, rope),
+    }
+}
@@ -0,0 +1,216 @@
+//! The lint rules of [`analyser::lint`] for open documents, with their fixes as quick fixes.
+//!
+//! Which rules run is configured by the `[lint]` section of `pglsp.toml`. Rule groups with names
+//! that no group has are ignored. The statements that the `[policy]` section denies are reported
+//! as well, see [`analyser::policy`]. The `lintRules` setting turns single rules off or overrides
+//! the severity of their diagnostics.
+//!
+//! The rules at `CREATE TABLE` look up primary keys, replica identities and publications in all
+//! open documents and in the migrations next to the document, see [`analyser::TableFacts`].
+
+use std::collections::BTreeMap;
+
+use analyser::policy::{check_policy, DeniedStatement, Policy};
+use analyser::{lint_cancellable, lint_fix, LintConfig, LintDiagnostic, RuleGroup, TableFacts};
+use parser::{Cancellation, Cancelled, SyntaxKind, TextSize};
+use tower_lsp::lsp_types::*;
+
+use crate::foreign_key_index::closed_migrations;
+use crate::rename::Document;
+use crate::settings::RuleLevel;
+use crate::utils::{lint_diagnostic_to_diagnostic, text_range_to_range};
+
+/// Returns the lint config of the `[lint]` section of `pglsp.toml`, without the rules that
+/// `levels` turns off
+pub fn lint_config(lint: &workspace::Lint, levels: &BTreeMap<String, RuleLevel>) -> LintConfig {
+    let mut disabled_rules = lint.disabled_rules.clone();
+    disabled_rules.extend(
+        levels
+            .iter()
+            .filter(|(_, level)| **level == RuleLevel::Off)
+            .map(|(rule, _)| rule.clone()),
+    );
+    let mut config = LintConfig {
+        disabled_rules,
+        ..LintConfig::default()
+    };
+    for group in lint.groups.iter().filter_map(|g| RuleGroup::from_name(g)) {
+        config = config.with_group(group);
+    }
+    config
+}
+
+/// Returns the policy of the `[policy]` section of `pglsp.toml`, without the statements of its
+/// `deny` list that are not known
+pub fn policy(policy: &workspace::Policy) -> Policy {
+    Policy {
+        denied: policy
+            .deny
+            .iter()
+            .filter_map(|name| DeniedStatement::from_name(name))
+            .collect(),
+        concurrent_index_tables: policy.concurrent_index_tables.clone(),
+    }
+}
+
+/// Returns what the open `documents`, including `document`, and the migrations next to `document`
+/// declare about their tables. The migrations are only read from disk if `document` creates a
+/// table, as only the findings at `CREATE TABLE` depend on them.
+pub fn table_facts(documents: &[Document<'_>], document: &Document<'_>) -> TableFacts {
+    let creates_table = document
+        .parse
+        .stmts
+        .iter()
+        .any(|stmt| SyntaxKind::from(&stmt.stmt) == SyntaxKind::CreateStmt);
+    let closed = if creates_table {
+        closed_migrations(documents, document)
+    } else {
+        Vec::new()
+    };
+    TableFacts::collect(
+        documents
+            .iter()
+            .map(|doc| doc.parse.stmts.as_slice())
+            .chain(closed.iter().map(|(_, parse)| parse.stmts.as_slice())),
+    )
+}
+
+/// Returns a diagnostic for every finding of the rules enabled in `config` in `document`, and for
+/// every statement that `policy` denies, with the severities of `levels`. Stops once
+/// `cancellation` has been cancelled.
+pub fn lint_diagnostics(
+    documents: &[Document<'_>],
+    document: &Document<'_>,
+    config: &LintConfig,
+    levels: &BTreeMap<String, RuleLevel>,
+    policy: &Policy,
+    cancellation: &Cancellation,
+) -> Result<Vec<Diagnostic>, Cancelled> {
+    let tables = table_facts(documents, document);
+    let mut diagnostics = lint_cancellable(&document.parse.stmts, config, &tables, cancellation)?;
+    diagnostics.extend(check_policy(&document.parse.stmts, policy));
+    set_levels(&mut diagnostics, levels);
+    Ok(diagnostics
+        .iter()
+        .filter_map(|d| lint_diagnostic_to_diagnostic(d, document.rope))
+        .collect())
+}
+
+/// Returns the fixes of the findings in the statement at `offset` of `document`, or none once
+/// `cancellation` has been cancelled
+pub fn lint_fix_actions(
+    documents: &[Document<'_>],
+    document: &Document<'_>,
+    offset: TextSize,
+    config: &LintConfig,
+    levels: &BTreeMap<String, RuleLevel>,
+    cancellation: &Cancellation,
+) -> Vec<CodeAction> {
+    let stmts = &document.parse.stmts;
+    let Some(stmt) = stmts
+        .iter()
+        .find(|stmt| stmt.range.contains_inclusive(offset))
+    else {
+        return Vec::new();
+    };
+    let tables = table_facts(documents, document);
+    let Ok(mut diagnostics) = lint_cancellable(stmts, config, &tables, cancellation) else {
+        return Vec::new();
+    };
+    set_levels(&mut diagnostics, levels);
+    diagnostics
+        .iter()
+        .filter(|d| stmt.range.contains_range(d.range))
+        .filter_map(|d| {
+            let fix = lint_fix(&document.parse.cst, stmts, d)?;
+            let diagnostic = lint_diagnostic_to_diagnostic(d, document.rope)?;
+            let range = text_range_to_range(fix.range, document.rope)?;
+            Some(CodeAction {
+                title: fix.title,
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![diagnostic]),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(
+                        [(document.uri.clone(), vec![TextEdit::new(range, fix.text)])]
+                            .into_iter()
+                            .collect(),
+                    ),
+                    ..WorkspaceEdit::default()
+                }),
+                ..CodeAction::default()
+            })
+        })
+        .collect()
+}
+
+/// Sets the severity of the diagnostics of the rules that `levels` has a severity for
+pub(crate) fn set_levels(diagnostics: &mut [LintDiagnostic], levels: &BTreeMap<String, RuleLevel>) {
+    for diagnostic in diagnostics {
+        if let Some(severity) = levels.get(diagnostic.rule).and_then(|l| l.severity()) {
+            diagnostic.severity = severity;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+    use ropey::Rope;
+
+    use super::*;
+
+    fn rules(texts: &[&str]) -> Vec<String> {
+        let ropes = texts
+            .iter()
+            .map(|text| Rope::from_str(text))
+            .collect::<Vec<_>>();
+        let parses = texts
+            .iter()
+            .map(|text| parse_source(text))
+            .collect::<Vec<_>>();
+        let documents = ropes
+            .iter()
+            .zip(&parses)
+            .enumerate()
+            .map(|(idx, (rope, parse))| Document {
+                uri: Url::parse(&format!("untitled:{}.sql", idx)).unwrap(),
+                rope,
+                parse,
+            })
+            .collect::<Vec<_>>();
+        let lint = workspace::Lint {
+            groups: vec!["replication".to_string()],
+            disabled_rules: Vec::new(),
+        };
+        let levels = BTreeMap::from([("truncate-table".to_string(), RuleLevel::Off)]);
+        lint_diagnostics(
+            &documents,
+            &documents[0],
+            &lint_config(&lint, &levels),
+            &levels,
+            &Policy::default(),
+            &Cancellation::new(),
+        )
+        .unwrap()
+        .into_iter()
+        .filter_map(|d| match d.code? {
+            NumberOrString::String(rule) => Some(rule),
+            NumberOrString::Number(_) => None,
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_lint_diagnostics() {
+        assert_eq!(
+            rules(&["create table note (body text);\ntruncate note;"]),
+            vec!["table-without-primary-key".to_string()]
+        );
+        // the primary key is added by another document
+        assert!(rules(&[
+            "create table note (body text);",
+            "alter table note add primary key (body);"
+        ])
+        .is_empty());
+    }
+}
@@ -1,51 +1,187 @@
+mod activity;
+mod cancellation;
+mod completion;
+mod db;
+mod ddl_actions;
+mod definition;
+mod document_symbol;
+mod execute;
+mod explain;
+mod fillfactor;
+mod foreign_key_index;
+mod hover;
+mod lint;
+mod partition_pruning;
+mod progress;
+mod pull_diagnostics;
+mod qualify_column;
+mod references;
+mod rename;
+mod rewrite;
+mod run;
+mod schema_browser;
+mod schema_cache;
+mod selection_range;
 mod semantic_token;
+mod settings;
+mod signature_help;
+mod split_migration;
+mod status;
+mod syntax_tree;
+mod type_hierarchy;
 mod utils;
+mod virtual_document;
+mod workspace_index;
 
-use dashmap::DashMap;
-use parser::{parse_source, Parse};
-use ropey::Rope;
-use semantic_token::{ImCompleteSemanticToken, LEGEND_TYPE};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use analyser::comments::{check_commented_objects, commented_objects, Catalog};
+use analyser::definitions::{reference_at, Reference};
+use analyser::execution_error::is_cursor_query;
+use analyser::messages;
+use analyser::migrations::MigrationState;
+use analyser::moniker::symbol_at;
+use analyser::name_arguments::{check_name_arguments, name_arguments, NameKind};
+use analyser::plan::{bind_parameters, is_explainable};
+use analyser::policy::check_policy;
+use analyser::read_only::check_read_only;
+use analyser::rename::identifier_at;
+use analyser::spelling::{check_spelling, Dictionary};
+use analyser::table_rewrite::check_table_rewrites;
+use analyser::LintConfig;
+use parser::{dump_cst, Cancellation, Cancelled, RawStmt, TextRange};
+use semantic_token::LEGEND_TYPE;
 use serde_json::Value;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
+use workspace::{Config, Database, Memo, Revision, Workspace, CONFIG_FILE};
 
-use crate::semantic_token::semantic_token_from_syntax_kind;
-use crate::utils::offset_to_position;
+use crate::activity::{
+    activity, signal_backend, ACTIVITY_COMMAND, CANCEL_BACKEND_COMMAND, TERMINATE_BACKEND_COMMAND,
+};
+use crate::cancellation::{check, checkpoint, current_request, request_cancelled, CancelRequests};
+use crate::completion::completion;
+use crate::db::{is_offline, set_offline};
+use crate::ddl_actions::ddl_actions;
+use crate::definition::definition;
+use crate::document_symbol::document_symbols;
+use crate::execute::{execute_statement, notice_diagnostics, EXECUTE_STATEMENT_COMMAND};
+use crate::explain::{explain, placeholder_values, EXPLAIN_COMMAND};
+use crate::fillfactor::fillfactor_diagnostics;
+use crate::foreign_key_index::{create_index_actions, foreign_key_diagnostics};
+use crate::hover::hover;
+use crate::lint::{lint_config, lint_diagnostics, lint_fix_actions, policy};
+use crate::partition_pruning::partition_pruning_diagnostics;
+use crate::progress::{Progress, INDEXING_TOKEN};
+use crate::pull_diagnostics::diagnostic_report;
+use crate::qualify_column::{ambiguous_column_diagnostics, qualify_column_actions};
+use crate::references::{document_highlights, references};
+use crate::rename::{prepare_rename, rename_edit, Document};
+use crate::rewrite::{expand_star_action, insert_columns_action};
+use crate::run::statement_lenses;
+use crate::schema_browser::{
+    object_definition, schema_tree, ObjectDefinitionParams, SchemaNode, SchemaTreeParams,
+    OBJECT_DEFINITION_REQUEST, SCHEMA_TREE_REQUEST,
+};
+use crate::schema_cache::{Functions, Roles, SchemaCache, Schemas, Views};
+use crate::selection_range::selection_range;
+use crate::semantic_token::{document_semantic_tokens, semantic_tokens_edits};
+use crate::settings::{RuleLevel, Settings, SET_ROLE_COMMAND};
+use crate::signature_help::signature_help;
+use crate::split_migration::split_migration_action;
+use crate::status::{StatusNotification, StatusParams};
+use crate::syntax_tree::{
+    statement_ast, syntax_tree, SyntaxTreeParams, DUMP_CST_COMMAND, STATEMENT_AST_COMMAND,
+    SYNTAX_TREE_REQUEST,
+};
+use crate::type_hierarchy::{prepare_type_hierarchy, subtypes, supertypes, PREPARE_TYPE_HIERARCHY};
+use crate::utils::{
+    apply_change, lint_diagnostic_to_diagnostic, position_to_byte_offset, range_to_text_range,
+    syntax_error_to_diagnostic, text_range_to_range,
+};
+use crate::virtual_document::{
+    virtual_definition, VirtualDocument, VirtualDocumentParams, VIRTUAL_DOCUMENT_REQUEST,
+};
+use crate::workspace_index::{sql_files, WorkspaceIndex};
 
 #[derive(Debug)]
 struct Backend {
     client: Client,
-    parse_map: DashMap<String, Parse>,
-    document_map: DashMap<String, Rope>,
-    semantic_token_map: DashMap<String, Vec<ImCompleteSemanticToken>>,
+    /// The capabilities that the client announced in `initialize`
+    client_capabilities: RwLock<ClientCapabilities>,
+    /// The open documents, which all handlers query
+    workspace: Workspace,
+    settings: RwLock<Settings>,
+    /// The translations of the messages into the locale of the client, if there are any
+    catalog: RwLock<Option<messages::Catalog>>,
+    /// The root of the workspace, which contains `pglsp.toml`
+    root: RwLock<Option<PathBuf>>,
+    config: RwLock<Config>,
+    /// The dictionary of the spell check, if `pglsp.toml` turns it on
+    dictionary: RwLock<Option<Arc<Dictionary>>>,
+    schema_cache: SchemaCache,
+    semantic_tokens: Memo<Revision, Vec<SemanticToken>>,
+    /// By the revision of the document, its database, the role and the generation of the schema
+    /// cache
+    schema_diagnostics: Memo<(Revision, Database, Option<String>, u64), Vec<Diagnostic>>,
+    /// The definitions and references of the sql files of the workspace
+    workspace_index: WorkspaceIndex,
+    /// The cancellation of indexing the workspace while it runs, which the client can cancel
+    indexing: RwLock<Option<Cancellation>>,
+    /// The notices that executed statements raised, by the uri and the version of their document
+    notices: RwLock<HashMap<String, (i32, Vec<Diagnostic>)>>,
 }
 
 #[tower_lsp::async_trait]
 impl LanguageServer for Backend {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
         self.client
             .log_message(MessageType::INFO, "initializing!")
             .await;
+        let settings = match &params.initialization_options {
+            Some(options) => self.read_settings(options).await,
+            None => None,
+        };
+        if let Some(settings) = settings {
+            if settings.offline {
+                set_offline();
+            }
+            *self.settings.write().unwrap() = settings;
+        }
+        self.load_catalog(params.locale.as_deref()).await;
+        *self.root.write().unwrap() = params.root_uri.and_then(|uri| uri.to_file_path().ok());
+        *self.client_capabilities.write().unwrap() = params.capabilities;
         Ok(InitializeResult {
-            server_info: None,
+            server_info: Some(ServerInfo {
+                name: env!("CARGO_PKG_NAME").to_string(),
+                version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            }),
             offset_encoding: None,
             capabilities: ServerCapabilities {
                 // inlay_hint_provider: Some(OneOf::Left(true)),
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
+                text_document_sync: Some(TextDocumentSyncCapability::Options(
+                    TextDocumentSyncOptions {
+                        open_close: Some(true),
+                        change: Some(TextDocumentSyncKind::INCREMENTAL),
+                        ..TextDocumentSyncOptions::default()
+                    },
                 )),
-                // completion_provider: Some(CompletionOptions {
-                //     resolve_provider: Some(false),
-                //     trigger_characters: Some(vec![".".to_string()]),
-                //     work_done_progress_options: Default::default(),
-                //     all_commit_characters: None,
-                //     completion_item: None,
-                // }),
-                // execute_command_provider: Some(ExecuteCommandOptions {
-                //     commands: vec!["dummy.do_something".to_string()],
-                //     work_done_progress_options: Default::default(),
-                // }),
+                completion_provider: Some(CompletionOptions {
+                    resolve_provider: Some(false),
+                    trigger_characters: None,
+                    work_done_progress_options: Default::default(),
+                    all_commit_characters: None,
+                    completion_item: None,
+                }),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: commands(),
+                    work_done_progress_options: Default::default(),
+                }),
                 workspace: Some(WorkspaceServerCapabilities {
                     workspace_folders: Some(WorkspaceFoldersServerCapabilities {
                         supported: Some(true),
@@ -59,7 +195,7 @@ impl LanguageServer for Backend {
                             text_document_registration_options: {
                                 TextDocumentRegistrationOptions {
                                     document_selector: Some(vec![DocumentFilter {
-                                        language: Some("nrs".to_string()),
+                                        language: Some("sql".to_string()),
                                         scheme: Some("file".to_string()),
                                         pattern: None,
                                     }]),
@@ -72,16 +208,52 @@ impl LanguageServer for Backend {
                                     token_modifiers: vec![],
                                 },
                                 range: Some(true),
-                                full: Some(SemanticTokensFullOptions::Bool(true)),
+                                full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
                             },
                             static_registration_options: StaticRegistrationOptions::default(),
                         },
                     ),
                 ),
                 // definition: Some(GotoCapability::default()),
-                // definition_provider: Some(OneOf::Left(true)),
-                // references_provider: Some(OneOf::Left(true)),
-                // rename_provider: Some(OneOf::Left(true)),
+                definition_provider: Some(OneOf::Left(true)),
+                signature_help_provider: Some(SignatureHelpOptions {
+                    trigger_characters: Some(vec!["(".to_string(), ",".to_string()]),
+                    retrigger_characters: None,
+                    work_done_progress_options: WorkDoneProgressOptions::default(),
+                }),
+                references_provider: Some(OneOf::Left(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                moniker_provider: Some(OneOf::Left(true)),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+                code_lens_provider: Some(CodeLensOptions {
+                    resolve_provider: Some(false),
+                }),
+                code_action_provider: Some(CodeActionProviderCapability::Options(
+                    CodeActionOptions {
+                        code_action_kinds: Some(vec![
+                            CodeActionKind::QUICKFIX,
+                            CodeActionKind::REFACTOR,
+                            CodeActionKind::REFACTOR_REWRITE,
+                        ]),
+                        work_done_progress_options: WorkDoneProgressOptions::default(),
+                        resolve_provider: None,
+                    },
+                )),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: WorkDoneProgressOptions::default(),
+                })),
+                diagnostic_provider: Some(DiagnosticServerCapabilities::Options(
+                    DiagnosticOptions {
+                        identifier: Some("pglsp".to_string()),
+                        // the schema diagnostics use the tables that other documents create
+                        inter_file_dependencies: true,
+                        workspace_diagnostics: false,
+                        work_done_progress_options: WorkDoneProgressOptions::default(),
+                    },
+                )),
                 ..ServerCapabilities::default()
             },
         })
@@ -91,9 +263,77 @@ impl LanguageServer for Backend {
         self.client
             .log_message(MessageType::INFO, "initialized!")
             .await;
+
+        // `ServerCapabilities` has no field for type hierarchies yet, so the provider is
+        // registered dynamically
+        let options = TypeHierarchyRegistrationOptions {
+            text_document_registration_options: TextDocumentRegistrationOptions {
+                document_selector: None,
+            },
+            type_hierarchy_options: TypeHierarchyOptions::default(),
+            static_registration_options: StaticRegistrationOptions::default(),
+        };
+        let registration = Registration {
+            id: PREPARE_TYPE_HIERARCHY.to_string(),
+            method: PREPARE_TYPE_HIERARCHY.to_string(),
+            register_options: serde_json::to_value(options).ok(),
+        };
+        let mut registrations = vec![registration];
+        // changes of `pglsp.toml` and of closed sql files only arrive if the client is asked to
+        // watch them
+        let can_watch = self
+            .client_capabilities
+            .read()
+            .unwrap()
+            .workspace
+            .as_ref()
+            .and_then(|w| w.did_change_watched_files.as_ref())
+            .and_then(|d| d.dynamic_registration)
+            .unwrap_or(false);
+        if can_watch {
+            let options = DidChangeWatchedFilesRegistrationOptions {
+                watchers: vec![
+                    FileSystemWatcher {
+                        glob_pattern: GlobPattern::String(format!("**/{}", CONFIG_FILE)),
+                        kind: None,
+                    },
+                    FileSystemWatcher {
+                        glob_pattern: GlobPattern::String("**/*.sql".to_string()),
+                        kind: None,
+                    },
+                ],
+            };
+            registrations.push(Registration {
+                id: CONFIG_FILE.to_string(),
+                method: "workspace/didChangeWatchedFiles".to_string(),
+                register_options: serde_json::to_value(options).ok(),
+            });
+        }
+        if let Err(err) = self.client.register_capability(registrations).await {
+            self.client.log_message(MessageType::ERROR, err).await;
+        }
+        self.publish_status().await;
+        self.load_config().await;
+
+        let root = self.root.read().unwrap().clone();
+        if let Some(root) = root {
+            self.index_workspace(&root).await;
+        }
+    }
+
+    async fn work_done_progress_cancel(&self, params: WorkDoneProgressCancelParams) {
+        if params.token != NumberOrString::String(INDEXING_TOKEN.to_string()) {
+            return;
+        }
+        if let Some(cancellation) = self.indexing.read().unwrap().as_ref() {
+            cancellation.cancel();
+        }
     }
 
     async fn shutdown(&self) -> Result<()> {
+        self.client
+            .log_message(MessageType::INFO, "shutting down!")
+            .await;
         Ok(())
     }
 
@@ -109,16 +349,31 @@ impl LanguageServer for Backend {
         .await
     }
 
-    async fn did_change(&self, mut params: DidChangeTextDocumentParams) {
+    async fn did_change(&self, params: DidChangeTextDocumentParams) {
+        let uri = params.text_document.uri;
+        // the changes are applied in order, each to the text that the previous one left behind,
+        // before anything is awaited, so that the next notification sees their text
+        let version = params.text_document.version;
+        let text = self.workspace.edit_text(uri.as_str(), version, |rope| {
+            params
+                .content_changes
+                .iter()
+                .try_for_each(|change| apply_change(rope, change))
+        });
         self.client
             .log_message(MessageType::INFO, "file changed!")
             .await;
-        self.on_change(TextDocumentItem {
-            uri: params.text_document.uri,
-            text: std::mem::take(&mut params.content_changes[0].text),
-            version: params.text_document.version,
-        })
-        .await
+        let Some(text) = text else {
+            self.client
+                .log_message(
+                    MessageType::ERROR,
+                    format!("change outside of the text of {}", uri),
+                )
+                .await;
+            return;
+        };
+        self.on_change(TextDocumentItem { uri, text, version })
+            .await
     }
 
     async fn did_save(&self, _: DidSaveTextDocumentParams) {
@@ -126,10 +381,23 @@ impl LanguageServer for Backend {
             .log_message(MessageType::INFO, "file saved!")
             .await;
     }
-    async fn did_close(&self, _: DidCloseTextDocumentParams) {
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
         self.client
             .log_message(MessageType::INFO, "file closed!")
             .await;
+        let uri = params.text_document.uri.as_str();
+        let file_id = self.workspace.file_id(uri);
+        self.workspace.close(uri);
+        self.semantic_tokens.remove(file_id);
+        self.schema_diagnostics.remove(file_id);
+        self.notices.write().unwrap().remove(uri);
+        self.workspace_index
+            .index_file(&params.text_document.uri)
+            .await;
+        // the diagnostics of a closed document are outdated as soon as it changes on disk
+        self.client
+            .publish_diagnostics(params.text_document.uri, Vec::new(), None)
+            .await;
     }
 
     async fn semantic_tokens_full(
@@ -140,53 +408,70 @@ impl LanguageServer for Backend {
         self.client
             .log_message(MessageType::LOG, "semantic_token_full")
             .await;
-        let semantic_tokens = || -> Option<Vec<SemanticToken>> {
-            let mut im_complete_tokens = self.semantic_token_map.get_mut(&uri)?;
-            let rope = self.document_map.get(&uri)?;
-            im_complete_tokens.sort_by(|a, b| a.start.cmp(&b.start));
-            let mut pre_line = 0;
-            let mut pre_start = 0;
-            let semantic_tokens = im_complete_tokens
-                .iter()
-                .filter_map(|token| {
-                    let line = rope.try_byte_to_line(token.start).ok()? as u32;
-                    let first = rope.try_line_to_char(line as usize).ok()? as u32;
-                    let start = rope.try_byte_to_char(token.start).ok()? as u32 - first;
-                    let delta_line = line - pre_line;
-                    let delta_start = if delta_line == 0 {
-                        start - pre_start
-                    } else {
-                        start
-                    };
-                    let ret = Some(SemanticToken {
-                        delta_line,
-                        delta_start,
-                        length: token.length as u32,
-                        token_type: token.token_type as u32,
-                        token_modifiers_bitset: 0,
+        let semantic_tokens = self
+            .cancellable(&params.text_document.uri, || {
+                let doc = self.workspace.document(&uri)?;
+                let tokens = self
+                    .semantic_tokens
+                    .get_or_compute(doc.file_id, doc.revision, || {
+                        document_semantic_tokens(&doc.parse, &doc.rope)
                     });
-                    pre_line = line;
-                    pre_start = start;
-                    ret
-                })
-                .collect::<Vec<_>>();
-            Some(semantic_tokens)
-        }();
+                Some((doc.revision, tokens))
+            })
+            .await?;
         self.client
             .log_message(
                 MessageType::LOG,
                 format!("semantic_tokens: {:?}", semantic_tokens),
             )
             .await;
-        if let Some(semantic_token) = semantic_tokens {
+        if let Some((revision, semantic_token)) = semantic_tokens {
             return Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
-                result_id: None,
-                data: semantic_token,
+                result_id: Some(revision.to_string()),
+                data: semantic_token.to_vec(),
             })));
         }
         Ok(None)
     }
 
+    /// Returns the changes of the semantic tokens since those of `previous_result_id`, which are
+    /// the last ones computed for the document if the client is up to date, or all of them
+    /// otherwise
+    async fn semantic_tokens_full_delta(
+        &self,
+        params: SemanticTokensDeltaParams,
+    ) -> Result<Option<SemanticTokensFullDeltaResult>> {
+        let uri = params.text_document.uri.to_string();
+        let cancellation = self.request_cancellation(&params.text_document.uri);
+        checkpoint(&cancellation).await?;
+        let Some(doc) = self.workspace.document(&uri) else {
+            return Ok(None);
+        };
+        let previous = self
+            .semantic_tokens
+            .latest(doc.file_id)
+            .filter(|(revision, _)| revision.to_string() == params.previous_result_id);
+        let tokens = self
+            .semantic_tokens
+            .get_or_compute(doc.file_id, doc.revision, || {
+                document_semantic_tokens(&doc.parse, &doc.rope)
+            });
+        let result_id = Some(doc.revision.to_string());
+        check(&cancellation)?;
+        Ok(Some(match previous {
+            Some((_, previous)) => {
+                SemanticTokensFullDeltaResult::TokensDelta(SemanticTokensDelta {
+                    result_id,
+                    edits: semantic_tokens_edits(&previous, &tokens),
+                })
+            }
+            None => SemanticTokensFullDeltaResult::Tokens(SemanticTokens {
+                result_id,
+                data: tokens.to_vec(),
+            }),
+        }))
+    }
+
     async fn semantic_tokens_range(
         &self,
         params: SemanticTokensRangeParams,
@@ -194,10 +479,379 @@ impl LanguageServer for Backend {
         return Ok(None);
     }
 
-    async fn did_change_configuration(&self, _: DidChangeConfigurationParams) {
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        self.cancellable(&params.text_document.uri, || {
+            self.with_documents(|documents| {
+                let doc = documents
+                    .iter()
+                    .find(|doc| doc.uri == params.text_document.uri)?;
+                let offset = position_to_byte_offset(params.position, doc.rope)?;
+                prepare_rename(doc, offset)
+            })
+        })
+        .await
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        if params.new_name.is_empty() {
+            return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                "the new name must not be empty",
+            ));
+        }
+        let position = params.text_document_position;
+        self.cancellable(&position.text_document.uri, || {
+            self.with_documents(|documents| {
+                let doc = documents
+                    .iter()
+                    .find(|doc| doc.uri == position.text_document.uri)?;
+                let offset = position_to_byte_offset(position.position, doc.rope)?;
+                rename_edit(
+                    &self.workspace_index,
+                    documents,
+                    doc,
+                    offset,
+                    &params.new_name,
+                )
+            })
+        })
+        .await
+    }
+
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        let position = params.text_document_position_params;
+        let cancellation = self.request_cancellation(&position.text_document.uri);
+        checkpoint(&cancellation).await?;
+        let mut relation = None;
+        let response = self.with_documents(|documents| {
+            let doc = documents
+                .iter()
+                .find(|doc| doc.uri == position.text_document.uri)?;
+            let offset = position_to_byte_offset(position.position, doc.rope)?;
+            relation = match reference_at(&doc.parse.cst, &doc.parse.stmts, offset) {
+                Some(Reference::Relation(identifier)) => Some(identifier),
+                _ => None,
+            };
+            definition(&self.workspace_index, documents, doc, offset)
+        });
+        check(&cancellation)?;
+        if response.is_some() {
+            return Ok(response);
+        }
+        // relations that only exist in the database are shown in a virtual document
+        let Some(identifier) = relation else {
+            return Ok(None);
+        };
+        let schemas = self.schemas(&position.text_document.uri).await;
+        let views = self.views(&position.text_document.uri).await;
+        checkpoint(&cancellation).await?;
+        Ok(
+            virtual_definition(&position.text_document.uri, &identifier, &schemas, &views)
+                .map(GotoDefinitionResponse::Scalar),
+        )
+    }
+
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        let position = params.text_document_position;
+        self.cancellable(&position.text_document.uri, || {
+            self.with_documents(|documents| {
+                let doc = documents
+                    .iter()
+                    .find(|doc| doc.uri == position.text_document.uri)?;
+                let offset = position_to_byte_offset(position.position, doc.rope)?;
+                references(
+                    &self.workspace_index,
+                    doc,
+                    offset,
+                    params.context.include_declaration,
+                )
+            })
+        })
+        .await
+    }
+
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> Result<Option<Vec<DocumentHighlight>>> {
+        let position = params.text_document_position_params;
+        self.cancellable(&position.text_document.uri, || {
+            self.with_documents(|documents| {
+                let doc = documents
+                    .iter()
+                    .find(|doc| doc.uri == position.text_document.uri)?;
+                let offset = position_to_byte_offset(position.position, doc.rope)?;
+                document_highlights(doc, offset)
+            })
+        })
+        .await
+    }
+
+    async fn signature_help(&self, params: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
+        let position = params.text_document_position_params;
+        let functions = self.functions(&position.text_document.uri).await;
+        let uri = position.text_document.uri.to_string();
+        self.cancellable(&position.text_document.uri, || {
+            let doc = self.workspace.document(&uri)?;
+            let offset = position_to_byte_offset(position.position, &doc.rope)?;
+            signature_help(&doc.parse, offset, &functions)
+        })
+        .await
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        let position = params.text_document_position_params;
+        let schemas = self.schemas(&position.text_document.uri).await;
+        let views = self.views(&position.text_document.uri).await;
+        let functions = self.functions(&position.text_document.uri).await;
+        let uri = position.text_document.uri.to_string();
+        self.cancellable(&position.text_document.uri, || {
+            let doc = self.workspace.document(&uri)?;
+            let offset = position_to_byte_offset(position.position, &doc.rope)?;
+            hover(&doc.rope, &doc.parse, offset, &schemas, &views, &functions)
+        })
+        .await
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        let position = params.text_document_position;
+        let uri = position.text_document.uri.to_string();
+        let snippet_support = self
+            .client_capabilities
+            .read()
+            .unwrap()
+            .text_document
+            .as_ref()
+            .and_then(|t| t.completion.as_ref())
+            .and_then(|c| c.completion_item.as_ref())
+            .and_then(|c| c.snippet_support)
+            .unwrap_or(false);
+        let cancellation = self.request_cancellation(&position.text_document.uri);
+        let functions = self.functions(&position.text_document.uri).await;
+        let roles = self.roles(&position.text_document.uri).await;
+        checkpoint(&cancellation).await?;
+        let objects = self.named_objects(&position.text_document.uri).await;
+        checkpoint(&cancellation).await?;
+        Ok(|| -> Option<CompletionResponse> {
+            let doc = self.workspace.document(&uri)?;
+            let offset = position_to_byte_offset(position.position, &doc.rope)?;
+            completion(
+                &doc.rope,
+                &doc.parse,
+                offset,
+                &functions,
+                &roles,
+                &objects,
+                snippet_support,
+            )
+        }())
+    }
+
+    async fn moniker(&self, params: MonikerParams) -> Result<Option<Vec<Moniker>>> {
+        let position = params.text_document_position_params;
+        let uri = position.text_document.uri.to_string();
+        let moniker = self.cancellable(&position.text_document.uri, || {
+            let doc = self.workspace.document(&uri)?;
+            let offset = position_to_byte_offset(position.position, &doc.rope)?;
+            let symbol = symbol_at(&doc.parse.cst, &doc.parse.stmts, offset)?;
+            Some(Moniker {
+                scheme: "postgres".to_string(),
+                identifier: symbol.identifier,
+                // objects are only unique within a database, whose schema is defined by the
+                // project
+                unique: UniquenessLevel::Project,
+                kind: Some(if symbol.is_definition {
+                    MonikerKind::Export
+                } else {
+                    MonikerKind::Import
+                }),
+            })
+        });
+        Ok(moniker.await?.map(|m| vec![m]))
+    }
+
+    async fn diagnostic(
+        &self,
+        params: DocumentDiagnosticParams,
+    ) -> Result<DocumentDiagnosticReportResult> {
+        let uri = params.text_document.uri;
+        let cancellation = self.request_cancellation(&uri);
+        let diagnostics = self
+            .diagnostics(&uri, &cancellation)
+            .await?
+            .map(|(_, diagnostics)| diagnostics)
+            .ok_or_else(|| {
+                tower_lsp::jsonrpc::Error::invalid_params(format!("{} is not open", uri))
+            })?;
+        Ok(diagnostic_report(
+            diagnostics,
+            params.previous_result_id.as_deref(),
+        ))
+    }
+
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> Result<Option<DocumentSymbolResponse>> {
+        let uri = params.text_document.uri.to_string();
+        let symbols = self
+            .cancellable(&params.text_document.uri, || {
+                let doc = self.workspace.document(&uri)?;
+                Some(document_symbols(&doc.rope, &doc.parse))
+            })
+            .await?;
+        Ok(symbols.map(DocumentSymbolResponse::Nested))
+    }
+
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        if is_offline() {
+            return Ok(None);
+        }
+        let uri = params.text_document.uri;
+        let enabled = self.settings.read().unwrap().execution.enabled;
+        self.cancellable(&uri, || {
+            self.workspace.document(uri.as_str()).map(|doc| {
+                statement_lenses(
+                    &Document {
+                        uri: uri.clone(),
+                        rope: &doc.rope,
+                        parse: &doc.parse,
+                    },
+                    enabled,
+                )
+            })
+        })
+        .await
+    }
+
+    async fn selection_range(
+        &self,
+        params: SelectionRangeParams,
+    ) -> Result<Option<Vec<SelectionRange>>> {
+        let uri = params.text_document.uri.to_string();
+        self.cancellable(&params.text_document.uri, || {
+            let doc = self.workspace.document(&uri)?;
+            Some(selection_range(&doc.rope, &doc.parse, &params.positions))
+        })
+        .await
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let uri = params.text_document.uri;
+        let cancellation = self.request_cancellation(&uri);
+        let schemas = self.schemas(&uri).await;
+        checkpoint(&cancellation).await?;
+        let (lint_config, levels) = self.lint_config(&uri);
+        let actions = self.with_documents(|documents| {
+            let Some(doc) = documents.iter().find(|doc| doc.uri == uri) else {
+                return Vec::new();
+            };
+            let offset = position_to_byte_offset(params.range.start, doc.rope);
+            let range = offset
+                .zip(position_to_byte_offset(params.range.end, doc.rope))
+                .filter(|(start, end)| start <= end)
+                .map(|(start, end)| TextRange::new(start, end));
+            let ddl = range
+                .map(|range| ddl_actions(doc, range))
+                .unwrap_or_default();
+            let star =
+                offset.and_then(|offset| expand_star_action(documents, doc, offset, &schemas));
+            let insert =
+                offset.and_then(|offset| insert_columns_action(documents, doc, offset, &schemas));
+            let (lint_fixes, create_index, qualify) = offset
+                .map(|offset| {
+                    (
+                        lint_fix_actions(
+                            documents,
+                            doc,
+                            offset,
+                            &lint_config,
+                            &levels,
+                            &cancellation,
+                        ),
+                        create_index_actions(documents, doc, offset, &schemas),
+                        qualify_column_actions(documents, doc, offset, &schemas),
+                    )
+                })
+                .unwrap_or_default();
+            lint_fixes
+                .into_iter()
+                .chain(create_index)
+                .chain(qualify)
+                .chain(star)
+                .chain(insert)
+                .chain(ddl)
+                .chain(
+                    range
+                        .and_then(|range| split_migration_action(&uri, doc.rope, doc.parse, range)),
+                )
+                .map(CodeActionOrCommand::CodeAction)
+                .collect::<Vec<_>>()
+        });
+        check(&cancellation)?;
+        Ok((!actions.is_empty()).then_some(actions))
+    }
+
+    async fn prepare_type_hierarchy(
+        &self,
+        params: TypeHierarchyPrepareParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        let position = params.text_document_position_params;
+        let uri = position.text_document.uri;
+        self.cancellable(&uri, || {
+            let (name, range) = {
+                let doc = self.workspace.document(uri.as_str())?;
+                let offset = position_to_byte_offset(position.position, &doc.rope)?;
+                let ident = identifier_at(&doc.parse.cst, offset)?;
+                (ident.name, text_range_to_range(ident.range, &doc.rope)?)
+            };
+            self.with_documents(|documents| prepare_type_hierarchy(documents, &uri, range, &name))
+        })
+        .await
+    }
+
+    async fn supertypes(
+        &self,
+        params: TypeHierarchySupertypesParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        self.cancellable(&params.item.uri, || {
+            Some(self.with_documents(|documents| supertypes(documents, &params.item)))
+        })
+        .await
+    }
+
+    async fn subtypes(
+        &self,
+        params: TypeHierarchySubtypesParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        self.cancellable(&params.item.uri, || {
+            Some(self.with_documents(|documents| subtypes(documents, &params.item)))
+        })
+        .await
+    }
+
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
         self.client
             .log_message(MessageType::INFO, "configuration changed!")
             .await;
+        if let Some(mut settings) = self.read_settings(&params.settings).await {
+            settings.offline = is_offline();
+            settings.message_catalog = self.settings.read().unwrap().message_catalog.clone();
+            // the `Run` lenses are only offered while executing statements is enabled
+            let lenses_changed =
+                settings.execution.enabled != self.settings.read().unwrap().execution.enabled;
+            *self.settings.write().unwrap() = settings;
+            self.publish_status().await;
+            self.reparse_documents().await;
+            if lenses_changed {
+                self.refresh_code_lenses().await;
+            }
+        }
     }
 
     async fn did_change_workspace_folders(&self, _: DidChangeWorkspaceFoldersParams) {
@@ -206,24 +860,277 @@ impl LanguageServer for Backend {
             .await;
     }
 
-    async fn did_change_watched_files(&self, _: DidChangeWatchedFilesParams) {
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
         self.client
             .log_message(MessageType::INFO, "watched files have changed!")
             .await;
+        if params
+            .changes
+            .iter()
+            .any(|change| change.uri.path().ends_with(CONFIG_FILE))
+        {
+            self.load_config().await;
+        }
+        // open documents are indexed from their text in the editor
+        let mut indexed = false;
+        for change in &params.changes {
+            if change.uri.path().ends_with(".sql")
+                && self.workspace.document(change.uri.as_str()).is_none()
+            {
+                self.workspace_index.index_file(&change.uri).await;
+                indexed = true;
+            }
+        }
+        if indexed && self.pulls_diagnostics() {
+            self.request_diagnostic_refresh().await;
+        }
     }
 
-    async fn execute_command(&self, _: ExecuteCommandParams) -> Result<Option<Value>> {
-        self.client
-            .log_message(MessageType::INFO, "command executed!")
-            .await;
-
-        match self.client.apply_edit(WorkspaceEdit::default()).await {
-            Ok(res) if res.applied => self.client.log_message(MessageType::INFO, "applied").await,
-            Ok(_) => self.client.log_message(MessageType::INFO, "rejected").await,
-            Err(err) => self.client.log_message(MessageType::ERROR, err).await,
+    async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<Value>> {
+        match params.command.as_str() {
+            ACTIVITY_COMMAND => {
+                let url = params.arguments.first().and_then(|url| url.as_str());
+                let role = self.settings.read().unwrap().role.clone();
+                Ok(Some(Value::String(activity(url, role.as_deref()).await?)))
+            }
+            CANCEL_BACKEND_COMMAND | TERMINATE_BACKEND_COMMAND => {
+                if !self.settings.read().unwrap().allow_backend_control {
+                    return Err(tower_lsp::jsonrpc::Error {
+                        message: "interrupting sessions is disabled, enable it with the \
+                            `pglsp.allowBackendControl` setting"
+                            .into(),
+                        ..tower_lsp::jsonrpc::Error::invalid_request()
+                    });
+                }
+                let pid = params
+                    .arguments
+                    .first()
+                    .and_then(Value::as_i64)
+                    .and_then(|pid| i32::try_from(pid).ok())
+                    .ok_or_else(|| {
+                        tower_lsp::jsonrpc::Error::invalid_params(
+                            "expected the pid of a session as first argument",
+                        )
+                    })?;
+                let url = params.arguments.get(1).and_then(|url| url.as_str());
+                let terminate = params.command == TERMINATE_BACKEND_COMMAND;
+                let role = self.settings.read().unwrap().role.clone();
+                Ok(Some(Value::String(
+                    signal_backend(url, role.as_deref(), pid, terminate).await?,
+                )))
+            }
+            SET_ROLE_COMMAND => {
+                let role = params
+                    .arguments
+                    .first()
+                    .and_then(|role| role.as_str())
+                    .map(str::to_string);
+                {
+                    let mut settings = self.settings.write().unwrap();
+                    if let Some(role) = role.as_ref().filter(|r| !settings.roles.contains(*r)) {
+                        return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                            "{} is not one of the roles of the `pglsp.roles` setting",
+                            role
+                        )));
+                    }
+                    settings.role = role;
+                }
+                self.publish_status().await;
+                // the role decides which objects of the database are visible
+                self.refresh_all_diagnostics().await;
+                Ok(None)
+            }
+            EXECUTE_STATEMENT_COMMAND => {
+                let settings = self.settings.read().unwrap().execution.clone();
+                if !settings.enabled {
+                    return Err(tower_lsp::jsonrpc::Error {
+                        message: "executing statements is disabled, enable it with the \
+                            `pglsp.execution.enabled` setting"
+                            .into(),
+                        ..tower_lsp::jsonrpc::Error::invalid_request()
+                    });
+                }
+                let uri = params
+                    .arguments
+                    .first()
+                    .and_then(|uri| serde_json::from_value::<Url>(uri.clone()).ok())
+                    .ok_or_else(|| {
+                        tower_lsp::jsonrpc::Error::invalid_params(
+                            "expected the uri of a document as first argument",
+                        )
+                    })?;
+                let position = params
+                    .arguments
+                    .get(1)
+                    .and_then(|position| serde_json::from_value::<Position>(position.clone()).ok())
+                    .ok_or_else(|| {
+                        tower_lsp::jsonrpc::Error::invalid_params(
+                            "expected the position of the statement as second argument",
+                        )
+                    })?;
+                let token = params
+                    .arguments
+                    .get(2)
+                    .and_then(|token| serde_json::from_value::<ProgressToken>(token.clone()).ok());
+                let (version, range, is_query, sql) = {
+                    let doc = self.workspace.document(uri.as_str()).ok_or_else(|| {
+                        tower_lsp::jsonrpc::Error::invalid_params(format!("{} is not open", uri))
+                    })?;
+                    let stmt = position_to_byte_offset(position, &doc.rope)
+                        .and_then(|offset| {
+                            doc.parse
+                                .stmts
+                                .iter()
+                                .find(|stmt| stmt.range.contains_inclusive(offset))
+                        })
+                        .ok_or_else(|| {
+                            tower_lsp::jsonrpc::Error::invalid_params(
+                                "there is no statement at the position",
+                            )
+                        })?;
+                    self.enforce_policy(&doc.parse.stmts, stmt.range)?;
+                    let sql = bind_parameters(&doc.parse.cst, stmt, &[]);
+                    (doc.version, stmt.range, is_cursor_query(&stmt.stmt), sql)
+                };
+                let url = self.database(&uri).and_then(|database| database.connection);
+                let role = self.settings.read().unwrap().role.clone();
+                let (result, notices) = execute_statement(
+                    url.as_deref(),
+                    role.as_deref(),
+                    &sql,
+                    is_query,
+                    &settings,
+                    &self.client,
+                    token,
+                )
+                .await?;
+                // the notices are dropped if the document changed in the meantime
+                let diagnostics = || -> Option<Vec<Diagnostic>> {
+                    let doc = self.workspace.document(uri.as_str())?;
+                    let stmt = doc
+                        .parse
+                        .stmts
+                        .iter()
+                        .find(|stmt| stmt.range == range)
+                        .filter(|_| doc.version == version)?;
+                    let diagnostics = notice_diagnostics(stmt, &doc.text(), &notices)
+                        .iter()
+                        .filter_map(|d| lint_diagnostic_to_diagnostic(d, &doc.rope))
+                        .collect();
+                    Some(diagnostics)
+                }();
+                if let Some(diagnostics) = diagnostics {
+                    self.notices
+                        .write()
+                        .unwrap()
+                        .insert(uri.to_string(), (version, diagnostics));
+                    self.refresh_diagnostics(uri).await;
+                }
+                Ok(Some(serde_json::to_value(result).unwrap()))
+            }
+            EXPLAIN_COMMAND => {
+                let uri = params
+                    .arguments
+                    .first()
+                    .and_then(|uri| serde_json::from_value::<Url>(uri.clone()).ok())
+                    .ok_or_else(|| {
+                        tower_lsp::jsonrpc::Error::invalid_params(
+                            "expected the uri of a document as first argument",
+                        )
+                    })?;
+                let position = params
+                    .arguments
+                    .get(1)
+                    .and_then(|position| serde_json::from_value::<Position>(position.clone()).ok())
+                    .ok_or_else(|| {
+                        tower_lsp::jsonrpc::Error::invalid_params(
+                            "expected the position of the statement as second argument",
+                        )
+                    })?;
+                let values = match params.arguments.get(2) {
+                    Some(values) => placeholder_values(values).ok_or_else(|| {
+                        tower_lsp::jsonrpc::Error::invalid_params(
+                            "expected the values of the placeholders as third argument",
+                        )
+                    })?,
+                    None => Vec::new(),
+                };
+                let sql = {
+                    let doc = self.workspace.document(uri.as_str()).ok_or_else(|| {
+                        tower_lsp::jsonrpc::Error::invalid_params(format!("{} is not open", uri))
+                    })?;
+                    let stmt = position_to_byte_offset(position, &doc.rope)
+                        .and_then(|offset| {
+                            doc.parse
+                                .stmts
+                                .iter()
+                                .find(|stmt| stmt.range.contains_inclusive(offset))
+                        })
+                        .filter(|stmt| is_explainable(&stmt.stmt))
+                        .ok_or_else(|| {
+                            tower_lsp::jsonrpc::Error::invalid_params(
+                                "there is no statement that can be explained at the position",
+                            )
+                        })?;
+                    self.enforce_policy(&doc.parse.stmts, stmt.range)?;
+                    bind_parameters(&doc.parse.cst, stmt, &values)
+                };
+                let url = self.database(&uri).and_then(|database| database.connection);
+                let role = self.settings.read().unwrap().role.clone();
+                let result = explain(url.as_deref(), role.as_deref(), &sql).await?;
+                Ok(Some(serde_json::to_value(result).unwrap()))
+            }
+            DUMP_CST_COMMAND => {
+                let uri = params
+                    .arguments
+                    .first()
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| {
+                        tower_lsp::jsonrpc::Error::invalid_params(
+                            "expected the uri of a document as first argument",
+                        )
+                    })?;
+                let doc = self.workspace.document(uri).ok_or_else(|| {
+                    tower_lsp::jsonrpc::Error::invalid_params(format!("{} is not open", uri))
+                })?;
+                Ok(Some(Value::String(dump_cst(&doc.parse.cst))))
+            }
+            STATEMENT_AST_COMMAND => {
+                let uri = params
+                    .arguments
+                    .first()
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| {
+                        tower_lsp::jsonrpc::Error::invalid_params(
+                            "expected the uri of a document as first argument",
+                        )
+                    })?;
+                let range = params
+                    .arguments
+                    .get(1)
+                    .and_then(|range| serde_json::from_value::<Range>(range.clone()).ok())
+                    .ok_or_else(|| {
+                        tower_lsp::jsonrpc::Error::invalid_params(
+                            "expected the selected range as second argument",
+                        )
+                    })?;
+                let doc = self.workspace.document(uri).ok_or_else(|| {
+                    tower_lsp::jsonrpc::Error::invalid_params(format!("{} is not open", uri))
+                })?;
+                let ast = range_to_text_range(range, &doc.rope)
+                    .and_then(|range| statement_ast(&doc.parse, &doc.rope, range))
+                    .ok_or_else(|| {
+                        tower_lsp::jsonrpc::Error::invalid_params(
+                            "the selection is not within a single statement",
+                        )
+                    })?;
+                Ok(Some(serde_json::to_value(ast).unwrap()))
+            }
+            command => Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                "unknown command: {}",
+                command
+            ))),
         }
-
-        Ok(None)
     }
 }
 
@@ -233,59 +1140,913 @@ struct TextDocumentItem {
     version: i32,
 }
 impl Backend {
+    /// Handles the `pglsp/syntaxTree` request
+    async fn syntax_tree(&self, params: SyntaxTreeParams) -> Result<String> {
+        let uri = params.text_document.uri;
+        let doc = self.workspace.document(uri.as_str()).ok_or_else(|| {
+            tower_lsp::jsonrpc::Error::invalid_params(format!("{} is not open", uri))
+        })?;
+        let range = params
+            .range
+            .and_then(|range| range_to_text_range(range, &doc.rope));
+        Ok(syntax_tree(&doc.parse, range))
+    }
+
+    /// Handles the `pglsp/schemaTree` request
+    async fn schema_tree(&self, params: SchemaTreeParams) -> Result<Vec<SchemaNode>> {
+        let database = self.browsed_database(&params.text_document.uri)?;
+        let role = self.settings.read().unwrap().role.clone();
+        let schemas = self.schema_cache.get(&database, role.as_deref()).await?;
+        let views = self.schema_cache.views(&database, role.as_deref()).await?;
+        Ok(schema_tree(&schemas, &views))
+    }
+
+    /// Handles the `pglsp/objectDefinition` request
+    async fn object_definition(&self, params: ObjectDefinitionParams) -> Result<Option<String>> {
+        let database = self.browsed_database(&params.text_document.uri)?;
+        let role = self.settings.read().unwrap().role.clone();
+        let schemas = self.schema_cache.get(&database, role.as_deref()).await?;
+        object_definition(
+            database.connection.as_deref(),
+            role.as_deref(),
+            &schemas,
+            &params,
+        )
+        .await
+    }
+
+    /// Handles the `pglsp/virtualDocument` request
+    async fn virtual_document(&self, params: VirtualDocumentParams) -> Result<String> {
+        let not_found =
+            || tower_lsp::jsonrpc::Error::invalid_params(format!("{} does not exist", params.uri));
+        let virtual_document = VirtualDocument::from_uri(&params.uri).ok_or_else(not_found)?;
+        let schemas = self.schemas(&virtual_document.document).await;
+        let views = self.views(&virtual_document.document).await;
+        virtual_document
+            .text(&schemas, &views)
+            .ok_or_else(not_found)
+    }
+
+    /// Returns the database of the document `uri` that the schema browser shows
+    fn browsed_database(&self, uri: &Url) -> Result<Database> {
+        self.database(uri).ok_or_else(|| {
+            tower_lsp::jsonrpc::Error::invalid_params(format!(
+                "no database is configured for {}",
+                uri
+            ))
+        })
+    }
+
+    /// Sends the active role to the client
+    async fn publish_status(&self) {
+        let role = self.settings.read().unwrap().role.clone();
+        self.client
+            .send_notification::<StatusNotification>(StatusParams {
+                role,
+                offline: is_offline(),
+            })
+            .await;
+    }
+
+    /// Reads `pglsp.toml` from the root of the workspace, if there is one, drops the schemas that
+    /// have been loaded with the previous configuration and refreshes the diagnostics
+    async fn load_config(&self) {
+        let Some(path) = self
+            .root
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|r| r.join(CONFIG_FILE))
+        else {
+            return;
+        };
+        let config = match fs::read_to_string(&path) {
+            Ok(text) => match Config::parse(&text) {
+                Ok(config) => config,
+                Err(err) => {
+                    self.client
+                        .log_message(
+                            MessageType::ERROR,
+                            format!("invalid {}: {}", path.display(), err),
+                        )
+                        .await;
+                    return;
+                }
+            },
+            Err(_) => Config::default(),
+        };
+        let dictionary = self.load_dictionary(&config).await;
+        *self.dictionary.write().unwrap() = dictionary.map(Arc::new);
+        *self.config.write().unwrap() = config;
+        self.schema_cache.clear().await;
+        self.schema_diagnostics.clear();
+        self.refresh_all_diagnostics().await;
+    }
+
+    /// Loads the catalog of the `messageCatalog` setting, or the bundled catalog of `locale`
+    async fn load_catalog(&self, locale: Option<&str>) {
+        let path = self.settings.read().unwrap().message_catalog.clone();
+        let catalog = match path {
+            Some(path) => {
+                let catalog = fs::read_to_string(&path)
+                    .map_err(|err| err.to_string())
+                    .and_then(|text| {
+                        messages::Catalog::parse(locale.unwrap_or_default(), &text)
+                            .map_err(|err| err.to_string())
+                    });
+                match catalog {
+                    Ok(catalog) => Some(catalog),
+                    Err(err) => {
+                        self.client
+                            .log_message(
+                                MessageType::ERROR,
+                                format!("invalid {}: {}", path.display(), err),
+                            )
+                            .await;
+                        None
+                    }
+                }
+            }
+            None => locale.and_then(messages::Catalog::bundled),
+        };
+        *self.catalog.write().unwrap() = catalog;
+    }
+
+    /// Reads the dictionaries of the spell check of `config`, or returns `None` if it is off
+    async fn load_dictionary(&self, config: &Config) -> Option<Dictionary> {
+        if !config.spelling.enabled {
+            return None;
+        }
+        let root = self.root.read().unwrap().clone()?;
+        let mut dictionary = Dictionary::default();
+        for path in &config.spelling.dictionaries {
+            let path = root.join(path);
+            match fs::read_to_string(&path) {
+                Ok(text) => dictionary.add_words(&text),
+                Err(err) => {
+                    self.client
+                        .log_message(
+                            MessageType::ERROR,
+                            format!("failed to read {}: {}", path.display(), err),
+                        )
+                        .await;
+                }
+            }
+        }
+        config
+            .spelling
+            .words
+            .iter()
+            .for_each(|word| dictionary.add_word(word));
+        Some(dictionary)
+    }
+
+    /// Indexes the sql files below `root` and loads the schemas of the databases of the workspace
+    /// into the schema cache, reporting the progress to the client until it is done or cancelled
+    async fn index_workspace(&self, root: &Path) {
+        let cancellation = Cancellation::new();
+        *self.indexing.write().unwrap() = Some(cancellation.clone());
+        let supported = self
+            .client_capabilities
+            .read()
+            .unwrap()
+            .window
+            .as_ref()
+            .and_then(|w| w.work_done_progress)
+            .unwrap_or(false);
+        let mut progress =
+            Progress::begin(&self.client, supported, INDEXING_TOKEN, "Indexing", true).await;
+
+        // the walk reads every directory of the workspace, so it runs on a blocking thread
+        progress
+            .report(0, 0, "looking for sql files".to_string())
+            .await;
+        let dir = root.to_path_buf();
+        let files = tokio::task::spawn_blocking(move || sql_files(&dir))
+            .await
+            .unwrap_or_default();
+        let databases = if is_offline() {
+            Vec::new()
+        } else {
+            self.config.read().unwrap().databases()
+        };
+        let total = files.len() + databases.len();
+        for (idx, uri) in files.iter().enumerate() {
+            if cancellation.is_cancelled() {
+                break;
+            }
+            self.workspace_index.index_file(uri).await;
+            let message = format!("{}/{} files", idx + 1, files.len());
+            progress.report(idx + 1, total, message).await;
+            // yields, so that the cancellation of the client is handled in between
+            tokio::task::yield_now().await;
+        }
+        let role = self.settings.read().unwrap().role.clone();
+        for (idx, database) in databases.iter().enumerate() {
+            if cancellation.is_cancelled() {
+                break;
+            }
+            let message = format!("schema {}/{}", idx + 1, databases.len());
+            progress.report(files.len() + idx, total, message).await;
+            let schemas = self.schema_cache.get(database, role.as_deref()).await;
+            if let Err(err) = schemas {
+                self.client.log_message(MessageType::ERROR, err).await;
+            }
+        }
+
+        let message = if cancellation.is_cancelled() {
+            "cancelled".to_string()
+        } else {
+            format!("{} files", files.len())
+        };
+        progress.end(message).await;
+        *self.indexing.write().unwrap() = None;
+    }
+
+    /// Reads the settings from `value`, logging the warnings about them, or the error if they are
+    /// invalid
+    async fn read_settings(&self, value: &Value) -> Option<Settings> {
+        match Settings::from_value(value) {
+            Ok((settings, warnings)) => {
+                for warning in warnings {
+                    self.client.log_message(MessageType::WARNING, warning).await;
+                }
+                Some(settings)
+            }
+            Err(err) => {
+                let message = format!("invalid settings: {}", err);
+                self.client.log_message(MessageType::ERROR, message).await;
+                None
+            }
+        }
+    }
+
+    /// Returns the database that `pglsp.toml` maps the directory of the document `uri` to, with
+    /// the connection of the `connection` setting if it configures none
+    fn database(&self, uri: &Url) -> Option<Database> {
+        let connection = self.settings.read().unwrap().for_document(uri).connection;
+        let root = self.root.read().unwrap().clone();
+        let path = uri
+            .to_file_path()
+            .ok()
+            .zip(root)
+            .and_then(|(path, root)| path.strip_prefix(root).ok().map(Path::to_path_buf));
+        match path {
+            Some(path) => self
+                .config
+                .read()
+                .unwrap()
+                .database_or(&path, connection.as_deref()),
+            // documents outside of the workspace only have the connection of the settings
+            None => Config::default().database_or(Path::new(""), connection.as_deref()),
+        }
+    }
+
+    /// Returns the lint rules that `pglsp.toml` enables for the document `uri`, and the levels of
+    /// its `lintRules` setting
+    fn lint_config(&self, uri: &Url) -> (LintConfig, BTreeMap<String, RuleLevel>) {
+        let levels = self.settings.read().unwrap().for_document(uri).lint_rules;
+        let config = lint_config(&self.config.read().unwrap().lint, &levels);
+        (config, levels)
+    }
+
+    /// Returns the cancellation of a request on the document `uri`, which is cancelled once the
+    /// document changes, the client cancels the request or it runs out of its latency budget
+    fn request_cancellation(&self, uri: &Url) -> Cancellation {
+        let cancellation = self
+            .workspace
+            .cancellation(uri.as_str())
+            .unwrap_or_default();
+        let cancellation = match current_request() {
+            Some(request) => cancellation.linked_with(&request),
+            None => cancellation,
+        };
+        match self.settings.read().unwrap().latency_budget_ms {
+            Some(budget) => cancellation.with_budget(Duration::from_millis(budget)),
+            None => cancellation,
+        }
+    }
+
+    /// Runs `f` for a request on the document `uri` once tower-lsp had the chance to drop the
+    /// request, and returns the error of a cancelled request instead of the result of `f` if the
+    /// request has been cancelled or the document changed in the meantime
+    async fn cancellable<T>(&self, uri: &Url, f: impl FnOnce() -> T) -> Result<T> {
+        let cancellation = self.request_cancellation(uri);
+        checkpoint(&cancellation).await?;
+        let result = f();
+        check(&cancellation)?;
+        Ok(result)
+    }
+
+    /// Returns the functions of the database of the document `uri`, or none in offline mode
+    async fn functions(&self, uri: &Url) -> Functions {
+        let Some(database) = self.database(uri).filter(|_| !is_offline()) else {
+            return Functions::default();
+        };
+        let role = self.settings.read().unwrap().role.clone();
+        let functions = self
+            .schema_cache
+            .functions(&database, role.as_deref())
+            .await;
+        self.or_log(functions).await
+    }
+
+    /// Returns the roles of the database of the document `uri`, or none in offline mode
+    async fn roles(&self, uri: &Url) -> Roles {
+        let Some(database) = self.database(uri).filter(|_| !is_offline()) else {
+            return Roles::default();
+        };
+        let role = self.settings.read().unwrap().role.clone();
+        let roles = self.schema_cache.roles(&database, role.as_deref()).await;
+        self.or_log(roles).await
+    }
+
+    /// Returns the schemas of the database of the document `uri`, or none in offline mode
+    async fn schemas(&self, uri: &Url) -> Schemas {
+        self.try_schemas(uri).await.unwrap_or_default()
+    }
+
+    /// Returns the schemas of the database of the document `uri`, or `None` in offline mode,
+    /// without a database, or after logging why they could not be loaded
+    async fn try_schemas(&self, uri: &Url) -> Option<Schemas> {
+        let database = self.database(uri).filter(|_| !is_offline())?;
+        let role = self.settings.read().unwrap().role.clone();
+        match self.schema_cache.get(&database, role.as_deref()).await {
+            Ok(schemas) => Some(schemas),
+            Err(err) => {
+                self.client
+                    .log_message(MessageType::ERROR, err.message)
+                    .await;
+                None
+            }
+        }
+    }
+
+    /// Returns the views of the database of the document `uri`, or none in offline mode
+    async fn views(&self, uri: &Url) -> Views {
+        let Some(database) = self.database(uri).filter(|_| !is_offline()) else {
+            return Views::default();
+        };
+        let role = self.settings.read().unwrap().role.clone();
+        let views = self.schema_cache.views(&database, role.as_deref()).await;
+        self.or_log(views).await
+    }
+
+    /// Returns what has been loaded from a database, or nothing after logging why it failed
+    async fn or_log<T: Default>(&self, result: Result<T>) -> T {
+        match result {
+            Ok(value) => value,
+            Err(err) => {
+                self.client
+                    .log_message(MessageType::ERROR, err.message)
+                    .await;
+                T::default()
+            }
+        }
+    }
+
+    /// Checks the document `uri` against the `schemas` of the database that `pglsp.toml` maps its
+    /// directory to. The diagnostics are computed again only after the document or the schemas
+    /// changed. There are none without schemas, e.g. in offline mode. Stops once `cancellation`
+    /// has been cancelled.
+    async fn schema_diagnostics(
+        &self,
+        uri: &Url,
+        schemas: Option<&Schemas>,
+        cancellation: &Cancellation,
+    ) -> Result<Vec<Diagnostic>> {
+        let Some((schemas, database)) = schemas.zip(self.database(uri)) else {
+            return Ok(Vec::new());
+        };
+        let Some(revision) = self
+            .workspace
+            .document(uri.as_str())
+            .map(|doc| doc.revision)
+        else {
+            return Ok(Vec::new());
+        };
+        let generation = self.schema_cache.generation();
+        let file_id = self.workspace.file_id(uri.as_str());
+        let role = self.settings.read().unwrap().role.clone();
+        let inputs = (revision, database, role, generation);
+        if let Some(diagnostics) = self.schema_diagnostics.get(file_id, &inputs) {
+            return Ok(diagnostics.to_vec());
+        }
+
+        let role = inputs.2.as_deref();
+        let table_sizes = self
+            .or_log(self.schema_cache.table_sizes(&inputs.1, role).await)
+            .await;
+        let casts = self
+            .or_log(self.schema_cache.casts(&inputs.1, role).await)
+            .await;
+
+        let Some(doc) = self.workspace.document(uri.as_str()) else {
+            return Ok(Vec::new());
+        };
+        // the document may have changed while the schemas were loaded
+        let inputs = (doc.revision, inputs.1, inputs.2, inputs.3);
+        let mut state = MigrationState::from_schemas((**schemas).clone());
+        state.casts = (*casts).clone();
+        let rewrites = check_table_rewrites(&doc.parse.stmts, &state, &table_sizes);
+        let diagnostics = state
+            .check_cancellable(&doc.parse.stmts, cancellation)
+            .map_err(|_| request_cancelled(cancellation))?
+            .iter()
+            .chain(&rewrites)
+            .filter_map(|d| lint_diagnostic_to_diagnostic(d, &doc.rope))
+            .collect();
+        Ok(self
+            .schema_diagnostics
+            .insert(file_id, inputs, diagnostics)
+            .to_vec())
+    }
+
+    /// Reports the objects of `COMMENT ON` and `SECURITY LABEL` in the document `uri` that neither
+    /// the workspace nor the database of the document define
+    async fn comment_diagnostics(&self, uri: &Url, schemas: &Schemas) -> Vec<Diagnostic> {
+        let has_comments = self
+            .workspace
+            .document(uri.as_str())
+            .is_some_and(|doc| !commented_objects(&doc.parse.cst, &doc.parse.stmts).is_empty());
+        if !has_comments {
+            return Vec::new();
+        }
+        let has_database = self.database(uri).is_some() && !is_offline();
+        let views = self.views(uri).await;
+        let functions = self.functions(uri).await;
+        let catalog = Catalog {
+            schemas,
+            views: &views,
+            functions: &functions,
+        };
+
+        let Some(doc) = self.workspace.document(uri.as_str()) else {
+            return Vec::new();
+        };
+        check_commented_objects(
+            &doc.parse.cst,
+            &doc.parse.stmts,
+            |object| self.workspace_index.defines(object),
+            has_database.then_some(&catalog),
+        )
+        .iter()
+        .filter_map(|d| lint_diagnostic_to_diagnostic(d, &doc.rope))
+        .collect()
+    }
+
+    /// Reports the names of relations that functions such as `to_regclass` take as strings in the
+    /// document `uri`, if neither the workspace nor the database of the document define them.
+    /// Nothing is reported unless the `schemas` and views of the database could be loaded.
+    async fn name_argument_diagnostics(
+        &self,
+        uri: &Url,
+        schemas: Option<&Schemas>,
+    ) -> Vec<Diagnostic> {
+        let Some(schemas) = schemas else {
+            return Vec::new();
+        };
+        let has_names = self
+            .workspace
+            .document(uri.as_str())
+            .is_some_and(|doc| !name_arguments(&doc.parse.cst, &doc.parse.stmts).is_empty());
+        let Some(database) = self.database(uri).filter(|_| has_names) else {
+            return Vec::new();
+        };
+        let role = self.settings.read().unwrap().role.clone();
+        let Ok(views) = self.schema_cache.views(&database, role.as_deref()).await else {
+            return Vec::new();
+        };
+        let catalog = Catalog {
+            schemas,
+            views: &views,
+            functions: &[],
+        };
+
+        let Some(doc) = self.workspace.document(uri.as_str()) else {
+            return Vec::new();
+        };
+        check_name_arguments(
+            &doc.parse.cst,
+            &doc.parse.stmts,
+            |argument| self.workspace_index.defines_name(argument),
+            &catalog,
+        )
+        .iter()
+        .filter_map(|d| lint_diagnostic_to_diagnostic(d, &doc.rope))
+        .collect()
+    }
+
+    /// Reports the findings of the lint rules and the policy violations in the document `uri`
+    fn lint_diagnostics(
+        &self,
+        uri: &Url,
+        cancellation: &Cancellation,
+    ) -> std::result::Result<Vec<Diagnostic>, Cancelled> {
+        let (config, levels) = self.lint_config(uri);
+        let policy = policy(&self.config.read().unwrap().policy);
+        self.with_documents(|documents| {
+            documents
+                .iter()
+                .find(|doc| doc.uri == *uri)
+                .map_or(Ok(Vec::new()), |doc| {
+                    lint_diagnostics(documents, doc, &config, &levels, &policy, cancellation)
+                })
+        })
+    }
+
+    /// Refuses to run the statement at `range` of `stmts` if the policy of `pglsp.toml` denies it
+    fn enforce_policy(&self, stmts: &[RawStmt], range: TextRange) -> Result<()> {
+        let policy = policy(&self.config.read().unwrap().policy);
+        match check_policy(stmts, &policy)
+            .into_iter()
+            .find(|d| d.range == range)
+        {
+            Some(violation) => Err(tower_lsp::jsonrpc::Error {
+                message: violation.message.into(),
+                ..tower_lsp::jsonrpc::Error::invalid_request()
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Reports the misspelled words of the document `uri`, if `pglsp.toml` turns on the spell check
+    fn spelling_diagnostics(&self, uri: &Url) -> Vec<Diagnostic> {
+        let Some(dictionary) = self.dictionary.read().unwrap().clone() else {
+            return Vec::new();
+        };
+        let Some(doc) = self.workspace.document(uri.as_str()) else {
+            return Vec::new();
+        };
+        check_spelling(&doc.parse.cst, &doc.parse.stmts, &dictionary)
+            .iter()
+            .filter_map(|d| lint_diagnostic_to_diagnostic(d, &doc.rope))
+            .collect()
+    }
+
+    /// Reports the statements that write in the document `uri`, if `pglsp.toml` lists its
+    /// directory as read-only
+    fn read_only_diagnostics(&self, uri: &Url) -> Vec<Diagnostic> {
+        let is_read_only = || -> Option<bool> {
+            let path = uri.to_file_path().ok()?;
+            let root = self.root.read().unwrap().clone()?;
+            let path = path.strip_prefix(root).ok()?;
+            Some(self.config.read().unwrap().is_read_only(path))
+        }();
+        if is_read_only != Some(true) {
+            return Vec::new();
+        }
+        let Some(doc) = self.workspace.document(uri.as_str()) else {
+            return Vec::new();
+        };
+        check_read_only(&doc.parse.stmts)
+            .iter()
+            .filter_map(|d| lint_diagnostic_to_diagnostic(d, &doc.rope))
+            .collect()
+    }
+
+    /// Reports the foreign keys of the document `uri` whose columns neither the database of the
+    /// document nor the open documents index
+    fn foreign_key_diagnostics(&self, uri: &Url, schemas: &Schemas) -> Vec<Diagnostic> {
+        self.with_documents(|documents| {
+            let Some(doc) = documents.iter().find(|doc| doc.uri == *uri) else {
+                return Vec::new();
+            };
+            foreign_key_diagnostics(documents, doc, schemas)
+        })
+    }
+
+    /// Reports the column references of the document `uri` that several tables of their query have
+    fn ambiguous_column_diagnostics(&self, uri: &Url, schemas: &Schemas) -> Vec<Diagnostic> {
+        self.with_documents(|documents| {
+            let Some(doc) = documents.iter().find(|doc| doc.uri == *uri) else {
+                return Vec::new();
+            };
+            ambiguous_column_diagnostics(documents, doc, schemas)
+        })
+    }
+
+    /// Advises on the queries of the document `uri` that filter a partitioned table without
+    /// pruning its partitions
+    fn partition_pruning_diagnostics(&self, uri: &Url, schemas: &Schemas) -> Vec<Diagnostic> {
+        self.with_documents(|documents| {
+            let Some(doc) = documents.iter().find(|doc| doc.uri == *uri) else {
+                return Vec::new();
+            };
+            partition_pruning_diagnostics(documents, doc, schemas)
+        })
+    }
+
+    /// Advises on the fillfactor of the tables that the updates of the document `uri` change, if
+    /// the workspace updates them heavily
+    fn fillfactor_diagnostics(&self, uri: &Url, schemas: &Schemas) -> Vec<Diagnostic> {
+        let (config, levels) = self.lint_config(uri);
+        let version = self.settings.read().unwrap().for_document(uri).pg_version();
+        let update_counts = self.workspace_index.update_counts();
+        self.with_documents(|documents| {
+            let Some(doc) = documents.iter().find(|doc| doc.uri == *uri) else {
+                return Vec::new();
+            };
+            fillfactor_diagnostics(
+                documents,
+                doc,
+                schemas,
+                &update_counts,
+                &config,
+                &levels,
+                version,
+            )
+        })
+    }
+
+    /// Returns the relations and sequences of the workspace and the database of the document
+    /// `uri`, by their qualified names
+    async fn named_objects(&self, uri: &Url) -> Vec<(NameKind, String)> {
+        let schemas = self.schemas(uri).await;
+        let views = self.views(uri).await;
+        let mut objects = self.workspace_index.named_objects();
+        for schema in schemas.values() {
+            for table in schema.tables.values() {
+                objects.push((
+                    NameKind::Relation,
+                    format!("{}.{}", schema.name, table.name),
+                ));
+                objects.extend(
+                    table.owned_sequences.keys().map(|sequence| {
+                        (NameKind::Sequence, format!("{}.{}", schema.name, sequence))
+                    }),
+                );
+            }
+        }
+        objects.extend(views.iter().map(|view| {
+            (
+                NameKind::Relation,
+                format!("{}.{}", view.schema_name, view.name),
+            )
+        }));
+        objects
+    }
+
+    /// Calls `f` with all open documents
+    fn with_documents<T>(&self, f: impl FnOnce(&[Document<'_>]) -> T) -> T {
+        self.workspace.with_documents(|documents| {
+            let documents = documents
+                .iter()
+                .filter_map(|doc| {
+                    Some(Document {
+                        uri: Url::parse(&doc.uri).ok()?,
+                        rope: &doc.rope,
+                        parse: &doc.parse,
+                    })
+                })
+                .collect::<Vec<_>>();
+            f(&documents)
+        })
+    }
+
     async fn on_change(&self, params: TextDocumentItem) {
         self.client
             .log_message(MessageType::INFO, format!("on_change {:?}", params.uri))
             .await;
-        let rope = ropey::Rope::from_str(&params.text);
-        self.document_map
-            .insert(params.uri.to_string(), rope.clone());
+        let pg_version = self
+            .settings
+            .read()
+            .unwrap()
+            .for_document(&params.uri)
+            .pg_version();
+        self.workspace.update_with_pg_version(
+            params.uri.as_str(),
+            params.version,
+            &params.text,
+            pg_version,
+        );
 
-        let rope = ropey::Rope::from_str(&params.text);
+        if let Some(doc) = self.workspace.document(params.uri.as_str()) {
+            self.workspace_index
+                .index_document(&params.uri, &doc.rope, &doc.parse);
+        }
 
-        let result = parse_source(&params.text);
+        self.publish_diagnostics(params.uri).await;
+        // the diagnostics of other documents may depend on this one, e.g. on the tables that it
+        // creates or updates, and clients that push them get those with their next edit
+        if self.pulls_diagnostics() {
+            self.request_diagnostic_refresh().await;
+        }
+    }
 
-        dbg!(&result.cst);
+    /// Parses the open documents again with the settings of their folders and refreshes their
+    /// diagnostics, e.g. after the targeted version of Postgres or the levels of lint rules changed
+    async fn reparse_documents(&self) {
+        let documents = self.workspace.with_documents(|documents| {
+            documents
+                .iter()
+                .map(|doc| (doc.uri.clone(), doc.version, doc.text()))
+                .collect::<Vec<_>>()
+        });
+        for (uri, version, text) in documents {
+            let Ok(uri) = Url::parse(&uri) else {
+                continue;
+            };
+            let pg_version = self
+                .settings
+                .read()
+                .unwrap()
+                .for_document(&uri)
+                .pg_version();
+            self.workspace
+                .update_with_pg_version(uri.as_str(), version, &text, pg_version);
+        }
+        self.refresh_all_diagnostics().await;
+    }
 
-        // update semantic tokens
-        let semantic_tokens = result
-            .cst
-            .descendants_with_tokens()
-            .filter_map(|item| match semantic_token_from_syntax_kind(item.kind()) {
-                Some(token_type) => Some(ImCompleteSemanticToken {
-                    start: item.text_range().start().into(),
-                    token_type,
-                    length: item.text_range().len().into(),
-                }),
-                None => None,
-            })
-            .collect::<Vec<_>>();
+    /// Returns the version of the document `uri` and all its diagnostics, or the error of a
+    /// cancelled request once `cancellation` has been cancelled
+    async fn diagnostics(
+        &self,
+        uri: &Url,
+        cancellation: &Cancellation,
+    ) -> Result<Option<(i32, Vec<Diagnostic>)>> {
+        let (version, mut diagnostics) = {
+            let Some(doc) = self.workspace.document(uri.as_str()) else {
+                return Ok(None);
+            };
+            let diagnostics = doc
+                .parse
+                .errors
+                .iter()
+                .filter_map(|error| syntax_error_to_diagnostic(error, &doc.rope))
+                .collect::<Vec<_>>();
+            (doc.version, diagnostics)
+        };
+        diagnostics.extend(
+            self.lint_diagnostics(uri, cancellation)
+                .map_err(|_| request_cancelled(cancellation))?,
+        );
+        diagnostics.extend(self.read_only_diagnostics(uri));
+        diagnostics.extend(self.spelling_diagnostics(uri));
+        checkpoint(cancellation).await?;
+        // the schemas are loaded once for all checks that need them
+        let schemas = self.try_schemas(uri).await;
+        checkpoint(cancellation).await?;
+        diagnostics.extend(
+            self.schema_diagnostics(uri, schemas.as_ref(), cancellation)
+                .await?,
+        );
+        diagnostics.extend(self.name_argument_diagnostics(uri, schemas.as_ref()).await);
+        checkpoint(cancellation).await?;
+        let schemas = schemas.unwrap_or_default();
+        diagnostics.extend(self.comment_diagnostics(uri, &schemas).await);
+        checkpoint(cancellation).await?;
+        diagnostics.extend(self.foreign_key_diagnostics(uri, &schemas));
+        diagnostics.extend(self.ambiguous_column_diagnostics(uri, &schemas));
+        diagnostics.extend(self.partition_pruning_diagnostics(uri, &schemas));
+        diagnostics.extend(self.fillfactor_diagnostics(uri, &schemas));
+        checkpoint(cancellation).await?;
+        // the notices only apply to the text that raised them
+        if let Some((_, notices)) = self
+            .notices
+            .read()
+            .unwrap()
+            .get(uri.as_str())
+            .filter(|(notice_version, _)| *notice_version == version)
+        {
+            diagnostics.extend(notices.iter().cloned());
+        }
+        if let Some(catalog) = self.catalog.read().unwrap().as_ref() {
+            for diagnostic in &mut diagnostics {
+                if let Some(message) = catalog.translate(&diagnostic.message) {
+                    diagnostic.message = message;
+                }
+            }
+        }
+        Ok(Some((version, diagnostics)))
+    }
 
-        // publish diagnostics
-        //
-        let diagnostics = result
-            .errors
-            .iter()
-            .map(|error| {
-                Diagnostic::new_simple(
-                    Range {
-                        start: offset_to_position(error.range().start().into(), &rope).unwrap(),
-                        end: offset_to_position(error.range().start().into(), &rope).unwrap(),
-                    },
-                    error.to_string(),
-                )
-            })
-            .collect::<Vec<_>>();
+    /// Returns true if the client pulls diagnostics with `textDocument/diagnostic`, so that they
+    /// are not pushed
+    fn pulls_diagnostics(&self) -> bool {
+        self.client_capabilities
+            .read()
+            .unwrap()
+            .text_document
+            .as_ref()
+            .is_some_and(|t| t.diagnostic.is_some())
+    }
 
+    /// Publishes all diagnostics of the document `uri`, unless the client pulls them
+    async fn publish_diagnostics(&self, uri: Url) {
+        if self.pulls_diagnostics() {
+            return;
+        }
+        let Some(cancellation) = self.workspace.cancellation(uri.as_str()) else {
+            return;
+        };
+        // the diagnostics of a version that changed before they are done are not published
+        let Ok(Some((version, diagnostics))) = self.diagnostics(&uri, &cancellation).await else {
+            return;
+        };
+        let version_support = self
+            .client_capabilities
+            .read()
+            .unwrap()
+            .text_document
+            .as_ref()
+            .and_then(|t| t.publish_diagnostics.as_ref())
+            .and_then(|p| p.version_support)
+            .unwrap_or(false);
         self.client
-            .publish_diagnostics(params.uri.clone(), diagnostics, Some(params.version))
+            .publish_diagnostics(uri, diagnostics, version_support.then_some(version))
             .await;
+    }
+
+    /// Asks the client to request the code lenses of its documents again, if it supports that
+    async fn refresh_code_lenses(&self) {
+        let refresh_support = self
+            .client_capabilities
+            .read()
+            .unwrap()
+            .workspace
+            .as_ref()
+            .and_then(|w| w.code_lens.as_ref())
+            .and_then(|c| c.refresh_support)
+            .unwrap_or(false);
+        if refresh_support {
+            let refresh = self.client.code_lens_refresh().await;
+            self.or_log(refresh).await;
+        }
+    }
 
-        self.semantic_token_map
-            .insert(params.uri.to_string(), semantic_tokens);
+    /// Tells the client that the diagnostics of the document `uri` changed without an edit, e.g.
+    /// by the notices of a statement. Clients that pull diagnostics are asked to pull them again.
+    async fn refresh_diagnostics(&self, uri: Url) {
+        if !self.pulls_diagnostics() {
+            self.publish_diagnostics(uri).await;
+            return;
+        }
+        self.request_diagnostic_refresh().await;
+    }
+
+    /// Tells the client that the diagnostics of all open documents may have changed without an
+    /// edit of theirs, e.g. since `pglsp.toml` or the role changed
+    async fn refresh_all_diagnostics(&self) {
+        if !self.pulls_diagnostics() {
+            let uris = self.workspace.with_documents(|documents| {
+                documents
+                    .iter()
+                    .filter_map(|doc| Url::parse(&doc.uri).ok())
+                    .collect::<Vec<_>>()
+            });
+            for uri in uris {
+                self.publish_diagnostics(uri).await;
+            }
+            return;
+        }
+        self.request_diagnostic_refresh().await;
+    }
+
+    /// Asks a client that pulls diagnostics to pull those of its documents again, if it supports
+    /// that
+    async fn request_diagnostic_refresh(&self) {
+        let refresh_support = self
+            .client_capabilities
+            .read()
+            .unwrap()
+            .workspace
+            .as_ref()
+            .and_then(|w| w.diagnostic.as_ref())
+            .and_then(|d| d.refresh_support)
+            .unwrap_or(false);
+        if refresh_support {
+            let refresh = self
+                .client
+                .send_request::<request::WorkspaceDiagnosticRefresh>(())
+                .await;
+            self.or_log(refresh).await;
+        }
+    }
+}
 
-        self.parse_map.insert(params.uri.to_string(), result);
+/// Returns the commands that the server offers, which are only those that do not need a
+/// database in offline mode
+fn commands() -> Vec<String> {
+    let mut commands = vec![
+        DUMP_CST_COMMAND.to_string(),
+        STATEMENT_AST_COMMAND.to_string(),
+    ];
+    if !is_offline() {
+        commands.extend(
+            [
+                ACTIVITY_COMMAND,
+                CANCEL_BACKEND_COMMAND,
+                TERMINATE_BACKEND_COMMAND,
+                SET_ROLE_COMMAND,
+                EXPLAIN_COMMAND,
+                EXECUTE_STATEMENT_COMMAND,
+            ]
+            .map(str::to_string),
+        );
     }
+    commands
 }
 
 #[tokio::main]
@@ -297,12 +2058,27 @@ async fn main() {
 
     let (service, socket) = LspService::build(|client| Backend {
         client,
-        // ast_map: DashMap::new(),
-        document_map: DashMap::new(),
-        parse_map: DashMap::new(),
-        semantic_token_map: DashMap::new(),
+        client_capabilities: RwLock::new(ClientCapabilities::default()),
+        workspace: Workspace::new(),
+        settings: RwLock::new(Settings::default()),
+        catalog: RwLock::new(None),
+        root: RwLock::new(None),
+        config: RwLock::new(Config::default()),
+        dictionary: RwLock::new(None),
+        schema_cache: SchemaCache::default(),
+        semantic_tokens: Memo::new(),
+        schema_diagnostics: Memo::new(),
+        workspace_index: WorkspaceIndex::default(),
+        indexing: RwLock::new(None),
+        notices: RwLock::new(HashMap::new()),
     })
+    .custom_method(SYNTAX_TREE_REQUEST, Backend::syntax_tree)
+    .custom_method(SCHEMA_TREE_REQUEST, Backend::schema_tree)
+    .custom_method(OBJECT_DEFINITION_REQUEST, Backend::object_definition)
+    .custom_method(VIRTUAL_DOCUMENT_REQUEST, Backend::virtual_document)
     .finish();
 
-    Server::new(stdin, stdout, socket).serve(service).await;
+    Server::new(stdin, stdout, socket)
+        .serve(CancelRequests::new(service))
+        .await;
 }
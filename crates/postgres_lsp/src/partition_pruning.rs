@@ -0,0 +1,31 @@
+//! Advice on queries that filter a partitioned table without pruning its partitions.
+//!
+//! The partition keys are looked up in the schemas of the database of the document as changed by
+//! the other open documents and the statements before the query, so a table that a migration
+//! partitions is known before it is applied. See [`analyser::partition_pruning`].
+
+use std::collections::BTreeMap;
+
+use analyser::partition_pruning::check_partition_pruning;
+use analyser::Schema;
+use parser::TextSize;
+use tower_lsp::lsp_types::*;
+
+use crate::rename::Document;
+use crate::rewrite::state_at;
+use crate::utils::lint_diagnostic_to_diagnostic;
+
+/// Returns an advice for every query of `document` that scans all partitions of a table although
+/// it filters the table
+pub fn partition_pruning_diagnostics(
+    documents: &[Document<'_>],
+    document: &Document<'_>,
+    schemas: &BTreeMap<String, Schema>,
+) -> Vec<Diagnostic> {
+    // the statements of the document are applied while they are checked
+    let mut state = state_at(documents, document, Some(TextSize::from(0)), schemas);
+    check_partition_pruning(&document.parse.cst, &document.parse.stmts, &mut state)
+        .iter()
+        .filter_map(|d| lint_diagnostic_to_diagnostic(d, document.rope))
+        .collect()
+}
@@ -0,0 +1,126 @@
+//! Work-done progress of long tasks of the server, e.g. indexing the workspace.
+//!
+//! The server creates a token with `window/workDoneProgress/create` and reports the task on it with
+//! `$/progress`, so that the client can show it, e.g. in its status bar. Clients that do not
+//! support work-done progress get no reports at all. A cancellable task is cancelled by the client
+//! with `window/workDoneProgress/cancel` on its token.
+
+use tower_lsp::lsp_types::notification::Progress as ProgressNotification;
+use tower_lsp::lsp_types::request::WorkDoneProgressCreate;
+use tower_lsp::lsp_types::{
+    NumberOrString, ProgressParams, ProgressParamsValue, ProgressToken, WorkDoneProgress,
+    WorkDoneProgressBegin, WorkDoneProgressCreateParams, WorkDoneProgressEnd,
+    WorkDoneProgressReport,
+};
+use tower_lsp::Client;
+
+/// The token of the progress of indexing the workspace
+pub const INDEXING_TOKEN: &str = "pglsp/indexing";
+
+/// A task whose progress is reported to the client
+pub struct Progress<'a> {
+    client: &'a Client,
+    /// The token of the progress, or `None` if it is not reported
+    token: Option<ProgressToken>,
+    /// The last reported percentage, so that the client only gets a report when it changes
+    percentage: u32,
+}
+
+impl<'a> Progress<'a> {
+    /// Creates the progress `token` and begins the task `title` on it, if the client `supported`
+    /// work-done progress and accepts the token
+    pub async fn begin(
+        client: &'a Client,
+        supported: bool,
+        token: &str,
+        title: &str,
+        cancellable: bool,
+    ) -> Progress<'a> {
+        let token = NumberOrString::String(token.to_string());
+        let created = supported
+            && client
+                .send_request::<WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+                    token: token.clone(),
+                })
+                .await
+                .is_ok();
+        let progress = Progress {
+            client,
+            token: created.then_some(token),
+            percentage: 0,
+        };
+        progress
+            .send(WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                title: title.to_string(),
+                cancellable: Some(cancellable),
+                message: None,
+                percentage: Some(0),
+            }))
+            .await;
+        progress
+    }
+
+    /// Reports that `done` of `total` steps are done, with `message`. Reports that do not change
+    /// the percentage are skipped, except for the first and the last.
+    pub async fn report(&mut self, done: usize, total: usize, message: String) {
+        let Some(percentage) = percentage(self.percentage, done, total) else {
+            return;
+        };
+        self.percentage = percentage;
+        self.send(WorkDoneProgress::Report(WorkDoneProgressReport {
+            cancellable: None,
+            message: Some(message),
+            percentage: Some(percentage),
+        }))
+        .await;
+    }
+
+    /// Ends the task with `message`
+    pub async fn end(self, message: String) {
+        self.send(WorkDoneProgress::End(WorkDoneProgressEnd {
+            message: Some(message),
+        }))
+        .await;
+    }
+
+    async fn send(&self, value: WorkDoneProgress) {
+        let Some(token) = self.token.clone() else {
+            return;
+        };
+        self.client
+            .send_notification::<ProgressNotification>(ProgressParams {
+                token,
+                value: ProgressParamsValue::WorkDone(value),
+            })
+            .await;
+    }
+}
+
+/// Returns the percentage of `done` of `total` steps, or `None` if the report of `last` already
+/// showed it and the step is neither the first nor the last, nor one before the first step
+fn percentage(last: u32, done: usize, total: usize) -> Option<u32> {
+    let percentage = (done * 100 / total.max(1)).min(100) as u32;
+    (percentage != last || done <= 1 || done == total).then_some(percentage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentage() {
+        // the steps before the first one and the first and last steps are always reported
+        assert_eq!(percentage(0, 0, 0), Some(0));
+        assert_eq!(percentage(0, 1, 1000), Some(0));
+        assert_eq!(percentage(99, 1000, 1000), Some(100));
+        assert_eq!(percentage(100, 1000, 1000), Some(100));
+        // the others only once the percentage changes
+        assert_eq!(percentage(0, 2, 1000), None);
+        assert_eq!(percentage(0, 10, 1000), Some(1));
+        assert_eq!(percentage(1, 11, 1000), None);
+        assert_eq!(percentage(0, 1, 3), Some(33));
+        assert_eq!(percentage(33, 2, 3), Some(66));
+        // more steps than the total do not go beyond 100
+        assert_eq!(percentage(100, 5, 3), None);
+    }
+}
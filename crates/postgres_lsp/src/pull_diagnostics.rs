@@ -0,0 +1,87 @@
+//! Diagnostics that clients pull with `textDocument/diagnostic`, as of LSP 3.17.
+//!
+//! Clients that support pulling ask for the diagnostics of a document when they need them, and the
+//! server stops pushing them with `textDocument/publishDiagnostics` on every edit. Every report has
+//! a result id, which is the hash of its diagnostics, and the client sends the id of the report it
+//! has with its next request. If the diagnostics are still the same, the report is `unchanged`
+//! instead of repeating them.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use tower_lsp::lsp_types::*;
+
+/// Returns the report of `diagnostics` for a client that has the report `previous_result_id`
+pub fn diagnostic_report(
+    diagnostics: Vec<Diagnostic>,
+    previous_result_id: Option<&str>,
+) -> DocumentDiagnosticReportResult {
+    let result_id = result_id(&diagnostics);
+    let report = if previous_result_id == Some(result_id.as_str()) {
+        DocumentDiagnosticReport::Unchanged(RelatedUnchangedDocumentDiagnosticReport {
+            related_documents: None,
+            unchanged_document_diagnostic_report: UnchangedDocumentDiagnosticReport { result_id },
+        })
+    } else {
+        DocumentDiagnosticReport::Full(RelatedFullDocumentDiagnosticReport {
+            related_documents: None,
+            full_document_diagnostic_report: FullDocumentDiagnosticReport {
+                result_id: Some(result_id),
+                items: diagnostics,
+            },
+        })
+    };
+    DocumentDiagnosticReportResult::Report(report)
+}
+
+/// Returns the hash of `diagnostics`, which is the same for the same diagnostics
+fn result_id(diagnostics: &[Diagnostic]) -> String {
+    let mut hasher = DefaultHasher::new();
+    // `Diagnostic` is not `Hash`, but its JSON is
+    serde_json::to_string(diagnostics)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(message: &str) -> Diagnostic {
+        Diagnostic {
+            range: Range::new(Position::new(0, 0), Position::new(0, 6)),
+            message: message.to_string(),
+            ..Diagnostic::default()
+        }
+    }
+
+    fn full_result_id(report: DocumentDiagnosticReportResult) -> String {
+        match report {
+            DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(report)) => {
+                report.full_document_diagnostic_report.result_id.unwrap()
+            }
+            report => panic!("expected a full report, got {:?}", report),
+        }
+    }
+
+    #[test]
+    fn test_diagnostic_report() {
+        let result_id = full_result_id(diagnostic_report(vec![diagnostic("a")], None));
+        match diagnostic_report(vec![diagnostic("a")], Some(&result_id)) {
+            DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Unchanged(report)) => {
+                assert_eq!(
+                    report.unchanged_document_diagnostic_report.result_id,
+                    result_id
+                )
+            }
+            report => panic!("expected an unchanged report, got {:?}", report),
+        }
+        let changed = full_result_id(diagnostic_report(vec![diagnostic("b")], Some(&result_id)));
+        assert_ne!(changed, result_id);
+        assert_ne!(
+            full_result_id(diagnostic_report(Vec::new(), Some(&result_id))),
+            result_id
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! Warnings about ambiguous column references, with a quick fix for every table that has the
+//! column.
+//!
+//! The columns of the joined tables are looked up in the schemas of the database of the document
+//! as changed by the other open documents and by the statements of the document before the query,
+//! see [`analyser::ambiguous_columns`]. Each fix prefixes the reference with the alias or name of
+//! one of the candidates.
+
+use std::collections::BTreeMap;
+
+use analyser::ambiguous_columns::{ambiguous_columns, AmbiguousColumn};
+use analyser::Schema;
+use parser::{TextRange, TextSize};
+use tower_lsp::lsp_types::*;
+
+use crate::rename::Document;
+use crate::rewrite::schemas_at;
+use crate::utils::{lint_diagnostic_to_diagnostic, text_range_to_range};
+
+/// Returns a warning for every ambiguous column reference of `document`
+pub fn ambiguous_column_diagnostics(
+    documents: &[Document<'_>],
+    document: &Document<'_>,
+    schemas: &BTreeMap<String, Schema>,
+) -> Vec<Diagnostic> {
+    columns(documents, document, schemas)
+        .iter()
+        .filter_map(|column| lint_diagnostic_to_diagnostic(&column.diagnostic(), document.rope))
+        .collect()
+}
+
+/// Returns the actions that qualify the ambiguous column reference at `offset` of `document`, one
+/// per table that has the column
+pub fn qualify_column_actions(
+    documents: &[Document<'_>],
+    document: &Document<'_>,
+    offset: TextSize,
+    schemas: &BTreeMap<String, Schema>,
+) -> Vec<CodeAction> {
+    let Some(column) = columns(documents, document, schemas)
+        .into_iter()
+        .find(|column| column.range.contains_inclusive(offset))
+    else {
+        return Vec::new();
+    };
+    let Some(diagnostic) = lint_diagnostic_to_diagnostic(&column.diagnostic(), document.rope)
+    else {
+        return Vec::new();
+    };
+    let Some(range) = text_range_to_range(TextRange::empty(column.range.start()), document.rope)
+    else {
+        return Vec::new();
+    };
+    column
+        .candidates
+        .iter()
+        .map(|candidate| CodeAction {
+            title: format!("Qualify with {}", candidate),
+            kind: Some(CodeActionKind::QUICKFIX),
+            diagnostics: Some(vec![diagnostic.clone()]),
+            edit: Some(WorkspaceEdit {
+                changes: Some(
+                    [(
+                        document.uri.clone(),
+                        vec![TextEdit::new(range, format!("{}.", candidate))],
+                    )]
+                    .into_iter()
+                    .collect(),
+                ),
+                ..WorkspaceEdit::default()
+            }),
+            ..CodeAction::default()
+        })
+        .collect()
+}
+
+fn columns(
+    documents: &[Document<'_>],
+    document: &Document<'_>,
+    schemas: &BTreeMap<String, Schema>,
+) -> Vec<AmbiguousColumn> {
+    let schemas = schemas_at(documents, document, Some(TextSize::from(0)), schemas);
+    ambiguous_columns(&document.parse.cst, &document.parse.stmts, &schemas)
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+    use ropey::Rope;
+
+    use super::*;
+    use crate::utils::apply_action;
+
+    #[test]
+    fn test_qualify_column_actions() {
+        let text = "create table contact (id int, name text);
+create table orders (id int, contact_id int);
+select id from contact c join orders on c.id = contact_id;";
+        let uri = Url::parse("file:///db/queries.sql").unwrap();
+        let rope = Rope::from_str(text);
+        let parse = parse_source(text);
+        let document = Document {
+            uri: uri.clone(),
+            rope: &rope,
+            parse: &parse,
+        };
+        let documents = [document];
+        let actions = |at: usize| {
+            qualify_column_actions(
+                &documents,
+                &documents[0],
+                TextSize::try_from(at).unwrap(),
+                &BTreeMap::new(),
+            )
+        };
+
+        let actions_at_id = actions(text.rfind("id from").unwrap());
+        assert_eq!(
+            actions_at_id
+                .iter()
+                .map(|action| action.title.as_str())
+                .collect::<Vec<_>>(),
+            ["Qualify with c", "Qualify with orders"]
+        );
+        assert_eq!(
+            actions_at_id[0].diagnostics,
+            Some(ambiguous_column_diagnostics(
+                &documents,
+                &documents[0],
+                &BTreeMap::new()
+            ))
+        );
+        assert_eq!(
+            apply_action(&rope, &uri, &actions_at_id[1]),
+            text.replace("select id", "select orders.id")
+        );
+        // the qualified reference is no longer ambiguous
+        let qualified = apply_action(&rope, &uri, &actions_at_id[0]);
+        let rope = Rope::from_str(&qualified);
+        let parse = parse_source(&qualified);
+        let document = Document {
+            uri,
+            rope: &rope,
+            parse: &parse,
+        };
+        assert!(ambiguous_column_diagnostics(&[], &document, &BTreeMap::new()).is_empty());
+
+        assert!(actions(text.rfind("contact_id;").unwrap()).is_empty());
+    }
+}
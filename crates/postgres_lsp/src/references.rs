@@ -0,0 +1,77 @@
+//! Find the references to tables, columns, functions, common table expressions and aliases.
+//!
+//! Tables, columns and functions are looked up in all sql files of the workspace, as found in the
+//! [`WorkspaceIndex`]. Common table expressions and aliases are only known within their statement,
+//! so they are looked up in the document itself. The declaration, e.g. the name in `CREATE TABLE`,
+//! is included if the client asks for it.
+//!
+//! Locations cannot tell how a reference accesses its object, so document highlights show the
+//! references within the statement at the cursor as reads and writes instead, e.g. the columns
+//! that an `INSERT` or `UPDATE` assigns.
+
+use analyser::references::{occurrence_at, occurrences, statement_occurrences, Access};
+use parser::TextSize;
+use tower_lsp::lsp_types::*;
+
+use crate::rename::Document;
+use crate::utils::text_range_to_range;
+use crate::workspace_index::WorkspaceIndex;
+
+/// Returns the references to the object at `offset` of `document` within the workspace
+pub fn references(
+    index: &WorkspaceIndex,
+    document: &Document<'_>,
+    offset: TextSize,
+    include_declaration: bool,
+) -> Option<Vec<Location>> {
+    let occurrence = occurrence_at(&document.parse.cst, &document.parse.stmts, offset)?;
+    let references = if occurrence.target.is_local() {
+        occurrences(&document.parse.cst, &document.parse.stmts)
+            .into_iter()
+            .filter(|o| o.target.matches(&occurrence.target))
+            .filter_map(|o| {
+                let location = Location {
+                    uri: document.uri.clone(),
+                    range: text_range_to_range(o.range, document.rope)?,
+                };
+                Some((location, o.access))
+            })
+            .collect()
+    } else {
+        index.occurrences(&occurrence.target)
+    };
+    Some(
+        references
+            .into_iter()
+            .filter(|(_, access)| include_declaration || *access != Access::Declaration)
+            .map(|(location, _)| location)
+            .collect(),
+    )
+}
+
+/// Returns the references to the object at `offset` within its statement of `document`, with
+/// declarations highlighted as text
+pub fn document_highlights(
+    document: &Document<'_>,
+    offset: TextSize,
+) -> Option<Vec<DocumentHighlight>> {
+    let occurrences = statement_occurrences(&document.parse.cst, &document.parse.stmts, offset);
+    if occurrences.is_empty() {
+        return None;
+    }
+    Some(
+        occurrences
+            .iter()
+            .filter_map(|o| {
+                Some(DocumentHighlight {
+                    range: text_range_to_range(o.range, document.rope)?,
+                    kind: Some(match o.access {
+                        Access::Declaration => DocumentHighlightKind::TEXT,
+                        Access::Read => DocumentHighlightKind::READ,
+                        Access::Write => DocumentHighlightKind::WRITE,
+                    }),
+                })
+            })
+            .collect(),
+    )
+}
@@ -0,0 +1,336 @@
+//! Renaming of common table expressions, aliases, tables, columns and functions.
+//!
+//! Common table expressions and aliases are renamed within the statement that declares them,
+//! including the columns they qualify. Tables, columns and functions are renamed in all sql files
+//! of the workspace where they occur, as found in the [`WorkspaceIndex`]. If a table or column is
+//! defined in the workspace, a migration that renames it in existing databases is created next to
+//! the file that defines it, with the version after the latest migration of its directory. Names
+//! that refer to none of these objects cannot be renamed.
+//!
+//! The edits of every document are grouped under a change annotation that summarizes them, so
+//! that clients can present a preview before applying the rename.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+use analyser::migrations::migration_version;
+use analyser::references::{occurrence_at, occurrences, Access, Target};
+use analyser::rename::{normalize_identifier, rename_column_migration, rename_table_migration};
+use parser::{parse_source, Parse, RawStmt, TextRange, TextSize};
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+
+use crate::utils::text_range_to_range;
+use crate::workspace_index::WorkspaceIndex;
+
+/// An open document
+pub struct Document<'a> {
+    pub uri: Url,
+    pub rope: &'a Rope,
+    pub parse: &'a Parse,
+}
+
+/// Returns the range and the name of the object at `offset` of `document`, if it is one that can
+/// be renamed
+pub fn prepare_rename(document: &Document<'_>, offset: TextSize) -> Option<PrepareRenameResponse> {
+    let occurrence = occurrence_at(&document.parse.cst, &document.parse.stmts, offset)?;
+    Some(PrepareRenameResponse::RangeWithPlaceholder {
+        range: text_range_to_range(occurrence.range, document.rope)?,
+        placeholder: name_at(document, occurrence.range),
+    })
+}
+
+/// Returns the edit that renames the object at `offset` of `document` to `new_name`
+pub fn rename_edit(
+    index: &WorkspaceIndex,
+    documents: &[Document<'_>],
+    document: &Document<'_>,
+    offset: TextSize,
+    new_name: &str,
+) -> Option<WorkspaceEdit> {
+    let occurrence = occurrence_at(&document.parse.cst, &document.parse.stmts, offset)?;
+    let name = name_at(document, occurrence.range);
+    if occurrence.target.is_local() {
+        let ranges = occurrences(&document.parse.cst, &document.parse.stmts)
+            .into_iter()
+            .filter(|o| o.target.matches(&occurrence.target))
+            .filter_map(|o| text_range_to_range(o.range, document.rope))
+            .collect::<Vec<_>>();
+        let edits = vec![(document.uri.clone(), ranges)];
+        return Some(workspace_edit(&name, new_name, edits, None));
+    }
+    Some(rename_workspace_object(
+        index,
+        documents,
+        document,
+        &occurrence.target,
+        &name,
+        new_name,
+    ))
+}
+
+/// Returns the name that the identifier at `range` of `document` refers to
+fn name_at(document: &Document<'_>, range: TextRange) -> String {
+    let text = document
+        .rope
+        .byte_slice(usize::from(range.start())..usize::from(range.end()))
+        .to_string();
+    normalize_identifier(&text)
+}
+
+/// Returns the edit that renames the table, column or function `target`, whose name is `name`, in
+/// all files of the workspace. Tables and columns that are defined in the workspace get a migration
+/// next to the file that defines them.
+fn rename_workspace_object(
+    index: &WorkspaceIndex,
+    documents: &[Document<'_>],
+    document: &Document<'_>,
+    target: &Target,
+    name: &str,
+    new_name: &str,
+) -> WorkspaceEdit {
+    let mut found = index.occurrences(target);
+    // the document may not be indexed, e.g. if it is not a file
+    if !found
+        .iter()
+        .any(|(location, _)| location.uri == document.uri)
+    {
+        found.extend(
+            occurrences(&document.parse.cst, &document.parse.stmts)
+                .into_iter()
+                .filter(|o| o.target.matches(target))
+                .filter_map(|o| {
+                    let location = Location {
+                        uri: document.uri.clone(),
+                        range: text_range_to_range(o.range, document.rope)?,
+                    };
+                    Some((location, o.access))
+                }),
+        );
+    }
+    let definition = found
+        .iter()
+        .find(|(_, access)| *access == Access::Declaration)
+        .map(|(location, _)| location.uri.clone());
+
+    let mut ranges = BTreeMap::<Url, Vec<Range>>::new();
+    for (location, _) in found {
+        ranges.entry(location.uri).or_default().push(location.range);
+    }
+
+    let migration = definition.and_then(|definition| {
+        let stmt = with_stmts(documents, &definition, |stmts| match target {
+            Target::Relation(identifier) => rename_table_migration(stmts, identifier, new_name),
+            Target::Column(identifier) => rename_column_migration(stmts, identifier, new_name),
+            _ => None,
+        })??;
+        let siblings = definition
+            .to_file_path()
+            .ok()
+            .and_then(|path| fs::read_dir(path.parent()?).ok())
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().into_owned());
+        let uri = definition
+            .join(&migration_file_name(name, new_name, siblings))
+            .ok()?;
+        Some((uri, vec![stmt]))
+    });
+
+    workspace_edit(name, new_name, ranges.into_iter().collect(), migration)
+}
+
+/// Calls `f` with the statements of the file `uri`, which are parsed from disk unless it is open
+fn with_stmts<T>(
+    documents: &[Document<'_>],
+    uri: &Url,
+    f: impl FnOnce(&[RawStmt]) -> T,
+) -> Option<T> {
+    match documents.iter().find(|doc| doc.uri == *uri) {
+        Some(doc) => Some(f(&doc.parse.stmts)),
+        None => {
+            let text = fs::read_to_string(uri.to_file_path().ok()?).ok()?;
+            Some(f(&parse_source(&text).stmts))
+        }
+    }
+}
+
+/// Returns the name of the migration file that renames `name` to `new_name`, with every character
+/// that is not a letter, digit or underscore replaced, so that the name cannot leave the directory
+/// or be invalid on any file system. The name starts with the version after the latest of the
+/// migrations `siblings`, in their format, if any of them is versioned.
+fn migration_file_name(
+    name: &str,
+    new_name: &str,
+    siblings: impl IntoIterator<Item = String>,
+) -> String {
+    let sanitize = |name: &str| {
+        name.chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>()
+    };
+    let description = format!("rename_{}_to_{}", sanitize(name), sanitize(new_name));
+    let latest = siblings
+        .into_iter()
+        .filter_map(|file_name| Some((migration_version(&file_name)?, file_name)))
+        .max();
+    let Some((version, file_name)) = latest else {
+        return format!("{}.sql", description);
+    };
+
+    // keep the `V` of Flyway, the zero padding of the version and the separator after it
+    let prefix = if file_name.starts_with('V') { "V" } else { "" };
+    let rest = &file_name[prefix.len()..];
+    let digits = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    let separator = if rest[digits..].starts_with("__") {
+        "__"
+    } else {
+        "_"
+    };
+    format!(
+        "{}{:0width$}{}{}.sql",
+        prefix,
+        version + 1,
+        separator,
+        description,
+        width = digits
+    )
+}
+
+/// Returns the edit that replaces the `edits`, the ranges of every document, with `new_name` and
+/// creates the `migration` file with its statements, if any
+fn workspace_edit(
+    name: &str,
+    new_name: &str,
+    edits: Vec<(Url, Vec<Range>)>,
+    migration: Option<(Url, Vec<String>)>,
+) -> WorkspaceEdit {
+    let new_text = parser::make::quote_ident(new_name);
+    let total = edits.iter().map(|(_, ranges)| ranges.len()).sum::<usize>();
+    let migration_summary = match &migration {
+        Some(_) => "a migration statement will be generated",
+        None => "no migration statement will be generated",
+    };
+
+    let mut annotations = HashMap::new();
+    let mut operations = Vec::new();
+    for (uri, ranges) in &edits {
+        let id = uri.to_string();
+        annotations.insert(
+            id.clone(),
+            ChangeAnnotation {
+                label: format!(
+                    "{} reference(s) in {}",
+                    ranges.len(),
+                    uri.path_segments().and_then(|s| s.last()).unwrap_or(""),
+                ),
+                needs_confirmation: Some(true),
+                description: Some(format!(
+                    "rename {} to {}: {} reference(s) in {} file(s), {}",
+                    name,
+                    new_name,
+                    total,
+                    edits.len(),
+                    migration_summary
+                )),
+            },
+        );
+        operations.push(DocumentChangeOperation::Edit(TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier {
+                uri: uri.clone(),
+                version: None,
+            },
+            edits: ranges
+                .iter()
+                .map(|range| {
+                    OneOf::Right(AnnotatedTextEdit {
+                        text_edit: TextEdit::new(*range, new_text.clone()),
+                        annotation_id: id.clone(),
+                    })
+                })
+                .collect(),
+        }));
+    }
+
+    if let Some((uri, stmts)) = migration {
+        let id = uri.to_string();
+        annotations.insert(
+            id.clone(),
+            ChangeAnnotation {
+                label: "Migration".to_string(),
+                needs_confirmation: Some(true),
+                description: Some(stmts.join("\n")),
+            },
+        );
+        operations.push(DocumentChangeOperation::Op(ResourceOp::Create(
+            CreateFile {
+                uri: uri.clone(),
+                options: Some(CreateFileOptions {
+                    overwrite: Some(false),
+                    ignore_if_exists: Some(true),
+                }),
+                annotation_id: Some(id.clone()),
+            },
+        )));
+        operations.push(DocumentChangeOperation::Edit(TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier { uri, version: None },
+            edits: vec![OneOf::Right(AnnotatedTextEdit {
+                text_edit: TextEdit::new(
+                    Range::new(Position::new(0, 0), Position::new(0, 0)),
+                    format!("{}\n", stmts.join("\n")),
+                ),
+                annotation_id: id,
+            })],
+        }));
+    }
+
+    WorkspaceEdit {
+        changes: None,
+        document_changes: Some(DocumentChanges::Operations(operations)),
+        change_annotations: Some(annotations),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migration_file_name() {
+        let siblings = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            migration_file_name("contact", "person", siblings(&["README.md"])),
+            "rename_contact_to_person.sql"
+        );
+        assert_eq!(
+            migration_file_name(
+                "contact",
+                "person",
+                siblings(&["V0009__orders.sql", "V0010__contact.sql", "seed.sql"])
+            ),
+            "V0011__rename_contact_to_person.sql"
+        );
+        assert_eq!(
+            migration_file_name(
+                "app.contact",
+                "person",
+                siblings(&["20240101120000_contact.sql"])
+            ),
+            "20240101120001_rename_app_contact_to_person.sql"
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! Rewrites of queries that spell out the columns of their tables.
+//!
+//! `SELECT *` is expanded into an explicit column list, and an `INSERT` without a column list
+//! gets the one that its values go to. The columns are looked up in the schemas of the database of
+//! the document, as changed by the other open documents and the statements of the document before
+//! the query, so that tables that the workspace creates are known as well. See
+//! [`analyser::star_expansion`] and [`analyser::insert_columns`].
+
+use std::collections::BTreeMap;
+
+use analyser::insert_columns::insert_column_list;
+use analyser::migrations::{migration_version, MigrationState};
+use analyser::star_expansion::expand_star;
+use analyser::Schema;
+use parser::{TextRange, TextSize};
+use tower_lsp::lsp_types::*;
+
+use crate::rename::Document;
+use crate::utils::text_range_to_range;
+
+/// Returns the action that replaces the `*` at `offset` of `document` with the columns it stands
+/// for
+pub fn expand_star_action(
+    documents: &[Document<'_>],
+    document: &Document<'_>,
+    offset: TextSize,
+    schemas: &BTreeMap<String, Schema>,
+) -> Option<CodeAction> {
+    let schemas = schemas_at(documents, document, Some(offset), schemas);
+    let expansion = expand_star(&document.parse.cst, &document.parse.stmts, offset, &schemas)?;
+    rewrite_action(
+        "Expand * into columns",
+        document,
+        expansion.range,
+        expansion.text,
+    )
+}
+
+/// Returns the action that adds the column list to the `INSERT` at `offset` of `document`
+pub fn insert_columns_action(
+    documents: &[Document<'_>],
+    document: &Document<'_>,
+    offset: TextSize,
+    schemas: &BTreeMap<String, Schema>,
+) -> Option<CodeAction> {
+    let schemas = schemas_at(documents, document, Some(offset), schemas);
+    let list = insert_column_list(&document.parse.cst, &document.parse.stmts, offset, &schemas)?;
+    rewrite_action(
+        "Add column list",
+        document,
+        TextRange::empty(list.offset),
+        list.text,
+    )
+}
+
+/// Returns the tables that the statement at `offset` of `document` sees: those of the database
+/// `schemas` as changed by the other open `documents` and the statements before it, or by all
+/// statements of `document` without an offset. The other documents are applied in the order of
+/// their migration versions, followed by those that are not migrations.
+pub(crate) fn schemas_at(
+    documents: &[Document<'_>],
+    document: &Document<'_>,
+    offset: Option<TextSize>,
+    schemas: &BTreeMap<String, Schema>,
+) -> BTreeMap<String, Schema> {
+    state_at(documents, document, offset, schemas).schemas
+}
+
+/// Like [`schemas_at`], but returns the state of the database with the search path that the
+/// statements before `offset` set
+pub(crate) fn state_at(
+    documents: &[Document<'_>],
+    document: &Document<'_>,
+    offset: Option<TextSize>,
+    schemas: &BTreeMap<String, Schema>,
+) -> MigrationState {
+    let mut state = MigrationState::from_schemas(schemas.clone());
+    let mut others = documents
+        .iter()
+        .filter(|d| d.uri != document.uri)
+        .collect::<Vec<_>>();
+    others.sort_by_cached_key(|d| replay_order(&d.uri));
+    for other in others {
+        state.replay(&other.parse.stmts);
+        state.end_session();
+    }
+    let preceding = document
+        .parse
+        .stmts
+        .iter()
+        .take_while(|stmt| offset.iter().all(|offset| stmt.range.end() < *offset))
+        .count();
+    state.replay(&document.parse.stmts[..preceding]);
+    state
+}
+
+/// Returns the key that orders the file `uri` among those that are replayed: migrations in the
+/// order of their versions, followed by the other files
+pub(crate) fn replay_order(uri: &Url) -> (bool, Option<u128>, String) {
+    let file_name = uri.path_segments().and_then(|mut s| s.next_back());
+    let version = file_name.and_then(migration_version);
+    (version.is_none(), version, uri.to_string())
+}
+
+fn rewrite_action(
+    title: &str,
+    document: &Document<'_>,
+    range: TextRange,
+    text: String,
+) -> Option<CodeAction> {
+    let edit = TextEdit::new(text_range_to_range(range, document.rope)?, text);
+    Some(CodeAction {
+        title: title.to_string(),
+        kind: Some(CodeActionKind::REFACTOR_REWRITE),
+        edit: Some(WorkspaceEdit {
+            changes: Some([(document.uri.clone(), vec![edit])].into_iter().collect()),
+            ..WorkspaceEdit::default()
+        }),
+        ..CodeAction::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+    use ropey::Rope;
+
+    use super::*;
+    use crate::utils::apply_action;
+
+    type Action = fn(
+        &[Document<'_>],
+        &Document<'_>,
+        TextSize,
+        &BTreeMap<String, Schema>,
+    ) -> Option<CodeAction>;
+
+    /// Runs `action` at `at` of `text`, whose tables are created by `schema`, another open
+    /// document, and returns the title of the action and the text after it
+    fn rewrite(schema: &str, text: &str, at: &str, action: Action) -> Option<(String, String)> {
+        let schema_rope = Rope::from_str(schema);
+        let schema_parse = parse_source(schema);
+        let rope = Rope::from_str(text);
+        let parse = parse_source(text);
+        let uri = Url::parse("file:///db/queries.sql").unwrap();
+        let documents = [
+            Document {
+                uri: Url::parse("file:///db/0001_schema.sql").unwrap(),
+                rope: &schema_rope,
+                parse: &schema_parse,
+            },
+            Document {
+                uri: uri.clone(),
+                rope: &rope,
+                parse: &parse,
+            },
+        ];
+        let offset = TextSize::try_from(text.rfind(at).unwrap()).unwrap();
+        let action = action(&documents, &documents[1], offset, &BTreeMap::new())?;
+        Some((action.title.clone(), apply_action(&rope, &uri, &action)))
+    }
+
+    #[test]
+    fn test_expand_star_action() {
+        let schema = "create table contact (id int, name text);";
+        assert_eq!(
+            rewrite(schema, "select * from contact;", "*", expand_star_action),
+            Some((
+                "Expand * into columns".to_string(),
+                "select id, name from contact;".to_string()
+            ))
+        );
+        // the statements before the query are applied to the schema of the other documents
+        let text = "alter table contact add column email text;\nselect * from contact;";
+        assert_eq!(
+            rewrite(schema, text, "*", expand_star_action).unwrap().1,
+            "alter table contact add column email text;\nselect id, name, email from contact;"
+        );
+        assert_eq!(
+            rewrite(schema, "select * from invoice;", "*", expand_star_action),
+            None
+        );
+    }
+
+    #[test]
+    fn test_insert_columns_action() {
+        let schema = "create table contact (id int, name text, email text);";
+        assert_eq!(
+            rewrite(
+                schema,
+                "insert into contact values (1, 'a');",
+                "insert",
+                insert_columns_action
+            ),
+            Some((
+                "Add column list".to_string(),
+                "insert into contact (id, name) values (1, 'a');".to_string()
+            ))
+        );
+        assert_eq!(
+            rewrite(
+                schema,
+                "insert into contact (id) values (1);",
+                "insert",
+                insert_columns_action
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,83 @@
+//! Code lenses that run or explain the statements of a document against its database.
+//!
+//! If the `pglsp.execution.enabled` setting allows executing statements, every statement gets a
+//! `Run` lens that triggers `pglsp.executeStatement` with the uri of the document and the start of
+//! the statement, see [`crate::execute`]. Statements that Postgres can explain get an `Explain`
+//! lens that triggers `pglsp.explain` with the same arguments, see [`crate::explain`]. There are no
+//! lenses in offline mode.
+
+use analyser::plan::is_explainable;
+use tower_lsp::lsp_types::*;
+
+use crate::execute::EXECUTE_STATEMENT_COMMAND;
+use crate::explain::EXPLAIN_COMMAND;
+use crate::rename::Document;
+use crate::utils::text_range_to_range;
+
+/// Returns the `Explain` lenses of the statements of `document`, and the `Run` lenses of all
+/// statements if executing statements is `enabled`
+pub fn statement_lenses(document: &Document<'_>, enabled: bool) -> Vec<CodeLens> {
+    let lens = |range: Range, title: &str, command: &str, argument: serde_json::Value| CodeLens {
+        range,
+        command: Some(Command {
+            title: title.to_string(),
+            command: command.to_string(),
+            arguments: Some(vec![serde_json::to_value(&document.uri).unwrap(), argument]),
+        }),
+        data: None,
+    };
+    let mut lenses = Vec::new();
+    for stmt in &document.parse.stmts {
+        let Some(range) = text_range_to_range(stmt.range, document.rope) else {
+            continue;
+        };
+        let position = serde_json::to_value(range.start).unwrap();
+        if enabled {
+            lenses.push(lens(
+                range,
+                "Run",
+                EXECUTE_STATEMENT_COMMAND,
+                position.clone(),
+            ));
+        }
+        if is_explainable(&stmt.stmt) {
+            lenses.push(lens(range, "Explain", EXPLAIN_COMMAND, position));
+        }
+    }
+    lenses
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+    use ropey::Rope;
+
+    use super::*;
+
+    #[test]
+    fn test_statement_lenses() {
+        let text = "select 1;\ncreate table note (body text);";
+        let rope = Rope::from_str(text);
+        let parse = parse_source(text);
+        let document = Document {
+            uri: Url::parse("file:///note.sql").unwrap(),
+            rope: &rope,
+            parse: &parse,
+        };
+        let commands = |enabled: bool| {
+            statement_lenses(&document, enabled)
+                .into_iter()
+                .map(|lens| (lens.range.start.line, lens.command.unwrap().command))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(commands(false), [(0, EXPLAIN_COMMAND.to_string())]);
+        assert_eq!(
+            commands(true),
+            [
+                (0, EXECUTE_STATEMENT_COMMAND.to_string()),
+                (0, EXPLAIN_COMMAND.to_string()),
+                (1, EXECUTE_STATEMENT_COMMAND.to_string()),
+            ]
+        );
+    }
+}
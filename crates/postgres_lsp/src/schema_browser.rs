@@ -0,0 +1,204 @@
+//! Requests for editor extensions that browse the database of a document, e.g. in a sidebar.
+//!
+//! `pglsp/schemaTree` returns the schemas of the database with their tables, views and columns as
+//! loaded into the schema cache. `pglsp/objectDefinition` returns the DDL of a single object of
+//! the database, see [`analyser::object_definition`], or `null` if it does not exist.
+
+use std::collections::BTreeMap;
+
+use analyser::object_definition::{table_definition, ObjectKind};
+use analyser::{Schema, View};
+use serde::{Deserialize, Serialize};
+use tower_lsp::jsonrpc::{Error, Result};
+use tower_lsp::lsp_types::TextDocumentIdentifier;
+
+use crate::db::{connect, database_error};
+
+pub const SCHEMA_TREE_REQUEST: &str = "pglsp/schemaTree";
+
+pub const OBJECT_DEFINITION_REQUEST: &str = "pglsp/objectDefinition";
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaTreeParams {
+    /// The document whose database is browsed
+    pub text_document: TextDocumentIdentifier,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaNode {
+    pub name: String,
+    pub tables: Vec<TableNode>,
+    pub views: Vec<ViewNode>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableNode {
+    pub name: String,
+    pub owner: Option<String>,
+    /// The table access method, `None` for the default `heap`
+    pub access_method: Option<String>,
+    pub columns: Vec<ColumnNode>,
+    pub indexes: Vec<String>,
+    pub constraints: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnNode {
+    pub name: String,
+    pub data_type: String,
+    pub not_null: bool,
+    pub default_expr: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ViewNode {
+    pub name: String,
+    pub is_materialized: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectDefinitionParams {
+    /// The document whose database the object is part of
+    pub text_document: TextDocumentIdentifier,
+    /// `table`, `view`, `function`, `index`, `trigger` or `constraint`
+    pub kind: String,
+    pub schema: String,
+    pub name: String,
+}
+
+/// Returns the tree of `schemas` and their `views`, in the order of their names
+pub fn schema_tree(schemas: &BTreeMap<String, Schema>, views: &[View]) -> Vec<SchemaNode> {
+    let mut nodes = schemas
+        .values()
+        .map(|schema| SchemaNode {
+            name: schema.name.clone(),
+            tables: schema
+                .tables
+                .values()
+                .map(|table| TableNode {
+                    name: table.name.clone(),
+                    owner: table.owner.clone(),
+                    access_method: table.access_method.clone(),
+                    columns: table
+                        .columns
+                        .iter()
+                        .map(|column| ColumnNode {
+                            name: column.name.clone(),
+                            data_type: column.data_type.clone(),
+                            not_null: column.not_null,
+                            default_expr: column.default_expr.clone(),
+                        })
+                        .collect(),
+                    indexes: table.indexes.keys().cloned().collect(),
+                    constraints: table.constraints.keys().cloned().collect(),
+                })
+                .collect(),
+            views: Vec::new(),
+        })
+        .collect::<Vec<_>>();
+    for view in views {
+        // schemas with views but without tables are not loaded into the schema cache
+        let idx = match nodes.binary_search_by(|node| node.name.cmp(&view.schema_name)) {
+            Ok(idx) => idx,
+            Err(idx) => {
+                nodes.insert(
+                    idx,
+                    SchemaNode {
+                        name: view.schema_name.clone(),
+                        tables: Vec::new(),
+                        views: Vec::new(),
+                    },
+                );
+                idx
+            }
+        };
+        nodes[idx].views.push(ViewNode {
+            name: view.name.clone(),
+            is_materialized: view.is_materialized,
+        });
+    }
+    for node in &mut nodes {
+        node.views.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+    nodes
+}
+
+/// Returns the DDL of the object of `params`, with tables taken from `schemas` and all other
+/// objects loaded from the database at `url` as `role`. Overloaded functions, and triggers and
+/// constraints of the same name on different tables, are all returned, separated by empty lines.
+/// Returns `None` if there is no such object.
+pub async fn object_definition(
+    url: Option<&str>,
+    role: Option<&str>,
+    schemas: &BTreeMap<String, Schema>,
+    params: &ObjectDefinitionParams,
+) -> Result<Option<String>> {
+    let kind = ObjectKind::from_name(&params.kind)
+        .ok_or_else(|| Error::invalid_params(format!("unknown object kind {}", params.kind)))?;
+    let definitions = match kind.definition_query() {
+        Some(query) => connect(url, role)
+            .await?
+            .query(query, &[&params.schema, &params.name])
+            .await
+            .map_err(database_error)?
+            .into_iter()
+            .map(|row| row.get::<_, String>("definition"))
+            .collect::<Vec<_>>(),
+        None => schemas
+            .get(&params.schema)
+            .and_then(|schema| table_definition(schema, &params.name))
+            .into_iter()
+            .collect(),
+    };
+    if definitions.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(definitions.join("\n\n")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_tree_order() {
+        let view = |schema_name: &str, name: &str| View {
+            schema_name: schema_name.to_string(),
+            name: name.to_string(),
+            definition: "select 1".to_string(),
+            is_materialized: false,
+        };
+        let schemas = BTreeMap::from([("public".to_string(), Schema::new("public"))]);
+        let tree = schema_tree(
+            &schemas,
+            &[
+                view("reporting", "totals"),
+                view("public", "recent"),
+                view("reporting", "daily"),
+                view("app", "active"),
+            ],
+        );
+        assert_eq!(
+            tree.iter()
+                .map(|node| (
+                    node.name.as_str(),
+                    node.views
+                        .iter()
+                        .map(|v| v.name.as_str())
+                        .collect::<Vec<_>>()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("app", vec!["active"]),
+                ("public", vec!["recent"]),
+                ("reporting", vec!["daily", "totals"]),
+            ]
+        );
+    }
+}
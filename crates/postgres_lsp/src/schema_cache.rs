@@ -0,0 +1,199 @@
+//! The schemas of the databases that documents are validated against.
+//!
+//! Every database of `pglsp.toml` gets its own connection, over which its schemas are loaded once
+//! and then shared by all documents of its directories. The functions, views and roles are loaded
+//! separately, since only completion, signature help and hover need them, and so are the sizes of
+//! the tables and the casts, which only the warnings about table rewrites need.
+//!
+//! Everything is cached per database and role, since the role decides which objects are visible.
+//! Failures are cached as well, so that an unreachable database is not connected to again on every
+//! keystroke: the next attempt is only made after a backoff, which doubles with every failure up
+//! to [`MAX_BACKOFF`].
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use analyser::table_rewrite::TableSize;
+use analyser::{CastGraph, Function, Schema, View};
+use tokio::sync::Mutex;
+use tokio_postgres::Client;
+use tower_lsp::jsonrpc::{Error, Result};
+use workspace::Database;
+
+use crate::db::{
+    connect, load_casts, load_functions, load_roles, load_schemas, load_table_sizes, load_views,
+};
+
+/// The tables of a database by schema name
+pub type Schemas = Arc<BTreeMap<String, Schema>>;
+
+/// The functions of a database, including the built-in ones
+pub type Functions = Arc<Vec<Function>>;
+
+/// The views and materialized views of a database
+pub type Views = Arc<Vec<View>>;
+
+/// The names of the roles of a database
+pub type Roles = Arc<Vec<String>>;
+
+/// The estimated sizes of the tables of a database
+pub type TableSizes = Arc<Vec<TableSize>>;
+
+/// The casts of the `pg_cast` catalog of a database
+pub type Casts = Arc<CastGraph>;
+
+/// The time until a database that failed to load is loaded again after the first failure
+const INITIAL_BACKOFF: Duration = Duration::from_secs(5);
+
+/// The longest time until a database that failed to load is loaded again
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// A database and the role that it is loaded as
+type Key = (Database, Option<String>);
+
+#[derive(Debug)]
+enum Entry<T> {
+    Loaded(Arc<T>),
+    /// The error of the last attempt to load, which is returned until `retry_at`
+    Failed {
+        error: Error,
+        retry_at: Instant,
+        backoff: Duration,
+    },
+}
+
+type Cache<T> = Mutex<HashMap<Key, Entry<T>>>;
+
+#[derive(Debug, Default)]
+pub struct SchemaCache {
+    /// Locked while a database is loaded, so that it is loaded only once
+    schemas: Cache<BTreeMap<String, Schema>>,
+    functions: Cache<Vec<Function>>,
+    views: Cache<Vec<View>>,
+    roles: Cache<Vec<String>>,
+    table_sizes: Cache<Vec<TableSize>>,
+    casts: Cache<CastGraph>,
+    /// Increased whenever the schemas are dropped, so that results computed from them can tell
+    /// that they are outdated
+    generation: AtomicU64,
+}
+
+impl SchemaCache {
+    /// Returns the schemas of `database`, loading them as `role` if they are not cached yet
+    pub async fn get(&self, database: &Database, role: Option<&str>) -> Result<Schemas> {
+        let schemas = database.schemas.clone();
+        cached(&self.schemas, database, role, |client| async move {
+            load_schemas(&client, &schemas).await
+        })
+        .await
+    }
+
+    /// Returns the functions of `database`, loading them as `role` if they are not cached yet
+    pub async fn functions(&self, database: &Database, role: Option<&str>) -> Result<Functions> {
+        let schemas = database.schemas.clone();
+        cached(&self.functions, database, role, |client| async move {
+            load_functions(&client, &schemas).await
+        })
+        .await
+    }
+
+    /// Returns the views of `database`, loading them as `role` if they are not cached yet
+    pub async fn views(&self, database: &Database, role: Option<&str>) -> Result<Views> {
+        let schemas = database.schemas.clone();
+        cached(&self.views, database, role, |client| async move {
+            load_views(&client, &schemas).await
+        })
+        .await
+    }
+
+    /// Returns the roles of `database`, loading them as `role` if they are not cached yet
+    pub async fn roles(&self, database: &Database, role: Option<&str>) -> Result<Roles> {
+        cached(&self.roles, database, role, |client| async move {
+            load_roles(&client).await
+        })
+        .await
+    }
+
+    /// Returns the table sizes of `database`, loading them as `role` if they are not cached yet
+    pub async fn table_sizes(&self, database: &Database, role: Option<&str>) -> Result<TableSizes> {
+        let schemas = database.schemas.clone();
+        cached(&self.table_sizes, database, role, |client| async move {
+            load_table_sizes(&client, &schemas).await
+        })
+        .await
+    }
+
+    /// Returns the casts of `database`, loading them as `role` if they are not cached yet
+    pub async fn casts(&self, database: &Database, role: Option<&str>) -> Result<Casts> {
+        cached(&self.casts, database, role, |client| async move {
+            load_casts(&client).await
+        })
+        .await
+    }
+
+    /// Returns the number of times the schemas have been dropped
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Drops all schemas, functions, views, roles, table sizes and casts, and the failures to load
+    /// them, e.g. because the configuration changed
+    pub async fn clear(&self) {
+        self.schemas.lock().await.clear();
+        self.functions.lock().await.clear();
+        self.views.lock().await.clear();
+        self.roles.lock().await.clear();
+        self.table_sizes.lock().await.clear();
+        self.casts.lock().await.clear();
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+/// Returns what `cache` holds for `database` and `role`, or else what `load` loads from a new
+/// connection to `database`. A failure is returned again until its backoff has passed.
+async fn cached<T, F, Fut>(
+    cache: &Cache<T>,
+    database: &Database,
+    role: Option<&str>,
+    load: F,
+) -> Result<Arc<T>>
+where
+    F: FnOnce(Client) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let key = (database.clone(), role.map(str::to_string));
+    let mut cache = cache.lock().await;
+    let backoff = match cache.get(&key) {
+        Some(Entry::Loaded(value)) => return Ok(value.clone()),
+        Some(Entry::Failed {
+            error, retry_at, ..
+        }) if Instant::now() < *retry_at => return Err(error.clone()),
+        Some(Entry::Failed { backoff, .. }) => (*backoff * 2).min(MAX_BACKOFF),
+        None => INITIAL_BACKOFF,
+    };
+    let result = match connect(database.connection.as_deref(), role).await {
+        Ok(client) => load(client).await,
+        Err(err) => Err(err),
+    };
+    match result {
+        Ok(value) => {
+            let value = Arc::new(value);
+            cache.insert(key, Entry::Loaded(value.clone()));
+            Ok(value)
+        }
+        Err(error) => {
+            cache.insert(
+                key,
+                Entry::Failed {
+                    error: error.clone(),
+                    retry_at: Instant::now() + backoff,
+                    backoff,
+                },
+            );
+            Err(error)
+        }
+    }
+}
@@ -0,0 +1,37 @@
+//! Expand selection along the syntax tree, from a token to its expression, clause, statement and
+//! the whole document.
+
+use analyser::selection::selection_ranges;
+use parser::Parse;
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+
+use crate::utils::{position_to_byte_offset, text_range_to_range};
+
+/// Returns the selection range at every position of `positions`, whose parents are the ranges it
+/// expands to
+pub fn selection_range(rope: &Rope, parse: &Parse, positions: &[Position]) -> Vec<SelectionRange> {
+    positions
+        .iter()
+        .map(|position| {
+            let ranges = position_to_byte_offset(*position, rope)
+                .map(|offset| selection_ranges(&parse.cst, offset))
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|range| text_range_to_range(range, rope));
+            // the protocol nests the ranges from the innermost one, so they are built outside in
+            ranges
+                .rev()
+                .fold(None, |parent, range| {
+                    Some(SelectionRange {
+                        range,
+                        parent: parent.map(Box::new),
+                    })
+                })
+                .unwrap_or(SelectionRange {
+                    range: Range::new(*position, *position),
+                    parent: None,
+                })
+        })
+        .collect()
+}
@@ -1,5 +1,6 @@
-use parser::SyntaxKind;
-use tower_lsp::lsp_types::SemanticTokenType;
+use parser::{Parse, SyntaxKind};
+use ropey::Rope;
+use tower_lsp::lsp_types::{SemanticToken, SemanticTokenType, SemanticTokensEdit};
 
 /// Semantic token types that are used for highlighting
 pub const LEGEND_TYPE: &[SemanticTokenType] = &[
@@ -34,7 +35,7 @@ pub const LEGEND_TYPE: &[SemanticTokenType] = &[
     // For identifiers that declare a member function or method.
     SemanticTokenType::METHOD,
     // For identifiers that declare a macro.
-    // SemanticTokenType::MACRO,
+    SemanticTokenType::MACRO,
     // For tokens that represent a comment.
     SemanticTokenType::COMMENT,
     // For tokens that represent a string literal.
@@ -68,7 +69,10 @@ pub fn semantic_token_from_syntax_kind(syntax: SyntaxKind) -> Option<usize> {
         SyntaxKind::Ascii62 => Some(SemanticTokenType::OPERATOR),
         SyntaxKind::Ascii63 => Some(SemanticTokenType::OPERATOR),
         SyntaxKind::Sconst => Some(SemanticTokenType::STRING),
-        SyntaxKind::Comment => Some(SemanticTokenType::COMMENT),
+        SyntaxKind::LineComment => Some(SemanticTokenType::COMMENT),
+        SyntaxKind::BlockComment => Some(SemanticTokenType::COMMENT),
+        // a `#!` line is not sql, but a directive for the shell that runs the file
+        SyntaxKind::Shebang => Some(SemanticTokenType::MACRO),
         SyntaxKind::Select => Some(SemanticTokenType::KEYWORD),
         SyntaxKind::From => Some(SemanticTokenType::KEYWORD),
         SyntaxKind::Where => Some(SemanticTokenType::KEYWORD),
@@ -81,3 +85,142 @@ pub fn semantic_token_from_syntax_kind(syntax: SyntaxKind) -> Option<usize> {
     }
     None
 }
+
+/// Returns the semantic tokens of the syntax tree of `parse`, ordered by their start
+pub fn im_complete_semantic_tokens(parse: &Parse) -> Vec<ImCompleteSemanticToken> {
+    parse
+        .cst
+        .descendants_with_tokens()
+        .filter_map(|item| {
+            Some(ImCompleteSemanticToken {
+                start: item.text_range().start().into(),
+                token_type: semantic_token_from_syntax_kind(item.kind())?,
+                length: item.text_range().len().into(),
+            })
+        })
+        .collect()
+}
+
+/// Returns the semantic tokens of `parse`, relative to each other as the protocol expects
+pub fn document_semantic_tokens(parse: &Parse, rope: &Rope) -> Vec<SemanticToken> {
+    let mut pre_line = 0;
+    let mut pre_start = 0;
+    im_complete_semantic_tokens(parse)
+        .iter()
+        .filter_map(|token| {
+            let line = rope.try_byte_to_line(token.start).ok()? as u32;
+            let first = rope.try_line_to_char(line as usize).ok()? as u32;
+            let start = rope.try_byte_to_char(token.start).ok()? as u32 - first;
+            let delta_line = line - pre_line;
+            let delta_start = if delta_line == 0 {
+                start - pre_start
+            } else {
+                start
+            };
+            let ret = Some(SemanticToken {
+                delta_line,
+                delta_start,
+                length: token.length as u32,
+                token_type: token.token_type as u32,
+                token_modifiers_bitset: 0,
+            });
+            pre_line = line;
+            pre_start = start;
+            ret
+        })
+        .collect()
+}
+
+/// Returns the edits that turn the `old` tokens into the `new` ones, which are none if they are
+/// equal
+///
+/// Like [`parser::text_edit`], the single edit covers everything between the longest common prefix
+/// and suffix, which after an edit is usually the tokens of the edited statement. Since tokens are
+/// relative to each other, the tokens after the edit stay the same even if their lines moved.
+pub fn semantic_tokens_edits(
+    old: &[SemanticToken],
+    new: &[SemanticToken],
+) -> Vec<SemanticTokensEdit> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    if prefix == old.len() && prefix == new.len() {
+        return Vec::new();
+    }
+    // the protocol counts the integers of the tokens, five per token
+    vec![SemanticTokensEdit {
+        start: (prefix * 5) as u32,
+        delete_count: ((old.len() - prefix - suffix) * 5) as u32,
+        data: Some(new[prefix..new.len() - suffix].to_vec()),
+    }]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(delta_line: u32, delta_start: u32, length: u32) -> SemanticToken {
+        SemanticToken {
+            delta_line,
+            delta_start,
+            length,
+            token_type: 0,
+            token_modifiers_bitset: 0,
+        }
+    }
+
+    #[test]
+    fn test_semantic_tokens_edits() {
+        let old = vec![
+            token(0, 0, 6),
+            token(0, 7, 1),
+            token(1, 0, 6),
+            token(0, 7, 7),
+        ];
+        assert!(semantic_tokens_edits(&old, &old).is_empty());
+
+        // a token is inserted in the middle
+        let new = vec![
+            token(0, 0, 6),
+            token(0, 7, 1),
+            token(0, 2, 4),
+            token(1, 0, 6),
+            token(0, 7, 7),
+        ];
+        assert_eq!(
+            semantic_tokens_edits(&old, &new),
+            vec![SemanticTokensEdit {
+                start: 10,
+                delete_count: 0,
+                data: Some(vec![token(0, 2, 4)]),
+            }]
+        );
+
+        // the last token is changed and another one is removed
+        let new = vec![token(0, 0, 6), token(0, 7, 1), token(1, 0, 8)];
+        assert_eq!(
+            semantic_tokens_edits(&old, &new),
+            vec![SemanticTokensEdit {
+                start: 10,
+                delete_count: 10,
+                data: Some(vec![token(1, 0, 8)]),
+            }]
+        );
+
+        // the prefix and the suffix overlap if a repeated token is removed
+        let old = vec![token(0, 1, 1), token(0, 1, 1), token(0, 1, 1)];
+        let new = vec![token(0, 1, 1), token(0, 1, 1)];
+        assert_eq!(
+            semantic_tokens_edits(&old, &new),
+            vec![SemanticTokensEdit {
+                start: 10,
+                delete_count: 5,
+                data: Some(Vec::new()),
+            }]
+        );
+    }
+}
@@ -0,0 +1,309 @@
+//! Settings of the server, given as initialization options or by `workspace/didChangeConfiguration`.
+//!
+//! Most settings apply to the whole session. The [`DocumentSettings`] apply to single documents
+//! instead, and can be overridden for the documents below a workspace folder in `folders`. The
+//! innermost folder that contains a document wins, and its settings only override those that it
+//! sets. The `connection` of a document is only used if `pglsp.toml` configures no connection for
+//! its directory, since that file is shared with the workspace while the settings are the user's.
+//!
+//! Lint rules with an unknown level are skipped with a warning instead of dropping all settings.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use analyser::Severity;
+use parser::PgVersion;
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use tower_lsp::lsp_types::Url;
+
+pub const SET_ROLE_COMMAND: &str = "pglsp.setRole";
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    /// Allows the `pglsp.cancelBackend` and `pglsp.terminateBackend` commands, which interrupt
+    /// other sessions of the database
+    pub allow_backend_control: bool,
+    /// The roles that the commands can run as with `SET ROLE`, e.g. the application role
+    pub roles: Vec<String>,
+    /// The role that the commands run as, switched with `pglsp.setRole`
+    pub role: Option<String>,
+    /// Never connects to a database, e.g. in security-sensitive CI. Features that need one are
+    /// not offered, and diagnostics only use the documents of the workspace. Only read from the
+    /// initialization options, since it cannot be turned off for a running session.
+    pub offline: bool,
+    /// A PO file that translates the messages into the locale of the client, used instead of the
+    /// catalog bundled for the locale. Only read from the initialization options.
+    pub message_catalog: Option<PathBuf>,
+    /// The milliseconds that a request on a document may take, after which it is cancelled so
+    /// that it does not hold up the edits behind it, and the client is asked to send it again
+    pub latency_budget_ms: Option<u64>,
+    pub execution: ExecutionSettings,
+    #[serde(flatten)]
+    pub document: DocumentSettings,
+    /// The overrides of the document settings for the documents below workspace folders
+    pub folders: Vec<FolderSettings>,
+}
+
+/// Settings that apply to single documents
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DocumentSettings {
+    /// The connection string of the documents that `pglsp.toml` configures no connection for,
+    /// which a connection of `pglsp.toml` takes precedence over even if a folder sets this
+    pub connection: Option<String>,
+    /// The version of Postgres that the documents target, e.g. `14`, so that newer syntax is
+    /// reported. Defaults to the version of the parser.
+    pub postgres_version: Option<String>,
+    /// The levels of lint rules by their names, which override the severity of a rule that runs
+    /// or turn it off
+    #[serde(deserialize_with = "known_levels")]
+    pub lint_rules: BTreeMap<String, RuleLevel>,
+}
+
+/// The settings of the documents below a workspace folder
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FolderSettings {
+    /// The uri of the folder
+    pub uri: String,
+    #[serde(flatten)]
+    pub document: DocumentSettings,
+}
+
+/// The level of a lint rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RuleLevel {
+    Off,
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+impl RuleLevel {
+    /// Returns the severity of the diagnostics of a rule at this level, or `None` if it is off
+    pub fn severity(self) -> Option<Severity> {
+        match self {
+            RuleLevel::Off => None,
+            RuleLevel::Error => Some(Severity::Error),
+            RuleLevel::Warning => Some(Severity::Warning),
+            RuleLevel::Information => Some(Severity::Information),
+            RuleLevel::Hint => Some(Severity::Hint),
+        }
+    }
+}
+
+impl DocumentSettings {
+    /// Returns these settings with those that `overrides` sets replaced
+    fn merge(&self, overrides: &DocumentSettings) -> DocumentSettings {
+        let mut lint_rules = self.lint_rules.clone();
+        lint_rules.extend(overrides.lint_rules.clone());
+        DocumentSettings {
+            connection: overrides.connection.clone().or(self.connection.clone()),
+            postgres_version: overrides
+                .postgres_version
+                .clone()
+                .or(self.postgres_version.clone()),
+            lint_rules,
+        }
+    }
+
+    /// Returns the targeted version of Postgres, or the version of the parser if it is not set or
+    /// invalid
+    pub fn pg_version(&self) -> PgVersion {
+        self.postgres_version
+            .as_deref()
+            .and_then(|version| version.parse().ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Settings of `pglsp.executeStatement`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ExecutionSettings {
+    /// Allows `pglsp.executeStatement`, which runs the statements of documents against their
+    /// database
+    pub enabled: bool,
+    /// Runs the statements in a read-write transaction, so that they can change data
+    pub read_write: bool,
+    /// The rows that are returned at most
+    pub max_rows: usize,
+    /// The rows that are sent in one page of partial results
+    pub page_size: usize,
+    /// The milliseconds that a statement can run for, after which it is cancelled
+    pub timeout_ms: u64,
+}
+
+impl Default for ExecutionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            read_write: false,
+            max_rows: 10_000,
+            page_size: 500,
+            timeout_ms: 30_000,
+        }
+    }
+}
+
+impl Settings {
+    /// Reads the settings from `value`, which holds them either directly or in a `pglsp` section,
+    /// with a warning for every lint rule whose level is unknown and skipped
+    pub fn from_value(value: &Value) -> Result<(Self, Vec<String>), serde_json::Error> {
+        let value = value.get("pglsp").unwrap_or(value);
+        let settings = serde_json::from_value(value.clone())?;
+        let folders = value.get("folders").and_then(Value::as_array);
+        let warnings = std::iter::once(value)
+            .chain(folders.into_iter().flatten())
+            .filter_map(|settings| settings.get("lintRules")?.as_object())
+            .flatten()
+            .filter(|(_, level)| RuleLevel::deserialize(*level).is_err())
+            .map(|(rule, level)| format!("unknown level {} of the lint rule {}", level, rule))
+            .collect();
+        Ok((settings, warnings))
+    }
+
+    /// Returns the settings of the document `uri` with the overrides of the innermost folder that
+    /// contains it
+    pub fn for_document(&self, uri: &Url) -> DocumentSettings {
+        let path = uri.to_file_path().ok();
+        let folder = self
+            .folders
+            .iter()
+            .filter_map(|folder| {
+                let dir = Url::parse(&folder.uri).ok()?.to_file_path().ok()?;
+                let path: &Path = path.as_ref()?;
+                path.starts_with(&dir)
+                    .then_some((dir.components().count(), folder))
+            })
+            .max_by_key(|(depth, _)| *depth)
+            .map(|(_, folder)| folder);
+        match folder {
+            Some(folder) => self.document.merge(&folder.document),
+            None => self.document.clone(),
+        }
+    }
+}
+
+/// Deserializes the levels of lint rules, skipping those that are unknown
+fn known_levels<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, RuleLevel>, D::Error> {
+    let levels = BTreeMap::<String, Value>::deserialize(deserializer)?;
+    Ok(levels
+        .into_iter()
+        .filter_map(|(rule, level)| Some((rule, RuleLevel::deserialize(level).ok()?)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_from_value() {
+        let (settings, warnings) = Settings::from_value(&json!({
+            "pglsp": {
+                "lintRules": { "fillfactor": "off", "column-padding": "loud" },
+                "folders": [{ "uri": "file:///work/app", "lintRules": { "spelling": 1 } }]
+            }
+        }))
+        .unwrap();
+        assert_eq!(
+            settings.document.lint_rules,
+            BTreeMap::from([("fillfactor".to_string(), RuleLevel::Off)])
+        );
+        assert!(settings.folders[0].document.lint_rules.is_empty());
+        assert_eq!(
+            warnings,
+            vec![
+                "unknown level \"loud\" of the lint rule column-padding",
+                "unknown level 1 of the lint rule spelling",
+            ]
+        );
+        assert!(Settings::from_value(&json!({ "roles": "app" })).is_err());
+    }
+
+    #[test]
+    fn test_for_document() {
+        let folder = |uri: &str, connection: &str| FolderSettings {
+            uri: uri.to_string(),
+            document: DocumentSettings {
+                connection: Some(connection.to_string()),
+                ..Default::default()
+            },
+        };
+        let settings = Settings {
+            document: DocumentSettings {
+                connection: Some("default".to_string()),
+                ..Default::default()
+            },
+            folders: vec![
+                folder("file:///work/app/", "app"),
+                folder("file:///work/app/admin", "admin"),
+                folder("file:///work/my%20app", "encoded"),
+            ],
+            ..Default::default()
+        };
+        let connection = |uri: &str| {
+            settings
+                .for_document(&Url::parse(uri).unwrap())
+                .connection
+                .unwrap()
+        };
+        assert_eq!(connection("file:///work/app/a.sql"), "app");
+        assert_eq!(connection("file:///work/app/admin/a.sql"), "admin");
+        assert_eq!(connection("file:///work/application/a.sql"), "default");
+        assert_eq!(connection("file:///work/my app/a.sql"), "encoded");
+        assert_eq!(connection("untitled:Untitled-1"), "default");
+    }
+
+    #[test]
+    fn test_merge() {
+        let settings = DocumentSettings {
+            connection: Some("default".to_string()),
+            postgres_version: Some("14".to_string()),
+            lint_rules: BTreeMap::from([
+                ("fillfactor".to_string(), RuleLevel::Off),
+                ("spelling".to_string(), RuleLevel::Hint),
+            ]),
+        };
+        let overrides = DocumentSettings {
+            connection: None,
+            postgres_version: Some("16".to_string()),
+            lint_rules: BTreeMap::from([("spelling".to_string(), RuleLevel::Error)]),
+        };
+        assert_eq!(
+            settings.merge(&overrides),
+            DocumentSettings {
+                connection: Some("default".to_string()),
+                postgres_version: Some("16".to_string()),
+                lint_rules: BTreeMap::from([
+                    ("fillfactor".to_string(), RuleLevel::Off),
+                    ("spelling".to_string(), RuleLevel::Error),
+                ]),
+            }
+        );
+    }
+
+    #[test]
+    fn test_pg_version() {
+        let version = |version: Option<&str>| {
+            DocumentSettings {
+                postgres_version: version.map(str::to_string),
+                ..Default::default()
+            }
+            .pg_version()
+        };
+        assert_eq!(version(Some("14")), PgVersion::new(14));
+        assert_eq!(version(Some("9.6")), PgVersion::new(9));
+        assert_eq!(version(Some("latest")), PgVersion::PARSER);
+        assert_eq!(version(None), PgVersion::PARSER);
+    }
+}
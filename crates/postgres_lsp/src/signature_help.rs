@@ -0,0 +1,45 @@
+//! Signatures of the function call around the cursor, as computed by the analyser.
+
+use analyser::signature_help::{call_at, signature_help as analyse};
+use analyser::Function;
+use parser::{Parse, TextSize};
+use tower_lsp::lsp_types::*;
+
+/// Returns the overloads of the function called around `offset` among `functions`
+pub fn signature_help(
+    parse: &Parse,
+    offset: TextSize,
+    functions: &[Function],
+) -> Option<SignatureHelp> {
+    let call = call_at(&parse.cst, offset)?;
+    let help = analyse(&call, functions)?;
+    let active_parameter = help.signatures[help.active_signature]
+        .active_parameter
+        .map(|p| p as u32);
+    Some(SignatureHelp {
+        signatures: help
+            .signatures
+            .into_iter()
+            .map(|s| {
+                // offsets within the label are counted in UTF-16 code units
+                let utf16 = |offset: usize| s.label[..offset].encode_utf16().count() as u32;
+                SignatureInformation {
+                    parameters: Some(
+                        s.parameters
+                            .iter()
+                            .map(|p| ParameterInformation {
+                                label: ParameterLabel::LabelOffsets([utf16(p.start), utf16(p.end)]),
+                                documentation: None,
+                            })
+                            .collect(),
+                    ),
+                    active_parameter: s.active_parameter.map(|p| p as u32),
+                    documentation: None,
+                    label: s.label,
+                }
+            })
+            .collect(),
+        active_signature: Some(help.active_signature as u32),
+        active_parameter,
+    })
+}
@@ -0,0 +1,189 @@
+//! Splitting of migrations that change both the schema and the data.
+//!
+//! The first part stays in the document, and every other part is moved into a new file next to
+//! it. Versioned migrations get the versions that follow, so that the parts run in their original
+//! order. With a selection, only the selected statements are split, and the statements after the
+//! selection move with the last part to keep their order. File names that are taken get a
+//! numeric suffix.
+
+use std::collections::HashMap;
+
+use analyser::data_migration::{split_migration, ChangeKind};
+use analyser::migrations::migration_version;
+use parser::{Parse, RawStmt, TextRange};
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+
+use crate::utils::text_range_to_range;
+
+/// Returns the action that splits the migration `uri` into schema and data migrations, if it
+/// contains both, or only the statements within `range` unless it is empty
+pub fn split_migration_action(
+    uri: &Url,
+    rope: &Rope,
+    parse: &Parse,
+    range: TextRange,
+) -> Option<CodeAction> {
+    let text = rope.to_string();
+    let selected = |stmt: &RawStmt| stmt.range.intersect(range).is_some_and(|r| !r.is_empty());
+    let stmts = if range.is_empty() {
+        &parse.stmts[..]
+    } else {
+        let start = parse.stmts.iter().position(selected)?;
+        let end = parse.stmts.iter().rposition(selected)?;
+        &parse.stmts[start..=end]
+    };
+    let parts = split_migration(&text, stmts);
+    if parts.len() < 2 {
+        return None;
+    }
+    let file_name = uri.path_segments()?.last()?;
+
+    let id = "split-migration".to_string();
+    let mut operations = vec![DocumentChangeOperation::Edit(TextDocumentEdit {
+        text_document: OptionalVersionedTextDocumentIdentifier {
+            uri: uri.clone(),
+            version: None,
+        },
+        edits: vec![OneOf::Right(AnnotatedTextEdit {
+            text_edit: TextEdit::new(
+                text_range_to_range(
+                    TextRange::new(parts[1].range.start(), parts.last()?.range.end()),
+                    rope,
+                )?,
+                String::new(),
+            ),
+            annotation_id: id.clone(),
+        })],
+    })];
+    let dir = uri
+        .to_file_path()
+        .ok()
+        .and_then(|path| Some(path.parent()?.to_path_buf()));
+    let mut taken = Vec::new();
+    for (idx, part) in parts.iter().enumerate().skip(1) {
+        let name = part_file_name(file_name, idx, part.kind, |name| {
+            taken.iter().any(|taken| taken == name)
+                || dir.as_ref().is_some_and(|dir| dir.join(name).exists())
+        });
+        let part_uri = uri.join(&name).ok()?;
+        taken.push(name);
+        operations.push(DocumentChangeOperation::Op(ResourceOp::Create(
+            CreateFile {
+                uri: part_uri.clone(),
+                options: Some(CreateFileOptions {
+                    overwrite: Some(false),
+                    ignore_if_exists: Some(false),
+                }),
+                annotation_id: Some(id.clone()),
+            },
+        )));
+        operations.push(DocumentChangeOperation::Edit(TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier {
+                uri: part_uri,
+                version: None,
+            },
+            edits: vec![OneOf::Right(AnnotatedTextEdit {
+                text_edit: TextEdit::new(
+                    Range::new(Position::new(0, 0), Position::new(0, 0)),
+                    text[part.range].trim_start().to_string(),
+                ),
+                annotation_id: id.clone(),
+            })],
+        }));
+    }
+
+    let annotations = HashMap::from([(
+        id,
+        ChangeAnnotation {
+            label: "Split migration".to_string(),
+            needs_confirmation: Some(true),
+            description: Some(format!(
+                "move {} part(s) of {} into new migrations",
+                parts.len() - 1,
+                file_name
+            )),
+        },
+    )]);
+
+    Some(CodeAction {
+        title: "Split into schema and data migrations".to_string(),
+        kind: Some(CodeActionKind::REFACTOR),
+        edit: Some(WorkspaceEdit {
+            changes: None,
+            document_changes: Some(DocumentChanges::Operations(operations)),
+            change_annotations: Some(annotations),
+        }),
+        ..CodeAction::default()
+    })
+}
+
+/// Returns the name of the file for the `idx`th part of the migration `file_name`, with a numeric
+/// suffix if the name is `taken`
+fn part_file_name(
+    file_name: &str,
+    idx: usize,
+    kind: ChangeKind,
+    taken: impl Fn(&str) -> bool,
+) -> String {
+    let name = versioned_part_file_name(file_name, idx, kind);
+    if !taken(&name) {
+        return name;
+    }
+    let (stem, extension) = name.rsplit_once('.').unwrap_or((&name, "sql"));
+    (2..)
+        .map(|n| format!("{}_{}.{}", stem, n, extension))
+        .find(|name| !taken(name))
+        .unwrap_or_default()
+}
+
+fn versioned_part_file_name(file_name: &str, idx: usize, kind: ChangeKind) -> String {
+    let (stem, extension) = file_name.rsplit_once('.').unwrap_or((file_name, "sql"));
+    let suffix = match kind {
+        ChangeKind::Schema => "schema",
+        ChangeKind::Data => "data",
+    };
+    let Some(version) = migration_version(stem) else {
+        return format!("{}_{}_{}.{}", stem, suffix, idx, extension);
+    };
+
+    // keep the `V` of Flyway and the zero padding of the version
+    let prefix = if stem.starts_with('V') { "V" } else { "" };
+    let rest = &stem[prefix.len()..];
+    let digits = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    format!(
+        "{}{:0width$}{}_{}.{}",
+        prefix,
+        version + idx as u128,
+        &rest[digits..],
+        suffix,
+        extension,
+        width = digits
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_part_file_name() {
+        let free = |_: &str| false;
+        assert_eq!(
+            part_file_name("V0003__contact.sql", 1, ChangeKind::Data, free),
+            "V0004__contact_data.sql"
+        );
+        assert_eq!(
+            part_file_name("seed.sql", 2, ChangeKind::Schema, free),
+            "seed_schema_2.sql"
+        );
+        assert_eq!(
+            part_file_name("V3__contact.sql", 1, ChangeKind::Data, |name| {
+                matches!(name, "V4__contact_data.sql" | "V4__contact_data_2.sql")
+            }),
+            "V4__contact_data_3.sql"
+        );
+    }
+}
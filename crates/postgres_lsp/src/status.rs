@@ -0,0 +1,22 @@
+//! The `pglsp/status` notification, which tells the client about the state of the server to show
+//! it, e.g. in its status bar.
+
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::notification::Notification;
+
+#[derive(Debug)]
+pub enum StatusNotification {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusParams {
+    /// The role that the commands run as, or `None` for the role of the connection
+    pub role: Option<String>,
+    /// Whether the server never connects to a database
+    pub offline: bool,
+}
+
+impl Notification for StatusNotification {
+    type Params = StatusParams;
+    const METHOD: &'static str = "pglsp/status";
+}
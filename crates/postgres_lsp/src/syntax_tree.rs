@@ -0,0 +1,66 @@
+//! The concrete syntax tree of a document for debugging, e.g. to attach it to a bug report.
+//!
+//! The `pglsp.dumpCst` command returns the tree of a whole document as printed by
+//! `pglsp parse --dump-cst`. Editor extensions can show the tree in a panel with the
+//! `pglsp/syntaxTree` request instead, which can be limited to a range. The panel stays up to date
+//! by sending the request again whenever the document changed.
+//!
+//! The `pglsp.statementAst` command returns the statement at a range both as the protobuf AST of
+//! pg_query, serialized as JSON, and as its tree, to compare the two when one of them looks wrong.
+
+use parser::{dump_cst, Parse, TextRange};
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tower_lsp::lsp_types::{Range, TextDocumentIdentifier};
+
+use crate::utils::text_range_to_range;
+
+pub const DUMP_CST_COMMAND: &str = "pglsp.dumpCst";
+
+pub const STATEMENT_AST_COMMAND: &str = "pglsp.statementAst";
+
+pub const SYNTAX_TREE_REQUEST: &str = "pglsp/syntaxTree";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyntaxTreeParams {
+    pub text_document: TextDocumentIdentifier,
+    /// Only returns the smallest node that covers the range, e.g. the selected statement
+    pub range: Option<Range>,
+}
+
+/// The result of the `pglsp.statementAst` command
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatementAst {
+    pub range: Range,
+    /// The `RawStmt` of pg_query, with the location of the statement in the document
+    pub ast: Value,
+    /// The tree of the statement as printed by `dump_cst`
+    pub cst: String,
+}
+
+/// Returns the tree of the smallest node of `parse` that covers `range`, or the whole tree
+pub fn syntax_tree(parse: &Parse, range: Option<TextRange>) -> String {
+    let Some(range) = range.filter(|r| parse.cst.text_range().contains_range(*r)) else {
+        return dump_cst(&parse.cst);
+    };
+    let element = parse.cst.covering_element(range);
+    let node = match element.into_token() {
+        Some(token) => token.parent(),
+        None => element
+            .into_node()
+            .expect("an element is a node or a token"),
+    };
+    dump_cst(node)
+}
+
+/// Returns the statement of `parse` that covers `range`, or `None` if no statement covers it
+pub fn statement_ast(parse: &Parse, rope: &Rope, range: TextRange) -> Option<StatementAst> {
+    let stmt = parse.stmts.iter().find(|s| s.range.contains_range(range))?;
+    Some(StatementAst {
+        range: text_range_to_range(stmt.range, rope)?,
+        ast: serde_json::to_value(stmt.to_protobuf()).ok()?,
+        cst: syntax_tree(parse, Some(stmt.range)),
+    })
+}
@@ -0,0 +1,170 @@
+//! Type hierarchy of domains, composite types and tables across all open documents.
+//!
+//! The supertypes of a domain are its base type, and the supertypes of a composite type or table
+//! are the types of its columns. Built-in types are not defined in any document, so their items
+//! point at the definition of the type that uses them.
+
+use analyser::{defined_type, TypeHierarchy, TypeKind, TypeRelation};
+use tower_lsp::lsp_types::*;
+
+use crate::rename::Document;
+use crate::utils::text_range_to_range;
+
+/// The method that clients use to prepare a type hierarchy
+pub const PREPARE_TYPE_HIERARCHY: &str = "textDocument/prepareTypeHierarchy";
+
+/// Returns the item for the type `name` refers to
+pub fn prepare_type_hierarchy(
+    documents: &[Document<'_>],
+    uri: &Url,
+    range: Range,
+    name: &str,
+) -> Option<Vec<TypeHierarchyItem>> {
+    let hierarchy = type_hierarchy(documents);
+    let name = hierarchy.resolve(name)?;
+    Some(vec![item(documents, &hierarchy, name, None, (uri, range))])
+}
+
+/// Returns the items for the types that the type of `item` is built on
+pub fn supertypes(documents: &[Document<'_>], item: &TypeHierarchyItem) -> Vec<TypeHierarchyItem> {
+    let hierarchy = type_hierarchy(documents);
+    related_items(
+        documents,
+        &hierarchy,
+        hierarchy.supertypes(&item.name),
+        item,
+    )
+}
+
+/// Returns the items for the types that are built on the type of `item`
+pub fn subtypes(documents: &[Document<'_>], item: &TypeHierarchyItem) -> Vec<TypeHierarchyItem> {
+    let hierarchy = type_hierarchy(documents);
+    related_items(documents, &hierarchy, hierarchy.subtypes(&item.name), item)
+}
+
+fn related_items(
+    documents: &[Document<'_>],
+    hierarchy: &TypeHierarchy,
+    related: Vec<(&str, &TypeRelation)>,
+    item: &TypeHierarchyItem,
+) -> Vec<TypeHierarchyItem> {
+    related
+        .into_iter()
+        .map(|(name, relation)| {
+            self::item(
+                documents,
+                hierarchy,
+                name,
+                Some(relation),
+                (&item.uri, item.range),
+            )
+        })
+        .collect()
+}
+
+fn type_hierarchy(documents: &[Document<'_>]) -> TypeHierarchy {
+    let mut hierarchy = TypeHierarchy::new();
+    documents
+        .iter()
+        .flat_map(|doc| doc.parse.stmts.iter())
+        .for_each(|stmt| hierarchy.add_stmt(stmt));
+    hierarchy
+}
+
+/// Returns the location of the statement that defines the type `name`
+fn definition_location(documents: &[Document<'_>], name: &str) -> Option<(Url, Range)> {
+    documents.iter().find_map(|doc| {
+        let stmt =
+            doc.parse.stmts.iter().find(|stmt| {
+                defined_type(&stmt.stmt).is_some_and(|(defined, _)| defined == name)
+            })?;
+        Some((doc.uri.clone(), text_range_to_range(stmt.range, doc.rope)?))
+    })
+}
+
+/// Returns the item for the type `name`. Types without a definition are located at `fallback`.
+fn item(
+    documents: &[Document<'_>],
+    hierarchy: &TypeHierarchy,
+    name: &str,
+    relation: Option<&TypeRelation>,
+    fallback: (&Url, Range),
+) -> TypeHierarchyItem {
+    let kind = hierarchy.definition(name).map(|d| d.kind);
+    let (uri, range) =
+        definition_location(documents, name).unwrap_or_else(|| (fallback.0.clone(), fallback.1));
+    let kind_detail = match kind {
+        Some(TypeKind::Domain) => "domain",
+        Some(TypeKind::Composite) => "composite type",
+        Some(TypeKind::Table) => "table",
+        None => "built-in type",
+    };
+    let detail = match relation {
+        Some(TypeRelation::Column(column)) => format!("{} (column {})", kind_detail, column),
+        _ => kind_detail.to_string(),
+    };
+    TypeHierarchyItem {
+        name: name.to_string(),
+        kind: match kind {
+            Some(TypeKind::Table) => SymbolKind::CLASS,
+            Some(TypeKind::Composite) => SymbolKind::STRUCT,
+            Some(TypeKind::Domain) | None => SymbolKind::TYPE_PARAMETER,
+        },
+        tags: None,
+        detail: Some(detail),
+        uri,
+        range,
+        selection_range: range,
+        data: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse_source;
+    use ropey::Rope;
+
+    use super::*;
+
+    #[test]
+    fn test_type_hierarchy() {
+        let text = "CREATE DOMAIN positive_int AS int CHECK (VALUE > 0);
+CREATE DOMAIN quantity AS positive_int;";
+        let uri = Url::parse("file:///db/types.sql").unwrap();
+        let rope = Rope::from_str(text);
+        let parse = parse_source(text);
+        let documents = [Document {
+            uri: uri.clone(),
+            rope: &rope,
+            parse: &parse,
+        }];
+        let cursor = Range::new(Position::new(1, 14), Position::new(1, 22));
+
+        let items = prepare_type_hierarchy(&documents, &uri, cursor, "quantity").unwrap();
+        let quantity = &items[0];
+        assert_eq!(quantity.name, "quantity");
+        assert_eq!(quantity.detail.as_deref(), Some("domain"));
+        assert_eq!(quantity.range.start, Position::new(1, 0));
+
+        // from the domain to its base types and back
+        let positive_int = supertypes(&documents, quantity).remove(0);
+        assert_eq!(positive_int.name, "positive_int");
+        assert_eq!(positive_int.range.start, Position::new(0, 0));
+        let int4 = supertypes(&documents, &positive_int).remove(0);
+        assert_eq!(int4.name, "int4");
+        assert_eq!(int4.detail.as_deref(), Some("built-in type"));
+        // built-in types are located at the type that uses them
+        assert_eq!(int4.range, positive_int.range);
+        let subtypes_of_int4 = subtypes(&documents, &int4);
+        assert_eq!(
+            subtypes_of_int4
+                .iter()
+                .map(|item| item.name.as_str())
+                .collect::<Vec<_>>(),
+            ["positive_int"]
+        );
+        assert_eq!(subtypes(&documents, &subtypes_of_int4[0])[0], *quantity);
+
+        assert!(prepare_type_hierarchy(&documents, &uri, cursor, "invoice").is_none());
+    }
+}
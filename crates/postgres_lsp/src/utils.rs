@@ -1,9 +1,155 @@
+use analyser::{LintDiagnostic, Severity};
+use parser::{SyntaxError, TextRange, TextSize};
 use ropey::Rope;
-use tower_lsp::lsp_types::Position;
+use tower_lsp::lsp_types::{
+    Diagnostic, DiagnosticSeverity, NumberOrString, Position, Range, TextDocumentContentChangeEvent,
+};
 
+/// Converts the char `offset` into a position, whose character counts UTF-16 code units as
+/// positions of the language server protocol do
 pub fn offset_to_position(offset: usize, rope: &Rope) -> Option<Position> {
     let line = rope.try_char_to_line(offset).ok()?;
     let first_char_of_line = rope.try_line_to_char(line).ok()?;
-    let column = offset - first_char_of_line;
+    let column = rope.try_char_to_utf16_cu(offset).ok()?
+        - rope.try_char_to_utf16_cu(first_char_of_line).ok()?;
     Some(Position::new(line as u32, column as u32))
 }
+
+/// Converts the byte `offset` into a position
+pub fn byte_offset_to_position(offset: TextSize, rope: &Rope) -> Option<Position> {
+    offset_to_position(rope.try_byte_to_char(offset.into()).ok()?, rope)
+}
+
+/// Converts the byte `range` into an lsp range
+pub fn text_range_to_range(range: TextRange, rope: &Rope) -> Option<Range> {
+    Some(Range::new(
+        byte_offset_to_position(range.start(), rope)?,
+        byte_offset_to_position(range.end(), rope)?,
+    ))
+}
+
+/// Converts `position`, whose character counts UTF-16 code units, into a char offset
+pub fn position_to_offset(position: Position, rope: &Rope) -> Option<usize> {
+    let line_start = rope.try_line_to_char(position.line as usize).ok()?;
+    let line_start = rope.try_char_to_utf16_cu(line_start).ok()?;
+    rope.try_utf16_cu_to_char(line_start + position.character as usize)
+        .ok()
+}
+
+/// Converts `position` into a byte offset
+pub fn position_to_byte_offset(position: Position, rope: &Rope) -> Option<TextSize> {
+    let offset = rope
+        .try_char_to_byte(position_to_offset(position, rope)?)
+        .ok()?;
+    TextSize::try_from(offset).ok()
+}
+
+/// Converts the lsp `range` into a byte range
+pub fn range_to_text_range(range: Range, rope: &Rope) -> Option<TextRange> {
+    let start = position_to_byte_offset(range.start, rope)?;
+    let end = position_to_byte_offset(range.end, rope)?;
+    (start <= end).then(|| TextRange::new(start, end))
+}
+
+/// Applies `change` to `rope`. Returns `None` if its range is outside of the text.
+pub fn apply_change(rope: &mut Rope, change: &TextDocumentContentChangeEvent) -> Option<()> {
+    let Some(range) = change.range else {
+        *rope = Rope::from_str(&change.text);
+        return Some(());
+    };
+    let start = position_to_offset(range.start, rope)?;
+    let end = position_to_offset(range.end, rope)?;
+    if start > end {
+        return None;
+    }
+    rope.remove(start..end);
+    rope.insert(start, &change.text);
+    Some(())
+}
+
+/// Returns the text of `rope` after the edits of `action` to the document `uri`
+#[cfg(test)]
+pub(crate) fn apply_action(
+    rope: &Rope,
+    uri: &tower_lsp::lsp_types::Url,
+    action: &tower_lsp::lsp_types::CodeAction,
+) -> String {
+    let mut edits = action
+        .edit
+        .as_ref()
+        .and_then(|edit| edit.changes.as_ref()?.get(uri).cloned())
+        .unwrap_or_default();
+    edits.sort_by_key(|edit| (edit.range.start.line, edit.range.start.character));
+    let mut rope = rope.clone();
+    // from the end, so that the ranges of the edits before are still valid
+    for edit in edits.iter().rev() {
+        let change = TextDocumentContentChangeEvent {
+            range: Some(edit.range),
+            range_length: None,
+            text: edit.new_text.clone(),
+        };
+        apply_change(&mut rope, &change).unwrap();
+    }
+    rope.to_string()
+}
+
+/// Converts a diagnostic of the analyser into an lsp diagnostic
+pub fn lint_diagnostic_to_diagnostic(d: &LintDiagnostic, rope: &Rope) -> Option<Diagnostic> {
+    let severity = match d.severity {
+        Severity::Error => DiagnosticSeverity::ERROR,
+        Severity::Warning => DiagnosticSeverity::WARNING,
+        Severity::Information => DiagnosticSeverity::INFORMATION,
+        Severity::Hint => DiagnosticSeverity::HINT,
+    };
+    Some(Diagnostic {
+        range: text_range_to_range(d.range, rope)?,
+        severity: Some(severity),
+        code: Some(NumberOrString::String(d.rule.to_string())),
+        source: Some("pglsp".to_string()),
+        message: d.message.clone(),
+        ..Diagnostic::default()
+    })
+}
+
+/// Converts a syntax error into an lsp diagnostic at the range that the error points at, e.g.
+/// the token that a statement failed to parse at
+pub fn syntax_error_to_diagnostic(error: &SyntaxError, rope: &Rope) -> Option<Diagnostic> {
+    Some(Diagnostic {
+        range: text_range_to_range(error.range(), rope)?,
+        severity: Some(DiagnosticSeverity::ERROR),
+        source: Some("postgres_lsp".to_string()),
+        message: error.to_string(),
+        ..Diagnostic::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utf16_positions() {
+        // 😀 is two UTF-16 code units, and é is one
+        let rope = Rope::from_str("select '😀é', 1;\nselect 2;");
+        let one = "select '😀é', ".len();
+        let position = byte_offset_to_position(TextSize::from(one as u32), &rope);
+        assert_eq!(position, Some(Position::new(0, 14)));
+        assert_eq!(
+            position_to_byte_offset(Position::new(0, 14), &rope),
+            Some(TextSize::from(one as u32))
+        );
+        assert_eq!(
+            position_to_byte_offset(Position::new(1, 7), &rope),
+            Some(TextSize::from(rope.len_bytes() as u32 - 2))
+        );
+
+        let mut rope = rope;
+        let change = TextDocumentContentChangeEvent {
+            range: Some(Range::new(Position::new(0, 8), Position::new(0, 10))),
+            range_length: None,
+            text: "x".to_string(),
+        };
+        assert_eq!(apply_change(&mut rope, &change), Some(()));
+        assert_eq!(rope.to_string(), "select 'xé', 1;\nselect 2;");
+    }
+}
@@ -0,0 +1,135 @@
+//! Virtual documents with the DDL of tables and views that only exist in the database.
+//!
+//! Go to definition falls back to the live database of a document if no sql file of the workspace
+//! defines the relation, and points to a document such as
+//! `pglsp://db/app/contact?document=file:///migrations/0001.sql`, which names the document whose
+//! database it is part of. Clients load its text with the `pglsp/virtualDocument` request and open
+//! it as an sql document, so that it is highlighted like any other. The DDL is generated from the
+//! schema cache, see [`analyser::object_definition`].
+
+use std::collections::BTreeMap;
+
+use analyser::object_definition::{table_definition, view_definition};
+use analyser::{Schema, View};
+use percent_encoding::percent_decode_str;
+use serde::Deserialize;
+use tower_lsp::lsp_types::{Location, Range, Url};
+
+pub const VIRTUAL_DOCUMENT_REQUEST: &str = "pglsp/virtualDocument";
+
+const SCHEME: &str = "pglsp";
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct VirtualDocumentParams {
+    pub uri: Url,
+}
+
+/// A relation of the database of a document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VirtualDocument {
+    pub document: Url,
+    pub schema: String,
+    pub name: String,
+}
+
+impl VirtualDocument {
+    /// Returns the relation of the virtual document `uri`, or `None` if the uri is not one
+    pub fn from_uri(uri: &Url) -> Option<VirtualDocument> {
+        if uri.scheme() != SCHEME {
+            return None;
+        }
+        let mut segments = uri.path_segments()?;
+        let (Some(schema), Some(name), None) = (segments.next(), segments.next(), segments.next())
+        else {
+            return None;
+        };
+        let document = uri
+            .query_pairs()
+            .find(|(key, _)| key == "document")
+            .and_then(|(_, document)| Url::parse(&document).ok())?;
+        Some(VirtualDocument {
+            document,
+            schema: percent_decode(schema)?,
+            name: percent_decode(name)?,
+        })
+    }
+
+    pub fn uri(&self) -> Option<Url> {
+        let mut uri = Url::parse(&format!("{}://db", SCHEME)).ok()?;
+        uri.path_segments_mut()
+            .ok()?
+            .push(&self.schema)
+            .push(&self.name);
+        uri.query_pairs_mut()
+            .append_pair("document", self.document.as_str());
+        Some(uri)
+    }
+
+    /// Returns the DDL of the relation, or `None` if it is neither a table of `schemas` nor one
+    /// of `views`
+    pub fn text(&self, schemas: &BTreeMap<String, Schema>, views: &[View]) -> Option<String> {
+        schemas
+            .get(&self.schema)
+            .and_then(|schema| table_definition(schema, &self.name))
+            .or_else(|| {
+                views
+                    .iter()
+                    .find(|view| view.schema_name == self.schema && view.name == self.name)
+                    .map(view_definition)
+            })
+    }
+}
+
+/// Returns the start of the virtual document of the relation `identifier` of the database of
+/// `document`, e.g. `app.contact`, if it is a table or view of `schemas` or `views`
+pub fn virtual_definition(
+    document: &Url,
+    identifier: &str,
+    schemas: &BTreeMap<String, Schema>,
+    views: &[View],
+) -> Option<Location> {
+    let (schema, name) = identifier.split_once('.')?;
+    let exists = schemas.get(schema).is_some_and(|s| s.table(name).is_some())
+        || views
+            .iter()
+            .any(|view| view.schema_name == schema && view.name == name);
+    if !exists {
+        return None;
+    }
+    let virtual_document = VirtualDocument {
+        document: document.clone(),
+        schema: schema.to_string(),
+        name: name.to_string(),
+    };
+    Some(Location {
+        uri: virtual_document.uri()?,
+        range: Range::default(),
+    })
+}
+
+/// Decodes the `%XX` escapes of a path segment of a url
+fn percent_decode(segment: &str) -> Option<String> {
+    percent_decode_str(segment)
+        .decode_utf8()
+        .ok()
+        .map(|decoded| decoded.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uri_round_trip() {
+        let virtual_document = VirtualDocument {
+            document: Url::parse("file:///migrations/0001%20init.sql").unwrap(),
+            schema: "app data".to_string(),
+            name: "Contact/100%?#ü".to_string(),
+        };
+        let uri = virtual_document.uri().unwrap();
+        assert_eq!(uri.path_segments().unwrap().count(), 2);
+        assert_eq!(VirtualDocument::from_uri(&uri), Some(virtual_document));
+        assert_eq!(percent_decode("%zz").as_deref(), Some("%zz"));
+        assert_eq!(percent_decode("%ff"), None);
+    }
+}
@@ -0,0 +1,228 @@
+//! An index of the sql files of the workspace: the tables, views, functions and other objects they
+//! define, the occurrences of tables, columns and functions within them, and the tables that
+//! their `UPDATE` statements update.
+//!
+//! All files below the root are indexed from disk once the server is initialized, and open
+//! documents from their latest text whenever they change, so that definitions and references which
+//! are not saved yet are found as well. Closing a document indexes its file from disk again, and
+//! so does a change on disk while it is closed, as the client reports it. Symbolic links are not
+//! followed, so that links to a parent directory do not make the walk loop. Indexing the root
+//! reports its progress to the client, which can cancel it. The files that are not indexed by then
+//! are left out until they are opened or change.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::RwLock;
+
+use analyser::comments::CommentedObject;
+use analyser::definitions::{definitions, Definition, ObjectKind, Reference};
+use analyser::fillfactor::updated_tables;
+use analyser::name_arguments::{NameArgument, NameKind};
+use analyser::references::{occurrences, Access, Occurrence, Target};
+use parser::{parse_source, Parse};
+use ropey::Rope;
+use tower_lsp::lsp_types::{Location, Range, Url};
+
+use crate::utils::text_range_to_range;
+
+/// Directories that never contain sql files of the workspace itself
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target"];
+
+/// Returns the sql files below `dir`, skipping hidden directories and those of build tools
+pub fn sql_files(dir: &Path) -> Vec<Url> {
+    let mut files = Vec::new();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return files;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        // the type of the entry itself, not of the target of a symbolic link
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            if !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_ref()) {
+                files.extend(sql_files(&path));
+            }
+        } else if file_type.is_file() && path.extension().is_some_and(|e| e == "sql") {
+            files.extend(Url::from_file_path(&path));
+        }
+    }
+    files
+}
+
+#[derive(Debug, Default)]
+struct IndexedFile {
+    /// The definitions with the ranges of their statements
+    definitions: Vec<(Definition, Range)>,
+    /// The occurrences of all targets that are not local to a statement
+    occurrences: Vec<(Occurrence, Range)>,
+    /// The qualified name of the table of every `UPDATE` statement
+    updates: Vec<String>,
+}
+
+impl IndexedFile {
+    /// Indexes the document whose text is `rope` and has been parsed into `parse`
+    fn new(rope: &Rope, parse: &Parse) -> IndexedFile {
+        IndexedFile {
+            definitions: definitions(&parse.stmts)
+                .into_iter()
+                .filter_map(|d| {
+                    let range = text_range_to_range(d.range, rope)?;
+                    Some((d, range))
+                })
+                .collect(),
+            occurrences: occurrences(&parse.cst, &parse.stmts)
+                .into_iter()
+                .filter(|o| !o.target.is_local())
+                .filter_map(|o| {
+                    let range = text_range_to_range(o.range, rope)?;
+                    Some((o, range))
+                })
+                .collect(),
+            updates: updated_tables(&parse.stmts),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.definitions.is_empty() && self.occurrences.is_empty() && self.updates.is_empty()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct WorkspaceIndex {
+    files: RwLock<BTreeMap<Url, IndexedFile>>,
+}
+
+impl WorkspaceIndex {
+    /// Indexes the file `uri` as it is on disk, or drops it from the index if it does not exist.
+    /// The file is read and parsed on a blocking thread, so that it does not hold up requests.
+    pub async fn index_file(&self, uri: &Url) {
+        let path = uri.to_file_path().ok();
+        let file = tokio::task::spawn_blocking(move || {
+            let text = fs::read_to_string(path?).ok()?;
+            Some(IndexedFile::new(
+                &Rope::from_str(&text),
+                &parse_source(&text),
+            ))
+        })
+        .await
+        .ok()
+        .flatten();
+        self.insert(uri, file);
+    }
+
+    /// Indexes the document `uri` whose text is `rope` and has been parsed into `parse`
+    pub fn index_document(&self, uri: &Url, rope: &Rope, parse: &Parse) {
+        self.insert(uri, Some(IndexedFile::new(rope, parse)));
+    }
+
+    /// Replaces the index of `uri` with `file`, dropping it if there is nothing to index
+    fn insert(&self, uri: &Url, file: Option<IndexedFile>) {
+        let mut files = self.files.write().unwrap();
+        match file.filter(|file| !file.is_empty()) {
+            Some(file) => {
+                files.insert(uri.clone(), file);
+            }
+            None => {
+                files.remove(uri);
+            }
+        }
+    }
+
+    /// Returns the locations of the statements that define the object of `reference`
+    pub fn definitions(&self, reference: &Reference) -> Vec<Location> {
+        self.files
+            .read()
+            .unwrap()
+            .iter()
+            .flat_map(|(uri, file)| {
+                file.definitions
+                    .iter()
+                    .filter(|(d, _)| reference.matches(d))
+                    .map(|(_, range)| Location {
+                        uri: uri.clone(),
+                        range: *range,
+                    })
+            })
+            .collect()
+    }
+
+    /// Returns true if a file of the workspace defines the object of a comment or security label
+    pub fn defines(&self, object: &CommentedObject) -> bool {
+        let target = object.target();
+        self.files.read().unwrap().values().any(|file| {
+            file.definitions
+                .iter()
+                .any(|(d, _)| object.is_defined_by(d))
+                || target.as_ref().is_some_and(|target| {
+                    file.occurrences
+                        .iter()
+                        .any(|(o, _)| o.access == Access::Declaration && o.target.matches(target))
+                })
+        })
+    }
+
+    /// Returns true if a file of the workspace defines the object that a function takes the name
+    /// of as a string
+    pub fn defines_name(&self, argument: &NameArgument) -> bool {
+        self.files.read().unwrap().values().any(|file| {
+            file.definitions
+                .iter()
+                .any(|(d, _)| argument.is_defined_by(d))
+        })
+    }
+
+    /// Returns the qualified names of the relations and sequences that the workspace defines
+    pub fn named_objects(&self) -> Vec<(NameKind, String)> {
+        self.files
+            .read()
+            .unwrap()
+            .values()
+            .flat_map(|file| &file.definitions)
+            .filter_map(|(d, _)| {
+                let kind = match d.kind {
+                    ObjectKind::Table | ObjectKind::View => NameKind::Relation,
+                    ObjectKind::Sequence => NameKind::Sequence,
+                    _ => return None,
+                };
+                Some((kind, d.identifier.clone()))
+            })
+            .collect()
+    }
+
+    /// Returns the number of `UPDATE` statements of every table that the workspace updates, by the
+    /// qualified name of the table
+    pub fn update_counts(&self) -> BTreeMap<String, usize> {
+        let files = self.files.read().unwrap();
+        let mut counts = BTreeMap::new();
+        for table in files.values().flat_map(|file| &file.updates) {
+            *counts.entry(table.clone()).or_default() += 1;
+        }
+        counts
+    }
+
+    /// Returns the locations of all occurrences of `target`, with how they access it
+    pub fn occurrences(&self, target: &Target) -> Vec<(Location, Access)> {
+        self.files
+            .read()
+            .unwrap()
+            .iter()
+            .flat_map(|(uri, file)| {
+                file.occurrences
+                    .iter()
+                    .filter(|(o, _)| o.target.matches(target))
+                    .map(|(o, range)| {
+                        let location = Location {
+                            uri: uri.clone(),
+                            range: *range,
+                        };
+                        (location, o.access)
+                    })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,328 @@
+//! The `pglsp.toml` file at the root of a workspace.
+//!
+//! It names the database that the SQL files are validated against, and can map subdirectories to
+//! other databases or schemas, e.g.
+//!
+//! ```toml
+//! connection = "postgres://localhost/app"
+//!
+//! [[directories]]
+//! path = "analytics"
+//! connection = "postgres://localhost/warehouse"
+//! schemas = ["analytics", "staging"]
+//! ```
+//!
+//! A directory takes the connection and schemas that it does not set from the top level.
+//!
+//! `read_only` lists the directories whose queries only read, e.g. because they are routed to
+//! replicas. Statements that write are reported in their files.
+//!
+//! ```toml
+//! read_only = ["queries/reports"]
+//! ```
+//!
+//! The `[lint]` section enables lint rule groups in addition to the recommended rules and turns
+//! off single rules, e.g.
+//!
+//! ```toml
+//! [lint]
+//! groups = ["replication"]
+//! disabled_rules = ["truncate-table"]
+//! ```
+//!
+//! The `[policy]` section denies kinds of statements and requires `CONCURRENTLY` for the indexes
+//! of the tables that match one of its patterns, e.g.
+//!
+//! ```toml
+//! [policy]
+//! deny = ["drop-database", "grant-to-public"]
+//! concurrent_index_tables = ["events_*", "billing.invoice"]
+//! ```
+//!
+//! The `[spelling]` section turns on the spell check of comments and of the identifiers of DDL.
+//! The dictionaries are files with one word per line, relative to the root of the workspace, and
+//! `words` adds single words such as the names of the domain, e.g.
+//!
+//! ```toml
+//! [spelling]
+//! enabled = true
+//! dictionaries = ["/usr/share/dict/words"]
+//! words = ["upsert", "tenant"]
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+pub const CONFIG_FILE: &str = "pglsp.toml";
+
+/// The schemas that are loaded if no schemas are configured
+const DEFAULT_SCHEMAS: &[&str] = &["public"];
+
+/// A database and the schemas of it that files are validated against
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(default)]
+pub struct Database {
+    /// The connection string, e.g. `postgres://localhost/app`
+    pub connection: Option<String>,
+    pub schemas: Vec<String>,
+}
+
+/// A subdirectory of the workspace with its own database
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Directory {
+    /// The path relative to the root of the workspace
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub database: Database,
+}
+
+/// The contents of `pglsp.toml`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The database of all files outside of `directories`
+    #[serde(flatten)]
+    pub database: Database,
+    pub directories: Vec<Directory>,
+    /// The directories whose files must only read, relative to the root of the workspace
+    pub read_only: Vec<PathBuf>,
+    pub lint: Lint,
+    pub policy: Policy,
+    pub spelling: Spelling,
+}
+
+/// The lint rules that run on the files of the workspace
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct Lint {
+    /// The names of the rule groups that run in addition to the recommended rules
+    pub groups: Vec<String>,
+    /// The names of rules that do not run, even if their group does
+    pub disabled_rules: Vec<String>,
+}
+
+/// The statements that the files of the workspace must not contain
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct Policy {
+    /// The names of the denied kinds of statements, e.g. `drop-database`
+    pub deny: Vec<String>,
+    /// The patterns of the tables whose indexes must be created concurrently
+    pub concurrent_index_tables: Vec<String>,
+}
+
+/// The spell check of the files of the workspace
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct Spelling {
+    pub enabled: bool,
+    /// The files with the correctly spelled words, relative to the root of the workspace. Without
+    /// any, only common misspellings are reported.
+    pub dictionaries: Vec<PathBuf>,
+    /// Correctly spelled words in addition to the dictionaries
+    pub words: Vec<String>,
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Config, toml::de::Error> {
+        toml::from_str(text)
+    }
+
+    /// Returns the database of the file at `path`, relative to the root of the workspace, or
+    /// `None` if no connection is configured for it. The innermost directory that contains the
+    /// file wins.
+    pub fn database(&self, path: &Path) -> Option<Database> {
+        self.database_or(path, None)
+    }
+
+    /// Like `database`, but uses `connection` if no connection is configured for the file
+    pub fn database_or(&self, path: &Path, connection: Option<&str>) -> Option<Database> {
+        let directory = self
+            .directories
+            .iter()
+            .filter(|dir| path.starts_with(&dir.path))
+            .max_by_key(|dir| dir.path.components().count());
+        let database = directory.map_or(&self.database, |dir| &dir.database);
+
+        let connection = database
+            .connection
+            .clone()
+            .or_else(|| self.database.connection.clone())
+            .or_else(|| connection.map(str::to_string))?;
+        let schemas = [&database.schemas, &self.database.schemas]
+            .into_iter()
+            .find(|schemas| !schemas.is_empty())
+            .cloned()
+            .unwrap_or_else(|| DEFAULT_SCHEMAS.iter().map(|s| s.to_string()).collect());
+        Some(Database {
+            connection: Some(connection),
+            schemas,
+        })
+    }
+
+    /// Returns the distinct databases that files of the workspace are validated against
+    pub fn databases(&self) -> Vec<Database> {
+        let mut databases = Vec::new();
+        let paths =
+            std::iter::once(Path::new("")).chain(self.directories.iter().map(|d| d.path.as_path()));
+        for database in paths.filter_map(|path| self.database(path)) {
+            if !databases.contains(&database) {
+                databases.push(database);
+            }
+        }
+        databases
+    }
+
+    /// Returns true if the file at `path`, relative to the root of the workspace, is in one of the
+    /// `read_only` directories
+    pub fn is_read_only(&self, path: &Path) -> bool {
+        self.read_only.iter().any(|dir| path.starts_with(dir))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_database() {
+        let config = Config::parse(
+            r#"
+connection = "postgres://localhost/app"
+
+[[directories]]
+path = "analytics"
+connection = "postgres://localhost/warehouse"
+schemas = ["analytics"]
+
+[[directories]]
+path = "analytics/staging"
+schemas = ["staging"]
+"#,
+        )
+        .unwrap();
+
+        let database = |path: &str| config.database(Path::new(path)).unwrap();
+        assert_eq!(
+            database("app/contact.sql"),
+            Database {
+                connection: Some("postgres://localhost/app".to_string()),
+                schemas: vec!["public".to_string()],
+            }
+        );
+        assert_eq!(
+            database("analytics/report.sql"),
+            Database {
+                connection: Some("postgres://localhost/warehouse".to_string()),
+                schemas: vec!["analytics".to_string()],
+            }
+        );
+        // the connection is taken from the top level
+        assert_eq!(
+            database("analytics/staging/load.sql").connection.as_deref(),
+            Some("postgres://localhost/app")
+        );
+        assert!(Config::default().database(Path::new("a.sql")).is_none());
+        assert_eq!(
+            Config::default().database_or(Path::new("a.sql"), Some("postgres://localhost/dev")),
+            Some(Database {
+                connection: Some("postgres://localhost/dev".to_string()),
+                schemas: vec!["public".to_string()],
+            })
+        );
+
+        let connections = config
+            .databases()
+            .into_iter()
+            .map(|d| (d.connection.unwrap(), d.schemas))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            connections,
+            [
+                (
+                    "postgres://localhost/app".to_string(),
+                    vec!["public".to_string()]
+                ),
+                (
+                    "postgres://localhost/warehouse".to_string(),
+                    vec!["analytics".to_string()]
+                ),
+                (
+                    "postgres://localhost/app".to_string(),
+                    vec!["staging".to_string()]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint() {
+        let config = Config::parse(
+            r#"
+connection = "postgres://localhost/app"
+
+[lint]
+groups = ["replication"]
+disabled_rules = ["truncate-table"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.lint,
+            Lint {
+                groups: vec!["replication".to_string()],
+                disabled_rules: vec!["truncate-table".to_string()],
+            }
+        );
+        assert_eq!(Config::default().lint, Lint::default());
+    }
+
+    #[test]
+    fn test_read_only() {
+        let config = Config::parse(r#"read_only = ["queries/reports"]"#).unwrap();
+        assert!(config.is_read_only(Path::new("queries/reports/daily.sql")));
+        assert!(!config.is_read_only(Path::new("queries/reports.sql")));
+        assert!(!config.is_read_only(Path::new("migrations/0001_init.sql")));
+    }
+
+    #[test]
+    fn test_policy() {
+        let config = Config::parse(
+            r#"
+[policy]
+deny = ["drop-database"]
+concurrent_index_tables = ["events_*"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.policy,
+            Policy {
+                deny: vec!["drop-database".to_string()],
+                concurrent_index_tables: vec!["events_*".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_spelling() {
+        let config = Config::parse(
+            r#"
+[spelling]
+enabled = true
+dictionaries = ["words.txt"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.spelling,
+            Spelling {
+                enabled: true,
+                dictionaries: vec![PathBuf::from("words.txt")],
+                words: Vec::new(),
+            }
+        );
+        assert!(!Config::default().spelling.enabled);
+    }
+}
@@ -0,0 +1,384 @@
+//! The model of the open documents of a workspace.
+//!
+//! The language server hands every change of a document to the [`Workspace`], which keeps the
+//! latest version of its text and parses it once. Handlers then query the model for the text and
+//! parse result of a document instead of parsing the text of a request themselves.
+//!
+//! Documents are identified by [`FileId`]s, which are interned from their uris. The ids are cheap
+//! to copy and compare, and remain stable when a document is closed and opened again.
+//!
+//! Every change of a document gets a new [`Revision`]. Results computed from a document are kept in
+//! a [`Memo`] by its revision, so that they are computed again only after a change. The document
+//! itself is parsed incrementally: only the statements whose text changed are parsed again. The
+//! trees of all documents share a node cache, which is evicted every few hundred parses and
+//! whenever a document is closed, so that the nodes of outdated versions do not pile up.
+//!
+//! Every version of a document has a [`Cancellation`], which is cancelled as soon as a newer
+//! version arrives. It stops the parse of the outdated text, and handlers pass it to their
+//! analyses of the document so that they do not keep working on text that is gone.
+//!
+//! The [`Config`] of a workspace is read from its `pglsp.toml` and tells which database each file
+//! is validated against.
+
+mod config;
+mod memo;
+
+use std::fmt;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use parser::{
+    parse_source_cancellable_with_version, Cancellation, NodeCache, Parse, ParseCache, PgVersion,
+};
+use ropey::Rope;
+
+pub use crate::config::{Config, Database, Directory, Lint, Policy, Spelling, CONFIG_FILE};
+pub use crate::memo::Memo;
+
+/// The number of parses after which the node cache is evicted
+const EVICT_AFTER_PARSES: usize = 256;
+
+/// The id of a document, interned from its uri
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileId(u32);
+
+/// Identifies a state of a document. Every change of any document gets a new revision, so a
+/// revision is never reused, not even after a document has been closed and opened again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Revision(u64);
+
+impl fmt::Display for Revision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// An open document
+#[derive(Debug)]
+pub struct Document {
+    pub file_id: FileId,
+    pub uri: String,
+    /// The version of the text as given by the client, which increases with every change
+    pub version: i32,
+    pub revision: Revision,
+    pub rope: Rope,
+    pub parse: Parse,
+}
+
+impl Document {
+    /// Returns the text of the document
+    pub fn text(&self) -> String {
+        self.rope.to_string()
+    }
+}
+
+/// The open documents of a workspace
+#[derive(Debug, Default)]
+pub struct Workspace {
+    /// Deduplicates tokens and nodes across the syntax trees of all documents
+    node_cache: NodeCache,
+    /// The number of parses since the node cache was last evicted
+    parses: AtomicUsize,
+    file_ids: DashMap<String, FileId>,
+    /// The uri of every file id, indexed by the id
+    uris: RwLock<Vec<String>>,
+    documents: DashMap<FileId, Document>,
+    /// The latest version and text of every open document, which may not have been parsed yet
+    texts: DashMap<FileId, (i32, Rope)>,
+    /// The statements of the latest parse of every open document. The caches are shared, so that
+    /// the map is not locked while a document is parsed.
+    parse_caches: DashMap<FileId, Arc<ParseCache>>,
+    /// The latest version of every open document with the cancellation of the work on it
+    cancellations: DashMap<FileId, (i32, Cancellation)>,
+    /// The last revision that has been given out
+    revision: AtomicU64,
+}
+
+impl Workspace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the id of `uri`, interning it if it has not been seen before
+    pub fn file_id(&self, uri: &str) -> FileId {
+        if let Some(file_id) = self.file_ids.get(uri) {
+            return *file_id;
+        }
+        *self.file_ids.entry(uri.to_string()).or_insert_with(|| {
+            let mut uris = self.uris.write().unwrap();
+            uris.push(uri.to_string());
+            FileId(uris.len() as u32 - 1)
+        })
+    }
+
+    /// Returns the uri that `file_id` has been interned from
+    pub fn uri(&self, file_id: FileId) -> String {
+        self.uris.read().unwrap()[file_id.0 as usize].clone()
+    }
+
+    /// Sets the text of the document `uri` to `text` and parses it. Changes with a version older
+    /// than the current one are ignored, e.g. if they arrive out of order. The work on older
+    /// versions is cancelled, including their parse if it is still running. Returns the id of the
+    /// document.
+    pub fn update(&self, uri: &str, version: i32, text: &str) -> FileId {
+        self.update_with_pg_version(uri, version, text, PgVersion::default())
+    }
+
+    /// Like `update`, but reports syntax that is not available in `pg_version` as errors
+    pub fn update_with_pg_version(
+        &self,
+        uri: &str,
+        version: i32,
+        text: &str,
+        pg_version: PgVersion,
+    ) -> FileId {
+        let file_id = self.file_id(uri);
+        let cancellation = Cancellation::new();
+        match self.cancellations.entry(file_id) {
+            Entry::Occupied(entry) if entry.get().0 > version => return file_id,
+            Entry::Occupied(mut entry) => {
+                entry.get().1.cancel();
+                entry.insert((version, cancellation.clone()));
+            }
+            Entry::Vacant(entry) => {
+                entry.insert((version, cancellation.clone()));
+            }
+        }
+        match self.texts.entry(file_id) {
+            // the text of a later change that is still to be parsed
+            Entry::Occupied(entry) if entry.get().0 >= version => {}
+            Entry::Occupied(mut entry) => {
+                entry.insert((version, Rope::from_str(text)));
+            }
+            Entry::Vacant(entry) => {
+                entry.insert((version, Rope::from_str(text)));
+            }
+        }
+        let parse_cache = self.parse_caches.entry(file_id).or_default().clone();
+        let parse = parse_source_cancellable_with_version(
+            text,
+            &self.node_cache,
+            &parse_cache,
+            &cancellation,
+            pg_version,
+        );
+        // a newer version arrived while parsing
+        if cancellation.is_cancelled() {
+            return file_id;
+        }
+        let Ok(parse) = parse else {
+            return file_id;
+        };
+        // the nodes of outdated versions stay in the node cache until it is evicted
+        if self.parses.fetch_add(1, Ordering::Relaxed) + 1 >= EVICT_AFTER_PARSES {
+            self.parses.store(0, Ordering::Relaxed);
+            self.node_cache.evict();
+        }
+        let document = || Document {
+            file_id,
+            uri: uri.to_string(),
+            version,
+            revision: Revision(self.revision.fetch_add(1, Ordering::Relaxed) + 1),
+            rope: Rope::from_str(text),
+            parse,
+        };
+        // a newer version may have been parsed and stored since the check above
+        match self.documents.entry(file_id) {
+            Entry::Occupied(entry) if entry.get().version > version => {}
+            Entry::Occupied(mut entry) => {
+                entry.insert(document());
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(document());
+            }
+        }
+        file_id
+    }
+
+    /// Applies `edit` to the latest text of the document `uri`, which is empty if it is not open,
+    /// and returns the text of `version`, or `None` if `edit` fails. The text is locked while
+    /// `edit` runs, so that the edits of concurrent changes are applied one after the other, and to
+    /// the text of the previous change even if it has not been parsed yet.
+    pub fn edit_text(
+        &self,
+        uri: &str,
+        version: i32,
+        edit: impl FnOnce(&mut Rope) -> Option<()>,
+    ) -> Option<String> {
+        let file_id = self.file_id(uri);
+        let mut text = self.texts.entry(file_id).or_default();
+        let mut rope = text.1.clone();
+        edit(&mut rope)?;
+        *text = (version, rope);
+        Some(text.1.to_string())
+    }
+
+    /// Removes the document `uri` and evicts the node cache, so that the nodes of its trees are
+    /// freed. Its id stays interned.
+    pub fn close(&self, uri: &str) {
+        if let Some(file_id) = self.file_ids.get(uri).map(|id| *id) {
+            self.documents.remove(&file_id);
+            self.texts.remove(&file_id);
+            self.parses.store(0, Ordering::Relaxed);
+            self.node_cache.evict();
+            self.parse_caches.remove(&file_id);
+            if let Some((_, (_, cancellation))) = self.cancellations.remove(&file_id) {
+                cancellation.cancel();
+            }
+        }
+    }
+
+    /// Returns the document `uri` if it is open
+    pub fn document(&self, uri: &str) -> Option<impl Deref<Target = Document> + '_> {
+        let file_id = *self.file_ids.get(uri)?;
+        self.documents.get(&file_id)
+    }
+
+    /// Returns the cancellation of the work on the latest version of the document `uri`, which is
+    /// cancelled once a newer version arrives or the document is closed
+    pub fn cancellation(&self, uri: &str) -> Option<Cancellation> {
+        let file_id = *self.file_ids.get(uri)?;
+        self.cancellations
+            .get(&file_id)
+            .map(|entry| entry.1.clone())
+    }
+
+    /// Calls `f` with all open documents, ordered by their ids
+    pub fn with_documents<T>(&self, f: impl FnOnce(&[&Document]) -> T) -> T {
+        let mut entries = self.documents.iter().collect::<Vec<_>>();
+        entries.sort_by_key(|entry| *entry.key());
+        let documents = entries
+            .iter()
+            .map(|entry| entry.value())
+            .collect::<Vec<_>>();
+        f(&documents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_id() {
+        let workspace = Workspace::new();
+        let a = workspace.file_id("file:///a.sql");
+        let b = workspace.file_id("file:///b.sql");
+        assert_ne!(a, b);
+        assert_eq!(workspace.file_id("file:///a.sql"), a);
+        assert_eq!(workspace.uri(b), "file:///b.sql");
+    }
+
+    #[test]
+    fn test_update() {
+        let workspace = Workspace::new();
+        let uri = "file:///a.sql";
+        let file_id = workspace.update(uri, 1, "select 1;");
+        workspace.update(uri, 3, "select 1;\nselect 2;");
+        // a change that arrives after a newer one is stale
+        workspace.update(uri, 2, "select 3;");
+
+        let doc = workspace.document(uri).unwrap();
+        assert_eq!(doc.file_id, file_id);
+        assert_eq!(doc.version, 3);
+        assert_eq!(doc.text(), "select 1;\nselect 2;");
+        assert_eq!(doc.parse.stmts.len(), 2);
+        let revision = doc.revision;
+        drop(doc);
+
+        workspace.update(uri, 4, "select 1;\nselect 2;");
+        assert!(workspace.document(uri).unwrap().revision > revision);
+
+        workspace.close(uri);
+        assert!(workspace.document(uri).is_none());
+        assert_eq!(workspace.update(uri, 1, "select 1;"), file_id);
+    }
+
+    #[test]
+    fn test_concurrent_update() {
+        let workspace = Workspace::new();
+        let uri = "file:///a.sql";
+        std::thread::scope(|scope| {
+            for version in 1..=16 {
+                let workspace = &workspace;
+                scope.spawn(move || {
+                    workspace.update(uri, version, &format!("select {version};"));
+                });
+            }
+        });
+        // an older version that finishes parsing last does not replace the newest one
+        let doc = workspace.document(uri).unwrap();
+        assert_eq!(doc.version, 16);
+        assert_eq!(doc.text(), "select 16;");
+    }
+
+    #[test]
+    fn test_edit_text() {
+        let workspace = Workspace::new();
+        workspace.update("file:///a.sql", 1, "select 1;");
+        let edit = |text: &'static str| {
+            move |rope: &mut Rope| {
+                rope.insert(rope.len_chars(), text);
+                Some(())
+            }
+        };
+        // the second edit applies to the text of the first one before it is parsed
+        workspace.edit_text("file:///a.sql", 2, edit(" select 2;"));
+        let text = workspace.edit_text("file:///a.sql", 3, edit(" select 3;"));
+        assert_eq!(text.as_deref(), Some("select 1; select 2; select 3;"));
+        workspace.update("file:///a.sql", 2, "select 1; select 2;");
+        let text = workspace.edit_text("file:///a.sql", 4, edit(" select 4;"));
+        assert_eq!(
+            text.as_deref(),
+            Some("select 1; select 2; select 3; select 4;")
+        );
+    }
+
+    #[test]
+    fn test_cancellation() {
+        let workspace = Workspace::new();
+        let uri = "file:///a.sql";
+        workspace.update(uri, 1, "select 1;");
+        let first = workspace.cancellation(uri).unwrap();
+        assert!(!first.is_cancelled());
+
+        workspace.update(uri, 2, "select 2;");
+        assert!(first.is_cancelled());
+        let second = workspace.cancellation(uri).unwrap();
+        assert!(!second.is_cancelled());
+
+        workspace.close(uri);
+        assert!(second.is_cancelled());
+        assert!(workspace.cancellation(uri).is_none());
+    }
+
+    #[test]
+    fn test_pg_version() {
+        let workspace = Workspace::new();
+        let uri = "file:///a.sql";
+        let text = "merge into t using s on t.id = s.id when matched then delete;";
+        let errors = |workspace: &Workspace| workspace.document(uri).unwrap().parse.errors.len();
+        workspace.update_with_pg_version(uri, 1, text, PgVersion::new(14));
+        assert_eq!(errors(&workspace), 1);
+        // the statements that are cached for another version are parsed again
+        workspace.update(uri, 1, text);
+        assert_eq!(errors(&workspace), 0);
+    }
+
+    #[test]
+    fn test_with_documents() {
+        let workspace = Workspace::new();
+        workspace.update("file:///b.sql", 1, "select 1;");
+        workspace.update("file:///a.sql", 1, "select 1;");
+        let uris = workspace.with_documents(|documents| {
+            documents
+                .iter()
+                .map(|doc| doc.uri.clone())
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(uris, vec!["file:///b.sql", "file:///a.sql"]);
+    }
+}
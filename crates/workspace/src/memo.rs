@@ -0,0 +1,127 @@
+//! Memoized results of queries on documents.
+//!
+//! Handlers such as semantic tokens or diagnostics are called many times for the same text, e.g.
+//! when the client scrolls or asks again after another document changed. Their results are kept
+//! per document until the inputs they have been computed from change.
+
+use std::sync::Arc;
+
+use dashmap::DashMap;
+
+use crate::FileId;
+
+/// The result of a query per document, together with the inputs it has been computed from
+///
+/// The inputs are usually the [`Revision`](crate::Revision) of the document, and whatever else the
+/// query reads, e.g. the schemas it resolves names against. A result is reused as long as the
+/// inputs are equal, and computed again once any of them changed.
+#[derive(Debug)]
+pub struct Memo<K, V> {
+    entries: DashMap<FileId, (K, Arc<V>)>,
+}
+
+impl<K, V> Default for Memo<K, V> {
+    fn default() -> Self {
+        Self {
+            entries: DashMap::new(),
+        }
+    }
+}
+
+impl<K: PartialEq, V> Memo<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the result for `file_id` if it has been computed from `inputs`
+    pub fn get(&self, file_id: FileId, inputs: &K) -> Option<Arc<V>> {
+        let entry = self.entries.get(&file_id)?;
+        (entry.0 == *inputs).then(|| entry.1.clone())
+    }
+
+    /// Stores the result for `file_id` that has been computed from `inputs`, replacing the
+    /// previous one
+    pub fn insert(&self, file_id: FileId, inputs: K, value: V) -> Arc<V> {
+        let value = Arc::new(value);
+        self.entries.insert(file_id, (inputs, value.clone()));
+        value
+    }
+
+    /// Returns the result for `file_id`, computing it with `f` unless it has been computed from
+    /// `inputs` before
+    ///
+    /// No lock is held while `f` runs, so that it may query other memos. If two threads compute
+    /// the same result, the last one is kept.
+    pub fn get_or_compute(&self, file_id: FileId, inputs: K, f: impl FnOnce() -> V) -> Arc<V> {
+        if let Some(value) = self.get(file_id, &inputs) {
+            return value;
+        }
+        self.insert(file_id, inputs, f())
+    }
+
+    /// Returns the result for `file_id` together with the inputs it has been computed from, even if
+    /// they are outdated, e.g. to compute the changes since the result was last returned
+    pub fn latest(&self, file_id: FileId) -> Option<(K, Arc<V>)>
+    where
+        K: Clone,
+    {
+        let entry = self.entries.get(&file_id)?;
+        Some((entry.0.clone(), entry.1.clone()))
+    }
+
+    /// Drops the result for `file_id`, e.g. because the document has been closed
+    pub fn remove(&self, file_id: FileId) {
+        self.entries.remove(&file_id);
+    }
+
+    /// Drops all results, e.g. because an input that is not part of their key changed
+    pub fn clear(&self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use crate::Workspace;
+
+    use super::*;
+
+    #[test]
+    fn test_get_or_compute() {
+        let workspace = Workspace::new();
+        let uri = "file:///a.sql";
+        let memo = Memo::new();
+        let computed = Cell::new(0);
+        let stmts = |workspace: &Workspace| {
+            let doc = workspace.document(uri).unwrap();
+            memo.get_or_compute(doc.file_id, doc.revision, || {
+                computed.set(computed.get() + 1);
+                doc.parse.stmts.len()
+            })
+        };
+
+        workspace.update(uri, 1, "select 1;");
+        assert_eq!(*stmts(&workspace), 1);
+        assert_eq!(*stmts(&workspace), 1);
+        assert_eq!(computed.get(), 1);
+
+        workspace.update(uri, 2, "select 1;\nselect 2;");
+        assert_eq!(*stmts(&workspace), 2);
+        assert_eq!(computed.get(), 2);
+
+        let file_id = workspace.file_id(uri);
+        let revision = workspace.document(uri).unwrap().revision;
+        workspace.update(uri, 3, "select 3;");
+        assert_eq!(
+            memo.latest(file_id).map(|(r, v)| (r, *v)),
+            Some((revision, 2))
+        );
+
+        memo.remove(file_id);
+        assert_eq!(memo.latest(file_id), None);
+        assert_eq!(*stmts(&workspace), 1);
+        assert_eq!(computed.get(), 3);
+    }
+}